@@ -0,0 +1,236 @@
+//! Static API key authentication
+//!
+//! [`ApiKeyLayer`] accepts requests presenting one of the keys from
+//! `AUTH_API_KEYS` in the `AUTH_API_KEY_HEADER` header. Several keys can be
+//! valid at once to allow rotation.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    http::{HeaderName, Request},
+    response::{IntoResponse, Response},
+};
+use barrzen_axum_core::{ApiError, AuthConfig, response::extract_request_id};
+use subtle::ConstantTimeEq;
+use tower::{Layer, Service};
+
+/// Identity of the API key that authenticated the request
+///
+/// Inserted as a request extension on success; read it with
+/// `Extension<ApiKeyIdentity>`. `name` is set for `name:key` entries.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKeyIdentity {
+    pub name: Option<String>,
+}
+
+/// Layer that rejects requests without a valid API key
+#[derive(Clone)]
+pub struct ApiKeyLayer {
+    keys: Arc<ApiKeys>,
+}
+
+struct ApiKeys {
+    header: HeaderName,
+    entries: Vec<(Option<String>, Vec<u8>)>,
+}
+
+impl ApiKeyLayer {
+    /// Build the layer from `AUTH_API_KEYS` and `AUTH_API_KEY_HEADER`
+    ///
+    /// # Errors
+    /// Returns error if no keys are configured or the header name is invalid.
+    pub fn from_config(config: &AuthConfig) -> anyhow::Result<Self> {
+        let entries: Vec<_> = config
+            .api_keys()
+            .into_iter()
+            .map(|(name, key)| (name, key.into_bytes()))
+            .collect();
+        if entries.is_empty() {
+            anyhow::bail!("API key auth requires AUTH_API_KEYS");
+        }
+
+        let header =
+            HeaderName::from_bytes(config.auth_api_key_header.trim().as_bytes()).map_err(|_| {
+                anyhow::anyhow!(
+                    "AUTH_API_KEY_HEADER is not a valid header name: {:?}",
+                    config.auth_api_key_header
+                )
+            })?;
+
+        Ok(Self {
+            keys: Arc::new(ApiKeys { header, entries }),
+        })
+    }
+}
+
+impl ApiKeys {
+    /// Find the matching key, comparing against every entry in constant time
+    fn authenticate(&self, presented: &[u8]) -> Option<ApiKeyIdentity> {
+        let mut matched = None;
+        for (name, key) in &self.entries {
+            if bool::from(key.as_slice().ct_eq(presented)) && matched.is_none() {
+                matched = Some(ApiKeyIdentity { name: name.clone() });
+            }
+        }
+        matched
+    }
+}
+
+impl<S> Layer<S> for ApiKeyLayer {
+    type Service = ApiKeyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ApiKeyService {
+            inner,
+            keys: self.keys.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ApiKeyService<S> {
+    inner: S,
+    keys: Arc<ApiKeys>,
+}
+
+impl<S, B> Service<Request<B>> for ApiKeyService<S>
+where
+    S: Service<Request<B>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let presented = req.headers().get(&self.keys.header);
+        let identity = presented.and_then(|value| self.keys.authenticate(value.as_bytes()));
+
+        let Some(identity) = identity else {
+            let reason = if presented.is_some() {
+                "invalid API key"
+            } else {
+                "missing API key"
+            };
+            let request_id = extract_request_id(req.headers());
+            // Never log the presented key
+            tracing::warn!(
+                request_id = request_id.as_deref().unwrap_or(""),
+                header = %self.keys.header,
+                reason,
+                "API key authentication failed"
+            );
+
+            let mut error = ApiError::unauthorized("Invalid or missing API key");
+            if let Some(request_id) = request_id {
+                error = error.with_request_id(request_id);
+            }
+            return Box::pin(async move { Ok(error.into_response()) });
+        };
+
+        req.extensions_mut().insert(identity);
+        Box::pin(async move { inner.call(req).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Extension, Router, body::Body, http::StatusCode, routing::get};
+    use tower::ServiceExt;
+
+    fn layer(keys: &str) -> ApiKeyLayer {
+        let config: AuthConfig =
+            serde_json::from_value(serde_json::json!({ "auth_api_keys": keys })).unwrap();
+        ApiKeyLayer::from_config(&config).unwrap()
+    }
+
+    fn app(keys: &str) -> Router {
+        Router::new()
+            .route(
+                "/internal",
+                get(
+                    |Extension(identity): Extension<ApiKeyIdentity>| async move {
+                        identity.name.unwrap_or_else(|| "anonymous".to_string())
+                    },
+                ),
+            )
+            .layer(layer(keys))
+    }
+
+    async fn call(app: Router, key: Option<&str>) -> (StatusCode, String) {
+        let mut request = Request::builder()
+            .uri("/internal")
+            .header("x-request-id", "req-1");
+        if let Some(key) = key {
+            request = request.header("x-api-key", key);
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[test]
+    fn test_from_config_requires_keys() {
+        let config: AuthConfig = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert!(ApiKeyLayer::from_config(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_valid_key() {
+        let (status, body) = call(app("billing:secret-1"), Some("secret-1")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "billing");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_key() {
+        let (status, body) = call(app("billing:secret-1"), Some("secret-2")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["status"], "error");
+        assert_eq!(json["code"], 401);
+        assert_eq!(json["request_id"], "req-1");
+    }
+
+    #[tokio::test]
+    async fn test_missing_header() {
+        let (status, _) = call(app("secret-1"), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_rotation_with_two_keys() {
+        let keys = "old:secret-1,new:secret-2";
+        assert_eq!(
+            call(app(keys), Some("secret-1")).await,
+            (StatusCode::OK, "old".to_string())
+        );
+        assert_eq!(
+            call(app(keys), Some("secret-2")).await,
+            (StatusCode::OK, "new".to_string())
+        );
+        assert_eq!(
+            call(app("secret-2"), Some("secret-2")).await,
+            (StatusCode::OK, "anonymous".to_string())
+        );
+    }
+}
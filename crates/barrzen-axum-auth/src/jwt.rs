@@ -0,0 +1,643 @@
+//! JWT bearer authentication
+//!
+//! [`JwtAuthLayer`] verifies `Authorization: Bearer <token>` against an HS256
+//! secret or a JWKS endpoint and makes the claims available through the
+//! [`AuthClaims`] extractor. Apply it with `Router::layer` on the routes
+//! that need it, then hand the router to `AppBuilder::merge`;
+//! `AppBuilder::layer` would also cover `/healthz`, `/readyz` and `/metrics`.
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::FromRequestParts,
+    http::{Request, header, request::Parts},
+    response::{IntoResponse, Response},
+};
+use barrzen_axum_core::{ApiError, AuthConfig, response::extract_request_id};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation, decode, decode_header, errors::ErrorKind, jwk::JwkSet,
+};
+use serde::de::DeserializeOwned;
+use tokio::sync::{Mutex, RwLock};
+use tower::{Layer, Service};
+
+/// Minimum time between JWKS fetches triggered by an unknown `kid` or a
+/// failed fetch
+const MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Connect timeout of the JWKS client
+const JWKS_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Overall timeout of a JWKS fetch
+const JWKS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Algorithms accepted with a shared secret
+const HMAC_ALGORITHMS: [Algorithm; 3] = [Algorithm::HS256, Algorithm::HS384, Algorithm::HS512];
+
+/// Layer that rejects requests without a valid bearer token
+#[derive(Clone)]
+pub struct JwtAuthLayer {
+    verifier: Arc<JwtVerifier>,
+}
+
+impl JwtAuthLayer {
+    /// Build the layer from `AUTH_*` settings
+    ///
+    /// `AUTH_JWT_SECRET` takes precedence over `AUTH_JWKS_URL`.
+    ///
+    /// # Errors
+    /// Returns error if neither a secret nor a JWKS URL is configured, or
+    /// the JWKS HTTP client cannot be built.
+    pub fn from_config(config: &AuthConfig) -> anyhow::Result<Self> {
+        let keys = if let Some(secret) = &config.auth_jwt_secret {
+            KeySource::Secret(DecodingKey::from_secret(secret.as_bytes()))
+        } else if let Some(url) = &config.auth_jwks_url {
+            KeySource::Jwks(JwksCache::new(
+                url.clone(),
+                Duration::from_secs(config.auth_jwks_refresh_seconds),
+            )?)
+        } else {
+            anyhow::bail!("JWT auth requires AUTH_JWT_SECRET or AUTH_JWKS_URL");
+        };
+
+        Ok(Self {
+            verifier: Arc::new(JwtVerifier {
+                keys,
+                issuer: config.auth_issuer.clone(),
+                audiences: config.audiences(),
+            }),
+        })
+    }
+}
+
+impl<S> Layer<S> for JwtAuthLayer {
+    type Service = JwtAuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        JwtAuthService {
+            inner,
+            verifier: self.verifier.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct JwtAuthService<S> {
+    inner: S,
+    verifier: Arc<JwtVerifier>,
+}
+
+impl<S, B> Service<Request<B>> for JwtAuthService<S>
+where
+    S: Service<Request<B>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let verifier = self.verifier.clone();
+
+        Box::pin(async move {
+            let token = bearer_token(req.headers()).map(str::to_string);
+            let result = match token {
+                Some(token) => verifier.verify(&token).await,
+                None => Err(AuthError::MissingToken),
+            };
+
+            match result {
+                Ok(claims) => {
+                    req.extensions_mut()
+                        .insert(VerifiedClaims(Arc::new(claims)));
+                    inner.call(req).await
+                }
+                Err(error) => {
+                    let request_id = extract_request_id(req.headers());
+                    tracing::debug!(
+                        request_id = request_id.as_deref().unwrap_or(""),
+                        reason = %error,
+                        "JWT authentication failed"
+                    );
+                    let mut response = error.into_api_error();
+                    if let Some(request_id) = request_id {
+                        response = response.with_request_id(request_id);
+                    }
+                    Ok(response.into_response())
+                }
+            }
+        })
+    }
+}
+
+/// Validated token claims
+///
+/// Deserializes the claims verified by [`JwtAuthLayer`] into `T`. Rejects
+/// with 401 when the route is not behind the layer or the claims do not
+/// match `T`.
+#[derive(Debug, Clone)]
+pub struct AuthClaims<T = serde_json::Value>(pub T);
+
+impl<T, S> FromRequestParts<S> for AuthClaims<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let reject = |error: ApiError| match extract_request_id(&parts.headers) {
+            Some(request_id) => error.with_request_id(request_id),
+            None => error,
+        };
+
+        let Some(VerifiedClaims(claims)) = parts.extensions.get::<VerifiedClaims>() else {
+            return Err(reject(ApiError::unauthorized("Missing authentication")));
+        };
+
+        T::deserialize(claims.as_ref()).map(Self).map_err(|e| {
+            reject(ApiError::unauthorized("Invalid token claims").with_details(e.to_string()))
+        })
+    }
+}
+
+/// Claims stored as a request extension by the layer
+#[derive(Clone)]
+struct VerifiedClaims(Arc<serde_json::Value>);
+
+struct JwtVerifier {
+    keys: KeySource,
+    issuer: Option<String>,
+    audiences: Vec<String>,
+}
+
+impl JwtVerifier {
+    async fn verify(&self, token: &str) -> Result<serde_json::Value, AuthError> {
+        let header = decode_header(token).map_err(|_| AuthError::InvalidToken)?;
+
+        let (key, algorithms) = match &self.keys {
+            KeySource::Secret(key) => (key.clone(), HMAC_ALGORITHMS.to_vec()),
+            KeySource::Jwks(cache) => {
+                let (key, algorithm) = cache.key(header.kid.as_deref()).await?;
+                (key, vec![algorithm.unwrap_or(header.alg)])
+            }
+        };
+        if !algorithms.contains(&header.alg) {
+            return Err(AuthError::InvalidToken);
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = algorithms;
+        validation.validate_aud = !self.audiences.is_empty();
+        if validation.validate_aud {
+            validation.set_audience(&self.audiences);
+        }
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        decode::<serde_json::Value>(token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| match e.kind() {
+                ErrorKind::ExpiredSignature => AuthError::Expired,
+                _ => AuthError::InvalidToken,
+            })
+    }
+}
+
+enum KeySource {
+    Secret(DecodingKey),
+    Jwks(JwksCache),
+}
+
+/// JWKS keys cached by `kid` and refreshed on an interval
+///
+/// Fetches run outside the state lock, so requests keep verifying with the
+/// cached keys meanwhile, and one at a time: concurrent misses wait for the
+/// running fetch instead of starting their own.
+struct JwksCache {
+    url: String,
+    refresh: Duration,
+    client: reqwest::Client,
+    state: RwLock<JwksState>,
+    fetching: Mutex<()>,
+}
+
+#[derive(Default)]
+struct JwksState {
+    keys: HashMap<String, (DecodingKey, Option<Algorithm>)>,
+    fetched_at: Option<Instant>,
+    attempted_at: Option<Instant>,
+}
+
+impl JwksState {
+    fn find(&self, kid: Option<&str>) -> Option<(DecodingKey, Option<Algorithm>)> {
+        match kid {
+            Some(kid) => self.keys.get(kid).cloned(),
+            // Tokens without a kid are only accepted with a single-key set
+            None if self.keys.len() == 1 => self.keys.values().next().cloned(),
+            None => None,
+        }
+    }
+
+    fn age(&self) -> Option<Duration> {
+        self.fetched_at.map(|at| at.elapsed())
+    }
+
+    fn lookup(&self, kid: Option<&str>) -> Result<(DecodingKey, Option<Algorithm>), AuthError> {
+        if self.fetched_at.is_none() {
+            return Err(AuthError::KeysUnavailable);
+        }
+        self.find(kid).ok_or(AuthError::UnknownKey)
+    }
+}
+
+impl JwksCache {
+    fn new(url: String, refresh: Duration) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(JWKS_CONNECT_TIMEOUT)
+            .timeout(JWKS_TIMEOUT)
+            .build()?;
+
+        Ok(Self {
+            url,
+            refresh,
+            client,
+            state: RwLock::new(JwksState::default()),
+            fetching: Mutex::new(()),
+        })
+    }
+
+    async fn key(&self, kid: Option<&str>) -> Result<(DecodingKey, Option<Algorithm>), AuthError> {
+        if let Some(result) = self.cached(kid).await {
+            return result;
+        }
+
+        let _fetching = self.fetching.lock().await;
+        // The fetch we waited for may have brought the key
+        if let Some(result) = self.cached(kid).await {
+            return result;
+        }
+
+        let fetched = self.fetch().await;
+        let mut state = self.state.write().await;
+        state.attempted_at = Some(Instant::now());
+        match fetched {
+            Ok(keys) => {
+                state.keys = keys;
+                state.fetched_at = state.attempted_at;
+            }
+            Err(e) => tracing::warn!(url = %self.url, error = %e, "Failed to fetch JWKS"),
+        }
+
+        state.lookup(kid)
+    }
+
+    /// Answer from the cache, `None` when a fetch is due
+    async fn cached(
+        &self,
+        kid: Option<&str>,
+    ) -> Option<Result<(DecodingKey, Option<Algorithm>), AuthError>> {
+        let state = self.state.read().await;
+        let fresh = state.age().is_some_and(|age| age < self.refresh);
+        if fresh && let Some(key) = state.find(kid) {
+            return Some(Ok(key));
+        }
+
+        // Stale keys keep serving while fetches fail
+        let attempted_recently = state
+            .attempted_at
+            .is_some_and(|at| at.elapsed() < MIN_REFETCH_INTERVAL);
+        attempted_recently.then(|| state.lookup(kid))
+    }
+
+    async fn fetch(&self) -> anyhow::Result<HashMap<String, (DecodingKey, Option<Algorithm>)>> {
+        let jwks: JwkSet = self
+            .client
+            .get(&self.url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let keys = jwks
+            .keys
+            .iter()
+            .filter_map(|jwk| {
+                let key = DecodingKey::from_jwk(jwk).ok()?;
+                let algorithm = jwk
+                    .common
+                    .key_algorithm
+                    .and_then(|alg| alg.to_string().parse().ok());
+                let kid = jwk.common.key_id.clone().unwrap_or_default();
+                Some((kid, (key, algorithm)))
+            })
+            .collect();
+
+        Ok(keys)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum AuthError {
+    #[error("missing bearer token")]
+    MissingToken,
+    #[error("invalid token")]
+    InvalidToken,
+    #[error("token expired")]
+    Expired,
+    #[error("unknown signing key")]
+    UnknownKey,
+    #[error("signing keys unavailable")]
+    KeysUnavailable,
+}
+
+impl AuthError {
+    fn into_api_error(self) -> ApiError {
+        match self {
+            Self::MissingToken => ApiError::unauthorized("Missing bearer token"),
+            Self::InvalidToken | Self::UnknownKey => ApiError::unauthorized("Invalid token"),
+            Self::Expired => ApiError::unauthorized("Invalid token").with_details("token expired"),
+            Self::KeysUnavailable => {
+                ApiError::service_unavailable("Authentication keys unavailable")
+            }
+        }
+    }
+}
+
+fn bearer_token(headers: &axum::http::HeaderMap) -> Option<&str> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, http::StatusCode, routing::get};
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use serde::{Deserialize, Serialize};
+    use tower::ServiceExt;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path},
+    };
+
+    const SECRET: &str = "test-secret";
+    const RSA_PRIVATE_KEY: &str = include_str!("../testdata/rsa_private.pem");
+    const JWKS: &str = include_str!("../testdata/jwks.json");
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        aud: String,
+        iss: String,
+        exp: u64,
+    }
+
+    fn now() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn claims(aud: &str, exp: u64) -> TestClaims {
+        TestClaims {
+            sub: "user-1".to_string(),
+            aud: aud.to_string(),
+            iss: "https://issuer.test".to_string(),
+            exp,
+        }
+    }
+
+    fn auth_config(secret: Option<&str>, jwks_url: Option<&str>) -> AuthConfig {
+        serde_json::from_value(serde_json::json!({
+            "auth_jwt_secret": secret,
+            "auth_jwks_url": jwks_url,
+            "auth_issuer": "https://issuer.test",
+            "auth_audience": "api"
+        }))
+        .unwrap()
+    }
+
+    fn app(layer: JwtAuthLayer) -> Router {
+        Router::new()
+            .route(
+                "/me",
+                get(|AuthClaims(claims): AuthClaims<TestClaims>| async move { claims.sub }),
+            )
+            .layer(layer)
+    }
+
+    async fn call(app: Router, token: Option<&str>) -> (StatusCode, String) {
+        let mut request = Request::builder()
+            .uri("/me")
+            .header("x-request-id", "req-1");
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    fn hs256_token(claims: &TestClaims) -> String {
+        encode(
+            &Header::new(Algorithm::HS256),
+            claims,
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn rs256_token(claims: &TestClaims, kid: &str) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        encode(
+            &header,
+            claims,
+            &EncodingKey::from_rsa_pem(RSA_PRIVATE_KEY.as_bytes()).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn hs256_layer() -> JwtAuthLayer {
+        JwtAuthLayer::from_config(&auth_config(Some(SECRET), None)).unwrap()
+    }
+
+    async fn jwks_server() -> MockServer {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/jwks.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(JWKS, "application/json"))
+            .mount(&server)
+            .await;
+        server
+    }
+
+    #[test]
+    fn test_from_config_requires_key_source() {
+        assert!(JwtAuthLayer::from_config(&auth_config(None, None)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_hs256_success() {
+        let token = hs256_token(&claims("api", now() + 600));
+        let (status, body) = call(app(hs256_layer()), Some(&token)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "user-1");
+    }
+
+    #[tokio::test]
+    async fn test_hs256_expired() {
+        let token = hs256_token(&claims("api", now() - 600));
+        let (status, body) = call(app(hs256_layer()), Some(&token)).await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["status"], "error");
+        assert_eq!(json["details"], "token expired");
+        assert_eq!(json["request_id"], "req-1");
+    }
+
+    #[tokio::test]
+    async fn test_hs256_wrong_audience() {
+        let token = hs256_token(&claims("other", now() + 600));
+        let (status, body) = call(app(hs256_layer()), Some(&token)).await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["message"], "Invalid token");
+    }
+
+    #[tokio::test]
+    async fn test_missing_header() {
+        let (status, body) = call(app(hs256_layer()), None).await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(json["message"], "Missing bearer token");
+    }
+
+    #[tokio::test]
+    async fn test_rs256_with_jwks() {
+        let server = jwks_server().await;
+        let layer = JwtAuthLayer::from_config(&auth_config(
+            None,
+            Some(&format!("{}/jwks.json", server.uri())),
+        ))
+        .unwrap();
+
+        let token = rs256_token(&claims("api", now() + 600), "test-key");
+        let (status, body) = call(app(layer.clone()), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "user-1");
+
+        let token = rs256_token(&claims("api", now() - 600), "test-key");
+        let (status, body) = call(app(layer.clone()), Some(&token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("token expired"));
+
+        let token = rs256_token(&claims("other", now() + 600), "test-key");
+        let (status, _) = call(app(layer.clone()), Some(&token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let token = rs256_token(&claims("api", now() + 600), "unknown-key");
+        let (status, _) = call(app(layer.clone()), Some(&token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // Keys are fetched once and then served from the cache
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_share_one_fetch() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/jwks.json"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(JWKS, "application/json")
+                    .set_delay(Duration::from_millis(100)),
+            )
+            .mount(&server)
+            .await;
+        let layer = JwtAuthLayer::from_config(&auth_config(
+            None,
+            Some(&format!("{}/jwks.json", server.uri())),
+        ))
+        .unwrap();
+
+        let token = rs256_token(&claims("api", now() + 600), "test-key");
+        let (first, second, third) = tokio::join!(
+            call(app(layer.clone()), Some(&token)),
+            call(app(layer.clone()), Some(&token)),
+            call(app(layer.clone()), Some(&token)),
+        );
+        for (status, _) in [first, second, third] {
+            assert_eq!(status, StatusCode::OK);
+        }
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_fetch_is_not_retried_per_request() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/jwks.json"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+        let layer = JwtAuthLayer::from_config(&auth_config(
+            None,
+            Some(&format!("{}/jwks.json", server.uri())),
+        ))
+        .unwrap();
+
+        let token = rs256_token(&claims("api", now() + 600), "test-key");
+        for _ in 0..3 {
+            let (status, _) = call(app(layer.clone()), Some(&token)).await;
+            assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        }
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hs256_token_rejected_by_jwks_layer() {
+        let server = jwks_server().await;
+        let layer = JwtAuthLayer::from_config(&auth_config(
+            None,
+            Some(&format!("{}/jwks.json", server.uri())),
+        ))
+        .unwrap();
+
+        let token = hs256_token(&claims("api", now() + 600));
+        let (status, _) = call(app(layer), Some(&token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,15 @@
+//! Barrzen Axum Auth
+//!
+//! Authentication middleware for Barrzen Axum applications:
+//! - Static API keys for service-to-service routes
+//! - JWT bearer tokens (HS256 secret or JWKS) with the `jwt` feature
+//!
+//! Layers are opt-in per router; nothing is applied globally by `AppBuilder`.
+
+pub mod api_key;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+
+pub use api_key::{ApiKeyIdentity, ApiKeyLayer};
+#[cfg(feature = "jwt")]
+pub use jwt::{AuthClaims, JwtAuthLayer};
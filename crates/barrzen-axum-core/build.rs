@@ -0,0 +1,8 @@
+//! Capture build details for `build_info!()` in this crate's own tests
+
+#[path = "src/build.rs"]
+mod build;
+
+fn main() {
+    build::emit_build_info();
+}
@@ -0,0 +1,288 @@
+//! Admin endpoints
+//!
+//! Opt-in operational endpoints enabled with `FEATURE_ADMIN_ENDPOINTS`. When
+//! `AUTH_API_KEYS` is set they require one of those keys in
+//! `AUTH_API_KEY_HEADER`, like the auth crate's API key layer.
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    config::AuthConfig,
+    extract::{ApiJson, ApiPath},
+    features::{FeatureError, FeatureState},
+    handlers::CoreState,
+    response::{ApiError, ApiResponse, extract_request_id},
+};
+
+/// Runtime control over the log filter
+///
+/// Implemented by the observability crate's tracing handle and registered
+/// with `AppBuilder::with_log_control`.
+pub trait LogLevelControl: Send + Sync {
+    /// Current filter directives, e.g. `info,hyper=warn`
+    fn current_filter(&self) -> String;
+
+    /// Replace the filter
+    ///
+    /// # Errors
+    /// Returns a description of the problem if `filter` cannot be parsed; the
+    /// current filter is then left untouched.
+    fn set_filter(&self, filter: &str) -> Result<(), String>;
+}
+
+/// Body of `PUT /admin/log-level`
+#[derive(Debug, Deserialize)]
+pub struct LogLevelRequest {
+    /// `EnvFilter` directives, e.g. `debug,hyper=warn`
+    pub filter: String,
+}
+
+/// Log level response data
+#[derive(Debug, Serialize)]
+pub struct LogLevelData {
+    pub filter: String,
+}
+
+/// PUT /admin/log-level - Replace the log filter at runtime
+pub async fn set_log_level(
+    headers: HeaderMap,
+    State(state): State<CoreState>,
+    ApiJson(body): ApiJson<LogLevelRequest>,
+) -> Response {
+    let request_id = extract_request_id(&headers);
+    let error = |error: ApiError| match &request_id {
+        Some(rid) => error.with_request_id(rid.clone()).into_response(),
+        None => error.into_response(),
+    };
+
+    let Some(control) = state.log_control.as_ref() else {
+        return error(ApiError::new(
+            StatusCode::NOT_IMPLEMENTED,
+            "Log level control is not configured",
+        ));
+    };
+    let previous = control.current_filter();
+    if let Err(e) = control.set_filter(body.filter.trim()) {
+        return error(ApiError::bad_request("Invalid log filter").with_details(e));
+    }
+
+    let data = LogLevelData {
+        filter: control.current_filter(),
+    };
+    tracing::warn!(previous, filter = data.filter, "Log filter changed");
+
+    if state.features.response_envelope() {
+        let mut response = ApiResponse::ok(data, "Log filter updated");
+        if let Some(rid) = request_id {
+            response = response.with_request_id(rid);
+        }
+        response.into_response()
+    } else {
+        axum::Json(data).into_response()
+    }
+}
+
+/// Body of `PUT /admin/maintenance`
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+    /// Message returned to clients while enabled
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// Maintenance mode response data
+#[derive(Debug, Serialize)]
+pub struct MaintenanceData {
+    pub enabled: bool,
+    pub message: String,
+}
+
+/// PUT /admin/maintenance - Switch maintenance mode at runtime
+pub async fn set_maintenance(
+    headers: HeaderMap,
+    State(state): State<CoreState>,
+    ApiJson(body): ApiJson<MaintenanceRequest>,
+) -> Response {
+    let request_id = extract_request_id(&headers);
+    let message = body
+        .message
+        .map(|message| message.trim().to_string())
+        .filter(|message| !message.is_empty());
+    state.maintenance.set(body.enabled, message);
+
+    let data = MaintenanceData {
+        enabled: state.maintenance.is_enabled(),
+        message: state.maintenance.message(),
+    };
+    tracing::warn!(
+        enabled = data.enabled,
+        message = data.message,
+        "Maintenance mode changed"
+    );
+
+    if state.features.response_envelope() {
+        let mut response = ApiResponse::ok(data, "Maintenance mode updated");
+        if let Some(rid) = request_id {
+            response = response.with_request_id(rid);
+        }
+        response.into_response()
+    } else {
+        axum::Json(data).into_response()
+    }
+}
+
+/// A feature flag as listed by `GET /admin/features`
+#[derive(Debug, Serialize)]
+pub struct FeatureFlagData {
+    /// Name without the `feature_` prefix, e.g. `request_log`
+    pub name: String,
+    pub enabled: bool,
+    /// Only changes with a restart; `PUT` is rejected
+    pub read_only: bool,
+}
+
+/// Feature flags response data
+#[derive(Debug, Serialize)]
+pub struct FeaturesData {
+    pub flags: Vec<FeatureFlagData>,
+}
+
+/// Body of `PUT /admin/features/{name}`
+#[derive(Debug, Deserialize)]
+pub struct FeatureRequest {
+    pub enabled: bool,
+}
+
+/// GET /admin/features - List feature flags and whether they can be switched
+pub async fn list_features(headers: HeaderMap, State(state): State<CoreState>) -> Response {
+    let flags = state
+        .features
+        .snapshot()
+        .into_iter()
+        .map(|(name, enabled)| FeatureFlagData {
+            read_only: !FeatureState::is_runtime(&name),
+            name,
+            enabled,
+        })
+        .collect();
+    respond(&state, &headers, FeaturesData { flags }, "Feature flags")
+}
+
+/// PUT /admin/features/{name} - Switch a runtime feature flag
+pub async fn set_feature(
+    headers: HeaderMap,
+    State(state): State<CoreState>,
+    ApiPath(name): ApiPath<String>,
+    ApiJson(body): ApiJson<FeatureRequest>,
+) -> Response {
+    if let Err(e) = state.features.set(&name, body.enabled) {
+        let error = match &e {
+            FeatureError::Unknown(_) => {
+                ApiError::not_found(e.to_string()).with_code("unknown_feature")
+            }
+            FeatureError::ReadOnly(_) => {
+                ApiError::conflict(e.to_string()).with_code("feature_read_only")
+            }
+        };
+        return match extract_request_id(&headers) {
+            Some(rid) => error.with_request_id(rid).into_response(),
+            None => error.into_response(),
+        };
+    }
+
+    let name = name.strip_prefix("feature_").unwrap_or(&name).to_string();
+    let data = FeatureFlagData {
+        enabled: state.features.get(&name).unwrap_or_default(),
+        read_only: false,
+        name,
+    };
+    tracing::warn!(
+        feature = data.name,
+        enabled = data.enabled,
+        "Feature flag changed"
+    );
+    respond(&state, &headers, data, "Feature flag updated")
+}
+
+/// `data` enveloped when `response_envelope` is on, bare JSON otherwise
+fn respond<T: Serialize>(
+    state: &CoreState,
+    headers: &HeaderMap,
+    data: T,
+    message: &str,
+) -> Response {
+    if state.features.response_envelope() {
+        let mut response = ApiResponse::ok(data, message);
+        if let Some(rid) = extract_request_id(headers) {
+            response = response.with_request_id(rid);
+        }
+        response.into_response()
+    } else {
+        axum::Json(data).into_response()
+    }
+}
+
+/// API keys guarding the admin endpoints
+#[derive(Clone)]
+pub(crate) struct AdminKeys {
+    /// `None` when `AUTH_API_KEY_HEADER` is invalid: every request is denied
+    header: Option<HeaderName>,
+    keys: Arc<Vec<Vec<u8>>>,
+}
+
+impl AdminKeys {
+    /// Keys from `AUTH_API_KEYS`, or `None` when no key is configured
+    pub(crate) fn from_config(config: &AuthConfig) -> Option<Self> {
+        let keys: Vec<_> = config
+            .api_keys()
+            .into_iter()
+            .map(|(_, key)| key.into_bytes())
+            .collect();
+        if keys.is_empty() {
+            return None;
+        }
+        Some(Self {
+            header: HeaderName::from_bytes(config.auth_api_key_header.trim().as_bytes()).ok(),
+            keys: Arc::new(keys),
+        })
+    }
+
+    fn accepts(&self, headers: &HeaderMap) -> bool {
+        let Some(presented) = self.header.as_ref().and_then(|h| headers.get(h)) else {
+            return false;
+        };
+        // Compare against every key so timing does not reveal which matched
+        self.keys.iter().fold(false, |matched, key| {
+            matched | bool::from(key.as_slice().ct_eq(presented.as_bytes()))
+        })
+    }
+
+    /// Middleware rejecting requests without a valid key
+    pub(crate) async fn require(self, req: Request, next: Next) -> Response {
+        if self.accepts(req.headers()) {
+            return next.run(req).await;
+        }
+
+        let request_id = extract_request_id(req.headers());
+        tracing::warn!(
+            request_id = request_id.as_deref().unwrap_or(""),
+            path = req.uri().path(),
+            "Admin endpoint authentication failed"
+        );
+        let mut error = ApiError::unauthorized("Invalid or missing API key");
+        if let Some(request_id) = request_id {
+            error = error.with_request_id(request_id);
+        }
+        error.into_response()
+    }
+}
@@ -3,355 +3,1390 @@
 //! Provides a builder pattern for constructing Axum applications.
 
 use std::{
+    convert::Infallible,
     future::Future,
+    net::{IpAddr, SocketAddr},
     pin::Pin,
-    sync::Arc,
-    task::{Context, Poll},
-    time::{Duration, Instant},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
 };
 
+use anyhow::Context as _;
 use axum::{
-    http::{HeaderName, HeaderValue, Method},
-    http::Request,
     Router,
+    extract::Request,
+    http::{HeaderName, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    routing::{MethodRouter, Route},
+    serve::ListenerExt,
 };
-use tokio::net::TcpListener;
-use tower::{Layer, Service};
+use tokio::{net::TcpListener, time::Instant};
+use tower::{Layer, Service, util::option_layer};
 use tower_http::{
-    compression::CompressionLayer,
-    cors::CorsLayer,
-    limit::RequestBodyLimitLayer,
-    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
-    sensitive_headers::SetSensitiveRequestHeadersLayer,
-    set_header::SetResponseHeaderLayer,
+    sensitive_headers::SetSensitiveRequestHeadersLayer, set_header::SetResponseHeaderLayer,
     trace::TraceLayer,
 };
 
+#[cfg(feature = "scheduler")]
+use crate::scheduler::{CronSchedule, JobOptions, ScheduledJob};
+#[cfg(feature = "static-files")]
+use crate::static_files::StaticOptions;
 use crate::{
-    config::{Config, LogBackend},
-    handlers::{self, CoreState, ReadyChecker},
     BuildInfo,
+    admin::{self, AdminKeys, LogLevelControl},
+    app_context::{AppContext, AppContextLayer},
+    audit::{AuditLogger, AuditSink},
+    client_ip::ClientIpLayer,
+    compression::build_compression_layer,
+    config::Config,
+    cors::build_cors_layer,
+    envelope::EnvelopeLayer,
+    etag::EtagLayer,
+    features::FeatureState,
+    handlers::{self, CoreState, ReadyChecker, ReadyCheckers},
+    ip_filter::IpFilterLayer,
+    load_shed::RequestLimit,
+    maintenance::{self, MaintenanceGuard},
+    path_redaction::PathRedactor,
+    request_context::RequestContextLayer,
+    request_limits::RequestLimitsLayer,
+    request_log::RequestLogLayer,
+    response_time::ResponseTimeLayer,
+    tasks::{BackgroundTasks, CancellationToken, TaskOptions},
+    tenant::TenantLayer,
+    timings::StartupTimings,
 };
-/// Header name for request ID
-pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+/// Default header name for request ID (`REQUEST_ID_HEADER_NAME` overrides it)
+pub static REQUEST_ID_HEADER: HeaderName =
+    HeaderName::from_static(crate::response::REQUEST_ID_HEADER);
+
+/// A user layer captured by [`AppBuilder::layer`]
+type UserLayer = Box<dyn FnOnce(Router<CoreState>) -> Router<CoreState> + Send>;
 
 /// Application builder
 ///
 /// Constructs an Axum application with standard middleware and routes.
-pub struct AppBuilder {
+/// `S` is the user application state set with [`AppBuilder::with_app_state`];
+/// core routes always use [`CoreState`].
+pub struct AppBuilder<S = ()> {
     config: Config,
     build_info: BuildInfo,
-    ready_checker: Option<Arc<dyn ReadyChecker>>,
-    user_router: Option<Router<CoreState>>,
-    user_stateless_router: Option<Router<()>>,
+    ready_checkers: Vec<Arc<dyn ReadyChecker>>,
+    log_control: Option<Arc<dyn LogLevelControl>>,
+    user_routers: Vec<Router<CoreState>>,
+    user_stateless_routers: Vec<Router<()>>,
+    user_layers: Vec<UserLayer>,
+    management_routers: Vec<Router<CoreState>>,
+    #[cfg(feature = "grpc")]
+    grpc_routes: Option<tonic::service::Routes>,
+    session_layer: Option<UserLayer>,
+    shutdown_signal: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    shutting_down: Arc<AtomicBool>,
+    /// Cancelled with `shutting_down`, ending long-lived responses (SSE)
+    shutdown: CancellationToken,
+    #[cfg(feature = "ws")]
+    ws_connections: crate::ws::WsConnections,
+    audit: AuditLogger,
+    /// Startup phases, logged and shown once serving starts
+    startup_timings: StartupTimings,
+    tasks: BackgroundTasks,
+    /// Problems found while registering, reported by `try_build`
+    setup_errors: Vec<String>,
+    app_state: S,
 }
 
 impl AppBuilder {
     /// Create a new app builder
     #[must_use]
     pub fn new(config: Config, build_info: BuildInfo) -> Self {
+        let audit = AuditLogger::from_config(&config.audit);
         Self {
             config,
             build_info,
-            ready_checker: None,
-            user_router: None,
-            user_stateless_router: None,
+            ready_checkers: Vec::new(),
+            log_control: None,
+            user_routers: Vec::new(),
+            user_stateless_routers: Vec::new(),
+            user_layers: Vec::new(),
+            management_routers: Vec::new(),
+            #[cfg(feature = "grpc")]
+            grpc_routes: None,
+            session_layer: None,
+            shutdown_signal: None,
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            shutdown: CancellationToken::new(),
+            #[cfg(feature = "ws")]
+            ws_connections: crate::ws::WsConnections::default(),
+            audit,
+            startup_timings: StartupTimings::disabled(),
+            tasks: BackgroundTasks::default(),
+            setup_errors: Vec::new(),
+            app_state: (),
+        }
+    }
+}
+
+impl<S> AppBuilder<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    /// Set the application state for routers added with
+    /// [`AppBuilder::merge_with_state`]
+    ///
+    /// Routers merged before this call keep the state they were given.
+    #[must_use]
+    pub fn with_app_state<T>(self, state: T) -> AppBuilder<T>
+    where
+        T: Clone + Send + Sync + 'static,
+    {
+        AppBuilder {
+            config: self.config,
+            build_info: self.build_info,
+            ready_checkers: self.ready_checkers,
+            log_control: self.log_control,
+            user_routers: self.user_routers,
+            user_stateless_routers: self.user_stateless_routers,
+            user_layers: self.user_layers,
+            management_routers: self.management_routers,
+            #[cfg(feature = "grpc")]
+            grpc_routes: self.grpc_routes,
+            session_layer: self.session_layer,
+            shutdown_signal: self.shutdown_signal,
+            shutting_down: self.shutting_down,
+            shutdown: self.shutdown,
+            #[cfg(feature = "ws")]
+            ws_connections: self.ws_connections,
+            audit: self.audit,
+            startup_timings: self.startup_timings,
+            tasks: self.tasks,
+            setup_errors: self.setup_errors,
+            app_state: state,
         }
     }
 
+    /// Merge user routes that extract `State<S>`
+    ///
+    /// The router receives the application state here and is then merged
+    /// like any other user router, so it gets the full middleware stack.
+    #[must_use]
+    pub fn merge_with_state(mut self, router: Router<S>) -> Self {
+        self.user_routers
+            .push(router.with_state(self.app_state.clone()));
+        self
+    }
+
     /// Add infrastructure for health checks
+    ///
+    /// Can be called more than once; /readyz reports the checks of every
+    /// checker in the order they were added.
     #[must_use]
     pub fn with_ready_checker(mut self, checker: impl ReadyChecker + 'static) -> Self {
-        self.ready_checker = Some(Arc::new(checker));
+        self.ready_checkers.push(Arc::new(checker));
+        self
+    }
+
+    /// Allow changing the log filter at runtime
+    ///
+    /// Served at `PUT /admin/log-level` (`CORE_LOG_LEVEL_PATH`) when
+    /// `FEATURE_ADMIN_ENDPOINTS=true`.
+    #[must_use]
+    pub fn with_log_control(mut self, control: impl LogLevelControl + 'static) -> Self {
+        self.log_control = Some(Arc::new(control));
         self
     }
 
     /// Merge user routes (stateful)
+    ///
+    /// Can be called repeatedly; all routers are merged in call order.
     #[must_use]
     pub fn merge(mut self, router: Router<CoreState>) -> Self {
-        self.user_router = Some(router);
+        self.user_routers.push(router);
+        self
+    }
+
+    /// Add a single route (sugar over [`AppBuilder::merge`])
+    #[must_use]
+    pub fn route(mut self, path: &str, method_router: MethodRouter<CoreState>) -> Self {
+        let router = self.user_routers.pop().unwrap_or_default();
+        self.user_routers.push(router.route(path, method_router));
+        self
+    }
+
+    /// Add a tower layer around every route, including the core endpoints
+    ///
+    /// Ordering guarantees:
+    /// - User layers run inside the request ID, sensitive header, request log
+    ///   and tracing layers, so they see `x-request-id` and their responses
+    ///   (e.g. auth rejections) are logged like any other.
+    /// - They wrap the body limit, security header and compression layers and
+    ///   the routes themselves.
+    /// - As with `Router::layer`, a later call wraps the layers added before it.
+    #[must_use]
+    pub fn layer<L>(mut self, layer: L) -> Self
+    where
+        L: Layer<Route> + Clone + Send + Sync + 'static,
+        L::Service: Service<Request> + Clone + Send + Sync + 'static,
+        <L::Service as Service<Request>>::Response: IntoResponse + 'static,
+        <L::Service as Service<Request>>::Error: Into<Infallible> + 'static,
+        <L::Service as Service<Request>>::Future: Send + 'static,
+    {
+        self.user_layers
+            .push(Box::new(move |router| router.layer(layer)));
         self
     }
-    
+
     /// Merge stateless routes (e.g. Swagger UI, unmodified static handlers)
+    ///
+    /// Can be called repeatedly; all stateless routers are merged in call
+    /// order and serve requests no stateful route matched.
     #[must_use]
     pub fn merge_stateless(mut self, router: Router<()>) -> Self {
-        self.user_stateless_router = Some(router);
+        self.user_stateless_routers.push(router);
         self
     }
 
-    /// Build the router with all middleware
+    /// Merge operator routes, e.g. a Prometheus `/metrics` handler
+    ///
+    /// Served on the management listener when `MANAGEMENT_PORT` is set and
+    /// next to the core endpoints otherwise. Like them, they bypass
+    /// maintenance mode and user layers.
+    #[must_use]
+    pub fn merge_management(mut self, router: Router<CoreState>) -> Self {
+        self.management_routers.push(router);
+        self
+    }
+
+    /// Add a tonic service, e.g. the generated `GreeterServer::new(greeter)`
+    ///
+    /// Served on `GRPC_PORT` when set, otherwise on the application
+    /// listeners next to the HTTP routes, picked by the
+    /// `content-type: application/grpc` header. gRPC calls get the request
+    /// id and trace context layers and shut down with the app, but none of
+    /// the HTTP middleware.
+    #[cfg(feature = "grpc")]
+    #[must_use]
+    pub fn add_grpc_service<Svc>(mut self, service: Svc) -> Self
+    where
+        Svc: Service<http::Request<tonic::body::Body>, Error = Infallible>
+            + tonic::server::NamedService
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+        Svc::Response: IntoResponse,
+        Svc::Future: Send + 'static,
+    {
+        self.grpc_routes = Some(match self.grpc_routes.take() {
+            Some(routes) => routes.add_service(service),
+            None => tonic::service::Routes::new(service),
+        });
+        self
+    }
+
+    /// Audit logger of the app, for recording outside handlers
+    ///
+    /// Handlers take [`AuditLogger`] as an extractor instead, which fills
+    /// the client IP.
+    #[must_use]
+    pub fn audit_logger(&self) -> AuditLogger {
+        self.audit.clone()
+    }
+
+    /// Also write audit records to `sink`, e.g. a database table
+    #[must_use]
+    pub fn with_audit_sink(self, sink: impl AuditSink + 'static) -> Self {
+        self.audit.add_sink(sink);
+        self
+    }
+
+    /// Report the startup phases recorded in `timings`
+    ///
+    /// Waiting for readiness, binding and building the router are recorded
+    /// too. Once the listeners are bound every phase is logged at info, shown
+    /// in the banner's TIMINGS section and reported by /version.
+    #[must_use]
+    pub fn with_startup_timings(mut self, timings: StartupTimings) -> Self {
+        self.startup_timings = timings;
+        self
+    }
+
+    /// Counter of the open [`WsSession`](crate::ws::WsSession) connections
+    ///
+    /// Clones share the count: export it as a metric, or report it in
+    /// /readyz with [`WsHealthChecker`](crate::ws::WsHealthChecker).
+    #[cfg(feature = "ws")]
+    #[must_use]
+    pub fn ws_connections(&self) -> crate::ws::WsConnections {
+        self.ws_connections.clone()
+    }
+
+    /// Serve the files in `dir` under `options.prefix`, e.g. an admin SPA
+    ///
+    /// Mounted like a [`AppBuilder::merge_stateless`] router, so stateful
+    /// and other stateless routes take precedence. Only one stateless router
+    /// may have a fallback, which a mount at `/` is.
+    #[cfg(feature = "static-files")]
+    #[must_use]
+    pub fn serve_static(self, dir: impl AsRef<std::path::Path>, options: StaticOptions) -> Self {
+        self.merge_stateless(crate::static_files::static_router(dir.as_ref(), options))
+    }
+
+    /// Replace the default Ctrl+C/SIGTERM shutdown trigger
+    ///
+    /// Applies to every `serve*` method.
+    #[must_use]
+    pub fn with_shutdown_signal(
+        mut self,
+        signal: impl Future<Output = ()> + Send + 'static,
+    ) -> Self {
+        self.shutdown_signal = Some(Box::pin(signal));
+        self
+    }
+
+    /// Run a background task while the server is up
+    ///
+    /// The task starts when serving begins. `shutdown` is cancelled when the
+    /// shutdown signal fires; after the server stops, tasks get
+    /// `APP_SHUTDOWN_GRACE_SECONDS` to return before they are abandoned.
+    /// Panics are logged with `name` and the task state is reported in
+    /// /readyz as `task:<name>`.
+    ///
+    /// ```no_run
+    /// # use barrzen_axum_core::{AppBuilder, BuildInfo, Config};
+    /// # let builder = AppBuilder::new(Config::default(), BuildInfo::new("app", "0.1.0", None, "1.85", None));
+    /// let builder = builder.spawn_task("queue-consumer", |shutdown| async move {
+    ///     while !shutdown.is_cancelled() {
+    ///         tokio::select! {
+    ///             () = shutdown.cancelled() => break,
+    ///             () = tokio::time::sleep(std::time::Duration::from_secs(1)) => {}
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    #[must_use]
+    pub fn spawn_task<F, Fut>(self, name: impl Into<String>, task: F) -> Self
+    where
+        F: Fn(CancellationToken) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn_task_with(name, TaskOptions::default(), task)
+    }
+
+    /// [`AppBuilder::spawn_task`] with options, e.g. restarting after panics
+    #[must_use]
+    pub fn spawn_task_with<F, Fut>(
+        mut self,
+        name: impl Into<String>,
+        options: TaskOptions,
+        task: F,
+    ) -> Self
+    where
+        F: Fn(CancellationToken) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.push(name.into(), options, task);
+        self
+    }
+
+    /// Run `job` on a cron schedule while the server is up
+    ///
+    /// `expression` has six fields, `second minute hour day-of-month month
+    /// day-of-week`, evaluated in UTC (five-field expressions run at second
+    /// 0). A tick is skipped while the previous run is still going. Outcomes
+    /// are logged with the job name and duration and, with
+    /// `FEATURE_OTEL_METRICS`, counted in `scheduler.job.runs`. The job runs
+    /// as a background task, so shutdown waits for an in-flight run up to
+    /// `APP_SHUTDOWN_GRACE_SECONDS`. An invalid expression fails
+    /// [`AppBuilder::try_build`].
+    ///
+    /// ```no_run
+    /// # use barrzen_axum_core::{AppBuilder, BuildInfo, Config};
+    /// # let builder = AppBuilder::new(Config::default(), BuildInfo::new("app", "0.1.0", None, "1.85", None));
+    /// let builder = builder.schedule("purge-sessions", "0 */5 * * * *", || async {
+    ///     // delete expired sessions
+    ///     Ok(())
+    /// });
+    /// ```
+    #[cfg(feature = "scheduler")]
     #[must_use]
+    pub fn schedule<F, Fut>(self, name: impl Into<String>, expression: &str, job: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.schedule_with(name, expression, JobOptions::default(), job)
+    }
+
+    /// [`AppBuilder::schedule`] with options: overlap, jitter and timeout
+    #[cfg(feature = "scheduler")]
+    #[must_use]
+    pub fn schedule_with<F, Fut>(
+        mut self,
+        name: impl Into<String>,
+        expression: &str,
+        options: JobOptions,
+        job: F,
+    ) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let name = name.into();
+        let schedule = match CronSchedule::parse(expression) {
+            Ok(schedule) => schedule,
+            Err(e) => {
+                self.setup_errors.push(format!("scheduled job {name}: {e}"));
+                return self;
+            }
+        };
+        let job = ScheduledJob::new(&name, schedule, options, job);
+        #[cfg(feature = "otel")]
+        let job = if self.config.features.feature_otel_metrics {
+            job.with_metrics(&opentelemetry::global::meter("barrzen-axum"))
+        } else {
+            job
+        };
+        self.tasks
+            .push(name, TaskOptions::default(), move |shutdown| {
+                job.clone().run(shutdown)
+            });
+        self
+    }
+
+    /// Install the session layer backed by `store`
+    ///
+    /// Only applied when `FEATURE_SESSION=true`; the cookie settings come
+    /// from the `SESSION_*` config. Handlers read sessions with the
+    /// [`tower_sessions::Session`] extractor.
+    #[cfg(feature = "session")]
+    #[must_use]
+    pub fn with_session_store<St>(mut self, store: St) -> Self
+    where
+        St: tower_sessions::SessionStore + Clone,
+    {
+        let layer = session_layer(&self.config.session, store);
+        self.session_layer = Some(Box::new(move |router| router.layer(layer)));
+        self
+    }
+
+    /// Build the router with all middleware
+    ///
+    /// # Panics
+    /// Panics if the configuration cannot be honored (see
+    /// [`AppBuilder::try_build`]).
     pub fn build(self) -> Router {
+        self.try_build().unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    /// Build the router with all middleware
+    ///
+    /// # Errors
+    /// Returns error if `FEATURE_SESSION=true` but no session store was
+    /// provided with `with_session_store`, or if a scheduled job has an
+    /// invalid cron expression.
+    pub fn try_build(self) -> anyhow::Result<Router> {
+        Ok(self.build_routers(false)?.0)
+    }
+
+    /// Build the application and management routers
+    ///
+    /// The management router holds the core endpoints, admin routes and
+    /// [`AppBuilder::merge_management`] routes, without `APP_BASE_PATH` and
+    /// with only the request ID middleware. The application router keeps
+    /// /healthz, /readyz and /version unless `MANAGEMENT_EXCLUSIVE=true`.
+    /// Both share one [`CoreState`].
+    ///
+    /// # Errors
+    /// Same as [`AppBuilder::try_build`].
+    pub fn try_build_with_management(self) -> anyhow::Result<(Router, Router)> {
+        let (app, management) = self.build_routers(true)?;
+        Ok((app, management.unwrap_or_default()))
+    }
+
+    /// Build the routers, with a separate management router when `split`
+    #[allow(clippy::too_many_lines)] // one step per layer, in stack order
+    fn build_routers(self, split: bool) -> anyhow::Result<(Router, Option<Router>)> {
         let Self {
             config,
             build_info,
-            ready_checker,
-            user_router,
-            user_stateless_router,
+            mut ready_checkers,
+            log_control,
+            user_routers,
+            user_stateless_routers,
+            user_layers,
+            management_routers,
+            #[cfg(feature = "grpc")]
+            grpc_routes,
+            session_layer,
+            shutdown_signal: _,
+            shutting_down,
+            shutdown,
+            #[cfg(feature = "ws")]
+            ws_connections,
+            audit,
+            startup_timings,
+            tasks: _,
+            setup_errors,
+            app_state: _,
         } = self;
 
-        let state = CoreState::new(build_info, config.features.feature_response_envelope);
-        let state = if let Some(checker) = ready_checker {
-            state.with_ready_checker(checker)
+        if let Some(error) = setup_errors.into_iter().next() {
+            anyhow::bail!(error);
+        }
+
+        let session_layer = if config.features.feature_session {
+            let Some(layer) = session_layer else {
+                anyhow::bail!(
+                    "FEATURE_SESSION is enabled but no session store was provided; enable the \
+                     'session' cargo feature and call AppBuilder::with_session_store"
+                );
+            };
+            Some(layer)
+        } else {
+            None
+        };
+
+        let state = CoreState::new(build_info, config.features.feature_response_envelope)
+            .with_config(&config)
+            .with_shutdown_flag(shutting_down)
+            .with_audit_logger(audit.clone())
+            .with_startup_timings(startup_timings);
+        let state = match ready_checkers.len() {
+            0 => state,
+            1 => state.with_ready_checker(ready_checkers.remove(0)),
+            _ => state.with_ready_checker(Arc::new(ReadyCheckers(ready_checkers))),
+        };
+        let state = if let Some(control) = log_control {
+            state.with_log_control(control)
         } else {
+            if config.features.feature_admin_endpoints {
+                tracing::warn!(
+                    "FEATURE_ADMIN_ENDPOINTS is enabled but no log control was provided; \
+                     PUT {} will answer 501",
+                    config.core_routes.core_log_level_path
+                );
+            }
             state
         };
 
-        // Start with core routes
-        let mut app: Router<CoreState> = Router::new()
-            .route("/healthz", axum::routing::get(handlers::healthz))
-            .route("/readyz", axum::routing::get(handlers::readyz))
-            .route("/version", axum::routing::get(handlers::version));
+        // Core and operator routes go to the management router when split
+        let management = management_routers
+            .into_iter()
+            .fold(admin_router(&config), Router::merge);
+        let (mut app, management) = match (split, config.management.management_exclusive) {
+            (false, _) => (health_router(&config).merge(management), None),
+            (true, true) => (Router::new(), Some(management)),
+            (true, false) => (health_router(&config), Some(management)),
+        };
+        let management = management.map(|management| {
+            let management = health_router(&config).merge(management);
+            apply_management_middleware(management, &config, &state.features)
+                .with_state(state.clone())
+        });
+
+        // Start with core routes, limited by their own reserved pool so
+        // probes pass while application traffic is shed
+        let shed = config.features.feature_load_shed;
+        if state.request_limit.is_some() && config.http.http_health_reserved_requests > 0 {
+            app = RequestLimit::new(config.http.http_health_reserved_requests).apply(app, shed);
+        }
+        #[cfg(feature = "otel")]
+        if let Some(limit) = &state.request_limit
+            && config.features.feature_otel_metrics
+        {
+            limit.register_metrics(&opentelemetry::global::meter("barrzen-axum"));
+        }
+
+        // Maintenance mode rejects application routes, never the core endpoints
+        let guard = MaintenanceGuard {
+            state: state.maintenance.clone(),
+            retry_after_seconds: config.maintenance.maintenance_retry_after_seconds,
+        };
+        let maintenance_layer =
+            axum::middleware::from_fn_with_state(guard, maintenance::reject_during_maintenance);
+
+        // Tenants are resolved for application routes only, so probes need none
+        let tenant_layer = config
+            .features
+            .feature_tenant
+            .then(|| TenantLayer::from_config(&config.tenant))
+            .transpose()?;
 
-        // Merge stateless routes as fallback
-        if let Some(router) = user_stateless_router {
-            app = app.fallback_service(router);
+        // Merge stateless routes as fallback, sharing the application limit
+        if !user_stateless_routers.is_empty() {
+            let stateless = user_stateless_routers
+                .into_iter()
+                .fold(Router::new(), Router::merge)
+                .layer(option_layer(tenant_layer.clone()))
+                .layer(maintenance_layer.clone());
+            app = app.fallback_service(match &state.request_limit {
+                Some(limit) => limit.apply(stateless, shed),
+                None => stateless,
+            });
         }
 
         // Merge user routes
-        if let Some(router) = user_router {
-            app = app.merge(router);
+        for router in user_routers {
+            let router = router
+                .layer(option_layer(tenant_layer.clone()))
+                .layer(maintenance_layer.clone());
+            app = app.merge(match &state.request_limit {
+                Some(limit) => limit.apply(router, shed),
+                None => router,
+            });
+        }
+
+        // Mount everything under the base path
+        if let Some(base_path) = config.app.base_path() {
+            let mut root = Router::new().nest(&base_path, app);
+            if config.app.app_base_path_redirect {
+                root = root.fallback(move |uri: Uri| {
+                    let response = redirect_to_base_path(&base_path, &uri);
+                    async move { response }
+                });
+            }
+            app = root;
+        }
+
+        // Config and shutdown token for responses built without the state
+        let mut context = AppContext::new(Arc::new(config.clone()), shutdown);
+        context.audit = audit;
+        #[cfg(feature = "ws")]
+        {
+            context.ws_connections = ws_connections;
         }
+        app = app.layer(AppContextLayer::new(context));
 
         // Apply middleware
-        app = apply_middleware(app, &config);
+        app = apply_middleware(app, &config, &state.features, user_layers, session_layer)?;
+        let app = app.with_state(state);
 
-        app.with_state(state)
+        // gRPC shares the listeners unless `run` moved it to GRPC_PORT
+        #[cfg(feature = "grpc")]
+        let app = match grpc_routes {
+            Some(routes) => crate::grpc::multiplex(app, crate::grpc::grpc_router(routes, &config)),
+            None => app,
+        };
+
+        Ok((app, management))
     }
 
     /// Serve the application
     ///
+    /// Binds every `APP_HOST` address on `APP_PORT` (see [`AppBuilder::bind`])
+    /// and serves them together, or the Unix socket at `APP_UDS_PATH` when set.
+    /// With `MANAGEMENT_PORT` set, the management listener is served as well.
+    /// With `STARTUP_REQUIRE_READY=true`, the ready checks must pass first.
+    ///
     /// # Errors
-    /// Returns error if binding or serving fails.
+    /// Returns error if the ready checks keep failing, or if resolving,
+    /// binding or serving fails.
     pub async fn serve(self) -> anyhow::Result<()> {
-        let addr = self.config.socket_addr();
-        let grace_seconds = self.config.app.app_shutdown_grace_seconds;
+        self.require_ready().await?;
 
-        // Print banner
-        crate::banner::print_banner(&self.config, &self.build_info);
+        #[cfg(unix)]
+        if let Some(path) = self.config.app.app_uds_path.clone() {
+            return self.serve_unix(path).await;
+        }
 
-        let app = self.build();
-        let listener = TcpListener::bind(addr).await?;
+        let started = Instant::now();
+        let listeners = self.bind().await?;
+        let management = self.bind_management().await?;
+        self.startup_timings.record("bind", started.elapsed());
+        self.serve_bound(listeners, management).await
+    }
 
-        tracing::info!("Server listening on http://{}", addr);
+    /// Wait for the ready checks before binding (`STARTUP_REQUIRE_READY`)
+    ///
+    /// Retries with backoff from `STARTUP_RETRY_BASE_MS` up to
+    /// `STARTUP_RETRY_MAX_MS` until every critical check passes.
+    ///
+    /// The future owns what it needs, so `serve` holds no borrow of the
+    /// builder across an await and stays `Send`.
+    fn require_ready(&self) -> impl Future<Output = anyhow::Result<()>> + Send + 'static {
+        let required = self.config.startup.startup_require_ready && !self.ready_checkers.is_empty();
+        let checker = ReadyCheckers(self.ready_checkers.clone());
+        let config = self.config.clone();
+        let timings = self.startup_timings.clone();
+        async move {
+            if !required {
+                return Ok(());
+            }
+            let ready = crate::startup::wait_until_ready(&checker, &config);
+            timings.phase("wait_ready", ready).await
+        }
+    }
 
-        axum::serve(listener, app)
-            .with_graceful_shutdown(shutdown_signal(grace_seconds))
-            .await?;
+    /// Bind a TCP listener for every `APP_HOST` entry on `APP_PORT`
+    ///
+    /// Entries are IP literals (IPv6 with or without brackets) or hostnames,
+    /// which are resolved here and bound once per resolved address. With
+    /// `APP_PORT=0` each listener gets its own ephemeral port.
+    ///
+    /// `::` accepts IPv4 too (as v4-mapped addresses) where the OS defaults
+    /// to dual-stack sockets, as Linux does; listing `0.0.0.0,::` then fails
+    /// with "address in use", so bind `::` alone for both families.
+    ///
+    /// # Errors
+    /// Returns error if a hostname does not resolve or an address cannot be
+    /// bound.
+    pub fn bind(&self) -> impl Future<Output = anyhow::Result<Vec<TcpListener>>> + Send + 'static {
+        bind_hosts(
+            "APP_HOST",
+            owned(&self.config.app.hosts()),
+            self.config.app.app_port,
+        )
+    }
 
-        tracing::info!("Server shutdown complete");
+    /// Bind the management listeners on `MANAGEMENT_HOST:MANAGEMENT_PORT`
+    ///
+    /// Empty when `MANAGEMENT_PORT` is unset. Hosts resolve as in
+    /// [`AppBuilder::bind`].
+    ///
+    /// # Errors
+    /// Returns error if a hostname does not resolve or an address cannot be
+    /// bound.
+    pub fn bind_management(
+        &self,
+    ) -> impl Future<Output = anyhow::Result<Vec<TcpListener>>> + Send + 'static {
+        let management = &self.config.management;
+        let hosts = owned(&management.hosts());
+        let port = management.management_port;
+        async move {
+            match port {
+                Some(port) => bind_hosts("MANAGEMENT_HOST", hosts, port).await,
+                None => Ok(Vec::new()),
+            }
+        }
+    }
 
-        Ok(())
+    /// Serve the application until `signal` resolves
+    ///
+    /// Same as [`AppBuilder::serve`] with a programmatic shutdown trigger
+    /// instead of Ctrl+C/SIGTERM (tests, embedded runners).
+    ///
+    /// # Errors
+    /// Returns error if binding or serving fails.
+    pub async fn serve_with_shutdown(
+        self,
+        signal: impl Future<Output = ()> + Send + 'static,
+    ) -> anyhow::Result<()> {
+        self.with_shutdown_signal(signal).serve().await
     }
-}
 
-fn apply_middleware(router: Router<CoreState>, config: &Config) -> Router<CoreState> {
-    // Sensitive headers
-    let sensitive_headers: Vec<HeaderName> = config
-        .logging
-        .request_log_headers_denylist
-        .split(',')
-        .filter_map(|h| h.trim().parse().ok())
-        .collect();
+    /// Serve the application on a pre-bound TCP listener
+    ///
+    /// Useful for socket activation (systemd, listenfd) and for tests binding
+    /// an ephemeral port. `APP_HOST`/`APP_PORT` are ignored; the management
+    /// listener is still bound from the config.
+    ///
+    /// # Errors
+    /// Returns error if serving fails.
+    pub async fn serve_with_listener(self, listener: TcpListener) -> anyhow::Result<()> {
+        self.serve_with_listeners(vec![listener]).await
+    }
 
-    // Start building middleware stack (applied in reverse order)
-    let router = router.layer(CompressionLayer::new());
+    /// Serve the application on several pre-bound TCP listeners at once
+    ///
+    /// All listeners share the router and shut down together.
+    /// `APP_HOST`/`APP_PORT` are ignored; the management listener is still
+    /// bound from the config.
+    ///
+    /// # Errors
+    /// Returns error if `listeners` is empty, the ready checks keep failing,
+    /// binding the management listener fails or serving fails.
+    pub async fn serve_with_listeners(self, listeners: Vec<TcpListener>) -> anyhow::Result<()> {
+        self.require_ready().await?;
+        let management = self.bind_management().await?;
+        self.serve_bound(listeners, management).await
+    }
 
-    // Security headers
-    let router = apply_security_headers(router);
+    /// Serve pre-bound application and management listeners
+    ///
+    /// The management listeners serve the router of
+    /// [`AppBuilder::try_build_with_management`]; when `management` is empty
+    /// everything is served on `listeners`. Both share shutdown and drain.
+    ///
+    /// # Errors
+    /// Returns error if `listeners` is empty, the ready checks keep failing
+    /// or serving fails.
+    pub async fn serve_with_management(
+        self,
+        listeners: Vec<TcpListener>,
+        management: Vec<TcpListener>,
+    ) -> anyhow::Result<()> {
+        self.require_ready().await?;
+        self.serve_bound(listeners, management).await
+    }
 
-    // Body limit
-    let router = router.layer(RequestBodyLimitLayer::new(config.http.http_body_limit_bytes));
+    async fn serve_bound(
+        self,
+        listeners: Vec<TcpListener>,
+        management: Vec<TcpListener>,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(!listeners.is_empty(), "No listener to serve on");
+        let addresses = listeners
+            .iter()
+            .map(|listener| Ok(format!("http://{}", listener.local_addr()?)))
+            .collect::<std::io::Result<_>>()?;
+        self.run(listeners, addresses, management).await
+    }
 
-    // Tracing layer (conditional)
-    let router = if config.features.feature_tracing {
-        // Keep spans for tracing, but disable default response logs to avoid duplicates.
-        router.layer(
-            TraceLayer::new_for_http()
-                .on_request(())
-                .on_response(())
-                .on_failure(()),
-        )
-    } else {
-        router
-    };
+    /// Serve the application on a Unix domain socket
+    ///
+    /// A stale socket file at `path` is removed first, and the new socket gets
+    /// the permissions from `APP_UDS_MODE`. The socket file is removed again on
+    /// shutdown.
+    #[cfg(unix)]
+    async fn serve_unix(self, path: String) -> anyhow::Result<()> {
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
 
-    // Request logging (conditional)
-    let router = if config.features.feature_request_log {
-        router.layer(RequestLogLayer::new(config.logging.log_backend))
-    } else {
-        router
-    };
+        let mode = u32::from_str_radix(&self.config.app.app_uds_mode, 8).map_err(|_| {
+            anyhow::anyhow!(
+                "APP_UDS_MODE must be an octal permission string, got {:?}",
+                self.config.app.app_uds_mode
+            )
+        })?;
 
-    // Sensitive headers protection
-    let router = router.layer(SetSensitiveRequestHeadersLayer::new(sensitive_headers));
+        match std::fs::symlink_metadata(&path) {
+            Ok(meta) if meta.file_type().is_socket() => std::fs::remove_file(&path)?,
+            Ok(_) => anyhow::bail!("APP_UDS_PATH {path} exists and is not a socket"),
+            Err(_) => {}
+        }
 
-    // Request ID layers
-    let router = router.layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER.clone()));
-    let router = router.layer(SetRequestIdLayer::new(
-        REQUEST_ID_HEADER.clone(),
-        MakeRequestUuid,
-    ));
+        let management = self.bind_management().await?;
+        let listener = tokio::net::UnixListener::bind(&path)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
 
-    // CORS (conditional)
-    let router = if config.features.feature_cors {
-        router.layer(build_cors_layer(config))
-    } else {
-        router
-    };
+        let address = format!("unix:{path}");
+        let result = self.run(vec![listener], vec![address], management).await;
+        let _ = std::fs::remove_file(&path);
+        result
+    }
 
-    router
-}
+    /// Serve until shutdown, then drain
+    ///
+    /// Once the shutdown signal fires, /readyz reports 503 and the listeners
+    /// keep accepting for `APP_SHUTDOWN_DRAIN_SECONDS`. After that the
+    /// listeners close and in-flight requests get `APP_SHUTDOWN_GRACE_SECONDS`
+    /// to finish before serving is abandoned. Background tasks are cancelled
+    /// with the signal and then get the same grace period. A listener failing
+    /// closes the others. Management listeners follow the same sequence.
+    #[allow(clippy::too_many_lines)] // the whole serve and shutdown sequence
+    async fn run<L>(
+        mut self,
+        listeners: Vec<L>,
+        addresses: Vec<String>,
+        management: Vec<TcpListener>,
+    ) -> anyhow::Result<()>
+    where
+        L: axum::serve::Listener,
+        L::Addr: std::fmt::Debug + Clone + Sync + 'static,
+    {
+        let grace = Duration::from_secs(self.config.app.app_shutdown_grace_seconds);
+        let drain = Duration::from_secs(self.config.app.app_shutdown_drain_seconds);
+        let signal = self
+            .shutdown_signal
+            .take()
+            .unwrap_or_else(|| Box::pin(shutdown_signal(grace.as_secs())));
+        let shutting_down = self.shutting_down.clone();
+        let shutdown = self.shutdown.clone();
 
-fn build_cors_layer(config: &Config) -> CorsLayer {
-    let mut cors = CorsLayer::new()
-        .max_age(Duration::from_secs(config.cors.cors_max_age_seconds));
+        // Background tasks only run while serving, so only then are they checked
+        let tasks = std::mem::take(&mut self.tasks);
+        if !tasks.is_empty() {
+            let checker = tasks.health_checker(&self.config.readiness);
+            self.ready_checkers.push(Arc::new(checker));
+        }
 
-    if config.cors.cors_allow_credentials {
-        cors = cors.allow_credentials(true);
-    }
+        // gRPC services on GRPC_PORT get listeners of their own; otherwise
+        // they stay in the application router
+        #[cfg(feature = "grpc")]
+        let (grpc, grpc_addresses) = match (self.config.grpc.grpc_port, self.grpc_routes.take()) {
+            (Some(port), Some(services)) => {
+                let hosts = owned(&self.config.app.hosts());
+                let listeners = bind_hosts("APP_HOST", hosts, port).await?;
+                let grpc_addresses = listeners
+                    .iter()
+                    .map(|listener| Ok(format!("http://{}", listener.local_addr()?)))
+                    .collect::<std::io::Result<Vec<_>>>()?;
+                let router = crate::grpc::grpc_router(services, &self.config);
+                (Some((router, listeners)), grpc_addresses)
+            }
+            (None, Some(routes)) => {
+                self.grpc_routes = Some(routes);
+                (None, addresses.clone())
+            }
+            (_, None) => (None, Vec::new()),
+        };
+        #[cfg(not(feature = "grpc"))]
+        let grpc_addresses: Vec<String> = Vec::new();
 
-    let methods: Vec<Method> = config
-        .cors
-        .methods()
-        .into_iter()
-        .filter_map(|method| Method::from_bytes(method.as_bytes()).ok())
-        .collect();
-    if !methods.is_empty() {
-        cors = cors.allow_methods(methods);
-    }
+        let config = self.config.clone();
+        let build_info = self.build_info.clone();
+        let management_addresses = management
+            .iter()
+            .map(|listener| Ok(format!("http://{}", listener.local_addr()?)))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let timings = self.startup_timings.clone();
+        let split = !management.is_empty();
+        let (app, management_app) =
+            timings.measure("build_router", || self.build_routers(split))?;
+        timings.finish();
 
-    let headers: Vec<HeaderName> = config
-        .cors
-        .headers()
-        .into_iter()
-        .filter_map(|header| HeaderName::from_bytes(header.as_bytes()).ok())
-        .collect();
-    if !headers.is_empty() {
-        cors = cors.allow_headers(headers);
-    }
+        // Print banner
+        let management_address = management_addresses.join(", ");
+        let grpc_address = grpc_addresses.join(", ");
+        crate::banner::print_banner_at(
+            &config,
+            &build_info,
+            &addresses.join(", "),
+            management_app.as_ref().map(|_| management_address.as_str()),
+            Some(grpc_address.as_str()).filter(|grpc| !grpc.is_empty()),
+            Some(&timings),
+        );
 
-    let origins: Vec<HeaderValue> = config
-        .cors
-        .origins()
-        .into_iter()
-        .filter_map(|origin| HeaderValue::from_str(&origin).ok())
-        .collect();
-    if !origins.is_empty() {
-        cors = cors.allow_origin(origins);
-    }
+        for address in &addresses {
+            tracing::info!("Server listening on {}", address);
+        }
+        for address in &management_addresses {
+            tracing::info!("Management listening on {}", address);
+        }
+        #[cfg(feature = "grpc")]
+        if grpc.is_some() {
+            for address in &grpc_addresses {
+                tracing::info!("gRPC listening on {}", address);
+            }
+        }
+        let tasks = tasks.start();
+        let stop_tasks = tasks.token();
 
-    cors
-}
+        let closing = CancellationToken::new();
+        let close = closing.clone();
+        let drain_then_close = async move {
+            signal.await;
+            shutting_down.store(true, Ordering::SeqCst);
+            shutdown.cancel();
+            stop_tasks.cancel();
+            if !drain.is_zero() {
+                tracing::info!(
+                    "Draining for {}s before closing the listener",
+                    drain.as_secs()
+                );
+                tokio::time::sleep(drain).await;
+            }
+            close.cancel();
+        };
+        let deadline = async move {
+            drain_then_close.await;
+            tokio::time::sleep(grace).await;
+        };
 
-#[derive(Clone, Copy)]
-struct RequestLogLayer {
-    backend: LogBackend,
-}
+        let mut servers = tokio::task::JoinSet::new();
+        for listener in listeners {
+            // Expose the peer address as `ConnectInfo<L::Addr>` (IP filter)
+            let listener = listener.tap_io(|_| {});
+            let server = axum::serve(
+                listener,
+                app.clone().into_make_service_with_connect_info::<L::Addr>(),
+            )
+            .with_graceful_shutdown(closing.clone().cancelled_owned())
+            .into_future();
+            servers.spawn(server);
+        }
+        if let Some(management_app) = management_app {
+            for listener in management {
+                let server = axum::serve(
+                    listener.tap_io(|_| {}),
+                    management_app
+                        .clone()
+                        .into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(closing.clone().cancelled_owned())
+                .into_future();
+                servers.spawn(server);
+            }
+        }
+        #[cfg(feature = "grpc")]
+        if let Some((grpc_app, listeners)) = grpc {
+            for listener in listeners {
+                let server = axum::serve(
+                    listener.tap_io(|_| {}),
+                    grpc_app
+                        .clone()
+                        .into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(closing.clone().cancelled_owned())
+                .into_future();
+                servers.spawn(server);
+            }
+        }
+        // Dropping the set on the deadline aborts the servers
+        let all_servers = async move {
+            let mut result = Ok(());
+            while let Some(joined) = servers.join_next().await {
+                let served = joined
+                    .map_err(anyhow::Error::from)
+                    .and_then(|served| served.map_err(anyhow::Error::from));
+                if result.is_ok() && served.is_err() {
+                    closing.cancel();
+                    result = served;
+                }
+            }
+            result
+        };
+
+        let result = tokio::select! {
+            result = all_servers => result,
+            () = deadline => {
+                tracing::warn!(
+                    "Grace period of {}s elapsed, abandoning in-flight requests",
+                    grace.as_secs()
+                );
+                Ok(())
+            }
+        };
+        tasks.shutdown(grace).await;
+        result?;
+
+        tracing::info!("Server shutdown complete");
 
-impl RequestLogLayer {
-    fn new(backend: LogBackend) -> Self {
-        Self { backend }
+        Ok(())
     }
 }
 
-impl<S> Layer<S> for RequestLogLayer {
-    type Service = RequestLogService<S>;
+fn owned(hosts: &[&str]) -> Vec<String> {
+    hosts.iter().map(ToString::to_string).collect()
+}
 
-    fn layer(&self, inner: S) -> Self::Service {
-        RequestLogService {
-            inner,
-            backend: self.backend,
+/// Bind every address of `hosts` on `port`, resolving hostnames
+async fn bind_hosts(
+    var: &'static str,
+    hosts: Vec<String>,
+    port: u16,
+) -> anyhow::Result<Vec<TcpListener>> {
+    let mut addrs: Vec<SocketAddr> = Vec::new();
+    for host in &hosts {
+        let resolved: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![SocketAddr::new(ip, port)],
+            Err(_) => tokio::net::lookup_host((host.as_str(), port))
+                .await
+                .with_context(|| format!("Failed to resolve {var} entry {host:?}"))?
+                .collect(),
+        };
+        anyhow::ensure!(
+            !resolved.is_empty(),
+            "{var} entry {host:?} resolved to no address"
+        );
+        for addr in resolved {
+            if !addrs.contains(&addr) {
+                addrs.push(addr);
+            }
         }
     }
-}
+    anyhow::ensure!(!addrs.is_empty(), "{var} lists no address to bind");
 
-#[derive(Clone)]
-struct RequestLogService<S> {
-    inner: S,
-    backend: LogBackend,
+    let mut listeners = Vec::with_capacity(addrs.len());
+    for addr in addrs {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("Failed to bind {addr}"))?;
+        listeners.push(listener);
+    }
+    Ok(listeners)
 }
 
-impl<S, B> Service<Request<B>> for RequestLogService<S>
+/// Session manager layer configured from `SESSION_*`
+#[cfg(feature = "session")]
+fn session_layer<St>(
+    config: &crate::config::SessionConfig,
+    store: St,
+) -> tower_sessions::SessionManagerLayer<St>
 where
-    S: Service<Request<B>, Response = axum::response::Response> + Clone + Send + 'static,
-    S::Future: Send + 'static,
-    B: Send + 'static,
+    St: tower_sessions::SessionStore + Clone,
 {
-    type Response = S::Response;
-    type Error = S::Error;
-    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+    use crate::config::SessionSameSite;
+    use tower_sessions::{Expiry, cookie::SameSite, cookie::time};
 
-    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.inner.poll_ready(cx)
-    }
+    let same_site = match config.session_same_site {
+        SessionSameSite::Strict => SameSite::Strict,
+        SessionSameSite::Lax => SameSite::Lax,
+        SessionSameSite::None => SameSite::None,
+    };
+    let ttl =
+        time::Duration::seconds(i64::try_from(config.session_ttl_seconds).unwrap_or(i64::MAX));
 
-    fn call(&mut self, req: Request<B>) -> Self::Future {
-        let mut inner = self.inner.clone();
-        let backend = self.backend;
+    tower_sessions::SessionManagerLayer::new(store)
+        .with_name(config.session_cookie_name.clone())
+        .with_secure(config.session_secure)
+        .with_same_site(same_site)
+        .with_expiry(Expiry::OnInactivity(ttl))
+}
 
-        let method = req.method().clone();
-        let path = req.uri().path().to_string();
-        let request_id = req
-            .headers()
-            .get(&REQUEST_ID_HEADER)
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("")
-            .to_string();
-        let start = Instant::now();
-
-        Box::pin(async move {
-            let response = inner.call(req).await?;
-            let latency_ms = start.elapsed().as_millis() as u64;
-
-            match backend {
-                LogBackend::Tracing => {
-                    tracing::info!(
-                        request_id = %request_id,
-                        method = %method,
-                        path = %path,
-                        status = response.status().as_u16(),
-                        latency_ms = latency_ms,
-                        "request completed"
-                    );
-                }
-                LogBackend::FastLog => {
-                    log::info!(
-                        "request completed request_id={} method={} path={} status={} latency_ms={}",
-                        request_id,
-                        method,
-                        path,
-                        response.status().as_u16(),
-                        latency_ms
-                    );
-                }
-            }
+/// Health and version endpoints at their configured paths (empty path =
+/// not registered)
+fn health_router(config: &Config) -> Router<CoreState> {
+    let paths = &config.core_routes;
+    let mut router = Router::new();
 
-            Ok(response)
-        })
+    if let Some(path) = paths.healthz_path() {
+        router = router.route(&path, axum::routing::get(handlers::healthz));
+    }
+    if let Some(path) = paths.readyz_path() {
+        router = router.route(&path, axum::routing::get(handlers::readyz));
     }
+    if let Some(path) = paths
+        .version_path()
+        .filter(|_| config.features.feature_version_endpoint)
+    {
+        router = router.route(&path, axum::routing::get(handlers::version));
+    }
+
+    router
 }
 
-/// Apply security-related response headers
-fn apply_security_headers(router: Router<CoreState>) -> Router<CoreState> {
+/// Configuration and admin endpoints at their configured paths
+fn admin_router(config: &Config) -> Router<CoreState> {
+    let paths = &config.core_routes;
+    let mut router = Router::new();
+
+    if let Some(path) = paths
+        .configz_path()
+        .filter(|_| config.config_endpoint_enabled())
+    {
+        let effective = handlers::EffectiveConfig(Arc::new(config.to_redacted_json()));
+        router = router.route(
+            &path,
+            axum::routing::get(handlers::configz).layer(axum::Extension(effective)),
+        );
+    }
+    if let Some(path) = paths
+        .log_level_path()
+        .filter(|_| config.features.feature_admin_endpoints)
+    {
+        let mut route = axum::routing::put(admin::set_log_level);
+        if let Some(keys) = AdminKeys::from_config(&config.auth) {
+            route = route.layer(axum::middleware::from_fn(move |req, next| {
+                keys.clone().require(req, next)
+            }));
+        }
+        router = router.route(&path, route);
+    }
+    if let Some(path) = paths
+        .maintenance_path()
+        .filter(|_| config.features.feature_admin_endpoints)
+    {
+        let mut route = axum::routing::put(admin::set_maintenance);
+        if let Some(keys) = AdminKeys::from_config(&config.auth) {
+            route = route.layer(axum::middleware::from_fn(move |req, next| {
+                keys.clone().require(req, next)
+            }));
+        }
+        router = router.route(&path, route);
+    }
+    if let Some(path) = paths
+        .features_path()
+        .filter(|_| config.features.feature_admin_endpoints)
+    {
+        let mut list = axum::routing::get(admin::list_features);
+        let mut set = axum::routing::put(admin::set_feature);
+        if let Some(keys) = AdminKeys::from_config(&config.auth) {
+            let list_keys = keys.clone();
+            list = list.layer(axum::middleware::from_fn(move |req, next| {
+                list_keys.clone().require(req, next)
+            }));
+            set = set.layer(axum::middleware::from_fn(move |req, next| {
+                keys.clone().require(req, next)
+            }));
+        }
+        router = router
+            .route(&path, list)
+            .route(&format!("{path}/{{name}}"), set);
+    }
+
     router
-        .layer(SetResponseHeaderLayer::if_not_present(
-            HeaderName::from_static("x-content-type-options"),
-            HeaderValue::from_static("nosniff"),
-        ))
-        .layer(SetResponseHeaderLayer::if_not_present(
-            HeaderName::from_static("x-frame-options"),
-            HeaderValue::from_static("DENY"),
-        ))
-        .layer(SetResponseHeaderLayer::if_not_present(
-            HeaderName::from_static("x-xss-protection"),
-            HeaderValue::from_static("1; mode=block"),
-        ))
-        .layer(SetResponseHeaderLayer::if_not_present(
-            HeaderName::from_static("referrer-policy"),
-            HeaderValue::from_static("strict-origin-when-cross-origin"),
+}
+
+/// Redirect an unprefixed request to the same path under the base path
+fn redirect_to_base_path(base_path: &str, uri: &Uri) -> Response {
+    let path = uri.path();
+    // Unmatched paths under the prefix would otherwise redirect forever
+    if path == base_path || path.starts_with(&format!("{base_path}/")) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+
+    let path_and_query = uri.path_and_query().map_or(path, |pq| pq.as_str());
+    Redirect::permanent(&format!("{base_path}{path_and_query}")).into_response()
+}
+
+fn apply_middleware(
+    router: Router<CoreState>,
+    config: &Config,
+    features: &FeatureState,
+    user_layers: Vec<UserLayer>,
+    session_layer: Option<UserLayer>,
+) -> anyhow::Result<Router<CoreState>> {
+    // Sensitive headers
+    let sensitive_headers: Vec<HeaderName> = config
+        .sensitive_headers()
+        .iter()
+        .filter_map(|h| h.parse().ok())
+        .collect();
+
+    // Start building middleware stack (applied in reverse order)
+
+    // Envelope injection (inside compression so it sees plain JSON), kept
+    // installed so `response_envelope` can be switched at runtime
+    let router = router.layer(EnvelopeLayer::switched_by(features.clone()));
+
+    // ETags (over the final, uncompressed body)
+    let router = if config.features.feature_etag {
+        router.layer(EtagLayer::new(&config.http))
+    } else {
+        router
+    };
+
+    // Compression (conditional)
+    let router = if config.features.feature_compression {
+        router.layer(build_compression_layer(&config.compression)?)
+    } else {
+        router
+    };
+
+    // Security headers
+    let router = apply_security_headers(router, config)?;
+
+    // Body limit and timeout (`RouteOverrides` on user routers replace them)
+    let router = router.layer(RequestLimitsLayer::new(&config.http));
+
+    // User layers
+    let router = user_layers
+        .into_iter()
+        .fold(router, |router, apply| apply(router));
+
+    // Sessions (outside user layers so they can read the session)
+    let router = match session_layer {
+        Some(apply) => apply(router),
+        None => router,
+    };
+
+    // IP filter (conditional, inside logging so denials are logged)
+    let router = if config.ip_filter.is_enabled() {
+        router.layer(IpFilterLayer::from_config(&config.ip_filter)?)
+    } else {
+        router
+    };
+
+    // Tracing layer (conditional)
+    let router = if config.features.feature_tracing {
+        // Keep spans for tracing, but disable default response logs to avoid duplicates.
+        // Same span as tower-http's default, with `REQUEST_LOG_PATH_REDACTIONS` applied.
+        let redactor = PathRedactor::new(&config.logging);
+        router.layer(
+            TraceLayer::new_for_http()
+                .make_span_with(move |request: &Request| {
+                    tracing::debug_span!(
+                        "request",
+                        method = %request.method(),
+                        uri = %redactor.redact_uri(request.uri()),
+                        version = ?request.version(),
+                        tenant = tracing::field::Empty,
+                    )
+                })
+                .on_request(())
+                .on_response(())
+                .on_failure(()),
+        )
+    } else {
+        router
+    };
+
+    // Trace context (outside tracing, so its server span is the parent)
+    #[cfg(feature = "otel")]
+    let router = if config.features.feature_otel {
+        router.layer(crate::trace_context::TraceContextLayer::new(
+            PathRedactor::new(&config.logging),
         ))
+    } else {
+        router
+    };
+
+    // Request logging, switched by `request_log` at runtime
+    let router = router.layer(RequestLogLayer::new(config).switched_by(features.clone()));
+
+    // Client IP (outside logging and the IP filter, which read it)
+    let router = router.layer(ClientIpLayer::from_config(&config.client_ip)?);
+
+    // Sensitive headers protection
+    let router = router.layer(SetSensitiveRequestHeadersLayer::new(sensitive_headers));
+
+    // Request ID, in scope for current_request_id() below it
+    let router = router.layer(RequestContextLayer::new(&config.request_id));
+
+    // CORS (conditional)
+    let router = if config.features.feature_cors {
+        router.layer(build_cors_layer(&config.cors)?)
+    } else {
+        router
+    };
+
+    // HTTP metrics (outside everything but response time)
+    #[cfg(feature = "otel")]
+    let router = if config.features.feature_otel_metrics {
+        let meter = opentelemetry::global::meter("barrzen-axum");
+        router.layer(crate::http_metrics::HttpMetricsLayer::new(&meter))
+    } else {
+        router
+    };
+
+    // Response time (outermost so it covers every other layer)
+    let router = if config.features.feature_response_time {
+        // The envelope may be switched on later, so always expose the timing
+        router.layer(ResponseTimeLayer::new(true))
+    } else {
+        router
+    };
+
+    Ok(router)
+}
+
+/// Request ID and envelope layers of the management router
+///
+/// Operators call it directly, so the IP filter, CORS, limits and user
+/// layers of the application stack stay off.
+fn apply_management_middleware(
+    router: Router<CoreState>,
+    config: &Config,
+    features: &FeatureState,
+) -> Router<CoreState> {
+    let router = router.layer(EnvelopeLayer::switched_by(features.clone()));
+    router.layer(RequestContextLayer::new(&config.request_id))
+}
+
+/// Apply security-related response headers from `SECURITY_*`
+fn apply_security_headers(
+    router: Router<CoreState>,
+    config: &Config,
+) -> anyhow::Result<Router<CoreState>> {
+    if !config.features.feature_security_headers {
+        return Ok(router);
+    }
+
+    config
+        .security
+        .headers(config.is_production())
+        .into_iter()
+        .try_fold(router, |router, (name, value)| {
+            let value = HeaderValue::from_str(&value)
+                .map_err(|_| anyhow::anyhow!("invalid value for the {name} header: {value:?}"))?;
+            Ok(router.layer(SetResponseHeaderLayer::if_not_present(
+                HeaderName::from_static(name),
+                value,
+            )))
+        })
 }
 
 /// Graceful shutdown signal handler
@@ -392,61 +1427,1572 @@ async fn shutdown_signal(grace_seconds: u64) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{LogCapture, test_config};
     use axum::body::Body;
-    use axum::http::Request;
     use tower::ServiceExt;
 
     #[tokio::test]
     async fn test_app_builder_creates_router() {
-        // Create config using JSON deserialization to avoid manual construction of private fields
-        let config: Config = serde_json::from_str(r#"{
-            "app_name": "test-app",
-            "app_env": "dev",
-            "app_host": "127.0.0.1",
-            "app_port": 0,
-            "app_debug": true,
-            "app_shutdown_grace_seconds": 1,
-            "feature_startup_banner": false,
-            "feature_db": false,
-            "feature_cache": false,
-            "feature_search": false,
-            "feature_broker": false,
-            "feature_openapi": false,
-            "feature_request_log": false,
-            "feature_tracing": false,
-            "feature_otel": false,
-            "feature_cors": false,
-            "feature_session": false,
-            "feature_response_envelope": true,
-            "http_body_limit_bytes": 1024,
-            "http_request_timeout_seconds": 1,
-            "log_level": "info",
-            "log_format": "pretty",
-            "log_include_target": false,
-            "log_include_fileline": false,
-            "request_log_headers_denylist": "",
-            "cache_backend": "none",
-            "cache_ttl_seconds": 60,
-            "cache_max_entries": 1000,
-            "cache_redis_pool_size": 1,
-            "cache_redis_connect_timeout_seconds": 1,
-            "cors_allow_methods": "GET",
-            "cors_allow_headers": "content-type",
-            "cors_allow_credentials": false,
-            "cors_max_age_seconds": 60,
-            "banner_show_secrets": false,
-            "banner_show_env_vars": false
-        }"#).expect("Failed to create test config");
+        let config = test_config();
 
         let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
         let app = AppBuilder::new(config, build).build();
 
         // Test healthz endpoint
         let response = app
-            .oneshot(Request::builder().uri("/healthz").body(Body::empty()).unwrap())
+            .oneshot(
+                Request::builder()
+                    .uri("/healthz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 200);
+    }
+
+    #[test]
+    fn test_session_feature_requires_store() {
+        let mut config = test_config();
+        config.features.feature_session = true;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+
+        let error = AppBuilder::new(config, build).try_build().unwrap_err();
+        assert!(error.to_string().contains("FEATURE_SESSION"));
+    }
+
+    #[cfg(feature = "scheduler")]
+    #[test]
+    fn test_invalid_schedule_fails_build() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let error = AppBuilder::new(test_config(), build)
+            .schedule("report", "0 0 25 * * *", || async { Ok(()) })
+            .try_build()
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("scheduled job report: invalid cron expression")
+        );
+    }
+
+    async fn healthz_headers(config: Config) -> axum::http::HeaderMap {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build).build();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/healthz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        response.headers().clone()
+    }
+
+    #[tokio::test]
+    async fn test_security_headers_defaults() {
+        let headers = healthz_headers(test_config()).await;
+
+        assert_eq!(headers["x-content-type-options"], "nosniff");
+        assert_eq!(headers["x-frame-options"], "DENY");
+        assert_eq!(
+            headers["referrer-policy"],
+            "strict-origin-when-cross-origin"
+        );
+        assert!(!headers.contains_key("content-security-policy"));
+        // Not production and not forced
+        assert!(!headers.contains_key("strict-transport-security"));
+    }
+
+    #[tokio::test]
+    async fn test_security_headers_overridden_and_omitted() {
+        let mut config = test_config();
+        config.security.security_csp = "default-src 'self'".to_string();
+        config.security.security_frame_options = String::new();
+        config.security.security_permissions_policy = "camera=()".to_string();
+        config.security.security_hsts_force = true;
+        let headers = healthz_headers(config).await;
+
+        assert_eq!(headers["content-security-policy"], "default-src 'self'");
+        assert_eq!(headers["permissions-policy"], "camera=()");
+        assert_eq!(headers["strict-transport-security"], "max-age=31536000");
+        assert!(!headers.contains_key("x-frame-options"));
+
+        let mut config = test_config();
+        config.features.feature_security_headers = false;
+        let headers = healthz_headers(config).await;
+        assert!(!headers.contains_key("x-content-type-options"));
+    }
+
+    #[test]
+    fn test_invalid_security_header_value() {
+        let mut config = test_config();
+        config.security.security_csp = "default-src\n'self'".to_string();
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+
+        let error = AppBuilder::new(config, build).try_build().unwrap_err();
+        assert!(error.to_string().contains("content-security-policy"));
+    }
+
+    #[tokio::test]
+    async fn test_merge_accumulates_routers() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build)
+            .merge(Router::new().route("/a", axum::routing::get(|| async { "a" })))
+            .merge(Router::new().route("/b", axum::routing::get(|| async { "b" })))
+            .build();
+
+        assert_eq!(status_of(&app, "/a").await, StatusCode::OK);
+        assert_eq!(status_of(&app, "/b").await, StatusCode::OK);
+        assert_eq!(status_of(&app, "/healthz").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_merge_mixes_stateless_and_stateful() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build)
+            .merge_stateless(Router::new().route("/docs", axum::routing::get(|| async { "docs" })))
+            .merge(Router::new().route("/a", axum::routing::get(|| async { "a" })))
+            .merge_stateless(
+                Router::new().route("/static", axum::routing::get(|| async { "static" })),
+            )
+            .route("/b", axum::routing::get(|| async { "b" }))
+            .build();
+
+        for path in ["/docs", "/static", "/a", "/b"] {
+            assert_eq!(status_of(&app, path).await, StatusCode::OK, "{path}");
+        }
+        assert_eq!(status_of(&app, "/missing").await, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_bare_api_error_gets_request_id() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build)
+            .route(
+                "/missing",
+                axum::routing::get(|| async { crate::ApiError::not_found("No such item") }),
+            )
+            .route(
+                "/explicit",
+                axum::routing::get(|| async {
+                    crate::ApiResponse::ok(1, "Found").with_request_id("set-by-handler")
+                }),
+            )
+            .build();
+
+        let request = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .header(&REQUEST_ID_HEADER, "req-42")
+                .body(Body::empty())
+                .unwrap()
+        };
+        let response = app.clone().oneshot(request("/missing")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[&REQUEST_ID_HEADER], "req-42");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["request_id"], "req-42");
+
+        // An explicit id still wins
+        let response = app.oneshot(request("/explicit")).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["request_id"], "set-by-handler");
+    }
+
+    #[tokio::test]
+    async fn test_plain_json_gets_envelope() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build)
+            .route(
+                "/item",
+                axum::routing::get(|| async { axum::Json(serde_json::json!({ "id": 1 })) }),
+            )
+            .build();
+
+        let response = app
+            .oneshot(Request::builder().uri("/item").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let request_id = response.headers()[&REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(json["status"], "success");
+        assert_eq!(json["data"]["id"], 1);
+        assert_eq!(json["request_id"], request_id.as_str());
+        assert!(json["duration_ms"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn test_custom_request_id_header_reaches_envelope() {
+        let mut config = test_config();
+        config.request_id.request_id_header_name = "x-correlation-id".to_string();
+        config.request_id.request_id_format = crate::config::RequestIdFormat::Ulid;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build)
+            .route(
+                "/item",
+                axum::routing::get(|| async { axum::Json(serde_json::json!({ "id": 1 })) }),
+            )
+            .build();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/item")
+                    .header("x-correlation-id", "corr-7")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()["x-correlation-id"], "corr-7");
+        assert!(!response.headers().contains_key(&REQUEST_ID_HEADER));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["request_id"], "corr-7");
+
+        let response = app
+            .oneshot(Request::builder().uri("/item").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let generated = response.headers()["x-correlation-id"].to_str().unwrap();
+        assert_eq!(generated.len(), 26);
+    }
+
+    #[derive(Clone)]
+    struct MyState {
+        greeting: &'static str,
+    }
+
+    #[tokio::test]
+    async fn test_merge_with_app_state() {
+        async fn hello(
+            axum::extract::State(state): axum::extract::State<MyState>,
+        ) -> crate::ApiResponse<&'static str> {
+            crate::ApiResponse::ok(state.greeting, "Hello")
+        }
+
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build)
+            .with_app_state(MyState { greeting: "hello" })
+            .merge_with_state(Router::new().route("/hello", axum::routing::get(hello)))
+            .build();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/hello")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key(&REQUEST_ID_HEADER));
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["data"], "hello");
+        assert!(json["duration_ms"].is_u64());
+
+        // Core routes keep working alongside the user state
+        assert_eq!(status_of(&app, "/healthz").await, StatusCode::OK);
+    }
+
+    fn response_time_ms(response: &axum::response::Response) -> Option<u64> {
+        response
+            .headers()
+            .get(&crate::response_time::RESPONSE_TIME_HEADER)?
+            .to_str()
+            .ok()?
+            .strip_suffix("ms")?
+            .parse()
+            .ok()
+    }
+
+    #[tokio::test]
+    async fn test_user_layer_and_route() {
+        use axum::http::HeaderValue;
+        use tower_http::set_header::SetResponseHeaderLayer;
+
+        let mut config = test_config();
+        config.features.feature_request_log = true;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let seen_request_id = Arc::new(std::sync::Mutex::new(None::<String>));
+        let seen = seen_request_id.clone();
+
+        let app = AppBuilder::new(config, build)
+            .route("/hello", axum::routing::get(|| async { "hello" }))
+            .layer(SetResponseHeaderLayer::overriding(
+                HeaderName::from_static("x-marker"),
+                HeaderValue::from_static("first"),
+            ))
+            .layer(axum::middleware::from_fn(
+                move |req: Request, next: axum::middleware::Next| {
+                    let seen = seen.clone();
+                    async move {
+                        let id = crate::response::extract_request_id(req.headers());
+                        *seen.lock().unwrap() = id;
+                        let mut response = next.run(req).await;
+                        // Runs after the first layer, so it sees its marker
+                        let marker = response.headers().get("x-marker").cloned();
+                        if let Some(marker) = marker {
+                            response.headers_mut().insert("x-marker-outer", marker);
+                        }
+                        response
+                    }
+                },
+            ))
+            .build();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/version")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
             .await
             .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["x-marker"], "first");
+        assert_eq!(response.headers()["x-marker-outer"], "first");
+        assert!(seen_request_id.lock().unwrap().is_some());
 
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/hello")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
         assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["x-marker"], "first");
+    }
+
+    async fn status_of(app: &Router, uri: &str) -> StatusCode {
+        app.clone()
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_base_path_prefixes_all_routes() {
+        for base_path in ["/api/orders", "/api/orders/", "api/orders"] {
+            let mut config = test_config();
+            config.app.app_base_path = base_path.to_string();
+            let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+            let matched = Arc::new(std::sync::Mutex::new(String::new()));
+            let seen = matched.clone();
+
+            let app = AppBuilder::new(config, build)
+                .route("/users", axum::routing::get(|| async { "users" }))
+                .merge_stateless(
+                    Router::new().route("/docs", axum::routing::get(|| async { "docs" })),
+                )
+                .layer(axum::middleware::from_fn(
+                    move |req: Request, next: axum::middleware::Next| {
+                        let seen = seen.clone();
+                        async move {
+                            if let Some(path) = req.extensions().get::<axum::extract::MatchedPath>()
+                            {
+                                *seen.lock().unwrap() = path.as_str().to_string();
+                            }
+                            next.run(req).await
+                        }
+                    },
+                ))
+                .build();
+
+            assert_eq!(status_of(&app, "/api/orders/healthz").await, StatusCode::OK);
+            assert_eq!(status_of(&app, "/api/orders/users").await, StatusCode::OK);
+            assert_eq!(status_of(&app, "/api/orders/docs").await, StatusCode::OK);
+            assert_eq!(status_of(&app, "/healthz").await, StatusCode::NOT_FOUND);
+            assert_eq!(status_of(&app, "/users").await, StatusCode::NOT_FOUND);
+
+            status_of(&app, "/api/orders/users").await;
+            assert_eq!(*matched.lock().unwrap(), "/api/orders/users");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_base_path_redirect() {
+        let mut config = test_config();
+        config.app.app_base_path = "/api/orders".to_string();
+        config.app.app_base_path_redirect = true;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build).build();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/healthz?x=1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()["location"], "/api/orders/healthz?x=1");
+
+        assert_eq!(
+            status_of(&app, "/api/orders/missing").await,
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_core_paths_renamed_and_disabled() {
+        let mut config = test_config();
+        config.core_routes.core_healthz_path = "/-/health".to_string();
+        config.core_routes.core_readyz_path = String::new();
+        config.features.feature_version_endpoint = false;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build)
+            .route("/readyz", axum::routing::get(|| async { "user readyz" }))
+            .build();
+
+        assert_eq!(status_of(&app, "/-/health").await, StatusCode::OK);
+        assert_eq!(status_of(&app, "/healthz").await, StatusCode::NOT_FOUND);
+        assert_eq!(status_of(&app, "/version").await, StatusCode::NOT_FOUND);
+
+        // A disabled core path falls through to normal routing
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/readyz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"user readyz");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_serve_fails_fast_when_dependencies_stay_unready() {
+        struct Down;
+
+        #[async_trait::async_trait]
+        impl ReadyChecker for Down {
+            async fn ready_checks(&self) -> Vec<crate::HealthCheck> {
+                vec![
+                    crate::HealthCheck::ok("cache"),
+                    crate::HealthCheck::fail("payments", "HTTP 500"),
+                ]
+            }
+        }
+
+        let mut config = test_config();
+        config.startup.startup_require_ready = true;
+        config.startup.startup_ready_timeout_seconds = 2;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let serve = AppBuilder::new(config, build)
+            .with_ready_checker(Down)
+            .serve();
+        let error = Box::pin(serve).await.unwrap_err();
+        assert!(error.to_string().contains("payments (HTTP 500)"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_ready_checkers_are_combined() {
+        struct Fixed(&'static str, bool);
+
+        #[async_trait::async_trait]
+        impl ReadyChecker for Fixed {
+            async fn ready_checks(&self) -> Vec<crate::HealthCheck> {
+                vec![if self.1 {
+                    crate::HealthCheck::ok(self.0)
+                } else {
+                    crate::HealthCheck::fail(self.0, "HTTP 500")
+                }]
+            }
+        }
+
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build)
+            .with_ready_checker(Fixed("database", true))
+            .with_ready_checker(Fixed("payments", false))
+            .build();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/readyz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["data"]["status"], "unready");
+        let names: Vec<_> = json["data"]["checks"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|check| check["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["database", "payments"]);
+    }
+
+    #[tokio::test]
+    async fn test_configz_endpoint() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build.clone()).build();
+        assert_eq!(status_of(&app, "/configz").await, StatusCode::NOT_FOUND);
+
+        let mut config = test_config();
+        config.features.feature_config_endpoint = true;
+        config.features.feature_response_envelope = false;
+        config.auth.auth_jwt_secret = Some("jwt-signing-secret".to_string());
+
+        let mut prod = config.clone();
+        prod.app.app_env = crate::config::Environment::Prod;
+        let app = AppBuilder::new(prod, build.clone()).build();
+        assert_eq!(status_of(&app, "/configz").await, StatusCode::NOT_FOUND);
+
+        let app = AppBuilder::new(config, build).build();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/configz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["app_name"], "test-app");
+        assert_eq!(json["feature_config_endpoint"], true);
+        assert_eq!(json["auth_jwt_secret"], "****");
+    }
+
+    #[tokio::test]
+    async fn test_version_reports_compile_time_build_info() {
+        let app = AppBuilder::new(test_config(), crate::build_info!()).build();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/version")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let data = &json["data"];
+        assert_eq!(data["name"], "barrzen-axum-core");
+        assert_eq!(data["version"], env!("CARGO_PKG_VERSION"));
+        assert!(data["target"].is_string());
+        assert!(data["profile"].is_string());
+
+        // Optional fields are left out when unknown
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build).build();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/version")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&body).contains("git_branch"));
+    }
+
+    /// Accepts any filter made of letters, `=` and `,`
+    #[derive(Clone, Default)]
+    struct FakeLogControl(Arc<std::sync::Mutex<String>>);
+
+    impl LogLevelControl for FakeLogControl {
+        fn current_filter(&self) -> String {
+            self.0.lock().unwrap().clone()
+        }
+
+        fn set_filter(&self, filter: &str) -> Result<(), String> {
+            if !filter
+                .chars()
+                .all(|c| c.is_ascii_alphabetic() || "=,".contains(c))
+            {
+                return Err(format!("invalid filter {filter:?}"));
+            }
+            *self.0.lock().unwrap() = filter.to_string();
+            Ok(())
+        }
+    }
+
+    fn put_log_level(filter: &str, api_key: Option<&str>) -> Request<Body> {
+        let mut request = Request::builder()
+            .method("PUT")
+            .uri("/admin/log-level")
+            .header("content-type", "application/json");
+        if let Some(key) = api_key {
+            request = request.header("x-api-key", key);
+        }
+        let body = serde_json::json!({ "filter": filter }).to_string();
+        request.body(Body::from(body)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_admin_log_level_endpoint() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let control = FakeLogControl::default();
+        *control.0.lock().unwrap() = "info".to_string();
+
+        // Not served unless enabled
+        let app = AppBuilder::new(test_config(), build.clone())
+            .with_log_control(control.clone())
+            .build();
+        let response = app.oneshot(put_log_level("debug", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let mut config = test_config();
+        config.features.feature_admin_endpoints = true;
+        let app = AppBuilder::new(config.clone(), build.clone())
+            .with_log_control(control.clone())
+            .build();
+
+        let response = app
+            .clone()
+            .oneshot(put_log_level("debug,hyper=warn", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["data"]["filter"], "debug,hyper=warn");
+
+        // Invalid filters are rejected and leave the current one in place
+        let response = app.oneshot(put_log_level("debug[", None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["status"], "error");
+        assert_eq!(json["message"], "Invalid log filter");
+        assert_eq!(control.current_filter(), "debug,hyper=warn");
+
+        // API keys protect the endpoint when configured
+        config.auth.auth_api_keys = Some("ops:s3cret".to_string());
+        let app = AppBuilder::new(config, build)
+            .with_log_control(control.clone())
+            .build();
+        let response = app
+            .clone()
+            .oneshot(put_log_level("warn", None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = app
+            .clone()
+            .oneshot(put_log_level("warn", Some("wrong")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(control.current_filter(), "debug,hyper=warn");
+
+        let response = app
+            .oneshot(put_log_level("warn", Some("s3cret")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(control.current_filter(), "warn");
+    }
+
+    fn put_maintenance(body: &serde_json::Value) -> Request<Body> {
+        Request::builder()
+            .method("PUT")
+            .uri("/admin/maintenance")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_compression_can_be_disabled() {
+        let large = || async { axum::Json(vec!["item"; 1000]) };
+        for (enabled, expected) in [(true, Some("gzip")), (false, None)] {
+            let mut config = test_config();
+            config.features.feature_compression = enabled;
+            let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+            let app = AppBuilder::new(config, build)
+                .route("/items", axum::routing::get(large))
+                .build();
+
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/items")
+                        .header("accept-encoding", "gzip")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            let encoding = response.headers().get("content-encoding");
+            assert_eq!(encoding.map(|v| v.to_str().unwrap()), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_etag_not_modified_keeps_request_id() {
+        let mut config = test_config();
+        config.features.feature_etag = true;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build)
+            .route(
+                "/items",
+                axum::routing::get(|| async { axum::Json(serde_json::json!([1, 2, 3])) }),
+            )
+            .build();
+
+        let first = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/items")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        let etag = first.headers()["etag"].clone();
+
+        let second = app
+            .oneshot(
+                Request::builder()
+                    .uri("/items")
+                    .header("if-none-match", etag.clone())
+                    .header("x-request-id", "req-304")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(second.headers()["etag"], etag);
+        assert_eq!(second.headers()["x-request-id"], "req-304");
+    }
+
+    #[tokio::test]
+    async fn test_route_overrides_beat_global_limits() {
+        async fn upload(body: axum::body::Bytes) -> String {
+            body.len().to_string()
+        }
+        async fn slow() -> &'static str {
+            tokio::time::sleep(Duration::from_millis(1200)).await;
+            "done"
+        }
+
+        // test_config: 1KB body limit, 1s timeout
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build)
+            .merge(
+                Router::new()
+                    .route("/upload", axum::routing::post(upload))
+                    .route("/upload/slow", axum::routing::get(slow))
+                    .layer(
+                        crate::RouteOverrides::new()
+                            .timeout(Duration::from_secs(5))
+                            .body_limit(10 * crate::MB),
+                    ),
+            )
+            .route("/notes", axum::routing::post(upload))
+            .route("/slow", axum::routing::get(slow))
+            .build();
+        let post = |path: &str| {
+            Request::post(path)
+                .body(Body::from(vec![b'x'; 5 * crate::MB]))
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(post("/upload")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, (5 * crate::MB).to_string());
+        let response = app.clone().oneshot(post("/notes")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let (overridden, global) =
+            tokio::join!(status_of(&app, "/upload/slow"), status_of(&app, "/slow"),);
+        assert_eq!(overridden, StatusCode::OK);
+        assert_eq!(global, StatusCode::REQUEST_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_load_shed_rejects_over_limit_but_not_probes() {
+        let mut config = test_config();
+        config.features.feature_load_shed = true;
+        config.http.http_max_concurrent_requests = 2;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let release = Arc::new(tokio::sync::Notify::new());
+        let (entered_tx, mut entered) = tokio::sync::mpsc::unbounded_channel();
+        let slow = {
+            let release = release.clone();
+            move || {
+                let release = release.clone();
+                let entered_tx = entered_tx.clone();
+                async move {
+                    let released = release.notified();
+                    let _ = entered_tx.send(());
+                    released.await;
+                    "done"
+                }
+            }
+        };
+        let app = AppBuilder::new(config, build)
+            .route("/slow", axum::routing::get(slow))
+            .route("/fast", axum::routing::get(|| async { "fast" }))
+            .merge_stateless(
+                Router::new().route("/stateless", axum::routing::get(|| async { "stateless" })),
+            )
+            .build();
+
+        // Saturate the limit with two slow requests
+        let held: Vec<_> = (0..2)
+            .map(|_| tokio::spawn(status_of_owned(app.clone(), "/slow")))
+            .collect();
+        for _ in 0..2 {
+            entered.recv().await.unwrap();
+        }
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/fast")
+                    .header("x-request-id", "shed-1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()["retry-after"], "1");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error_code"], "overloaded");
+        assert_eq!(json["request_id"], "shed-1");
+        assert_eq!(
+            status_of(&app, "/stateless").await,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        assert_eq!(status_of(&app, "/healthz").await, StatusCode::OK);
+        assert_eq!(status_of(&app, "/readyz").await, StatusCode::OK);
+
+        release.notify_waiters();
+        for request in held {
+            assert_eq!(request.await.unwrap(), StatusCode::OK);
+        }
+        assert_eq!(status_of(&app, "/fast").await, StatusCode::OK);
+        assert_eq!(status_of(&app, "/stateless").await, StatusCode::OK);
+    }
+
+    async fn status_of_owned(app: Router, uri: &'static str) -> StatusCode {
+        status_of(&app, uri).await
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_mode_toggles_user_routes() {
+        let mut config = test_config();
+        config.features.feature_admin_endpoints = true;
+        config.features.feature_maintenance_mode = true;
+        config.maintenance.maintenance_retry_after_seconds = 120;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build)
+            .route("/orders", axum::routing::get(|| async { "orders" }))
+            .build();
+
+        // Starts enabled from FEATURE_MAINTENANCE_MODE; core routes stay up
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/orders")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()["retry-after"], "120");
+        assert_eq!(status_of(&app, "/healthz").await, StatusCode::OK);
+        assert_eq!(status_of(&app, "/version").await, StatusCode::OK);
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/readyz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["data"]["status"], "degraded");
+        assert_eq!(json["data"]["checks"][1]["name"], "maintenance");
+        assert_eq!(json["data"]["checks"][1]["status"], "warn");
+
+        let response = app
+            .clone()
+            .oneshot(put_maintenance(&serde_json::json!({ "enabled": false })))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(status_of(&app, "/orders").await, StatusCode::OK);
+
+        app.clone()
+            .oneshot(put_maintenance(&serde_json::json!({
+                "enabled": true,
+                "message": "Migrating the orders table"
+            })))
+            .await
+            .unwrap();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/orders")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["message"], "Migrating the orders table");
+        assert_eq!(json["error_code"], "maintenance");
+    }
+
+    fn put_feature(name: &str, enabled: bool) -> Request<Body> {
+        Request::builder()
+            .method("PUT")
+            .uri(format!("/admin/features/{name}"))
+            .header("content-type", "application/json")
+            .body(Body::from(
+                serde_json::json!({ "enabled": enabled }).to_string(),
+            ))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_feature_flags_switch_request_log() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+        let mut config = test_config();
+        config.features.feature_admin_endpoints = true;
+        config.features.feature_request_log = true;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build)
+            .route("/orders", axum::routing::get(|| async { "orders" }))
+            .build();
+        let logged = || {
+            capture
+                .lines()
+                .iter()
+                .filter(|line| line.contains("request completed") && line.contains("path=/orders"))
+                .count()
+        };
+
+        assert_eq!(status_of(&app, "/orders").await, StatusCode::OK);
+        assert_eq!(logged(), 1);
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/admin/features")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let flags = json["data"]["flags"].as_array().unwrap();
+        let flag = |name: &str| flags.iter().find(|flag| flag["name"] == name).unwrap();
+        assert_eq!(flag("request_log")["enabled"], true);
+        assert_eq!(flag("request_log")["read_only"], false);
+        assert_eq!(flag("db")["read_only"], true);
+
+        let response = app
+            .clone()
+            .oneshot(put_feature("request_log", false))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(status_of(&app, "/orders").await, StatusCode::OK);
+        assert_eq!(logged(), 1);
+
+        // Flags read at startup and unknown names are rejected
+        let response = app.clone().oneshot(put_feature("db", true)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = app.oneshot(put_feature("nope", true)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_of_application_routes() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+        let mut config = test_config();
+        config.features.feature_tenant = true;
+        config.features.feature_request_log = true;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build)
+            .route(
+                "/orders",
+                axum::routing::get(|| async { crate::current_tenant().unwrap().0 }),
+            )
+            .build();
+
+        let request = Request::builder()
+            .uri("/orders")
+            .header("x-tenant-id", "acme")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"acme");
+        assert!(
+            capture
+                .lines()
+                .iter()
+                .any(|line| line.contains("path=/orders") && line.contains("tenant=\"acme\"")),
+            "{:?}",
+            capture.lines()
+        );
+
+        // Application routes need a tenant, probes do not
+        assert_eq!(status_of(&app, "/orders").await, StatusCode::BAD_REQUEST);
+        assert_eq!(status_of(&app, "/healthz").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_root_route_without_base_path() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build)
+            .route("/", axum::routing::get(|| async { "root" }))
+            .build();
+
+        assert_eq!(status_of(&app, "/").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_response_time_header_and_duration() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build).build();
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/healthz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(response_time_ms(&response).is_some());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(json["duration_ms"].is_u64());
+
+        // Fallback responses are timed too
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/missing")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 404);
+        assert!(response_time_ms(&response).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_response_time_disabled() {
+        let mut config = test_config();
+        config.features.feature_response_time = false;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build).build();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/healthz")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(response_time_ms(&response).is_none());
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(json.get("duration_ms").is_none());
+    }
+
+    async fn http_get<S>(mut stream: S, path: &str) -> String
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let request =
+            format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    fn assert_send<T: Send>(_: T) {}
+
+    #[test]
+    fn test_serve_futures_are_send() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = || AppBuilder::new(test_config(), build.clone());
+        assert_send(app().serve());
+        assert_send(app().serve_with_shutdown(async {}));
+        assert_send(app().bind());
+        assert_send(app().bind_management());
+    }
+
+    #[tokio::test]
+    async fn test_serve_with_listener() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let server =
+            tokio::spawn(AppBuilder::new(test_config(), build).serve_with_listener(listener));
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let response = http_get(stream, "/healthz").await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_serve_binds_every_host() {
+        // Hosts without IPv6 (some CI containers) cannot bind ::1
+        if std::net::TcpListener::bind("[::1]:0").is_err() {
+            return;
+        }
+        let mut config = test_config();
+        config.app.app_host = "127.0.0.1,::1".to_string();
+        config.app.app_port = 0;
+        let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build).with_shutdown_signal(async move {
+            let _ = signal.await;
+        });
+
+        let listeners = app.bind().await.unwrap();
+        let addrs: Vec<_> = listeners.iter().map(|l| l.local_addr().unwrap()).collect();
+        assert_eq!(addrs.len(), 2);
+        assert!(addrs[0].is_ipv4() && addrs[1].is_ipv6(), "{addrs:?}");
+        let server = tokio::spawn(app.serve_with_listeners(listeners));
+
+        for addr in addrs {
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let response = http_get(stream, "/healthz").await;
+            assert!(response.starts_with("HTTP/1.1 200"), "{addr}: {response}");
+        }
+
+        trigger.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_management_port_serves_core_endpoints() {
+        let mut config = test_config();
+        config.management.management_host = "127.0.0.1".to_string();
+        config.management.management_port = Some(0);
+        let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let metrics = Router::new().route("/metrics", axum::routing::get(|| async { "up 1" }));
+        let app = AppBuilder::new(config, build)
+            .route("/orders", axum::routing::get(|| async { "orders" }))
+            .merge_management(metrics)
+            .with_shutdown_signal(async move {
+                let _ = signal.await;
+            });
+
+        let listeners = app.bind().await.unwrap();
+        let management = app.bind_management().await.unwrap();
+        let app_addr = listeners[0].local_addr().unwrap();
+        let management_addr = management[0].local_addr().unwrap();
+        let server = tokio::spawn(app.serve_with_management(listeners, management));
+
+        for (addr, path, status) in [
+            (app_addr, "/orders", "200"),
+            (app_addr, "/metrics", "404"),
+            (app_addr, "/healthz", "404"),
+            (management_addr, "/metrics", "200"),
+            (management_addr, "/healthz", "200"),
+            (management_addr, "/readyz", "200"),
+            (management_addr, "/orders", "404"),
+        ] {
+            let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let response = http_get(stream, path).await;
+            assert!(
+                response.starts_with(&format!("HTTP/1.1 {status}")),
+                "{addr}{path}: {response}"
+            );
+        }
+
+        trigger.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_management_not_exclusive_keeps_health_on_app() {
+        let mut config = test_config();
+        config.management.management_port = Some(0);
+        config.management.management_exclusive = false;
+        config.features.feature_admin_endpoints = true;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let (app, management) = AppBuilder::new(config, build)
+            .try_build_with_management()
+            .unwrap();
+
+        let get = |path: &str| Request::get(path).body(Body::empty()).unwrap();
+        let put = |path: &str| Request::put(path).body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(get("/healthz")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app.oneshot(put("/admin/maintenance")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = management.oneshot(get("/healthz")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[cfg(feature = "grpc")]
+    async fn grpc_health_check(
+        addr: SocketAddr,
+        request_id: &str,
+    ) -> Result<tonic::Response<tonic_health::pb::HealthCheckResponse>, tonic::Status> {
+        use tonic_health::pb::{HealthCheckRequest, health_client::HealthClient};
+
+        let channel = tonic::transport::Endpoint::from_shared(format!("http://{addr}"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let mut client = HealthClient::new(channel);
+        let mut request = tonic::Request::new(HealthCheckRequest {
+            service: String::new(),
+        });
+        request
+            .metadata_mut()
+            .insert("x-request-id", request_id.parse().unwrap());
+        client.check(request).await
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_grpc_multiplexed_with_http() {
+        use tonic_health::pb::health_check_response::ServingStatus;
+
+        let (_reporter, health) = tonic_health::server::health_reporter();
+        let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(test_config(), build)
+            .add_grpc_service(health)
+            .with_shutdown_signal(async move {
+                let _ = signal.await;
+            });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(app.serve_with_listener(listener));
+
+        let response = grpc_health_check(addr, "grpc-1").await.unwrap();
+        assert_eq!(response.metadata().get("x-request-id").unwrap(), "grpc-1");
+        assert_eq!(response.into_inner().status, ServingStatus::Serving as i32);
+
+        // Plain HTTP on the same port still reaches the router
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let response = http_get(stream, "/healthz").await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+        trigger.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[cfg(feature = "grpc")]
+    #[tokio::test]
+    async fn test_grpc_port_serves_grpc_only() {
+        // A free port for GRPC_PORT, which `serve` binds itself
+        let grpc_port = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut config = test_config();
+        config.app.app_host = "127.0.0.1".to_string();
+        config.grpc.grpc_port = Some(grpc_port);
+        let (_reporter, health) = tonic_health::server::health_reporter();
+        let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build)
+            .add_grpc_service(health)
+            .with_shutdown_signal(async move {
+                let _ = signal.await;
+            });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let app_addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(app.serve_with_listener(listener));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let grpc_addr = SocketAddr::from(([127, 0, 0, 1], grpc_port));
+        assert!(grpc_health_check(grpc_addr, "grpc-2").await.is_ok());
+        assert!(grpc_health_check(app_addr, "grpc-3").await.is_err());
+
+        let stream = tokio::net::TcpStream::connect(app_addr).await.unwrap();
+        let response = http_get(stream, "/healthz").await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+        trigger.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_custom_shutdown_flips_readyz() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut config = test_config();
+        config.app.app_shutdown_drain_seconds = 1;
+        let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let server = tokio::spawn(
+            AppBuilder::new(config, build)
+                .with_shutdown_signal(async move {
+                    let _ = signal.await;
+                })
+                .serve_with_listener(listener),
+        );
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        assert!(
+            http_get(stream, "/readyz")
+                .await
+                .starts_with("HTTP/1.1 200")
+        );
+
+        trigger.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        // Still accepting during the drain window, but no longer ready
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let response = http_get(stream, "/readyz").await;
+        assert!(response.starts_with("HTTP/1.1 503"));
+        assert!(response.contains("shutting down"));
+
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_background_tasks_stop_with_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+        let (cancelled_tx, cancelled_rx) = tokio::sync::mpsc::unbounded_channel::<&str>();
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let server = tokio::spawn(
+            AppBuilder::new(test_config(), build)
+                .spawn_task("consumer", move |shutdown| {
+                    let cancelled = cancelled_tx.clone();
+                    async move {
+                        shutdown.cancelled().await;
+                        let _ = cancelled.send("consumer");
+                    }
+                })
+                .with_shutdown_signal(async move {
+                    let _ = signal.await;
+                })
+                .serve_with_listener(listener),
+        );
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let response = http_get(stream, "/readyz").await;
+        assert!(
+            response.contains(r#""name":"task:consumer","status":"ok""#),
+            "{response}"
+        );
+
+        trigger.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let mut cancelled = cancelled_rx;
+        assert_eq!(cancelled.try_recv(), Ok("consumer"));
+    }
+
+    #[tokio::test]
+    async fn test_grace_period_is_hard_deadline() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+        let slow = Router::new().route(
+            "/slow",
+            axum::routing::get(|| async {
+                tokio::time::sleep(Duration::from_secs(30)).await;
+                "done"
+            }),
+        );
+        // Keep the request timeout out of the way of the grace period
+        let mut config = test_config();
+        config.http.http_request_timeout_seconds = 60;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let server = tokio::spawn(
+            AppBuilder::new(config, build)
+                .merge(slow)
+                .with_shutdown_signal(async move {
+                    let _ = signal.await;
+                })
+                .serve_with_listener(listener),
+        );
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let in_flight = tokio::spawn(http_get(stream, "/slow"));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        trigger.send(()).unwrap();
+        let started = std::time::Instant::now();
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        in_flight.abort();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serve_unix_socket() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.sock");
+        // A stale socket from a previous run must not prevent binding
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let mut config = test_config();
+        config.app.app_uds_path = Some(path.to_string_lossy().into_owned());
+        config.app.app_uds_mode = "600".to_string();
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let server = tokio::spawn(AppBuilder::new(config, build).serve());
+
+        let stream = loop {
+            if let Ok(stream) = tokio::net::UnixStream::connect(&path).await {
+                break stream;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let response = http_get(stream, "/healthz").await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+
+        server.abort();
     }
 }
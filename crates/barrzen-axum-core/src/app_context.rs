@@ -0,0 +1,105 @@
+//! Config, shutdown token and shared counters of the app handling the request
+//!
+//! Responses such as [`ApiSse`](crate::sse::ApiSse) are built without access
+//! to the state, yet need the configured defaults and must end when the app
+//! shuts down. The built app runs every request inside a scope holding both.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use tower::{Layer, Service};
+
+use crate::{audit::AuditLogger, config::Config, tasks::CancellationToken};
+
+tokio::task_local! {
+    static APP_CONTEXT: AppContext;
+}
+
+/// What the scope of a request holds
+#[derive(Clone)]
+pub(crate) struct AppContext {
+    pub(crate) config: Arc<Config>,
+    /// Cancelled when the shutdown signal fires, before the drain period
+    pub(crate) shutdown: CancellationToken,
+    /// Audit logger handed to the `AuditLogger` extractor
+    pub(crate) audit: AuditLogger,
+    /// Open [`WsSession`](crate::ws::WsSession) connections of the app
+    #[cfg(feature = "ws")]
+    pub(crate) ws_connections: crate::ws::WsConnections,
+}
+
+impl AppContext {
+    pub(crate) fn new(config: Arc<Config>, shutdown: CancellationToken) -> Self {
+        Self {
+            config,
+            shutdown,
+            audit: AuditLogger::new(),
+            #[cfg(feature = "ws")]
+            ws_connections: crate::ws::WsConnections::default(),
+        }
+    }
+
+    /// Context of the request being handled, `None` outside the built app
+    pub(crate) fn current() -> Option<Self> {
+        APP_CONTEXT.try_with(Clone::clone).ok()
+    }
+
+    /// Run `future` inside this context
+    #[cfg(test)]
+    pub(crate) async fn scope<F: Future>(self, future: F) -> F::Output {
+        APP_CONTEXT.scope(self, future).await
+    }
+}
+
+/// Layer running each request inside an [`AppContext`] scope
+#[derive(Clone)]
+pub(crate) struct AppContextLayer {
+    context: AppContext,
+}
+
+impl AppContextLayer {
+    pub(crate) fn new(context: AppContext) -> Self {
+        Self { context }
+    }
+}
+
+impl<S> Layer<S> for AppContextLayer {
+    type Service = AppContextService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AppContextService {
+            inner,
+            context: self.context.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct AppContextService<S> {
+    inner: S,
+    context: AppContext,
+}
+
+impl<S, Req> Service<Req> for AppContextService<S>
+where
+    S: Service<Req>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let context = self.context.clone();
+        let future = APP_CONTEXT.sync_scope(context.clone(), || self.inner.call(req));
+        Box::pin(APP_CONTEXT.scope(context, future))
+    }
+}
@@ -0,0 +1,499 @@
+//! Audit log of security-relevant actions
+//!
+//! [`AuditLogger`] records [`AuditEvent`]s (login, permission change, data
+//! export) apart from the request log: as JSON on the `audit` tracing target,
+//! which `AUDIT_LOG_FORCE` keeps at `info` whatever the log filter says, and
+//! in the [`AuditSink`]s, such as the `AUDIT_LOG_FILE` file.
+//!
+//! Each [`AuditRecord`] carries a sequence number and the hash of the
+//! previous record, so a removed or edited record breaks the chain
+//! ([`AuditRecord::verify_chain`]). Chains start over when the process
+//! starts.
+
+use std::{
+    fmt::Write as _,
+    net::IpAddr,
+    path::PathBuf,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use axum::{extract::FromRequestParts, http::request::Parts};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use tokio::{
+    fs::{File, OpenOptions},
+    io::AsyncWriteExt,
+    sync::Mutex,
+};
+
+use crate::{
+    app_context::AppContext,
+    client_ip::ClientIp,
+    config::AuditConfig,
+    request_context::current_request_id,
+    response::{ApiError, extract_request_id},
+};
+
+/// Tracing target of the audit records
+pub const AUDIT_TARGET: &str = "audit";
+
+/// `version` of the [`AuditRecord`] schema
+pub const AUDIT_SCHEMA_VERSION: u32 = 1;
+
+/// `prev_hash` of the first record of a chain
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// How the audited action ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+    /// Refused by an authorization check
+    Denied,
+}
+
+/// An action to audit
+///
+/// ```rust,ignore
+/// audit
+///     .record(
+///         AuditEvent::new(user.id, "permission.grant", AuditOutcome::Success)
+///             .with_resource(format!("user:{target}"))
+///             .with_metadata("role", "admin"),
+///     )
+///     .await?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    /// Who acted, e.g. a user id or `system`
+    pub actor: String,
+    /// What was done, e.g. `login` or `data.export`
+    pub action: String,
+    /// What it was done to, e.g. `user:42`
+    pub resource: Option<String>,
+    pub outcome: AuditOutcome,
+    /// Extra details; keep secrets out
+    pub metadata: Map<String, Value>,
+}
+
+impl AuditEvent {
+    #[must_use]
+    pub fn new(actor: impl Into<String>, action: impl Into<String>, outcome: AuditOutcome) -> Self {
+        Self {
+            actor: actor.into(),
+            action: action.into(),
+            resource: None,
+            outcome,
+            metadata: Map::new(),
+        }
+    }
+
+    /// Set the resource acted on
+    #[must_use]
+    pub fn with_resource(mut self, resource: impl Into<String>) -> Self {
+        self.resource = Some(resource.into());
+        self
+    }
+
+    /// Add a metadata entry (`null` if `value` cannot be serialized)
+    #[must_use]
+    pub fn with_metadata(mut self, key: impl Into<String>, value: impl Serialize) -> Self {
+        let value = serde_json::to_value(value).unwrap_or(Value::Null);
+        self.metadata.insert(key.into(), value);
+        self
+    }
+}
+
+/// A recorded [`AuditEvent`], in the stable schema written to every sink
+///
+/// Every field is always present; unknown values are `null`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// [`AUDIT_SCHEMA_VERSION`]
+    pub version: u32,
+    /// 1 for the first record of the process, then one more per record
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub request_id: Option<String>,
+    pub client_ip: Option<IpAddr>,
+    pub actor: String,
+    pub action: String,
+    pub resource: Option<String>,
+    pub outcome: AuditOutcome,
+    pub metadata: Map<String, Value>,
+    /// `hash` of the previous record, zeros for the first
+    pub prev_hash: String,
+    /// SHA-256 (hex) of this record serialized with an empty `hash`
+    pub hash: String,
+}
+
+impl AuditRecord {
+    /// Hash of the record as stored in `hash`
+    #[must_use]
+    pub fn compute_hash(&self) -> String {
+        let unhashed = Self {
+            hash: String::new(),
+            ..self.clone()
+        };
+        let json = serde_json::to_vec(&unhashed).unwrap_or_default();
+        Sha256::digest(json)
+            .iter()
+            .fold(String::with_capacity(64), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            })
+    }
+
+    /// Check that `records` form an unbroken chain
+    ///
+    /// # Errors
+    /// Returns the `seq` of the first record that was altered or does not
+    /// follow the one before it.
+    pub fn verify_chain(records: &[Self]) -> Result<(), u64> {
+        let mut previous: Option<&Self> = None;
+        for record in records {
+            let follows = previous.is_none_or(|previous| {
+                record.seq == previous.seq + 1 && record.prev_hash == previous.hash
+            });
+            if !follows || record.hash != record.compute_hash() {
+                return Err(record.seq);
+            }
+            previous = Some(record);
+        }
+        Ok(())
+    }
+}
+
+/// Destination of audit records besides the `audit` tracing target
+#[async_trait::async_trait]
+pub trait AuditSink: Send + Sync {
+    /// Store one record; `line` is its JSON
+    async fn write(&self, record: &AuditRecord, line: &str) -> anyhow::Result<()>;
+}
+
+/// Appends records as JSON lines to a file (`AUDIT_LOG_FILE`)
+///
+/// The file is opened on the first record and created if missing.
+pub struct FileAuditSink {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl FileAuditSink {
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditSink for FileAuditSink {
+    async fn write(&self, _record: &AuditRecord, line: &str) -> anyhow::Result<()> {
+        let mut file = self.file.lock().await;
+        if file.is_none() {
+            let opened = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await?;
+            *file = Some(opened);
+        }
+        if let Some(file) = file.as_mut() {
+            file.write_all(format!("{line}\n").as_bytes()).await?;
+            file.flush().await?;
+        }
+        Ok(())
+    }
+}
+
+/// A sink failed to store a record; it was still logged on the `audit` target
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to store audit record {seq}: {message}")]
+pub struct AuditError {
+    pub seq: u64,
+    pub message: String,
+}
+
+impl From<AuditError> for ApiError {
+    fn from(error: AuditError) -> Self {
+        tracing::error!(seq = error.seq, error = %error.message, "audit sink failed");
+        Self::internal("Failed to record the audit event").with_code("audit_failed")
+    }
+}
+
+/// Position in the hash chain
+struct Chain {
+    seq: u64,
+    last_hash: String,
+}
+
+struct Shared {
+    chain: Mutex<Chain>,
+    sinks: RwLock<Vec<Arc<dyn AuditSink>>>,
+}
+
+/// Records [`AuditEvent`]s; clones share the chain and the sinks
+///
+/// Take it as an extractor in handlers, which fills `client_ip` too, or from
+/// [`CoreState::audit`](crate::CoreState::audit) and
+/// [`AppBuilder::audit_logger`](crate::AppBuilder::audit_logger).
+#[derive(Clone)]
+pub struct AuditLogger {
+    shared: Arc<Shared>,
+    request_id: Option<String>,
+    client_ip: Option<IpAddr>,
+}
+
+impl Default for AuditLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuditLogger {
+    /// Logger writing to the `audit` tracing target only
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                chain: Mutex::new(Chain {
+                    seq: 0,
+                    last_hash: GENESIS_HASH.to_string(),
+                }),
+                sinks: RwLock::default(),
+            }),
+            request_id: None,
+            client_ip: None,
+        }
+    }
+
+    /// Logger with the file sink of `AUDIT_LOG_FILE`
+    ///
+    /// `AUDIT_BROKER_SUBJECT` is wired by `barrzen-axum-infra`, which owns
+    /// the broker.
+    #[must_use]
+    pub fn from_config(config: &AuditConfig) -> Self {
+        let logger = Self::new();
+        if let Some(path) = &config.audit_log_file {
+            logger.add_sink(FileAuditSink::new(path));
+        }
+        logger
+    }
+
+    /// Add a sink; clones, including those handed out already, use it too
+    pub fn add_sink(&self, sink: impl AuditSink + 'static) {
+        self.shared
+            .sinks
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(sink));
+    }
+
+    /// Same logger, attributing records to a request
+    #[must_use]
+    pub fn for_request(&self, request_id: Option<String>, client_ip: Option<IpAddr>) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            request_id,
+            client_ip,
+        }
+    }
+
+    /// Record `event` on the `audit` target and in every sink
+    ///
+    /// `request_id` falls back to the request being handled. Records are
+    /// written in `seq` order: concurrent calls wait for each other.
+    ///
+    /// # Errors
+    /// Returns error if a sink fails; the other sinks and the `audit` target
+    /// still got the record.
+    pub async fn record(&self, event: AuditEvent) -> Result<AuditRecord, AuditError> {
+        let mut chain = self.shared.chain.lock().await;
+        let mut record = AuditRecord {
+            version: AUDIT_SCHEMA_VERSION,
+            seq: chain.seq + 1,
+            timestamp: Utc::now(),
+            request_id: self.request_id.clone().or_else(current_request_id),
+            client_ip: self.client_ip,
+            actor: event.actor,
+            action: event.action,
+            resource: event.resource,
+            outcome: event.outcome,
+            metadata: event.metadata,
+            prev_hash: chain.last_hash.clone(),
+            hash: String::new(),
+        };
+        record.hash = record.compute_hash();
+        chain.seq = record.seq;
+        chain.last_hash.clone_from(&record.hash);
+
+        let line = serde_json::to_string(&record).map_err(|error| AuditError {
+            seq: record.seq,
+            message: error.to_string(),
+        })?;
+        tracing::info!(target: AUDIT_TARGET, "{line}");
+
+        let sinks = self
+            .shared
+            .sinks
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let mut failure = None;
+        for sink in sinks {
+            if let Err(error) = sink.write(&record, &line).await {
+                failure.get_or_insert(error.to_string());
+            }
+        }
+        drop(chain);
+
+        match failure {
+            Some(message) => Err(AuditError {
+                seq: record.seq,
+                message,
+            }),
+            None => Ok(record),
+        }
+    }
+}
+
+impl<S> FromRequestParts<S> for AuditLogger
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let request_id = current_request_id().or_else(|| extract_request_id(&parts.headers));
+        let Some(context) = AppContext::current() else {
+            let error = ApiError::internal("Audit log is not available outside the built app");
+            return Err(match request_id {
+                Some(request_id) => error.with_request_id(request_id),
+                None => error,
+            });
+        };
+        let client_ip = parts.extensions.get::<ClientIp>().map(|ClientIp(ip)| *ip);
+        Ok(context.audit.for_request(request_id, client_ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request, routing::post};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{
+        AppBuilder, BuildInfo,
+        test_support::{LogCapture, test_config},
+    };
+
+    /// JSON of the audit lines in `capture`
+    fn audit_lines(capture: &LogCapture) -> Vec<Value> {
+        capture
+            .lines()
+            .iter()
+            .filter(|line| line.contains(" audit: "))
+            .map(|line| serde_json::from_str(&line[line.find('{').unwrap()..]).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_record_schema_from_a_handler() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        let app = AppBuilder::new(
+            test_config(),
+            BuildInfo::new("test", "1.0.0", None, "1.75.0", None),
+        )
+        .route(
+            "/export",
+            post(|audit: AuditLogger| async move {
+                let event = AuditEvent::new("user-7", "data.export", AuditOutcome::Success)
+                    .with_resource("report:q3")
+                    .with_metadata("rows", 120);
+                let record = audit.record(event).await?;
+                Ok::<_, ApiError>(record.seq.to_string())
+            }),
+        )
+        .build();
+        let request = Request::post("/export")
+            .header("x-request-id", "req-audit-1")
+            .body(Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap();
+
+        let records = audit_lines(&capture);
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        let mut fields: Vec<_> = record.as_object().unwrap().keys().cloned().collect();
+        fields.sort();
+        assert_eq!(
+            fields,
+            [
+                "action",
+                "actor",
+                "client_ip",
+                "hash",
+                "metadata",
+                "outcome",
+                "prev_hash",
+                "request_id",
+                "resource",
+                "seq",
+                "timestamp",
+                "version"
+            ]
+        );
+        assert_eq!(record["version"], 1);
+        assert_eq!(record["seq"], 1);
+        assert_eq!(record["request_id"], "req-audit-1");
+        assert_eq!(record["actor"], "user-7");
+        assert_eq!(record["action"], "data.export");
+        assert_eq!(record["resource"], "report:q3");
+        assert_eq!(record["outcome"], "success");
+        assert_eq!(record["metadata"]["rows"], 120);
+        assert_eq!(record["prev_hash"], GENESIS_HASH);
+        assert!(record["timestamp"].as_str().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_records_chain_and_reach_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let config = AuditConfig {
+            audit_log_file: Some(path.display().to_string()),
+            ..AuditConfig::default()
+        };
+        let audit = AuditLogger::from_config(&config);
+        let login = |outcome| AuditEvent::new("ada", "login", outcome);
+        audit.record(login(AuditOutcome::Failure)).await.unwrap();
+        let client = audit.for_request(Some("req-2".into()), Some("203.0.113.5".parse().unwrap()));
+        client.record(login(AuditOutcome::Success)).await.unwrap();
+
+        let mut records: Vec<AuditRecord> = tokio::fs::read_to_string(&path)
+            .await
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.iter().map(|r| r.seq).collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(records[1].client_ip, Some("203.0.113.5".parse().unwrap()));
+        assert_eq!(records[1].request_id.as_deref(), Some("req-2"));
+        assert_eq!(AuditRecord::verify_chain(&records), Ok(()));
+
+        // Rewriting history breaks the chain
+        let mut edited = records.clone();
+        edited[0].outcome = AuditOutcome::Success;
+        assert_eq!(AuditRecord::verify_chain(&edited), Err(1));
+        records.swap(0, 1);
+        assert_eq!(AuditRecord::verify_chain(&records), Err(1));
+    }
+}
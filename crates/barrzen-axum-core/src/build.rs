@@ -0,0 +1,133 @@
+//! Build script helper
+//!
+//! Call [`emit_build_info`] from `main` in an application's `build.rs` (with
+//! `barrzen-axum-core` in `[build-dependencies]`) to capture git, toolchain
+//! and timestamp details that [`build_info!`](crate::build_info) then bakes
+//! into [`BuildInfo`](crate::BuildInfo):
+//!
+//! ```no_run
+//! barrzen_axum_core::build::emit_build_info();
+//! ```
+//!
+//! This module only uses `std`; the crate's own build script includes it by
+//! path.
+
+use std::{
+    path::PathBuf,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Emit the `BARRZEN_BUILD_*` variables read by `build_info!()`
+///
+/// Git details are skipped outside a git checkout. `SOURCE_DATE_EPOCH` pins
+/// the timestamp for reproducible builds.
+pub fn emit_build_info() {
+    if let Some(sha) = git(&["rev-parse", "--short", "HEAD"]) {
+        set("BARRZEN_BUILD_GIT_SHA", &sha);
+    }
+    if let Some(branch) = git(&["rev-parse", "--abbrev-ref", "HEAD"]) {
+        set("BARRZEN_BUILD_GIT_BRANCH", &branch);
+    }
+    if let Some(status) = git(&["status", "--porcelain", "--untracked-files=no"]) {
+        set("BARRZEN_BUILD_GIT_DIRTY", &(!status.is_empty()).to_string());
+    }
+    if let Some(version) = rustc_version() {
+        set("BARRZEN_BUILD_RUSTC_VERSION", &version);
+    }
+    for (name, var) in [
+        ("BARRZEN_BUILD_TARGET", "TARGET"),
+        ("BARRZEN_BUILD_PROFILE", "PROFILE"),
+    ] {
+        if let Ok(value) = std::env::var(var) {
+            set(name, &value);
+        }
+    }
+    set("BARRZEN_BUILD_TIME", &iso8601(build_timestamp()));
+
+    // Re-run when HEAD moves or the index changes rather than on every build
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]).map(PathBuf::from) {
+        for file in ["HEAD", "index"] {
+            let path = git_dir.join(file);
+            if path.exists() {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+    }
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
+
+fn set(name: &str, value: &str) {
+    println!("cargo:rustc-env={name}={value}");
+}
+
+/// Trimmed stdout of a successful git command
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// `1.85.0` from `rustc 1.85.0 (4d91de4e4 2025-02-17)`
+fn rustc_version() -> Option<String> {
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    String::from_utf8_lossy(&output.stdout)
+        .split_whitespace()
+        .nth(1)
+        .map(str::to_string)
+}
+
+/// Seconds since the epoch, from `SOURCE_DATE_EPOCH` when set
+fn build_timestamp() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        })
+}
+
+/// Format seconds since the epoch as `YYYY-MM-DDTHH:MM:SSZ`
+fn iso8601(secs: u64) -> String {
+    let days = secs / 86_400;
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Gregorian date for a day count since 1970-01-01 (Howard Hinnant's
+/// `civil_from_days`, restricted to dates after the epoch)
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iso8601() {
+        assert_eq!(iso8601(0), "1970-01-01T00:00:00Z");
+        assert_eq!(iso8601(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(iso8601(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+}
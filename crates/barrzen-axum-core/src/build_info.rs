@@ -1,6 +1,7 @@
 //! Build information
 //!
-//! Contains version, git info, and build timestamp.
+//! Contains version, git info, and build timestamp. [`build_info!`] captures
+//! them at compile time; see [`crate::build`] for the build script half.
 
 use serde::Serialize;
 
@@ -17,6 +18,47 @@ pub struct BuildInfo {
     pub rust_version: String,
     /// Build timestamp (ISO 8601)
     pub build_time: Option<String>,
+    /// Git branch the build was made from
+    pub git_branch: Option<String>,
+    /// Whether tracked files had uncommitted changes
+    pub git_dirty: Option<bool>,
+    /// Target triple, e.g. `x86_64-unknown-linux-gnu`
+    pub target: Option<String>,
+    /// Cargo profile (`debug` or `release`)
+    pub profile: Option<String>,
+}
+
+/// Capture [`BuildInfo`] for the calling crate at compile time
+///
+/// Name and version always come from Cargo. Git details, rustc version,
+/// target and build time need [`crate::build::emit_build_info`] in the
+/// crate's `build.rs`; without it they are `None` and the profile is
+/// inferred from `debug_assertions`.
+///
+/// ```
+/// let build = barrzen_axum_core::build_info!();
+/// assert_eq!(build.name, "barrzen-axum-core");
+/// ```
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::BuildInfo::from_compile_env(
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            |key| match key {
+                "GIT_SHA" => option_env!("BARRZEN_BUILD_GIT_SHA"),
+                "GIT_BRANCH" => option_env!("BARRZEN_BUILD_GIT_BRANCH"),
+                "GIT_DIRTY" => option_env!("BARRZEN_BUILD_GIT_DIRTY"),
+                "RUSTC_VERSION" => option_env!("BARRZEN_BUILD_RUSTC_VERSION")
+                    .or(option_env!("CARGO_PKG_RUST_VERSION")),
+                "TARGET" => option_env!("BARRZEN_BUILD_TARGET"),
+                "PROFILE" => option_env!("BARRZEN_BUILD_PROFILE"),
+                "DEBUG_ASSERTIONS" => cfg!(debug_assertions).then_some("true"),
+                "TIME" => option_env!("BARRZEN_BUILD_TIME"),
+                _ => None,
+            },
+        )
+    };
 }
 
 impl BuildInfo {
@@ -35,6 +77,32 @@ impl BuildInfo {
             git_sha,
             rust_version: rust_version.into(),
             build_time,
+            ..Self::default()
+        }
+    }
+
+    /// Build info from compile-time variables; use [`build_info!`] instead
+    #[doc(hidden)]
+    #[must_use]
+    pub fn from_compile_env(
+        name: &str,
+        version: &str,
+        var: impl Fn(&str) -> Option<&'static str>,
+    ) -> Self {
+        let var = |key| var(key).filter(|v| !v.is_empty()).map(str::to_string);
+        Self {
+            name: name.to_string(),
+            version: version.to_string(),
+            git_sha: var("GIT_SHA"),
+            rust_version: var("RUSTC_VERSION").unwrap_or_else(|| "unknown".to_string()),
+            build_time: var("TIME"),
+            git_branch: var("GIT_BRANCH"),
+            git_dirty: var("GIT_DIRTY").map(|dirty| dirty == "true"),
+            target: var("TARGET"),
+            profile: var("PROFILE").or_else(|| {
+                let debug = var("DEBUG_ASSERTIONS").is_some();
+                Some(if debug { "debug" } else { "release" }.to_string())
+            }),
         }
     }
 
@@ -51,8 +119,12 @@ impl BuildInfo {
             name: std::env::var("CARGO_PKG_NAME").unwrap_or_else(|_| "unknown".to_string()),
             version: std::env::var("CARGO_PKG_VERSION").unwrap_or_else(|_| "0.0.0".to_string()),
             git_sha: std::env::var("GIT_SHA").ok(),
-            rust_version: env!("CARGO_PKG_RUST_VERSION").to_string(),
+            rust_version: option_env!("CARGO_PKG_RUST_VERSION")
+                .filter(|v| !v.is_empty())
+                .unwrap_or("unknown")
+                .to_string(),
             build_time: std::env::var("BUILD_TIME").ok(),
+            ..Self::default()
         }
     }
 }
@@ -89,6 +161,24 @@ mod tests {
         assert!(!info.version.is_empty() || info.version == "0.0.0");
     }
 
+    #[test]
+    fn test_build_info_macro() {
+        let info = crate::build_info!();
+        assert_eq!(info.name, "barrzen-axum-core");
+        assert!(!info.version.is_empty());
+        assert_ne!(info.rust_version, "unknown");
+        assert!(info.target.is_some());
+        assert!(info.profile.is_some());
+
+        // YYYY-MM-DDTHH:MM:SSZ
+        let build_time = info.build_time.unwrap();
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(&build_time).is_ok(),
+            "{build_time}"
+        );
+        assert_eq!(build_time.len(), 20, "{build_time}");
+    }
+
     #[test]
     fn test_build_info_serializes() {
         let info = BuildInfo::new("app", "1.0.0", None, "1.75.0", None);
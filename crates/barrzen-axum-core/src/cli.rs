@@ -0,0 +1,432 @@
+//! Command-line runner (`cli` feature)
+//!
+//! Gives every service the same subcommands instead of a hand-written clap
+//! setup:
+//!
+//! - `serve` (default): load the config and serve the app
+//! - `check-config`: load and validate the config, print it redacted
+//! - `config-spec`: print every variable as a commented `.env` template
+//! - `print-openapi [--yaml] [--out FILE]`: print the OpenAPI document
+//! - `migrate`: run the registered migrations
+//! - `version`: print the build info as JSON
+//!
+//! ```ignore
+//! #[tokio::main]
+//! async fn main() -> std::process::ExitCode {
+//!     let build = barrzen_axum_core::build_info!();
+//!     Cli::new(build.clone())
+//!         .openapi(|_config| Ok(serde_json::to_value(ApiDoc::openapi())?))
+//!         .migrate(|config| async move {
+//!             Infra::init(&config).await?.run_migrations::<Migrator>().await?;
+//!             Ok(())
+//!         })
+//!         .run(move |config| AppBuilder::new(config, build).merge(my_app::router()))
+//!         .await
+//! }
+//! ```
+
+use std::{
+    ffi::OsString,
+    future::Future,
+    io::{self, Write},
+    path::PathBuf,
+    pin::Pin,
+    process::ExitCode,
+};
+
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+
+use crate::{
+    AppBuilder, BuildInfo,
+    config::{Config, ConfigError},
+};
+
+type ConfigLoader = Box<dyn Fn() -> Result<Config, ConfigError> + Send + Sync>;
+type OpenApiHook = Box<dyn Fn(&Config) -> anyhow::Result<serde_json::Value> + Send + Sync>;
+type MigrateHook =
+    Box<dyn FnOnce(Config) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>> + Send>;
+
+/// Exit code for command-line usage errors, as clap uses
+const EXIT_USAGE: u8 = 2;
+
+#[derive(Debug, Parser)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Serve the application (default)
+    Serve,
+    /// Load and validate the configuration, then print it redacted
+    CheckConfig,
+    /// Print every configuration variable as a commented .env template
+    ConfigSpec,
+    /// Print the OpenAPI document
+    PrintOpenapi {
+        /// Print YAML instead of JSON
+        #[arg(long)]
+        yaml: bool,
+        /// Write the document to FILE instead of stdout
+        #[arg(long, value_name = "FILE")]
+        out: Option<PathBuf>,
+    },
+    /// Run the database migrations
+    Migrate,
+    /// Print the build information as JSON
+    Version,
+}
+
+/// Subcommand dispatcher around an [`AppBuilder`] factory
+#[must_use]
+pub struct Cli {
+    build_info: BuildInfo,
+    load_config: ConfigLoader,
+    openapi: Option<OpenApiHook>,
+    migrate: Option<MigrateHook>,
+}
+
+impl Cli {
+    /// Runner loading the config with [`Config::from_env`]
+    pub fn new(build_info: BuildInfo) -> Self {
+        Self {
+            build_info,
+            load_config: Box::new(Config::from_env),
+            openapi: None,
+            migrate: None,
+        }
+    }
+
+    /// Load the config some other way, e.g. [`Config::from_env_prefixed`]
+    pub fn config_loader(
+        mut self,
+        load: impl Fn() -> Result<Config, ConfigError> + Send + Sync + 'static,
+    ) -> Self {
+        self.load_config = Box::new(load);
+        self
+    }
+
+    /// Supply the OpenAPI document for `print-openapi`
+    pub fn openapi(
+        mut self,
+        doc: impl Fn(&Config) -> anyhow::Result<serde_json::Value> + Send + Sync + 'static,
+    ) -> Self {
+        self.openapi = Some(Box::new(doc));
+        self
+    }
+
+    /// Register the migrations run by `migrate` (requires `FEATURE_DB=true`)
+    pub fn migrate<F, Fut>(mut self, migrate: F) -> Self
+    where
+        F: FnOnce(Config) -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.migrate = Some(Box::new(move |config| Box::pin(migrate(config))));
+        self
+    }
+
+    /// Parse the process arguments and run the subcommand
+    ///
+    /// `factory` receives the loaded config and returns the app to serve.
+    pub async fn run<S, F>(self, factory: F) -> ExitCode
+    where
+        S: Clone + Send + Sync + 'static,
+        F: FnOnce(Config) -> AppBuilder<S>,
+    {
+        self.run_with_args(std::env::args_os(), factory).await
+    }
+
+    /// [`Cli::run`] with explicit arguments, the program name first
+    pub async fn run_with_args<S, F>(
+        self,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+        factory: F,
+    ) -> ExitCode
+    where
+        S: Clone + Send + Sync + 'static,
+        F: FnOnce(Config) -> AppBuilder<S>,
+    {
+        let code = self
+            .dispatch(args, factory, &mut io::stdout(), &mut io::stderr())
+            .await;
+        ExitCode::from(code)
+    }
+
+    async fn dispatch<S, F>(
+        self,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+        factory: F,
+        out: &mut dyn Write,
+        err: &mut dyn Write,
+    ) -> u8
+    where
+        S: Clone + Send + Sync + 'static,
+        F: FnOnce(Config) -> AppBuilder<S>,
+    {
+        let args = match Args::try_parse_for(&self.build_info, args) {
+            Ok(args) => args,
+            Err(e) => {
+                if e.use_stderr() {
+                    let _ = write!(err, "{}", e.render());
+                    return EXIT_USAGE;
+                }
+                let _ = write!(out, "{}", e.render());
+                return 0;
+            }
+        };
+
+        match self
+            .execute(args.command.unwrap_or(Command::Serve), factory, out)
+            .await
+        {
+            Ok(()) => 0,
+            Err(e) => {
+                let _ = writeln!(err, "Error: {e:#}");
+                1
+            }
+        }
+    }
+
+    async fn execute<S, F>(
+        self,
+        command: Command,
+        factory: F,
+        out: &mut dyn Write,
+    ) -> anyhow::Result<()>
+    where
+        S: Clone + Send + Sync + 'static,
+        F: FnOnce(Config) -> AppBuilder<S>,
+    {
+        match command {
+            Command::Version => {
+                writeln!(out, "{}", serde_json::to_string_pretty(&self.build_info)?)?;
+            }
+            Command::ConfigSpec => write!(out, "{}", Config::render_env_example())?,
+            Command::CheckConfig => {
+                let config = (self.load_config)()?;
+                let redacted = serde_json::to_string_pretty(&config.to_redacted_json())?;
+                writeln!(out, "{redacted}")?;
+            }
+            Command::PrintOpenapi { yaml, out: file } => {
+                let doc = self
+                    .openapi
+                    .ok_or_else(|| anyhow::anyhow!("No OpenAPI document: use Cli::openapi"))?;
+                let spec = doc(&(self.load_config)()?)?;
+                let rendered = if yaml {
+                    serde_yaml::to_string(&spec)?
+                } else {
+                    serde_json::to_string_pretty(&spec)? + "\n"
+                };
+                match file {
+                    Some(path) => std::fs::write(&path, rendered)
+                        .map_err(|e| anyhow::anyhow!("Failed to write {}: {e}", path.display()))?,
+                    None => write!(out, "{rendered}")?,
+                }
+            }
+            Command::Migrate => {
+                let migrate = self
+                    .migrate
+                    .ok_or_else(|| anyhow::anyhow!("No migrations: use Cli::migrate"))?;
+                let config = (self.load_config)()?;
+                anyhow::ensure!(
+                    config.features.feature_db,
+                    "FEATURE_DB=false: there is no database to migrate"
+                );
+                migrate(config).await?;
+                writeln!(out, "Migrations applied")?;
+            }
+            Command::Serve => {
+                let config = (self.load_config)()?;
+                factory(config).serve().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Args {
+    /// Parse `args` under the name and version of the service
+    fn try_parse_for(
+        build_info: &BuildInfo,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    ) -> Result<Self, clap::Error> {
+        let matches = Self::command()
+            .name(build_info.name.clone())
+            .version(build_info.version.clone())
+            .try_get_matches_from(args)?;
+        Self::from_arg_matches(&matches)
+    }
+}
+
+/// [`Cli::run`] without hooks
+pub async fn run<S, F>(build_info: BuildInfo, factory: F) -> ExitCode
+where
+    S: Clone + Send + Sync + 'static,
+    F: FnOnce(Config) -> AppBuilder<S>,
+{
+    Cli::new(build_info).run(factory).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    };
+
+    use super::*;
+    use crate::test_support::test_config;
+
+    fn build() -> BuildInfo {
+        BuildInfo::new("orders", "1.2.3", None, "1.85.0", None)
+    }
+
+    fn cli() -> Cli {
+        Cli::new(build()).config_loader(|| Ok(test_config()))
+    }
+
+    fn factory(config: Config) -> AppBuilder {
+        AppBuilder::new(config, build())
+    }
+
+    async fn call(cli: Cli, args: &[&str]) -> (u8, String, String) {
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let command = std::iter::once("orders").chain(args.iter().copied());
+        let code = Box::pin(cli.dispatch(command, factory, &mut out, &mut err)).await;
+        (
+            code,
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_version() {
+        let (code, out, _) = call(cli(), &["version"]).await;
+        assert_eq!(code, 0);
+        let build: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(build["name"], "orders");
+        assert_eq!(build["version"], "1.2.3");
+    }
+
+    #[tokio::test]
+    async fn test_check_config() {
+        let loader = || {
+            let mut config = test_config();
+            config.auth.auth_jwt_secret = Some("sk_live_123".to_string());
+            Ok(config)
+        };
+        let (code, out, _) = call(cli().config_loader(loader), &["check-config"]).await;
+        assert_eq!(code, 0);
+        let config: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(config["app_name"], "test-app");
+        assert_eq!(config["auth_jwt_secret"], "****");
+
+        let invalid = || {
+            Err(ConfigError::Validation(
+                "APP_PORT must not be 0".to_string(),
+            ))
+        };
+        let (code, out, err) = call(cli().config_loader(invalid), &["check-config"]).await;
+        assert_eq!(code, 1);
+        assert!(out.is_empty());
+        assert!(err.contains("APP_PORT must not be 0"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_config_spec() {
+        let (code, out, _) = call(cli(), &["config-spec"]).await;
+        assert_eq!(code, 0);
+        assert_eq!(out, Config::render_env_example());
+    }
+
+    #[tokio::test]
+    async fn test_print_openapi() {
+        let with_doc =
+            || cli().openapi(|config| Ok(serde_json::json!({ "title": config.app.app_name })));
+
+        let (code, out, _) = call(with_doc(), &["print-openapi"]).await;
+        assert_eq!(code, 0);
+        assert_eq!(out, "{\n  \"title\": \"test-app\"\n}\n");
+
+        let (code, out, _) = call(with_doc(), &["print-openapi", "--yaml"]).await;
+        assert_eq!(code, 0);
+        assert_eq!(out, "title: test-app\n");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("openapi.yaml");
+        let path_arg = path.display().to_string();
+        let (code, out, _) =
+            call(with_doc(), &["print-openapi", "--yaml", "--out", &path_arg]).await;
+        assert_eq!(code, 0);
+        assert!(out.is_empty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "title: test-app\n");
+
+        let (code, _, err) = call(cli(), &["print-openapi"]).await;
+        assert_eq!(code, 1);
+        assert!(err.contains("Cli::openapi"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_migrate() {
+        let ran = Arc::new(AtomicBool::new(false));
+        let with_db = || {
+            let mut config = test_config();
+            config.features.feature_db = true;
+            Ok(config)
+        };
+        let hook = {
+            let ran = Arc::clone(&ran);
+            move |_config: Config| async move {
+                ran.store(true, Ordering::SeqCst);
+                anyhow::Ok(())
+            }
+        };
+        let (code, out, _) = call(cli().config_loader(with_db).migrate(hook), &["migrate"]).await;
+        assert_eq!(code, 0);
+        assert_eq!(out, "Migrations applied\n");
+        assert!(ran.load(Ordering::SeqCst));
+
+        // The hook is not called without FEATURE_DB
+        let (code, _, err) = call(cli().migrate(|_| async { anyhow::Ok(()) }), &["migrate"]).await;
+        assert_eq!(code, 1);
+        assert!(err.contains("FEATURE_DB=false"), "{err}");
+
+        let failing = |_: Config| async { Err(anyhow::anyhow!("migration m0002 failed")) };
+        let (code, _, err) =
+            call(cli().config_loader(with_db).migrate(failing), &["migrate"]).await;
+        assert_eq!(code, 1);
+        assert!(err.contains("m0002"), "{err}");
+
+        let (code, _, err) = call(cli().config_loader(with_db), &["migrate"]).await;
+        assert_eq!(code, 1);
+        assert!(err.contains("Cli::migrate"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_serve_is_default_and_fails_on_bad_config() {
+        let invalid = || Err(ConfigError::Parse("invalid APP_PORT".to_string()));
+        let (code, _, err) = call(cli().config_loader(invalid), &[]).await;
+        assert_eq!(code, 1);
+        assert!(err.contains("invalid APP_PORT"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_usage_errors_and_help() {
+        let (code, _, err) = call(cli(), &["deploy"]).await;
+        assert_eq!(code, EXIT_USAGE);
+        assert!(err.contains("deploy"), "{err}");
+
+        let (code, out, _) = call(cli(), &["--help"]).await;
+        assert_eq!(code, 0);
+        assert!(
+            out.contains("check-config") && out.contains("print-openapi"),
+            "{out}"
+        );
+
+        let (code, out, _) = call(cli(), &["--version"]).await;
+        assert_eq!(code, 0);
+        assert_eq!(out.trim(), "orders 1.2.3");
+    }
+}
@@ -0,0 +1,346 @@
+//! Client IP resolution
+//!
+//! [`ClientIpLayer`] resolves the client IP once per request and stores it as
+//! a [`ClientIp`] request extension. Forwarding headers are only honoured
+//! when the peer is one of the `TRUSTED_PROXIES`, so clients cannot spoof
+//! their address by sending the headers themselves.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{HeaderMap, Request, request::Parts},
+};
+use ipnet::IpNet;
+use tower::{Layer, Service};
+
+use crate::{
+    config::ClientIpConfig,
+    response::{ApiError, extract_request_id},
+};
+
+/// Resolved client IP
+///
+/// Set by the built app for every request; extract it in handlers with
+/// `ClientIp(ip): ClientIp`. Outside the built app it falls back to the
+/// socket address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(client_ip) = parts.extensions.get::<Self>() {
+            return Ok(*client_ip);
+        }
+        if let Some(ConnectInfo(addr)) = parts.extensions.get::<ConnectInfo<SocketAddr>>() {
+            return Ok(Self(addr.ip().to_canonical()));
+        }
+
+        let error = ApiError::internal("Client IP is not available");
+        Err(match extract_request_id(&parts.headers) {
+            Some(request_id) => error.with_request_id(request_id),
+            None => error,
+        })
+    }
+}
+
+/// Layer that resolves the client IP
+///
+/// The peer is the socket address (`ConnectInfo<SocketAddr>`). When it is a
+/// trusted proxy, the hops from `Forwarded`, `X-Forwarded-For` or
+/// `X-Real-IP` (first header present wins) are walked from the right and the
+/// first untrusted address is the client. Without a socket address (Unix
+/// socket) the local peer counts as trusted when any proxies are configured.
+#[derive(Clone)]
+pub struct ClientIpLayer {
+    trusted_proxies: Arc<[IpNet]>,
+}
+
+impl ClientIpLayer {
+    /// Create a resolver trusting the given proxy networks
+    #[must_use]
+    pub fn new(trusted_proxies: Vec<IpNet>) -> Self {
+        Self {
+            trusted_proxies: trusted_proxies.into(),
+        }
+    }
+
+    /// Build the resolver from `TRUSTED_PROXIES`
+    ///
+    /// # Errors
+    /// Returns error if an entry is not a valid CIDR block or address.
+    pub fn from_config(config: &ClientIpConfig) -> anyhow::Result<Self> {
+        let trusted_proxies = config
+            .trusted_proxies()
+            .iter()
+            .map(|entry| {
+                parse_network(entry).ok_or_else(|| {
+                    anyhow::anyhow!("TRUSTED_PROXIES contains an invalid CIDR block: {entry:?}")
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self::new(trusted_proxies))
+    }
+}
+
+impl<S> Layer<S> for ClientIpLayer {
+    type Service = ClientIpService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientIpService {
+            inner,
+            trusted_proxies: self.trusted_proxies.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ClientIpService<S> {
+    inner: S,
+    trusted_proxies: Arc<[IpNet]>,
+}
+
+impl<S, B> Service<Request<B>> for ClientIpService<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let peer = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        if let Some(ip) = resolve(peer, req.headers(), &self.trusted_proxies) {
+            req.extensions_mut().insert(ClientIp(ip));
+        }
+        self.inner.call(req)
+    }
+}
+
+/// Resolve the client IP from the peer and its forwarding headers
+fn resolve(peer: Option<IpAddr>, headers: &HeaderMap, trusted: &[IpNet]) -> Option<IpAddr> {
+    let is_trusted = |ip: &IpAddr| trusted.iter().any(|net| net.contains(ip));
+    let peer = peer.map(|ip| ip.to_canonical());
+
+    match peer {
+        Some(ip) if !is_trusted(&ip) => return Some(ip),
+        None if trusted.is_empty() => return None,
+        _ => {}
+    }
+
+    // Walk from the nearest hop; stop at the first untrusted or unparsable one
+    let mut client = peer;
+    for hop in forwarded_hops(headers).into_iter().rev() {
+        let Some(ip) = hop else { break };
+        client = Some(ip);
+        if !is_trusted(&ip) {
+            break;
+        }
+    }
+    client
+}
+
+/// Hops from the first forwarding header present, client first
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let values = |name: &str| -> Vec<String> {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|entry| entry.trim().to_string())
+            .filter(|entry| !entry.is_empty())
+            .collect()
+    };
+
+    let forwarded = values("forwarded");
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, value)| parse_forwarded_addr(value.trim().trim_matches('"')))
+            })
+            .collect();
+    }
+
+    let x_forwarded_for = values("x-forwarded-for");
+    if !x_forwarded_for.is_empty() {
+        return x_forwarded_for
+            .iter()
+            .map(|entry| parse_forwarded_addr(entry))
+            .collect();
+    }
+
+    values("x-real-ip")
+        .last()
+        .map(|entry| vec![parse_forwarded_addr(entry)])
+        .unwrap_or_default()
+}
+
+/// Parse a CIDR block, or a single address as a host network
+pub(crate) fn parse_network(entry: &str) -> Option<IpNet> {
+    entry
+        .parse::<IpNet>()
+        .ok()
+        .or_else(|| entry.parse::<IpAddr>().ok().map(IpNet::from))
+}
+
+/// Parse an address that may carry a port (`1.2.3.4:80`, `[::1]:80`)
+pub(crate) fn parse_forwarded_addr(entry: &str) -> Option<IpAddr> {
+    entry
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| entry.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .or_else(|| {
+            entry
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|inner| inner.parse().ok())
+        })
+        .map(|ip| ip.to_canonical())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, routing::get};
+    use tower::ServiceExt;
+
+    fn trusted() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap(), "fd00::/8".parse().unwrap()]
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_untrusted_peer_ignores_forwarded_headers() {
+        let spoofed = headers(&[
+            ("x-forwarded-for", "1.2.3.4"),
+            ("x-real-ip", "1.2.3.4"),
+            ("forwarded", "for=1.2.3.4"),
+        ]);
+
+        assert_eq!(
+            resolve(Some(ip("198.51.100.9")), &spoofed, &trusted()),
+            Some(ip("198.51.100.9"))
+        );
+        assert_eq!(
+            resolve(Some(ip("198.51.100.9")), &spoofed, &[]),
+            Some(ip("198.51.100.9"))
+        );
+    }
+
+    #[test]
+    fn test_trusted_hops_are_skipped() {
+        // client -> 10.1.1.1 -> 10.2.2.2 -> app; the client prepended a fake hop
+        let chain = headers(&[("x-forwarded-for", "6.6.6.6, 203.0.113.5, 10.1.1.1")]);
+        assert_eq!(
+            resolve(Some(ip("10.2.2.2")), &chain, &trusted()),
+            Some(ip("203.0.113.5"))
+        );
+
+        // Every hop trusted: the leftmost one is the client
+        let internal = headers(&[("x-forwarded-for", "10.3.3.3, 10.1.1.1")]);
+        assert_eq!(
+            resolve(Some(ip("10.2.2.2")), &internal, &trusted()),
+            Some(ip("10.3.3.3"))
+        );
+
+        // Unparsable hop: stop at the last trusted address
+        let garbage = headers(&[("x-forwarded-for", "203.0.113.5, nonsense")]);
+        assert_eq!(
+            resolve(Some(ip("10.2.2.2")), &garbage, &trusted()),
+            Some(ip("10.2.2.2"))
+        );
+    }
+
+    #[test]
+    fn test_forwarded_header_and_x_real_ip() {
+        let forwarded = headers(&[(
+            "forwarded",
+            r#"for=192.0.2.60;proto=http, For="[2001:db8:cafe::17]:4711";by=10.0.0.1"#,
+        )]);
+        assert_eq!(
+            resolve(Some(ip("fd00::1")), &forwarded, &trusted()),
+            Some(ip("2001:db8:cafe::17"))
+        );
+
+        let real_ip = headers(&[("x-real-ip", "192.0.2.60")]);
+        assert_eq!(
+            resolve(Some(ip("10.0.0.1")), &real_ip, &trusted()),
+            Some(ip("192.0.2.60"))
+        );
+
+        let none = HeaderMap::new();
+        assert_eq!(
+            resolve(Some(ip("10.0.0.1")), &none, &trusted()),
+            Some(ip("10.0.0.1"))
+        );
+    }
+
+    #[test]
+    fn test_ipv4_mapped_peer_is_canonical() {
+        let chain = headers(&[("x-forwarded-for", "203.0.113.5")]);
+        assert_eq!(
+            resolve(Some(ip("::ffff:10.0.0.1")), &chain, &trusted()),
+            Some(ip("203.0.113.5"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_layer_sets_extension() {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|ClientIp(ip): ClientIp| async move { ip.to_string() }),
+            )
+            .layer(ClientIpLayer::new(trusted()));
+
+        let mut request = Request::builder()
+            .uri("/")
+            .header("x-forwarded-for", "203.0.113.5")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo("10.0.0.1:443".parse::<SocketAddr>().unwrap()));
+
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"203.0.113.5");
+    }
+}
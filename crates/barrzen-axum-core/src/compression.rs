@@ -0,0 +1,200 @@
+//! Compression layer built from `COMPRESSION_*`
+
+use std::sync::Arc;
+
+use axum::http::{Response, header};
+use tower_http::compression::{
+    CompressionLayer,
+    predicate::{NotForContentType, Predicate},
+};
+
+use crate::config::CompressionConfig;
+
+/// Compresses responses of at least `min_size` bytes whose content type is
+/// not excluded
+///
+/// gRPC and server-sent events are never compressed, since buffering them
+/// would break streaming.
+#[derive(Clone, Debug)]
+pub(crate) struct CompressWhen {
+    min_size: u64,
+    excluded: Arc<[String]>,
+}
+
+/// Layer compressing responses with the configured encodings
+pub(crate) type ConfiguredCompressionLayer = CompressionLayer<CompressWhen>;
+
+/// Build the compression layer
+///
+/// # Errors
+/// Returns error if `COMPRESSION_ALGORITHMS` lists an encoding whose cargo
+/// feature is not enabled.
+#[cfg_attr(
+    all(feature = "compression-br", feature = "compression-zstd"),
+    allow(clippy::unnecessary_wraps)
+)]
+pub(crate) fn build_compression_layer(
+    config: &CompressionConfig,
+) -> anyhow::Result<ConfiguredCompressionLayer> {
+    let algorithms = config.algorithms();
+    let enabled = |name: &str| algorithms.iter().any(|algorithm| algorithm == name);
+
+    let layer = CompressionLayer::new().gzip(enabled("gzip"));
+    #[cfg(feature = "compression-br")]
+    let layer = layer.br(enabled("br"));
+    #[cfg(not(feature = "compression-br"))]
+    if enabled("br") {
+        anyhow::bail!(
+            "COMPRESSION_ALGORITHMS includes br but the 'compression-br' cargo feature is disabled"
+        );
+    }
+    #[cfg(feature = "compression-zstd")]
+    let layer = layer.zstd(enabled("zstd"));
+    #[cfg(not(feature = "compression-zstd"))]
+    if enabled("zstd") {
+        anyhow::bail!(
+            "COMPRESSION_ALGORITHMS includes zstd but the 'compression-zstd' cargo feature is disabled"
+        );
+    }
+
+    Ok(layer.compress_when(CompressWhen {
+        min_size: config.compression_min_size_bytes,
+        excluded: config.excluded_content_types().into(),
+    }))
+}
+
+impl Predicate for CompressWhen {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: axum::body::HttpBody,
+    {
+        if !NotForContentType::GRPC.should_compress(response)
+            || !NotForContentType::SSE.should_compress(response)
+        {
+            return false;
+        }
+
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if self
+            .excluded
+            .iter()
+            .any(|prefix| is_excluded(&content_type, prefix))
+        {
+            return false;
+        }
+
+        // Unknown sizes (streams) are compressed
+        let size = response.body().size_hint().exact().or_else(|| {
+            response
+                .headers()
+                .get(header::CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok()?.parse().ok())
+        });
+        size.is_none_or(|size| size >= self.min_size)
+    }
+}
+
+/// SVG is text, so an `image/` prefix does not exclude it
+fn is_excluded(content_type: &str, prefix: &str) -> bool {
+    content_type.starts_with(prefix)
+        && !(prefix == "image/" && content_type.starts_with("image/svg+xml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, body::Body, http::Request, routing::get};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn app(config: &CompressionConfig) -> Router {
+        Router::new()
+            .route(
+                "/small",
+                get(|| async { axum::Json(serde_json::json!({"ok": true})) }),
+            )
+            .route("/large", get(|| async { axum::Json(vec!["item"; 1000]) }))
+            .route(
+                "/archive",
+                get(|| async {
+                    (
+                        [(header::CONTENT_TYPE, "application/zip")],
+                        vec![0_u8; 4096],
+                    )
+                }),
+            )
+            .route(
+                "/logo",
+                get(|| async {
+                    (
+                        [(header::CONTENT_TYPE, "image/svg+xml")],
+                        "<svg/>".repeat(500),
+                    )
+                }),
+            )
+            .layer(build_compression_layer(config).unwrap())
+    }
+
+    async fn encoding(app: &Router, path: &str) -> Option<String> {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get(path)
+                    .header(header::ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        response
+            .headers()
+            .get(header::CONTENT_ENCODING)
+            .map(|v| v.to_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn test_compression_follows_size_and_content_type() {
+        let app = app(&CompressionConfig::default());
+
+        assert_eq!(encoding(&app, "/small").await, None);
+        assert_eq!(encoding(&app, "/large").await.as_deref(), Some("gzip"));
+        assert_eq!(encoding(&app, "/archive").await, None);
+        assert_eq!(encoding(&app, "/logo").await.as_deref(), Some("gzip"));
+    }
+
+    #[tokio::test]
+    async fn test_min_size_and_excludes_are_configurable() {
+        let config = CompressionConfig {
+            compression_min_size_bytes: 0,
+            compression_exclude_content_types: Some("application/json".to_string()),
+            ..CompressionConfig::default()
+        };
+        let app = app(&config);
+
+        assert_eq!(encoding(&app, "/large").await, None);
+        assert_eq!(encoding(&app, "/archive").await.as_deref(), Some("gzip"));
+    }
+
+    #[test]
+    fn test_encodings_without_cargo_feature_fail() {
+        let config = CompressionConfig {
+            compression_algorithms: "gzip,zstd".to_string(),
+            ..CompressionConfig::default()
+        };
+        let result = build_compression_layer(&config);
+        if cfg!(feature = "compression-zstd") {
+            assert!(result.is_ok());
+        } else {
+            let error = result.unwrap_err().to_string();
+            assert!(
+                error.contains("'compression-zstd' cargo feature"),
+                "{error}"
+            );
+        }
+    }
+}
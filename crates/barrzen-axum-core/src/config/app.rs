@@ -1,9 +1,11 @@
 //! Core application settings
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
 
 /// Core application settings
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct AppConfig {
     #[serde(default = "default_app_name")]
     pub app_name: String,
@@ -11,6 +13,7 @@ pub struct AppConfig {
     #[serde(default)]
     pub app_env: Environment,
 
+    /// Addresses to bind: IP literals or hostnames, comma-separated
     #[serde(default = "default_host")]
     pub app_host: String,
 
@@ -25,10 +28,66 @@ pub struct AppConfig {
     #[serde(default = "default_shutdown_grace")]
     #[serde(deserialize_with = "crate::config::de_u64")]
     pub app_shutdown_grace_seconds: u64,
+
+    /// Seconds to keep accepting traffic after shutdown starts while /readyz reports 503
+    #[serde(default = "default_shutdown_drain")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub app_shutdown_drain_seconds: u64,
+
+    /// Prefix for every route, e.g. `/api/orders` (empty = no prefix)
+    #[serde(default)]
+    pub app_base_path: String,
+
+    /// Redirect unprefixed requests to the base path instead of returning 404
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub app_base_path_redirect: bool,
+
+    /// Serve on this Unix domain socket instead of `app_host:app_port`
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub app_uds_path: Option<String>,
+
+    /// Octal permissions applied to the Unix domain socket
+    #[serde(default = "default_uds_mode")]
+    pub app_uds_mode: String,
+
+    /// Prefix the variables were loaded with (`ORDERS_`), from `ENV_PREFIX`
+    /// or [`Config::from_env_prefixed`](crate::Config::from_env_prefixed)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub env_prefix: Option<String>,
+
+    /// Fail on unrecognized config variables instead of warning
+    /// (default: on in dev, off elsewhere)
+    #[serde(default, deserialize_with = "crate::config::de_opt_bool")]
+    pub config_strict: Option<bool>,
+
+    /// Env files loaded at startup, lowest precedence first (`DOTENV_DIR`)
+    #[serde(default, skip_deserializing)]
+    pub dotenv_files: Vec<String>,
+}
+
+impl AppConfig {
+    /// Entries of `app_host`, trimmed, with brackets removed from IPv6 literals
+    #[must_use]
+    pub fn hosts(&self) -> Vec<&str> {
+        split_hosts(&self.app_host)
+    }
+
+    /// Normalized base path (`/api/orders`), or `None` when routes live at the root
+    #[must_use]
+    pub fn base_path(&self) -> Option<String> {
+        let trimmed = self.app_base_path.trim().trim_matches('/');
+        if trimmed.is_empty() {
+            None
+        } else {
+            Some(format!("/{trimmed}"))
+        }
+    }
 }
 
 /// Environment type
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum Environment {
     #[default]
@@ -47,6 +106,20 @@ impl std::fmt::Display for Environment {
     }
 }
 
+/// Comma-separated host list, trimmed, with brackets removed from IPv6 literals
+pub(crate) fn split_hosts(value: &str) -> Vec<&str> {
+    value
+        .split(',')
+        .map(|host| {
+            let host = host.trim();
+            host.strip_prefix('[')
+                .and_then(|host| host.strip_suffix(']'))
+                .unwrap_or(host)
+        })
+        .filter(|host| !host.is_empty())
+        .collect()
+}
+
 fn default_app_name() -> String {
     "barrzen-app".to_string()
 }
@@ -59,3 +132,31 @@ fn default_port() -> u16 {
 fn default_shutdown_grace() -> u64 {
     10
 }
+fn default_shutdown_drain() -> u64 {
+    5
+}
+fn default_uds_mode() -> String {
+    "660".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_path_normalization() {
+        let mut config: AppConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(config.base_path(), None);
+
+        for (raw, expected) in [
+            ("/", None),
+            ("  ", None),
+            ("/api", Some("/api")),
+            ("/api/", Some("/api")),
+            ("api/orders", Some("/api/orders")),
+        ] {
+            config.app_base_path = raw.to_string();
+            assert_eq!(config.base_path().as_deref(), expected, "{raw:?}");
+        }
+    }
+}
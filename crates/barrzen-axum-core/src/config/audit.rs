@@ -0,0 +1,35 @@
+//! Audit log settings
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// Where [`AuditLogger`](crate::audit::AuditLogger) records go besides the
+/// `audit` tracing target
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// File the records are appended to as JSON lines
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub audit_log_file: Option<String>,
+
+    /// Broker subject the records are published on (needs the infra broker)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub audit_broker_subject: Option<String>,
+
+    /// Keep the `audit` target at `info` whatever `LOG_LEVEL`,
+    /// `LOG_DIRECTIVES` or `RUST_LOG` say
+    #[serde(default = "default_true")]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub audit_log_force: bool,
+}
+
+impl Default for AuditConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().audit
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
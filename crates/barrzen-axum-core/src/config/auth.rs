@@ -0,0 +1,125 @@
+//! Authentication configuration
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// Authentication configuration (consumed by `barrzen-axum-auth`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuthConfig {
+    /// Shared secret for HS256 tokens
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub auth_jwt_secret: Option<String>,
+
+    /// JWKS endpoint for asymmetric (RS256/ES256) tokens
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub auth_jwks_url: Option<String>,
+
+    #[serde(default = "default_jwks_refresh_seconds")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub auth_jwks_refresh_seconds: u64,
+
+    /// Required `iss` claim
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub auth_issuer: Option<String>,
+
+    /// Accepted `aud` values (comma-separated)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub auth_audience: Option<String>,
+
+    /// Static API keys (comma-separated `key` or `name:key` entries)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub auth_api_keys: Option<String>,
+
+    #[serde(default = "default_api_key_header")]
+    pub auth_api_key_header: String,
+}
+
+impl AuthConfig {
+    /// Parse accepted audiences into a vector
+    #[must_use]
+    pub fn audiences(&self) -> Vec<String> {
+        self.auth_audience
+            .as_ref()
+            .map(|s| {
+                s.split(',')
+                    .map(|a| a.trim().to_string())
+                    .filter(|a| !a.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Parse API keys into `(name, key)` pairs
+    ///
+    /// An entry is split on its first `:` into a name and the key; entries
+    /// without a `:` are unnamed.
+    #[must_use]
+    pub fn api_keys(&self) -> Vec<(Option<String>, String)> {
+        self.auth_api_keys
+            .as_ref()
+            .map(|s| {
+                s.split(',')
+                    .map(str::trim)
+                    .filter(|entry| !entry.is_empty())
+                    .filter_map(|entry| match entry.split_once(':') {
+                        Some((name, key)) if !key.trim().is_empty() => {
+                            let name = name.trim();
+                            Some((
+                                (!name.is_empty()).then(|| name.to_string()),
+                                key.trim().to_string(),
+                            ))
+                        }
+                        Some(_) => None,
+                        None => Some((None, entry.to_string())),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn default_jwks_refresh_seconds() -> u64 {
+    300
+}
+fn default_api_key_header() -> String {
+    "x-api-key".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_defaults_and_audiences() {
+        let config: AuthConfig = serde_json::from_value(serde_json::json!({
+            "auth_jwt_secret": "",
+            "auth_audience": "api, admin"
+        }))
+        .unwrap();
+
+        assert!(config.auth_jwt_secret.is_none());
+        assert!(config.auth_jwks_url.is_none());
+        assert_eq!(config.auth_jwks_refresh_seconds, 300);
+        assert_eq!(config.audiences(), vec!["api", "admin"]);
+        assert!(config.api_keys().is_empty());
+        assert_eq!(config.auth_api_key_header, "x-api-key");
+    }
+
+    #[test]
+    fn test_api_keys_parsing() {
+        let config: AuthConfig = serde_json::from_value(serde_json::json!({
+            "auth_api_keys": "billing:k1, k2,:k3, broken:"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            config.api_keys(),
+            vec![
+                (Some("billing".to_string()), "k1".to_string()),
+                (None, "k2".to_string()),
+                (None, "k3".to_string()),
+            ]
+        );
+    }
+}
@@ -1,11 +1,11 @@
 //! Banner display configuration
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::empty_string_as_none;
 
 /// Banner display configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct BannerConfig {
     #[serde(default)]
     #[serde(deserialize_with = "crate::config::de_bool")]
@@ -17,4 +17,58 @@ pub struct BannerConfig {
 
     #[serde(default, deserialize_with = "empty_string_as_none")]
     pub banner_env_allowlist: Option<String>,
+
+    /// Extra key patterns (comma-separated) whose values are always masked
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub banner_secret_patterns: Option<String>,
+
+    /// How the banner is emitted
+    #[serde(default)]
+    pub banner_style: BannerStyle,
+
+    /// Widest the banner box may grow, in columns; longer values are cut
+    #[serde(default = "default_max_width")]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub banner_max_width: usize,
+
+    /// Title line of the box, replacing the default
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub banner_title: Option<String>,
+
+    /// Text file printed verbatim above the box (e.g. an ASCII logo)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub banner_logo_file: Option<String>,
+}
+
+/// Banner output style
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BannerStyle {
+    /// Box-drawing characters and emoji on stdout
+    #[default]
+    Box,
+    /// ASCII only on stdout
+    Plain,
+    /// One `tracing` event per line, following `LOG_FORMAT`
+    Log,
+}
+
+impl BannerConfig {
+    /// Parse `BANNER_SECRET_PATTERNS` into a vector
+    #[must_use]
+    pub fn secret_patterns(&self) -> Vec<String> {
+        self.banner_secret_patterns
+            .as_ref()
+            .map(|s| {
+                s.split(',')
+                    .map(|pattern| pattern.trim().to_string())
+                    .filter(|pattern| !pattern.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn default_max_width() -> usize {
+    100
 }
@@ -0,0 +1,32 @@
+//! Broker configuration
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// Message broker connection configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrokerConfig {
+    #[serde(default)]
+    pub broker_backend: BrokerBackend,
+
+    /// NATS server URL, e.g. `nats://nats:4222`
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub nats_url: Option<String>,
+}
+
+/// Broker backend type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BrokerBackend {
+    #[default]
+    Nats,
+}
+
+impl std::fmt::Display for BrokerBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nats => write!(f, "nats"),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+//! Programmatic configuration
+//!
+//! Defaults come from deserializing an empty environment, so the builder and
+//! the serde defaults share one source of truth and cannot drift.
+
+use super::{Config, Environment};
+
+impl Default for Config {
+    /// Configuration as loaded from an empty environment
+    fn default() -> Self {
+        match envy::from_iter(std::iter::empty::<(String, String)>()) {
+            Ok(config) => config,
+            Err(e) => unreachable!("every config field has a serde default: {e}"),
+        }
+    }
+}
+
+impl Config {
+    /// Start a [`ConfigBuilder`] from the default configuration
+    ///
+    /// ```
+    /// use barrzen_axum_core::Config;
+    ///
+    /// let config = Config::builder().port(0).feature_cors(true).build();
+    /// assert_eq!(config.app.app_port, 0);
+    /// ```
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+}
+
+/// Builder for [`Config`] in tests and embedded use
+///
+/// Fields without a setter keep their default and can be changed with
+/// [`ConfigBuilder::with`] or on the built [`Config`].
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+macro_rules! feature_setters {
+    ($($name:ident),* $(,)?) => {
+        $(
+            #[doc = concat!("Set `", stringify!($name), "`")]
+            pub fn $name(mut self, enabled: bool) -> Self {
+                self.config.features.$name = enabled;
+                self
+            }
+        )*
+    };
+}
+
+impl ConfigBuilder {
+    /// Set `APP_NAME`
+    pub fn app_name(mut self, name: impl Into<String>) -> Self {
+        self.config.app.app_name = name.into();
+        self
+    }
+
+    /// Set `APP_ENV`
+    pub fn env(mut self, env: Environment) -> Self {
+        self.config.app.app_env = env;
+        self
+    }
+
+    /// Set `APP_HOST`
+    pub fn host(mut self, host: impl Into<String>) -> Self {
+        self.config.app.app_host = host.into();
+        self
+    }
+
+    /// Set `APP_PORT` (0 picks a free port)
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.app.app_port = port;
+        self
+    }
+
+    /// Set `MANAGEMENT_PORT` (0 picks a free port)
+    pub fn management_port(mut self, port: u16) -> Self {
+        self.config.management.management_port = Some(port);
+        self
+    }
+
+    /// Set `GRPC_PORT` (0 picks a free port)
+    pub fn grpc_port(mut self, port: u16) -> Self {
+        self.config.grpc.grpc_port = Some(port);
+        self
+    }
+
+    /// Set `HTTP_BODY_LIMIT_BYTES`
+    pub fn body_limit(mut self, bytes: usize) -> Self {
+        self.config.http.http_body_limit_bytes = bytes;
+        self
+    }
+
+    /// Set `LOG_LEVEL`
+    pub fn log_level(mut self, level: impl Into<String>) -> Self {
+        self.config.logging.log_level = level.into();
+        self
+    }
+
+    /// Set `LOG_DIRECTIVES`
+    pub fn log_directives(mut self, directives: impl Into<String>) -> Self {
+        self.config.logging.log_directives = Some(directives.into());
+        self
+    }
+
+    feature_setters!(
+        feature_startup_banner,
+        feature_db,
+        feature_cache,
+        feature_search,
+        feature_broker,
+        feature_mailer,
+        feature_openapi,
+        feature_request_log,
+        feature_tracing,
+        feature_otel,
+        feature_otel_metrics,
+        feature_tokio_console,
+        feature_cors,
+        feature_session,
+        feature_response_envelope,
+        feature_response_time,
+        feature_version_endpoint,
+        feature_security_headers,
+        feature_config_endpoint,
+        feature_admin_endpoints,
+        feature_maintenance_mode,
+        feature_etag,
+        feature_compression,
+        feature_load_shed,
+        feature_tenant,
+    );
+
+    /// Adjust any other field
+    pub fn with(mut self, f: impl FnOnce(&mut Config)) -> Self {
+        f(&mut self.config);
+        self
+    }
+
+    /// Finish building
+    #[must_use]
+    pub fn build(self) -> Config {
+        self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_builder_matches_serde_defaults() {
+        let from_serde: Config = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(Config::builder().build(), from_serde);
+        assert_eq!(Config::default(), from_serde);
+    }
+
+    #[test]
+    fn test_builder_setters() {
+        let config = Config::builder()
+            .app_name("orders")
+            .env(Environment::Stage)
+            .port(0)
+            .body_limit(1024)
+            .log_level("debug")
+            .feature_cors(true)
+            .feature_cache(false)
+            .with(|config| config.cors.cors_allow_origins = Some("*".to_string()))
+            .build();
+
+        let expected: Config = serde_json::from_value(json!({
+            "app_name": "orders",
+            "app_env": "stage",
+            "app_port": "0",
+            "http_body_limit_bytes": "1024",
+            "log_level": "debug",
+            "feature_cors": "true",
+            "feature_cache": "false",
+            "cors_allow_origins": "*"
+        }))
+        .unwrap();
+        assert_eq!(config, expected);
+    }
+}
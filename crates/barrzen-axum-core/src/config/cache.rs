@@ -1,11 +1,11 @@
 //! Cache configuration
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::empty_string_as_none;
 
 /// Cache configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CacheConfig {
     #[serde(default)]
     pub cache_backend: CacheBackend,
@@ -28,16 +28,30 @@ pub struct CacheConfig {
     #[serde(default = "default_connect_timeout")]
     #[serde(deserialize_with = "crate::config::de_u64")]
     pub cache_redis_connect_timeout_seconds: u64,
+
+    /// Upper bound on how long the in-process layer of `CACHE_BACKEND=tiered`
+    /// keeps an entry, independent of `CACHE_TTL_SECONDS`
+    #[serde(default = "default_l1_ttl")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub cache_l1_ttl_seconds: u64,
+
+    /// Broadcast deletes over Redis pub/sub so other instances evict their
+    /// in-process copy (`CACHE_BACKEND=tiered`)
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub cache_tiered_invalidation: bool,
 }
 
 /// Cache backend type
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum CacheBackend {
     None,
     #[default]
     Moka,
     Redis,
+    /// Moka in front of Redis
+    Tiered,
 }
 
 impl std::fmt::Display for CacheBackend {
@@ -46,6 +60,7 @@ impl std::fmt::Display for CacheBackend {
             Self::None => write!(f, "none"),
             Self::Moka => write!(f, "moka"),
             Self::Redis => write!(f, "redis"),
+            Self::Tiered => write!(f, "tiered"),
         }
     }
 }
@@ -62,3 +77,6 @@ fn default_redis_pool_size() -> usize {
 fn default_connect_timeout() -> u64 {
     5
 }
+fn default_l1_ttl() -> u64 {
+    60
+}
@@ -0,0 +1,43 @@
+//! Circuit breaker configuration
+
+use serde::{Deserialize, Serialize};
+
+/// Settings for the circuit breakers around infra clients
+/// (`barrzen-axum-infra`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures that open the circuit
+    #[serde(default = "default_failure_threshold")]
+    #[serde(deserialize_with = "crate::config::de_u32")]
+    pub cb_failure_threshold: u32,
+
+    /// How long an open circuit fails fast before letting probes through
+    #[serde(default = "default_open_duration_ms")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub cb_open_duration_ms: u64,
+
+    /// Successful probes that close a half-open circuit; as many calls are
+    /// let through at once
+    #[serde(default = "default_half_open_probes")]
+    #[serde(deserialize_with = "crate::config::de_u32")]
+    pub cb_half_open_probes: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().circuit_breaker
+    }
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_open_duration_ms() -> u64 {
+    30_000
+}
+
+fn default_half_open_probes() -> u32 {
+    1
+}
@@ -0,0 +1,45 @@
+//! Client IP resolution configuration
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// Client IP resolution configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientIpConfig {
+    /// Proxies whose forwarding headers are trusted (comma-separated CIDR blocks)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub trusted_proxies: Option<String>,
+}
+
+impl ClientIpConfig {
+    /// Parse trusted proxy entries into a vector
+    #[must_use]
+    pub fn trusted_proxies(&self) -> Vec<String> {
+        self.trusted_proxies
+            .as_ref()
+            .map(|s| {
+                s.split(',')
+                    .map(|p| p.trim().to_string())
+                    .filter(|p| !p.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_trusted_proxies_parsing() {
+        let config: ClientIpConfig =
+            serde_json::from_value(json!({ "trusted_proxies": "10.0.0.0/8, ::1," })).unwrap();
+        assert_eq!(config.trusted_proxies(), vec!["10.0.0.0/8", "::1"]);
+
+        let config: ClientIpConfig = serde_json::from_value(json!({})).unwrap();
+        assert!(config.trusted_proxies().is_empty());
+    }
+}
@@ -0,0 +1,90 @@
+//! Response compression configuration
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// Encodings `COMPRESSION_ALGORITHMS` may list
+pub(crate) const COMPRESSION_ALGORITHMS: [&str; 3] = ["gzip", "br", "zstd"];
+
+/// Response compression settings (`FEATURE_COMPRESSION`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    /// Encodings offered to clients, e.g. `gzip,br,zstd`
+    ///
+    /// `br` and `zstd` need the core `compression-br`/`compression-zstd`
+    /// cargo features.
+    #[serde(default = "default_algorithms")]
+    pub compression_algorithms: String,
+
+    /// Responses smaller than this are sent uncompressed
+    #[serde(default = "default_min_size_bytes")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub compression_min_size_bytes: u64,
+
+    /// Content type prefixes never compressed (comma-separated), e.g.
+    /// already-compressed formats
+    #[serde(
+        default = "default_exclude_content_types",
+        deserialize_with = "empty_string_as_none"
+    )]
+    pub compression_exclude_content_types: Option<String>,
+}
+
+impl Default for CompressionConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().compression
+    }
+}
+
+impl CompressionConfig {
+    /// Lower-cased encodings from `COMPRESSION_ALGORITHMS`
+    #[must_use]
+    pub fn algorithms(&self) -> Vec<String> {
+        split_list(Some(&self.compression_algorithms))
+    }
+
+    /// Lower-cased content type prefixes from
+    /// `COMPRESSION_EXCLUDE_CONTENT_TYPES`
+    #[must_use]
+    pub fn excluded_content_types(&self) -> Vec<String> {
+        split_list(self.compression_exclude_content_types.as_deref())
+    }
+
+    /// Entries of `COMPRESSION_ALGORITHMS` that are not a known encoding
+    pub(crate) fn invalid_algorithms(&self) -> Vec<String> {
+        self.algorithms()
+            .into_iter()
+            .filter(|algorithm| !COMPRESSION_ALGORITHMS.contains(&algorithm.as_str()))
+            .collect()
+    }
+}
+
+fn split_list(value: Option<&str>) -> Vec<String> {
+    value
+        .map(|s| {
+            s.split(',')
+                .map(|entry| entry.trim().to_ascii_lowercase())
+                .filter(|entry| !entry.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn default_algorithms() -> String {
+    "gzip".to_string()
+}
+
+fn default_min_size_bytes() -> u64 {
+    1024
+}
+
+#[allow(clippy::unnecessary_wraps)] // shaped for `serde(default)`
+fn default_exclude_content_types() -> Option<String> {
+    Some(
+        "image/,audio/,video/,font/woff2,application/zip,application/gzip,application/zstd,\
+         application/x-7z-compressed"
+            .to_string(),
+    )
+}
@@ -0,0 +1,151 @@
+//! Built-in endpoint paths
+
+use serde::{Deserialize, Serialize};
+
+/// Paths of the built-in core endpoints
+///
+/// An empty value disables the endpoint; requests to it then follow normal
+/// routing (user routes or fallback).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreRoutesConfig {
+    #[serde(default = "default_healthz_path")]
+    pub core_healthz_path: String,
+
+    #[serde(default = "default_readyz_path")]
+    pub core_readyz_path: String,
+
+    #[serde(default = "default_version_path")]
+    pub core_version_path: String,
+
+    /// Path of the effective configuration endpoint (`FEATURE_CONFIG_ENDPOINT`)
+    #[serde(default = "default_configz_path")]
+    pub core_configz_path: String,
+
+    /// Path of the log level endpoint (`FEATURE_ADMIN_ENDPOINTS`)
+    #[serde(default = "default_log_level_path")]
+    pub core_log_level_path: String,
+
+    /// Path of the maintenance mode endpoint (`FEATURE_ADMIN_ENDPOINTS`)
+    #[serde(default = "default_maintenance_path")]
+    pub core_maintenance_path: String,
+
+    /// Path of the feature flags endpoints (`FEATURE_ADMIN_ENDPOINTS`)
+    #[serde(default = "default_features_path")]
+    pub core_features_path: String,
+
+    /// Serve the configuration endpoint even when `APP_ENV=prod`
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub config_endpoint_force: bool,
+}
+
+impl CoreRoutesConfig {
+    /// Liveness endpoint path, if enabled
+    #[must_use]
+    pub fn healthz_path(&self) -> Option<String> {
+        normalize_path(&self.core_healthz_path)
+    }
+
+    /// Readiness endpoint path, if enabled
+    #[must_use]
+    pub fn readyz_path(&self) -> Option<String> {
+        normalize_path(&self.core_readyz_path)
+    }
+
+    /// Version endpoint path, if enabled
+    #[must_use]
+    pub fn version_path(&self) -> Option<String> {
+        normalize_path(&self.core_version_path)
+    }
+
+    /// Configuration endpoint path, if enabled
+    #[must_use]
+    pub fn configz_path(&self) -> Option<String> {
+        normalize_path(&self.core_configz_path)
+    }
+
+    /// Log level endpoint path, if enabled
+    #[must_use]
+    pub fn log_level_path(&self) -> Option<String> {
+        normalize_path(&self.core_log_level_path)
+    }
+
+    /// Maintenance mode endpoint path, if enabled
+    #[must_use]
+    pub fn maintenance_path(&self) -> Option<String> {
+        normalize_path(&self.core_maintenance_path)
+    }
+
+    /// Feature flags endpoint path, if enabled
+    #[must_use]
+    pub fn features_path(&self) -> Option<String> {
+        normalize_path(&self.core_features_path)
+    }
+}
+
+pub(crate) fn normalize_path(value: &str) -> Option<String> {
+    let trimmed = value.trim().trim_end_matches('/');
+    if trimmed.is_empty() {
+        None
+    } else if trimmed.starts_with('/') {
+        Some(trimmed.to_string())
+    } else {
+        Some(format!("/{trimmed}"))
+    }
+}
+
+fn default_healthz_path() -> String {
+    "/healthz".to_string()
+}
+fn default_readyz_path() -> String {
+    "/readyz".to_string()
+}
+fn default_version_path() -> String {
+    "/version".to_string()
+}
+fn default_configz_path() -> String {
+    "/configz".to_string()
+}
+fn default_log_level_path() -> String {
+    "/admin/log-level".to_string()
+}
+fn default_maintenance_path() -> String {
+    "/admin/maintenance".to_string()
+}
+fn default_features_path() -> String {
+    "/admin/features".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core_paths() {
+        let config: CoreRoutesConfig = serde_json::from_value(serde_json::json!({
+            "core_healthz_path": "-/health",
+            "core_readyz_path": "/-/ready/",
+            "core_version_path": ""
+        }))
+        .unwrap();
+
+        assert_eq!(config.healthz_path().as_deref(), Some("/-/health"));
+        assert_eq!(config.readyz_path().as_deref(), Some("/-/ready"));
+        assert_eq!(config.version_path(), None);
+
+        let defaults: CoreRoutesConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(defaults.healthz_path().as_deref(), Some("/healthz"));
+        assert_eq!(defaults.readyz_path().as_deref(), Some("/readyz"));
+        assert_eq!(defaults.version_path().as_deref(), Some("/version"));
+        assert_eq!(defaults.configz_path().as_deref(), Some("/configz"));
+        assert_eq!(
+            defaults.log_level_path().as_deref(),
+            Some("/admin/log-level")
+        );
+        assert_eq!(
+            defaults.maintenance_path().as_deref(),
+            Some("/admin/maintenance")
+        );
+        assert_eq!(defaults.features_path().as_deref(), Some("/admin/features"));
+    }
+}
@@ -1,11 +1,14 @@
 //! CORS configuration
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::empty_string_as_none;
 
 /// CORS configuration
-#[derive(Debug, Clone, Deserialize)]
+///
+/// `CORS_ALLOW_ORIGINS` entries are exact origins, `*` (any origin), or
+/// subdomain patterns such as `https://*.preview.example.com`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CorsConfig {
     #[serde(default, deserialize_with = "empty_string_as_none")]
     pub cors_allow_origins: Option<String>,
@@ -16,6 +19,9 @@ pub struct CorsConfig {
     #[serde(default = "default_cors_headers")]
     pub cors_allow_headers: String,
 
+    #[serde(default = "default_cors_expose_headers")]
+    pub cors_expose_headers: String,
+
     #[serde(default)]
     #[serde(deserialize_with = "crate::config::de_bool")]
     pub cors_allow_credentials: bool,
@@ -59,6 +65,16 @@ impl CorsConfig {
             .filter(|h| !h.is_empty())
             .collect()
     }
+
+    /// Parse response headers exposed to browsers
+    #[must_use]
+    pub fn expose_headers(&self) -> Vec<String> {
+        self.cors_expose_headers
+            .split(',')
+            .map(|h| h.trim().to_string())
+            .filter(|h| !h.is_empty())
+            .collect()
+    }
 }
 
 fn default_cors_methods() -> String {
@@ -67,6 +83,9 @@ fn default_cors_methods() -> String {
 fn default_cors_headers() -> String {
     "content-type,authorization".to_string()
 }
+fn default_cors_expose_headers() -> String {
+    "x-request-id,x-response-time".to_string()
+}
 fn default_cors_max_age() -> u64 {
     600
 }
@@ -81,6 +100,7 @@ mod tests {
             cors_allow_origins: Some("http://localhost:3000, http://example.com".to_string()),
             cors_allow_methods: "GET,POST".to_string(),
             cors_allow_headers: "content-type".to_string(),
+            cors_expose_headers: "x-request-id, x-response-time".to_string(),
             cors_allow_credentials: false,
             cors_max_age_seconds: 600,
         };
@@ -90,5 +110,9 @@ mod tests {
             vec!["http://localhost:3000", "http://example.com"]
         );
         assert_eq!(cors.methods(), vec!["GET", "POST"]);
+        assert_eq!(
+            cors.expose_headers(),
+            vec!["x-request-id", "x-response-time"]
+        );
     }
 }
@@ -0,0 +1,198 @@
+//! Database configuration
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// Database connection configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatabaseConfig {
+    /// Connection URL (also readable from `DATABASE_URL_FILE`)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub database_url: Option<String>,
+
+    /// Legacy alias for `DATABASE_URL`
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub db_url: Option<String>,
+
+    /// Maximum connections of the primary pool
+    #[serde(default = "default_max_connections")]
+    #[serde(deserialize_with = "crate::config::de_u32")]
+    pub db_max_connections: u32,
+
+    /// Connections the primary pool keeps open
+    #[serde(default = "default_min_connections")]
+    #[serde(deserialize_with = "crate::config::de_u32")]
+    pub db_min_connections: u32,
+
+    /// Read replica URL; reads use the primary when unset (also readable from
+    /// `DATABASE_READ_URL_FILE`)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub database_read_url: Option<String>,
+
+    /// Maximum connections of the replica pool
+    #[serde(default = "default_max_connections")]
+    #[serde(deserialize_with = "crate::config::de_u32")]
+    pub db_read_max_connections: u32,
+
+    /// Connections the replica pool keeps open
+    #[serde(default = "default_min_connections")]
+    #[serde(deserialize_with = "crate::config::de_u32")]
+    pub db_read_min_connections: u32,
+
+    /// Schema migrations at startup (`true`, `false` or `check`)
+    #[serde(default, deserialize_with = "de_run_migrations")]
+    pub db_run_migrations: RunMigrations,
+}
+
+/// What to do with pending migrations at startup (`DB_RUN_MIGRATIONS`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Default)]
+pub enum RunMigrations {
+    /// Leave the schema alone
+    #[default]
+    #[serde(rename = "false")]
+    Off,
+    /// Apply pending migrations before the listener binds
+    #[serde(rename = "true")]
+    Apply,
+    /// Fail startup if migrations are pending
+    #[serde(rename = "check")]
+    Check,
+}
+
+impl std::fmt::Display for RunMigrations {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "false"),
+            Self::Apply => write!(f, "true"),
+            Self::Check => write!(f, "check"),
+        }
+    }
+}
+
+/// `check`, or any boolean accepted by the other flags
+fn de_run_migrations<'de, D>(deserializer: D) -> Result<RunMigrations, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct Visitor;
+    impl serde::de::Visitor<'_> for Visitor {
+        type Value = RunMigrations;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            formatter.write_str("true, false or check")
+        }
+
+        fn visit_bool<E>(self, v: bool) -> Result<RunMigrations, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(if v {
+                RunMigrations::Apply
+            } else {
+                RunMigrations::Off
+            })
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<RunMigrations, E>
+        where
+            E: serde::de::Error,
+        {
+            match v.trim().to_lowercase().as_str() {
+                "check" => Ok(RunMigrations::Check),
+                "true" | "1" | "yes" | "y" | "on" => Ok(RunMigrations::Apply),
+                "false" | "0" | "no" | "n" | "off" | "" => Ok(RunMigrations::Off),
+                _ => Err(E::custom("expected true, false or check")),
+            }
+        }
+
+        fn visit_string<E>(self, v: String) -> Result<RunMigrations, E>
+        where
+            E: serde::de::Error,
+        {
+            self.visit_str(&v)
+        }
+    }
+
+    deserializer.deserialize_any(Visitor)
+}
+
+impl DatabaseConfig {
+    /// Connection URL, preferring `DATABASE_URL` over `DB_URL`
+    #[must_use]
+    pub fn url(&self) -> Option<&str> {
+        self.database_url.as_deref().or(self.db_url.as_deref())
+    }
+
+    /// Read replica URL (`DATABASE_READ_URL`), if one is configured
+    #[must_use]
+    pub fn read_url(&self) -> Option<&str> {
+        self.database_read_url.as_deref()
+    }
+}
+
+fn default_max_connections() -> u32 {
+    100
+}
+fn default_min_connections() -> u32 {
+    5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_database_url_precedence() {
+        let config: DatabaseConfig = serde_json::from_value(json!({
+            "database_url": "postgres://primary",
+            "db_url": "postgres://legacy"
+        }))
+        .unwrap();
+        assert_eq!(config.url(), Some("postgres://primary"));
+
+        let config: DatabaseConfig =
+            serde_json::from_value(json!({ "db_url": "postgres://legacy" })).unwrap();
+        assert_eq!(config.url(), Some("postgres://legacy"));
+
+        let config: DatabaseConfig = serde_json::from_value(json!({ "database_url": "" })).unwrap();
+        assert_eq!(config.url(), None);
+    }
+
+    #[test]
+    fn test_read_replica_settings() {
+        let config: DatabaseConfig = serde_json::from_value(json!({
+            "database_read_url": "postgres://replica",
+            "db_read_max_connections": "20"
+        }))
+        .unwrap();
+        assert_eq!(config.read_url(), Some("postgres://replica"));
+        assert_eq!(config.db_read_max_connections, 20);
+        assert_eq!(config.db_read_min_connections, 5);
+        assert_eq!(config.db_max_connections, 100);
+
+        let config: DatabaseConfig =
+            serde_json::from_value(json!({ "database_read_url": "" })).unwrap();
+        assert_eq!(config.read_url(), None);
+    }
+
+    #[test]
+    fn test_run_migrations_parsing() {
+        for (value, expected) in [
+            (json!("check"), RunMigrations::Check),
+            (json!("TRUE"), RunMigrations::Apply),
+            (json!("0"), RunMigrations::Off),
+            (json!(true), RunMigrations::Apply),
+        ] {
+            let config: DatabaseConfig =
+                serde_json::from_value(json!({ "db_run_migrations": value })).unwrap();
+            assert_eq!(config.db_run_migrations, expected);
+        }
+
+        let config: DatabaseConfig = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(config.db_run_migrations, RunMigrations::Off);
+        let invalid = json!({ "db_run_migrations": "up" });
+        assert!(serde_json::from_value::<DatabaseConfig>(invalid).is_err());
+    }
+}
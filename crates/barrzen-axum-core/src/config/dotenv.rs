@@ -0,0 +1,141 @@
+//! Layered env-file loading
+//!
+//! Files are layered `.env`, `.env.local`, `.env.{APP_ENV}`,
+//! `.env.{APP_ENV}.local`: later files override earlier ones and real
+//! environment variables always win. `DOTENV_DIR` points at the directory
+//! holding them (default: the working directory).
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+/// Load the env files into the process environment
+///
+/// Returns the variables resolved for the config loader together with the
+/// files that were found, lowest precedence first. The process environment
+/// is populated too, for libraries that read variables such as `RUST_LOG`
+/// directly.
+pub(crate) fn load() -> (BTreeMap<String, String>, Vec<PathBuf>) {
+    let dir = std::env::var_os("DOTENV_DIR").map_or_else(|| PathBuf::from("."), PathBuf::from);
+    let app_env = std::env::var("APP_ENV").ok();
+    let files = dotenv_files(&dir, app_env.as_deref());
+
+    // dotenvy never overrides a variable that is already set, so loading
+    // the most specific file first keeps the layering order
+    for file in files.iter().rev() {
+        if let Err(e) = dotenvy::from_path(file) {
+            tracing::warn!("Failed to load {}: {e}", file.display());
+        }
+    }
+    tracing::debug!(files = ?files, "Loaded env files");
+
+    (layered_vars(&files, std::env::vars()), files)
+}
+
+/// Env files present in `dir`, lowest precedence first
+///
+/// Without an `APP_ENV` from the environment, the value from `.env` or
+/// `.env.local` selects the per-environment files.
+fn dotenv_files(dir: &Path, app_env: Option<&str>) -> Vec<PathBuf> {
+    let existing = |name: &str| {
+        let path = dir.join(name);
+        path.is_file().then_some(path)
+    };
+
+    let mut files: Vec<PathBuf> = [".env", ".env.local"]
+        .into_iter()
+        .filter_map(existing)
+        .collect();
+
+    let app_env = app_env
+        .map(str::to_string)
+        .or_else(|| layered_vars(&files, std::iter::empty()).remove("APP_ENV"))
+        .map(|env| env.trim().to_ascii_lowercase())
+        .filter(|env| !env.is_empty());
+    if let Some(env) = app_env {
+        files.extend(
+            [format!(".env.{env}"), format!(".env.{env}.local")]
+                .iter()
+                .filter_map(|name| existing(name)),
+        );
+    }
+
+    files
+}
+
+/// Merge the files in order, then let `env` override everything
+fn layered_vars(
+    files: &[PathBuf],
+    env: impl IntoIterator<Item = (String, String)>,
+) -> BTreeMap<String, String> {
+    let mut vars = BTreeMap::new();
+    for file in files {
+        if let Ok(entries) = dotenvy::from_path_iter(file) {
+            vars.extend(entries.filter_map(Result::ok));
+        }
+    }
+    vars.extend(env);
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layered_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let write =
+            |name: &str, contents: &str| std::fs::write(dir.path().join(name), contents).unwrap();
+        write(
+            ".env",
+            "APP_ENV=stage\nAPP_NAME=base\nAPP_PORT=1000\nLOG_LEVEL=warn\n",
+        );
+        write(".env.local", "APP_PORT=2000\nLOG_FORMAT=json\n");
+        write(
+            ".env.stage",
+            "LOG_FORMAT=pretty\nLOG_LEVEL=debug\nCORS_MAX_AGE_SECONDS=60\n",
+        );
+        write(".env.stage.local", "CORS_MAX_AGE_SECONDS=90\n");
+        write(".env.prod", "APP_NAME=prod-only\n");
+
+        let files = dotenv_files(dir.path(), None);
+        let names: Vec<_> = files
+            .iter()
+            .map(|file| file.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            vec![".env", ".env.local", ".env.stage", ".env.stage.local"]
+        );
+
+        let env = [("LOG_LEVEL".to_string(), "error".to_string())];
+        let vars = layered_vars(&files, env);
+        assert_eq!(vars["APP_NAME"], "base");
+        assert_eq!(vars["APP_PORT"], "2000");
+        assert_eq!(vars["LOG_FORMAT"], "pretty");
+        assert_eq!(vars["CORS_MAX_AGE_SECONDS"], "90");
+        // Real environment variables always win
+        assert_eq!(vars["LOG_LEVEL"], "error");
+
+        let config = crate::Config::from_vars(vars, None).unwrap();
+        assert_eq!(config.app.app_port, 2000);
+        assert_eq!(config.cors.cors_max_age_seconds, 90);
+    }
+
+    #[test]
+    fn test_app_env_from_environment_selects_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(".env"), "APP_ENV=stage\n").unwrap();
+        std::fs::write(dir.path().join(".env.prod"), "APP_NAME=prod\n").unwrap();
+
+        let files = dotenv_files(dir.path(), Some("prod"));
+        assert_eq!(
+            files,
+            vec![dir.path().join(".env"), dir.path().join(".env.prod")]
+        );
+
+        assert!(dotenv_files(&dir.path().join("missing"), None).is_empty());
+    }
+}
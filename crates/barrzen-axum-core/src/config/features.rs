@@ -1,6 +1,6 @@
 //! Feature toggles (runtime)
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 fn default_true() -> bool {
     true
@@ -10,7 +10,8 @@ fn default_true() -> bool {
 ///
 /// These control what modules are initialized at runtime.
 /// Separate from Cargo features which control compile-time inclusion.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct FeatureFlags {
     #[serde(default = "default_true")]
     #[serde(deserialize_with = "crate::config::de_bool")]
@@ -32,6 +33,11 @@ pub struct FeatureFlags {
     #[serde(deserialize_with = "crate::config::de_bool")]
     pub feature_broker: bool,
 
+    /// Send mail over SMTP (`SMTP_*`, infra `mailer` feature)
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_mailer: bool,
+
     #[serde(default)]
     #[serde(deserialize_with = "crate::config::de_bool")]
     pub feature_openapi: bool,
@@ -48,6 +54,16 @@ pub struct FeatureFlags {
     #[serde(deserialize_with = "crate::config::de_bool")]
     pub feature_otel: bool,
 
+    /// Export HTTP server metrics over OTLP
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_otel_metrics: bool,
+
+    /// Serve tokio-console on `TOKIO_CONSOLE_BIND` (obs `console` feature)
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_tokio_console: bool,
+
     #[serde(default)]
     #[serde(deserialize_with = "crate::config::de_bool")]
     pub feature_cors: bool,
@@ -59,4 +75,55 @@ pub struct FeatureFlags {
     #[serde(default = "default_true")]
     #[serde(deserialize_with = "crate::config::de_bool")]
     pub feature_response_envelope: bool,
+
+    #[serde(default = "default_true")]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_response_time: bool,
+
+    #[serde(default = "default_true")]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_version_endpoint: bool,
+
+    #[serde(default = "default_true")]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_security_headers: bool,
+
+    /// Serve the redacted effective configuration (never in prod unless forced)
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_config_endpoint: bool,
+
+    /// Serve the `/admin/*` endpoints (log level); API-key protected when
+    /// `AUTH_API_KEYS` is set
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_admin_endpoints: bool,
+
+    /// Start in maintenance mode: application routes answer 503 until
+    /// switched off with `PUT /admin/maintenance`
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_maintenance_mode: bool,
+
+    /// Tag successful GET responses with an `ETag` and answer matching
+    /// `If-None-Match` requests with 304
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_etag: bool,
+
+    /// Compress responses (`COMPRESSION_*`)
+    #[serde(default = "default_true")]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_compression: bool,
+
+    /// Answer 503 at once instead of queueing when
+    /// `HTTP_MAX_CONCURRENT_REQUESTS` is reached
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_load_shed: bool,
+
+    /// Resolve the tenant of each request from `TENANT_HEADER`
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub feature_tenant: bool,
 }
@@ -0,0 +1,21 @@
+//! gRPC listener settings
+
+use serde::{Deserialize, Serialize};
+
+/// Where services added with `AppBuilder::add_grpc_service` are served
+///
+/// Unset `GRPC_PORT` multiplexes them with the HTTP routes on `APP_PORT`,
+/// routed by `content-type: application/grpc`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GrpcConfig {
+    /// Dedicated port for gRPC on every `APP_HOST` entry (unset = `APP_PORT`)
+    #[serde(default, deserialize_with = "crate::config::de_opt_u16")]
+    pub grpc_port: Option<u16>,
+}
+
+impl Default for GrpcConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().grpc
+    }
+}
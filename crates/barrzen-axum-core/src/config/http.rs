@@ -1,10 +1,10 @@
 //! HTTP server settings
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 /// HTTP server settings
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HttpConfig {
     #[serde(default = "default_body_limit")]
     #[serde(deserialize_with = "crate::config::de_usize")]
@@ -13,6 +13,27 @@ pub struct HttpConfig {
     #[serde(default = "default_request_timeout")]
     #[serde(deserialize_with = "crate::config::de_u64")]
     pub http_request_timeout_seconds: u64,
+
+    /// Largest response body tagged with an `ETag` (`FEATURE_ETAG`)
+    #[serde(default = "default_etag_max_bytes")]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub http_etag_max_bytes: usize,
+
+    /// `Cache-Control` for tagged responses that do not set one, e.g.
+    /// `private, max-age=0, must-revalidate`
+    #[serde(default, deserialize_with = "super::empty_string_as_none")]
+    pub http_cache_control_default: Option<String>,
+
+    /// Concurrent requests allowed on application routes (0 = unlimited)
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub http_max_concurrent_requests: usize,
+
+    /// Concurrent requests reserved for the core endpoints (health, version,
+    /// admin) while `HTTP_MAX_CONCURRENT_REQUESTS` is set (0 = unlimited)
+    #[serde(default = "default_health_reserved_requests")]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub http_health_reserved_requests: usize,
 }
 
 impl HttpConfig {
@@ -29,3 +50,9 @@ fn default_body_limit() -> usize {
 fn default_request_timeout() -> u64 {
     15
 }
+fn default_etag_max_bytes() -> usize {
+    1_048_576 // 1MB
+}
+fn default_health_reserved_requests() -> usize {
+    8
+}
@@ -0,0 +1,42 @@
+//! Outbound HTTP client configuration
+
+use serde::{Deserialize, Serialize};
+
+/// Settings for the outbound HTTP client (`barrzen-axum-infra`,
+/// `http-client` feature)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HttpClientConfig {
+    /// Whole-request timeout, from connecting until the body is read
+    #[serde(default = "default_timeout_seconds")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub http_client_timeout_seconds: u64,
+
+    /// Timeout of establishing a connection
+    #[serde(default = "default_connect_timeout_seconds")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub http_client_connect_timeout_seconds: u64,
+
+    /// Idle connections kept open per host
+    #[serde(default = "default_pool_max_idle")]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub http_client_pool_max_idle: usize,
+}
+
+impl Default for HttpClientConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().http_client
+    }
+}
+
+fn default_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_connect_timeout_seconds() -> u64 {
+    5
+}
+
+fn default_pool_max_idle() -> usize {
+    32
+}
@@ -0,0 +1,74 @@
+//! Idempotency-Key configuration
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// Settings for the idempotency layer (`barrzen-axum-infra`, `idempotency`
+/// feature)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IdempotencyConfig {
+    /// How long a stored response is replayed for the same key
+    #[serde(default = "default_ttl_seconds")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub idempotency_ttl_seconds: u64,
+
+    /// Methods honoring `Idempotency-Key` (comma-separated)
+    #[serde(default = "default_methods")]
+    pub idempotency_methods: String,
+
+    /// Path prefixes honoring `Idempotency-Key` (comma-separated, unset for
+    /// every route the layer wraps)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub idempotency_paths: Option<String>,
+
+    /// Responses larger than this are returned but not stored
+    #[serde(default = "default_max_response_bytes")]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub idempotency_max_response_bytes: usize,
+}
+
+impl Default for IdempotencyConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().idempotency
+    }
+}
+
+impl IdempotencyConfig {
+    /// Upper-cased methods from `IDEMPOTENCY_METHODS`
+    #[must_use]
+    pub fn methods(&self) -> Vec<String> {
+        split_list(&self.idempotency_methods)
+            .map(str::to_ascii_uppercase)
+            .collect()
+    }
+
+    /// Path prefixes from `IDEMPOTENCY_PATHS`; empty means every path
+    #[must_use]
+    pub fn paths(&self) -> Vec<String> {
+        self.idempotency_paths
+            .as_deref()
+            .map(|paths| split_list(paths).map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+}
+
+fn default_ttl_seconds() -> u64 {
+    86_400
+}
+
+fn default_methods() -> String {
+    "POST".to_string()
+}
+
+fn default_max_response_bytes() -> usize {
+    1024 * 1024
+}
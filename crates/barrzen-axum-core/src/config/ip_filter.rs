@@ -0,0 +1,80 @@
+//! IP filter configuration
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// IP allowlist/denylist configuration
+///
+/// The filter is applied to every route when either list is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpFilterConfig {
+    /// Allowed CIDR blocks or addresses (comma-separated, IPv4 and IPv6)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub ip_allowlist: Option<String>,
+
+    /// Denied CIDR blocks or addresses (comma-separated, IPv4 and IPv6)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub ip_denylist: Option<String>,
+
+    /// Filter on the resolved client IP (forwarding headers from
+    /// `TRUSTED_PROXIES`) instead of the socket address
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub ip_filter_trust_forwarded: bool,
+}
+
+impl IpFilterConfig {
+    /// Parse allowed entries into a vector
+    #[must_use]
+    pub fn allowlist(&self) -> Vec<String> {
+        split_list(self.ip_allowlist.as_deref())
+    }
+
+    /// Parse denied entries into a vector
+    #[must_use]
+    pub fn denylist(&self) -> Vec<String> {
+        split_list(self.ip_denylist.as_deref())
+    }
+
+    /// Whether any list is configured
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        !self.allowlist().is_empty() || !self.denylist().is_empty()
+    }
+}
+
+fn split_list(value: Option<&str>) -> Vec<String> {
+    value
+        .map(|s| {
+            s.split(',')
+                .map(|entry| entry.trim().to_string())
+                .filter(|entry| !entry.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_ip_filter_lists_parsing() {
+        let config: IpFilterConfig = serde_json::from_value(json!({
+            "ip_allowlist": "10.0.0.0/8, fd00::/8,",
+            "ip_denylist": "",
+            "ip_filter_trust_forwarded": "true"
+        }))
+        .unwrap();
+
+        assert_eq!(config.allowlist(), vec!["10.0.0.0/8", "fd00::/8"]);
+        assert!(config.denylist().is_empty());
+        assert!(config.ip_filter_trust_forwarded);
+        assert!(config.is_enabled());
+
+        let config: IpFilterConfig = serde_json::from_value(json!({})).unwrap();
+        assert!(!config.is_enabled());
+    }
+}
@@ -1,15 +1,20 @@
 //! Logging configuration
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::empty_string_as_none;
 
 /// Logging configuration
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LoggingConfig {
+    /// Base level for every module, e.g. `info`
     #[serde(default = "default_log_level")]
     pub log_level: String,
 
+    /// Per-module overrides on top of `log_level`, e.g. `hyper=warn,sea_orm=debug`
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub log_directives: Option<String>,
+
     #[serde(default)]
     pub log_backend: LogBackend,
 
@@ -29,10 +34,213 @@ pub struct LoggingConfig {
 
     #[serde(default = "default_headers_denylist")]
     pub request_log_headers_denylist: String,
+
+    /// Path patterns to redact in logs and traces, e.g. `/password-reset/*=mask`
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub request_log_path_redactions: Option<String>,
+
+    /// Requests slower than this are logged at `warn` (0 = disabled)
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub request_log_slow_threshold_ms: u64,
+
+    /// When request and response bodies are logged (never in prod unless
+    /// `REQUEST_LOG_BODY_FORCE=true`)
+    #[serde(default)]
+    pub request_log_body: RequestLogBody,
+
+    /// Logged bodies are truncated to this many bytes
+    #[serde(default = "default_request_log_body_max_bytes")]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub request_log_body_max_bytes: usize,
+
+    /// JSON keys whose values are logged as `***` (case-insensitive)
+    #[serde(default = "default_request_log_body_redact_keys")]
+    pub request_log_body_redact_keys: String,
+
+    /// Log bodies in prod as well
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub request_log_body_force: bool,
+
+    /// Where log lines go: stdout, rolling files or both
+    #[serde(default)]
+    pub log_output: LogOutput,
+
+    /// Directory for log files (`LOG_OUTPUT=file|both`)
+    #[serde(default = "default_log_file_dir")]
+    pub log_file_dir: String,
+
+    /// Log file name prefix, e.g. `app` for `app.2026-01-02.log`
+    #[serde(default = "default_log_file_prefix")]
+    pub log_file_prefix: String,
+
+    #[serde(default)]
+    pub log_file_rotation: LogRotation,
+
+    /// Rotate once the file reaches this size (`LOG_FILE_ROTATION=size`)
+    #[serde(default = "default_log_file_max_size_mb")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub log_file_max_size_mb: u64,
+
+    /// Rotated log files to keep; older ones are deleted (0 = keep all)
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub log_file_keep: u64,
+
+    /// Records buffered for the `fast_log` writer thread
+    #[serde(default = "default_fast_log_chunk_size")]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub fast_log_chunk_size: usize,
+
+    /// tokio-console gRPC address (`FEATURE_TOKIO_CONSOLE=true`)
+    #[serde(default = "default_tokio_console_bind")]
+    pub tokio_console_bind: String,
+}
+
+impl LoggingConfig {
+    /// Parse the request header allowlist (lowercased)
+    #[must_use]
+    pub fn headers_allowlist(&self) -> Vec<String> {
+        self.request_log_headers_allowlist
+            .as_deref()
+            .map(split_header_list)
+            .unwrap_or_default()
+    }
+
+    /// Log filter directives for `tracing`'s `EnvFilter`
+    ///
+    /// `log_level` comes first, then `log_directives`, then `rust_log`
+    /// (`RUST_LOG`, if set). A later directive for the same target replaces
+    /// an earlier one, so `RUST_LOG=debug` raises the base level while the
+    /// module overrides stay in place.
+    #[must_use]
+    pub fn filter_directives(&self, rust_log: Option<&str>) -> String {
+        [
+            Some(self.log_level.as_str()),
+            self.log_directives.as_deref(),
+            rust_log,
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|list| list.split(','))
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+
+    /// Parse `REQUEST_LOG_PATH_REDACTIONS` into `(pattern, strategy)` rules
+    ///
+    /// Entries are `pattern=strategy`, comma-separated. Entries that are not
+    /// `pattern=mask|hash` are skipped (and reported by [`Config::validate`]).
+    ///
+    /// [`Config::validate`]: crate::Config::validate
+    #[must_use]
+    pub fn path_redactions(&self) -> Vec<(String, PathRedaction)> {
+        self.path_redaction_entries()
+            .filter_map(|entry| parse_path_redaction(entry).ok())
+            .collect()
+    }
+
+    /// `REQUEST_LOG_PATH_REDACTIONS` entries that cannot be parsed
+    pub(crate) fn invalid_path_redactions(&self) -> Vec<String> {
+        self.path_redaction_entries()
+            .filter(|entry| parse_path_redaction(entry).is_err())
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn path_redaction_entries(&self) -> impl Iterator<Item = &str> {
+        self.request_log_path_redactions
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+    }
+
+    /// Parse the request header denylist (lowercased)
+    #[must_use]
+    pub fn headers_denylist(&self) -> Vec<String> {
+        split_header_list(&self.request_log_headers_denylist)
+    }
+
+    /// Parse `REQUEST_LOG_BODY_REDACT_KEYS` (lowercased)
+    #[must_use]
+    pub fn body_redact_keys(&self) -> Vec<String> {
+        split_header_list(&self.request_log_body_redact_keys)
+    }
+}
+
+impl LogOutput {
+    /// Whether log lines are written to stdout
+    #[must_use]
+    pub fn to_stdout(self) -> bool {
+        matches!(self, Self::Stdout | Self::Both)
+    }
+
+    /// Whether log lines are written to files
+    #[must_use]
+    pub fn to_file(self) -> bool {
+        matches!(self, Self::File | Self::Both)
+    }
+}
+
+fn parse_path_redaction(entry: &str) -> Result<(String, PathRedaction), ()> {
+    let (pattern, strategy) = entry.rsplit_once('=').ok_or(())?;
+    let pattern = pattern.trim();
+    if !pattern.starts_with('/') {
+        return Err(());
+    }
+    let strategy = match strategy.trim().to_ascii_lowercase().as_str() {
+        "mask" => PathRedaction::Mask,
+        "hash" => PathRedaction::Hash,
+        _ => return Err(()),
+    };
+    Ok((pattern.to_string(), strategy))
+}
+
+fn split_header_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|h| h.trim().to_ascii_lowercase())
+        .filter(|h| !h.is_empty())
+        .collect()
+}
+
+/// How a redacted path segment is logged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PathRedaction {
+    /// Replace the segment with `****`
+    Mask,
+    /// Replace the segment with the first 8 hex digits of its SHA-256
+    Hash,
+}
+
+impl std::fmt::Display for PathRedaction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mask => write!(f, "mask"),
+            Self::Hash => write!(f, "hash"),
+        }
+    }
+}
+
+/// When `RequestLogLayer` logs bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestLogBody {
+    #[default]
+    Off,
+    /// Only for responses with status >= 400
+    OnError,
+    Always,
 }
 
 /// Log format type
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum LogFormat {
     #[default]
@@ -42,7 +250,7 @@ pub enum LogFormat {
 }
 
 /// Log backend type
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
 pub enum LogBackend {
     #[default]
@@ -51,9 +259,140 @@ pub enum LogBackend {
     FastLog,
 }
 
+/// Log output destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogOutput {
+    #[default]
+    Stdout,
+    File,
+    Both,
+}
+
+/// Log file rotation policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    /// Rotate at `LOG_FILE_MAX_SIZE_MB`
+    Size,
+}
+
 fn default_log_level() -> String {
     "info".to_string()
 }
 fn default_headers_denylist() -> String {
     "authorization,cookie,set-cookie,x-api-key".to_string()
 }
+fn default_request_log_body_max_bytes() -> usize {
+    2048
+}
+fn default_request_log_body_redact_keys() -> String {
+    "password,token,card_number".to_string()
+}
+fn default_log_file_dir() -> String {
+    "logs".to_string()
+}
+fn default_log_file_prefix() -> String {
+    "app".to_string()
+}
+fn default_log_file_max_size_mb() -> u64 {
+    100
+}
+fn default_fast_log_chunk_size() -> usize {
+    100_000
+}
+fn default_tokio_console_bind() -> String {
+    "127.0.0.1:6669".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_lists_parsing() {
+        let config: LoggingConfig = serde_json::from_value(serde_json::json!({
+            "request_log_headers_allowlist": "X-Tenant-Id, ,user-agent",
+            "request_log_headers_denylist": "Authorization,cookie"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            config.headers_allowlist(),
+            vec!["x-tenant-id", "user-agent"]
+        );
+        assert_eq!(config.headers_denylist(), vec!["authorization", "cookie"]);
+    }
+
+    #[test]
+    fn test_log_file_settings() {
+        let defaults: LoggingConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(defaults.log_output, LogOutput::Stdout);
+        assert_eq!(defaults.log_file_dir, "logs");
+        assert_eq!(defaults.log_file_rotation, LogRotation::Daily);
+        assert_eq!(defaults.log_file_max_size_mb, 100);
+        assert_eq!(defaults.log_file_keep, 0);
+        assert_eq!(defaults.fast_log_chunk_size, 100_000);
+
+        let config: LoggingConfig = serde_json::from_value(serde_json::json!({
+            "log_output": "both",
+            "log_file_rotation": "size",
+            "log_file_max_size_mb": "5",
+            "log_file_keep": "3"
+        }))
+        .unwrap();
+        assert!(config.log_output.to_stdout() && config.log_output.to_file());
+        assert_eq!(config.log_file_rotation, LogRotation::Size);
+        assert_eq!(config.log_file_max_size_mb, 5);
+        assert_eq!(config.log_file_keep, 3);
+        assert!(!LogOutput::File.to_stdout());
+    }
+
+    #[test]
+    fn test_filter_directives_precedence() {
+        let defaults: LoggingConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(defaults.filter_directives(None), "info");
+
+        let config: LoggingConfig = serde_json::from_value(serde_json::json!({
+            "log_level": "warn",
+            "log_directives": "hyper=warn, sea_orm=debug,,tower_http=off"
+        }))
+        .unwrap();
+        assert_eq!(
+            config.filter_directives(None),
+            "warn,hyper=warn,sea_orm=debug,tower_http=off"
+        );
+        assert_eq!(
+            config.filter_directives(Some("debug,hyper=trace")),
+            "warn,hyper=warn,sea_orm=debug,tower_http=off,debug,hyper=trace"
+        );
+        assert_eq!(
+            config.filter_directives(Some("")),
+            config.filter_directives(None)
+        );
+    }
+
+    #[test]
+    fn test_path_redactions_parsing() {
+        let config: LoggingConfig = serde_json::from_value(serde_json::json!({
+            "request_log_path_redactions":
+                "/password-reset/*=mask, /invites/{code}/accept=HASH,/x=drop,nopath=mask"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            config.path_redactions(),
+            vec![
+                ("/password-reset/*".to_string(), PathRedaction::Mask),
+                ("/invites/{code}/accept".to_string(), PathRedaction::Hash),
+            ]
+        );
+        assert_eq!(
+            config.invalid_path_redactions(),
+            vec!["/x=drop", "nopath=mask"]
+        );
+    }
+}
@@ -0,0 +1,80 @@
+//! Mail (SMTP) configuration
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// SMTP connection for outgoing mail (`barrzen-axum-infra` `mailer`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MailConfig {
+    /// SMTP server host name, e.g. `smtp.example.com`
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub smtp_host: Option<String>,
+
+    #[serde(default = "default_port")]
+    #[serde(deserialize_with = "crate::config::de_u16")]
+    pub smtp_port: u16,
+
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub smtp_user: Option<String>,
+
+    /// Password for `SMTP_USER` (also readable from `SMTP_PASSWORD_FILE`)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub smtp_password: Option<String>,
+
+    /// Sender of every message, e.g. `Orders <orders@example.com>`
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub smtp_from: Option<String>,
+
+    #[serde(default)]
+    pub smtp_tls: SmtpTls,
+
+    /// Connections kept open to the server
+    #[serde(default = "default_pool_size")]
+    #[serde(deserialize_with = "crate::config::de_u32")]
+    pub smtp_pool_size: u32,
+
+    #[serde(default = "default_timeout")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub smtp_timeout_seconds: u64,
+}
+
+impl Default for MailConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().mail
+    }
+}
+
+/// Transport security of the SMTP connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text, for local relays and test servers
+    None,
+    /// Upgrade with `STARTTLS`, usually on port 587
+    #[default]
+    Starttls,
+    /// TLS from the first byte, usually on port 465
+    Implicit,
+}
+
+impl std::fmt::Display for SmtpTls {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Starttls => write!(f, "starttls"),
+            Self::Implicit => write!(f, "implicit"),
+        }
+    }
+}
+
+fn default_port() -> u16 {
+    587
+}
+fn default_pool_size() -> u32 {
+    4
+}
+fn default_timeout() -> u64 {
+    10
+}
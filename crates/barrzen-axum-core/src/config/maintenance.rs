@@ -0,0 +1,16 @@
+//! Maintenance mode configuration
+
+use serde::{Deserialize, Serialize};
+
+/// Settings for maintenance mode (`FEATURE_MAINTENANCE_MODE`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceConfig {
+    /// `Retry-After` sent with the 503 while in maintenance mode
+    #[serde(default = "default_retry_after_seconds")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub maintenance_retry_after_seconds: u64,
+}
+
+fn default_retry_after_seconds() -> u64 {
+    300
+}
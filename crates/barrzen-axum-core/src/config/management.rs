@@ -0,0 +1,77 @@
+//! Management listener settings
+
+use serde::{Deserialize, Serialize};
+
+/// Separate listener for the health, version and admin endpoints
+///
+/// Off until `MANAGEMENT_PORT` is set, so ingress can route the application
+/// port only.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagementConfig {
+    /// Addresses of the management listener, same forms as `APP_HOST`
+    #[serde(default = "default_management_host")]
+    pub management_host: String,
+
+    /// Port of the management listener (unset = core endpoints stay on `APP_PORT`)
+    #[serde(default, deserialize_with = "crate::config::de_opt_u16")]
+    pub management_port: Option<u16>,
+
+    /// Remove /healthz, /readyz and /version from the application port
+    ///
+    /// Admin and management routes never stay on the application port.
+    #[serde(default = "default_true")]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub management_exclusive: bool,
+}
+
+impl Default for ManagementConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().management
+    }
+}
+
+impl ManagementConfig {
+    /// Whether a management listener is configured
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.management_port.is_some()
+    }
+
+    /// Entries of `management_host`, as [`AppConfig::hosts`](super::AppConfig::hosts)
+    #[must_use]
+    pub fn hosts(&self) -> Vec<&str> {
+        super::app::split_hosts(&self.management_host)
+    }
+}
+
+fn default_management_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_management_defaults_and_parsing() {
+        let defaults: ManagementConfig = serde_json::from_str("{}").unwrap();
+        assert!(!defaults.is_enabled());
+        assert!(defaults.management_exclusive);
+        assert_eq!(defaults.hosts(), vec!["0.0.0.0"]);
+
+        let config: ManagementConfig = serde_json::from_value(serde_json::json!({
+            "management_host": "127.0.0.1, [::1]",
+            "management_port": "9090",
+            "management_exclusive": "false"
+        }))
+        .unwrap();
+        assert_eq!(config.management_port, Some(9090));
+        assert!(!config.management_exclusive);
+        assert_eq!(config.hosts(), vec!["127.0.0.1", "::1"]);
+    }
+}
@@ -4,27 +4,97 @@
 //! Provides comprehensive configuration for Axum applications.
 
 mod app;
+mod audit;
+mod auth;
 mod banner;
+mod broker;
+mod builder;
 mod cache;
+mod circuit_breaker;
+mod client_ip;
+mod compression;
+mod core_routes;
 mod cors;
+mod database;
+mod dotenv;
 mod features;
+mod grpc;
 mod http;
+mod http_client;
+mod idempotency;
+mod ip_filter;
 mod logging;
+mod mail;
+mod maintenance;
+mod management;
+mod openapi;
+mod otel;
+mod outbox;
+mod readiness;
+mod redact;
+mod request_id;
+mod response_cache;
+mod search;
+mod security;
+mod session;
+mod spec;
+mod sse;
+mod startup;
+mod strict;
+mod tenant;
+mod upload;
+mod validate;
+mod ws;
 
 pub use app::{AppConfig, Environment};
-pub use banner::BannerConfig;
+pub use audit::AuditConfig;
+pub use auth::AuthConfig;
+pub use banner::{BannerConfig, BannerStyle};
+pub use broker::{BrokerBackend, BrokerConfig};
+pub use builder::ConfigBuilder;
 pub use cache::{CacheBackend, CacheConfig};
+pub use circuit_breaker::CircuitBreakerConfig;
+pub use client_ip::ClientIpConfig;
+pub use compression::CompressionConfig;
+pub use core_routes::CoreRoutesConfig;
 pub use cors::CorsConfig;
+pub use database::{DatabaseConfig, RunMigrations};
 pub use features::FeatureFlags;
+pub use grpc::GrpcConfig;
 pub use http::HttpConfig;
-pub use logging::{LogBackend, LogFormat, LoggingConfig};
-
-use serde::Deserialize;
+pub use http_client::HttpClientConfig;
+pub use idempotency::IdempotencyConfig;
+pub use ip_filter::IpFilterConfig;
+pub use logging::{
+    LogBackend, LogFormat, LogOutput, LogRotation, LoggingConfig, PathRedaction, RequestLogBody,
+};
+pub use mail::{MailConfig, SmtpTls};
+pub use maintenance::MaintenanceConfig;
+pub use management::ManagementConfig;
+pub use openapi::{OpenApiAuth, OpenApiConfig};
+pub use otel::{OtelConfig, OtlpProtocol, TraceSampler};
+pub use outbox::OutboxConfig;
+pub use readiness::ReadinessConfig;
+pub use redact::redact_value;
+pub use request_id::{RequestIdConfig, RequestIdFormat};
+pub use response_cache::ResponseCacheConfig;
+pub use search::SearchConfig;
+pub use security::SecurityHeadersConfig;
+pub use session::{SessionConfig, SessionSameSite};
+pub use spec::{EnvVarSpec, EnvVarType};
+pub use sse::SseConfig;
+pub use startup::StartupConfig;
+pub use strict::UnrecognizedVar;
+pub use tenant::TenantConfig;
+pub use upload::UploadConfig;
+pub use ws::WsConfig;
+
+use serde::{Deserialize, Serialize};
 
 /// Main application configuration
 ///
 /// This aggregates all configuration sections and can be loaded from environment variables.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Config {
     #[serde(flatten)]
     pub app: AppConfig,
@@ -35,33 +105,231 @@ pub struct Config {
     #[serde(flatten)]
     pub http: HttpConfig,
 
+    #[serde(flatten)]
+    pub http_client: HttpClientConfig,
+
+    #[serde(flatten)]
+    pub request_id: RequestIdConfig,
+
+    #[serde(flatten)]
+    pub compression: CompressionConfig,
+
+    #[serde(flatten)]
+    pub sse: SseConfig,
+
+    #[serde(flatten)]
+    pub ws: WsConfig,
+
     #[serde(flatten)]
     pub logging: LoggingConfig,
 
+    #[serde(flatten)]
+    pub audit: AuditConfig,
+
     #[serde(flatten)]
     pub cache: CacheConfig,
 
+    #[serde(flatten)]
+    pub circuit_breaker: CircuitBreakerConfig,
+
     #[serde(flatten)]
     pub cors: CorsConfig,
 
     #[serde(flatten)]
     pub banner: BannerConfig,
+
+    #[serde(flatten)]
+    pub core_routes: CoreRoutesConfig,
+
+    #[serde(flatten)]
+    pub management: ManagementConfig,
+
+    #[serde(flatten)]
+    pub grpc: GrpcConfig,
+
+    #[serde(flatten)]
+    pub readiness: ReadinessConfig,
+
+    #[serde(flatten)]
+    pub startup: StartupConfig,
+
+    #[serde(flatten)]
+    pub maintenance: MaintenanceConfig,
+
+    #[serde(flatten)]
+    pub idempotency: IdempotencyConfig,
+
+    #[serde(flatten)]
+    pub response_cache: ResponseCacheConfig,
+
+    #[serde(flatten)]
+    pub upload: UploadConfig,
+
+    #[serde(flatten)]
+    pub openapi: OpenApiConfig,
+
+    #[serde(flatten)]
+    pub auth: AuthConfig,
+
+    #[serde(flatten)]
+    pub session: SessionConfig,
+
+    #[serde(flatten)]
+    pub ip_filter: IpFilterConfig,
+
+    #[serde(flatten)]
+    pub client_ip: ClientIpConfig,
+
+    #[serde(flatten)]
+    pub tenant: TenantConfig,
+
+    #[serde(flatten)]
+    pub security: SecurityHeadersConfig,
+
+    #[serde(flatten)]
+    pub database: DatabaseConfig,
+
+    #[serde(flatten)]
+    pub search: SearchConfig,
+
+    #[serde(flatten)]
+    pub broker: BrokerConfig,
+
+    #[serde(flatten)]
+    pub mail: MailConfig,
+
+    #[serde(flatten)]
+    pub outbox: OutboxConfig,
+
+    #[serde(flatten)]
+    pub otel: OtelConfig,
 }
 
+/// Variables that may be read from a file named by `<NAME>_FILE`
+///
+/// Mounted secrets (Docker/Kubernetes) keep credentials out of the
+/// environment. When both are set, the file wins.
+pub const SECRET_FILE_VARS: [&str; 10] = [
+    "DATABASE_URL",
+    "DB_URL",
+    "DATABASE_READ_URL",
+    "CACHE_REDIS_URL",
+    "AUTH_JWT_SECRET",
+    "AUTH_API_KEYS",
+    "MEILI_API_KEY",
+    "NATS_URL",
+    "OPENAPI_BASIC_PASSWORD",
+    "SMTP_PASSWORD",
+];
+
 impl Config {
-    /// Load configuration from environment variables
+    /// Load configuration from environment variables and validate it
     ///
     /// # Errors
-    /// Returns error if required environment variables are missing or invalid.
+    /// Returns error if required environment variables are missing or invalid,
+    /// or if [`Config::validate`] reports problems.
     pub fn from_env() -> Result<Self, ConfigError> {
-        // Load .env file if present (ignore errors for production)
-        let _ = dotenvy::dotenv();
+        let config = Self::from_env_unvalidated()?;
+        config.validate()?;
+        Ok(config)
+    }
 
-        envy::from_env::<Self>().map_err(|e| ConfigError::Parse(e.to_string()))
+    /// Load configuration from environment variables without [`Config::validate`]
+    ///
+    /// Honours `ENV_PREFIX` like [`Config::from_env_prefixed`].
+    ///
+    /// # Errors
+    /// Returns error if required environment variables are missing or invalid.
+    pub fn from_env_unvalidated() -> Result<Self, ConfigError> {
+        Self::load(None)
+    }
+
+    /// Load and validate configuration from variables namespaced by `prefix`
+    ///
+    /// With `prefix = "ORDERS"`, `ORDERS_APP_PORT` is read as `APP_PORT`.
+    /// Unprefixed variables are used when the prefixed one is absent, so
+    /// shared settings can stay unprefixed.
+    ///
+    /// # Errors
+    /// Returns error if variables are invalid or [`Config::validate`] fails.
+    pub fn from_env_prefixed(prefix: &str) -> Result<Self, ConfigError> {
+        let config = Self::load(Some(prefix))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load the env files, then build the configuration from the environment
+    ///
+    /// Without an explicit prefix, `ENV_PREFIX` is used.
+    fn load(prefix: Option<&str>) -> Result<Self, ConfigError> {
+        let (vars, files) = dotenv::load();
+        let prefix = prefix
+            .map(str::to_string)
+            .or_else(|| vars.get("ENV_PREFIX").cloned());
+        let keys: Vec<String> = vars.keys().cloned().collect();
+
+        let mut config = Self::from_vars(vars, prefix.as_deref())?;
+        config.check_unrecognized(keys.iter().map(String::as_str))?;
+        config.app.dotenv_files = files
+            .iter()
+            .map(|file| file.display().to_string())
+            .collect();
+        Ok(config)
+    }
+
+    /// Report variables under a config prefix that match no field
+    ///
+    /// Fails in strict mode (see [`Config::is_strict`]) and warns otherwise.
+    fn check_unrecognized<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(), ConfigError> {
+        let unrecognized = strict::unrecognized_vars(keys, self.app.env_prefix.as_deref());
+        if unrecognized.is_empty() {
+            return Ok(());
+        }
+        if self.is_strict() {
+            return Err(ConfigError::Unrecognized(unrecognized));
+        }
+        for var in &unrecognized {
+            tracing::warn!("Unrecognized config variable {var}");
+        }
+        Ok(())
+    }
+
+    /// Build the configuration from `(name, value)` pairs
+    pub(crate) fn from_vars(
+        vars: impl IntoIterator<Item = (String, String)>,
+        prefix: Option<&str>,
+    ) -> Result<Self, ConfigError> {
+        let prefix = prefix.map(normalize_prefix).filter(|p| !p.is_empty());
+
+        let mut resolved = std::collections::BTreeMap::new();
+        let mut prefixed = Vec::new();
+        for (key, value) in vars {
+            match strip_env_prefix(&key, prefix.as_deref()) {
+                Some(name) => prefixed.push((name.to_string(), value)),
+                None => {
+                    resolved.insert(key, value);
+                }
+            }
+        }
+        // Prefixed values win over unprefixed ones
+        resolved.extend(prefixed);
+        resolve_secret_files(&mut resolved)?;
+
+        let mut config: Self =
+            envy::from_iter(resolved).map_err(|e| ConfigError::Parse(e.to_string()))?;
+        config.app.env_prefix = prefix;
+        Ok(config)
     }
 
     /// Get the socket address to bind to
+    ///
+    /// Only the first `APP_HOST` entry is used, and a hostname yields
+    /// `0.0.0.0`.
     #[must_use]
+    #[deprecated(note = "APP_HOST may list several hosts; use `listen_urls` or `AppBuilder::bind`")]
     pub fn socket_addr(&self) -> std::net::SocketAddr {
         std::net::SocketAddr::new(
             self.app
@@ -72,11 +340,70 @@ impl Config {
         )
     }
 
+    /// `http://host:port` for every `APP_HOST` entry, hostnames unresolved
+    #[must_use]
+    pub fn listen_urls(&self) -> Vec<String> {
+        host_urls(&self.app.hosts(), self.app.app_port)
+    }
+
+    /// `http://host:port` for every `MANAGEMENT_HOST` entry; empty when
+    /// `MANAGEMENT_PORT` is unset
+    #[must_use]
+    pub fn management_urls(&self) -> Vec<String> {
+        self.management
+            .management_port
+            .map(|port| host_urls(&self.management.hosts(), port))
+            .unwrap_or_default()
+    }
+
+    /// `http://host:port` for every `APP_HOST` entry on `GRPC_PORT`; empty
+    /// when `GRPC_PORT` is unset
+    #[must_use]
+    pub fn grpc_urls(&self) -> Vec<String> {
+        self.grpc
+            .grpc_port
+            .map(|port| host_urls(&self.app.hosts(), port))
+            .unwrap_or_default()
+    }
+
+    /// Whether unrecognized variables fail loading (`CONFIG_STRICT`)
+    ///
+    /// Defaults to on in dev and off in stage and prod.
+    #[must_use]
+    pub fn is_strict(&self) -> bool {
+        self.app
+            .config_strict
+            .unwrap_or(self.app.app_env == Environment::Dev)
+    }
+
     /// Check if running in production mode
     #[must_use]
     pub fn is_production(&self) -> bool {
         self.app.app_env == Environment::Prod
     }
+
+    /// Request headers that must never be logged (lowercased)
+    ///
+    /// `REQUEST_LOG_HEADERS_DENYLIST` plus the API key header.
+    #[must_use]
+    pub fn sensitive_headers(&self) -> Vec<String> {
+        let mut headers = self.logging.headers_denylist();
+        let api_key_header = self.auth.auth_api_key_header.trim().to_ascii_lowercase();
+        if !api_key_header.is_empty() && !headers.contains(&api_key_header) {
+            headers.push(api_key_header);
+        }
+        headers
+    }
+
+    /// `REQUEST_LOG_BODY`, forced off in prod unless `REQUEST_LOG_BODY_FORCE=true`
+    #[must_use]
+    pub fn request_log_body(&self) -> RequestLogBody {
+        if self.is_production() && !self.logging.request_log_body_force {
+            RequestLogBody::Off
+        } else {
+            self.logging.request_log_body
+        }
+    }
 }
 
 /// Configuration error types
@@ -87,6 +414,75 @@ pub enum ConfigError {
 
     #[error("Configuration validation error: {0}")]
     Validation(String),
+
+    #[error(
+        "Unrecognized config variables (CONFIG_STRICT=false only warns): {}",
+        join_vars(.0)
+    )]
+    Unrecognized(Vec<UnrecognizedVar>),
+
+    #[error("Failed to read {name}_FILE from {path}: {source}")]
+    SecretFile {
+        name: &'static str,
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+fn join_vars(vars: &[UnrecognizedVar]) -> String {
+    vars.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Replace each [`SECRET_FILE_VARS`] entry with the trimmed contents of
+/// `<NAME>_FILE` when that variable is set
+fn resolve_secret_files(
+    vars: &mut std::collections::BTreeMap<String, String>,
+) -> Result<(), ConfigError> {
+    for name in SECRET_FILE_VARS {
+        let Some(path) = vars
+            .get(&format!("{name}_FILE"))
+            .map(|path| path.trim().to_string())
+            .filter(|path| !path.is_empty())
+        else {
+            continue;
+        };
+
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|source| ConfigError::SecretFile { name, path, source })?;
+        vars.insert(name.to_string(), contents.trim().to_string());
+    }
+    Ok(())
+}
+
+/// Normalize an env prefix to `UPPER_` form (`orders` -> `ORDERS_`)
+fn normalize_prefix(prefix: &str) -> String {
+    let prefix = prefix.trim().trim_end_matches('_');
+    if prefix.is_empty() {
+        String::new()
+    } else {
+        format!("{}_", prefix.to_ascii_uppercase())
+    }
+}
+
+/// Variable name without the env prefix, if `key` carries it
+pub(crate) fn strip_env_prefix<'a>(key: &'a str, prefix: Option<&str>) -> Option<&'a str> {
+    prefix
+        .and_then(|prefix| key.strip_prefix(prefix))
+        .filter(|name| !name.is_empty())
+}
+
+fn host_urls(hosts: &[&str], port: u16) -> Vec<String> {
+    hosts
+        .iter()
+        .map(|host| match host.parse::<std::net::IpAddr>() {
+            Ok(ip) => format!("http://{}", std::net::SocketAddr::new(ip, port)),
+            Err(_) => format!("http://{host}:{port}"),
+        })
+        .collect()
 }
 
 /// Redact sensitive values for logging
@@ -130,7 +526,7 @@ macro_rules! de_number {
                     if v < 0 {
                         return Err(E::custom("negative value not allowed"));
                     }
-                    <$ty>::try_from(v as u64).map_err(|_| E::custom("out of range"))
+                    <$ty>::try_from(v.cast_unsigned()).map_err(|_| E::custom("out of range"))
                 }
 
                 fn visit_str<E>(self, v: &str) -> Result<$ty, E>
@@ -156,6 +552,7 @@ macro_rules! de_number {
 }
 
 de_number!(de_u16, u16);
+de_number!(de_u32, u32);
 de_number!(de_u64, u64);
 de_number!(de_usize, usize);
 
@@ -164,7 +561,7 @@ where
     D: serde::Deserializer<'de>,
 {
     struct Visitor;
-    impl<'de> serde::de::Visitor<'de> for Visitor {
+    impl serde::de::Visitor<'_> for Visitor {
         type Value = bool;
 
         fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -200,6 +597,22 @@ where
     deserializer.deserialize_any(Visitor)
 }
 
+/// [`de_bool`] for optional flags; absent keys take the field default
+pub(crate) fn de_opt_bool<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    de_bool(deserializer).map(Some)
+}
+
+/// [`de_u16`] for optional numbers; absent keys take the field default
+pub(crate) fn de_opt_u16<'de, D>(deserializer: D) -> Result<Option<u16>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    de_u16(deserializer).map(Some)
+}
+
 /// Deserializer helper: treat empty strings as None
 pub(crate) fn empty_string_as_none<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
 where
@@ -232,21 +645,142 @@ mod tests {
         assert_eq!(redact_secret("my-super-secret-key"), "my-s****");
     }
 
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+            .collect()
+    }
+
     #[test]
-    fn test_config_loads_with_defaults() {
-        // Config should load even with various env states
-        // Testing that from_env() doesn't panic and returns a result
-        let result = Config::from_env();
-        // Either succeeds or returns a parse error - both are valid outcomes
-        match result {
-            Ok(config) => {
-                // Verify some defaults are sensible
-                assert!(config.app.app_port > 0);
-            }
-            Err(ConfigError::Parse(_)) => {
-                // Parse errors are acceptable in test environment
+    fn test_prefixed_vars_take_precedence() {
+        let env = vars(&[
+            ("APP_PORT", "8080"),
+            ("ORDERS_APP_PORT", "9001"),
+            ("PAYMENTS_APP_PORT", "9002"),
+            ("APP_NAME", "shared"),
+            ("orders_FEATURE_DB", "true"),
+            ("ORDERS_FEATURE_CACHE", "false"),
+        ]);
+
+        let config = Config::from_vars(env.clone(), Some("orders")).unwrap();
+        assert_eq!(config.app.app_port, 9001);
+        // Falls back to the unprefixed value
+        assert_eq!(config.app.app_name, "shared");
+        assert!(!config.features.feature_cache);
+        // Prefix matching is case-sensitive on the variable name
+        assert!(!config.features.feature_db);
+        assert_eq!(config.app.env_prefix.as_deref(), Some("ORDERS_"));
+
+        let config = Config::from_vars(env, None).unwrap();
+        assert_eq!(config.app.app_port, 8080);
+        assert_eq!(config.app.env_prefix, None);
+    }
+
+    #[test]
+    fn test_prefix_normalization() {
+        assert_eq!(normalize_prefix("orders"), "ORDERS_");
+        assert_eq!(normalize_prefix("ORDERS_"), "ORDERS_");
+        assert_eq!(normalize_prefix(" _ "), "");
+        assert_eq!(
+            strip_env_prefix("ORDERS_APP_PORT", Some("ORDERS_")),
+            Some("APP_PORT")
+        );
+        assert_eq!(strip_env_prefix("ORDERS_", Some("ORDERS_")), None);
+        assert_eq!(strip_env_prefix("APP_PORT", None), None);
+    }
+
+    #[test]
+    fn test_secret_files_override_plain_vars() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_url = dir.path().join("db_url");
+        let jwt_secret = dir.path().join("jwt_secret");
+        std::fs::write(&db_url, "postgres://from-file\n").unwrap();
+        std::fs::write(&jwt_secret, "  file-secret  \n").unwrap();
+
+        let db_url = db_url.display().to_string();
+        let jwt_secret = jwt_secret.display().to_string();
+        let env = vars(&[
+            ("DATABASE_URL", "postgres://from-env"),
+            ("DATABASE_URL_FILE", &db_url),
+            ("ORDERS_AUTH_JWT_SECRET_FILE", &jwt_secret),
+            ("CACHE_REDIS_URL", "redis://from-env"),
+        ]);
+
+        let config = Config::from_vars(env, Some("ORDERS")).unwrap();
+        assert_eq!(config.database.url(), Some("postgres://from-file"));
+        assert_eq!(config.auth.auth_jwt_secret.as_deref(), Some("file-secret"));
+        let redis_url = config.cache.cache_redis_url.as_deref();
+        assert_eq!(redis_url, Some("redis://from-env"));
+    }
+
+    #[test]
+    fn test_unreadable_secret_file_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing").display().to_string();
+        let env = vars(&[
+            ("CACHE_REDIS_URL", "redis://from-env"),
+            ("CACHE_REDIS_URL_FILE", &missing),
+        ]);
+
+        let error = Config::from_vars(env, None).unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::SecretFile {
+                name: "CACHE_REDIS_URL",
+                ..
             }
-            Err(e) => panic!("Unexpected error type: {e:?}"),
-        }
+        ));
+        let message = error.to_string();
+        assert!(message.contains("CACHE_REDIS_URL_FILE"));
+        assert!(message.contains(&missing));
+        assert!(!message.contains("redis://from-env"));
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_misspelled_vars() {
+        let env = vars(&[("FEATRUE_CORS", "true")]);
+        let keys = ["FEATRUE_CORS"];
+
+        let config = Config::from_vars(env.clone(), None).unwrap();
+        assert!(config.is_strict());
+        let error = config.check_unrecognized(keys).unwrap_err();
+        assert!(matches!(error, ConfigError::Unrecognized(_)));
+        assert!(
+            error
+                .to_string()
+                .contains("FEATRUE_CORS (did you mean FEATURE_CORS?)"),
+            "{error}"
+        );
+
+        // Prod and an explicit CONFIG_STRICT=false only warn
+        let mut prod = env.clone();
+        prod.push(("APP_ENV".to_string(), "prod".to_string()));
+        let config = Config::from_vars(prod, None).unwrap();
+        assert!(!config.is_strict());
+        assert!(config.check_unrecognized(keys).is_ok());
+
+        let mut lenient = env;
+        lenient.push(("CONFIG_STRICT".to_string(), "false".to_string()));
+        let config = Config::from_vars(lenient, None).unwrap();
+        assert_eq!(config.app.config_strict, Some(false));
+        assert!(config.check_unrecognized(keys).is_ok());
+    }
+
+    #[test]
+    fn test_config_loads_with_defaults() {
+        // Variables of other tools on the host must not trip strict mode
+        let env = vars(&[
+            ("PATH", "/usr/bin"),
+            ("GRPC_DEFAULT_SSL_ROOTS_FILE_PATH", "/etc/ssl/roots.pem"),
+            ("GRPC_VERBOSITY", "ERROR"),
+        ]);
+        let keys: Vec<&str> = env.iter().map(|(key, _)| key.as_str()).collect();
+
+        let config = Config::from_vars(env.clone(), None).unwrap();
+        assert!(config.is_strict());
+        config.check_unrecognized(keys).unwrap();
+        config.validate().unwrap();
+        assert!(config.app.app_port > 0);
     }
 }
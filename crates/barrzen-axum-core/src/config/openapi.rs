@@ -0,0 +1,144 @@
+//! OpenAPI documentation settings
+
+use serde::{Deserialize, Serialize};
+
+use super::{Config, core_routes::normalize_path, empty_string_as_none};
+
+/// Where `barrzen-axum-openapi` serves the docs (`FEATURE_OPENAPI`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenApiConfig {
+    /// Path of the documentation UI
+    #[serde(default = "default_docs_path")]
+    pub openapi_docs_path: String,
+
+    /// Path of the JSON specification
+    #[serde(default = "default_spec_path")]
+    pub openapi_spec_path: String,
+
+    /// Serve the docs even when `APP_ENV=prod`
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub openapi_enable_in_prod: bool,
+
+    /// Write the spec to this file at startup (YAML for `.yaml`/`.yml`)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub openapi_export_path: Option<String>,
+
+    /// Fail startup instead of warning when the export fails
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub openapi_export_required: bool,
+
+    /// Protection of the docs and spec routes
+    #[serde(default)]
+    pub openapi_auth: OpenApiAuth,
+
+    /// User for `OPENAPI_AUTH=basic`
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub openapi_basic_user: Option<String>,
+
+    /// Password for `OPENAPI_AUTH=basic` (also readable from
+    /// `OPENAPI_BASIC_PASSWORD_FILE`)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub openapi_basic_password: Option<String>,
+}
+
+/// How the docs and spec routes are protected (`OPENAPI_AUTH`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenApiAuth {
+    /// Publicly readable
+    #[default]
+    None,
+    /// HTTP basic auth with `OPENAPI_BASIC_USER`/`OPENAPI_BASIC_PASSWORD`
+    Basic,
+    /// A key from `AUTH_API_KEYS` in the `AUTH_API_KEY_HEADER` header
+    ApiKey,
+}
+
+impl std::fmt::Display for OpenApiAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Basic => write!(f, "basic"),
+            Self::ApiKey => write!(f, "api_key"),
+        }
+    }
+}
+
+impl OpenApiConfig {
+    /// Documentation UI path, `None` when empty
+    #[must_use]
+    pub fn docs_path(&self) -> Option<String> {
+        normalize_path(&self.openapi_docs_path)
+    }
+
+    /// Specification path, `None` when empty
+    #[must_use]
+    pub fn spec_path(&self) -> Option<String> {
+        normalize_path(&self.openapi_spec_path)
+    }
+}
+
+impl Config {
+    /// Whether the OpenAPI docs are served: `FEATURE_OPENAPI=true` and not
+    /// `APP_ENV=prod` unless `OPENAPI_ENABLE_IN_PROD=true`
+    #[must_use]
+    pub fn openapi_enabled(&self) -> bool {
+        self.features.feature_openapi
+            && (!self.is_production() || self.openapi.openapi_enable_in_prod)
+    }
+}
+
+fn default_docs_path() -> String {
+    "/docs".to_string()
+}
+fn default_spec_path() -> String {
+    "/openapi.json".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Environment;
+
+    #[test]
+    fn test_openapi_enabled() {
+        let mut config = Config::builder().feature_openapi(true).build();
+        assert!(config.openapi_enabled());
+
+        config.app.app_env = Environment::Prod;
+        assert!(!config.openapi_enabled());
+
+        config.openapi.openapi_enable_in_prod = true;
+        assert!(config.openapi_enabled());
+
+        config.features.feature_openapi = false;
+        assert!(!config.openapi_enabled());
+    }
+
+    #[test]
+    fn test_openapi_paths() {
+        let config: OpenApiConfig = serde_json::from_value(serde_json::json!({
+            "openapi_docs_path": "reference/",
+            "openapi_spec_path": ""
+        }))
+        .unwrap();
+        assert_eq!(config.docs_path().as_deref(), Some("/reference"));
+        assert_eq!(config.spec_path(), None);
+    }
+
+    #[test]
+    fn test_openapi_auth_parsing() {
+        let config: OpenApiConfig = serde_json::from_value(serde_json::json!({
+            "openapi_auth": "api_key",
+            "openapi_basic_user": ""
+        }))
+        .unwrap();
+        assert_eq!(config.openapi_auth, OpenApiAuth::ApiKey);
+        assert_eq!(config.openapi_basic_user, None);
+
+        let config: OpenApiConfig = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(config.openapi_auth, OpenApiAuth::None);
+    }
+}
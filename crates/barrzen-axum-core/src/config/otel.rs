@@ -0,0 +1,305 @@
+//! OpenTelemetry configuration
+//!
+//! Field names match the standard `OTEL_*` variables, so settings written
+//! for other OTEL SDKs apply unchanged.
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// OpenTelemetry tracing configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OtelConfig {
+    #[serde(default)]
+    pub otel_traces_sampler: TraceSampler,
+
+    /// Sampling ratio in `0.0..=1.0` for the `traceidratio` samplers
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub otel_traces_sampler_arg: Option<String>,
+
+    /// Collector base URL; `/v1/<signal>` is appended for `http/protobuf`
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub otel_exporter_otlp_endpoint: Option<String>,
+
+    #[serde(default)]
+    pub otel_exporter_otlp_protocol: OtlpProtocol,
+
+    /// Comma-separated `key=value` headers sent with every export
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub otel_exporter_otlp_headers: Option<String>,
+
+    /// Export timeout in milliseconds
+    #[serde(default = "default_otlp_timeout")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub otel_exporter_otlp_timeout: u64,
+
+    /// Extra resource attributes as comma-separated `key=value` pairs
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub otel_resource_attributes: Option<String>,
+}
+
+impl OtelConfig {
+    /// Sampling ratio from `OTEL_TRACES_SAMPLER_ARG`
+    ///
+    /// Falls back to `1.0` when unset or invalid, as the OTEL spec requires.
+    #[must_use]
+    pub fn sampler_ratio(&self) -> f64 {
+        self.valid_sampler_ratio().unwrap_or(1.0)
+    }
+
+    /// `OTEL_TRACES_SAMPLER_ARG` if it is a ratio in `0.0..=1.0`
+    pub(crate) fn valid_sampler_ratio(&self) -> Option<f64> {
+        self.otel_traces_sampler_arg
+            .as_deref()
+            .and_then(|arg| arg.trim().parse::<f64>().ok())
+            .filter(|ratio| (0.0..=1.0).contains(ratio))
+    }
+
+    /// Span export URL for the configured protocol
+    #[must_use]
+    pub fn traces_endpoint(&self) -> String {
+        self.signal_endpoint("traces")
+    }
+
+    /// Metric export URL for the configured protocol
+    #[must_use]
+    pub fn metrics_endpoint(&self) -> String {
+        self.signal_endpoint("metrics")
+    }
+
+    fn signal_endpoint(&self, signal: &str) -> String {
+        let endpoint = self.otel_exporter_otlp_endpoint.as_deref();
+        match self.otel_exporter_otlp_protocol {
+            OtlpProtocol::Grpc => endpoint.unwrap_or("http://localhost:4317").to_string(),
+            OtlpProtocol::HttpProtobuf => format!(
+                "{}/v1/{signal}",
+                endpoint
+                    .unwrap_or("http://localhost:4318")
+                    .trim_end_matches('/')
+            ),
+        }
+    }
+
+    /// Parse `OTEL_EXPORTER_OTLP_HEADERS` into `(name, value)` pairs
+    ///
+    /// Entries split on the first `=`, so values may contain `=`. Values
+    /// are percent-decoded as the OTEL spec requires; entries without `=`
+    /// are skipped (and reported by [`Config::validate`]).
+    ///
+    /// [`Config::validate`]: crate::Config::validate
+    #[must_use]
+    pub fn exporter_headers(&self) -> Vec<(String, String)> {
+        parse_pairs(self.otel_exporter_otlp_headers.as_deref())
+    }
+
+    /// Parse `OTEL_RESOURCE_ATTRIBUTES` like [`OtelConfig::exporter_headers`]
+    #[must_use]
+    pub fn resource_attributes(&self) -> Vec<(String, String)> {
+        parse_pairs(self.otel_resource_attributes.as_deref())
+    }
+
+    /// `OTEL_EXPORTER_OTLP_HEADERS` entries that are not `key=value`
+    pub(crate) fn malformed_header_entries(&self) -> usize {
+        pair_entries(self.otel_exporter_otlp_headers.as_deref())
+            .filter(|entry| {
+                entry
+                    .split_once('=')
+                    .is_none_or(|(name, _)| name.trim().is_empty())
+            })
+            .count()
+    }
+
+    /// Export timeout (`OTEL_EXPORTER_OTLP_TIMEOUT`)
+    #[must_use]
+    pub fn exporter_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.otel_exporter_otlp_timeout)
+    }
+
+    /// Effective sampler, e.g. `parentbased_traceidratio(0.25)`
+    #[must_use]
+    pub fn sampler_description(&self) -> String {
+        let sampler = self.otel_traces_sampler;
+        if sampler.uses_ratio() {
+            format!("{sampler}({})", self.sampler_ratio())
+        } else {
+            sampler.to_string()
+        }
+    }
+}
+
+/// Trace sampler (`OTEL_TRACES_SAMPLER`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceSampler {
+    AlwaysOn,
+    AlwaysOff,
+    #[serde(rename = "traceidratio")]
+    TraceIdRatio,
+    /// Follow the parent's decision, sample root spans by ratio
+    #[default]
+    #[serde(rename = "parentbased_traceidratio")]
+    ParentBasedTraceIdRatio,
+}
+
+impl TraceSampler {
+    /// Whether `OTEL_TRACES_SAMPLER_ARG` applies
+    #[must_use]
+    pub fn uses_ratio(self) -> bool {
+        matches!(self, Self::TraceIdRatio | Self::ParentBasedTraceIdRatio)
+    }
+}
+
+impl std::fmt::Display for TraceSampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AlwaysOn => write!(f, "always_on"),
+            Self::AlwaysOff => write!(f, "always_off"),
+            Self::TraceIdRatio => write!(f, "traceidratio"),
+            Self::ParentBasedTraceIdRatio => write!(f, "parentbased_traceidratio"),
+        }
+    }
+}
+
+/// OTLP transport (`OTEL_EXPORTER_OTLP_PROTOCOL`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum OtlpProtocol {
+    #[default]
+    #[serde(rename = "grpc")]
+    Grpc,
+    #[serde(rename = "http/protobuf")]
+    HttpProtobuf,
+}
+
+impl std::fmt::Display for OtlpProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Grpc => write!(f, "grpc"),
+            Self::HttpProtobuf => write!(f, "http/protobuf"),
+        }
+    }
+}
+
+/// Non-empty entries of a comma-separated `key=value` list
+fn pair_entries(list: Option<&str>) -> impl Iterator<Item = &str> {
+    list.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+}
+
+/// `(key, value)` pairs split on the first `=`, values percent-decoded
+fn parse_pairs(list: Option<&str>) -> Vec<(String, String)> {
+    pair_entries(list)
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), percent_decode(value.trim())))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+/// Decode `%XX` escapes; invalid escapes are kept as-is
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn default_otlp_timeout() -> u64 {
+    10_000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampler_settings() {
+        let defaults: OtelConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(
+            defaults.otel_traces_sampler,
+            TraceSampler::ParentBasedTraceIdRatio
+        );
+        assert!((defaults.sampler_ratio() - 1.0).abs() < f64::EPSILON);
+        assert_eq!(
+            defaults.sampler_description(),
+            "parentbased_traceidratio(1)"
+        );
+
+        let config: OtelConfig = serde_json::from_value(serde_json::json!({
+            "otel_traces_sampler": "traceidratio",
+            "otel_traces_sampler_arg": "0.25"
+        }))
+        .unwrap();
+        assert_eq!(config.sampler_description(), "traceidratio(0.25)");
+
+        let invalid: OtelConfig = serde_json::from_value(serde_json::json!({
+            "otel_traces_sampler": "always_off",
+            "otel_traces_sampler_arg": "2"
+        }))
+        .unwrap();
+        assert!((invalid.sampler_ratio() - 1.0).abs() < f64::EPSILON);
+        assert_eq!(invalid.sampler_description(), "always_off");
+
+        let unknown = serde_json::from_value::<OtelConfig>(serde_json::json!({
+            "otel_traces_sampler": "sometimes"
+        }));
+        assert!(unknown.is_err());
+    }
+
+    #[test]
+    fn test_exporter_headers_parsing() {
+        let config: OtelConfig = serde_json::from_value(serde_json::json!({
+            "otel_exporter_otlp_headers":
+                "Authorization=Basic dXNlcjpwYXNz==, x-tenant = a%20b,broken,=nameless,"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            config.exporter_headers(),
+            vec![
+                (
+                    "Authorization".to_string(),
+                    "Basic dXNlcjpwYXNz==".to_string()
+                ),
+                ("x-tenant".to_string(), "a b".to_string()),
+            ]
+        );
+        assert_eq!(config.malformed_header_entries(), 2);
+        assert!(config.resource_attributes().is_empty());
+        assert_eq!(percent_decode("100%25 %zz%"), "100% %zz%");
+    }
+
+    #[test]
+    fn test_exporter_protocol_and_endpoint() {
+        let defaults: OtelConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(defaults.otel_exporter_otlp_protocol, OtlpProtocol::Grpc);
+        assert_eq!(defaults.traces_endpoint(), "http://localhost:4317");
+        assert_eq!(defaults.exporter_timeout().as_millis(), 10_000);
+
+        let config: OtelConfig = serde_json::from_value(serde_json::json!({
+            "otel_exporter_otlp_protocol": "http/protobuf",
+            "otel_exporter_otlp_endpoint": "https://otlp.vendor.io/",
+            "otel_exporter_otlp_timeout": "2500"
+        }))
+        .unwrap();
+        assert_eq!(config.traces_endpoint(), "https://otlp.vendor.io/v1/traces");
+        assert_eq!(
+            config.metrics_endpoint(),
+            "https://otlp.vendor.io/v1/metrics"
+        );
+        assert_eq!(config.exporter_timeout().as_millis(), 2500);
+    }
+}
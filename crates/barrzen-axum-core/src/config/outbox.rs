@@ -0,0 +1,59 @@
+//! Transactional outbox configuration
+
+use serde::{Deserialize, Serialize};
+
+/// Settings of the outbox relay (`barrzen-axum-infra` `outbox`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxConfig {
+    /// Pause between polls while the outbox is drained
+    #[serde(default = "default_poll_interval_ms")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub outbox_poll_interval_ms: u64,
+
+    /// Events published per poll
+    #[serde(default = "default_batch_size")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub outbox_batch_size: u64,
+
+    /// Failed publishes before an event is dead-lettered
+    #[serde(default = "default_max_attempts")]
+    #[serde(deserialize_with = "crate::config::de_u32")]
+    pub outbox_max_attempts: u32,
+
+    /// Delay before the first retry, doubled on each further failure
+    #[serde(default = "default_retry_base_ms")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub outbox_retry_base_ms: u64,
+
+    /// Longest delay between retries
+    #[serde(default = "default_retry_max_ms")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub outbox_retry_max_ms: u64,
+}
+
+impl Default for OutboxConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().outbox
+    }
+}
+
+fn default_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_batch_size() -> u64 {
+    100
+}
+
+fn default_max_attempts() -> u32 {
+    10
+}
+
+fn default_retry_base_ms() -> u64 {
+    1000
+}
+
+fn default_retry_max_ms() -> u64 {
+    300_000
+}
@@ -0,0 +1,161 @@
+//! Readiness check configuration
+
+use axum::http::Uri;
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// Dependencies probed by /readyz beyond the built-in infrastructure
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadinessConfig {
+    /// HTTP dependencies as `name=url` pairs, e.g.
+    /// `payments=https://payments.internal/health,auth=https://auth.internal/livez`
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub readyz_http_checks: Option<String>,
+
+    /// Time a dependency has to answer with a 2xx status
+    #[serde(default = "default_http_check_timeout_ms")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub readyz_http_check_timeout_ms: u64,
+
+    /// Components whose failure makes the service unready, e.g. `database,payments`
+    ///
+    /// Unset means every component except `cache`, `database_read` and `mailer`.
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub readyz_critical_components: Option<String>,
+
+    /// Answer 503 instead of 200 when a critical check fails
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub readyz_strict: bool,
+
+    /// Reuse the last readiness result for this long (0 disables)
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub readyz_cache_ttl_ms: u64,
+}
+
+impl Default for ReadinessConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().readiness
+    }
+}
+
+impl ReadinessConfig {
+    /// `READYZ_HTTP_CHECKS` as `(name, url)` pairs
+    ///
+    /// Entries that are not `name=http(s)://...` are skipped (and reported
+    /// by [`Config::validate`]).
+    ///
+    /// [`Config::validate`]: crate::Config::validate
+    #[must_use]
+    pub fn http_checks(&self) -> Vec<(String, String)> {
+        self.http_check_entries()
+            .filter_map(parse_http_check)
+            .map(|(name, url)| (name.to_string(), url.to_string()))
+            .collect()
+    }
+
+    /// Whether a failing `component` makes the service unready
+    ///
+    /// Follows `READYZ_CRITICAL_COMPONENTS` when set; otherwise only the
+    /// cache, the read replica and the mailer are non-critical, since the
+    /// service can serve without them.
+    #[must_use]
+    pub fn is_critical(&self, component: &str) -> bool {
+        match self.readyz_critical_components.as_deref() {
+            Some(list) => list
+                .split(',')
+                .any(|entry| entry.trim().eq_ignore_ascii_case(component)),
+            None => !matches!(component, "cache" | "database_read" | "mailer"),
+        }
+    }
+
+    /// `READYZ_HTTP_CHECKS` entries that cannot be parsed
+    pub(crate) fn invalid_http_checks(&self) -> Vec<String> {
+        self.http_check_entries()
+            .filter(|entry| parse_http_check(entry).is_none())
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn http_check_entries(&self) -> impl Iterator<Item = &str> {
+        self.readyz_http_checks
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+    }
+}
+
+fn parse_http_check(entry: &str) -> Option<(&str, &str)> {
+    let (name, url) = entry.split_once('=')?;
+    let (name, url) = (name.trim(), url.trim());
+    let uri = url.parse::<Uri>().ok()?;
+    let http = matches!(uri.scheme_str(), Some("http" | "https")) && uri.host().is_some();
+    (!name.is_empty() && http).then_some((name, url))
+}
+
+fn default_http_check_timeout_ms() -> u64 {
+    2000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_checks() {
+        let config: ReadinessConfig = serde_json::from_value(serde_json::json!({
+            "readyz_http_checks": "payments=https://payments.internal/health, auth = http://auth:8080/livez?deep=1,nourl,ftp=ftp://files,=http://x",
+            "readyz_http_check_timeout_ms": "500"
+        }))
+        .unwrap();
+
+        assert_eq!(
+            config.http_checks(),
+            vec![
+                (
+                    "payments".to_string(),
+                    "https://payments.internal/health".to_string()
+                ),
+                (
+                    "auth".to_string(),
+                    "http://auth:8080/livez?deep=1".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            config.invalid_http_checks(),
+            vec!["nourl", "ftp=ftp://files", "=http://x"]
+        );
+        assert_eq!(config.readyz_http_check_timeout_ms, 500);
+
+        let defaults: ReadinessConfig = serde_json::from_str("{}").unwrap();
+        assert!(defaults.http_checks().is_empty());
+        assert_eq!(defaults.readyz_http_check_timeout_ms, 2000);
+    }
+
+    #[test]
+    fn test_critical_components() {
+        let defaults: ReadinessConfig = serde_json::from_str("{}").unwrap();
+        assert!(defaults.is_critical("database"));
+        assert!(defaults.is_critical("payments"));
+        assert!(!defaults.is_critical("cache"));
+        assert!(!defaults.is_critical("database_read"));
+        assert!(!defaults.is_critical("mailer"));
+        assert!(!defaults.readyz_strict);
+
+        let config: ReadinessConfig = serde_json::from_value(serde_json::json!({
+            "readyz_critical_components": "Database, cache",
+            "readyz_strict": "true"
+        }))
+        .unwrap();
+        assert!(config.is_critical("database"));
+        assert!(config.is_critical("cache"));
+        assert!(!config.is_critical("payments"));
+        assert!(config.readyz_strict);
+    }
+}
@@ -0,0 +1,67 @@
+//! Request id settings
+
+use axum::http::HeaderName;
+use serde::{Deserialize, Serialize};
+
+/// Header carrying the request id and how new ids are made
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RequestIdConfig {
+    /// Header read from clients and echoed on responses, e.g. `x-correlation-id`
+    #[serde(default = "default_header_name")]
+    pub request_id_header_name: String,
+
+    /// Format of generated ids
+    #[serde(default)]
+    pub request_id_format: RequestIdFormat,
+
+    /// Longest inbound id kept; longer ones are replaced with a new id
+    #[serde(default = "default_max_length")]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub request_id_max_length: usize,
+}
+
+impl Default for RequestIdConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().request_id
+    }
+}
+
+impl RequestIdConfig {
+    /// `REQUEST_ID_HEADER_NAME` as a header name, if valid
+    #[must_use]
+    pub fn header_name(&self) -> Option<HeaderName> {
+        HeaderName::from_bytes(self.request_id_header_name.trim().as_bytes()).ok()
+    }
+}
+
+/// Format of generated request ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestIdFormat {
+    /// Random UUID, e.g. `67e55044-10b1-426f-9247-bb680e5fe0c8`
+    #[default]
+    Uuidv4,
+    /// Time-ordered UUID
+    Uuidv7,
+    /// 26-character time-ordered id in Crockford base32
+    Ulid,
+}
+
+impl std::fmt::Display for RequestIdFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Uuidv4 => write!(f, "uuidv4"),
+            Self::Uuidv7 => write!(f, "uuidv7"),
+            Self::Ulid => write!(f, "ulid"),
+        }
+    }
+}
+
+fn default_header_name() -> String {
+    "x-request-id".to_string()
+}
+
+fn default_max_length() -> usize {
+    128
+}
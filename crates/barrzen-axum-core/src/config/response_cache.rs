@@ -0,0 +1,46 @@
+//! Response cache configuration
+
+use serde::{Deserialize, Serialize};
+
+/// Settings shared by the response cache layers (`barrzen-axum-infra`,
+/// `response-cache` feature); each layer sets its own TTL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResponseCacheConfig {
+    /// Request headers whose values are part of the cache key
+    /// (comma-separated)
+    #[serde(default = "default_vary_headers")]
+    pub response_cache_vary_headers: String,
+
+    /// Responses larger than this are returned but not cached
+    #[serde(default = "default_max_body_bytes")]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub response_cache_max_body_bytes: usize,
+}
+
+impl Default for ResponseCacheConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().response_cache
+    }
+}
+
+impl ResponseCacheConfig {
+    /// Lower-cased header names from `RESPONSE_CACHE_VARY_HEADERS`
+    #[must_use]
+    pub fn vary_headers(&self) -> Vec<String> {
+        self.response_cache_vary_headers
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_ascii_lowercase)
+            .collect()
+    }
+}
+
+fn default_vary_headers() -> String {
+    "accept-language".to_string()
+}
+
+fn default_max_body_bytes() -> usize {
+    1024 * 1024
+}
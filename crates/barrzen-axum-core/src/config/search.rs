@@ -0,0 +1,42 @@
+//! Search (Meilisearch) configuration
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// Meilisearch connection configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchConfig {
+    /// Meilisearch URL, e.g. `http://meilisearch:7700`
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub meili_url: Option<String>,
+
+    /// Meilisearch API key (also readable from `MEILI_API_KEY_FILE`)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub meili_api_key: Option<String>,
+
+    /// How long index helpers wait for a Meilisearch task to finish
+    #[serde(default = "default_task_timeout")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub meili_task_timeout_seconds: u64,
+
+    /// Documents sent per task when reindexing
+    #[serde(default = "default_batch_size")]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub meili_batch_size: usize,
+}
+
+impl Default for SearchConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().search
+    }
+}
+
+fn default_task_timeout() -> u64 {
+    30
+}
+
+fn default_batch_size() -> usize {
+    1_000
+}
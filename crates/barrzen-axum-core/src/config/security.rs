@@ -0,0 +1,119 @@
+//! Security response headers configuration
+
+use serde::{Deserialize, Serialize};
+
+/// Security response headers configuration
+///
+/// Applied when `FEATURE_SECURITY_HEADERS=true`. An empty value omits the
+/// header. Headers already set by a handler are left untouched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecurityHeadersConfig {
+    /// `Content-Security-Policy` (omitted by default)
+    #[serde(default)]
+    pub security_csp: String,
+
+    /// `Strict-Transport-Security` max-age in seconds (0 disables)
+    #[serde(default = "default_hsts_max_age")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub security_hsts_max_age: u64,
+
+    /// Send HSTS outside production (e.g. TLS terminated in a staging app)
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub security_hsts_force: bool,
+
+    /// `X-Frame-Options`
+    #[serde(default = "default_frame_options")]
+    pub security_frame_options: String,
+
+    /// `Permissions-Policy` (omitted by default)
+    #[serde(default)]
+    pub security_permissions_policy: String,
+}
+
+impl SecurityHeadersConfig {
+    /// `Strict-Transport-Security` value, if it should be sent
+    ///
+    /// HSTS is only sent in production (where TLS is expected in front of the
+    /// app) unless `SECURITY_HSTS_FORCE=true`.
+    #[must_use]
+    pub fn hsts(&self, is_production: bool) -> Option<String> {
+        (self.security_hsts_max_age > 0 && (is_production || self.security_hsts_force))
+            .then(|| format!("max-age={}", self.security_hsts_max_age))
+    }
+
+    /// Configured headers as `(name, value)` pairs, empty values omitted
+    #[must_use]
+    pub fn headers(&self, is_production: bool) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            ("x-content-type-options", "nosniff".to_string()),
+            (
+                "x-frame-options",
+                self.security_frame_options.trim().to_string(),
+            ),
+            ("x-xss-protection", "1; mode=block".to_string()),
+            (
+                "referrer-policy",
+                "strict-origin-when-cross-origin".to_string(),
+            ),
+            (
+                "content-security-policy",
+                self.security_csp.trim().to_string(),
+            ),
+            (
+                "permissions-policy",
+                self.security_permissions_policy.trim().to_string(),
+            ),
+        ];
+        if let Some(hsts) = self.hsts(is_production) {
+            headers.push(("strict-transport-security", hsts));
+        }
+        headers.retain(|(_, value)| !value.is_empty());
+        headers
+    }
+}
+
+fn default_hsts_max_age() -> u64 {
+    31_536_000 // 1 year
+}
+fn default_frame_options() -> String {
+    "DENY".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_security_headers_defaults() {
+        let config: SecurityHeadersConfig = serde_json::from_value(json!({})).unwrap();
+        let names: Vec<_> = config.headers(false).into_iter().map(|(n, _)| n).collect();
+
+        assert_eq!(
+            names,
+            vec![
+                "x-content-type-options",
+                "x-frame-options",
+                "x-xss-protection",
+                "referrer-policy"
+            ]
+        );
+        assert_eq!(config.hsts(true).as_deref(), Some("max-age=31536000"));
+    }
+
+    #[test]
+    fn test_hsts_conditions() {
+        let mut config: SecurityHeadersConfig = serde_json::from_value(json!({
+            "security_hsts_max_age": "600"
+        }))
+        .unwrap();
+        assert_eq!(config.hsts(false), None);
+
+        config.security_hsts_force = true;
+        assert_eq!(config.hsts(false).as_deref(), Some("max-age=600"));
+
+        config.security_hsts_max_age = 0;
+        assert_eq!(config.hsts(true), None);
+    }
+}
@@ -0,0 +1,72 @@
+//! Session configuration
+
+use serde::{Deserialize, Serialize};
+
+/// Session cookie configuration (used when `FEATURE_SESSION=true`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionConfig {
+    #[serde(default = "default_cookie_name")]
+    pub session_cookie_name: String,
+
+    /// Inactivity expiry in seconds
+    #[serde(default = "default_ttl_seconds")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub session_ttl_seconds: u64,
+
+    #[serde(default = "default_true")]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub session_secure: bool,
+
+    #[serde(default)]
+    pub session_same_site: SessionSameSite,
+}
+
+/// Session cookie `SameSite` attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionSameSite {
+    Strict,
+    #[default]
+    Lax,
+    None,
+}
+
+impl std::fmt::Display for SessionSameSite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Strict => write!(f, "strict"),
+            Self::Lax => write!(f, "lax"),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
+
+fn default_cookie_name() -> String {
+    "session".to_string()
+}
+fn default_ttl_seconds() -> u64 {
+    86_400
+}
+fn default_true() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_config_parsing() {
+        let config: SessionConfig = serde_json::from_value(serde_json::json!({
+            "session_ttl_seconds": "600",
+            "session_secure": "false",
+            "session_same_site": "strict"
+        }))
+        .unwrap();
+
+        assert_eq!(config.session_cookie_name, "session");
+        assert_eq!(config.session_ttl_seconds, 600);
+        assert!(!config.session_secure);
+        assert_eq!(config.session_same_site, SessionSameSite::Strict);
+    }
+}
@@ -0,0 +1,443 @@
+//! Reference of the variables read by [`Config`]
+//!
+//! Names and types come from the `Deserialize` impls of the config sections
+//! and defaults from [`Config::default`], so the reference cannot drift from
+//! what loading actually accepts.
+
+use std::fmt::{self, Write as _};
+
+use serde::{
+    Deserializer,
+    de::{self, DeserializeOwned, DeserializeSeed, MapAccess, Visitor, value::StrDeserializer},
+};
+use serde_json::Value;
+
+use super::{
+    AppConfig, AuditConfig, AuthConfig, BannerConfig, BrokerConfig, CacheConfig,
+    CircuitBreakerConfig, ClientIpConfig, CompressionConfig, Config, CoreRoutesConfig, CorsConfig,
+    DatabaseConfig, FeatureFlags, GrpcConfig, HttpClientConfig, HttpConfig, IdempotencyConfig,
+    IpFilterConfig, LoggingConfig, MailConfig, MaintenanceConfig, ManagementConfig, OpenApiConfig,
+    OtelConfig, OutboxConfig, ReadinessConfig, RequestIdConfig, ResponseCacheConfig,
+    SECRET_FILE_VARS, SearchConfig, SecurityHeadersConfig, SessionConfig, SseConfig, StartupConfig,
+    TenantConfig, UploadConfig, WsConfig, redact::is_header_list_key,
+};
+
+/// Value type of a config variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvVarType {
+    /// `true`/`false` (also `1`/`0`, `yes`/`no`, `on`/`off`)
+    Bool,
+    /// Non-negative integer
+    Integer,
+    /// Free-form text, including lists and URLs
+    String,
+    /// One of the listed values
+    Enum(&'static [&'static str]),
+}
+
+impl fmt::Display for EnvVarType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool => f.write_str("bool"),
+            Self::Integer => f.write_str("integer"),
+            Self::String => f.write_str("string"),
+            Self::Enum(values) => write!(f, "one of {}", values.join(", ")),
+        }
+    }
+}
+
+/// One variable of the configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVarSpec {
+    /// Variable name, e.g. `APP_PORT`
+    pub name: String,
+    /// Config section the variable belongs to, e.g. `App`
+    pub section: &'static str,
+    pub ty: EnvVarType,
+    /// Value used when the variable is unset; `None` when unset means off
+    pub default: Option<String>,
+    /// Holds credentials: never log or commit the value
+    pub sensitive: bool,
+}
+
+type SectionProbe = fn() -> Vec<(&'static str, Probed)>;
+
+/// Config sections in the order of [`Config`]
+const SECTIONS: [(&str, SectionProbe); 36] = [
+    ("App", probe::<AppConfig>),
+    ("Features", probe::<FeatureFlags>),
+    ("HTTP", probe::<HttpConfig>),
+    ("HTTP client", probe::<HttpClientConfig>),
+    ("Request ID", probe::<RequestIdConfig>),
+    ("Compression", probe::<CompressionConfig>),
+    ("SSE", probe::<SseConfig>),
+    ("WebSocket", probe::<WsConfig>),
+    ("Logging", probe::<LoggingConfig>),
+    ("Audit", probe::<AuditConfig>),
+    ("Cache", probe::<CacheConfig>),
+    ("Circuit breaker", probe::<CircuitBreakerConfig>),
+    ("CORS", probe::<CorsConfig>),
+    ("Banner", probe::<BannerConfig>),
+    ("Core routes", probe::<CoreRoutesConfig>),
+    ("Management", probe::<ManagementConfig>),
+    ("gRPC", probe::<GrpcConfig>),
+    ("Readiness", probe::<ReadinessConfig>),
+    ("Startup", probe::<StartupConfig>),
+    ("Maintenance", probe::<MaintenanceConfig>),
+    ("Idempotency", probe::<IdempotencyConfig>),
+    ("Response cache", probe::<ResponseCacheConfig>),
+    ("Uploads", probe::<UploadConfig>),
+    ("OpenAPI", probe::<OpenApiConfig>),
+    ("Auth", probe::<AuthConfig>),
+    ("Sessions", probe::<SessionConfig>),
+    ("IP filter", probe::<IpFilterConfig>),
+    ("Client IP", probe::<ClientIpConfig>),
+    ("Tenant", probe::<TenantConfig>),
+    ("Security headers", probe::<SecurityHeadersConfig>),
+    ("Database", probe::<DatabaseConfig>),
+    ("Search", probe::<SearchConfig>),
+    ("Broker", probe::<BrokerConfig>),
+    ("Mail", probe::<MailConfig>),
+    ("Outbox", probe::<OutboxConfig>),
+    ("OpenTelemetry", probe::<OtelConfig>),
+];
+
+impl Config {
+    /// Every variable the configuration reads, grouped by section
+    #[must_use]
+    pub fn env_spec() -> Vec<EnvVarSpec> {
+        let defaults = match serde_json::to_value(Self::default()) {
+            Ok(Value::Object(defaults)) => defaults,
+            _ => serde_json::Map::new(),
+        };
+
+        SECTIONS
+            .iter()
+            .flat_map(|(section, probe)| probe().into_iter().map(move |field| (*section, field)))
+            .map(|(section, (field, probed))| {
+                let default = defaults.get(field).unwrap_or(&Value::Null);
+                let name = field.to_ascii_uppercase();
+                EnvVarSpec {
+                    sensitive: SECRET_FILE_VARS.contains(&name.as_str())
+                        || is_header_list_key(&name),
+                    name,
+                    section,
+                    ty: probed.resolve(default),
+                    default: match default {
+                        Value::Null => None,
+                        Value::String(value) => Some(value.clone()),
+                        other => Some(other.to_string()),
+                    },
+                }
+            })
+            .collect()
+    }
+
+    /// Commented `.env` template listing every variable with its default
+    ///
+    /// All assignments are commented out, so the file changes nothing until
+    /// a line is uncommented.
+    #[must_use]
+    pub fn render_env_example() -> String {
+        let mut out = String::from("# Generated by Config::render_env_example()\n");
+        let mut section = "";
+        for spec in Self::env_spec() {
+            if spec.section != section {
+                section = spec.section;
+                let _ = write!(out, "\n# --- {section} ---\n");
+            }
+            let mut notes = spec.ty.to_string();
+            if let Some(default) = spec.default.as_deref().filter(|d| !d.is_empty()) {
+                let _ = write!(notes, ", default {default}");
+            }
+            if spec.sensitive {
+                notes.push_str(", sensitive");
+            }
+            let _ = writeln!(
+                out,
+                "# {}: {notes}\n#{}={}",
+                spec.name,
+                spec.name,
+                spec.default.unwrap_or_default()
+            );
+        }
+        out
+    }
+}
+
+/// What a field's `Deserialize` impl asked the probe for
+#[derive(Debug, Clone, Copy)]
+enum Probed {
+    Bool,
+    Integer,
+    String,
+    Enum(&'static [&'static str]),
+    /// Self-describing helper (`de_bool`, `de_u64`, ...); `accepts` is the
+    /// sample type its visitor took, if any
+    Any {
+        accepts: Option<EnvVarType>,
+    },
+}
+
+impl Probed {
+    /// Settle self-describing fields with the default value
+    fn resolve(self, default: &Value) -> EnvVarType {
+        match self {
+            Self::Bool => EnvVarType::Bool,
+            Self::Integer => EnvVarType::Integer,
+            Self::String => EnvVarType::String,
+            Self::Enum(values) => EnvVarType::Enum(values),
+            Self::Any { accepts } => match default {
+                Value::Bool(_) => EnvVarType::Bool,
+                Value::Number(_) => EnvVarType::Integer,
+                Value::Null => accepts.unwrap_or(EnvVarType::String),
+                _ => EnvVarType::String,
+            },
+        }
+    }
+}
+
+/// Fields of `T` with what each one deserializes as
+///
+/// A derived `Deserialize` passes its field list to `deserialize_struct`;
+/// each field is then fed to the impl alone, with a value deserializer that
+/// records the request and fails. Self-describing fields are offered a bool,
+/// then an integer, until one is taken.
+fn probe<T: DeserializeOwned>() -> Vec<(&'static str, Probed)> {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(StructProbe::Fields(&mut fields));
+
+    fields
+        .iter()
+        .filter_map(|field| {
+            let mut probed = None;
+            for sample in [EnvVarType::Bool, EnvVarType::Integer] {
+                let _ = T::deserialize(StructProbe::Field(field, sample, &mut probed));
+                if !matches!(probed, Some(Probed::Any { accepts: None })) {
+                    break;
+                }
+            }
+            probed.map(|probed| (*field, probed))
+        })
+        .collect()
+}
+
+type Error = de::value::Error;
+
+enum StructProbe<'a> {
+    /// Record the field list
+    Fields(&'a mut &'static [&'static str]),
+    /// Probe one field, offering self-describing helpers a sample value
+    Field(&'static str, EnvVarType, &'a mut Option<Probed>),
+}
+
+impl<'de> Deserializer<'de> for StructProbe<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Self::Fields(out) => {
+                *out = fields;
+                Err(de::Error::custom("fields recorded"))
+            }
+            Self::Field(field, sample, probed) => visitor.visit_map(OneField {
+                field: Some(field),
+                sample,
+                probed,
+            }),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+        byte_buf option unit unit_struct newtype_struct seq tuple tuple_struct map enum
+        identifier ignored_any
+    }
+}
+
+/// Map holding a single field whose value is a [`ValueProbe`]
+struct OneField<'a> {
+    field: Option<&'static str>,
+    sample: EnvVarType,
+    probed: &'a mut Option<Probed>,
+}
+
+impl<'de> MapAccess<'de> for OneField<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        self.field
+            .take()
+            .map(|field| seed.deserialize(StrDeserializer::<Error>::new(field)))
+            .transpose()
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(ValueProbe {
+            sample: self.sample,
+            probed: self.probed,
+        })
+    }
+}
+
+/// Deserializer recording which type is requested; never yields a value
+struct ValueProbe<'a> {
+    /// Value offered to `deserialize_any` visitors: `Bool` or `Integer`
+    sample: EnvVarType,
+    probed: &'a mut Option<Probed>,
+}
+
+impl ValueProbe<'_> {
+    fn record<T>(self, probed: Probed) -> Result<T, Error> {
+        *self.probed = Some(probed);
+        Err(de::Error::custom("type recorded"))
+    }
+}
+
+macro_rules! probe_as {
+    ($probed:expr => $($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+                self.record($probed)
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ValueProbe<'_> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let sample = self.sample;
+        let accepted = match sample {
+            EnvVarType::Bool => visitor.visit_bool::<Error>(true).is_ok(),
+            _ => visitor.visit_u64::<Error>(1).is_ok(),
+        };
+        self.record(Probed::Any {
+            accepts: accepted.then_some(sample),
+        })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // `Option<T>` is documented by its `T`
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.record(Probed::Enum(variants))
+    }
+
+    probe_as!(Probed::Bool => deserialize_bool);
+    probe_as!(Probed::Integer =>
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_i128
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_u128);
+    probe_as!(Probed::String => deserialize_char deserialize_str deserialize_string);
+
+    serde::forward_to_deserialize_any! {
+        f32 f64 bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct map
+        struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn test_every_field_appears_once() {
+        let spec = Config::env_spec();
+        let Value::Object(fields) = serde_json::to_value(Config::default()).unwrap() else {
+            panic!("config is not an object");
+        };
+
+        let mut counts = BTreeMap::new();
+        for var in &spec {
+            *counts.entry(var.name.to_ascii_lowercase()).or_insert(0) += 1;
+        }
+        for field in fields.keys().filter(|field| *field != "dotenv_files") {
+            assert_eq!(counts.get(field), Some(&1), "{field}");
+        }
+        assert_eq!(counts.len(), spec.len());
+        assert_eq!(spec.len(), fields.len() - 1);
+    }
+
+    #[test]
+    fn test_defaults_match_serde_defaults() {
+        let vars = Config::env_spec()
+            .into_iter()
+            .filter_map(|spec| Some((spec.name, spec.default?)));
+        assert_eq!(Config::from_vars(vars, None).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_types_and_sensitivity() {
+        let spec: BTreeMap<_, _> = Config::env_spec()
+            .into_iter()
+            .map(|spec| (spec.name.clone(), spec))
+            .collect();
+
+        let port = &spec["APP_PORT"];
+        assert_eq!(port.ty, EnvVarType::Integer);
+        assert_eq!(port.default.as_deref(), Some("8080"));
+        assert_eq!(port.section, "App");
+        assert_eq!(spec["FEATURE_CORS"].ty, EnvVarType::Bool);
+        assert_eq!(spec["CONFIG_STRICT"].ty, EnvVarType::Bool);
+        assert_eq!(spec["CONFIG_STRICT"].default, None);
+        assert_eq!(spec["MANAGEMENT_PORT"].ty, EnvVarType::Integer);
+        assert_eq!(spec["MANAGEMENT_PORT"].section, "Management");
+        assert_eq!(spec["APP_NAME"].ty, EnvVarType::String);
+        assert_eq!(
+            spec["APP_ENV"].ty,
+            EnvVarType::Enum(&["dev", "stage", "prod"])
+        );
+        assert_eq!(spec["DATABASE_URL"].ty, EnvVarType::String);
+
+        assert!(spec["DATABASE_URL"].sensitive);
+        assert!(spec["SMTP_PASSWORD"].sensitive);
+        assert!(spec["OTEL_EXPORTER_OTLP_HEADERS"].sensitive);
+        assert!(!spec["AUTH_API_KEY_HEADER"].sensitive);
+        assert!(!spec["APP_PORT"].sensitive);
+    }
+
+    #[test]
+    fn test_render_env_example() {
+        let example = Config::render_env_example();
+        assert!(example.contains("\n# --- App ---\n"), "{example}");
+        assert!(
+            example.contains("# APP_PORT: integer, default 8080\n#APP_PORT=8080\n"),
+            "{example}"
+        );
+        assert!(
+            example.contains("# AUTH_JWT_SECRET: string, sensitive\n#AUTH_JWT_SECRET=\n"),
+            "{example}"
+        );
+        assert!(
+            example.contains("# APP_ENV: one of dev, stage, prod, default dev\n"),
+            "{example}"
+        );
+        assert!(
+            example
+                .lines()
+                .all(|line| line.is_empty() || line.starts_with('#'))
+        );
+    }
+}
@@ -0,0 +1,23 @@
+//! Server-sent events settings
+
+use serde::{Deserialize, Serialize};
+
+/// Defaults of [`ApiSse`](crate::sse::ApiSse) responses
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SseConfig {
+    /// Seconds between keep-alive comments on idle streams (0 disables)
+    #[serde(default = "default_keepalive_seconds")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub sse_keepalive_seconds: u64,
+}
+
+impl Default for SseConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().sse
+    }
+}
+
+fn default_keepalive_seconds() -> u64 {
+    15
+}
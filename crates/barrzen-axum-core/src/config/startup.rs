@@ -0,0 +1,46 @@
+//! Startup readiness gate configuration
+
+use serde::{Deserialize, Serialize};
+
+/// Wait for dependencies before binding (`STARTUP_REQUIRE_READY`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StartupConfig {
+    /// Run the ready checks before binding and fail when they stay unready
+    #[serde(default)]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub startup_require_ready: bool,
+
+    /// Time the critical checks have to pass
+    #[serde(default = "default_ready_timeout_seconds")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub startup_ready_timeout_seconds: u64,
+
+    /// Delay before the second attempt, doubled after each further failure
+    #[serde(default = "default_retry_base_ms")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub startup_retry_base_ms: u64,
+
+    /// Longest delay between attempts
+    #[serde(default = "default_retry_max_ms")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub startup_retry_max_ms: u64,
+}
+
+impl Default for StartupConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().startup
+    }
+}
+
+fn default_ready_timeout_seconds() -> u64 {
+    60
+}
+
+fn default_retry_base_ms() -> u64 {
+    250
+}
+
+fn default_retry_max_ms() -> u64 {
+    5000
+}
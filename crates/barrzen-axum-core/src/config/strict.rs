@@ -0,0 +1,263 @@
+//! Detection of unrecognized variables (`CONFIG_STRICT`)
+//!
+//! envy ignores keys it does not know, so `FEATRUE_CORS=true` silently does
+//! nothing. Variables under one of the config prefixes, or within a few edits
+//! of a known variable or prefix, that match no field are reported with the
+//! closest field name as a suggestion. The field names come from
+//! [`Config::env_spec`].
+
+use std::{collections::BTreeSet, fmt};
+
+use super::{Config, SECRET_FILE_VARS, strip_env_prefix};
+
+/// Prefixes owned by the config; other variables are only reported when
+/// they are close to a known variable or prefix
+const STRICT_PREFIXES: [&str; 36] = [
+    "APP_",
+    "AUDIT_",
+    "AUTH_",
+    "BANNER_",
+    "BROKER_",
+    "CACHE_",
+    "CB_",
+    "COMPRESSION_",
+    "CONFIG_",
+    "CORE_",
+    "CORS_",
+    "DATABASE_",
+    "DB_",
+    "FEATURE_",
+    "HTTP_",
+    "IDEMPOTENCY_",
+    "IP_",
+    "LOG_",
+    "MAINTENANCE_",
+    "MANAGEMENT_",
+    "MEILI_",
+    "NATS_",
+    "OPENAPI_",
+    "OUTBOX_",
+    "READYZ_",
+    "REQUEST_ID_",
+    "REQUEST_LOG_",
+    "RESPONSE_CACHE_",
+    "SECURITY_",
+    "SESSION_",
+    "SMTP_",
+    "SSE_",
+    "STARTUP_",
+    "TENANT_",
+    "UPLOAD_",
+    "WS_",
+];
+
+/// Prefixes of other tools reading variables of their own (the gRPC runtime,
+/// the OpenTelemetry SDK and tokio); variables under them are never reported
+const FOREIGN_PREFIXES: [&str; 3] = ["GRPC_", "OTEL_", "TOKIO_"];
+
+/// Well-known variables of other tools under or close to a config prefix
+const FOREIGN_VARS: [&str; 2] = ["HTTP_PROXY", "HTTPS_PROXY"];
+
+/// Longest edit distance still offered as a suggestion
+const MAX_SUGGESTION_DISTANCE: usize = 3;
+
+/// Variable under a config prefix that matches no field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnrecognizedVar {
+    /// Name as set, including any env prefix
+    pub name: String,
+    /// Closest known variable, with the same env prefix
+    pub suggestion: Option<String>,
+}
+
+impl fmt::Display for UnrecognizedVar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.suggestion {
+            Some(suggestion) => write!(f, "{} (did you mean {suggestion}?)", self.name),
+            None => f.write_str(&self.name),
+        }
+    }
+}
+
+/// Variables among `keys` that look like config but match no field
+///
+/// `prefix` is the normalized env prefix (`ORDERS_`).
+pub(crate) fn unrecognized_vars<'a>(
+    keys: impl IntoIterator<Item = &'a str>,
+    prefix: Option<&str>,
+) -> Vec<UnrecognizedVar> {
+    let known = known_vars();
+    keys.into_iter()
+        .filter_map(|key| {
+            let (env_prefix, name) = match strip_env_prefix(key, prefix) {
+                Some(name) => (prefix.unwrap_or_default(), name),
+                None => ("", key),
+            };
+            let name = name.to_ascii_uppercase();
+            if FOREIGN_PREFIXES.iter().any(|p| name.starts_with(p))
+                || FOREIGN_VARS.contains(&name.as_str())
+                || known.contains(&name)
+                || is_secret_file_var(&name)
+            {
+                return None;
+            }
+            let suggestion = suggest(&name, &known);
+            let config_like = STRICT_PREFIXES.iter().any(|p| name.starts_with(p))
+                || suggestion.is_some()
+                || has_misspelled_prefix(&name);
+            config_like.then(|| UnrecognizedVar {
+                name: key.to_string(),
+                suggestion: suggestion.map(|known| format!("{env_prefix}{known}")),
+            })
+        })
+        .collect()
+}
+
+/// Every variable read by [`Config`], upper case
+pub(crate) fn known_vars() -> BTreeSet<String> {
+    Config::env_spec()
+        .into_iter()
+        .map(|spec| spec.name)
+        .collect()
+}
+
+/// `<NAME>_FILE` of a [`SECRET_FILE_VARS`] entry
+fn is_secret_file_var(name: &str) -> bool {
+    name.strip_suffix("_FILE")
+        .is_some_and(|name| SECRET_FILE_VARS.contains(&name))
+}
+
+/// Closest known variable within [`MAX_SUGGESTION_DISTANCE`] edits
+fn suggest<'a>(name: &str, known: &'a BTreeSet<String>) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| is_close(*distance, candidate))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// First word of `name` is a typo of a one-word config prefix (`FEATRUE_`)
+fn has_misspelled_prefix(name: &str) -> bool {
+    let Some((word, _)) = name.split_once('_') else {
+        return false;
+    };
+    STRICT_PREFIXES
+        .iter()
+        .filter_map(|prefix| prefix.strip_suffix('_'))
+        .filter(|prefix| !prefix.contains('_'))
+        .any(|prefix| is_close(edit_distance(word, prefix), prefix))
+}
+
+/// `distance` edits to `target` are few enough to be a typo
+///
+/// At most [`MAX_SUGGESTION_DISTANCE`] and under a third of `target`, so
+/// short names such as `SSL_` or `PGHOST` are not taken for `SSE_` or
+/// `APP_HOST`.
+fn is_close(distance: usize, target: &str) -> bool {
+    distance <= MAX_SUGGESTION_DISTANCE && 3 * distance < target.len()
+}
+
+/// Levenshtein distance over bytes (variable names are ASCII)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.as_bytes();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.bytes().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_vars_cover_config() {
+        let known = known_vars();
+        assert!(known.contains("FEATURE_CORS"));
+        assert!(known.contains("CONFIG_STRICT"));
+
+        // A section missing from `known_vars` would show up here
+        let serde_json::Value::Object(fields) = serde_json::to_value(Config::default()).unwrap()
+        else {
+            panic!("config is not an object");
+        };
+        for field in fields.keys().filter(|field| *field != "dotenv_files") {
+            assert!(known.contains(&field.to_ascii_uppercase()), "{field}");
+        }
+    }
+
+    #[test]
+    fn test_misspelled_flag_gets_suggestion() {
+        let unrecognized = unrecognized_vars(["FEATRUE_CORS", "FEATURE_CORS"], None);
+        assert_eq!(
+            unrecognized,
+            vec![UnrecognizedVar {
+                name: "FEATRUE_CORS".to_string(),
+                suggestion: Some("FEATURE_CORS".to_string()),
+            }]
+        );
+        assert_eq!(
+            unrecognized[0].to_string(),
+            "FEATRUE_CORS (did you mean FEATURE_CORS?)"
+        );
+    }
+
+    #[test]
+    fn test_unrelated_and_special_vars_are_ignored() {
+        let keys = [
+            "PATH",
+            "HOME",
+            "RUST_LOG",
+            "OTEL_SERVICE_NAME",
+            "GRPC_TRACE",
+            "GRPC_DEFAULT_SSL_ROOTS_FILE_PATH",
+            "HTTP_PROXY",
+            "HTTPS_PROXY",
+            "SSL_CERT_FILE",
+            "PGHOST",
+            "REQUESTS_CA_BUNDLE",
+            "DATABASE_URL_FILE",
+            "app_port",
+            "ENV_PREFIX",
+        ];
+        assert_eq!(unrecognized_vars(keys, None), vec![]);
+    }
+
+    #[test]
+    fn test_misspelled_prefix_is_reported() {
+        let unrecognized = unrecognized_vars(["CONFG_COLOR", "FEATUR_CROS"], None);
+        assert_eq!(unrecognized.len(), 2);
+        assert_eq!(unrecognized[0].to_string(), "CONFG_COLOR");
+        assert_eq!(unrecognized[1].suggestion.as_deref(), Some("FEATURE_CORS"));
+    }
+
+    #[test]
+    fn test_prefixed_and_unknown_vars() {
+        let unrecognized = unrecognized_vars(
+            ["ORDERS_CACHE_TTL_SECOND", "APP_COLOR", "ORDERS_APP_PORT"],
+            Some("ORDERS_"),
+        );
+        assert_eq!(unrecognized.len(), 2);
+        assert_eq!(unrecognized[0].name, "ORDERS_CACHE_TTL_SECOND");
+        assert_eq!(
+            unrecognized[0].suggestion.as_deref(),
+            Some("ORDERS_CACHE_TTL_SECONDS")
+        );
+        assert_eq!(unrecognized[1].to_string(), "APP_COLOR");
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("FEATURE_CORS", "FEATURE_CORS"), 0);
+        assert_eq!(edit_distance("FEATRUE_CORS", "FEATURE_CORS"), 2);
+        assert_eq!(edit_distance("LOG_LEVL", "LOG_LEVEL"), 1);
+        assert_eq!(edit_distance("", "APP"), 3);
+    }
+}
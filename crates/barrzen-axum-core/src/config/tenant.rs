@@ -0,0 +1,58 @@
+//! Tenant resolution configuration
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// Settings for the tenant layer (`FEATURE_TENANT`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TenantConfig {
+    /// Request header carrying the tenant id
+    #[serde(default = "default_header")]
+    pub tenant_header: String,
+
+    /// Regular expression the whole tenant id must match
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub tenant_pattern: Option<String>,
+
+    /// Accepted tenant ids (comma-separated, unset accepts any)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub tenant_allowlist: Option<String>,
+
+    /// Answer 400 to requests without a tenant; `false` serves them without
+    #[serde(default = "default_true")]
+    #[serde(deserialize_with = "crate::config::de_bool")]
+    pub tenant_required: bool,
+}
+
+impl Default for TenantConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().tenant
+    }
+}
+
+impl TenantConfig {
+    /// Tenant ids from `TENANT_ALLOWLIST`; empty means any
+    #[must_use]
+    pub fn allowlist(&self) -> Vec<String> {
+        self.tenant_allowlist
+            .as_deref()
+            .map(|list| {
+                list.split(',')
+                    .map(str::trim)
+                    .filter(|entry| !entry.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn default_header() -> String {
+    "x-tenant-id".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
@@ -0,0 +1,56 @@
+//! Multipart upload configuration
+
+use serde::{Deserialize, Serialize};
+
+use super::empty_string_as_none;
+
+/// Limits for `ApiMultipart` (`uploads` feature)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadConfig {
+    /// Largest single part (file or field) in bytes
+    #[serde(default = "default_max_file_size_bytes")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub upload_max_file_size_bytes: u64,
+
+    /// Most file parts per request
+    #[serde(default = "default_max_files")]
+    #[serde(deserialize_with = "crate::config::de_usize")]
+    pub upload_max_files: usize,
+
+    /// Content types accepted for file parts (comma-separated, `image/*`
+    /// matches a whole type; unset accepts any)
+    #[serde(default, deserialize_with = "empty_string_as_none")]
+    pub upload_allowed_content_types: Option<String>,
+}
+
+impl Default for UploadConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().upload
+    }
+}
+
+impl UploadConfig {
+    /// Lower-cased entries of `UPLOAD_ALLOWED_CONTENT_TYPES`; empty means any
+    #[must_use]
+    pub fn allowed_content_types(&self) -> Vec<String> {
+        self.upload_allowed_content_types
+            .as_deref()
+            .map(|types| {
+                types
+                    .split(',')
+                    .map(|entry| entry.trim().to_ascii_lowercase())
+                    .filter(|entry| !entry.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn default_max_file_size_bytes() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_max_files() -> usize {
+    10
+}
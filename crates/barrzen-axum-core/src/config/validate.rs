@@ -0,0 +1,1036 @@
+//! Cross-field configuration validation
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::http::{HeaderName, HeaderValue};
+
+use super::{
+    BrokerBackend, CacheBackend, Config, ConfigError, Environment, LogBackend, LogRotation,
+    OpenApiAuth, RequestLogBody, RunMigrations,
+};
+use crate::client_ip::parse_network;
+
+impl Config {
+    /// Check the configuration for invalid values and conflicting settings
+    ///
+    /// Every problem is collected so they can be fixed in one go.
+    ///
+    /// # Errors
+    /// Returns [`ConfigError::Validation`] listing every violation.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let problems = self.problems();
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        Err(ConfigError::Validation(format!(
+            "{} problem(s) found:\n  - {}",
+            problems.len(),
+            problems.join("\n  - ")
+        )))
+    }
+
+    #[allow(clippy::too_many_lines)] // one check per setting
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let features = &self.features;
+
+        // Server
+        if self.app.app_uds_path.is_none() {
+            host_problems("APP_HOST", &self.app.hosts(), &mut problems);
+        }
+        if self.app.app_uds_path.is_none()
+            && self.app.app_port == 0
+            && self.app.app_env == Environment::Prod
+        {
+            problems.push("APP_PORT must not be 0 when APP_ENV=prod".to_string());
+        }
+        if self.app.app_uds_path.is_some()
+            && u32::from_str_radix(&self.app.app_uds_mode, 8).is_err()
+        {
+            problems.push(format!(
+                "APP_UDS_MODE must be an octal permission string, got {:?}",
+                self.app.app_uds_mode
+            ));
+        }
+        self.management_problems(&mut problems);
+        self.grpc_problems(&mut problems);
+        self.http_problems(&mut problems);
+        if self.request_id.header_name().is_none() {
+            problems.push(format!(
+                "REQUEST_ID_HEADER_NAME must be a valid header name, got {:?}",
+                self.request_id.request_id_header_name
+            ));
+        }
+        if self.request_id.request_id_max_length == 0 {
+            problems.push("REQUEST_ID_MAX_LENGTH must be greater than 0".to_string());
+        }
+
+        // Logging
+        self.logging_problems(&mut problems);
+
+        // OpenTelemetry
+        if features.feature_otel {
+            self.otel_problems(&mut problems);
+        }
+
+        // Readiness
+        let invalid_http_checks = self.readiness.invalid_http_checks();
+        if !invalid_http_checks.is_empty() {
+            problems.push(format!(
+                "READYZ_HTTP_CHECKS entries must be name=http(s)://host/path, got {}",
+                invalid_http_checks.join(",")
+            ));
+        }
+        if self.readiness.readyz_http_checks.is_some()
+            && self.readiness.readyz_http_check_timeout_ms == 0
+        {
+            problems.push("READYZ_HTTP_CHECK_TIMEOUT_MS must be greater than 0".to_string());
+        }
+        let startup = &self.startup;
+        if startup.startup_require_ready {
+            if startup.startup_ready_timeout_seconds == 0 {
+                problems.push("STARTUP_READY_TIMEOUT_SECONDS must be greater than 0".to_string());
+            }
+            if startup.startup_retry_base_ms == 0 {
+                problems.push("STARTUP_RETRY_BASE_MS must be greater than 0".to_string());
+            }
+            if startup.startup_retry_base_ms > startup.startup_retry_max_ms {
+                problems.push(format!(
+                    "STARTUP_RETRY_BASE_MS must not exceed STARTUP_RETRY_MAX_MS ({})",
+                    startup.startup_retry_max_ms
+                ));
+            }
+        }
+
+        // Database
+        if self.database.db_run_migrations != RunMigrations::Off && !features.feature_db {
+            problems.push(format!(
+                "DB_RUN_MIGRATIONS={} requires FEATURE_DB=true",
+                self.database.db_run_migrations
+            ));
+        }
+        let database = &self.database;
+        for (prefix, max, min) in [
+            (
+                "DB",
+                database.db_max_connections,
+                database.db_min_connections,
+            ),
+            (
+                "DB_READ",
+                database.db_read_max_connections,
+                database.db_read_min_connections,
+            ),
+        ] {
+            if max == 0 {
+                problems.push(format!("{prefix}_MAX_CONNECTIONS must be greater than 0"));
+            } else if min > max {
+                problems.push(format!(
+                    "{prefix}_MIN_CONNECTIONS must not exceed {prefix}_MAX_CONNECTIONS ({max})"
+                ));
+            }
+        }
+
+        // Circuit breakers
+        let breaker = &self.circuit_breaker;
+        for (name, value) in [
+            (
+                "CB_FAILURE_THRESHOLD",
+                u64::from(breaker.cb_failure_threshold),
+            ),
+            ("CB_OPEN_DURATION_MS", breaker.cb_open_duration_ms),
+            (
+                "CB_HALF_OPEN_PROBES",
+                u64::from(breaker.cb_half_open_probes),
+            ),
+        ] {
+            if value == 0 {
+                problems.push(format!("{name} must be greater than 0"));
+            }
+        }
+
+        // Outbox relay
+        let outbox = &self.outbox;
+        for (name, value) in [
+            ("OUTBOX_POLL_INTERVAL_MS", outbox.outbox_poll_interval_ms),
+            ("OUTBOX_BATCH_SIZE", outbox.outbox_batch_size),
+            ("OUTBOX_MAX_ATTEMPTS", u64::from(outbox.outbox_max_attempts)),
+        ] {
+            if value == 0 {
+                problems.push(format!("{name} must be greater than 0"));
+            }
+        }
+        if outbox.outbox_retry_base_ms > outbox.outbox_retry_max_ms {
+            problems.push(format!(
+                "OUTBOX_RETRY_BASE_MS must not exceed OUTBOX_RETRY_MAX_MS ({})",
+                outbox.outbox_retry_max_ms
+            ));
+        }
+
+        // Mail
+        if features.feature_mailer {
+            self.mail_problems(&mut problems);
+        }
+
+        // Search
+        if features.feature_search && self.search.meili_url.is_none() {
+            problems.push("FEATURE_SEARCH=true requires MEILI_URL".to_string());
+        }
+        if self.search.meili_task_timeout_seconds == 0 {
+            problems.push("MEILI_TASK_TIMEOUT_SECONDS must be greater than 0".to_string());
+        }
+        if self.search.meili_batch_size == 0 {
+            problems.push("MEILI_BATCH_SIZE must be greater than 0".to_string());
+        }
+
+        // OpenAPI
+        if features.feature_openapi {
+            let openapi = &self.openapi;
+            match (openapi.docs_path(), openapi.spec_path()) {
+                (Some(docs), Some(spec)) if docs == spec => problems.push(format!(
+                    "OPENAPI_DOCS_PATH and OPENAPI_SPEC_PATH must differ, both are {docs:?}"
+                )),
+                (Some(_), Some(_)) => {}
+                _ => problems
+                    .push("OPENAPI_DOCS_PATH and OPENAPI_SPEC_PATH must not be empty".to_string()),
+            }
+        }
+        if self.openapi.openapi_export_required && self.openapi.openapi_export_path.is_none() {
+            problems.push("OPENAPI_EXPORT_REQUIRED=true requires OPENAPI_EXPORT_PATH".to_string());
+        }
+        if features.feature_openapi {
+            match self.openapi.openapi_auth {
+                OpenApiAuth::Basic
+                    if self.openapi.openapi_basic_user.is_none()
+                        || self.openapi.openapi_basic_password.is_none() =>
+                {
+                    problems.push(
+                        "OPENAPI_AUTH=basic requires OPENAPI_BASIC_USER and OPENAPI_BASIC_PASSWORD"
+                            .to_string(),
+                    );
+                }
+                OpenApiAuth::ApiKey if self.auth.api_keys().is_empty() => {
+                    problems.push("OPENAPI_AUTH=api_key requires AUTH_API_KEYS".to_string());
+                }
+                _ => {}
+            }
+        }
+
+        // CORS
+        if features.feature_cors && self.cors.cors_allow_credentials {
+            let origins = self.cors.origins();
+            if origins.is_empty() {
+                problems.push(
+                    "CORS_ALLOW_CREDENTIALS=true requires CORS_ALLOW_ORIGINS to list the allowed origins"
+                        .to_string(),
+                );
+            } else if origins.iter().any(|origin| origin == "*") {
+                problems.push(
+                    "CORS_ALLOW_ORIGINS=* cannot be combined with CORS_ALLOW_CREDENTIALS=true"
+                        .to_string(),
+                );
+            }
+        }
+
+        // Cache and sessions
+        let cache_backend = features
+            .feature_cache
+            .then_some(self.cache.cache_backend)
+            .filter(|backend| *backend != CacheBackend::None);
+        if let Some(backend @ (CacheBackend::Redis | CacheBackend::Tiered)) = cache_backend
+            && self.cache.cache_redis_url.is_none()
+        {
+            problems.push(format!("CACHE_BACKEND={backend} requires CACHE_REDIS_URL"));
+        }
+        if cache_backend == Some(CacheBackend::Tiered) && self.cache.cache_l1_ttl_seconds == 0 {
+            problems.push("CACHE_L1_TTL_SECONDS must be greater than 0".to_string());
+        }
+        if features.feature_session && cache_backend.is_none() {
+            problems.push(
+                "FEATURE_SESSION requires FEATURE_CACHE=true with CACHE_BACKEND other than none"
+                    .to_string(),
+            );
+        }
+
+        // Broker
+        if features.feature_broker
+            && self.broker.broker_backend == BrokerBackend::Nats
+            && self.broker.nats_url.is_none()
+        {
+            problems.push("BROKER_BACKEND=nats requires NATS_URL".to_string());
+        }
+
+        // Idempotency
+        self.idempotency_problems(&mut problems);
+
+        // Response cache
+        let invalid_headers: Vec<_> = self
+            .response_cache
+            .vary_headers()
+            .into_iter()
+            .filter(|name| HeaderName::from_bytes(name.as_bytes()).is_err())
+            .collect();
+        if !invalid_headers.is_empty() {
+            problems.push(format!(
+                "RESPONSE_CACHE_VARY_HEADERS contains invalid header names: {}",
+                invalid_headers.join(", ")
+            ));
+        }
+
+        // Tenant
+        if HeaderName::from_bytes(self.tenant.tenant_header.trim().as_bytes()).is_err() {
+            problems.push(format!(
+                "TENANT_HEADER is not a valid header name: {:?}",
+                self.tenant.tenant_header
+            ));
+        }
+        if let Some(pattern) = &self.tenant.tenant_pattern
+            && let Err(e) = regex::Regex::new(pattern)
+        {
+            problems.push(format!(
+                "TENANT_PATTERN is not a valid regular expression: {e}"
+            ));
+        }
+
+        // Networks
+        for (name, entries) in [
+            ("IP_ALLOWLIST", self.ip_filter.allowlist()),
+            ("IP_DENYLIST", self.ip_filter.denylist()),
+            ("TRUSTED_PROXIES", self.client_ip.trusted_proxies()),
+        ] {
+            let invalid: Vec<_> = entries
+                .iter()
+                .filter(|entry| parse_network(entry).is_none())
+                .map(String::as_str)
+                .collect();
+            if !invalid.is_empty() {
+                problems.push(format!(
+                    "{name} contains invalid CIDR blocks: {}",
+                    invalid.join(", ")
+                ));
+            }
+        }
+
+        problems
+    }
+
+    fn http_problems(&self, problems: &mut Vec<String>) {
+        if self.http.http_body_limit_bytes == 0 {
+            problems.push("HTTP_BODY_LIMIT_BYTES must be greater than 0".to_string());
+        }
+        if self.features.feature_load_shed && self.http.http_max_concurrent_requests == 0 {
+            problems.push(
+                "FEATURE_LOAD_SHED requires HTTP_MAX_CONCURRENT_REQUESTS to be greater than 0"
+                    .to_string(),
+            );
+        }
+        if self.features.feature_compression {
+            let invalid = self.compression.invalid_algorithms();
+            if !invalid.is_empty() {
+                problems.push(format!(
+                    "COMPRESSION_ALGORITHMS entries must be gzip, br or zstd, got {}",
+                    invalid.join(",")
+                ));
+            } else if self.compression.algorithms().is_empty() {
+                problems.push(
+                    "COMPRESSION_ALGORITHMS must list an encoding; set FEATURE_COMPRESSION=false \
+                     to disable compression"
+                        .to_string(),
+                );
+            }
+        }
+        if self.upload.upload_max_file_size_bytes == 0 {
+            problems.push("UPLOAD_MAX_FILE_SIZE_BYTES must be greater than 0".to_string());
+        }
+        if self.upload.upload_max_files == 0 {
+            problems.push("UPLOAD_MAX_FILES must be greater than 0".to_string());
+        }
+        if let Some(cache_control) = &self.http.http_cache_control_default
+            && HeaderValue::from_str(cache_control).is_err()
+        {
+            problems.push(format!(
+                "HTTP_CACHE_CONTROL_DEFAULT must be a valid header value, got {cache_control:?}"
+            ));
+        }
+        if self.http_client.http_client_timeout_seconds == 0 {
+            problems.push("HTTP_CLIENT_TIMEOUT_SECONDS must be greater than 0".to_string());
+        }
+        if self.http_client.http_client_connect_timeout_seconds == 0 {
+            problems.push("HTTP_CLIENT_CONNECT_TIMEOUT_SECONDS must be greater than 0".to_string());
+        }
+    }
+
+    fn idempotency_problems(&self, problems: &mut Vec<String>) {
+        if self.idempotency.idempotency_ttl_seconds == 0 {
+            problems.push("IDEMPOTENCY_TTL_SECONDS must be greater than 0".to_string());
+        }
+        let invalid_methods: Vec<_> = self
+            .idempotency
+            .methods()
+            .into_iter()
+            .filter(|method| axum::http::Method::from_bytes(method.as_bytes()).is_err())
+            .collect();
+        if !invalid_methods.is_empty() {
+            problems.push(format!(
+                "IDEMPOTENCY_METHODS contains invalid methods: {}",
+                invalid_methods.join(", ")
+            ));
+        }
+    }
+
+    fn mail_problems(&self, problems: &mut Vec<String>) {
+        let mail = &self.mail;
+        if mail.smtp_host.is_none() {
+            problems.push("FEATURE_MAILER=true requires SMTP_HOST".to_string());
+        }
+        match mail.smtp_from.as_deref() {
+            None => problems.push("FEATURE_MAILER=true requires SMTP_FROM".to_string()),
+            Some(from) if !from.contains('@') => {
+                problems.push(format!("SMTP_FROM must be an email address, got {from:?}"));
+            }
+            Some(_) => {}
+        }
+        if mail.smtp_password.is_some() && mail.smtp_user.is_none() {
+            problems.push("SMTP_PASSWORD requires SMTP_USER".to_string());
+        }
+        if mail.smtp_pool_size == 0 {
+            problems.push("SMTP_POOL_SIZE must be greater than 0".to_string());
+        }
+    }
+
+    fn logging_problems(&self, problems: &mut Vec<String>) {
+        if self.features.feature_otel && self.logging.log_backend == LogBackend::FastLog {
+            problems
+                .push("LOG_BACKEND=fast_log is not compatible with FEATURE_OTEL=true".to_string());
+        }
+        if self.logging.log_backend == LogBackend::FastLog && self.logging.fast_log_chunk_size == 0
+        {
+            problems.push("FAST_LOG_CHUNK_SIZE must be greater than 0".to_string());
+        }
+        let invalid_redactions = self.logging.invalid_path_redactions();
+        if !invalid_redactions.is_empty() {
+            problems.push(format!(
+                "REQUEST_LOG_PATH_REDACTIONS entries must be /path/pattern=mask|hash, got {}",
+                invalid_redactions.join(",")
+            ));
+        }
+        if self.logging.request_log_body != RequestLogBody::Off
+            && self.logging.request_log_body_max_bytes == 0
+        {
+            problems.push("REQUEST_LOG_BODY_MAX_BYTES must be greater than 0".to_string());
+        }
+        if self.features.feature_tokio_console
+            && self
+                .logging
+                .tokio_console_bind
+                .parse::<SocketAddr>()
+                .is_err()
+        {
+            problems.push(format!(
+                "TOKIO_CONSOLE_BIND must be an address like 127.0.0.1:6669, got {:?}",
+                self.logging.tokio_console_bind
+            ));
+        }
+        if self.logging.log_output.to_file() {
+            if self.logging.log_file_dir.trim().is_empty() {
+                problems.push("LOG_FILE_DIR must not be empty when logging to files".to_string());
+            }
+            if self.logging.log_file_rotation == LogRotation::Size
+                && self.logging.log_file_max_size_mb == 0
+            {
+                problems.push(
+                    "LOG_FILE_MAX_SIZE_MB must be greater than 0 when LOG_FILE_ROTATION=size"
+                        .to_string(),
+                );
+            }
+        }
+    }
+
+    fn management_problems(&self, problems: &mut Vec<String>) {
+        let Some(port) = self.management.management_port else {
+            return;
+        };
+        host_problems("MANAGEMENT_HOST", &self.management.hosts(), problems);
+        if port == 0 && self.app.app_env == Environment::Prod {
+            problems.push("MANAGEMENT_PORT must not be 0 when APP_ENV=prod".to_string());
+        }
+        if port != 0 && port == self.app.app_port && self.app.app_uds_path.is_none() {
+            problems.push(format!(
+                "MANAGEMENT_PORT must differ from APP_PORT, both are {port}"
+            ));
+        }
+    }
+
+    fn grpc_problems(&self, problems: &mut Vec<String>) {
+        let Some(port) = self.grpc.grpc_port else {
+            return;
+        };
+        if port == 0 && self.app.app_env == Environment::Prod {
+            problems.push("GRPC_PORT must not be 0 when APP_ENV=prod".to_string());
+        }
+        if port != 0 && port == self.app.app_port && self.app.app_uds_path.is_none() {
+            problems.push(format!(
+                "GRPC_PORT must differ from APP_PORT, both are {port}; unset it to share the port"
+            ));
+        }
+        if port != 0 && self.management.management_port == Some(port) {
+            problems.push(format!(
+                "GRPC_PORT must differ from MANAGEMENT_PORT, both are {port}"
+            ));
+        }
+    }
+
+    fn otel_problems(&self, problems: &mut Vec<String>) {
+        let otel = &self.otel;
+        if otel.otel_traces_sampler.uses_ratio()
+            && otel.otel_traces_sampler_arg.is_some()
+            && otel.valid_sampler_ratio().is_none()
+        {
+            problems.push(format!(
+                "OTEL_TRACES_SAMPLER_ARG must be a ratio between 0 and 1, got {:?}",
+                otel.otel_traces_sampler_arg.as_deref().unwrap_or_default()
+            ));
+        }
+
+        // Header values are credentials: name the header, never the value
+        if otel.malformed_header_entries() > 0 {
+            problems.push("OTEL_EXPORTER_OTLP_HEADERS entries must be key=value pairs".to_string());
+        }
+        for (name, value) in otel.exporter_headers() {
+            if HeaderName::from_bytes(name.as_bytes()).is_err() {
+                problems.push(format!(
+                    "OTEL_EXPORTER_OTLP_HEADERS has an invalid header name {name:?}"
+                ));
+            } else if HeaderValue::from_str(&value).is_err() {
+                problems.push(format!(
+                    "OTEL_EXPORTER_OTLP_HEADERS has an invalid value for {name:?}"
+                ));
+            }
+        }
+    }
+}
+
+/// Every entry of a host list (`APP_HOST`) must be an IP address or hostname
+fn host_problems(var: &str, hosts: &[&str], problems: &mut Vec<String>) {
+    if hosts.is_empty() {
+        problems.push(format!("{var} must list at least one address"));
+    }
+    for host in hosts {
+        if host.parse::<IpAddr>().is_err() && !is_hostname(host) {
+            problems.push(format!(
+                "{var} entries must be IP addresses or hostnames, got {host:?}"
+            ));
+        }
+    }
+}
+
+/// RFC 1123 hostname: dot-separated labels of letters, digits and inner hyphens
+fn is_hostname(host: &str) -> bool {
+    host.len() <= 253
+        && host.trim_end_matches('.').split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+    use serde_json::json;
+
+    #[test]
+    fn test_valid_config_passes() {
+        assert!(test_config().validate().is_ok());
+    }
+
+    #[test]
+    fn test_every_violation_is_reported() {
+        let config: Config = serde_json::from_value(json!({
+            "app_env": "prod",
+            "app_host": "0.0.0.0, my_host",
+            "app_port": "0",
+            "log_backend": "fast_log",
+            "feature_otel": "true",
+            "feature_cors": "true",
+            "cors_allow_credentials": "true",
+            "feature_cache": "true",
+            "cache_backend": "redis",
+            "ip_allowlist": "10.0.0.0/8, 10.0.0.0/33",
+            "trusted_proxies": "proxy"
+        }))
+        .unwrap();
+
+        let message = config.validate().unwrap_err().to_string();
+        for expected in [
+            "7 problem(s) found",
+            "APP_HOST entries must be IP addresses or hostnames, got \"my_host\"",
+            "APP_PORT must not be 0 when APP_ENV=prod",
+            "LOG_BACKEND=fast_log is not compatible with FEATURE_OTEL=true",
+            "CORS_ALLOW_CREDENTIALS=true requires CORS_ALLOW_ORIGINS",
+            "CACHE_BACKEND=redis requires CACHE_REDIS_URL",
+            "IP_ALLOWLIST contains invalid CIDR blocks: 10.0.0.0/33",
+            "TRUSTED_PROXIES contains invalid CIDR blocks: proxy",
+        ] {
+            assert!(
+                message.contains(expected),
+                "missing {expected:?} in {message}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_host_lists_and_hostnames() {
+        let mut config = test_config();
+        for host in ["::", "[::1]", "localhost", "api.internal", "127.0.0.1, ::1"] {
+            config.app.app_host = host.to_string();
+            assert!(config.validate().is_ok(), "{host}");
+        }
+
+        config.app.app_host = " , ".to_string();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("APP_HOST must list at least one address"));
+
+        config.app.app_host = "127.0.0.1,-bad-,::1".to_string();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("got \"-bad-\""), "{message}");
+    }
+
+    #[test]
+    fn test_management_violations() {
+        let mut config = test_config();
+        config.app.app_port = 8080;
+        config.management.management_port = Some(8080);
+        config.management.management_host = "internal_host".to_string();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("MANAGEMENT_PORT must differ from APP_PORT, both are 8080"));
+        assert!(message.contains("MANAGEMENT_HOST entries must be IP addresses or hostnames"));
+
+        config.management.management_port = Some(9090);
+        config.management.management_host = "127.0.0.1".to_string();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_grpc_port_must_be_its_own() {
+        let mut config = test_config();
+        config.app.app_port = 8080;
+        config.management.management_port = Some(9090);
+        config.grpc.grpc_port = Some(8080);
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("GRPC_PORT must differ from APP_PORT, both are 8080"));
+
+        config.grpc.grpc_port = Some(9090);
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("GRPC_PORT must differ from MANAGEMENT_PORT, both are 9090"));
+
+        config.grpc.grpc_port = Some(50051);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_startup_gate_settings() {
+        let mut config = test_config();
+        config.startup.startup_ready_timeout_seconds = 0;
+        assert!(config.validate().is_ok());
+
+        config.startup.startup_require_ready = true;
+        config.startup.startup_retry_base_ms = 10_000;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("STARTUP_READY_TIMEOUT_SECONDS must be greater than 0"));
+        assert!(
+            message.contains("STARTUP_RETRY_BASE_MS must not exceed"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_request_id_settings() {
+        let mut config = test_config();
+        config.request_id.request_id_header_name = "x correlation id".to_string();
+        config.request_id.request_id_max_length = 0;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("REQUEST_ID_HEADER_NAME must be a valid header name"));
+        assert!(message.contains("REQUEST_ID_MAX_LENGTH must be greater than 0"));
+    }
+
+    #[test]
+    fn test_tiered_cache_settings() {
+        let mut config = test_config();
+        config.features.feature_cache = true;
+        config.cache.cache_backend = CacheBackend::Tiered;
+        config.cache.cache_l1_ttl_seconds = 0;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("CACHE_BACKEND=tiered requires CACHE_REDIS_URL"),
+            "{message}"
+        );
+        assert!(
+            message.contains("CACHE_L1_TTL_SECONDS must be greater than 0"),
+            "{message}"
+        );
+
+        config.cache.cache_redis_url = Some("redis://cache:6379".to_string());
+        config.cache.cache_l1_ttl_seconds = 30;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_broker_requires_url() {
+        let mut config = test_config();
+        config.features.feature_broker = true;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("BROKER_BACKEND=nats requires NATS_URL"),
+            "{message}"
+        );
+
+        config.broker.nats_url = Some("nats://nats:4222".to_string());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_session_and_wildcard_cors_violations() {
+        let mut config = test_config();
+        config.features.feature_session = true;
+        config.features.feature_cors = true;
+        config.cors.cors_allow_credentials = true;
+        config.cors.cors_allow_origins = Some("*".to_string());
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("2 problem(s) found"));
+        assert!(message.contains("FEATURE_SESSION requires FEATURE_CACHE=true"));
+        assert!(message.contains("CORS_ALLOW_ORIGINS=* cannot be combined"));
+    }
+
+    #[test]
+    fn test_path_redaction_violation() {
+        let mut config = test_config();
+        config.logging.request_log_path_redactions =
+            Some("/password-reset/*=mask,/invites/*=erase".to_string());
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("1 problem(s) found"), "{message}");
+        assert!(message.contains("got /invites/*=erase"), "{message}");
+    }
+
+    #[test]
+    fn test_request_log_body_needs_a_cap() {
+        let mut config = test_config();
+        config.logging.request_log_body_max_bytes = 0;
+        assert!(config.validate().is_ok());
+
+        config.logging.request_log_body = RequestLogBody::OnError;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("REQUEST_LOG_BODY_MAX_BYTES must be greater than 0"));
+    }
+
+    #[test]
+    fn test_http_check_violations() {
+        let mut config = test_config();
+        config.readiness.readyz_http_checks =
+            Some("payments=https://payments.internal/health,auth=auth:8080".to_string());
+        config.readiness.readyz_http_check_timeout_ms = 0;
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("2 problem(s) found"), "{message}");
+        assert!(
+            message.contains("READYZ_HTTP_CHECKS entries must be"),
+            "{message}"
+        );
+        assert!(message.contains("got auth=auth:8080"), "{message}");
+        assert!(message.contains("READYZ_HTTP_CHECK_TIMEOUT_MS must be greater than 0"));
+    }
+
+    #[test]
+    fn test_run_migrations_requires_db() {
+        let mut config = test_config();
+        config.database.db_run_migrations = RunMigrations::Check;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("DB_RUN_MIGRATIONS=check requires FEATURE_DB=true"),
+            "{message}"
+        );
+
+        config.features.feature_db = true;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_database_pool_sizes() {
+        let mut config = test_config();
+        config.database.db_read_min_connections = 10;
+        config.database.db_read_max_connections = 4;
+        config.database.db_max_connections = 0;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("DB_MAX_CONNECTIONS must be greater than 0"),
+            "{message}"
+        );
+        let expected = "DB_READ_MIN_CONNECTIONS must not exceed DB_READ_MAX_CONNECTIONS (4)";
+        assert!(message.contains(expected), "{message}");
+    }
+
+    #[test]
+    fn test_circuit_breaker_settings() {
+        let mut config = test_config();
+        config.circuit_breaker.cb_half_open_probes = 0;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("CB_HALF_OPEN_PROBES must be greater than 0"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_mail_settings() {
+        let mut config = test_config();
+        config.features.feature_mailer = true;
+        config.mail.smtp_from = Some("orders".to_string());
+        config.mail.smtp_password = Some("secret".to_string());
+        let message = config.validate().unwrap_err().to_string();
+        for expected in [
+            "FEATURE_MAILER=true requires SMTP_HOST",
+            "SMTP_FROM must be an email address, got \"orders\"",
+            "SMTP_PASSWORD requires SMTP_USER",
+        ] {
+            assert!(
+                message.contains(expected),
+                "missing {expected:?} in {message}"
+            );
+        }
+
+        config.mail.smtp_host = Some("smtp.example.com".to_string());
+        config.mail.smtp_from = Some("Orders <orders@example.com>".to_string());
+        config.mail.smtp_user = Some("orders".to_string());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_outbox_settings() {
+        let mut config = test_config();
+        config.outbox.outbox_batch_size = 0;
+        config.outbox.outbox_retry_base_ms = 10_000;
+        config.outbox.outbox_retry_max_ms = 1000;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("OUTBOX_BATCH_SIZE must be greater than 0"),
+            "{message}"
+        );
+        assert!(
+            message.contains("OUTBOX_RETRY_BASE_MS must not exceed"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_search_settings() {
+        let mut config = test_config();
+        config.search.meili_task_timeout_seconds = 0;
+        config.search.meili_batch_size = 0;
+        config.features.feature_search = true;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("FEATURE_SEARCH=true requires MEILI_URL"),
+            "{message}"
+        );
+        assert!(
+            message.contains("MEILI_TASK_TIMEOUT_SECONDS must be greater than 0"),
+            "{message}"
+        );
+        assert!(
+            message.contains("MEILI_BATCH_SIZE must be greater than 0"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_openapi_path_violations() {
+        let mut config = test_config();
+        config.openapi.openapi_spec_path = "/docs/".to_string();
+        assert!(config.validate().is_ok());
+
+        config.features.feature_openapi = true;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("OPENAPI_DOCS_PATH and OPENAPI_SPEC_PATH must differ"));
+
+        config.openapi.openapi_spec_path = " ".to_string();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("must not be empty"), "{message}");
+
+        let mut config = test_config();
+        config.openapi.openapi_export_required = true;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("OPENAPI_EXPORT_REQUIRED=true requires OPENAPI_EXPORT_PATH"));
+
+        let mut config = test_config();
+        config.features.feature_openapi = true;
+        config.openapi.openapi_auth = OpenApiAuth::Basic;
+        config.openapi.openapi_basic_user = Some("docs".to_string());
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("OPENAPI_AUTH=basic requires OPENAPI_BASIC_USER"),
+            "{message}"
+        );
+
+        config.openapi.openapi_auth = OpenApiAuth::ApiKey;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("OPENAPI_AUTH=api_key requires AUTH_API_KEYS"),
+            "{message}"
+        );
+
+        config.auth.auth_api_keys = Some("docs:k1".to_string());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_compression_violations() {
+        let mut config = test_config();
+        config.compression.compression_algorithms = "gzip, deflate".to_string();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message
+                .contains("COMPRESSION_ALGORITHMS entries must be gzip, br or zstd, got deflate")
+        );
+
+        config.compression.compression_algorithms = " ,".to_string();
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("set FEATURE_COMPRESSION=false"),
+            "{message}"
+        );
+
+        config.features.feature_compression = false;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_upload_limits_must_be_positive() {
+        let mut config = test_config();
+        config.upload.upload_max_file_size_bytes = 0;
+        config.upload.upload_max_files = 0;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("UPLOAD_MAX_FILE_SIZE_BYTES must be greater than 0"));
+        assert!(message.contains("UPLOAD_MAX_FILES must be greater than 0"));
+    }
+
+    #[test]
+    fn test_load_shed_requires_a_limit() {
+        let mut config = test_config();
+        config.features.feature_load_shed = true;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("FEATURE_LOAD_SHED requires HTTP_MAX_CONCURRENT_REQUESTS"));
+
+        config.http.http_max_concurrent_requests = 64;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_idempotency_violations() {
+        let mut config = test_config();
+        config.idempotency.idempotency_methods = "post, put,bad method".to_string();
+        config.idempotency.idempotency_ttl_seconds = 0;
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("2 problem(s) found"), "{message}");
+        assert!(message.contains("IDEMPOTENCY_METHODS contains invalid methods: BAD METHOD"));
+        assert!(message.contains("IDEMPOTENCY_TTL_SECONDS must be greater than 0"));
+    }
+
+    #[test]
+    fn test_response_cache_vary_headers_violation() {
+        let mut config = test_config();
+        config.response_cache.response_cache_vary_headers = "Accept-Language, bad header".into();
+
+        let message = config.validate().unwrap_err().to_string();
+        let expected = "RESPONSE_CACHE_VARY_HEADERS contains invalid header names: bad header";
+        assert!(message.contains(expected), "{message}");
+    }
+
+    #[test]
+    fn test_http_client_timeout_violations() {
+        let mut config = test_config();
+        config.http_client.http_client_timeout_seconds = 0;
+        config.http_client.http_client_connect_timeout_seconds = 0;
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("2 problem(s) found"), "{message}");
+        assert!(message.contains("HTTP_CLIENT_TIMEOUT_SECONDS must be greater than 0"));
+        assert!(message.contains("HTTP_CLIENT_CONNECT_TIMEOUT_SECONDS must be greater than 0"));
+    }
+
+    #[test]
+    fn test_tenant_violations() {
+        let mut config = test_config();
+        config.tenant.tenant_header = "bad header".into();
+        config.tenant.tenant_pattern = Some("[a-z".into());
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("2 problem(s) found"), "{message}");
+        assert!(message.contains("TENANT_HEADER is not a valid header name: \"bad header\""));
+        assert!(message.contains("TENANT_PATTERN is not a valid regular expression"));
+    }
+
+    #[test]
+    fn test_tokio_console_bind_violation() {
+        let mut config = test_config();
+        config.logging.tokio_console_bind = "localhost".to_string();
+        assert!(config.validate().is_ok());
+
+        config.features.feature_tokio_console = true;
+        let message = config.validate().unwrap_err().to_string();
+        assert!(
+            message.contains("TOKIO_CONSOLE_BIND must be an address"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn test_log_file_violations() {
+        let mut config = test_config();
+        config.logging.log_output = crate::config::LogOutput::File;
+        config.logging.log_backend = LogBackend::FastLog;
+        config.logging.log_file_rotation = LogRotation::Size;
+        config.logging.log_file_max_size_mb = 0;
+        config.logging.fast_log_chunk_size = 0;
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("2 problem(s) found"));
+        assert!(message.contains("FAST_LOG_CHUNK_SIZE must be greater than 0"));
+        assert!(message.contains("LOG_FILE_MAX_SIZE_MB must be greater than 0"));
+    }
+
+    #[test]
+    fn test_otel_sampler_arg_violation() {
+        let mut config = test_config();
+        config.features.feature_otel = true;
+        config.otel.otel_traces_sampler_arg = Some("10%".to_string());
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("OTEL_TRACES_SAMPLER_ARG must be a ratio between 0 and 1"));
+
+        config.otel.otel_traces_sampler = crate::config::TraceSampler::AlwaysOn;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_otel_header_violations_hide_values() {
+        let mut config = test_config();
+        config.features.feature_otel = true;
+        config.otel.otel_exporter_otlp_headers =
+            Some("authorization=Bearer s3\ncr3t,bad name=x,nokey".to_string());
+
+        let message = config.validate().unwrap_err().to_string();
+        assert!(message.contains("3 problem(s) found"), "{message}");
+        assert!(message.contains("entries must be key=value pairs"));
+        assert!(message.contains("invalid header name \"bad name\""));
+        assert!(message.contains("invalid value for \"authorization\""));
+        assert!(!message.contains("cr3t"), "{message}");
+    }
+}
@@ -0,0 +1,33 @@
+//! WebSocket settings
+
+use serde::{Deserialize, Serialize};
+
+/// Defaults of [`WsSession`](crate::ws::WsSession) connections
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WsConfig {
+    /// Seconds between pings sent to the client (0 disables)
+    #[serde(default = "default_ping_interval_seconds")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub ws_ping_interval_seconds: u64,
+
+    /// Seconds without a text or binary message, either way, before the
+    /// connection is closed (0 disables)
+    #[serde(default = "default_idle_timeout_seconds")]
+    #[serde(deserialize_with = "crate::config::de_u64")]
+    pub ws_idle_timeout_seconds: u64,
+}
+
+impl Default for WsConfig {
+    /// Settings as loaded from an empty environment
+    fn default() -> Self {
+        super::Config::default().ws
+    }
+}
+
+fn default_ping_interval_seconds() -> u64 {
+    30
+}
+
+fn default_idle_timeout_seconds() -> u64 {
+    300
+}
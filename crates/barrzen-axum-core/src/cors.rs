@@ -0,0 +1,281 @@
+//! CORS layer built from `CORS_*`
+
+use std::time::Duration;
+
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::config::CorsConfig;
+
+/// Allowed origins parsed from `CORS_ALLOW_ORIGINS`
+#[derive(Debug, Default, PartialEq)]
+struct Origins {
+    any: bool,
+    exact: Vec<HeaderValue>,
+    patterns: Vec<OriginPattern>,
+    dropped: Vec<String>,
+}
+
+/// Subdomain pattern such as `https://*.preview.example.com`
+///
+/// Without a scheme the pattern matches any scheme. Only strict subdomains
+/// match: `*.example.com` does not match `example.com`.
+#[derive(Debug, Clone, PartialEq)]
+struct OriginPattern {
+    scheme: Option<String>,
+    suffix: String,
+}
+
+impl OriginPattern {
+    fn parse(entry: &str) -> Option<Self> {
+        let (scheme, host) = match entry.split_once("://") {
+            Some((scheme, host)) => (Some(scheme.to_ascii_lowercase()), host),
+            None => (None, entry),
+        };
+        let suffix = host.strip_prefix('*')?;
+        let valid = suffix.len() > 1
+            && suffix.starts_with('.')
+            && !suffix.contains(['*', '/'])
+            && scheme.as_deref().is_none_or(|s| !s.is_empty());
+
+        valid.then(|| Self {
+            scheme,
+            suffix: suffix.to_ascii_lowercase(),
+        })
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        let Some((scheme, host)) = origin.split_once("://") else {
+            return false;
+        };
+        if self.scheme.as_deref().is_some_and(|s| s != scheme) {
+            return false;
+        }
+        host.strip_suffix(self.suffix.as_str())
+            .is_some_and(|label| !label.is_empty() && !label.contains('/'))
+    }
+}
+
+impl Origins {
+    fn parse(entries: Vec<String>) -> Self {
+        let mut origins = Self::default();
+        for entry in entries {
+            if entry == "*" {
+                origins.any = true;
+            } else if entry.contains('*') {
+                match OriginPattern::parse(&entry) {
+                    Some(pattern) => origins.patterns.push(pattern),
+                    None => origins.dropped.push(entry),
+                }
+            } else {
+                match HeaderValue::from_str(&entry) {
+                    Ok(value) if is_origin(&entry) => origins.exact.push(value),
+                    _ => origins.dropped.push(entry),
+                }
+            }
+        }
+        origins
+    }
+
+    fn allows(&self, origin: &HeaderValue) -> bool {
+        self.any
+            || self.exact.contains(origin)
+            || origin
+                .to_str()
+                .is_ok_and(|origin| self.patterns.iter().any(|p| p.matches(origin)))
+    }
+}
+
+/// Whether `entry` has the `scheme://host[:port]` shape browsers send
+///
+/// Catches missing schemes and trailing slashes, which never match.
+fn is_origin(entry: &str) -> bool {
+    entry
+        .split_once("://")
+        .is_some_and(|(scheme, host)| !scheme.is_empty() && !host.is_empty() && !host.contains('/'))
+}
+
+/// Build the CORS layer
+///
+/// # Errors
+/// Returns error if `CORS_ALLOW_ORIGINS=*` is combined with
+/// `CORS_ALLOW_CREDENTIALS=true`, which browsers reject.
+pub(crate) fn build_cors_layer(config: &CorsConfig) -> anyhow::Result<CorsLayer> {
+    let mut cors = CorsLayer::new().max_age(Duration::from_secs(config.cors_max_age_seconds));
+
+    if config.cors_allow_credentials {
+        cors = cors.allow_credentials(true);
+    }
+
+    let methods: Vec<Method> = config
+        .methods()
+        .into_iter()
+        .filter_map(|method| Method::from_bytes(method.as_bytes()).ok())
+        .collect();
+    if !methods.is_empty() {
+        cors = cors.allow_methods(methods);
+    }
+
+    let headers: Vec<HeaderName> = config
+        .headers()
+        .into_iter()
+        .filter_map(|header| HeaderName::from_bytes(header.as_bytes()).ok())
+        .collect();
+    if !headers.is_empty() {
+        cors = cors.allow_headers(headers);
+    }
+
+    let expose_headers: Vec<HeaderName> = config
+        .expose_headers()
+        .into_iter()
+        .filter_map(|header| HeaderName::from_bytes(header.as_bytes()).ok())
+        .collect();
+    if !expose_headers.is_empty() {
+        cors = cors.expose_headers(expose_headers);
+    }
+
+    let origins = Origins::parse(config.origins());
+    if !origins.dropped.is_empty() {
+        tracing::warn!(
+            "Ignoring invalid CORS_ALLOW_ORIGINS entries: {}",
+            origins.dropped.join(", ")
+        );
+    }
+
+    if origins.any {
+        if config.cors_allow_credentials {
+            anyhow::bail!(
+                "CORS_ALLOW_ORIGINS=* cannot be combined with CORS_ALLOW_CREDENTIALS=true; list the origins instead"
+            );
+        }
+        cors = cors.allow_origin(AllowOrigin::any());
+    } else if !origins.patterns.is_empty() {
+        cors = cors.allow_origin(AllowOrigin::predicate(move |origin, _| {
+            origins.allows(origin)
+        }));
+    } else if !origins.exact.is_empty() {
+        cors = cors.allow_origin(origins.exact);
+    }
+
+    Ok(cors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{LogCapture, test_config};
+    use axum::{Router, body::Body, extract::Request, http::header, routing::get};
+    use tower::ServiceExt;
+
+    fn origins(entries: &[&str]) -> Origins {
+        Origins::parse(entries.iter().map(ToString::to_string).collect())
+    }
+
+    #[test]
+    fn test_origin_pattern_matching() {
+        let pattern = OriginPattern::parse("https://*.preview.example.com").unwrap();
+        assert!(pattern.matches("https://pr-123.preview.example.com"));
+        assert!(pattern.matches("HTTPS://PR-1.Preview.Example.com"));
+        assert!(!pattern.matches("http://pr-123.preview.example.com"));
+        assert!(!pattern.matches("https://preview.example.com"));
+        assert!(!pattern.matches("https://evil.com/.preview.example.com"));
+        assert!(!pattern.matches("https://pr-1.preview.example.com.evil.com"));
+
+        let any_scheme = OriginPattern::parse("*.example.com").unwrap();
+        assert!(any_scheme.matches("http://a.example.com"));
+        assert!(any_scheme.matches("https://a.b.example.com"));
+        assert!(!any_scheme.matches("https://a.example.com:8443"));
+    }
+
+    #[test]
+    fn test_invalid_origins_are_dropped() {
+        let parsed = origins(&[
+            "https://app.example.com",
+            "https://*.preview.example.com",
+            "https://foo*.example.com",
+            "*",
+            "bad\norigin",
+            "app.example.com",
+            "*.",
+        ]);
+
+        assert!(parsed.any);
+        assert_eq!(parsed.exact.len(), 1);
+        assert_eq!(parsed.patterns.len(), 1);
+        assert_eq!(
+            parsed.dropped,
+            vec![
+                "https://foo*.example.com",
+                "bad\norigin",
+                "app.example.com",
+                "*."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wildcard_with_credentials_fails() {
+        let mut config = test_config().cors;
+        config.cors_allow_origins = Some("*".to_string());
+        config.cors_allow_credentials = true;
+
+        let error = build_cors_layer(&config).unwrap_err();
+        assert!(error.to_string().contains("CORS_ALLOW_CREDENTIALS"));
+
+        config.cors_allow_credentials = false;
+        assert!(build_cors_layer(&config).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_predicate_origins_and_expose_headers() {
+        let mut config = test_config().cors;
+        config.cors_allow_origins = Some(
+            "https://app.example.com, https://*.preview.example.com, https://app.example.org/"
+                .to_string(),
+        );
+
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(build_cors_layer(&config).unwrap());
+        assert!(capture.lines()[0].contains("https://app.example.org/"));
+
+        let allow_origin = |origin: &'static str| {
+            let app = app.clone();
+            async move {
+                let response = app
+                    .oneshot(
+                        Request::builder()
+                            .uri("/")
+                            .header(header::ORIGIN, origin)
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                response.headers().clone()
+            }
+        };
+
+        let headers = allow_origin("https://pr-42.preview.example.com").await;
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://pr-42.preview.example.com"
+        );
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_EXPOSE_HEADERS],
+            "x-request-id,x-response-time"
+        );
+
+        let headers = allow_origin("https://app.example.com").await;
+        assert_eq!(
+            headers[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+
+        let headers = allow_origin("https://evil.example.org").await;
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+}
@@ -0,0 +1,314 @@
+//! Response envelope injection middleware
+//!
+//! Wraps plain JSON responses into the standard [`ApiResponse`] shape so
+//! handlers can return `Json(value)` and still produce an envelope.
+//!
+//! Only bodies of a known size up to [`MAX_WRAP_BYTES`] are buffered;
+//! streaming and larger bodies pass through unchanged.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::{Body, HttpBody as _},
+    http::{HeaderMap, HeaderValue, Request, Response, StatusCode, header},
+};
+use tower::{Layer, Service};
+
+use crate::{
+    features::FeatureState,
+    response::{ApiResponse, extract_request_id},
+};
+
+/// Largest JSON body that is buffered and wrapped
+pub(crate) const MAX_WRAP_BYTES: usize = 1024 * 1024;
+
+/// Response extension that opts a response out of envelope injection
+///
+/// Insert it for file downloads, SSE streams or proxied bodies that must
+/// reach the client unchanged:
+///
+/// ```rust,ignore
+/// let mut response = Json(value).into_response();
+/// response.extensions_mut().insert(SkipEnvelope);
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SkipEnvelope;
+
+/// Layer that wraps non-enveloped JSON responses
+#[derive(Clone, Default)]
+pub(crate) struct EnvelopeLayer {
+    features: Option<FeatureState>,
+}
+
+impl EnvelopeLayer {
+    /// Only wrap while the `response_envelope` flag of `features` is on
+    pub(crate) fn switched_by(features: FeatureState) -> Self {
+        Self {
+            features: Some(features),
+        }
+    }
+}
+
+impl<S> Layer<S> for EnvelopeLayer {
+    type Service = EnvelopeService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        EnvelopeService {
+            inner,
+            features: self.features.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct EnvelopeService<S> {
+    inner: S,
+    features: Option<FeatureState>,
+}
+
+impl<S, B> Service<Request<B>> for EnvelopeService<S>
+where
+    S: Service<Request<B>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        if self
+            .features
+            .as_ref()
+            .is_some_and(|features| !features.response_envelope())
+        {
+            return Box::pin(async move { inner.call(req).await });
+        }
+        let request_id = extract_request_id(req.headers());
+
+        Box::pin(async move {
+            let response = inner.call(req).await?;
+            if !should_wrap(&response) {
+                return Ok(response);
+            }
+
+            let (mut parts, body) = response.into_parts();
+            let Ok(bytes) = axum::body::to_bytes(body, MAX_WRAP_BYTES).await else {
+                // The body already failed mid-stream; nothing sensible to send
+                parts.headers.remove(header::CONTENT_LENGTH);
+                return Ok(Response::from_parts(parts, Body::empty()));
+            };
+
+            let wrapped = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+                Ok(value) if !is_envelope(&value) => {
+                    serde_json::to_vec(&wrap(parts.status, value, request_id)).ok()
+                }
+                _ => None,
+            };
+
+            let body = match wrapped {
+                Some(body) => {
+                    set_content_length(&mut parts.headers, body.len());
+                    Body::from(body)
+                }
+                None => Body::from(bytes),
+            };
+
+            Ok(Response::from_parts(parts, body))
+        })
+    }
+}
+
+/// Only uncompressed JSON bodies of a known, bounded size without the
+/// opt-out marker are wrapped
+fn should_wrap(response: &Response<Body>) -> bool {
+    let bounded = response
+        .body()
+        .size_hint()
+        .exact()
+        .is_some_and(|size| usize::try_from(size).is_ok_and(|size| size <= MAX_WRAP_BYTES));
+    if !bounded
+        || response.extensions().get::<SkipEnvelope>().is_some()
+        || response.headers().contains_key(header::CONTENT_ENCODING)
+    {
+        return false;
+    }
+
+    response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(is_json_content_type)
+}
+
+pub(crate) fn is_json_content_type(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime == "application/json" || (mime.starts_with("application/") && mime.ends_with("+json"))
+}
+
+/// Whether a JSON body already has the `ApiResponse`/`ApiError` shape
+pub(crate) fn is_envelope(value: &serde_json::Value) -> bool {
+    let Some(object) = value.as_object() else {
+        return false;
+    };
+
+    matches!(
+        object.get("status").and_then(serde_json::Value::as_str),
+        Some("success" | "error")
+    ) && object.get("code").is_some_and(serde_json::Value::is_u64)
+        && object.contains_key("timestamp")
+}
+
+fn wrap(
+    status: StatusCode,
+    value: serde_json::Value,
+    request_id: Option<String>,
+) -> ApiResponse<serde_json::Value> {
+    let message = status.canonical_reason().unwrap_or_default();
+    let mut envelope = ApiResponse::with_status(status, value, message);
+    if status.is_client_error() || status.is_server_error() {
+        envelope.status = "error";
+    }
+    envelope.request_id = request_id;
+    envelope.duration_ms = crate::response_time::elapsed_ms();
+    envelope
+}
+
+fn set_content_length(headers: &mut HeaderMap, len: usize) {
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app_builder::REQUEST_ID_HEADER;
+    use axum::{Json, Router, response::IntoResponse, routing::get};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/plain",
+                get(|| async { Json(serde_json::json!({ "id": 7 })) }),
+            )
+            .route(
+                "/missing",
+                get(|| async {
+                    (
+                        StatusCode::NOT_FOUND,
+                        Json(serde_json::json!({ "reason": "gone" })),
+                    )
+                }),
+            )
+            .route("/enveloped", get(|| async { ApiResponse::ok(1, "Done") }))
+            .route(
+                "/skip",
+                get(|| async {
+                    let mut response = Json(serde_json::json!([1, 2])).into_response();
+                    response.extensions_mut().insert(SkipEnvelope);
+                    response
+                }),
+            )
+            .route("/text", get(|| async { "{\"id\":7}" }))
+            .route(
+                "/stream",
+                get(|| async {
+                    let chunks =
+                        futures_util::stream::iter([Ok::<_, std::io::Error>("{\"id\":7}")]);
+                    (
+                        [(header::CONTENT_TYPE, "application/json")],
+                        Body::from_stream(chunks),
+                    )
+                }),
+            )
+            .route(
+                "/large",
+                get(|| async { Json(serde_json::json!({ "blob": "x".repeat(MAX_WRAP_BYTES) })) }),
+            )
+            .layer(EnvelopeLayer::default())
+    }
+
+    async fn get_json(uri: &str) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .uri(uri)
+            .header(&REQUEST_ID_HEADER, "req-1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_plain_json_is_wrapped() {
+        let (status, json) = get_json("/plain").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["status"], "success");
+        assert_eq!(json["code"], 200);
+        assert_eq!(json["request_id"], "req-1");
+        assert_eq!(json["data"]["id"], 7);
+        assert!(json["timestamp"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_error_status_is_wrapped_as_error() {
+        let (status, json) = get_json("/missing").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(json["status"], "error");
+        assert_eq!(json["code"], 404);
+        assert_eq!(json["data"]["reason"], "gone");
+    }
+
+    #[tokio::test]
+    async fn test_existing_envelope_passes_through() {
+        let (_, json) = get_json("/enveloped").await;
+        assert_eq!(json["message"], "Done");
+        assert_eq!(json["data"], 1);
+        assert!(json.get("request_id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_skip_marker_and_non_json_pass_through() {
+        let (_, json) = get_json("/skip").await;
+        assert_eq!(json, serde_json::json!([1, 2]));
+
+        let (_, json) = get_json("/text").await;
+        assert_eq!(json, serde_json::json!({ "id": 7 }));
+    }
+
+    #[tokio::test]
+    async fn test_streaming_and_large_bodies_pass_through() {
+        let (_, json) = get_json("/stream").await;
+        assert_eq!(json, serde_json::json!({ "id": 7 }));
+
+        let (_, json) = get_json("/large").await;
+        assert!(json.get("status").is_none());
+        assert!(json["blob"].is_string());
+    }
+
+    #[test]
+    fn test_json_content_types() {
+        assert!(is_json_content_type("application/json"));
+        assert!(is_json_content_type("application/json; charset=utf-8"));
+        assert!(is_json_content_type("application/problem+json"));
+        assert!(!is_json_content_type("text/plain"));
+        assert!(!is_json_content_type("text/event-stream"));
+    }
+}
@@ -0,0 +1,312 @@
+//! `ETag` and conditional GET middleware (`FEATURE_ETAG`)
+//!
+//! Successful GET/HEAD responses with a known size up to
+//! `HTTP_ETAG_MAX_BYTES` get a weak `ETag` computed from the body. For
+//! enveloped JSON the per-request fields (`timestamp`, `request_id`,
+//! `duration_ms`) are left out of the hash, so an unchanged payload keeps
+//! its tag. A request whose `If-None-Match` matches gets 304 with no body.
+//! Streaming and non-2xx responses pass through untouched.
+//!
+//! The tag is weak because it is computed before compression: the gzip and
+//! identity representations share it while their bytes differ.
+
+use std::{
+    fmt::Write as _,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::{Body, HttpBody as _},
+    http::{HeaderMap, HeaderValue, Method, Request, Response, StatusCode, header},
+};
+use sha2::{Digest, Sha256};
+use tower::{Layer, Service};
+
+use crate::{
+    config::HttpConfig,
+    envelope::{is_envelope, is_json_content_type},
+};
+
+/// Envelope fields that differ on every request
+const VOLATILE_ENVELOPE_FIELDS: [&str; 3] = ["timestamp", "request_id", "duration_ms"];
+
+/// Layer adding `ETag` headers and answering conditional GETs
+#[derive(Clone)]
+pub(crate) struct EtagLayer {
+    settings: Arc<EtagSettings>,
+}
+
+struct EtagSettings {
+    max_bytes: usize,
+    cache_control: Option<HeaderValue>,
+}
+
+impl EtagLayer {
+    /// Layer using `HTTP_ETAG_MAX_BYTES` and `HTTP_CACHE_CONTROL_DEFAULT`
+    pub(crate) fn new(config: &HttpConfig) -> Self {
+        Self {
+            settings: Arc::new(EtagSettings {
+                max_bytes: config.http_etag_max_bytes,
+                cache_control: config
+                    .http_cache_control_default
+                    .as_deref()
+                    .and_then(|value| HeaderValue::from_str(value).ok()),
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for EtagLayer {
+    type Service = EtagService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        EtagService {
+            inner,
+            settings: self.settings.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct EtagService<S> {
+    inner: S,
+    settings: Arc<EtagSettings>,
+}
+
+impl<S, B> Service<Request<B>> for EtagService<S>
+where
+    S: Service<Request<B>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        if !matches!(*req.method(), Method::GET | Method::HEAD) {
+            return Box::pin(async move { inner.call(req).await });
+        }
+
+        let settings = self.settings.clone();
+        let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+        Box::pin(async move {
+            let response = inner.call(req).await?;
+            let size = response.body().size_hint().exact();
+            let taggable = response.status().is_success()
+                && response.status() != StatusCode::PARTIAL_CONTENT
+                && size.is_some_and(|size| {
+                    usize::try_from(size).is_ok_and(|size| size <= settings.max_bytes)
+                });
+            if !taggable {
+                return Ok(response);
+            }
+
+            let (mut parts, body) = response.into_parts();
+            // A tag set by the handler is kept
+            let (etag, body) = if let Some(etag) = parts.headers.get(header::ETAG).cloned() {
+                (etag, body)
+            } else {
+                let Ok(bytes) = axum::body::to_bytes(body, settings.max_bytes).await else {
+                    // The body already failed mid-stream; nothing sensible to send
+                    parts.headers.remove(header::CONTENT_LENGTH);
+                    return Ok(Response::from_parts(parts, Body::empty()));
+                };
+                let etag = compute_etag(&parts.headers, &bytes);
+                parts.headers.insert(header::ETAG, etag.clone());
+                (etag, Body::from(bytes))
+            };
+            if let Some(cache_control) = &settings.cache_control {
+                parts
+                    .headers
+                    .entry(header::CACHE_CONTROL)
+                    .or_insert_with(|| cache_control.clone());
+            }
+
+            if if_none_match.is_some_and(|candidates| matches_etag(&candidates, &etag)) {
+                parts.status = StatusCode::NOT_MODIFIED;
+                // The body is gone, so drop the headers describing it
+                for name in [
+                    header::CONTENT_LENGTH,
+                    header::CONTENT_TYPE,
+                    header::CONTENT_ENCODING,
+                ] {
+                    parts.headers.remove(name);
+                }
+                return Ok(Response::from_parts(parts, Body::empty()));
+            }
+            Ok(Response::from_parts(parts, body))
+        })
+    }
+}
+
+/// Weak `ETag` over the body, ignoring per-request envelope fields
+fn compute_etag(headers: &HeaderMap, body: &[u8]) -> HeaderValue {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(is_json_content_type);
+    let stable = is_json
+        .then(|| serde_json::from_slice::<serde_json::Value>(body).ok())
+        .flatten()
+        .filter(is_envelope)
+        .and_then(|mut envelope| {
+            let object = envelope.as_object_mut()?;
+            for field in VOLATILE_ENVELOPE_FIELDS {
+                object.remove(field);
+            }
+            serde_json::to_vec(&envelope).ok()
+        });
+
+    let digest = Sha256::digest(stable.as_deref().unwrap_or(body));
+    let tag = digest
+        .iter()
+        .take(16)
+        .fold(String::with_capacity(34), |mut tag, byte| {
+            let _ = write!(tag, "{byte:02x}");
+            tag
+        });
+    HeaderValue::from_str(&format!("W/\"{tag}\""))
+        .unwrap_or_else(|_| HeaderValue::from_static("W/\"\""))
+}
+
+/// `If-None-Match` comparison (weak, as RFC 9110 requires for it)
+fn matches_etag(if_none_match: &HeaderValue, etag: &HeaderValue) -> bool {
+    let (Ok(candidates), Ok(etag)) = (if_none_match.to_str(), etag.to_str()) else {
+        return false;
+    };
+    let etag = etag.trim_start_matches("W/");
+    candidates
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, http::HeaderName, routing::get};
+    use tokio_util::io::ReaderStream;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::response::ApiResponse;
+
+    fn layer(max_bytes: usize, cache_control: Option<&str>) -> EtagLayer {
+        let mut config = crate::test_support::test_config().http;
+        config.http_etag_max_bytes = max_bytes;
+        config.http_cache_control_default = cache_control.map(str::to_string);
+        EtagLayer::new(&config)
+    }
+
+    async fn get_with(
+        app: &Router,
+        path: &str,
+        if_none_match: Option<&HeaderValue>,
+    ) -> Response<Body> {
+        let mut request = Request::get(path);
+        if let Some(etag) = if_none_match {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        app.clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_matching_if_none_match_gets_304() {
+        let body = Arc::new(std::sync::Mutex::new("v1"));
+        let current = body.clone();
+        let app = Router::new()
+            .route(
+                "/item",
+                get(move || {
+                    let body = *current.lock().unwrap();
+                    async move { body }
+                }),
+            )
+            .layer(layer(1024, Some("private, max-age=0")));
+
+        let first = get_with(&app, "/item", None).await;
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(first.headers()[header::CACHE_CONTROL], "private, max-age=0");
+        let etag = first.headers()[header::ETAG].clone();
+        assert!(etag.to_str().unwrap().starts_with("W/\""));
+
+        let second = get_with(&app, "/item", Some(&etag)).await;
+        assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(second.headers()[header::ETAG], etag);
+        assert!(!second.headers().contains_key(header::CONTENT_TYPE));
+        let bytes = axum::body::to_bytes(second.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(bytes.is_empty());
+
+        *body.lock().unwrap() = "v2";
+        let changed = get_with(&app, "/item", Some(&etag)).await;
+        assert_eq!(changed.status(), StatusCode::OK);
+        assert_ne!(changed.headers()[header::ETAG], etag);
+    }
+
+    #[tokio::test]
+    async fn test_envelope_fields_do_not_change_the_tag() {
+        let app = Router::new()
+            .route(
+                "/item",
+                get(|| async {
+                    ApiResponse::ok(serde_json::json!({"id": 1}), "ok")
+                        .with_request_id(uuid::Uuid::new_v4().to_string())
+                }),
+            )
+            .layer(layer(1024, None));
+
+        let first = get_with(&app, "/item", None).await;
+        assert!(!first.headers().contains_key(header::CACHE_CONTROL));
+        let etag = first.headers()[header::ETAG].clone();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        let second = get_with(&app, "/item", Some(&etag)).await;
+        assert_eq!(second.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn test_streaming_large_and_error_responses_pass_through() {
+        let app = Router::new()
+            .route(
+                "/stream",
+                get(|| async { Body::from_stream(ReaderStream::new(&b"chunk"[..])) }),
+            )
+            .route("/large", get(|| async { "x".repeat(2048) }))
+            .route(
+                "/missing",
+                get(|| async { (StatusCode::NOT_FOUND, "missing") }),
+            )
+            .route(
+                "/tagged",
+                get(|| async { ([(HeaderName::from_static("etag"), "\"v7\"")], "body") }),
+            )
+            .layer(layer(1024, None));
+
+        for path in ["/stream", "/large", "/missing"] {
+            let response = get_with(&app, path, Some(&HeaderValue::from_static("*"))).await;
+            assert_ne!(response.status(), StatusCode::NOT_MODIFIED, "{path}");
+            assert!(!response.headers().contains_key(header::ETAG), "{path}");
+        }
+
+        let tagged = get_with(
+            &app,
+            "/tagged",
+            Some(&HeaderValue::from_static("W/\"v7\", \"v8\"")),
+        )
+        .await;
+        assert_eq!(tagged.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(tagged.headers()[header::ETAG], "\"v7\"");
+    }
+}
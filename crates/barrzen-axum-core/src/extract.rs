@@ -0,0 +1,364 @@
+//! Request extractors with envelope-shaped rejections
+//!
+//! Rejections are returned as [`ApiError`] so clients always receive the
+//! standard JSON error envelope.
+
+use axum::{
+    Json,
+    extract::{
+        FromRequest, FromRequestParts, Path, Query, Request,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::response::{ApiError, extract_request_id};
+
+#[cfg(feature = "validation")]
+pub use validated::ValidatedJson;
+
+/// Query string extractor rejecting with a 400 [`ApiError`]
+///
+/// Drop-in replacement for [`axum::extract::Query`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApiQuery<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ApiQuery<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Query::<T>::from_request_parts(parts, state).await {
+            Ok(Query(value)) => Ok(Self(value)),
+            Err(rejection) => Err(with_request_id(
+                query_rejection(&rejection),
+                extract_request_id(&parts.headers).as_deref(),
+            )),
+        }
+    }
+}
+
+/// Path parameter extractor rejecting with an [`ApiError`]
+///
+/// Drop-in replacement for [`axum::extract::Path`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApiPath<T>(pub T);
+
+impl<T, S> FromRequestParts<S> for ApiPath<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Path::<T>::from_request_parts(parts, state).await {
+            Ok(Path(value)) => Ok(Self(value)),
+            Err(rejection) => Err(with_request_id(
+                path_rejection(&rejection),
+                extract_request_id(&parts.headers).as_deref(),
+            )),
+        }
+    }
+}
+
+/// JSON body extractor and response rejecting with an [`ApiError`]
+///
+/// Drop-in replacement for [`axum::Json`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApiJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let request_id = extract_request_id(req.headers());
+
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) => Err(with_request_id(
+                json_rejection(&rejection),
+                request_id.as_deref(),
+            )),
+        }
+    }
+}
+
+impl<T: Serialize> IntoResponse for ApiJson<T> {
+    fn into_response(self) -> Response {
+        Json(self.0).into_response()
+    }
+}
+
+fn with_request_id(error: ApiError, request_id: Option<&str>) -> ApiError {
+    match request_id {
+        Some(id) => error.with_request_id(id),
+        None => error,
+    }
+}
+
+fn query_rejection(rejection: &QueryRejection) -> ApiError {
+    ApiError::new(rejection.status(), "Invalid query string").with_details(rejection.body_text())
+}
+
+fn path_rejection(rejection: &PathRejection) -> ApiError {
+    ApiError::new(rejection.status(), "Invalid path parameters").with_details(rejection.body_text())
+}
+
+fn json_rejection(rejection: &JsonRejection) -> ApiError {
+    match rejection {
+        JsonRejection::JsonDataError(_) | JsonRejection::JsonSyntaxError(_) => {
+            ApiError::bad_request("Invalid JSON body").with_details(rejection.body_text())
+        }
+        _ => ApiError::new(rejection.status(), "Invalid request body")
+            .with_details(rejection.body_text()),
+    }
+}
+
+#[cfg(feature = "validation")]
+mod validated {
+    use std::collections::BTreeMap;
+
+    use axum::{
+        Json,
+        extract::{FromRequest, Request},
+    };
+    use serde::de::DeserializeOwned;
+    use validator::{Validate, ValidationErrors};
+
+    use super::{json_rejection, with_request_id};
+    use crate::response::{ApiError, extract_request_id};
+
+    /// JSON body extractor that runs [`Validate::validate`] after deserializing
+    ///
+    /// - Malformed or mistyped JSON is rejected with a 400 [`ApiError`] whose
+    ///   `details` carry the serde error (including its location).
+    /// - Validation failures are rejected with a 422 [`ApiError`] whose
+    ///   `errors` map each field to its messages.
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct ValidatedJson<T>(pub T);
+
+    impl<T, S> FromRequest<S> for ValidatedJson<T>
+    where
+        T: DeserializeOwned + Validate,
+        S: Send + Sync,
+    {
+        type Rejection = ApiError;
+
+        async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+            let request_id = extract_request_id(req.headers());
+
+            let Json(value) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(|rejection| {
+                    with_request_id(json_rejection(&rejection), request_id.as_deref())
+                })?;
+
+            value.validate().map_err(|errors| {
+                with_request_id(validation_error(&errors), request_id.as_deref())
+            })?;
+
+            Ok(Self(value))
+        }
+    }
+
+    fn validation_error(errors: &ValidationErrors) -> ApiError {
+        let fields: BTreeMap<String, Vec<String>> = errors
+            .field_errors()
+            .into_iter()
+            .map(|(field, errors)| {
+                let messages = errors
+                    .iter()
+                    .map(|error| {
+                        error
+                            .message
+                            .as_ref()
+                            .map_or_else(|| error.code.to_string(), ToString::to_string)
+                    })
+                    .collect();
+                (field.to_string(), messages)
+            })
+            .collect();
+
+        ApiError::unprocessable("Validation failed").with_errors(fields)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use axum::{Router, body::Body, http::StatusCode, routing::post};
+        use serde::Deserialize;
+        use tower::ServiceExt;
+
+        #[derive(Debug, Deserialize, Validate)]
+        struct CreateUser {
+            #[validate(length(min = 3, message = "must be at least 3 characters"))]
+            name: String,
+            #[validate(email)]
+            email: String,
+        }
+
+        async fn post_json(body: &str) -> (StatusCode, serde_json::Value) {
+            let app = Router::new().route(
+                "/users",
+                post(|ValidatedJson(user): ValidatedJson<CreateUser>| async move { user.name }),
+            );
+            let request = Request::builder()
+                .method("POST")
+                .uri("/users")
+                .header("content-type", "application/json")
+                .header("x-request-id", "req-1")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, serde_json::from_slice(&body).unwrap_or_default())
+        }
+
+        #[tokio::test]
+        async fn test_malformed_json_is_bad_request() {
+            let (status, json) = post_json(r#"{"name": "ann","#).await;
+
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(json["status"], "error");
+            assert_eq!(json["code"], 400);
+            assert_eq!(json["request_id"], "req-1");
+            assert!(json["details"].as_str().unwrap().contains("line 1"));
+            assert!(json.get("errors").is_none());
+        }
+
+        #[tokio::test]
+        async fn test_invalid_payload_is_unprocessable() {
+            let (status, json) = post_json(r#"{"name": "al", "email": "nope"}"#).await;
+
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(json["code"], 422);
+            assert_eq!(
+                json["errors"]["name"],
+                serde_json::json!(["must be at least 3 characters"])
+            );
+            assert_eq!(json["errors"]["email"], serde_json::json!(["email"]));
+        }
+
+        #[tokio::test]
+        async fn test_valid_payload_passes() {
+            let (status, _) = post_json(r#"{"name": "alice", "email": "a@example.com"}"#).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        http::StatusCode,
+        routing::{get, post},
+    };
+    use serde::Deserialize;
+    use tower::ServiceExt;
+
+    #[derive(Deserialize, Serialize)]
+    struct Page {
+        page: u32,
+    }
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/items",
+                get(|ApiQuery(query): ApiQuery<Page>| async move { query.page.to_string() }),
+            )
+            .route(
+                "/items/{id}",
+                get(|ApiPath(id): ApiPath<u64>| async move { id.to_string() }),
+            )
+            .route(
+                "/items",
+                post(|ApiJson(page): ApiJson<Page>| async move { ApiJson(page) }),
+            )
+    }
+
+    async fn send(request: Request) -> (StatusCode, serde_json::Value) {
+        let response = app().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or_default())
+    }
+
+    fn get_request(uri: &str) -> Request {
+        Request::builder()
+            .uri(uri)
+            .header("x-request-id", "req-1")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn assert_bad_request(status: StatusCode, json: &serde_json::Value) {
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["status"], "error");
+        assert_eq!(json["code"], 400);
+        assert_eq!(json["request_id"], "req-1");
+        assert!(json["details"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_api_query_rejection() {
+        let (status, json) = send(get_request("/items?page=abc")).await;
+        assert_bad_request(status, &json);
+        assert_eq!(json["message"], "Invalid query string");
+    }
+
+    #[tokio::test]
+    async fn test_api_path_rejection() {
+        let (status, json) = send(get_request("/items/abc")).await;
+        assert_bad_request(status, &json);
+        assert_eq!(json["message"], "Invalid path parameters");
+    }
+
+    #[tokio::test]
+    async fn test_api_json_rejection() {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/items")
+            .header("content-type", "application/json")
+            .header("x-request-id", "req-1")
+            .body(Body::from(r#"{"page": "abc"}"#))
+            .unwrap();
+        let (status, json) = send(request).await;
+        assert_bad_request(status, &json);
+        assert_eq!(json["message"], "Invalid JSON body");
+    }
+
+    #[tokio::test]
+    async fn test_extractors_accept_valid_input() {
+        let (status, _) = send(get_request("/items?page=2")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let request = Request::builder()
+            .method("POST")
+            .uri("/items")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"page": 3}"#))
+            .unwrap();
+        let (status, json) = send(request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["page"], 3);
+    }
+}
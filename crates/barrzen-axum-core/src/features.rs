@@ -0,0 +1,185 @@
+//! Feature flags of the running app
+//!
+//! [`FeatureState`] holds the `FEATURE_*` flags as loaded, of which
+//! [`RUNTIME_FLAGS`] can be switched without a restart, e.g. with
+//! `PUT /admin/features/{name}`. The middleware they control stays installed
+//! and reads the current value per request.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use crate::{config::FeatureFlags, maintenance::MaintenanceState};
+
+/// Flags that take effect per request, without the `feature_` prefix
+pub const RUNTIME_FLAGS: [&str; 3] = ["maintenance_mode", "request_log", "response_envelope"];
+
+/// Flag that cannot be switched
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FeatureError {
+    #[error("Unknown feature flag '{0}'")]
+    Unknown(String),
+    /// Only read at startup, e.g. `db`
+    #[error("Feature flag '{0}' only changes with a restart")]
+    ReadOnly(String),
+}
+
+/// Shared feature flags; clones see the same values
+#[derive(Clone)]
+pub struct FeatureState {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    /// Flags read at startup only
+    fixed: BTreeMap<String, bool>,
+    request_log: AtomicBool,
+    response_envelope: AtomicBool,
+    /// `maintenance_mode`, shared with `PUT /admin/maintenance`
+    maintenance: MaintenanceState,
+}
+
+impl FeatureState {
+    /// State of `flags`, with `maintenance_mode` kept in `maintenance`
+    #[must_use]
+    pub fn new(flags: &FeatureFlags, maintenance: MaintenanceState) -> Self {
+        let flags = serde_json::to_value(flags).unwrap_or_default();
+        let values = flags
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(name, enabled)| {
+                let name = name.strip_prefix("feature_").unwrap_or(name);
+                Some((name.to_string(), enabled.as_bool()?))
+            });
+        Self::from_values(values, maintenance)
+    }
+
+    /// State holding only `values` besides `maintenance_mode`
+    pub(crate) fn from_values(
+        values: impl IntoIterator<Item = (String, bool)>,
+        maintenance: MaintenanceState,
+    ) -> Self {
+        let mut fixed = BTreeMap::new();
+        let (mut request_log, mut response_envelope) = (false, false);
+        for (name, enabled) in values {
+            match name.as_str() {
+                "request_log" => request_log = enabled,
+                "response_envelope" => response_envelope = enabled,
+                // Read from the maintenance state instead
+                "maintenance_mode" => {}
+                _ => {
+                    fixed.insert(name, enabled);
+                }
+            }
+        }
+        Self {
+            inner: Arc::new(Inner {
+                fixed,
+                request_log: AtomicBool::new(request_log),
+                response_envelope: AtomicBool::new(response_envelope),
+                maintenance,
+            }),
+        }
+    }
+
+    /// Whether requests are logged (`FEATURE_REQUEST_LOG`)
+    #[must_use]
+    pub fn request_log(&self) -> bool {
+        self.inner.request_log.load(Ordering::Relaxed)
+    }
+
+    /// Whether JSON responses are enveloped (`FEATURE_RESPONSE_ENVELOPE`)
+    #[must_use]
+    pub fn response_envelope(&self) -> bool {
+        self.inner.response_envelope.load(Ordering::Relaxed)
+    }
+
+    /// Current value of `name` (with or without the `feature_` prefix)
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name.strip_prefix("feature_").unwrap_or(name) {
+            "request_log" => Some(self.request_log()),
+            "response_envelope" => Some(self.response_envelope()),
+            "maintenance_mode" => Some(self.inner.maintenance.is_enabled()),
+            name => self.inner.fixed.get(name).copied(),
+        }
+    }
+
+    /// Switch a flag of [`RUNTIME_FLAGS`]
+    ///
+    /// # Errors
+    /// Returns error if `name` is unknown or only read at startup.
+    pub fn set(&self, name: &str, enabled: bool) -> Result<(), FeatureError> {
+        let name = name.strip_prefix("feature_").unwrap_or(name);
+        let flag = match name {
+            "request_log" => &self.inner.request_log,
+            "response_envelope" => &self.inner.response_envelope,
+            "maintenance_mode" => {
+                self.inner.maintenance.set_enabled(enabled);
+                return Ok(());
+            }
+            name if self.inner.fixed.contains_key(name) => {
+                return Err(FeatureError::ReadOnly(name.to_string()));
+            }
+            name => return Err(FeatureError::Unknown(name.to_string())),
+        };
+        flag.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Every flag with its current value, by name
+    #[must_use]
+    pub fn snapshot(&self) -> BTreeMap<String, bool> {
+        let mut flags = self.inner.fixed.clone();
+        for name in RUNTIME_FLAGS {
+            flags.insert(name.to_string(), self.get(name).unwrap_or_default());
+        }
+        flags
+    }
+
+    /// Whether `name` can be switched without a restart
+    #[must_use]
+    pub fn is_runtime(name: &str) -> bool {
+        RUNTIME_FLAGS.contains(&name.strip_prefix("feature_").unwrap_or(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_config;
+
+    #[test]
+    fn test_runtime_and_read_only_flags() {
+        let mut flags = test_config().features;
+        flags.feature_db = true;
+        flags.feature_request_log = true;
+        let maintenance = MaintenanceState::default();
+        let features = FeatureState::new(&flags, maintenance.clone());
+
+        assert_eq!(features.get("db"), Some(true));
+        assert_eq!(features.get("feature_request_log"), Some(true));
+        features.set("request_log", false).unwrap();
+        assert!(!features.clone().request_log());
+        features.set("feature_maintenance_mode", true).unwrap();
+        assert!(maintenance.is_enabled());
+
+        assert_eq!(
+            features.set("db", false),
+            Err(FeatureError::ReadOnly("db".to_string()))
+        );
+        assert_eq!(
+            features.set("nope", true),
+            Err(FeatureError::Unknown("nope".to_string()))
+        );
+        let snapshot = features.snapshot();
+        assert!(snapshot["db"]);
+        assert!(!snapshot["request_log"]);
+        assert!(snapshot["maintenance_mode"]);
+    }
+}
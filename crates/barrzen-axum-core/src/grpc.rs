@@ -0,0 +1,74 @@
+//! gRPC services served next to the HTTP routes
+//!
+//! Services added with [`AppBuilder::add_grpc_service`] become one router
+//! with the request id layer (the id travels as gRPC metadata, which is an
+//! HTTP/2 header) and, with `otel`, trace context propagation. The HTTP
+//! middleware stack (envelope, compression, timeouts) stays off: it would
+//! break the gRPC framing.
+//!
+//! [`AppBuilder::add_grpc_service`]: crate::AppBuilder::add_grpc_service
+
+use std::{
+    convert::Infallible,
+    task::{Context, Poll},
+};
+
+use axum::{Router, extract::Request, http::header::CONTENT_TYPE, response::Response};
+use tower::Service;
+
+use crate::{config::Config, request_context::RequestContextLayer};
+
+/// Router of the gRPC services with their middleware
+pub(crate) fn grpc_router(services: tonic::service::Routes, config: &Config) -> Router {
+    let router = services.into_axum_router();
+
+    #[cfg(feature = "otel")]
+    let router = if config.features.feature_otel {
+        router.layer(crate::trace_context::TraceContextLayer::new(
+            crate::path_redaction::PathRedactor::new(&config.logging),
+        ))
+    } else {
+        router
+    };
+
+    router.layer(RequestContextLayer::new(&config.request_id))
+}
+
+/// One router answering gRPC and HTTP on the same listener
+pub(crate) fn multiplex(http: Router, grpc: Router) -> Router {
+    Router::new().fallback_service(Multiplex { http, grpc })
+}
+
+/// Whether the request is a gRPC call (`application/grpc`, `application/grpc+proto`, ...)
+fn is_grpc(req: &Request) -> bool {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/grpc"))
+}
+
+/// Routes each request by content type
+#[derive(Clone)]
+struct Multiplex {
+    http: Router,
+    grpc: Router,
+}
+
+impl Service<Request> for Multiplex {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = <Router as Service<Request>>::Future;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Routers are always ready
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        if is_grpc(&req) {
+            self.grpc.call(req)
+        } else {
+            self.http.call(req)
+        }
+    }
+}
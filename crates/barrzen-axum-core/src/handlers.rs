@@ -1,36 +1,70 @@
 //! Core HTTP handlers
 //!
-//! Provides /healthz, /readyz, and /version endpoints.
+//! Provides /healthz, /readyz, /version and the opt-in /configz endpoints.
 
-use axum::{extract::State, http::HeaderMap, response::IntoResponse};
+use axum::{
+    extract::{Extension, State},
+    http::{HeaderMap, StatusCode, Uri},
+    response::IntoResponse,
+};
 use serde::Serialize;
-use std::sync::Arc;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use tokio::time::Instant;
 
 use crate::{
-    response::{extract_request_id, ApiResponse},
     BuildInfo,
+    admin::LogLevelControl,
+    audit::AuditLogger,
+    config::{Config, Environment, FeatureFlags},
+    features::FeatureState,
+    load_shed::RequestLimit,
+    maintenance::MaintenanceState,
+    ready_cache::ReadyCache,
+    response::{ApiResponse, extract_request_id},
+    timings::{StartupReport, StartupTimings},
 };
 
 /// Health check response data
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct HealthData {
     pub status: String,
 }
 
 /// Readiness check response data
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct ReadyData {
     pub status: String,
     pub checks: Vec<HealthCheck>,
+    /// Whether the checks were served from the cache (`READYZ_CACHE_TTL_MS`)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+    /// Age of the cached checks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age_ms: Option<u64>,
 }
 
 /// Individual health check result
-#[derive(Debug, Serialize)]
+///
+/// `status` is `ok`, `warn` (degraded but serving), `fail` or `skip`.
+/// Checks are critical unless marked with [`HealthCheck::non_critical`]; only
+/// a failing critical check makes the service unready.
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct HealthCheck {
     pub name: String,
     pub status: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
+    pub critical: bool,
 }
 
 impl HealthCheck {
@@ -41,6 +75,18 @@ impl HealthCheck {
             name: name.into(),
             status: "ok".to_string(),
             message: None,
+            critical: true,
+        }
+    }
+
+    /// Create a warning: the dependency is degraded but the service can serve
+    #[must_use]
+    pub fn warn(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: "warn".to_string(),
+            message: Some(message.into()),
+            critical: true,
         }
     }
 
@@ -51,6 +97,7 @@ impl HealthCheck {
             name: name.into(),
             status: "fail".to_string(),
             message: Some(message.into()),
+            critical: true,
         }
     }
 
@@ -61,17 +108,87 @@ impl HealthCheck {
             name: name.into(),
             status: "skip".to_string(),
             message: Some(reason.into()),
+            critical: true,
+        }
+    }
+
+    /// Mark the check as non-critical: a failure degrades readiness instead
+    /// of making the service unready
+    #[must_use]
+    pub fn non_critical(mut self) -> Self {
+        self.critical = false;
+        self
+    }
+
+    /// Set whether a failure makes the service unready
+    #[must_use]
+    pub fn with_critical(mut self, critical: bool) -> Self {
+        self.critical = critical;
+        self
+    }
+}
+
+/// Overall readiness derived from the individual checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadyStatus {
+    /// Every check is `ok` or `skip`
+    Ok,
+    /// A check warns or a non-critical check fails; still serving
+    Degraded,
+    /// A critical check fails
+    Unready,
+}
+
+impl ReadyStatus {
+    /// Aggregate `checks` into one status
+    #[must_use]
+    pub fn of(checks: &[HealthCheck]) -> Self {
+        let mut status = Self::Ok;
+        for check in checks {
+            match check.status.as_str() {
+                "ok" | "skip" => {}
+                "fail" if check.critical => return Self::Unready,
+                _ => status = Self::Degraded,
+            }
+        }
+        status
+    }
+
+    /// Value of the `status` field in the /readyz body
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Degraded => "degraded",
+            Self::Unready => "unready",
         }
     }
 }
 
 /// Version info response data
 #[derive(Debug, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
 pub struct VersionData {
     pub name: String,
     pub version: String,
     pub git_hash: Option<String>,
     pub rust_version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_dirty: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    pub build_time: Option<String>,
+    pub environment: Environment,
+    pub uptime_seconds: u64,
+    /// Runtime feature flags without the `feature_` prefix
+    pub features: BTreeMap<String, bool>,
+    /// Startup phases, when recorded with `AppBuilder::with_startup_timings`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub startup: Option<StartupReport>,
 }
 
 /// Application state for core handlers
@@ -79,26 +196,134 @@ pub struct VersionData {
 pub struct CoreState {
     pub build_info: Arc<BuildInfo>,
     pub ready_checker: Option<Arc<dyn ReadyChecker>>,
-    pub feature_response_envelope: bool,
+    /// Set once graceful shutdown starts; /readyz then reports 503
+    pub shutting_down: Arc<AtomicBool>,
+    /// When the state was created, for `uptime_seconds` in /version
+    pub started_at: Instant,
+    pub environment: Environment,
+    /// Feature flags, reported by /version and switched by
+    /// `PUT /admin/features/{name}`
+    pub features: FeatureState,
+    /// Log filter control for the admin endpoint
+    pub log_control: Option<Arc<dyn LogLevelControl>>,
+    /// Answer 503 when /readyz is unready (`READYZ_STRICT`)
+    pub readyz_strict: bool,
+    /// Last /readyz checks (`READYZ_CACHE_TTL_MS`)
+    pub(crate) ready_cache: Arc<ReadyCache>,
+    /// Maintenance mode switch (`FEATURE_MAINTENANCE_MODE`)
+    pub maintenance: MaintenanceState,
+    /// Concurrency limit for application routes
+    /// (`HTTP_MAX_CONCURRENT_REQUESTS`), `None` when unlimited
+    pub request_limit: Option<RequestLimit>,
+    /// Audit log of the app (`AUDIT_*`)
+    pub audit: AuditLogger,
+    /// Startup phases reported by /version
+    pub startup_timings: StartupTimings,
 }
 
 impl CoreState {
     /// Create new core state
     #[must_use]
     pub fn new(build_info: BuildInfo, feature_response_envelope: bool) -> Self {
+        let maintenance = MaintenanceState::default();
+        let envelope = ("response_envelope".to_string(), feature_response_envelope);
         Self {
             build_info: Arc::new(build_info),
             ready_checker: None,
-            feature_response_envelope,
+            shutting_down: Arc::new(AtomicBool::new(false)),
+            started_at: Instant::now(),
+            environment: Environment::default(),
+            features: FeatureState::from_values([envelope], maintenance.clone()),
+            log_control: None,
+            readyz_strict: false,
+            ready_cache: Arc::default(),
+            maintenance,
+            request_limit: None,
+            audit: AuditLogger::new(),
+            startup_timings: StartupTimings::disabled(),
         }
     }
 
+    /// Add log filter control for `PUT /admin/log-level`
+    #[must_use]
+    pub fn with_log_control(mut self, control: Arc<dyn LogLevelControl>) -> Self {
+        self.log_control = Some(control);
+        self
+    }
+
+    /// Report the environment and feature flags of `config` in /version
+    ///
+    /// Only the boolean `FEATURE_*` flags are reported, nothing else from the
+    /// configuration. `READYZ_STRICT` and `READYZ_CACHE_TTL_MS` are applied to
+    /// /readyz, `FEATURE_MAINTENANCE_MODE` sets the initial maintenance
+    /// state, and `HTTP_MAX_CONCURRENT_REQUESTS` sizes the request limit.
+    /// The envelope flag passed to [`CoreState::new`] is kept.
+    #[must_use]
+    pub fn with_config(mut self, config: &Config) -> Self {
+        self.environment = config.app.app_env;
+        self.readyz_strict = config.readiness.readyz_strict;
+        self.ready_cache = Arc::new(ReadyCache::new(Duration::from_millis(
+            config.readiness.readyz_cache_ttl_ms,
+        )));
+        self.maintenance = MaintenanceState::new(config.features.feature_maintenance_mode);
+        let flags = FeatureFlags {
+            feature_response_envelope: self.features.response_envelope(),
+            ..config.features.clone()
+        };
+        self.features = FeatureState::new(&flags, self.maintenance.clone());
+        self.request_limit = RequestLimit::from_config(config);
+        self
+    }
+
+    /// Seconds since the state was created
+    #[must_use]
+    pub fn uptime_seconds(&self) -> u64 {
+        self.started_at.elapsed().as_secs()
+    }
+
     /// Add a ready checker
     #[must_use]
     pub fn with_ready_checker(mut self, checker: Arc<dyn ReadyChecker>) -> Self {
         self.ready_checker = Some(checker);
         self
     }
+
+    /// Use `audit` for the audit log
+    #[must_use]
+    pub fn with_audit_logger(mut self, audit: AuditLogger) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Report `timings` in /version
+    #[must_use]
+    pub fn with_startup_timings(mut self, timings: StartupTimings) -> Self {
+        self.startup_timings = timings;
+        self
+    }
+
+    /// Share a shutdown flag with the server lifecycle
+    #[must_use]
+    pub fn with_shutdown_flag(mut self, flag: Arc<AtomicBool>) -> Self {
+        self.shutting_down = flag;
+        self
+    }
+
+    /// Application requests currently in flight under the concurrency limit
+    ///
+    /// Always 0 when `HTTP_MAX_CONCURRENT_REQUESTS` is unset.
+    #[must_use]
+    pub fn in_flight_requests(&self) -> usize {
+        self.request_limit
+            .as_ref()
+            .map_or(0, RequestLimit::in_flight)
+    }
+
+    /// Whether graceful shutdown has started
+    #[must_use]
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
 }
 
 /// Trait for readiness checking
@@ -110,6 +335,20 @@ pub trait ReadyChecker: Send + Sync {
     async fn ready_checks(&self) -> Vec<HealthCheck>;
 }
 
+/// Several checkers reported as one, in registration order
+pub(crate) struct ReadyCheckers(pub(crate) Vec<Arc<dyn ReadyChecker>>);
+
+#[async_trait::async_trait]
+impl ReadyChecker for ReadyCheckers {
+    async fn ready_checks(&self) -> Vec<HealthCheck> {
+        let mut checks = Vec::new();
+        for checker in &self.0 {
+            checks.extend(checker.ready_checks().await);
+        }
+        checks
+    }
+}
+
 /// GET /healthz - Basic liveness check (always 200 OK)
 pub async fn healthz(headers: HeaderMap, State(state): State<CoreState>) -> impl IntoResponse {
     let request_id = extract_request_id(&headers);
@@ -117,7 +356,7 @@ pub async fn healthz(headers: HeaderMap, State(state): State<CoreState>) -> impl
         status: "ok".to_string(),
     };
 
-    if state.feature_response_envelope {
+    if state.features.response_envelope() {
         let mut response = ApiResponse::ok(data, "Service is healthy");
         if let Some(rid) = request_id {
             response = response.with_request_id(rid);
@@ -129,44 +368,85 @@ pub async fn healthz(headers: HeaderMap, State(state): State<CoreState>) -> impl
 }
 
 /// GET /readyz - Readiness check (checks enabled dependencies)
-pub async fn readyz(headers: HeaderMap, State(state): State<CoreState>) -> impl IntoResponse {
+///
+/// Returns 503 once graceful shutdown has started so load balancers stop
+/// routing new traffic while in-flight requests drain. With
+/// `READYZ_CACHE_TTL_MS`, results are reused until they expire;
+/// `?fresh=true` runs the checks regardless.
+pub async fn readyz(
+    uri: Uri,
+    headers: HeaderMap,
+    State(state): State<CoreState>,
+) -> impl IntoResponse {
     let request_id = extract_request_id(&headers);
 
-    let checks = if let Some(ref checker) = state.ready_checker {
-        checker.ready_checks().await
+    if state.is_shutting_down() {
+        let data = ReadyData {
+            status: "shutting_down".to_string(),
+            checks: vec![HealthCheck::fail("shutdown", "shutting down")],
+            cached: false,
+            age_ms: None,
+        };
+
+        return if state.features.response_envelope() {
+            let mut response = ApiResponse::with_status(
+                StatusCode::SERVICE_UNAVAILABLE,
+                data,
+                "Service is shutting down",
+            );
+            if let Some(rid) = request_id {
+                response = response.with_request_id(rid);
+            }
+            response.into_response()
+        } else {
+            (StatusCode::SERVICE_UNAVAILABLE, axum::Json(data)).into_response()
+        };
+    }
+
+    let (mut checks, age) = if let Some(ref checker) = state.ready_checker {
+        let fresh = uri
+            .query()
+            .is_some_and(|query| query.split('&').any(|pair| pair == "fresh=true"));
+        let result = state.ready_cache.checks(checker.as_ref(), fresh).await;
+        (result.checks, result.age)
     } else {
-        vec![HealthCheck::skip("infra", "not configured")]
+        (vec![HealthCheck::skip("infra", "not configured")], None)
     };
+    if state.maintenance.is_enabled() {
+        checks.push(HealthCheck::warn(
+            "maintenance",
+            state.maintenance.message(),
+        ));
+    }
 
-    let all_ok = checks.iter().all(|c| c.status == "ok" || c.status == "skip");
-
+    let ready = ReadyStatus::of(&checks);
     let data = ReadyData {
-        status: if all_ok {
-            "ok".to_string()
-        } else {
-            "degraded".to_string()
-        },
+        status: ready.as_str().to_string(),
         checks,
+        cached: age.is_some(),
+        age_ms: age.map(|age| u64::try_from(age.as_millis()).unwrap_or(u64::MAX)),
+    };
+    // Only strict mode lets load balancers take an unready instance out
+    let status = if ready == ReadyStatus::Unready && state.readyz_strict {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
     };
 
-    if state.feature_response_envelope {
-        let message = if all_ok {
-            "Service is ready"
-        } else {
-            "Service is degraded"
+    if state.features.response_envelope() {
+        let message = match ready {
+            ReadyStatus::Ok => "Service is ready",
+            ReadyStatus::Degraded => "Service is degraded",
+            ReadyStatus::Unready => "Service is not ready",
         };
 
-        let mut response = ApiResponse::ok(data, message);
+        let mut response = ApiResponse::with_status(status, data, message);
         if let Some(rid) = request_id {
             response = response.with_request_id(rid);
         }
         response.into_response()
     } else {
-        // For readiness, we might want to set status code even without envelope?
-        // Standard behavior: 200 OK or 503 if strict?
-        // Current implementation logic always returned 200 OK with status inside body.
-        // We stick to that for raw JSON too unless specific requirement.
-        axum::Json(data).into_response()
+        (status, axum::Json(data)).into_response()
     }
 }
 
@@ -180,9 +460,18 @@ pub async fn version(headers: HeaderMap, State(state): State<CoreState>) -> impl
         version: build.version.clone(),
         git_hash: build.git_sha.clone(),
         rust_version: build.rust_version.clone(),
+        git_branch: build.git_branch.clone(),
+        git_dirty: build.git_dirty,
+        target: build.target.clone(),
+        profile: build.profile.clone(),
+        build_time: build.build_time.clone(),
+        environment: state.environment,
+        uptime_seconds: state.uptime_seconds(),
+        features: state.features.snapshot(),
+        startup: state.startup_timings.report(),
     };
 
-    if state.feature_response_envelope {
+    if state.features.response_envelope() {
         let mut response = ApiResponse::ok(data, "Version information");
         if let Some(rid) = request_id {
             response = response.with_request_id(rid);
@@ -193,6 +482,30 @@ pub async fn version(headers: HeaderMap, State(state): State<CoreState>) -> impl
     }
 }
 
+/// Redacted effective configuration served by /configz
+#[derive(Debug, Clone)]
+pub struct EffectiveConfig(pub Arc<serde_json::Value>);
+
+/// GET /configz - Effective configuration with secrets redacted
+pub async fn configz(
+    headers: HeaderMap,
+    State(state): State<CoreState>,
+    Extension(EffectiveConfig(config)): Extension<EffectiveConfig>,
+) -> impl IntoResponse {
+    let request_id = extract_request_id(&headers);
+    let data = config.as_ref().clone();
+
+    if state.features.response_envelope() {
+        let mut response = ApiResponse::ok(data, "Effective configuration");
+        if let Some(rid) = request_id {
+            response = response.with_request_id(rid);
+        }
+        response.into_response()
+    } else {
+        axum::Json(data).into_response()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -211,10 +524,235 @@ mod tests {
         assert!(check.message.is_some());
     }
 
+    #[test]
+    fn test_ready_status_aggregation() {
+        assert_eq!(ReadyStatus::of(&[]), ReadyStatus::Ok);
+        assert_eq!(
+            ReadyStatus::of(&[
+                HealthCheck::ok("database"),
+                HealthCheck::skip("cache", "disabled")
+            ]),
+            ReadyStatus::Ok
+        );
+        assert_eq!(
+            ReadyStatus::of(&[
+                HealthCheck::ok("database"),
+                HealthCheck::warn("cache", "slow")
+            ]),
+            ReadyStatus::Degraded
+        );
+        assert_eq!(
+            ReadyStatus::of(&[
+                HealthCheck::ok("database"),
+                HealthCheck::fail("cache", "connection refused").non_critical(),
+            ]),
+            ReadyStatus::Degraded
+        );
+        assert_eq!(
+            ReadyStatus::of(&[
+                HealthCheck::warn("cache", "slow"),
+                HealthCheck::fail("database", "connection refused"),
+            ]),
+            ReadyStatus::Unready
+        );
+    }
+
+    struct Fixed(fn() -> Vec<HealthCheck>);
+
+    #[async_trait::async_trait]
+    impl ReadyChecker for Fixed {
+        async fn ready_checks(&self) -> Vec<HealthCheck> {
+            (self.0)()
+        }
+    }
+
+    async fn readyz_response(state: CoreState) -> (StatusCode, serde_json::Value) {
+        readyz_response_for(state, "/readyz").await
+    }
+
+    async fn readyz_response_for(state: CoreState, uri: &str) -> (StatusCode, serde_json::Value) {
+        let uri = uri.parse::<Uri>().unwrap();
+        let response = readyz(uri, HeaderMap::new(), State(state))
+            .await
+            .into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_readyz_unready_only_fails_in_strict_mode() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let state = CoreState::new(build, false).with_ready_checker(Arc::new(Fixed(|| {
+            vec![HealthCheck::fail("database", "connection refused")]
+        })));
+
+        let (status, json) = readyz_response(state.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["status"], "unready");
+        assert_eq!(json["checks"][0]["critical"], true);
+
+        let mut strict = state;
+        strict.readyz_strict = true;
+        let (status, json) = readyz_response(strict).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(json["status"], "unready");
+    }
+
+    #[tokio::test]
+    async fn test_readyz_degraded_stays_ready_in_strict_mode() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let mut state = CoreState::new(build, true).with_ready_checker(Arc::new(Fixed(|| {
+            vec![
+                HealthCheck::ok("database"),
+                HealthCheck::fail("cache", "connection refused").non_critical(),
+            ]
+        })));
+        state.readyz_strict = true;
+
+        let (status, json) = readyz_response(state).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["data"]["status"], "degraded");
+        assert_eq!(json["data"]["checks"][1]["critical"], false);
+        assert_eq!(json["message"], "Service is degraded");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_readyz_serves_cached_checks() {
+        let config = Config::builder()
+            .with(|config| config.readiness.readyz_cache_ttl_ms = 5000)
+            .build();
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let state = CoreState::new(build, false)
+            .with_config(&config)
+            .with_ready_checker(Arc::new(Fixed(|| vec![HealthCheck::ok("database")])));
+
+        let (_, json) = readyz_response(state.clone()).await;
+        assert!(json.get("cached").is_none(), "{json}");
+        tokio::time::advance(std::time::Duration::from_millis(1200)).await;
+
+        let (_, json) = readyz_response(state.clone()).await;
+        assert_eq!(json["cached"], true);
+        assert_eq!(json["age_ms"], 1200);
+        assert_eq!(json["checks"][0]["name"], "database");
+
+        let (_, json) = readyz_response_for(state, "/readyz?verbose=1&fresh=true").await;
+        assert!(json.get("age_ms").is_none(), "{json}");
+    }
+
     #[test]
     fn test_core_state_creation() {
         let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
         let state = CoreState::new(build, true);
         assert!(state.ready_checker.is_none());
+        assert!(!state.is_shutting_down());
+    }
+
+    #[tokio::test]
+    async fn test_readyz_reports_shutdown() {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let state = CoreState::new(build, true);
+        state.shutting_down.store(true, Ordering::SeqCst);
+
+        let response = readyz(Uri::from_static("/readyz"), HeaderMap::new(), State(state))
+            .await
+            .into_response();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["data"]["status"], "shutting_down");
+        assert_eq!(json["data"]["checks"][0]["name"], "shutdown");
+    }
+
+    async fn version_body(state: &CoreState) -> String {
+        let response = version(HeaderMap::new(), State(state.clone()))
+            .await
+            .into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_version_reports_runtime_info() {
+        let config = Config::builder()
+            .env(Environment::Stage)
+            .feature_cors(true)
+            .with(|config| config.auth.auth_jwt_secret = Some("jwt-secret".to_string()))
+            .build();
+        let build = BuildInfo::new(
+            "test",
+            "1.0.0",
+            Some("abc123".to_string()),
+            "1.75.0",
+            Some("2024-01-01T00:00:00Z".to_string()),
+        );
+        let state = CoreState::new(build, true).with_config(&config);
+
+        let body = version_body(&state).await;
+        // Existing keys keep their order, new ones follow
+        let positions: Vec<_> = [
+            "\"name\"",
+            "\"version\"",
+            "\"git_hash\"",
+            "\"rust_version\"",
+            "\"build_time\"",
+            "\"environment\"",
+            "\"uptime_seconds\"",
+            "\"features\"",
+        ]
+        .iter()
+        .map(|key| body.find(key).unwrap())
+        .collect();
+        assert!(positions.is_sorted(), "{body}");
+        assert!(!body.contains("jwt-secret"));
+
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let data = &json["data"];
+        assert_eq!(data["build_time"], "2024-01-01T00:00:00Z");
+        assert_eq!(data["environment"], "stage");
+        assert_eq!(data["features"]["cors"], true);
+        assert_eq!(data["features"]["config_endpoint"], false);
+        assert!(
+            data["features"]
+                .as_object()
+                .unwrap()
+                .values()
+                .all(serde_json::Value::is_boolean)
+        );
+
+        let first = data["uptime_seconds"].as_u64().unwrap();
+        tokio::time::advance(std::time::Duration::from_secs(5)).await;
+        let body = version_body(&state).await;
+        let json: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let second = json["data"]["uptime_seconds"].as_u64().unwrap();
+        assert_eq!(second, first + 5);
+        assert!(json["data"].get("startup").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_version_reports_startup_timings() {
+        let timings = StartupTimings::new();
+        timings.record("config", std::time::Duration::from_millis(12));
+        timings.record("migrations", std::time::Duration::from_millis(800));
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let state = CoreState::new(build, true).with_startup_timings(timings);
+
+        let json: serde_json::Value = serde_json::from_str(&version_body(&state).await).unwrap();
+        let startup = &json["data"]["startup"];
+        assert!(startup["total_ms"].is_u64());
+        assert_eq!(
+            startup["phases"],
+            serde_json::json!([
+                { "name": "config", "duration_ms": 12 },
+                { "name": "migrations", "duration_ms": 800 },
+            ])
+        );
     }
 }
@@ -0,0 +1,299 @@
+//! Readiness checks registered as closures
+//!
+//! [`HealthRegistry`] is a [`ReadyChecker`] built from named async closures
+//! instead of a trait impl per dependency. Clones share the registered
+//! checks, so a background task holding a clone can add or remove its own
+//! check after the app is built.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use crate::handlers::{HealthCheck, ReadyChecker};
+
+/// Time a check has to complete before it is reported as failed
+pub const DEFAULT_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+type CheckFuture = Pin<Box<dyn Future<Output = Result<(), String>> + Send>>;
+type CheckFn = Arc<dyn Fn() -> CheckFuture + Send + Sync>;
+
+/// How a failure of a registered check is reported
+#[derive(Clone, Copy)]
+enum OnFailure {
+    /// `fail`, critical or not
+    Fail { critical: bool },
+    /// `warn`: degraded but serving
+    Warn,
+}
+
+#[derive(Clone)]
+struct Registered {
+    name: String,
+    on_failure: OnFailure,
+    check: CheckFn,
+}
+
+/// Named readiness checks, run concurrently and reported in registration order
+///
+/// ```rust,ignore
+/// let registry = HealthRegistry::new()
+///     .register("payments", true, move || {
+///         let client = client.clone();
+///         async move { client.ping().await.map_err(|e| e.to_string()) }
+///     })
+///     .register_warn("recommendations", || async { Ok(()) });
+///
+/// AppBuilder::new(config, build).with_ready_checker(registry.clone());
+/// ```
+#[derive(Clone)]
+pub struct HealthRegistry {
+    checks: Arc<RwLock<Vec<Registered>>>,
+    timeout: Duration,
+}
+
+impl Default for HealthRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthRegistry {
+    /// Empty registry with [`DEFAULT_CHECK_TIMEOUT`] per check
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            checks: Arc::default(),
+            timeout: DEFAULT_CHECK_TIMEOUT,
+        }
+    }
+
+    /// Set the time each check has to complete
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Register a check whose error reports `fail`
+    ///
+    /// A failing `critical` check makes the service unready.
+    #[must_use]
+    pub fn register<F, Fut>(self, name: impl Into<String>, critical: bool, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.add(name, critical, check);
+        self
+    }
+
+    /// Register a check whose error only reports `warn` (degraded)
+    #[must_use]
+    pub fn register_warn<F, Fut>(self, name: impl Into<String>, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.insert(name.into(), OnFailure::Warn, boxed(check));
+        self
+    }
+
+    /// Add a check to a registry that may already be serving
+    ///
+    /// Replaces a check registered under the same name, keeping its place.
+    pub fn add<F, Fut>(&self, name: impl Into<String>, critical: bool, check: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        self.insert(name.into(), OnFailure::Fail { critical }, boxed(check));
+    }
+
+    /// Remove the check registered under `name`, returning whether there was one
+    #[must_use]
+    pub fn remove(&self, name: &str) -> bool {
+        let mut checks = self.write();
+        let before = checks.len();
+        checks.retain(|registered| registered.name != name);
+        checks.len() != before
+    }
+
+    /// Names of the registered checks, in order
+    #[must_use]
+    pub fn names(&self) -> Vec<String> {
+        self.read()
+            .iter()
+            .map(|registered| registered.name.clone())
+            .collect()
+    }
+
+    fn insert(&self, name: String, on_failure: OnFailure, check: CheckFn) {
+        let registered = Registered {
+            name,
+            on_failure,
+            check,
+        };
+        let mut checks = self.write();
+        match checks
+            .iter_mut()
+            .find(|existing| existing.name == registered.name)
+        {
+            Some(existing) => *existing = registered,
+            None => checks.push(registered),
+        }
+    }
+
+    // A check never panics while the lock is held, so a poisoned lock still
+    // holds a consistent list
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Vec<Registered>> {
+        self.checks
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Vec<Registered>> {
+        self.checks
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+fn boxed<F, Fut>(check: F) -> CheckFn
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<(), String>> + Send + 'static,
+{
+    Arc::new(move || Box::pin(check()) as CheckFuture)
+}
+
+#[async_trait::async_trait]
+impl ReadyChecker for HealthRegistry {
+    async fn ready_checks(&self) -> Vec<HealthCheck> {
+        // Snapshot, so checks added meanwhile wait for the next call
+        let registered = self.read().clone();
+        let timeout = self.timeout;
+        let handles: Vec<_> = registered
+            .iter()
+            .map(|registered| {
+                let check = (registered.check)();
+                tokio::spawn(async move {
+                    tokio::time::timeout(timeout, check)
+                        .await
+                        .unwrap_or_else(|_| {
+                            Err(format!("timed out after {}ms", timeout.as_millis()))
+                        })
+                })
+            })
+            .collect();
+
+        let mut checks = Vec::with_capacity(handles.len());
+        for (registered, handle) in registered.into_iter().zip(handles) {
+            let outcome = handle
+                .await
+                .unwrap_or_else(|_| Err("check panicked".to_string()));
+            checks.push(match (outcome, registered.on_failure) {
+                (Ok(()), OnFailure::Fail { critical }) => {
+                    HealthCheck::ok(registered.name).with_critical(critical)
+                }
+                (Ok(()), OnFailure::Warn) => HealthCheck::ok(registered.name).non_critical(),
+                (Err(message), OnFailure::Fail { critical }) => {
+                    HealthCheck::fail(registered.name, message).with_critical(critical)
+                }
+                (Err(message), OnFailure::Warn) => {
+                    HealthCheck::warn(registered.name, message).non_critical()
+                }
+            });
+        }
+        checks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    fn summary(checks: &[HealthCheck]) -> Vec<(&str, &str, bool)> {
+        checks
+            .iter()
+            .map(|check| (check.name.as_str(), check.status.as_str(), check.critical))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_mixed_outcomes_in_registration_order() {
+        let registry = HealthRegistry::new()
+            .register("database", true, || async {
+                // Finishes last, still reported first
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok(())
+            })
+            .register("payments", true, || async {
+                Err("connection refused".to_string())
+            })
+            .register_warn("recommendations", || async { Err("slow".to_string()) });
+
+        let checks = registry.ready_checks().await;
+        assert_eq!(
+            summary(&checks),
+            [
+                ("database", "ok", true),
+                ("payments", "fail", true),
+                ("recommendations", "warn", false),
+            ]
+        );
+        assert_eq!(checks[1].message.as_deref(), Some("connection refused"));
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_registration_through_a_clone() {
+        let registry = HealthRegistry::new().register("database", true, || async { Ok(()) });
+        assert_eq!(registry.ready_checks().await.len(), 1);
+
+        // A background task adds its check after the first call
+        let handle = registry.clone();
+        let healthy = Arc::new(AtomicBool::new(false));
+        let flag = healthy.clone();
+        handle.add("worker", false, move || {
+            let healthy = flag.load(Ordering::SeqCst);
+            async move {
+                if healthy {
+                    Ok(())
+                } else {
+                    Err("starting".to_string())
+                }
+            }
+        });
+
+        let checks = registry.ready_checks().await;
+        assert_eq!(
+            summary(&checks),
+            [("database", "ok", true), ("worker", "fail", false)]
+        );
+
+        healthy.store(true, Ordering::SeqCst);
+        assert_eq!(registry.ready_checks().await[1].status, "ok");
+
+        assert!(handle.remove("worker"));
+        assert!(!handle.remove("worker"));
+        assert_eq!(registry.names(), ["database"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_slow_check_times_out() {
+        let registry = HealthRegistry::new()
+            .with_timeout(Duration::from_millis(100))
+            .register("search", true, || async {
+                tokio::time::sleep(Duration::from_mins(1)).await;
+                Ok(())
+            });
+
+        let checks = registry.ready_checks().await;
+        assert_eq!(checks[0].status, "fail");
+        assert_eq!(checks[0].message.as_deref(), Some("timed out after 100ms"));
+    }
+}
@@ -0,0 +1,248 @@
+//! HTTP server metrics
+//!
+//! Records the OpenTelemetry semantic-convention instruments
+//! `http.server.request.duration` (histogram, seconds) and
+//! `http.server.active_requests` (up/down counter) through a [`Meter`].
+//! The app uses the global meter, which `barrzen_axum_obs::init` backs
+//! with an OTLP exporter when `FEATURE_OTEL_METRICS=true`.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use axum::{
+    extract::MatchedPath,
+    http::{Request, Response},
+};
+use opentelemetry::{
+    KeyValue,
+    metrics::{Histogram, Meter, UpDownCounter},
+};
+use tower::{Layer, Service};
+
+/// Histogram bucket boundaries (seconds) recommended by the HTTP semconv
+const DURATION_BUCKETS: [f64; 14] = [
+    0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0, 7.5, 10.0,
+];
+
+/// Layer recording request duration and in-flight requests
+///
+/// Applied with `Router::layer`, so the matched route template is known
+/// and used as `http.route` (unmatched requests have none).
+#[derive(Clone)]
+pub(crate) struct HttpMetricsLayer {
+    duration: Histogram<f64>,
+    active: UpDownCounter<i64>,
+}
+
+impl HttpMetricsLayer {
+    pub(crate) fn new(meter: &Meter) -> Self {
+        Self {
+            duration: meter
+                .f64_histogram("http.server.request.duration")
+                .with_unit("s")
+                .with_description("Duration of HTTP server requests")
+                .with_boundaries(DURATION_BUCKETS.to_vec())
+                .build(),
+            active: meter
+                .i64_up_down_counter("http.server.active_requests")
+                .with_unit("{request}")
+                .with_description("Number of active HTTP server requests")
+                .build(),
+        }
+    }
+}
+
+impl<S> Layer<S> for HttpMetricsLayer {
+    type Service = HttpMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HttpMetricsService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct HttpMetricsService<S> {
+    inner: S,
+    layer: HttpMetricsLayer,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for HttpMetricsService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let mut attributes = vec![KeyValue::new(
+            "http.request.method",
+            req.method().as_str().to_string(),
+        )];
+        if let Some(route) = req.extensions().get::<MatchedPath>() {
+            attributes.push(KeyValue::new("http.route", route.as_str().to_string()));
+        }
+
+        let metrics = self.layer.clone();
+        metrics.active.add(1, &attributes);
+        let active = ActiveRequest {
+            counter: metrics.active.clone(),
+            attributes: attributes.clone(),
+        };
+        let start = Instant::now();
+        let future = self.inner.call(req);
+
+        Box::pin(async move {
+            let result = future.await;
+            drop(active);
+
+            if let Ok(response) = &result {
+                attributes.push(KeyValue::new(
+                    "http.response.status_code",
+                    i64::from(response.status().as_u16()),
+                ));
+            }
+            metrics
+                .duration
+                .record(start.elapsed().as_secs_f64(), &attributes);
+            result
+        })
+    }
+}
+
+/// Decrements `http.server.active_requests` when the request ends, including
+/// when its future is dropped (client gone, timeout, load shedding)
+struct ActiveRequest {
+    counter: UpDownCounter<i64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl Drop for ActiveRequest {
+    fn drop(&mut self) {
+        self.counter.add(-1, &self.attributes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, http::StatusCode, routing::get};
+    use opentelemetry::metrics::MeterProvider as _;
+    use opentelemetry_sdk::metrics::{
+        InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+        data::{AggregatedMetrics, Metric, MetricData, ResourceMetrics, ScopeMetrics},
+    };
+    use tower::ServiceExt;
+
+    fn find<'a>(metrics: &'a [&'a Metric], name: &str) -> &'a AggregatedMetrics {
+        metrics
+            .iter()
+            .find(|metric| metric.name() == name)
+            .map(|metric| metric.data())
+            .unwrap()
+    }
+
+    fn provider() -> (SdkMeterProvider, InMemoryMetricExporter) {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        (provider, exporter)
+    }
+
+    fn active_requests(provider: &SdkMeterProvider, exporter: &InMemoryMetricExporter) -> i64 {
+        provider.force_flush().unwrap();
+        let finished = exporter.get_finished_metrics().unwrap();
+        let metrics: Vec<&Metric> = finished
+            .iter()
+            .flat_map(ResourceMetrics::scope_metrics)
+            .flat_map(ScopeMetrics::metrics)
+            .collect();
+        let AggregatedMetrics::I64(MetricData::Sum(active)) =
+            find(&metrics, "http.server.active_requests")
+        else {
+            panic!("active requests is not an i64 sum");
+        };
+        active.data_points().next().unwrap().value()
+    }
+
+    #[tokio::test]
+    async fn test_request_is_recorded() {
+        let (provider, exporter) = provider();
+        let layer = HttpMetricsLayer::new(&provider.meter("test"));
+
+        let app = Router::new()
+            .route("/orders/{id}", get(|| async { StatusCode::CREATED }))
+            .layer(layer);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/orders/7")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        provider.force_flush().unwrap();
+        let finished = exporter.get_finished_metrics().unwrap();
+        let metrics: Vec<&Metric> = finished
+            .iter()
+            .flat_map(ResourceMetrics::scope_metrics)
+            .flat_map(ScopeMetrics::metrics)
+            .collect();
+
+        let AggregatedMetrics::F64(MetricData::Histogram(histogram)) =
+            find(&metrics, "http.server.request.duration")
+        else {
+            panic!("duration is not an f64 histogram");
+        };
+        let point = histogram.data_points().next().unwrap();
+        assert_eq!(point.count(), 1);
+        let attributes: Vec<_> = point
+            .attributes()
+            .map(|kv| (kv.key.as_str().to_string(), kv.value.to_string()))
+            .collect();
+        for expected in [
+            ("http.request.method", "GET"),
+            ("http.route", "/orders/{id}"),
+            ("http.response.status_code", "201"),
+        ] {
+            assert!(
+                attributes.contains(&(expected.0.to_string(), expected.1.to_string())),
+                "{attributes:?}"
+            );
+        }
+        assert_eq!(active_requests(&provider, &exporter), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dropped_request_is_no_longer_active() {
+        let (provider, exporter) = provider();
+        let layer = HttpMetricsLayer::new(&provider.meter("test"));
+
+        let app = Router::new()
+            .route("/slow", get(std::future::pending::<()>))
+            .layer(layer);
+        let request = Request::builder().uri("/slow").body(Body::empty()).unwrap();
+        let timed_out =
+            tokio::time::timeout(std::time::Duration::from_secs(1), app.oneshot(request)).await;
+        assert!(timed_out.is_err());
+
+        assert_eq!(active_requests(&provider, &exporter), 0);
+    }
+}
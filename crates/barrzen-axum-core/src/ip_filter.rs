@@ -0,0 +1,367 @@
+//! IP allowlist/denylist middleware
+//!
+//! [`IpFilterLayer`] rejects requests whose client IP is denylisted or, when
+//! an allowlist is set, not allowlisted. It is applied to every route when
+//! `IP_ALLOWLIST` or `IP_DENYLIST` is set, and can be added to single routers
+//! with `Router::layer`.
+
+use std::{
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::ConnectInfo,
+    http::Request,
+    response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use tower::{Layer, Service};
+
+use crate::{
+    client_ip::{ClientIp, parse_network},
+    config::IpFilterConfig,
+    response::{ApiError, extract_request_id},
+};
+
+/// Layer that filters requests by client IP
+///
+/// The client IP is the socket address (`ConnectInfo<SocketAddr>`, set by
+/// `AppBuilder::serve`) or, with [`IpFilterLayer::trust_forwarded`], the
+/// [`ClientIp`] resolved by an outer [`ClientIpLayer`](crate::ClientIpLayer),
+/// which only follows forwarding headers through `TRUSTED_PROXIES`.
+/// Requests whose IP cannot be determined are denied.
+#[derive(Clone)]
+pub struct IpFilterLayer {
+    rules: Arc<IpRules>,
+}
+
+#[derive(Clone)]
+struct IpRules {
+    allowlist: Vec<IpNet>,
+    denylist: Vec<IpNet>,
+    trust_forwarded: bool,
+}
+
+impl IpFilterLayer {
+    /// Create a filter from parsed networks
+    ///
+    /// An empty allowlist allows every IP that is not denylisted.
+    #[must_use]
+    pub fn new(allowlist: Vec<IpNet>, denylist: Vec<IpNet>) -> Self {
+        Self {
+            rules: Arc::new(IpRules {
+                allowlist,
+                denylist,
+                trust_forwarded: false,
+            }),
+        }
+    }
+
+    /// Build the filter from `IP_ALLOWLIST`, `IP_DENYLIST` and
+    /// `IP_FILTER_TRUST_FORWARDED`
+    ///
+    /// Entries are CIDR blocks (`10.0.0.0/8`, `fd00::/8`) or single addresses.
+    ///
+    /// # Errors
+    /// Returns error if an entry is not a valid CIDR block or address.
+    pub fn from_config(config: &IpFilterConfig) -> anyhow::Result<Self> {
+        let allowlist = parse_networks("IP_ALLOWLIST", &config.allowlist())?;
+        let denylist = parse_networks("IP_DENYLIST", &config.denylist())?;
+
+        Ok(Self::new(allowlist, denylist).trust_forwarded(config.ip_filter_trust_forwarded))
+    }
+
+    /// Use the [`ClientIp`] request extension instead of the socket address
+    ///
+    /// The built app sets it; on a plain router add a
+    /// [`ClientIpLayer`](crate::ClientIpLayer) outside this layer. Without the
+    /// extension the socket address is used.
+    #[must_use]
+    pub fn trust_forwarded(mut self, trust: bool) -> Self {
+        Arc::make_mut(&mut self.rules).trust_forwarded = trust;
+        self
+    }
+}
+
+impl IpRules {
+    fn is_allowed(&self, ip: IpAddr) -> bool {
+        if self.denylist.iter().any(|net| net.contains(&ip)) {
+            return false;
+        }
+        self.allowlist.is_empty() || self.allowlist.iter().any(|net| net.contains(&ip))
+    }
+
+    fn client_ip<B>(&self, req: &Request<B>) -> Option<IpAddr> {
+        let peer = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        let resolved = req
+            .extensions()
+            .get::<ClientIp>()
+            .filter(|_| self.trust_forwarded)
+            .map(|ClientIp(ip)| *ip);
+        resolved.or(peer).map(|ip| ip.to_canonical())
+    }
+}
+
+impl<S> Layer<S> for IpFilterLayer {
+    type Service = IpFilterService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IpFilterService {
+            inner,
+            rules: self.rules.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct IpFilterService<S> {
+    inner: S,
+    rules: Arc<IpRules>,
+}
+
+impl<S, B> Service<Request<B>> for IpFilterService<S>
+where
+    S: Service<Request<B>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let ip = self.rules.client_ip(&req);
+        if ip.is_some_and(|ip| self.rules.is_allowed(ip)) {
+            let mut inner = self.inner.clone();
+            return Box::pin(async move { inner.call(req).await });
+        }
+
+        let evaluated = ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+        tracing::warn!(
+            ip = %evaluated,
+            method = %req.method(),
+            path = %req.uri().path(),
+            "request denied by IP filter"
+        );
+
+        let mut error = ApiError::forbidden("Access denied");
+        if let Some(request_id) = extract_request_id(req.headers()) {
+            error = error.with_request_id(request_id);
+        }
+        Box::pin(async move { Ok(error.into_response()) })
+    }
+}
+
+fn parse_networks(name: &str, entries: &[String]) -> anyhow::Result<Vec<IpNet>> {
+    entries
+        .iter()
+        .map(|entry| {
+            parse_network(entry)
+                .ok_or_else(|| anyhow::anyhow!("{name} contains an invalid CIDR block: {entry:?}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_ip::{ClientIpLayer, parse_forwarded_addr};
+    use axum::{Router, body::Body, http::StatusCode, routing::get};
+    use tower::ServiceExt;
+
+    fn nets(entries: &[&str]) -> Vec<IpNet> {
+        entries.iter().map(|e| parse_network(e).unwrap()).collect()
+    }
+
+    fn app(layer: IpFilterLayer) -> Router {
+        Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(layer)
+    }
+
+    async fn status_for(app: Router, peer: &str, forwarded: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri("/");
+        if let Some(forwarded) = forwarded {
+            request = request.header("x-forwarded-for", forwarded);
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+
+        app.oneshot(request).await.unwrap().status()
+    }
+
+    #[test]
+    fn test_cidr_matching() {
+        let layer = IpFilterLayer::new(
+            nets(&["10.0.0.0/8", "192.168.1.10"]),
+            nets(&["10.0.13.0/24"]),
+        );
+        let rules = &layer.rules;
+
+        assert!(rules.is_allowed("10.1.2.3".parse().unwrap()));
+        assert!(rules.is_allowed("192.168.1.10".parse().unwrap()));
+        assert!(!rules.is_allowed("192.168.1.11".parse().unwrap()));
+        assert!(!rules.is_allowed("10.0.13.7".parse().unwrap()));
+        assert!(!rules.is_allowed("8.8.8.8".parse().unwrap()));
+
+        let deny_only = IpFilterLayer::new(Vec::new(), nets(&["203.0.113.0/24"]));
+        assert!(deny_only.rules.is_allowed("8.8.8.8".parse().unwrap()));
+        assert!(!deny_only.rules.is_allowed("203.0.113.9".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_invalid_entry_rejected() {
+        let config: IpFilterConfig =
+            serde_json::from_value(serde_json::json!({ "ip_allowlist": "10.0.0.0/8,10.0.0/33" }))
+                .unwrap();
+
+        let error = IpFilterLayer::from_config(&config).err().unwrap();
+        assert!(error.to_string().contains("IP_ALLOWLIST"));
+    }
+
+    #[test]
+    fn test_forwarded_addr_parsing() {
+        assert_eq!(
+            parse_forwarded_addr("198.51.100.7:4711"),
+            Some("198.51.100.7".parse().unwrap())
+        );
+        assert_eq!(parse_forwarded_addr("garbage"), None);
+        assert_eq!(
+            parse_forwarded_addr("[2001:db8::1]:443"),
+            Some("2001:db8::1".parse().unwrap())
+        );
+        assert_eq!(
+            parse_forwarded_addr("[2001:db8::1]"),
+            Some("2001:db8::1".parse().unwrap())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_socket_address_filtering() {
+        let layer = IpFilterLayer::new(nets(&["10.0.0.0/8"]), Vec::new());
+
+        assert_eq!(
+            status_for(app(layer.clone()), "10.2.3.4:5000", None).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status_for(app(layer.clone()), "8.8.8.8:5000", Some("10.0.0.1")).await,
+            StatusCode::FORBIDDEN
+        );
+
+        // No socket address at all
+        let response = app(layer)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_trusted_forwarded_header() {
+        let layer = IpFilterLayer::new(nets(&["10.0.0.0/8"]), Vec::new()).trust_forwarded(true);
+        let proxied = || app(layer.clone()).layer(ClientIpLayer::new(nets(&["10.0.0.2"])));
+
+        // Spoofed leftmost entry is ignored, the proxy-appended one counts
+        assert_eq!(
+            status_for(proxied(), "10.0.0.2:80", Some("10.9.9.9, 8.8.8.8")).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status_for(proxied(), "10.0.0.2:80", Some("8.8.8.8, 10.1.1.1")).await,
+            StatusCode::OK
+        );
+        // Without the header the socket address is used
+        assert_eq!(
+            status_for(proxied(), "10.0.0.2:80", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forwarded_header_from_untrusted_peer_is_ignored() {
+        let layer = IpFilterLayer::new(nets(&["10.0.0.0/8"]), Vec::new()).trust_forwarded(true);
+
+        // A client that is not one of the trusted proxies cannot pick its IP
+        let proxied = app(layer.clone()).layer(ClientIpLayer::new(nets(&["10.0.0.2"])));
+        assert_eq!(
+            status_for(proxied, "8.8.8.8:80", Some("10.1.1.1")).await,
+            StatusCode::FORBIDDEN
+        );
+        // Without a ClientIpLayer the header is not read at all
+        assert_eq!(
+            status_for(app(layer), "8.8.8.8:80", Some("10.1.1.1")).await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ipv6_addresses() {
+        let layer = IpFilterLayer::new(nets(&["fd00::/8", "10.0.0.0/8"]), nets(&["fd00:bad::/32"]));
+
+        assert_eq!(
+            status_for(app(layer.clone()), "[fd12::1]:443", None).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status_for(app(layer.clone()), "[fd00:bad::1]:443", None).await,
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status_for(app(layer.clone()), "[2001:db8::1]:443", None).await,
+            StatusCode::FORBIDDEN
+        );
+        // IPv4-mapped addresses match IPv4 blocks
+        assert_eq!(
+            status_for(app(layer), "[::ffff:10.0.0.5]:443", None).await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_denied_request_is_logged_with_ip() {
+        let capture = crate::test_support::LogCapture::new();
+        let _guard = capture.set_default();
+
+        let layer = IpFilterLayer::new(Vec::new(), nets(&["192.0.2.0/24"]));
+        let response = app(layer)
+            .oneshot({
+                let mut request = Request::builder()
+                    .uri("/")
+                    .header("x-request-id", "req-1")
+                    .body(Body::empty())
+                    .unwrap();
+                request.extensions_mut().insert(ConnectInfo(
+                    "192.0.2.44:1234".parse::<SocketAddr>().unwrap(),
+                ));
+                request
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["request_id"], "req-1");
+
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("WARN") && lines[0].contains("ip=192.0.2.44"));
+    }
+}
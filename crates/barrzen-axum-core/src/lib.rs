@@ -8,21 +8,111 @@
 //! - Standard API response types
 //! - Core endpoints: /healthz, /readyz, /version
 
+pub mod admin;
 pub mod app_builder;
+mod app_context;
+pub mod audit;
 pub mod banner;
+pub mod build;
 pub mod build_info;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod client_ip;
+mod compression;
 pub mod config;
+mod cors;
+pub mod envelope;
+mod etag;
+pub mod extract;
+pub mod features;
+#[cfg(feature = "grpc")]
+mod grpc;
 pub mod handlers;
+pub mod health_registry;
+#[cfg(feature = "otel")]
+mod http_metrics;
+pub mod ip_filter;
+pub mod load_shed;
+pub mod maintenance;
+mod path_redaction;
+mod ready_cache;
+pub mod request_context;
+pub mod request_limits;
+mod request_log;
 pub mod response;
+pub mod response_time;
+#[cfg(feature = "scheduler")]
+pub mod scheduler;
+pub mod single_flight;
+pub mod sse;
+mod startup;
+#[cfg(feature = "static-files")]
+pub mod static_files;
+pub mod tasks;
+pub mod tenant;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+pub mod timings;
+#[cfg(feature = "otel")]
+pub mod trace_context;
+#[cfg(feature = "uploads")]
+pub mod uploads;
+#[cfg(feature = "ws")]
+pub mod ws;
 
+#[cfg(test)]
+mod test_support;
+
+pub use admin::LogLevelControl;
 pub use app_builder::AppBuilder;
+pub use audit::{AuditEvent, AuditLogger, AuditOutcome, AuditRecord, AuditSink};
 pub use build_info::BuildInfo;
+#[cfg(feature = "cli")]
+pub use cli::Cli;
+pub use client_ip::{ClientIp, ClientIpLayer};
 pub use config::{
-    AppConfig, BannerConfig, CacheBackend, CacheConfig, Config, ConfigError, CorsConfig,
-    Environment, FeatureFlags, HttpConfig, LogBackend, LogFormat, LoggingConfig,
+    AppConfig, AuditConfig, AuthConfig, BannerConfig, BannerStyle, BrokerBackend, BrokerConfig,
+    CacheBackend, CacheConfig, CircuitBreakerConfig, ClientIpConfig, CompressionConfig, Config,
+    ConfigBuilder, ConfigError, CoreRoutesConfig, CorsConfig, DatabaseConfig, Environment,
+    FeatureFlags, GrpcConfig, HttpClientConfig, HttpConfig, IdempotencyConfig, IpFilterConfig,
+    LogBackend, LogFormat, LogOutput, LogRotation, LoggingConfig, MailConfig, MaintenanceConfig,
+    ManagementConfig, OpenApiAuth, OpenApiConfig, OtelConfig, OtlpProtocol, OutboxConfig,
+    PathRedaction, ReadinessConfig, RequestIdConfig, RequestIdFormat, RequestLogBody,
+    ResponseCacheConfig, RunMigrations, SearchConfig, SecurityHeadersConfig, SessionConfig,
+    SessionSameSite, SmtpTls, SseConfig, StartupConfig, TenantConfig, TraceSampler,
+    UnrecognizedVar, UploadConfig, WsConfig,
 };
-pub use handlers::{CoreState, HealthCheck, ReadyChecker};
+pub use envelope::SkipEnvelope;
+#[cfg(feature = "validation")]
+pub use extract::ValidatedJson;
+pub use extract::{ApiJson, ApiPath, ApiQuery};
+pub use features::{FeatureError, FeatureState};
+pub use handlers::{CoreState, HealthCheck, ReadyChecker, ReadyStatus};
+pub use health_registry::HealthRegistry;
+pub use ip_filter::IpFilterLayer;
+pub use ipnet::IpNet;
+pub use load_shed::RequestLimit;
+pub use maintenance::MaintenanceState;
+pub use request_context::current_request_id;
+pub use request_limits::{MB, RouteOverrides};
 pub use response::{ApiError, ApiResponse, ApiResult};
+#[cfg(feature = "scheduler")]
+pub use scheduler::{CronSchedule, JobOptions};
+pub use single_flight::{SingleFlightLayer, SingleFlightStats};
+pub use sse::{ApiSse, SseEvent};
+#[cfg(feature = "static-files")]
+pub use static_files::StaticOptions;
+pub use tasks::{CancellationToken, TaskOptions};
+pub use tenant::{TenantId, TenantLayer, current_tenant};
+#[cfg(feature = "test-util")]
+pub use test_util::{FakeReadyChecker, LogCapture, TestApp, TestResponse};
+pub use timings::StartupTimings;
+#[cfg(feature = "session")]
+pub use tower_sessions::Session;
+#[cfg(feature = "uploads")]
+pub use uploads::{ApiMultipart, MultipartLimits, UploadPart};
+#[cfg(feature = "ws")]
+pub use ws::{WsConnections, WsHealthChecker, WsSession};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,147 @@
+//! Concurrency limit and load shedding (`HTTP_MAX_CONCURRENT_REQUESTS`)
+//!
+//! Application routes share a pool of `HTTP_MAX_CONCURRENT_REQUESTS`
+//! permits. Without `FEATURE_LOAD_SHED` requests beyond the limit wait for a
+//! permit; with it they are answered at once with a 503 and `Retry-After`.
+//! The core endpoints draw from their own small pool
+//! (`HTTP_HEALTH_RESERVED_REQUESTS`), so probes keep passing while
+//! application traffic is shed.
+
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    error_handling::HandleErrorLayer,
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+};
+use tokio::sync::Semaphore;
+use tower::{BoxError, ServiceBuilder, limit::GlobalConcurrencyLimitLayer};
+
+use crate::{
+    config::Config,
+    response::{ApiError, extract_request_id},
+};
+
+/// `Retry-After` sent with shed requests
+const RETRY_AFTER_SECONDS: u64 = 1;
+
+/// Permit pool limiting concurrent requests
+#[derive(Clone, Debug)]
+pub struct RequestLimit {
+    semaphore: Arc<Semaphore>,
+    max: usize,
+}
+
+impl RequestLimit {
+    /// Pool of `max` permits
+    #[must_use]
+    pub fn new(max: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max)),
+            max,
+        }
+    }
+
+    /// Limit for application routes, `None` when unlimited
+    pub(crate) fn from_config(config: &Config) -> Option<Self> {
+        let max = config.http.http_max_concurrent_requests;
+        (max > 0).then(|| Self::new(max))
+    }
+
+    /// Maximum number of concurrent requests
+    #[must_use]
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Requests currently holding a permit
+    #[must_use]
+    pub fn in_flight(&self) -> usize {
+        self.max.saturating_sub(self.semaphore.available_permits())
+    }
+
+    /// Report the in-flight count as `http.server.limited_requests`
+    #[cfg(feature = "otel")]
+    pub(crate) fn register_metrics(&self, meter: &opentelemetry::metrics::Meter) {
+        let limit = self.clone();
+        meter
+            .u64_observable_gauge("http.server.limited_requests")
+            .with_unit("{request}")
+            .with_description("Application requests holding a concurrency limit permit")
+            .with_callback(move |observer| observer.observe(limit.in_flight() as u64, &[]))
+            .build();
+    }
+
+    /// Limit `router` to this pool, answering 503 when full if `shed`
+    pub(crate) fn apply<S>(&self, router: Router<S>, shed: bool) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let limit = GlobalConcurrencyLimitLayer::with_semaphore(self.semaphore.clone());
+        if shed {
+            router.layer(
+                ServiceBuilder::new()
+                    .layer(HandleErrorLayer::new(overloaded))
+                    .load_shed()
+                    .layer(limit),
+            )
+        } else {
+            router.layer(limit)
+        }
+    }
+}
+
+#[allow(clippy::unused_async)] // `HandleErrorLayer` expects an async handler
+async fn overloaded(headers: HeaderMap, error: BoxError) -> Response {
+    tracing::warn!(error = %error, "Shedding request, concurrency limit reached");
+    let mut error = ApiError::service_unavailable("Server is overloaded, retry later")
+        .with_code("overloaded")
+        .with_retry_after(RETRY_AFTER_SECONDS);
+    if let Some(request_id) = extract_request_id(&headers) {
+        error = error.with_request_id(request_id);
+    }
+    error.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{body::Body, http::Request, http::StatusCode, routing::get};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_flight_counts_requests_holding_a_permit() {
+        let limit = RequestLimit::new(1);
+        let app = limit.apply(
+            Router::new().route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }),
+            ),
+            false,
+        );
+        assert_eq!(limit.in_flight(), 0);
+
+        let request = tokio::spawn(
+            app.clone()
+                .oneshot(Request::get("/slow").body(Body::empty()).unwrap()),
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(limit.in_flight(), 1);
+
+        // Without shedding, the next request waits for the permit
+        let queued = app
+            .oneshot(Request::get("/slow").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(queued.status(), StatusCode::OK);
+        assert_eq!(request.await.unwrap().unwrap().status(), StatusCode::OK);
+        assert_eq!(limit.in_flight(), 0);
+        assert_eq!(limit.max(), 1);
+    }
+}
@@ -0,0 +1,100 @@
+//! Maintenance mode
+//!
+//! While enabled, application routes answer 503 with a `Retry-After`
+//! header; the core endpoints stay reachable so probes keep working. The
+//! initial state comes from `FEATURE_MAINTENANCE_MODE` and can be flipped at
+//! runtime with `PUT /admin/maintenance`.
+
+use std::sync::{
+    Arc, RwLock,
+    atomic::{AtomicBool, Ordering},
+};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::response::{ApiError, extract_request_id};
+
+/// Message used when maintenance is enabled without one
+pub const DEFAULT_MAINTENANCE_MESSAGE: &str = "Service is under maintenance";
+
+/// Shared maintenance switch
+#[derive(Clone, Default)]
+pub struct MaintenanceState {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    enabled: AtomicBool,
+    message: RwLock<Option<String>>,
+}
+
+impl MaintenanceState {
+    /// State starting enabled or disabled
+    #[must_use]
+    pub fn new(enabled: bool) -> Self {
+        let state = Self::default();
+        state.inner.enabled.store(enabled, Ordering::SeqCst);
+        state
+    }
+
+    /// Whether application routes are currently rejected
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.inner.enabled.load(Ordering::SeqCst)
+    }
+
+    /// Message returned to clients, [`DEFAULT_MAINTENANCE_MESSAGE`] if unset
+    #[must_use]
+    pub fn message(&self) -> String {
+        self.inner
+            .message
+            .read()
+            .ok()
+            .and_then(|message| message.clone())
+            .unwrap_or_else(|| DEFAULT_MAINTENANCE_MESSAGE.to_string())
+    }
+
+    /// Switch maintenance mode, keeping the message
+    pub fn set_enabled(&self, enabled: bool) {
+        self.inner.enabled.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Switch maintenance mode and replace the message
+    pub fn set(&self, enabled: bool, message: Option<String>) {
+        if let Ok(mut current) = self.inner.message.write() {
+            *current = message;
+        }
+        self.inner.enabled.store(enabled, Ordering::SeqCst);
+    }
+}
+
+/// Settings for [`reject_during_maintenance`]
+#[derive(Clone)]
+pub(crate) struct MaintenanceGuard {
+    pub(crate) state: MaintenanceState,
+    pub(crate) retry_after_seconds: u64,
+}
+
+/// Middleware answering 503 while maintenance mode is enabled
+pub(crate) async fn reject_during_maintenance(
+    State(guard): State<MaintenanceGuard>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !guard.state.is_enabled() {
+        return next.run(req).await;
+    }
+
+    let mut error = ApiError::service_unavailable(guard.state.message())
+        .with_code("maintenance")
+        .with_retry_after(guard.retry_after_seconds);
+    if let Some(request_id) = extract_request_id(req.headers()) {
+        error = error.with_request_id(request_id);
+    }
+    error.into_response()
+}
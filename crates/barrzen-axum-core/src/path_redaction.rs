@@ -0,0 +1,157 @@
+//! Redaction of sensitive path segments
+//!
+//! `REQUEST_LOG_PATH_REDACTIONS` maps path patterns to a [`PathRedaction`]
+//! strategy. Patterns are matched segment by segment: `*` or a route
+//! parameter like `{token}` matches any one segment and marks it for
+//! redaction, other segments must match literally. The request log and
+//! the request spans record the redacted path; metrics keep the matched
+//! route template.
+
+use std::{borrow::Cow, fmt::Write as _, sync::Arc};
+
+use axum::http::Uri;
+use sha2::{Digest, Sha256};
+
+use crate::config::{LoggingConfig, PathRedaction};
+
+/// Replacement for masked segments; fixed so the length does not leak
+const MASK: &str = "****";
+
+/// Redacts request paths for logs and traces
+#[derive(Clone, Default)]
+pub(crate) struct PathRedactor {
+    rules: Arc<[Rule]>,
+}
+
+struct Rule {
+    /// `None` for wildcard segments
+    segments: Vec<Option<String>>,
+    strategy: PathRedaction,
+}
+
+impl PathRedactor {
+    pub(crate) fn new(logging: &LoggingConfig) -> Self {
+        let rules = logging
+            .path_redactions()
+            .into_iter()
+            .map(|(pattern, strategy)| Rule {
+                segments: pattern
+                    .split('/')
+                    .map(|segment| {
+                        let wildcard =
+                            segment == "*" || (segment.starts_with('{') && segment.ends_with('}'));
+                        (!wildcard).then(|| segment.to_string())
+                    })
+                    .collect(),
+                strategy,
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// `path` with the segments of the first matching rule redacted
+    ///
+    /// Paths no rule matches are returned unchanged.
+    pub(crate) fn redact<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        let segments: Vec<&str> = path.split('/').collect();
+        let Some(rule) = self.rules.iter().find(|rule| rule.matches(&segments)) else {
+            return Cow::Borrowed(path);
+        };
+
+        let redacted: Vec<Cow<'_, str>> = segments
+            .iter()
+            .zip(&rule.segments)
+            .map(|(segment, pattern)| match (pattern, rule.strategy) {
+                (Some(_), _) => Cow::Borrowed(*segment),
+                (None, PathRedaction::Mask) => Cow::Borrowed(MASK),
+                (None, PathRedaction::Hash) => Cow::Owned(short_hash(segment)),
+            })
+            .collect();
+        Cow::Owned(redacted.join("/"))
+    }
+
+    /// `uri` with a redacted path; unmatched URIs are returned as-is
+    pub(crate) fn redact_uri(&self, uri: &Uri) -> String {
+        match (self.redact(uri.path()), uri.query()) {
+            (Cow::Borrowed(_), _) => uri.to_string(),
+            (Cow::Owned(path), None) => path,
+            (Cow::Owned(path), Some(query)) => format!("{path}?{query}"),
+        }
+    }
+}
+
+impl Rule {
+    fn matches(&self, segments: &[&str]) -> bool {
+        segments.len() == self.segments.len()
+            && segments
+                .iter()
+                .zip(&self.segments)
+                .all(|(segment, pattern)| pattern.as_deref().is_none_or(|p| p == *segment))
+    }
+}
+
+/// First 8 hex digits of the SHA-256 of `value`
+fn short_hash(value: &str) -> String {
+    Sha256::digest(value.as_bytes()).iter().take(4).fold(
+        String::with_capacity(8),
+        |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor(rules: &str) -> PathRedactor {
+        let logging: LoggingConfig = serde_json::from_value(serde_json::json!({
+            "request_log_path_redactions": rules
+        }))
+        .unwrap();
+        PathRedactor::new(&logging)
+    }
+
+    #[test]
+    fn test_mask_and_hash_strategies() {
+        let redactor =
+            redactor("/password-reset/*=mask,/invites/{code}/accept=hash,/users/*/tokens/*=mask");
+
+        assert_eq!(
+            redactor.redact("/password-reset/s3cr3t-t0ken"),
+            "/password-reset/****"
+        );
+        // echo -n abc | sha256sum => ba7816bf...
+        assert_eq!(
+            redactor.redact("/invites/abc/accept"),
+            "/invites/ba7816bf/accept"
+        );
+        assert_eq!(
+            redactor.redact("/users/7/tokens/t0k"),
+            "/users/****/tokens/****"
+        );
+    }
+
+    #[test]
+    fn test_unmatched_paths_are_unchanged() {
+        let redactor = redactor("/password-reset/*=mask");
+
+        for path in ["/password-reset", "/password-reset/a/b", "/health", "/"] {
+            assert!(matches!(redactor.redact(path), Cow::Borrowed(p) if p == path));
+        }
+        assert_eq!(
+            PathRedactor::default().redact("/password-reset/x"),
+            "/password-reset/x"
+        );
+    }
+
+    #[test]
+    fn test_redact_uri_keeps_query() {
+        let redactor = redactor("/password-reset/*=mask");
+        let uri: Uri = "/password-reset/t0ken?lang=en".parse().unwrap();
+        assert_eq!(redactor.redact_uri(&uri), "/password-reset/****?lang=en");
+        let uri: Uri = "/orders?page=2".parse().unwrap();
+        assert_eq!(redactor.redact_uri(&uri), "/orders?page=2");
+    }
+}
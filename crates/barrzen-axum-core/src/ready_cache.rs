@@ -0,0 +1,148 @@
+//! Memoized readiness results (`READYZ_CACHE_TTL_MS`)
+//!
+//! Frequent probes from several replicas would otherwise ping the database
+//! and cache on every request. Results are reused until the TTL expires; a
+//! single caller refreshes them while concurrent callers wait for that
+//! result instead of starting their own checks.
+
+use std::time::Duration;
+
+use tokio::{
+    sync::{Mutex, RwLock},
+    time::Instant,
+};
+
+use crate::handlers::{HealthCheck, ReadyChecker};
+
+/// Last readiness result and when it was taken
+#[derive(Default)]
+pub(crate) struct ReadyCache {
+    ttl: Duration,
+    last: RwLock<Option<(Instant, Vec<HealthCheck>)>>,
+    refresh: Mutex<()>,
+}
+
+/// Checks and, when served from the cache, their age
+pub(crate) struct ReadyResult {
+    pub(crate) checks: Vec<HealthCheck>,
+    pub(crate) age: Option<Duration>,
+}
+
+impl ReadyCache {
+    /// Cache keeping results for `ttl` (zero disables caching)
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            ..Self::default()
+        }
+    }
+
+    /// Checks of `checker`, from the cache unless expired or `fresh`
+    pub(crate) async fn checks(&self, checker: &dyn ReadyChecker, fresh: bool) -> ReadyResult {
+        if self.ttl.is_zero() {
+            return ReadyResult {
+                checks: checker.ready_checks().await,
+                age: None,
+            };
+        }
+        if !fresh && let Some(result) = self.cached().await {
+            return result;
+        }
+
+        let _refresh = self.refresh.lock().await;
+        // Another caller may have refreshed while this one waited
+        if !fresh && let Some(result) = self.cached().await {
+            return result;
+        }
+        let checks = checker.ready_checks().await;
+        *self.last.write().await = Some((Instant::now(), checks.clone()));
+        ReadyResult { checks, age: None }
+    }
+
+    async fn cached(&self) -> Option<ReadyResult> {
+        let last = self.last.read().await;
+        let (taken, checks) = last.as_ref()?;
+        let age = taken.elapsed();
+        (age < self.ttl).then(|| ReadyResult {
+            checks: checks.clone(),
+            age: Some(age),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    /// Checker counting how often it runs
+    #[derive(Default)]
+    struct Counting(AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl ReadyChecker for Counting {
+        async fn ready_checks(&self) -> Vec<HealthCheck> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            vec![HealthCheck::ok("database")]
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_checker_runs_once_per_ttl_window() {
+        let cache = ReadyCache::new(Duration::from_secs(1));
+        let checker = Counting::default();
+
+        let first = cache.checks(&checker, false).await;
+        assert!(first.age.is_none());
+        tokio::time::advance(Duration::from_millis(500)).await;
+        let second = cache.checks(&checker, false).await;
+        assert_eq!(second.age, Some(Duration::from_millis(500)));
+        assert_eq!(second.checks[0].name, "database");
+        assert_eq!(checker.0.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert!(cache.checks(&checker, false).await.age.is_none());
+        assert_eq!(checker.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_expiry_refreshes_once() {
+        let cache = Arc::new(ReadyCache::new(Duration::from_secs(1)));
+        let checker = Arc::new(Counting::default());
+
+        let callers: Vec<_> = (0..8)
+            .map(|_| {
+                let (cache, checker) = (cache.clone(), checker.clone());
+                tokio::spawn(async move { cache.checks(checker.as_ref(), false).await.age })
+            })
+            .collect();
+        let mut fresh = 0;
+        for caller in callers {
+            if caller.await.unwrap().is_none() {
+                fresh += 1;
+            }
+        }
+
+        assert_eq!(fresh, 1);
+        assert_eq!(checker.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fresh_and_disabled_bypass_the_cache() {
+        let cache = ReadyCache::new(Duration::from_secs(1));
+        let checker = Counting::default();
+        cache.checks(&checker, false).await;
+        assert!(cache.checks(&checker, true).await.age.is_none());
+        assert_eq!(checker.0.load(Ordering::SeqCst), 2);
+
+        let disabled = ReadyCache::new(Duration::ZERO);
+        disabled.checks(&checker, false).await;
+        disabled.checks(&checker, false).await;
+        assert_eq!(checker.0.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,269 @@
+//! Request id of the request being handled
+//!
+//! The built app gives every request an id under `REQUEST_ID_HEADER_NAME`
+//! (default `x-request-id`) and runs it inside a scope holding that id, so
+//! code without access to the headers (broker messages, background calls
+//! made on behalf of the request) can still correlate through
+//! [`current_request_id`].
+//!
+//! An id sent by the client is kept only if it is at most
+//! `REQUEST_ID_MAX_LENGTH` characters of `[A-Za-z0-9._-]`; anything else is
+//! replaced, so the header cannot inject text into logs.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::http::{HeaderName, HeaderValue, Request, Response};
+use tower::{Layer, Service};
+use tower_http::request_id::RequestId;
+
+use crate::{
+    app_builder::REQUEST_ID_HEADER,
+    config::{RequestIdConfig, RequestIdFormat},
+};
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Request id of the current request
+///
+/// Returns `None` outside of a request handled by the built app, including
+/// tasks spawned from a handler.
+#[must_use]
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Crockford base32 alphabet of ULIDs
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// New request id in `format`
+pub(crate) fn new_request_id(format: RequestIdFormat) -> String {
+    match format {
+        RequestIdFormat::Uuidv4 => uuid::Uuid::new_v4().to_string(),
+        RequestIdFormat::Uuidv7 => uuid::Uuid::now_v7().to_string(),
+        RequestIdFormat::Ulid => new_ulid(),
+    }
+}
+
+/// 48-bit millisecond timestamp and 80 random bits, as 26 base32 characters
+fn new_ulid() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis());
+    // Bytes of a v4 UUID outside its version and variant bits
+    let bytes = uuid::Uuid::new_v4().into_bytes();
+    let random = bytes[..6]
+        .iter()
+        .chain(&bytes[9..13])
+        .fold(0u128, |acc, byte| acc << 8 | u128::from(*byte));
+    let value = (millis & 0xFFFF_FFFF_FFFF) << 80 | random;
+
+    (0..26)
+        .map(|i| char::from(CROCKFORD[(value >> (125 - 5 * i)) as usize & 31]))
+        .collect()
+}
+
+/// Whether an inbound id is short and plain enough to keep
+pub(crate) fn is_valid_request_id(value: &str, max_length: usize) -> bool {
+    !value.is_empty()
+        && value.len() <= max_length
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'))
+}
+
+/// Header, format and length limit shared by the services of the layer
+struct RequestIds {
+    header: HeaderName,
+    format: RequestIdFormat,
+    max_length: usize,
+}
+
+/// Layer setting, propagating and scoping the request id
+///
+/// Keeps a valid inbound id or generates one, stores it in the request
+/// header and as a [`RequestId`] extension, runs the request inside its
+/// scope and echoes it on the response.
+#[derive(Clone)]
+pub(crate) struct RequestContextLayer {
+    ids: Arc<RequestIds>,
+}
+
+impl RequestContextLayer {
+    pub(crate) fn new(config: &RequestIdConfig) -> Self {
+        Self {
+            ids: Arc::new(RequestIds {
+                header: config
+                    .header_name()
+                    .unwrap_or_else(|| REQUEST_ID_HEADER.clone()),
+                format: config.request_id_format,
+                max_length: config.request_id_max_length,
+            }),
+        }
+    }
+}
+
+impl<S> Layer<S> for RequestContextLayer {
+    type Service = RequestContextService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestContextService {
+            inner,
+            ids: self.ids.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RequestContextService<S> {
+    inner: S,
+    ids: Arc<RequestIds>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for RequestContextService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<ReqBody>) -> Self::Future {
+        let header = self.ids.header.clone();
+        let inbound = req.headers().get(&header).map(HeaderValue::to_str);
+        let request_id = match inbound {
+            Some(Ok(id)) if is_valid_request_id(id, self.ids.max_length) => id.to_string(),
+            Some(_) => {
+                tracing::debug!(header = %header, "Replacing an invalid inbound request id");
+                new_request_id(self.ids.format)
+            }
+            None => new_request_id(self.ids.format),
+        };
+        let Ok(value) = HeaderValue::from_str(&request_id) else {
+            unreachable!("request ids are visible ASCII");
+        };
+        req.headers_mut().insert(header.clone(), value.clone());
+        req.extensions_mut().insert(RequestId::new(value.clone()));
+
+        // Inner layers read the id while building their future too
+        let future = REQUEST_ID.sync_scope(request_id.clone(), || self.inner.call(req));
+
+        Box::pin(async move {
+            let mut response = REQUEST_ID.scope(request_id, future).await?;
+            response.headers_mut().entry(header).or_insert(value);
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, routing::get};
+    use tower::ServiceExt;
+
+    fn app(config: &RequestIdConfig) -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|| async { current_request_id().unwrap_or_default() }),
+            )
+            .layer(RequestContextLayer::new(config))
+    }
+
+    async fn call(app: Router, header: Option<(&str, &str)>) -> (String, Option<String>) {
+        let mut request = Request::builder().uri("/");
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let echoed = header
+            .and_then(|(name, _)| response.headers().get(name))
+            .or_else(|| response.headers().get(&REQUEST_ID_HEADER))
+            .map(|value| value.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (String::from_utf8(body.to_vec()).unwrap(), echoed)
+    }
+
+    #[tokio::test]
+    async fn test_request_id_only_inside_scope() {
+        assert!(current_request_id().is_none());
+
+        let (body, echoed) = call(
+            app(&RequestIdConfig::default()),
+            Some(("x-request-id", "req-1")),
+        )
+        .await;
+        assert_eq!(body, "req-1");
+        assert_eq!(echoed.as_deref(), Some("req-1"));
+    }
+
+    #[tokio::test]
+    async fn test_custom_header_name() {
+        let config = RequestIdConfig {
+            request_id_header_name: "x-correlation-id".to_string(),
+            ..RequestIdConfig::default()
+        };
+        let (body, echoed) = call(app(&config), Some(("x-correlation-id", "corr-7"))).await;
+        assert_eq!(body, "corr-7");
+        assert_eq!(echoed.as_deref(), Some("corr-7"));
+
+        // The default header is just another header now
+        let (body, _) = call(app(&config), Some(("x-request-id", "req-1"))).await;
+        assert_ne!(body, "req-1");
+        assert_eq!(body.len(), 36);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_inbound_ids_are_replaced() {
+        let config = RequestIdConfig {
+            request_id_max_length: 8,
+            ..RequestIdConfig::default()
+        };
+        for inbound in ["req 1 level=error", "a\"b", "123456789"] {
+            let (body, echoed) = call(app(&config), Some(("x-request-id", inbound))).await;
+            assert_ne!(body, inbound);
+            assert_eq!(echoed.as_deref(), Some(body.as_str()));
+            assert!(uuid::Uuid::parse_str(&body).is_ok(), "{body}");
+        }
+
+        // http refuses to build such a header, but the check stands on its own
+        assert!(!is_valid_request_id("req-1\nlevel=error msg=forged", 128));
+        assert!(!is_valid_request_id("", 128));
+        assert!(is_valid_request_id("Req_1.a-b", 128));
+    }
+
+    #[test]
+    fn test_generated_formats() {
+        let v4 = uuid::Uuid::parse_str(&new_request_id(RequestIdFormat::Uuidv4)).unwrap();
+        assert_eq!(v4.get_version_num(), 4);
+        let v7 = uuid::Uuid::parse_str(&new_request_id(RequestIdFormat::Uuidv7)).unwrap();
+        assert_eq!(v7.get_version_num(), 7);
+
+        let ulid = new_request_id(RequestIdFormat::Ulid);
+        assert_eq!(ulid.len(), 26);
+        assert!(ulid.bytes().all(|b| CROCKFORD.contains(&b)), "{ulid}");
+        // Time-ordered: the timestamp characters never decrease
+        let later = new_request_id(RequestIdFormat::Ulid);
+        assert!(later[..10] >= ulid[..10]);
+        assert!(is_valid_request_id(&ulid, 128));
+    }
+}
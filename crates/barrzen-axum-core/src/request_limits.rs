@@ -0,0 +1,416 @@
+//! Request timeout and body limit with per-route overrides
+//!
+//! The global layer (`HTTP_REQUEST_TIMEOUT_SECONDS`, `HTTP_BODY_LIMIT_BYTES`)
+//! does not enforce its values up front. It puts them in a per-request slot
+//! and reads them back late: the timeout on every poll of the response
+//! future, the body limit when the body is first read. A [`RouteOverrides`]
+//! layer on a sub-router runs in between and replaces the values in the
+//! slot, so an override wins whether it is larger or smaller than the
+//! global setting.
+
+use std::{
+    future::{Future, poll_fn},
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::DefaultBodyLimit,
+    http::{Request, Response, StatusCode},
+    response::IntoResponse,
+};
+use http_body::{Frame, SizeHint};
+use http_body_util::Limited;
+use tokio::time::{Instant, Sleep};
+use tower::{BoxError, Layer, Service};
+
+use crate::{
+    config::HttpConfig,
+    response::{ApiError, extract_request_id},
+};
+
+/// Timeout and body limit for one request, shared between the layers
+#[derive(Debug)]
+struct Limits {
+    body_limit: AtomicUsize,
+    /// Milliseconds, 0 for no timeout
+    timeout_ms: AtomicU64,
+}
+
+impl Limits {
+    fn new(body_limit: usize, timeout: Option<Duration>) -> Self {
+        Self {
+            body_limit: AtomicUsize::new(body_limit),
+            timeout_ms: AtomicU64::new(timeout.map_or(0, duration_ms)),
+        }
+    }
+
+    fn body_limit(&self) -> usize {
+        self.body_limit.load(Ordering::Relaxed)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        match self.timeout_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    fn apply(&self, overrides: &RouteOverrides) {
+        if let Some(body_limit) = overrides.body_limit {
+            self.body_limit.store(body_limit, Ordering::Relaxed);
+        }
+        if let Some(timeout) = overrides.timeout {
+            self.timeout_ms
+                .store(duration_ms(timeout), Ordering::Relaxed);
+        }
+    }
+}
+
+/// Whole milliseconds, rounded up so only `Duration::ZERO` means none
+fn duration_ms(duration: Duration) -> u64 {
+    if duration.is_zero() {
+        return 0;
+    }
+    u64::try_from(duration.as_millis())
+        .unwrap_or(u64::MAX)
+        .max(1)
+}
+
+type BoxFuture<E> = Pin<Box<dyn Future<Output = Result<Response<Body>, E>> + Send>>;
+
+/// Request extension carrying the limits of the enclosing global layer
+#[derive(Clone, Debug)]
+struct LimitsSlot(Arc<Limits>);
+
+/// Global timeout and body limit, applied by `AppBuilder`
+#[derive(Clone, Debug)]
+pub(crate) struct RequestLimitsLayer {
+    body_limit: usize,
+    timeout: Option<Duration>,
+}
+
+impl RequestLimitsLayer {
+    /// Layer using `HTTP_BODY_LIMIT_BYTES` and `HTTP_REQUEST_TIMEOUT_SECONDS`
+    /// (0 disables the timeout)
+    pub(crate) fn new(config: &HttpConfig) -> Self {
+        Self {
+            body_limit: config.http_body_limit_bytes,
+            timeout: (config.http_request_timeout_seconds > 0).then(|| config.request_timeout()),
+        }
+    }
+}
+
+impl<S> Layer<S> for RequestLimitsLayer {
+    type Service = RequestLimitsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestLimitsService {
+            // The limit is enforced here, so axum's 2MB extractor default
+            // must not clamp it
+            inner: DefaultBodyLimit::disable().layer(inner),
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RequestLimitsService<S> {
+    inner: <DefaultBodyLimit as Layer<S>>::Service,
+    layer: RequestLimitsLayer,
+}
+
+impl<S> Service<Request<Body>> for RequestLimitsService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let limits = Arc::new(Limits::new(self.layer.body_limit, self.layer.timeout));
+        limit_request(self.inner.clone(), req, limits)
+    }
+}
+
+/// Per-route timeout and body limit, overriding the global settings
+///
+/// Apply it as a layer on a sub-router; the global limits then let its
+/// routes through with these values instead, including a larger body limit
+/// or a longer timeout. Unset values keep the global setting. Outside an
+/// `AppBuilder` app the layer enforces its own values.
+///
+/// ```ignore
+/// let uploads = Router::new()
+///     .route("/upload", post(upload))
+///     .layer(RouteOverrides::new().timeout(Duration::from_secs(120)).body_limit(50 * MB));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RouteOverrides {
+    timeout: Option<Duration>,
+    body_limit: Option<usize>,
+}
+
+/// One mebibyte, for readable body limits
+pub const MB: usize = 1024 * 1024;
+
+impl RouteOverrides {
+    /// Overrides that keep every global setting
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time allowed until the response starts (`Duration::ZERO` disables
+    /// the timeout for these routes)
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Largest request body in bytes
+    #[must_use]
+    pub fn body_limit(mut self, bytes: usize) -> Self {
+        self.body_limit = Some(bytes);
+        self
+    }
+}
+
+impl<S> Layer<S> for RouteOverrides {
+    type Service = RouteOverridesService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RouteOverridesService {
+            inner: DefaultBodyLimit::disable().layer(inner),
+            overrides: *self,
+        }
+    }
+}
+
+/// Service applying [`RouteOverrides`]
+#[derive(Clone)]
+pub struct RouteOverridesService<S> {
+    inner: <DefaultBodyLimit as Layer<S>>::Service,
+    overrides: RouteOverrides,
+}
+
+impl<S> Service<Request<Body>> for RouteOverridesService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<S::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        if let Some(LimitsSlot(limits)) = req.extensions().get::<LimitsSlot>() {
+            limits.apply(&self.overrides);
+            return Box::pin(inner.call(req));
+        }
+
+        // No global layer around us: enforce the overrides alone
+        let limits = Limits::new(usize::MAX, None);
+        limits.apply(&self.overrides);
+        limit_request(inner, req, Arc::new(limits))
+    }
+}
+
+/// Run `req` through `inner` under `limits`, which may change until the
+/// body is read and the response is ready
+fn limit_request<S>(mut inner: S, req: Request<Body>, limits: Arc<Limits>) -> BoxFuture<S::Error>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    let started = Instant::now();
+    let request_id = extract_request_id(req.headers());
+    let (mut parts, body) = req.into_parts();
+    parts.extensions.insert(LimitsSlot(limits.clone()));
+    let body = Body::new(DeferredLimit::new(body, limits.clone()));
+    let mut response = Box::pin(inner.call(Request::from_parts(parts, body)));
+
+    Box::pin(async move {
+        let mut sleep: Option<Pin<Box<Sleep>>> = None;
+        // The inner service is polled first, so an override seen on this
+        // poll already moves the deadline
+        let outcome = poll_fn(|cx| {
+            if let Poll::Ready(result) = response.as_mut().poll(cx) {
+                return Poll::Ready(result.map(Some));
+            }
+            let Some(timeout) = limits.timeout() else {
+                return Poll::Pending;
+            };
+            let deadline = started + timeout;
+            let sleep = sleep.get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+            if sleep.deadline() != deadline {
+                sleep.as_mut().reset(deadline);
+            }
+            sleep.as_mut().poll(cx).map(|()| Ok(None))
+        })
+        .await?;
+
+        Ok(outcome.unwrap_or_else(|| {
+            let mut error = ApiError::new(StatusCode::REQUEST_TIMEOUT, "Request timed out")
+                .with_code("request_timeout");
+            if let Some(request_id) = request_id {
+                error = error.with_request_id(request_id);
+            }
+            error.into_response()
+        }))
+    })
+}
+
+/// Request body limited by whatever limit is in effect when it is first
+/// read
+///
+/// Reading past the limit fails with `http_body_util::LengthLimitError`,
+/// which axum's extractors turn into 413.
+struct DeferredLimit {
+    /// Taken on the first read, when the limit is fixed
+    unread: Option<Body>,
+    body: Limited<Body>,
+    limits: Arc<Limits>,
+}
+
+impl DeferredLimit {
+    fn new(body: Body, limits: Arc<Limits>) -> Self {
+        Self {
+            unread: Some(body),
+            body: Limited::new(Body::empty(), 0),
+            limits,
+        }
+    }
+}
+
+impl http_body::Body for DeferredLimit {
+    type Data = Bytes;
+    type Error = BoxError;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = &mut *self;
+        if let Some(body) = this.unread.take() {
+            this.body = Limited::new(body, this.limits.body_limit());
+        }
+        Pin::new(&mut this.body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.unread
+            .as_ref()
+            .map_or_else(|| self.body.is_end_stream(), Body::is_end_stream)
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.unread
+            .as_ref()
+            .map_or_else(|| self.body.size_hint(), Body::size_hint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, routing::post};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn upload(body: Bytes) -> String {
+        body.len().to_string()
+    }
+
+    fn post_bytes(path: &str, len: usize) -> Request<Body> {
+        Request::post(path)
+            .body(Body::from(vec![b'x'; len]))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_overrides_apply_without_global_layer() {
+        let app = Router::new()
+            .route("/upload", post(upload))
+            .route(
+                "/slow",
+                post(|| async {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }),
+            )
+            .layer(
+                RouteOverrides::new()
+                    .body_limit(3 * MB)
+                    .timeout(Duration::from_millis(50)),
+            );
+
+        // Larger than axum's 2MB extractor default
+        let response = app
+            .clone()
+            .oneshot(post_bytes("/upload", 5 * MB / 2))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = app
+            .clone()
+            .oneshot(post_bytes("/upload", 4 * MB))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let response = app.oneshot(post_bytes("/slow", 0)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::REQUEST_TIMEOUT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["error_code"], "request_timeout");
+    }
+
+    #[tokio::test]
+    async fn test_smaller_override_wins_over_global() {
+        let mut config = crate::test_support::test_config().http;
+        config.http_body_limit_bytes = MB;
+        let app = Router::new()
+            .route("/upload", post(upload))
+            .route_layer(RouteOverrides::new().body_limit(16))
+            .route("/other", post(upload))
+            .layer(RequestLimitsLayer::new(&config));
+
+        let response = app
+            .clone()
+            .oneshot(post_bytes("/upload", 32))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let response = app.oneshot(post_bytes("/other", 32)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_zero_timeout_disables() {
+        let limits = Limits::new(MB, Some(Duration::from_secs(1)));
+        limits.apply(&RouteOverrides::new().timeout(Duration::ZERO));
+        assert_eq!(limits.timeout(), None);
+        limits.apply(&RouteOverrides::new().timeout(Duration::from_micros(10)));
+        assert_eq!(limits.timeout(), Some(Duration::from_millis(1)));
+    }
+}
@@ -0,0 +1,924 @@
+//! Request logging middleware
+//!
+//! Emits one completion line per request through the configured log backend,
+//! with the request and response bodies when `REQUEST_LOG_BODY` asks for them.
+
+use std::{
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    http::{
+        HeaderMap, HeaderName, Request, StatusCode,
+        header::{CONTENT_ENCODING, CONTENT_TYPE},
+    },
+    response::Response,
+};
+use futures_util::StreamExt;
+use http_body::Body as _;
+use http_body_util::BodyExt;
+use tower::{Layer, Service};
+
+use crate::{
+    client_ip::ClientIp,
+    config::{Config, LogBackend, RequestLogBody},
+    features::FeatureState,
+    path_redaction::PathRedactor,
+    response::extract_request_id,
+    tenant::TenantId,
+};
+
+/// Maximum number of characters logged per header value
+const MAX_HEADER_VALUE_CHARS: usize = 256;
+
+/// Layer that logs request completion (method, path, status, latency)
+///
+/// `remote_addr` is the resolved [`ClientIp`] when available, `tenant` the
+/// [`TenantId`] set by the tenant layer (`FEATURE_TENANT`).
+///
+/// Headers listed in `REQUEST_LOG_HEADERS_ALLOWLIST` are logged as
+/// `hdr_<name>=<value>` pairs unless they are sensitive (denylisted or the
+/// API key header). The tracing
+/// backend cannot create field names at runtime, so the pairs are rendered
+/// into a single `headers` field there.
+///
+/// Paths matching `REQUEST_LOG_PATH_REDACTIONS` are logged redacted.
+///
+/// With `REQUEST_LOG_BODY`, JSON and text bodies are logged as `req_body`
+/// and `res_body`, truncated at `REQUEST_LOG_BODY_MAX_BYTES`. Only the
+/// first bytes of a request body are buffered; the handler still reads it
+/// as a stream. Response bodies are only logged when already in memory, so
+/// streams such as SSE pass through untouched.
+#[derive(Clone)]
+pub(crate) struct RequestLogLayer {
+    backend: LogBackend,
+    slow_threshold_ms: u64,
+    logged_headers: Arc<[LoggedHeader]>,
+    redactor: PathRedactor,
+    body_logging: Option<Arc<BodyLogging>>,
+    features: Option<FeatureState>,
+}
+
+impl RequestLogLayer {
+    pub(crate) fn new(config: &Config) -> Self {
+        let denylist = config.sensitive_headers();
+        let logged_headers = config
+            .logging
+            .headers_allowlist()
+            .into_iter()
+            .filter(|name| !denylist.contains(name))
+            .filter_map(|name| {
+                let header = HeaderName::from_bytes(name.as_bytes()).ok()?;
+                Some(LoggedHeader {
+                    field: format!("hdr_{}", name.replace('-', "_")),
+                    name: header,
+                })
+            })
+            .collect();
+
+        Self {
+            backend: config.logging.log_backend,
+            slow_threshold_ms: config.logging.request_log_slow_threshold_ms,
+            logged_headers,
+            redactor: PathRedactor::new(&config.logging),
+            body_logging: BodyLogging::from_config(config).map(Arc::new),
+            features: None,
+        }
+    }
+
+    /// Only log while the `request_log` flag of `features` is on
+    pub(crate) fn switched_by(mut self, features: FeatureState) -> Self {
+        self.features = Some(features);
+        self
+    }
+}
+
+struct LoggedHeader {
+    name: HeaderName,
+    field: String,
+}
+
+impl<S> Layer<S> for RequestLogLayer {
+    type Service = RequestLogService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestLogService {
+            inner,
+            backend: self.backend,
+            slow_threshold_ms: self.slow_threshold_ms,
+            logged_headers: self.logged_headers.clone(),
+            redactor: self.redactor.clone(),
+            body_logging: self.body_logging.clone(),
+            features: self.features.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct RequestLogService<S> {
+    inner: S,
+    backend: LogBackend,
+    slow_threshold_ms: u64,
+    logged_headers: Arc<[LoggedHeader]>,
+    redactor: PathRedactor,
+    body_logging: Option<Arc<BodyLogging>>,
+    features: Option<FeatureState>,
+}
+
+impl<S> Service<Request<Body>> for RequestLogService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        if self
+            .features
+            .as_ref()
+            .is_some_and(|features| !features.request_log())
+        {
+            return Box::pin(async move { inner.call(req).await });
+        }
+        let backend = self.backend;
+        let slow_threshold_ms = self.slow_threshold_ms;
+
+        let method = req.method().clone();
+        let path = self.redactor.redact(req.uri().path()).into_owned();
+        let request_id = extract_request_id(req.headers()).unwrap_or_default();
+        let remote_addr = req
+            .extensions()
+            .get::<ClientIp>()
+            .map(|ClientIp(ip)| ip.to_string());
+        let headers = HeaderFields::capture(&self.logged_headers, req.headers());
+        let body_logging = self.body_logging.clone();
+        let start = Instant::now();
+
+        Box::pin(async move {
+            // The status is not known yet, so `on_error` buffers too
+            let (req, request_body) = match (&body_logging, body_kind(req.headers())) {
+                (Some(logging), Some(kind)) => {
+                    let (parts, body) = req.into_parts();
+                    let (head, body) = peek(body, logging.max_bytes).await;
+                    (Request::from_parts(parts, body), Some((head, kind)))
+                }
+                _ => (req, None),
+            };
+
+            let response = inner.call(req).await?;
+            let latency_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+            let status = response.status();
+            let tenant = response.extensions().get::<TenantId>().map(|t| t.0.clone());
+            let (level, slow) = completion_level(status, latency_ms, slow_threshold_ms);
+
+            let (response, bodies) = match body_logging {
+                Some(logging) if logging.logs(status) => {
+                    let (response, response_body) = capture(response).await;
+                    let bodies = BodyFields {
+                        request: request_body.and_then(|(head, kind)| logging.render(&head, kind)),
+                        response: response_body
+                            .and_then(|(bytes, kind)| logging.render(&bytes, kind)),
+                    };
+                    (response, bodies)
+                }
+                _ => (response, BodyFields::default()),
+            };
+
+            match backend {
+                LogBackend::Tracing => {
+                    macro_rules! emit {
+                        ($event:ident) => {
+                            tracing::$event!(
+                                request_id = %request_id,
+                                method = %method,
+                                path = %path,
+                                remote_addr = remote_addr.as_deref(),
+                                tenant = tenant.as_deref(),
+                                status = status.as_u16(),
+                                latency_ms = latency_ms,
+                                slow = slow.then_some(true),
+                                headers = (!headers.is_empty()).then_some(tracing::field::display(&headers)),
+                                req_body = bodies.request.as_deref(),
+                                res_body = bodies.response.as_deref(),
+                                "request completed"
+                            )
+                        };
+                    }
+
+                    match level {
+                        log::Level::Error => emit!(error),
+                        log::Level::Warn => emit!(warn),
+                        _ => emit!(info),
+                    }
+                }
+                LogBackend::FastLog => {
+                    log::log!(
+                        level,
+                        "request completed request_id={} method={} path={}{}{}{}{} status={} latency_ms={}{}{}{}{}",
+                        request_id,
+                        method,
+                        path,
+                        if remote_addr.is_some() {
+                            " remote_addr="
+                        } else {
+                            ""
+                        },
+                        remote_addr.as_deref().unwrap_or(""),
+                        if tenant.is_some() { " tenant=" } else { "" },
+                        tenant.as_deref().unwrap_or(""),
+                        status.as_u16(),
+                        latency_ms,
+                        if slow { " slow=true" } else { "" },
+                        if headers.is_empty() { "" } else { " " },
+                        headers,
+                        bodies
+                    );
+                }
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+/// Allowlisted header values captured from a request
+struct HeaderFields(Vec<(String, String)>);
+
+impl HeaderFields {
+    fn capture(logged_headers: &[LoggedHeader], headers: &HeaderMap) -> Self {
+        let fields = logged_headers
+            .iter()
+            .filter_map(|logged| {
+                let value = headers.get(&logged.name)?;
+                let value = String::from_utf8_lossy(value.as_bytes());
+                Some((logged.field.clone(), truncate_value(&value)))
+            })
+            .collect();
+        Self(fields)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for HeaderFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (field, value)) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{field}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+fn truncate_value(value: &str) -> String {
+    if value.chars().count() > MAX_HEADER_VALUE_CHARS {
+        let truncated: String = value.chars().take(MAX_HEADER_VALUE_CHARS).collect();
+        format!("{truncated}…")
+    } else {
+        value.to_string()
+    }
+}
+
+/// Body logging settings, `None` when `REQUEST_LOG_BODY` is off
+struct BodyLogging {
+    /// Log bodies of every request, not just failed ones
+    always: bool,
+    max_bytes: usize,
+    redact_keys: Vec<String>,
+}
+
+impl BodyLogging {
+    fn from_config(config: &Config) -> Option<Self> {
+        let always = match config.request_log_body() {
+            RequestLogBody::Off => return None,
+            RequestLogBody::OnError => false,
+            RequestLogBody::Always => true,
+        };
+        Some(Self {
+            always,
+            max_bytes: config.logging.request_log_body_max_bytes,
+            redact_keys: config.logging.body_redact_keys(),
+        })
+    }
+
+    fn logs(&self, status: StatusCode) -> bool {
+        self.always || status.is_client_error() || status.is_server_error()
+    }
+
+    /// Body as logged: JSON keys redacted, truncated to `max_bytes`
+    fn render(&self, bytes: &[u8], kind: BodyKind) -> Option<String> {
+        if bytes.is_empty() {
+            return None;
+        }
+        let text = String::from_utf8_lossy(bytes);
+        let text = match kind {
+            BodyKind::Json => redact_json(&text, &self.redact_keys),
+            BodyKind::Text => text.into_owned(),
+        };
+        if bytes.len() <= self.max_bytes && text.len() <= self.max_bytes {
+            return Some(text);
+        }
+        let mut end = self.max_bytes.min(text.len());
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        Some(format!("{}…", &text[..end]))
+    }
+}
+
+/// Bodies logged with a completion line
+#[derive(Default)]
+struct BodyFields {
+    request: Option<String>,
+    response: Option<String>,
+}
+
+impl fmt::Display for BodyFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(body) = &self.request {
+            write!(f, " req_body={body:?}")?;
+        }
+        if let Some(body) = &self.response {
+            write!(f, " res_body={body:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Content types whose bodies are logged
+#[derive(Clone, Copy)]
+enum BodyKind {
+    Json,
+    Text,
+}
+
+fn body_kind(headers: &HeaderMap) -> Option<BodyKind> {
+    if headers.contains_key(CONTENT_ENCODING) {
+        return None;
+    }
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if essence == "application/json" || essence.ends_with("+json") {
+        Some(BodyKind::Json)
+    } else if essence.starts_with("text/") && essence != "text/event-stream" {
+        Some(BodyKind::Text)
+    } else {
+        None
+    }
+}
+
+/// Read the first `max_bytes` (or slightly more) of `body`
+///
+/// Returns them with a body that still yields everything, so the handler
+/// reads the request as if nothing happened.
+async fn peek(body: Body, max_bytes: usize) -> (Bytes, Body) {
+    let mut stream = body.into_data_stream();
+    let mut head = Vec::new();
+    let mut chunks = Vec::new();
+    let mut complete = false;
+    while head.len() <= max_bytes {
+        match stream.next().await {
+            Some(Ok(chunk)) => {
+                head.extend_from_slice(&chunk);
+                chunks.push(Ok(chunk));
+            }
+            Some(Err(error)) => {
+                chunks.push(Err(error));
+                break;
+            }
+            None => {
+                complete = true;
+                break;
+            }
+        }
+    }
+
+    let head = Bytes::from(head);
+    let body = if complete {
+        Body::from(head.clone())
+    } else {
+        Body::from_stream(futures_util::stream::iter(chunks).chain(stream))
+    };
+    (head, body)
+}
+
+/// Buffer a response body that is already in memory (exact size known)
+async fn capture(response: Response) -> (Response, Option<(Bytes, BodyKind)>) {
+    let Some(kind) = body_kind(response.headers()) else {
+        return (response, None);
+    };
+    if response.body().size_hint().exact().is_none() {
+        return (response, None);
+    }
+    let (parts, body) = response.into_parts();
+    match body.collect().await {
+        Ok(collected) => {
+            let bytes = collected.to_bytes();
+            let response = Response::from_parts(parts, Body::from(bytes.clone()));
+            (response, Some((bytes, kind)))
+        }
+        Err(_) => (Response::from_parts(parts, Body::empty()), None),
+    }
+}
+
+/// Replace the values of `keys` in JSON `text` with `"***"`
+///
+/// Scans the text instead of parsing it, so truncated request bodies are
+/// redacted too; a value cut off by the truncation is masked to the end.
+fn redact_json(text: &str, keys: &[String]) -> String {
+    if keys.is_empty() {
+        return text.to_string();
+    }
+    let bytes = text.as_bytes();
+    let mut redacted = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'"' {
+            i += 1;
+            continue;
+        }
+        let end = string_end(bytes, i);
+        let key = &text[i + 1..end];
+        let key = key.strip_suffix('"').unwrap_or(key);
+        let colon = skip_whitespace(bytes, end);
+        if bytes.get(colon) == Some(&b':') && keys.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+            let value = skip_whitespace(bytes, colon + 1);
+            redacted.push_str(&text[copied..value]);
+            redacted.push_str("\"***\"");
+            copied = value_end(bytes, value);
+            i = copied;
+        } else {
+            i = end;
+        }
+    }
+    redacted.push_str(&text[copied..]);
+    redacted
+}
+
+/// Index after the closing quote of the string starting at `start`
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Index after the JSON value starting at `start`
+fn value_end(bytes: &[u8], start: usize) -> usize {
+    match bytes.get(start) {
+        Some(b'"') => string_end(bytes, start),
+        Some(b'{' | b'[') => {
+            let mut depth = 0_usize;
+            let mut i = start;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'"' => {
+                        i = string_end(bytes, i);
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth = depth.saturating_sub(1);
+                        if depth == 0 {
+                            return i + 1;
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+            bytes.len()
+        }
+        _ => bytes[start.min(bytes.len())..]
+            .iter()
+            .position(|b| matches!(b, b',' | b'}' | b']') || b.is_ascii_whitespace())
+            .map_or(bytes.len(), |offset| start + offset),
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], start: usize) -> usize {
+    bytes[start.min(bytes.len())..]
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .map_or(bytes.len(), |offset| start + offset)
+}
+
+/// Pick the level for a completion line
+///
+/// Server errors are always logged at `error`. Requests slower than a non-zero
+/// threshold are logged at `warn` and flagged as slow.
+fn completion_level(
+    status: StatusCode,
+    latency_ms: u64,
+    slow_threshold_ms: u64,
+) -> (log::Level, bool) {
+    let slow = slow_threshold_ms > 0 && latency_ms > slow_threshold_ms;
+
+    let level = if status.is_server_error() {
+        log::Level::Error
+    } else if slow {
+        log::Level::Warn
+    } else {
+        log::Level::Info
+    };
+
+    (level, slow)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{LogCapture, test_config};
+    use axum::{
+        Router,
+        routing::{get, post},
+    };
+    use serde_json::json;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn router(slow_threshold_ms: u64) -> Router {
+        let mut config = test_config();
+        config.logging.request_log_slow_threshold_ms = slow_threshold_ms;
+
+        Router::new()
+            .route("/fast", get(|| async { "ok" }))
+            .route(
+                "/slow",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_millis(60)).await;
+                    "ok"
+                }),
+            )
+            .route("/boom", get(|| async { StatusCode::INTERNAL_SERVER_ERROR }))
+            .layer(RequestLogLayer::new(&config))
+    }
+
+    async fn call_with_headers(app: Router, uri: &str, headers: &[(&str, &str)]) {
+        let mut request = Request::builder().uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+    }
+
+    async fn call(app: Router, uri: &str) {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        app.oneshot(request).await.unwrap();
+    }
+
+    #[test]
+    fn test_completion_level_boundary() {
+        assert_eq!(
+            completion_level(StatusCode::OK, 100, 100),
+            (log::Level::Info, false)
+        );
+        assert_eq!(
+            completion_level(StatusCode::OK, 101, 100),
+            (log::Level::Warn, true)
+        );
+        assert_eq!(
+            completion_level(StatusCode::OK, 10_000, 0),
+            (log::Level::Info, false)
+        );
+        assert_eq!(
+            completion_level(StatusCode::BAD_GATEWAY, 0, 100),
+            (log::Level::Error, false)
+        );
+        assert_eq!(
+            completion_level(StatusCode::BAD_GATEWAY, 101, 100),
+            (log::Level::Error, true)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_request_logged_at_warn() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        call(router(30), "/fast").await;
+        call(router(30), "/slow").await;
+
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("INFO") && lines[0].contains("path=/fast"));
+        assert!(!lines[0].contains("slow="));
+        assert!(lines[1].contains("WARN") && lines[1].contains("path=/slow"));
+        assert!(lines[1].contains("slow=true"));
+    }
+
+    #[tokio::test]
+    async fn test_slow_threshold_disabled_by_default() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        call(router(0), "/slow").await;
+
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("INFO"));
+        assert!(!lines[0].contains("slow="));
+    }
+
+    #[tokio::test]
+    async fn test_server_error_logged_at_error() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        call(router(0), "/boom").await;
+
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("ERROR") && lines[0].contains("status=500"));
+    }
+
+    #[tokio::test]
+    async fn test_allowlisted_headers_are_logged() {
+        let mut config = test_config();
+        config.logging.request_log_headers_allowlist =
+            Some("X-Tenant-Id,authorization,x-long,x-api-key".to_string());
+        config.logging.request_log_headers_denylist = "authorization".to_string();
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(RequestLogLayer::new(&config));
+
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        let long_value = "v".repeat(300);
+        call_with_headers(
+            app,
+            "/",
+            &[
+                ("x-tenant-id", "acme"),
+                ("authorization", "Bearer secret-token"),
+                ("x-api-key", "secret-key"),
+                ("x-long", &long_value),
+                ("x-other", "ignored"),
+            ],
+        )
+        .await;
+
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("hdr_x_tenant_id=acme"));
+        assert!(!lines[0].contains("secret-token"));
+        assert!(!lines[0].contains("secret-key"));
+        assert!(!lines[0].contains("hdr_authorization"));
+        assert!(!lines[0].contains("x_other"));
+        assert!(!lines[0].contains("hdr_x_api_key"));
+        assert!(lines[0].contains(&format!("hdr_x_long={}…", "v".repeat(256))));
+        assert!(!lines[0].contains(&"v".repeat(257)));
+    }
+
+    #[tokio::test]
+    async fn test_remote_addr_from_client_ip() {
+        let trusted = vec!["10.0.0.0/8".parse().unwrap()];
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(RequestLogLayer::new(&test_config()))
+            .layer(crate::client_ip::ClientIpLayer::new(trusted));
+
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        let mut request = Request::builder()
+            .uri("/")
+            .header("x-forwarded-for", "203.0.113.5")
+            .body(Body::empty())
+            .unwrap();
+        request.extensions_mut().insert(axum::extract::ConnectInfo(
+            "10.0.0.1:443".parse::<std::net::SocketAddr>().unwrap(),
+        ));
+        app.oneshot(request).await.unwrap();
+
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].contains("remote_addr=\"203.0.113.5\""));
+    }
+
+    #[tokio::test]
+    async fn test_empty_allowlist_logs_no_headers() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        call_with_headers(router(0), "/fast", &[("x-tenant-id", "acme")]).await;
+
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(!lines[0].contains("headers="));
+        assert!(!lines[0].contains("acme"));
+    }
+
+    #[tokio::test]
+    async fn test_sensitive_path_segments_are_redacted() {
+        let mut config = test_config();
+        config.logging.request_log_path_redactions =
+            Some("/password-reset/*=mask,/invites/{code}=hash".to_string());
+        let app = Router::new()
+            .route("/password-reset/{token}", get(|| async { "ok" }))
+            .route("/invites/{code}", get(|| async { "ok" }))
+            .route("/orders/{id}", get(|| async { "ok" }))
+            .layer(RequestLogLayer::new(&config));
+
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+        call(app.clone(), "/password-reset/s3cr3t-t0ken").await;
+        call(app.clone(), "/invites/abc").await;
+        call(app, "/orders/42").await;
+
+        let output = capture.lines().join("\n");
+        assert!(output.contains("path=/password-reset/****"), "{output}");
+        assert!(output.contains("path=/invites/ba7816bf"), "{output}");
+        assert!(output.contains("path=/orders/42"), "{output}");
+        assert!(!output.contains("s3cr3t-t0ken"), "{output}");
+        assert!(!output.contains("/invites/abc"), "{output}");
+    }
+
+    fn body_router(mode: RequestLogBody, max_bytes: usize) -> Router {
+        let mut config = test_config();
+        config.logging.request_log_body = mode;
+        config.logging.request_log_body_max_bytes = max_bytes;
+        body_router_with(&config)
+    }
+
+    fn body_router_with(config: &Config) -> Router {
+        Router::new()
+            .route(
+                "/echo",
+                post(|headers: HeaderMap, body: Bytes| async move {
+                    ([(CONTENT_TYPE, headers[CONTENT_TYPE].clone())], body)
+                }),
+            )
+            .route(
+                "/reject",
+                post(|| async {
+                    let body = json!({ "error": "card declined" });
+                    (StatusCode::UNPROCESSABLE_ENTITY, axum::Json(body))
+                }),
+            )
+            .layer(RequestLogLayer::new(config))
+    }
+
+    async fn post_body(app: Router, uri: &str, content_type: &str, body: &str) -> Bytes {
+        let request = Request::post(uri)
+            .header(CONTENT_TYPE, content_type)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_bodies_truncated_at_the_cap() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        let long = "a".repeat(100);
+        let app = body_router(RequestLogBody::Always, 16);
+        let echoed = post_body(app, "/echo", "text/plain", &long).await;
+
+        // The handler still reads the whole body
+        assert_eq!(echoed.len(), 100);
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 1);
+        let truncated = format!("{}…\"", "a".repeat(16));
+        assert!(
+            lines[0].contains(&format!("req_body=\"{truncated}")),
+            "{}",
+            lines[0]
+        );
+        assert!(
+            lines[0].contains(&format!("res_body=\"{truncated}")),
+            "{}",
+            lines[0]
+        );
+        assert!(!lines[0].contains(&"a".repeat(17)), "{}", lines[0]);
+    }
+
+    #[tokio::test]
+    async fn test_json_keys_are_redacted() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        let body = concat!(
+            r#"{"user":"ada","Password":"hunter2","#,
+            r#""card":{"card_number":"4111 1111"},"token":null}"#
+        );
+        let app = body_router(RequestLogBody::Always, 2048);
+        post_body(app, "/echo", "application/json", body).await;
+
+        let line = &capture.lines()[0];
+        assert!(line.contains(r#"\"user\":\"ada\""#), "{line}");
+        assert!(line.contains(r#"\"Password\":\"***\""#), "{line}");
+        assert!(line.contains(r#"{\"card_number\":\"***\"}"#), "{line}");
+        assert!(line.contains(r#"\"token\":\"***\"}"#), "{line}");
+        assert!(
+            !line.contains("hunter2") && !line.contains("4111"),
+            "{line}"
+        );
+    }
+
+    #[test]
+    fn test_redaction_survives_truncation() {
+        let keys = vec!["password".to_string(), "token".to_string()];
+        assert_eq!(
+            redact_json(r#"{"name":"x","token":"abc"#, &keys),
+            r#"{"name":"x","token":"***""#
+        );
+        assert_eq!(
+            redact_json(r#"{"password" : {"old":"a","new":["b"]}, "n":1}"#, &keys),
+            r#"{"password" : "***", "n":1}"#
+        );
+        // A value equal to a key name is not a key
+        assert_eq!(redact_json(r#"["token","x"]"#, &keys), r#"["token","x"]"#);
+    }
+
+    #[tokio::test]
+    async fn test_on_error_logs_only_failed_requests() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        let app = body_router(RequestLogBody::OnError, 2048);
+        post_body(app.clone(), "/echo", "application/json", r#"{"ok":true}"#).await;
+        post_body(app, "/reject", "application/json", r#"{"amount":5}"#).await;
+
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(!lines[0].contains("req_body") && !lines[0].contains("res_body"));
+        assert!(
+            lines[1].contains(r#"req_body="{\"amount\":5}""#),
+            "{}",
+            lines[1]
+        );
+        assert!(
+            lines[1].contains(r#"res_body="{\"error\":\"card declined\"}""#),
+            "{}",
+            lines[1]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_binary_bodies_are_skipped() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        let app = body_router(RequestLogBody::Always, 2048);
+        post_body(app, "/echo", "application/octet-stream", "raw-bytes").await;
+
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 1);
+        assert!(!lines[0].contains("req_body") && !lines[0].contains("raw-bytes"));
+    }
+
+    #[tokio::test]
+    async fn test_body_logging_off_in_prod_unless_forced() {
+        let capture = LogCapture::new();
+        let _guard = capture.set_default();
+
+        let mut config = test_config();
+        config.app.app_env = crate::config::Environment::Prod;
+        config.logging.request_log_body = RequestLogBody::Always;
+        post_body(body_router_with(&config), "/echo", "text/plain", "hello").await;
+
+        config.logging.request_log_body_force = true;
+        post_body(body_router_with(&config), "/echo", "text/plain", "hello").await;
+
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 2);
+        assert!(!lines[0].contains("req_body"), "{}", lines[0]);
+        assert!(lines[1].contains("req_body=\"hello\""), "{}", lines[1]);
+    }
+}
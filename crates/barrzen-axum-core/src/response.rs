@@ -3,12 +3,13 @@
 //! Provides consistent JSON envelope responses for API endpoints.
 
 use axum::{
-    http::{HeaderMap, StatusCode},
-    response::{IntoResponse, Response},
     Json,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
 };
 use chrono::{DateTime, Utc};
 use serde::Serialize;
+use std::collections::BTreeMap;
 
 /// Standard API response wrapper
 ///
@@ -27,6 +28,9 @@ pub struct ApiResponse<T: Serialize> {
     /// Request ID for tracing
     #[serde(skip_serializing_if = "Option::is_none")]
     pub request_id: Option<String>,
+    /// Server-side handling time in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
     /// Response data payload
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<T>,
@@ -42,6 +46,7 @@ impl<T: Serialize> ApiResponse<T> {
             timestamp: Utc::now(),
             request_id: None,
             message: message.into(),
+            duration_ms: None,
             data: Some(data),
         }
     }
@@ -55,11 +60,15 @@ impl<T: Serialize> ApiResponse<T> {
             timestamp: Utc::now(),
             request_id: None,
             message: message.into(),
+            duration_ms: None,
             data: Some(data),
         }
     }
 
     /// Set the request ID
+    ///
+    /// Only needed outside the built app: inside it `into_response` fills
+    /// in the id of the current request when none is set.
     #[must_use]
     pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
         self.request_id = Some(request_id.into());
@@ -75,13 +84,20 @@ impl<T: Serialize> ApiResponse<T> {
             timestamp: Utc::now(),
             request_id: None,
             message: message.into(),
+            duration_ms: None,
             data: Some(data),
         }
     }
 }
 
 impl<T: Serialize> IntoResponse for ApiResponse<T> {
-    fn into_response(self) -> Response {
+    fn into_response(mut self) -> Response {
+        if self.duration_ms.is_none() {
+            self.duration_ms = crate::response_time::elapsed_ms();
+        }
+        if self.request_id.is_none() {
+            self.request_id = crate::request_context::current_request_id();
+        }
         let status = StatusCode::from_u16(self.code).unwrap_or(StatusCode::OK);
         (status, Json(self)).into_response()
     }
@@ -99,25 +115,41 @@ pub struct ApiError {
     pub code: u16,
     /// Human-readable error message
     pub message: String,
+    /// Stable machine-readable error code (e.g. `user_not_found`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
     /// ISO 8601 timestamp
     pub timestamp: DateTime<Utc>,
     /// Request ID for tracing
     #[serde(skip_serializing_if = "Option::is_none")]
     pub request_id: Option<String>,
+    /// Server-side handling time in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
     /// Optional error details
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<String>,
+    /// Field-level validation errors (field name -> messages)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub errors: Option<BTreeMap<String, Vec<String>>>,
+    /// Seconds sent in the `Retry-After` header (not serialized)
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
 }
 
 impl ApiError {
-    fn new(code: StatusCode, message: impl Into<String>) -> Self {
+    pub(crate) fn new(code: StatusCode, message: impl Into<String>) -> Self {
         Self {
             status: "error",
             code: code.as_u16(),
             timestamp: Utc::now(),
             request_id: None,
             message: message.into(),
+            error_code: None,
+            duration_ms: None,
             details: None,
+            errors: None,
+            retry_after: None,
         }
     }
 
@@ -145,19 +177,74 @@ impl ApiError {
         Self::new(StatusCode::NOT_FOUND, message)
     }
 
+    /// Create a conflict error (409)
+    #[must_use]
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::CONFLICT, message)
+    }
+
+    /// Create a payload too large error (413)
+    #[must_use]
+    pub fn payload_too_large(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::PAYLOAD_TOO_LARGE, message)
+    }
+
+    /// Create an unprocessable entity error (422)
+    #[must_use]
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, message)
+    }
+
+    /// Create a too many requests error (429)
+    ///
+    /// Combine with [`ApiError::with_retry_after`] to emit `Retry-After`.
+    #[must_use]
+    pub fn too_many_requests(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::TOO_MANY_REQUESTS, message)
+    }
+
     /// Create an internal server error (500)
     #[must_use]
     pub fn internal(message: impl Into<String>) -> Self {
         Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
     }
 
+    /// Create a bad gateway error (502)
+    #[must_use]
+    pub fn bad_gateway(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_GATEWAY, message)
+    }
+
     /// Create a service unavailable error (503)
     #[must_use]
     pub fn service_unavailable(message: impl Into<String>) -> Self {
         Self::new(StatusCode::SERVICE_UNAVAILABLE, message)
     }
 
+    /// Create a gateway timeout error (504)
+    #[must_use]
+    pub fn gateway_timeout(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::GATEWAY_TIMEOUT, message)
+    }
+
+    /// Set the machine-readable error code
+    #[must_use]
+    pub fn with_code(mut self, error_code: impl Into<String>) -> Self {
+        self.error_code = Some(error_code.into());
+        self
+    }
+
+    /// Set the `Retry-After` header value in seconds
+    #[must_use]
+    pub fn with_retry_after(mut self, seconds: u64) -> Self {
+        self.retry_after = Some(seconds);
+        self
+    }
+
     /// Set the request ID
+    ///
+    /// Only needed outside the built app: inside it `into_response` fills
+    /// in the id of the current request when none is set.
     #[must_use]
     pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
         self.request_id = Some(request_id.into());
@@ -170,28 +257,54 @@ impl ApiError {
         self.details = Some(details.into());
         self
     }
+
+    /// Set field-level errors
+    #[must_use]
+    pub fn with_errors(mut self, errors: BTreeMap<String, Vec<String>>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
 }
 
 impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
+    fn into_response(mut self) -> Response {
+        if self.duration_ms.is_none() {
+            self.duration_ms = crate::response_time::elapsed_ms();
+        }
+        if self.request_id.is_none() {
+            self.request_id = crate::request_context::current_request_id();
+        }
         let status = StatusCode::from_u16(self.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-        (status, Json(self)).into_response()
+        let retry_after = self.retry_after;
+        let mut response = (status, Json(self)).into_response();
+        if let Some(seconds) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
     }
 }
 
 /// Result type for API handlers
 pub type ApiResult<T> = Result<ApiResponse<T>, ApiError>;
 
-/// Header name for request ID
+/// Default header name for request ID (`REQUEST_ID_HEADER_NAME` overrides it)
 pub const REQUEST_ID_HEADER: &str = "x-request-id";
 
-/// Helper to extract request ID from headers
+/// Helper to extract request ID
+///
+/// Inside the built app this is the id of the current request, whatever
+/// `REQUEST_ID_HEADER_NAME` is; elsewhere it falls back to the default
+/// header.
 #[must_use]
 pub fn extract_request_id(headers: &HeaderMap) -> Option<String> {
-    headers
-        .get(REQUEST_ID_HEADER)
-        .and_then(|v| v.to_str().ok())
-        .map(String::from)
+    crate::request_context::current_request_id().or_else(|| {
+        headers
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    })
 }
 
 #[cfg(test)]
@@ -220,5 +333,49 @@ mod tests {
         assert!(json.contains("\"status\":\"success\""));
         assert!(json.contains("\"code\":200"));
         assert!(json.contains("\"timestamp\":"));
+        assert!(!json.contains("duration_ms"));
+    }
+
+    #[test]
+    fn test_api_error_constructors() {
+        let cases = [
+            (ApiError::conflict("x"), StatusCode::CONFLICT),
+            (
+                ApiError::unprocessable("x"),
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                ApiError::too_many_requests("x"),
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
+            (ApiError::bad_gateway("x"), StatusCode::BAD_GATEWAY),
+            (ApiError::gateway_timeout("x"), StatusCode::GATEWAY_TIMEOUT),
+        ];
+
+        for (error, status) in cases {
+            assert_eq!(error.code, status.as_u16());
+            assert_eq!(error.into_response().status(), status);
+        }
+    }
+
+    #[test]
+    fn test_api_error_code_serialization() {
+        let json = serde_json::to_value(ApiError::not_found("missing")).unwrap();
+        assert!(json.get("error_code").is_none());
+
+        let json = serde_json::to_value(ApiError::not_found("missing").with_code("user_not_found"))
+            .unwrap();
+        assert_eq!(json["error_code"], "user_not_found");
+    }
+
+    #[test]
+    fn test_retry_after_header() {
+        let response = ApiError::too_many_requests("slow down")
+            .with_retry_after(30)
+            .into_response();
+        assert_eq!(response.headers()[header::RETRY_AFTER], "30");
+
+        let response = ApiError::too_many_requests("slow down").into_response();
+        assert!(!response.headers().contains_key(header::RETRY_AFTER));
     }
 }
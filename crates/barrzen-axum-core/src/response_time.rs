@@ -0,0 +1,129 @@
+//! Response time middleware
+//!
+//! Sets an `x-response-time` header on every response and makes the elapsed
+//! time available to the response envelope as `duration_ms`.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use axum::http::{HeaderName, HeaderValue, Request, Response};
+use tower::{Layer, Service};
+
+/// Header name for the server-side response time
+pub static RESPONSE_TIME_HEADER: HeaderName = HeaderName::from_static("x-response-time");
+
+tokio::task_local! {
+    static REQUEST_STARTED: Instant;
+}
+
+/// Milliseconds elapsed since the current request entered the response time layer
+///
+/// Returns `None` outside of a request handled by the built app.
+pub(crate) fn elapsed_ms() -> Option<u64> {
+    REQUEST_STARTED
+        .try_with(|started| u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX))
+        .ok()
+}
+
+/// Layer that measures request handling time
+#[derive(Clone, Copy)]
+pub(crate) struct ResponseTimeLayer {
+    expose_in_body: bool,
+}
+
+impl ResponseTimeLayer {
+    /// `expose_in_body` makes the timing visible to envelope serialization
+    pub(crate) fn new(expose_in_body: bool) -> Self {
+        Self { expose_in_body }
+    }
+}
+
+impl<S> Layer<S> for ResponseTimeLayer {
+    type Service = ResponseTimeService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ResponseTimeService {
+            inner,
+            expose_in_body: self.expose_in_body,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ResponseTimeService<S> {
+    inner: S,
+    expose_in_body: bool,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for ResponseTimeService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let mut inner = self.inner.clone();
+        let expose_in_body = self.expose_in_body;
+        let start = Instant::now();
+
+        Box::pin(async move {
+            let mut response = if expose_in_body {
+                REQUEST_STARTED
+                    .scope(start, async move { inner.call(req).await })
+                    .await?
+            } else {
+                inner.call(req).await?
+            };
+
+            let elapsed_ms = start.elapsed().as_millis();
+            if let Ok(value) = HeaderValue::from_str(&format!("{elapsed_ms}ms")) {
+                response
+                    .headers_mut()
+                    .insert(RESPONSE_TIME_HEADER.clone(), value);
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, routing::get};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_elapsed_ms_only_inside_scope() {
+        assert!(elapsed_ms().is_none());
+
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async { elapsed_ms().map(|ms| ms.to_string()).unwrap_or_default() }),
+            )
+            .layer(ResponseTimeLayer::new(true));
+
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+
+        assert!(String::from_utf8_lossy(&body).parse::<u64>().is_ok());
+    }
+}
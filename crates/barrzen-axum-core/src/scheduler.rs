@@ -0,0 +1,523 @@
+//! Cron-style scheduler for periodic jobs (`scheduler` feature)
+//!
+//! Jobs registered with [`AppBuilder::schedule`] run as background tasks:
+//! they start when serving begins, show up in /readyz as `task:<name>` and
+//! stop with the server. Expressions have six fields,
+//! `second minute hour day-of-month month day-of-week`, evaluated in UTC;
+//! five-field expressions run at second 0.
+//!
+//! [`AppBuilder::schedule`]: crate::AppBuilder::schedule
+
+use std::{fmt, future::Future, pin::Pin, str::FromStr, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Datelike, NaiveDate, TimeDelta, Timelike, Utc};
+use tokio::{task::JoinSet, time::Instant};
+
+use crate::tasks::CancellationToken;
+
+/// How far ahead to look for a matching time before giving up
+/// (e.g. `0 0 0 30 2 *`)
+const MAX_YEARS_AHEAD: i32 = 5;
+
+/// Error parsing a cron expression
+#[derive(Debug, thiserror::Error)]
+#[error("invalid cron expression {expression:?}: {reason}")]
+pub struct CronError {
+    expression: String,
+    reason: String,
+}
+
+/// Parsed cron expression
+#[derive(Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    expression: String,
+    seconds: u64,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// `*` day fields; otherwise either day field matching is enough
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl fmt::Debug for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CronSchedule")
+            .field(&self.expression)
+            .finish()
+    }
+}
+
+impl fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = CronError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let error = |reason: String| CronError {
+            expression: expression.to_string(),
+            reason,
+        };
+        let mut fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() == 5 {
+            fields.insert(0, "0");
+        }
+        let [second, minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(error(format!(
+                "expected 5 or 6 fields, got {}",
+                fields.len()
+            )));
+        };
+
+        let weekdays = parse_field(day_of_week, 0, 7, &WEEKDAYS).map_err(error)?;
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            seconds: parse_field(second, 0, 59, &[]).map_err(error)?,
+            minutes: parse_field(minute, 0, 59, &[]).map_err(error)?,
+            hours: parse_field(hour, 0, 23, &[]).map_err(error)?,
+            days_of_month: parse_field(day_of_month, 1, 31, &[]).map_err(error)?,
+            months: parse_field(month, 1, 12, &MONTHS).map_err(error)?,
+            // 7 is Sunday too
+            days_of_week: (weekdays | (weekdays >> 7)) & 0x7f,
+            any_day_of_month: is_wildcard(day_of_month),
+            any_day_of_week: is_wildcard(day_of_week),
+        })
+    }
+}
+
+impl CronSchedule {
+    /// Parse a cron expression
+    ///
+    /// # Errors
+    /// Returns [`CronError`] if a field is malformed or out of range.
+    pub fn parse(expression: &str) -> Result<Self, CronError> {
+        expression.parse()
+    }
+
+    /// First matching time strictly after `after`
+    ///
+    /// `None` if nothing matches within the next few years.
+    #[must_use]
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = after.with_nanosecond(0)? + TimeDelta::seconds(1);
+        let limit = start.year() + MAX_YEARS_AHEAD;
+        let mut t = start.naive_utc();
+
+        while t.year() <= limit {
+            if !has(self.months, t.month()) {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.day_matches(t.date()) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !has(self.hours, t.hour()) {
+                t = t.with_minute(0)?.with_second(0)? + TimeDelta::hours(1);
+            } else if !has(self.minutes, t.minute()) {
+                t = t.with_second(0)? + TimeDelta::minutes(1);
+            } else if !has(self.seconds, t.second()) {
+                t += TimeDelta::seconds(1);
+            } else {
+                return Some(t.and_utc());
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day_of_month = has(self.days_of_month, date.day());
+        let day_of_week = has(self.days_of_week, date.weekday().num_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+fn is_wildcard(field: &str) -> bool {
+    field == "*" || field == "?"
+}
+
+/// Bit set of the values a field matches
+///
+/// `names` map to `min`, `min + 1`, ... (months and weekdays).
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |part: &str| -> Result<u32, String> {
+        let lower = part.to_ascii_lowercase();
+        let parsed = names
+            .iter()
+            .position(|name| *name == lower)
+            .and_then(|index| u32::try_from(index).ok())
+            .map(|index| index + min)
+            .or_else(|| part.parse().ok())
+            .ok_or_else(|| format!("{part:?} is not a number"))?;
+        if (min..=max).contains(&parsed) {
+            Ok(parsed)
+        } else {
+            Err(format!("{parsed} is outside {min}-{max}"))
+        }
+    };
+
+    let mut set = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| format!("invalid step in {item:?}"))?;
+                (range, step)
+            }
+            None => (item, 1),
+        };
+        let (first, last) = match range {
+            "*" | "?" => (min, max),
+            _ => match range.split_once('-') {
+                Some((first, last)) => (value(first)?, value(last)?),
+                // `5/15` runs from 5 to the end of the range
+                None if step > 1 => (value(range)?, max),
+                None => {
+                    let single = value(range)?;
+                    (single, single)
+                }
+            },
+        };
+        if first > last {
+            return Err(format!("range {range:?} is reversed"));
+        }
+        for v in (first..=last).step_by(step as usize) {
+            set |= 1 << v;
+        }
+    }
+    Ok(set)
+}
+
+/// Options for [`AppBuilder::schedule_with`](crate::AppBuilder::schedule_with)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JobOptions {
+    /// Start a run even if the previous one is still going (default: skip
+    /// the tick)
+    pub allow_overlap: bool,
+    /// Random delay up to this long before each run, to spread load across
+    /// replicas
+    pub jitter: Duration,
+    /// Abort a run that takes longer than this
+    pub timeout: Option<Duration>,
+}
+
+type JobFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+type JobFn = Arc<dyn Fn() -> JobFuture + Send + Sync>;
+
+/// A job with its schedule, run by [`ScheduledJob::run`]
+#[derive(Clone)]
+pub(crate) struct ScheduledJob {
+    name: Arc<str>,
+    schedule: CronSchedule,
+    options: JobOptions,
+    job: JobFn,
+    #[cfg(feature = "otel")]
+    metrics: Option<JobMetrics>,
+}
+
+impl ScheduledJob {
+    pub(crate) fn new<F, Fut>(
+        name: &str,
+        schedule: CronSchedule,
+        options: JobOptions,
+        job: F,
+    ) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        Self {
+            name: name.into(),
+            schedule,
+            options,
+            job: Arc::new(move || Box::pin(job())),
+            #[cfg(feature = "otel")]
+            metrics: None,
+        }
+    }
+
+    /// Count runs in `scheduler.job.runs` and `scheduler.job.duration`
+    #[cfg(feature = "otel")]
+    pub(crate) fn with_metrics(mut self, meter: &opentelemetry::metrics::Meter) -> Self {
+        self.metrics = Some(JobMetrics::new(meter));
+        self
+    }
+
+    /// Run the job on its schedule until `shutdown` is cancelled, then wait
+    /// for in-flight runs
+    pub(crate) async fn run(self, shutdown: CancellationToken) {
+        let mut runs = JoinSet::new();
+        loop {
+            let Some(next) = self.schedule.next_after(Utc::now()) else {
+                tracing::warn!(job = %self.name, schedule = %self.schedule, "Schedule has no upcoming runs");
+                break;
+            };
+            let wait = (next - Utc::now()).to_std().unwrap_or_default() + self.jitter();
+            tokio::select! {
+                () = shutdown.cancelled() => break,
+                () = tokio::time::sleep(wait) => {}
+            }
+
+            while runs.try_join_next().is_some() {}
+            if !self.options.allow_overlap && !runs.is_empty() {
+                tracing::warn!(job = %self.name, "Skipping scheduled run, the previous one is still in progress");
+                self.record("skipped", None);
+                continue;
+            }
+            runs.spawn(self.clone().run_once());
+        }
+        while runs.join_next().await.is_some() {}
+    }
+
+    fn jitter(&self) -> Duration {
+        let max = u64::try_from(self.options.jitter.as_millis()).unwrap_or(u64::MAX);
+        if max == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_millis(fastrand::u64(0..max))
+        }
+    }
+
+    async fn run_once(self) {
+        let started = Instant::now();
+        let run = tokio::spawn((self.job)());
+        let result = match self.options.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, run).await.ok(),
+            None => Some(run.await),
+        };
+        let elapsed = started.elapsed();
+        let duration_ms = u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX);
+
+        let outcome = match result {
+            Some(Ok(Ok(()))) => {
+                tracing::info!(job = %self.name, outcome = "success", duration_ms, "Scheduled job finished");
+                "success"
+            }
+            Some(Ok(Err(e))) => {
+                tracing::error!(job = %self.name, outcome = "failure", duration_ms, error = %e, "Scheduled job failed");
+                "failure"
+            }
+            Some(Err(e)) => {
+                tracing::error!(job = %self.name, outcome = "panic", duration_ms, error = %e, "Scheduled job panicked");
+                "panic"
+            }
+            None => {
+                tracing::error!(job = %self.name, outcome = "timeout", duration_ms, "Scheduled job timed out");
+                "timeout"
+            }
+        };
+        self.record(outcome, Some(elapsed));
+    }
+
+    #[cfg_attr(not(feature = "otel"), allow(clippy::unused_self))]
+    fn record(&self, outcome: &'static str, elapsed: Option<Duration>) {
+        #[cfg(feature = "otel")]
+        if let Some(metrics) = &self.metrics {
+            metrics.record(&self.name, outcome, elapsed);
+        }
+        #[cfg(not(feature = "otel"))]
+        let _ = (outcome, elapsed);
+    }
+}
+
+/// `scheduler.job.runs` counter and `scheduler.job.duration` histogram
+#[cfg(feature = "otel")]
+#[derive(Clone)]
+struct JobMetrics {
+    runs: opentelemetry::metrics::Counter<u64>,
+    duration: opentelemetry::metrics::Histogram<f64>,
+}
+
+#[cfg(feature = "otel")]
+impl JobMetrics {
+    fn new(meter: &opentelemetry::metrics::Meter) -> Self {
+        Self {
+            runs: meter
+                .u64_counter("scheduler.job.runs")
+                .with_unit("{run}")
+                .with_description("Scheduled job runs by outcome")
+                .build(),
+            duration: meter
+                .f64_histogram("scheduler.job.duration")
+                .with_unit("s")
+                .with_description("Duration of scheduled job runs")
+                .build(),
+        }
+    }
+
+    fn record(&self, job: &str, outcome: &'static str, elapsed: Option<Duration>) {
+        let attributes = [
+            opentelemetry::KeyValue::new("job", job.to_string()),
+            opentelemetry::KeyValue::new("outcome", outcome),
+        ];
+        self.runs.add(1, &attributes);
+        if let Some(elapsed) = elapsed {
+            self.duration.record(elapsed.as_secs_f64(), &attributes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32, s: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap()
+    }
+
+    fn next(expression: &str, after: DateTime<Utc>) -> DateTime<Utc> {
+        CronSchedule::parse(expression)
+            .unwrap()
+            .next_after(after)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_next_after() {
+        let t = at(2026, 3, 14, 10, 7, 30);
+        assert_eq!(next("* * * * * *", t), at(2026, 3, 14, 10, 7, 31));
+        assert_eq!(next("0 */5 * * * *", t), at(2026, 3, 14, 10, 10, 0));
+        assert_eq!(next("*/5 * * * *", t), at(2026, 3, 14, 10, 10, 0));
+        assert_eq!(next("0 30 2 * * *", t), at(2026, 3, 15, 2, 30, 0));
+        assert_eq!(next("0 0 0 1 jan *", t), at(2027, 1, 1, 0, 0, 0));
+        // 2026-03-14 is a Saturday
+        assert_eq!(next("0 0 9 * * MON-FRI", t), at(2026, 3, 16, 9, 0, 0));
+        assert_eq!(next("0 0 0 * * 7", t), at(2026, 3, 15, 0, 0, 0));
+        // Restricted day-of-month and day-of-week match either
+        assert_eq!(next("0 0 0 20 * 1", t), at(2026, 3, 16, 0, 0, 0));
+        assert_eq!(next("0 0 12 29 2 *", t), at(2028, 2, 29, 12, 0, 0));
+        assert_eq!(next("15,45 10/20 * * * *", t), at(2026, 3, 14, 10, 10, 15));
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        for expression in [
+            "",
+            "* * * *",
+            "61 * * * * *",
+            "* * * * 13 *",
+            "*/0 * * * * *",
+            "5-1 * * * * *",
+            "x * * * * *",
+        ] {
+            assert!(CronSchedule::parse(expression).is_err(), "{expression}");
+        }
+        let error = CronSchedule::parse("0 0 25 * * *").unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "invalid cron expression \"0 0 25 * * *\": 25 is outside 0-23"
+        );
+        assert!(
+            CronSchedule::parse("0 0 0 30 2 *")
+                .unwrap()
+                .next_after(Utc::now())
+                .is_none()
+        );
+    }
+
+    /// Job recording how many runs were in flight at once
+    fn tracked_job(
+        schedule: &str,
+        options: JobOptions,
+        work: Duration,
+    ) -> (ScheduledJob, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let active = Arc::new(AtomicUsize::new(0));
+        let (r, p) = (runs.clone(), peak.clone());
+        let job = ScheduledJob::new("tracked", schedule.parse().unwrap(), options, move || {
+            let (runs, peak, active) = (r.clone(), p.clone(), active.clone());
+            async move {
+                runs.fetch_add(1, Ordering::SeqCst);
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(work).await;
+                active.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            }
+        });
+        (job, runs, peak)
+    }
+
+    async fn run_for(job: ScheduledJob, duration: Duration) {
+        let shutdown = CancellationToken::new();
+        let runner = tokio::spawn(job.run(shutdown.clone()));
+        tokio::time::sleep(duration).await;
+        shutdown.cancel();
+        runner.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_runs_every_tick_and_waits_on_shutdown() {
+        let (job, runs, _) = tracked_job(
+            "* * * * * *",
+            JobOptions::default(),
+            Duration::from_millis(300),
+        );
+        let started = std::time::Instant::now();
+        run_for(job, Duration::from_millis(2500)).await;
+
+        assert!(runs.load(Ordering::SeqCst) >= 2);
+        // The run started in the last tick was awaited, not dropped
+        assert!(started.elapsed() >= Duration::from_millis(2500));
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_ticks_are_skipped() {
+        let (job, runs, peak) = tracked_job(
+            "* * * * * *",
+            JobOptions::default(),
+            Duration::from_millis(1500),
+        );
+        run_for(job, Duration::from_millis(3200)).await;
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+        assert!(runs.load(Ordering::SeqCst) <= 2, "{runs:?}");
+
+        let options = JobOptions {
+            allow_overlap: true,
+            ..JobOptions::default()
+        };
+        let (job, _, peak) = tracked_job("* * * * * *", options, Duration::from_millis(1500));
+        run_for(job, Duration::from_millis(3200)).await;
+        assert!(peak.load(Ordering::SeqCst) >= 2);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_aborts_the_run() {
+        let options = JobOptions {
+            timeout: Some(Duration::from_millis(100)),
+            ..JobOptions::default()
+        };
+        let (job, runs, _) = tracked_job("* * * * * *", options, Duration::from_mins(1));
+        let started = std::time::Instant::now();
+        run_for(job, Duration::from_millis(1500)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+}
@@ -0,0 +1,491 @@
+//! Coalescing of identical in-flight `GET` requests
+//!
+//! [`SingleFlightLayer`] lets the first of concurrent `GET` requests with the
+//! same path, query and vary headers through and parks the rest until it is
+//! answered; they then get a copy of its response, error statuses included.
+//! When a cache entry expires, the backend sees one request instead of
+//! hundreds:
+//!
+//! ```ignore
+//! let single_flight = SingleFlightLayer::new();
+//! let catalog = Router::new()
+//!     .route("/catalog/{category}", get(list_products))
+//!     .layer(single_flight.clone());
+//! ```
+//!
+//! Responses over [`SingleFlightLayer::max_body_bytes`] or setting cookies
+//! are not shared: the parked requests then run on their own.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Write as _},
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::Request,
+    http::{HeaderMap, HeaderName, Method, StatusCode, Version, header},
+    response::Response,
+};
+use futures_util::StreamExt;
+use http_body::Body as _;
+use tokio::sync::watch;
+use tower::{Layer, Service};
+
+/// Largest response body shared by default
+const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Layer coalescing concurrent identical `GET` requests
+///
+/// Requests vary by the `Authorization` and `Cookie` headers by default, so
+/// callers never get a response made for someone else. Clones share the
+/// in-flight requests and the counters.
+#[derive(Clone)]
+pub struct SingleFlightLayer {
+    settings: Arc<Settings>,
+    state: Arc<State>,
+}
+
+#[derive(Clone)]
+struct Settings {
+    vary_headers: Vec<HeaderName>,
+    max_body_bytes: usize,
+}
+
+/// In-flight requests and counters shared by the clones of a layer
+struct State {
+    /// Outcome of the request in flight, by key
+    flights: Mutex<HashMap<String, watch::Sender<Option<Outcome>>>>,
+    executed: AtomicU64,
+    coalesced: AtomicU64,
+    bypassed: AtomicU64,
+}
+
+/// What the first request hands to the parked ones
+#[derive(Clone)]
+enum Outcome {
+    Shared(Arc<SharedResponse>),
+    /// Not shareable; the parked requests run on their own
+    Bypass,
+}
+
+struct SharedResponse {
+    status: StatusCode,
+    version: Version,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl SharedResponse {
+    fn to_response(&self) -> Response {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.version_mut() = self.version;
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
+}
+
+/// Counts of a [`SingleFlightLayer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SingleFlightStats {
+    /// `GET` requests that reached the handler
+    pub executed: u64,
+    /// Requests answered with the response of another one
+    pub coalesced: u64,
+    /// Responses that could not be shared (too large or setting cookies)
+    pub bypassed: u64,
+}
+
+impl fmt::Debug for SingleFlightLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SingleFlightLayer")
+            .field("vary_headers", &self.settings.vary_headers)
+            .field("max_body_bytes", &self.settings.max_body_bytes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for SingleFlightLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SingleFlightLayer {
+    /// Layer sharing bodies up to 1 MiB, varying by `Authorization` and
+    /// `Cookie`
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            settings: Arc::new(Settings {
+                vary_headers: vec![header::AUTHORIZATION, header::COOKIE],
+                max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            }),
+            state: Arc::new(State {
+                flights: Mutex::new(HashMap::new()),
+                executed: AtomicU64::new(0),
+                coalesced: AtomicU64::new(0),
+                bypassed: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Headers whose values must match too, replacing the defaults
+    #[must_use]
+    pub fn vary_headers(mut self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        Arc::make_mut(&mut self.settings).vary_headers = headers.into_iter().collect();
+        self
+    }
+
+    /// Largest response body shared; larger ones are sent to the first
+    /// request only
+    #[must_use]
+    pub fn max_body_bytes(mut self, max_body_bytes: usize) -> Self {
+        Arc::make_mut(&mut self.settings).max_body_bytes = max_body_bytes;
+        self
+    }
+
+    #[must_use]
+    pub fn stats(&self) -> SingleFlightStats {
+        SingleFlightStats {
+            executed: self.state.executed.load(Ordering::Relaxed),
+            coalesced: self.state.coalesced.load(Ordering::Relaxed),
+            bypassed: self.state.bypassed.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Export the counts as the `http.server.single_flight.requests`
+    /// observable counter, labeled with `single_flight.result`
+    #[cfg(feature = "otel")]
+    #[must_use]
+    pub fn with_meter(self, meter: &opentelemetry::metrics::Meter) -> Self {
+        let layer = self.clone();
+        meter
+            .u64_observable_counter("http.server.single_flight.requests")
+            .with_unit("{request}")
+            .with_description("GET requests by single-flight outcome")
+            .with_callback(move |observer| {
+                let stats = layer.stats();
+                for (result, value) in [
+                    ("executed", stats.executed),
+                    ("coalesced", stats.coalesced),
+                    ("bypassed", stats.bypassed),
+                ] {
+                    observer.observe(
+                        value,
+                        &[opentelemetry::KeyValue::new("single_flight.result", result)],
+                    );
+                }
+            })
+            .build();
+        self
+    }
+}
+
+impl Settings {
+    /// Method, path, query and vary header values
+    fn key(&self, req: &Request) -> String {
+        let mut key = format!("{} {}", req.method(), req.uri());
+        for name in &self.vary_headers {
+            for value in req.headers().get_all(name) {
+                let _ = write!(
+                    key,
+                    "\n{name}: {}",
+                    String::from_utf8_lossy(value.as_bytes())
+                );
+            }
+        }
+        key
+    }
+}
+
+impl State {
+    /// Lead the flight of `key`, or wait for the outcome of its leader
+    fn join(self: &Arc<Self>, key: String) -> Result<Flight, watch::Receiver<Option<Outcome>>> {
+        let mut flights = self.flights.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(flight) = flights.get(&key) {
+            return Err(flight.subscribe());
+        }
+        flights.insert(key.clone(), watch::channel(None).0);
+        Ok(Flight {
+            state: Arc::clone(self),
+            key: Some(key),
+        })
+    }
+
+    /// End the flight of `key`; waiters see `outcome`, or run on their own
+    /// when `None`
+    fn land(&self, key: &str, outcome: Option<Outcome>) {
+        let flight = self
+            .flights
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key);
+        if let (Some(flight), Some(outcome)) = (flight, outcome) {
+            let _ = flight.send(Some(outcome));
+        }
+    }
+}
+
+/// Leadership of a flight; waiters run on their own if it is dropped early
+struct Flight {
+    state: Arc<State>,
+    key: Option<String>,
+}
+
+impl Flight {
+    fn finish(mut self, outcome: Outcome) {
+        if let Some(key) = self.key.take() {
+            self.state.land(&key, Some(outcome));
+        }
+    }
+}
+
+impl Drop for Flight {
+    fn drop(&mut self) {
+        if let Some(key) = self.key.take() {
+            self.state.land(&key, None);
+        }
+    }
+}
+
+impl<S> Layer<S> for SingleFlightLayer {
+    type Service = SingleFlightService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SingleFlightService {
+            inner,
+            settings: self.settings.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SingleFlightService<S> {
+    inner: S,
+    settings: Arc<Settings>,
+    state: Arc<State>,
+}
+
+impl<S> Service<Request> for SingleFlightService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let mut inner = self.inner.clone();
+        if req.method() != Method::GET {
+            return Box::pin(async move { inner.call(req).await });
+        }
+        let key = self.settings.key(&req);
+        let max_body_bytes = self.settings.max_body_bytes;
+        let state = self.state.clone();
+
+        Box::pin(async move {
+            let flight = match state.join(key) {
+                Ok(flight) => flight,
+                Err(mut waiting) => {
+                    let outcome = waiting
+                        .wait_for(Option::is_some)
+                        .await
+                        .ok()
+                        .and_then(|o| o.clone());
+                    if let Some(Outcome::Shared(response)) = outcome {
+                        state.coalesced.fetch_add(1, Ordering::Relaxed);
+                        return Ok(response.to_response());
+                    }
+                    state.executed.fetch_add(1, Ordering::Relaxed);
+                    return inner.call(req).await;
+                }
+            };
+
+            state.executed.fetch_add(1, Ordering::Relaxed);
+            let response = inner.call(req).await?;
+            let (parts, body) = response.into_parts();
+            let body = if parts.headers.contains_key(header::SET_COOKIE) {
+                Err(body)
+            } else {
+                buffer(body, max_body_bytes).await
+            };
+            match body {
+                Ok(bytes) => {
+                    flight.finish(Outcome::Shared(Arc::new(SharedResponse {
+                        status: parts.status,
+                        version: parts.version,
+                        headers: parts.headers.clone(),
+                        body: bytes.clone(),
+                    })));
+                    Ok(Response::from_parts(parts, Body::from(bytes)))
+                }
+                Err(body) => {
+                    state.bypassed.fetch_add(1, Ordering::Relaxed);
+                    flight.finish(Outcome::Bypass);
+                    Ok(Response::from_parts(parts, body))
+                }
+            }
+        })
+    }
+}
+
+/// Whole body when it ends within `max_bytes`, else a body replaying what
+/// was read followed by the rest
+async fn buffer(body: Body, max_bytes: usize) -> Result<Bytes, Body> {
+    let lower = usize::try_from(body.size_hint().lower()).unwrap_or(usize::MAX);
+    if lower > max_bytes {
+        return Err(body);
+    }
+    let mut stream = body.into_data_stream();
+    let mut buffered = Vec::new();
+    let mut chunks = Vec::new();
+    loop {
+        match stream.next().await {
+            Some(Ok(chunk)) => {
+                buffered.extend_from_slice(&chunk);
+                chunks.push(Ok(chunk));
+                if buffered.len() > max_bytes {
+                    break;
+                }
+            }
+            Some(Err(error)) => {
+                chunks.push(Err(error));
+                break;
+            }
+            None => return Ok(Bytes::from(buffered)),
+        }
+    }
+    Err(Body::from_stream(
+        futures_util::stream::iter(chunks).chain(stream),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, routing::get};
+    use std::{sync::atomic::AtomicUsize, time::Duration};
+    use tower::ServiceExt;
+
+    /// App whose handler takes 50ms and answers `status` with `body`
+    fn app(
+        layer: &SingleFlightLayer,
+        status: StatusCode,
+        body: &'static str,
+    ) -> (Router, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        let router = Router::new()
+            .route(
+                "/items",
+                get(move || {
+                    let counter = Arc::clone(&counter);
+                    async move {
+                        let call = counter.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        (status, format!("{body} {call}"))
+                    }
+                }),
+            )
+            .layer(layer.clone());
+        (router, calls)
+    }
+
+    /// Send `count` identical requests at once, returning statuses and bodies
+    async fn fire(app: &Router, count: usize, uri: &str) -> Vec<(StatusCode, String)> {
+        let requests = (0..count).map(|_| {
+            let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+            async move {
+                let response = app.clone().oneshot(request).await.unwrap();
+                let status = response.status();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .unwrap();
+                (status, String::from_utf8(body.to_vec()).unwrap())
+            }
+        });
+        futures_util::future::join_all(requests).await
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_requests_run_once() {
+        let layer = SingleFlightLayer::new();
+        let (app, calls) = app(&layer, StatusCode::OK, "items");
+
+        let responses = fire(&app, 20, "/items?page=1").await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(
+            responses
+                .iter()
+                .all(|r| *r == (StatusCode::OK, "items 0".to_string()))
+        );
+        let stats = layer.stats();
+        assert_eq!((stats.executed, stats.coalesced), (1, 19));
+
+        // A later request starts a new flight
+        let responses = fire(&app, 1, "/items?page=1").await;
+        assert_eq!(responses[0].1, "items 1");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_error_responses_fan_out() {
+        let layer = SingleFlightLayer::new();
+        let (app, calls) = app(&layer, StatusCode::BAD_GATEWAY, "upstream down");
+
+        let responses = fire(&app, 5, "/items").await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(
+            responses
+                .iter()
+                .all(|(status, _)| *status == StatusCode::BAD_GATEWAY)
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_large_responses_bypass() {
+        let layer = SingleFlightLayer::new().max_body_bytes(4);
+        let (app, calls) = app(&layer, StatusCode::OK, "items");
+
+        let responses = fire(&app, 3, "/items").await;
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(
+            responses
+                .iter()
+                .all(|(status, _)| *status == StatusCode::OK)
+        );
+        let stats = layer.stats();
+        assert_eq!((stats.executed, stats.coalesced, stats.bypassed), (3, 0, 1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_vary_headers_split_flights() {
+        let layer = SingleFlightLayer::new();
+        let (app, calls) = app(&layer, StatusCode::OK, "items");
+
+        let requests = ["alice", "bob"].map(|user| {
+            let request = Request::builder()
+                .uri("/items")
+                .header(header::AUTHORIZATION, format!("Bearer {user}"))
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request)
+        });
+        futures_util::future::join_all(requests).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
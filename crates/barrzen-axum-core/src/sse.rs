@@ -0,0 +1,344 @@
+//! Server-sent events responses
+//!
+//! [`ApiSse`] turns a stream of [`SseEvent`]s into an axum SSE response with
+//! keep-alive comments every `SSE_KEEPALIVE_SECONDS`. Inside the built app
+//! the stream ends when shutdown starts, so open connections do not hold the
+//! grace period, and clients reconnect to another instance.
+//!
+//! Return `Result<ApiSse, ApiError>` from the handler: errors found before
+//! the stream starts get the usual envelope. An error yielded by the stream
+//! is sent as a final `error` event carrying the same envelope.
+
+use std::{
+    convert::Infallible,
+    fmt::Display,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::{
+    http::{HeaderName, HeaderValue},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+};
+use futures_util::{Stream, StreamExt, stream::BoxStream};
+use serde::Serialize;
+use tokio_util::sync::WaitForCancellationFutureOwned;
+
+use crate::{app_context::AppContext, request_context::current_request_id, response::ApiError};
+
+/// Keep-alive interval outside the built app
+const DEFAULT_KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// One event of an [`ApiSse`] stream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// `id` field, sent back by browsers as `Last-Event-ID` on reconnect
+    pub id: Option<String>,
+    /// `event` field, the listener name on the client (`message` when unset)
+    pub event: Option<String>,
+    /// `data` field; newlines are sent as several `data` lines
+    pub data: String,
+}
+
+impl SseEvent {
+    /// Event with `data` and no id or name
+    #[must_use]
+    pub fn new(data: impl Into<String>) -> Self {
+        Self {
+            data: data.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Event with `value` serialized as JSON data
+    ///
+    /// # Errors
+    /// Returns error if `value` cannot be serialized.
+    pub fn json<T: Serialize + ?Sized>(value: &T) -> Result<Self, serde_json::Error> {
+        serde_json::to_string(value).map(Self::new)
+    }
+
+    /// Set the `id` field
+    #[must_use]
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Set the `event` field
+    #[must_use]
+    pub fn with_event(mut self, event: impl Into<String>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    fn into_event(self) -> Event {
+        let mut event = Event::default().data(self.data);
+        // axum panics on line breaks in these fields
+        if let Some(id) = self.id {
+            event = event.id(single_line(&id));
+        }
+        if let Some(name) = self.event {
+            event = event.event(single_line(&name));
+        }
+        event
+    }
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n', '\0'], "")
+}
+
+/// Server-sent events response
+///
+/// ```rust,ignore
+/// async fn progress(Path(id): Path<u64>) -> Result<ApiSse, ApiError> {
+///     let job = jobs::find(id).await.ok_or_else(|| ApiError::not_found("No such job"))?;
+///     Ok(ApiSse::from_stream(job.updates().map(|update| {
+///         SseEvent::json(&update).map(|event| event.with_event("progress"))
+///     })))
+/// }
+/// ```
+pub struct ApiSse {
+    events: BoxStream<'static, Result<SseEvent, String>>,
+    keep_alive: Option<Duration>,
+}
+
+impl ApiSse {
+    /// Response streaming `stream`, ending with an `error` event on its first
+    /// error
+    pub fn from_stream<St, E>(stream: St) -> Self
+    where
+        St: Stream<Item = Result<SseEvent, E>> + Send + 'static,
+        E: Display,
+    {
+        Self {
+            events: stream
+                .map(|item| item.map_err(|error| error.to_string()))
+                .boxed(),
+            keep_alive: None,
+        }
+    }
+
+    /// Response streaming events that cannot fail
+    pub fn from_events<St>(stream: St) -> Self
+    where
+        St: Stream<Item = SseEvent> + Send + 'static,
+    {
+        Self::from_stream(stream.map(Ok::<_, Infallible>))
+    }
+
+    /// Override `SSE_KEEPALIVE_SECONDS` for this response (zero disables)
+    #[must_use]
+    pub fn keep_alive(mut self, interval: Duration) -> Self {
+        self.keep_alive = Some(interval);
+        self
+    }
+}
+
+impl IntoResponse for ApiSse {
+    fn into_response(self) -> Response {
+        // Read while still inside the request's scope
+        let context = AppContext::current();
+        let interval = self.keep_alive.unwrap_or_else(|| {
+            context.as_ref().map_or(DEFAULT_KEEPALIVE, |context| {
+                Duration::from_secs(context.config.sse.sse_keepalive_seconds)
+            })
+        });
+        let events = Events {
+            inner: self.events,
+            shutdown: context.map(|context| Box::pin(context.shutdown.cancelled_owned())),
+            request_id: current_request_id(),
+            done: false,
+        };
+
+        let sse = Sse::new(events);
+        let mut response = if interval.is_zero() {
+            sse.into_response()
+        } else {
+            sse.keep_alive(KeepAlive::new().interval(interval).text("keep-alive"))
+                .into_response()
+        };
+        // Reverse proxies such as nginx would otherwise buffer the stream
+        response.headers_mut().insert(
+            HeaderName::from_static("x-accel-buffering"),
+            HeaderValue::from_static("no"),
+        );
+        response
+    }
+}
+
+/// Events of the response, ending on shutdown or after the first error
+struct Events {
+    inner: BoxStream<'static, Result<SseEvent, String>>,
+    shutdown: Option<Pin<Box<WaitForCancellationFutureOwned>>>,
+    request_id: Option<String>,
+    done: bool,
+}
+
+impl Events {
+    fn error_event(&self, message: String) -> Event {
+        let mut error = ApiError::internal(message).with_code("stream_error");
+        if let Some(request_id) = &self.request_id {
+            error = error.with_request_id(request_id.clone());
+        }
+        Event::default()
+            .event("error")
+            .data(serde_json::to_string(&error).unwrap_or_default())
+    }
+}
+
+impl Stream for Events {
+    type Item = Result<Event, Infallible>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        if let Some(shutdown) = &mut this.shutdown
+            && shutdown.as_mut().poll(cx).is_ready()
+        {
+            this.done = true;
+            return Poll::Ready(None);
+        }
+
+        match this.inner.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(event))) => Poll::Ready(Some(Ok(event.into_event()))),
+            Poll::Ready(Some(Err(message))) => {
+                tracing::warn!(error = %message, "SSE stream failed");
+                this.done = true;
+                Poll::Ready(Some(Ok(this.error_event(message))))
+            }
+            Poll::Ready(None) => {
+                this.done = true;
+                Poll::Ready(None)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        body::Body,
+        http::{Request, header},
+    };
+    use futures_util::stream;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{AppBuilder, BuildInfo, tasks::CancellationToken, test_support::test_config};
+
+    async fn body_text(response: Response) -> String {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_events_and_keep_alive_through_the_app() {
+        let mut config = test_config();
+        config.sse.sse_keepalive_seconds = 1;
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let app = AppBuilder::new(config, build)
+            .route(
+                "/progress",
+                axum::routing::get(|| async {
+                    let first = SseEvent::json(&serde_json::json!({ "percent": 50 }))
+                        .unwrap()
+                        .with_id("1")
+                        .with_event("progress");
+                    let last = stream::once(async {
+                        tokio::time::sleep(Duration::from_millis(2500)).await;
+                        SseEvent::new("done").with_id("2").with_event("complete")
+                    });
+                    ApiSse::from_events(stream::iter([first]).chain(last))
+                }),
+            )
+            .build();
+
+        let response = app
+            .oneshot(
+                Request::get("/progress")
+                    .header(header::ACCEPT_ENCODING, "gzip")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        assert_eq!(response.headers()["x-accel-buffering"], "no");
+        assert!(!response.headers().contains_key(header::CONTENT_ENCODING));
+
+        let text = body_text(response).await;
+        assert!(text.contains("id: 1\n"), "{text}");
+        assert!(text.contains("event: progress\n"), "{text}");
+        assert!(text.contains("data: {\"percent\":50}\n"), "{text}");
+        assert_eq!(text.matches("keep-alive").count(), 2, "{text}");
+        assert!(text.contains("event: complete\n"), "{text}");
+    }
+
+    #[tokio::test]
+    async fn test_stream_error_ends_with_error_event() {
+        let events = stream::iter([
+            Ok(SseEvent::new("first")),
+            Err("upstream closed"),
+            Ok(SseEvent::new("never sent")),
+        ]);
+        let response = ApiSse::from_stream(events).into_response();
+
+        let text = body_text(response).await;
+        assert!(text.contains("data: first\n"), "{text}");
+        assert!(text.contains("event: error\n"), "{text}");
+        assert!(text.contains("\"message\":\"upstream closed\""), "{text}");
+        assert!(text.contains("\"error_code\":\"stream_error\""), "{text}");
+        assert!(!text.contains("never sent"), "{text}");
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_ends_the_stream() {
+        let shutdown = CancellationToken::new();
+        let context = AppContext::new(Arc::new(test_config()), shutdown.clone());
+        let response = context
+            .scope(async {
+                let events = stream::iter([SseEvent::new("hello")]).chain(stream::pending());
+                ApiSse::from_events(events).into_response()
+            })
+            .await;
+
+        let mut body = response.into_body();
+        let first = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert!(first.starts_with(b"data: hello\n"), "{first:?}");
+
+        // The pending stream would otherwise keep the response open
+        shutdown.cancel();
+        let rest = tokio::time::timeout(Duration::from_secs(5), body.frame())
+            .await
+            .unwrap();
+        assert!(rest.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_line_breaks_are_dropped_from_fields() {
+        let event = SseEvent::new("a\nb")
+            .with_id("1\n2")
+            .with_event("pro\rgress");
+        let text = body_text(ApiSse::from_events(stream::iter([event])).into_response()).await;
+        assert!(text.contains("id: 12\n"), "{text}");
+        assert!(text.contains("event: progress\n"), "{text}");
+        assert!(text.contains("data: a\ndata: b\n"), "{text}");
+    }
+}
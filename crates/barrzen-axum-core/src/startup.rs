@@ -0,0 +1,167 @@
+//! Readiness gate before binding (`STARTUP_REQUIRE_READY`)
+//!
+//! Services that cannot do anything useful without their dependencies are
+//! better off crashing than answering 503s. The ready checks run in a loop
+//! with exponential backoff until every critical check passes or
+//! `STARTUP_READY_TIMEOUT_SECONDS` elapses.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use tokio::time::Instant;
+
+use crate::{
+    banner,
+    config::Config,
+    handlers::{HealthCheck, ReadyChecker},
+};
+
+/// Run the checks of `checker` until the critical ones pass
+///
+/// Logs how long each dependency took to become ready.
+///
+/// # Errors
+/// Returns error listing the failing checks when the timeout elapses.
+pub(crate) async fn wait_until_ready(
+    checker: &dyn ReadyChecker,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let startup = &config.startup;
+    let timeout = Duration::from_secs(startup.startup_ready_timeout_seconds);
+    let max_delay = Duration::from_millis(startup.startup_retry_max_ms);
+    let mut delay = Duration::from_millis(startup.startup_retry_base_ms).min(max_delay);
+    let started = Instant::now();
+    let mut ready_after: BTreeMap<String, Duration> = BTreeMap::new();
+
+    loop {
+        let remaining = timeout.saturating_sub(started.elapsed());
+        let checks = tokio::time::timeout(remaining, checker.ready_checks())
+            .await
+            .unwrap_or_else(|_| vec![HealthCheck::fail("ready_checks", "did not answer in time")]);
+        let elapsed = started.elapsed();
+
+        let failing: Vec<&HealthCheck> = checks.iter().filter(|check| is_failing(check)).collect();
+        for check in checks.iter().filter(|check| !is_failing(check)) {
+            ready_after.entry(check.name.clone()).or_insert(elapsed);
+        }
+        if failing.is_empty() {
+            for (name, after) in &ready_after {
+                tracing::info!(
+                    dependency = %name,
+                    elapsed_ms = u64::try_from(after.as_millis()).unwrap_or(u64::MAX),
+                    "Dependency {name} ready after {after:.1?}"
+                );
+            }
+            return Ok(());
+        }
+
+        if elapsed >= timeout {
+            let failing: Vec<String> = failing.iter().map(|check| describe(check)).collect();
+            anyhow::bail!(
+                "Dependencies not ready after {}s (STARTUP_REQUIRE_READY): {}",
+                timeout.as_secs(),
+                failing.join(", ")
+            );
+        }
+
+        let names: Vec<&str> = failing.iter().map(|check| check.name.as_str()).collect();
+        banner::print_waiting(config, elapsed, &names);
+        tokio::time::sleep(delay.min(timeout.saturating_sub(elapsed))).await;
+        delay = (delay * 2).min(max_delay);
+    }
+}
+
+/// A failing critical check keeps the service unready
+fn is_failing(check: &HealthCheck) -> bool {
+    check.critical && check.status == "fail"
+}
+
+/// `name (message)` of a failing check
+fn describe(check: &HealthCheck) -> String {
+    match &check.message {
+        Some(message) => format!("{} ({message})", check.name),
+        None => check.name.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::test_support::test_config;
+
+    /// Fails until the given attempt, then passes
+    struct ReadyAfter {
+        attempts: AtomicUsize,
+        ready_on: usize,
+    }
+
+    #[async_trait::async_trait]
+    impl ReadyChecker for ReadyAfter {
+        async fn ready_checks(&self) -> Vec<HealthCheck> {
+            let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+            let database = if attempt >= self.ready_on {
+                HealthCheck::ok("database")
+            } else {
+                HealthCheck::fail("database", "connection refused")
+            };
+            vec![database, HealthCheck::ok("cache")]
+        }
+    }
+
+    fn gated_config() -> Config {
+        let mut config = test_config();
+        config.startup.startup_require_ready = true;
+        config.startup.startup_ready_timeout_seconds = 5;
+        config.startup.startup_retry_base_ms = 100;
+        config.startup.startup_retry_max_ms = 1000;
+        config
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_waits_until_ready() {
+        let checker = ReadyAfter {
+            attempts: AtomicUsize::new(0),
+            ready_on: 3,
+        };
+        let started = Instant::now();
+        wait_until_ready(&checker, &gated_config()).await.unwrap();
+
+        // Two failed attempts, backing off 100ms then 200ms
+        assert_eq!(checker.attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(started.elapsed(), Duration::from_millis(300));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_times_out_listing_failing_checks() {
+        let checker = ReadyAfter {
+            attempts: AtomicUsize::new(0),
+            ready_on: usize::MAX,
+        };
+        let started = Instant::now();
+        let error = wait_until_ready(&checker, &gated_config())
+            .await
+            .unwrap_err();
+
+        assert_eq!(started.elapsed(), Duration::from_secs(5));
+        assert_eq!(
+            error.to_string(),
+            "Dependencies not ready after 5s (STARTUP_REQUIRE_READY): \
+             database (connection refused)"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_non_critical_failures_do_not_block() {
+        struct Degraded;
+
+        #[async_trait::async_trait]
+        impl ReadyChecker for Degraded {
+            async fn ready_checks(&self) -> Vec<HealthCheck> {
+                vec![HealthCheck::fail("cache", "down").non_critical()]
+            }
+        }
+
+        wait_until_ready(&Degraded, &gated_config()).await.unwrap();
+    }
+}
@@ -0,0 +1,311 @@
+//! Static file and SPA serving (`static-files` feature)
+//!
+//! [`AppBuilder::serve_static`](crate::AppBuilder::serve_static) mounts a
+//! directory with tower-http's `ServeDir`. Unknown paths can fall back to
+//! `index.html` for browser navigations (GET/HEAD preferring `text/html`);
+//! everything else, such as a `fetch` from the SPA itself, gets a JSON 404
+//! envelope.
+
+use std::{convert::Infallible, path::Path, sync::Arc};
+
+use axum::{
+    Router,
+    body::Body,
+    extract::Request,
+    http::{HeaderMap, HeaderValue, Method, header},
+    response::{IntoResponse, Response},
+};
+use tower::ServiceExt;
+use tower_http::services::{ServeDir, ServeFile};
+
+use crate::response::{ApiError, extract_request_id};
+
+/// Options for [`AppBuilder::serve_static`](crate::AppBuilder::serve_static)
+#[derive(Debug, Clone)]
+pub struct StaticOptions {
+    /// URL prefix the directory is served under, e.g. `/admin` (`/` for the
+    /// root)
+    pub prefix: String,
+    /// Answer unknown paths with `index.html` when the client prefers HTML
+    pub spa_fallback: bool,
+    /// Serve `.br`/`.gz` siblings of a file to clients accepting them
+    pub precompressed: bool,
+    /// `Cache-Control` of hashed assets such as `app-3f9a1c2e.js`
+    pub asset_cache_control: String,
+    /// `Cache-Control` of HTML files, including the SPA fallback
+    pub html_cache_control: String,
+}
+
+impl Default for StaticOptions {
+    fn default() -> Self {
+        Self {
+            prefix: "/".to_string(),
+            spa_fallback: false,
+            precompressed: false,
+            asset_cache_control: "public, max-age=31536000, immutable".to_string(),
+            html_cache_control: "no-cache".to_string(),
+        }
+    }
+}
+
+/// Router serving `dir` under `options.prefix`
+///
+/// At `/` the files are the router's fallback, so explicit routes of other
+/// stateless routers still win.
+pub(crate) fn static_router(dir: &Path, options: StaticOptions) -> Router {
+    let options = Arc::new(options);
+    let index = Arc::new(dir.join("index.html"));
+    let fallback = tower::service_fn({
+        let options = options.clone();
+        move |req: Request| {
+            let options = options.clone();
+            let index = index.clone();
+            async move { Ok::<_, Infallible>(not_found(req, &index, &options).await) }
+        }
+    });
+
+    let mut serve_dir = ServeDir::new(dir);
+    if options.precompressed {
+        serve_dir = serve_dir.precompressed_br().precompressed_gzip();
+    }
+    let serve_dir = serve_dir.fallback(fallback);
+
+    let prefix = options.prefix.trim_end_matches('/').to_string();
+    let service = tower::service_fn(move |req: Request| {
+        let serve_dir = serve_dir.clone();
+        let options = options.clone();
+        async move {
+            let hashed = is_hashed_asset(req.uri().path());
+            let Ok(response) = serve_dir.oneshot(req).await;
+            let mut response = response.map(Body::new);
+            if response.status().is_success() {
+                set_cache_control(&mut response, hashed, &options);
+            }
+            Ok::<_, Infallible>(response)
+        }
+    });
+
+    if prefix.is_empty() {
+        Router::new().fallback_service(service)
+    } else {
+        Router::new().nest_service(&prefix, service)
+    }
+}
+
+/// SPA fallback for HTML navigations, JSON 404 otherwise
+async fn not_found(req: Request, index: &Path, options: &StaticOptions) -> Response {
+    let navigation =
+        matches!(*req.method(), Method::GET | Method::HEAD) && prefers_html(req.headers());
+    if options.spa_fallback && navigation {
+        let mut serve_index = ServeFile::new(index);
+        if options.precompressed {
+            serve_index = serve_index.precompressed_br().precompressed_gzip();
+        }
+        let Ok(response) = serve_index.oneshot(req).await;
+        let mut response = response.map(Body::new);
+        if response.status().is_success() {
+            set_cache_control(&mut response, false, options);
+        }
+        return response;
+    }
+
+    let mut error = ApiError::not_found("Not found");
+    if let Some(request_id) = extract_request_id(req.headers()) {
+        error = error.with_request_id(request_id);
+    }
+    error.into_response()
+}
+
+fn set_cache_control(response: &mut Response, hashed: bool, options: &StaticOptions) {
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    let value = if is_html {
+        &options.html_cache_control
+    } else if hashed {
+        &options.asset_cache_control
+    } else {
+        return;
+    };
+    if let Ok(value) = HeaderValue::from_str(value) {
+        response.headers_mut().insert(header::CACHE_CONTROL, value);
+    }
+}
+
+/// Whether the `Accept` header ranks `text/html` at least as high as any
+/// other listed type; `*/*` alone (e.g. `fetch`) does not count
+fn prefers_html(headers: &HeaderMap) -> bool {
+    let Some(accept) = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()) else {
+        return false;
+    };
+    let mut html = None::<f32>;
+    let mut best_other = 0.0_f32;
+    for entry in accept.split(',') {
+        let mut params = entry.split(';');
+        let media_type = params
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let quality = params
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        match media_type.as_str() {
+            "text/html" => html = Some(quality),
+            "*/*" => {}
+            _ => best_other = best_other.max(quality),
+        }
+    }
+    html.is_some_and(|q| q > 0.0 && q >= best_other)
+}
+
+/// Whether the file name carries a content hash, as bundlers emit them:
+/// `app.3f9a1c2e.js`, `index-B8xYz12a.js`
+///
+/// The segment before the extension must be at least 8 letters or digits
+/// and contain a digit.
+fn is_hashed_asset(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let Some((stem, _extension)) = name.rsplit_once('.') else {
+        return false;
+    };
+    let segment = stem.rsplit(['.', '-']).next().unwrap_or_default();
+    segment.len() >= 8
+        && segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && segment.chars().any(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::StatusCode;
+
+    use super::*;
+    use crate::{AppBuilder, BuildInfo, test_support::test_config};
+
+    const BROWSER_ACCEPT: &str = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+    fn site() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("assets")).unwrap();
+        std::fs::write(dir.path().join("index.html"), "<html>spa</html>").unwrap();
+        std::fs::write(dir.path().join("assets/app-3f9a1c2e.js"), "console.log(1)").unwrap();
+        std::fs::write(dir.path().join("robots.txt"), "User-agent: *").unwrap();
+        std::fs::write(dir.path().join("assets/app-3f9a1c2e.js.gz"), b"gzipped").unwrap();
+        dir
+    }
+
+    fn app(dir: &Path, prefix: &str) -> Router {
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        AppBuilder::new(test_config(), build)
+            .merge_stateless(Router::new().route("/docs", axum::routing::get(|| async { "docs" })))
+            .serve_static(
+                dir,
+                StaticOptions {
+                    prefix: prefix.to_string(),
+                    spa_fallback: true,
+                    precompressed: true,
+                    ..StaticOptions::default()
+                },
+            )
+            .build()
+    }
+
+    async fn get(app: &Router, path: &str, headers: &[(header::HeaderName, &str)]) -> Response {
+        let mut request = Request::get(path);
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        app.clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn text(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serves_assets_with_cache_headers() {
+        let dir = site();
+        let app = app(dir.path(), "/admin");
+
+        let response = get(&app, "/admin/assets/app-3f9a1c2e.js", &[]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=31536000, immutable"
+        );
+        assert_eq!(text(response).await, "console.log(1)");
+
+        let response = get(&app, "/admin/robots.txt", &[]).await;
+        assert!(!response.headers().contains_key(header::CACHE_CONTROL));
+
+        let response = get(&app, "/admin/", &[]).await;
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+        assert_eq!(text(response).await, "<html>spa</html>");
+
+        let response = get(
+            &app,
+            "/admin/assets/app-3f9a1c2e.js",
+            &[(header::ACCEPT_ENCODING, "gzip")],
+        )
+        .await;
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_eq!(text(response).await, "gzipped");
+
+        // Other stateless routes keep working
+        assert_eq!(text(get(&app, "/docs", &[]).await).await, "docs");
+    }
+
+    #[tokio::test]
+    async fn test_spa_fallback_only_for_html_navigations() {
+        let dir = site();
+        let app = app(dir.path(), "/");
+
+        let response = get(
+            &app,
+            "/settings/profile",
+            &[(header::ACCEPT, BROWSER_ACCEPT)],
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+        assert_eq!(text(response).await, "<html>spa</html>");
+
+        for accept in ["application/json", "*/*"] {
+            let response = get(
+                &app,
+                "/api/missing",
+                &[
+                    (header::ACCEPT, accept),
+                    (header::HeaderName::from_static("x-request-id"), "r-1"),
+                ],
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{accept}");
+            let json: serde_json::Value = serde_json::from_str(&text(response).await).unwrap();
+            assert_eq!(json["status"], "error");
+            assert_eq!(json["request_id"], "r-1");
+        }
+
+        assert_eq!(text(get(&app, "/docs", &[]).await).await, "docs");
+        assert_eq!(get(&app, "/healthz", &[]).await.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_hashed_asset_names() {
+        assert!(is_hashed_asset("/assets/index-B8xYz12a.js"));
+        assert!(is_hashed_asset("/main.3f9a1c2e.css"));
+        assert!(!is_hashed_asset("/assets/component.js"));
+        assert!(!is_hashed_asset("/favicon.ico"));
+    }
+}
@@ -0,0 +1,381 @@
+//! Background tasks tied to the server lifecycle
+//!
+//! Tasks registered with [`AppBuilder::spawn_task`] start when serving
+//! begins and receive a [`CancellationToken`] that is cancelled once the
+//! shutdown signal fires. After the server stops they get
+//! `APP_SHUTDOWN_GRACE_SECONDS` to return before being abandoned. A panic is
+//! logged with the task name and, with [`TaskOptions::restart`], the task is
+//! started again. Each task is reported in /readyz as `task:<name>`.
+//!
+//! [`AppBuilder::spawn_task`]: crate::AppBuilder::spawn_task
+
+use std::{
+    any::Any,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::task::JoinHandle;
+pub use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::ReadinessConfig,
+    handlers::{HealthCheck, ReadyChecker},
+};
+
+/// Pause before restarting a panicked task, so a task failing on start
+/// does not spin
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+type TaskFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type TaskFn = Arc<dyn Fn(CancellationToken) -> TaskFuture + Send + Sync>;
+
+/// Options for [`AppBuilder::spawn_task_with`](crate::AppBuilder::spawn_task_with)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TaskOptions {
+    /// Start the task again after a panic (until shutdown)
+    pub restart: bool,
+}
+
+struct TaskSpec {
+    name: String,
+    options: TaskOptions,
+    run: TaskFn,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TaskState {
+    Pending,
+    Running,
+    Finished,
+    Stopped,
+    Panicked(String),
+}
+
+struct TaskEntry {
+    name: String,
+    state: TaskState,
+    restarts: u32,
+}
+
+/// Shared state of every task, read by [`TaskHealthChecker`]
+#[derive(Clone, Default)]
+struct TaskRegistry(Arc<Mutex<Vec<TaskEntry>>>);
+
+impl TaskRegistry {
+    fn update(&self, index: usize, update: impl FnOnce(&mut TaskEntry)) {
+        if let Ok(mut entries) = self.0.lock()
+            && let Some(entry) = entries.get_mut(index)
+        {
+            update(entry);
+        }
+    }
+}
+
+/// Tasks registered on the builder, not yet started
+#[derive(Default)]
+pub(crate) struct BackgroundTasks {
+    specs: Vec<TaskSpec>,
+    registry: TaskRegistry,
+}
+
+impl BackgroundTasks {
+    pub(crate) fn push<F, Fut>(&mut self, name: String, options: TaskOptions, task: F)
+    where
+        F: Fn(CancellationToken) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        if let Ok(mut entries) = self.registry.0.lock() {
+            entries.push(TaskEntry {
+                name: name.clone(),
+                state: TaskState::Pending,
+                restarts: 0,
+            });
+        }
+        self.specs.push(TaskSpec {
+            name,
+            options,
+            run: Arc::new(move |token| Box::pin(task(token))),
+        });
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+
+    /// Readiness checks for these tasks; `READYZ_CRITICAL_COMPONENTS` decides
+    /// whether a panicked task makes the service unready
+    pub(crate) fn health_checker(&self, readiness: &ReadinessConfig) -> TaskHealthChecker {
+        TaskHealthChecker {
+            registry: self.registry.clone(),
+            readiness: readiness.clone(),
+        }
+    }
+
+    /// Spawn every task with a fresh cancellation token
+    pub(crate) fn start(self) -> RunningTasks {
+        let token = CancellationToken::new();
+        let handles = self
+            .specs
+            .into_iter()
+            .enumerate()
+            .map(|(index, spec)| {
+                let name = spec.name.clone();
+                let supervisor = supervise(index, spec, self.registry.clone(), token.clone());
+                (name, tokio::spawn(supervisor))
+            })
+            .collect();
+        RunningTasks { token, handles }
+    }
+}
+
+/// Started tasks and the token that stops them
+pub(crate) struct RunningTasks {
+    token: CancellationToken,
+    handles: Vec<(String, JoinHandle<()>)>,
+}
+
+impl RunningTasks {
+    /// Token cancelled when the shutdown signal fires
+    pub(crate) fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Cancel the tasks and wait up to `grace` for them to return
+    ///
+    /// Tasks still running afterwards are aborted and logged.
+    pub(crate) async fn shutdown(self, grace: Duration) {
+        self.token.cancel();
+        let deadline = tokio::time::Instant::now() + grace;
+        for (name, mut handle) in self.handles {
+            if tokio::time::timeout_at(deadline, &mut handle)
+                .await
+                .is_err()
+            {
+                tracing::warn!(
+                    task = name,
+                    "Background task did not stop within the grace period of {}s, abandoning it",
+                    grace.as_secs()
+                );
+                handle.abort();
+            }
+        }
+    }
+}
+
+/// Aborts the wrapped task when dropped, so abandoning a supervisor also
+/// stops the task it runs
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Run one task until it returns, restarting it after panics if asked to
+async fn supervise(index: usize, spec: TaskSpec, registry: TaskRegistry, token: CancellationToken) {
+    loop {
+        registry.update(index, |entry| entry.state = TaskState::Running);
+        let mut task = AbortOnDrop(tokio::spawn((spec.run)(token.clone())));
+        let panic = match (&mut task.0).await {
+            Ok(()) => None,
+            Err(e) if e.is_panic() => Some(panic_message(&*e.into_panic())),
+            // Aborted from outside; nothing left to supervise
+            Err(_) => return,
+        };
+
+        let Some(message) = panic else {
+            let state = if token.is_cancelled() {
+                TaskState::Stopped
+            } else {
+                TaskState::Finished
+            };
+            registry.update(index, |entry| entry.state = state);
+            tracing::info!(task = spec.name, "Background task finished");
+            return;
+        };
+
+        if !spec.options.restart || token.is_cancelled() {
+            tracing::error!(
+                task = spec.name,
+                panic = message,
+                "Background task panicked"
+            );
+            registry.update(index, |entry| entry.state = TaskState::Panicked(message));
+            return;
+        }
+
+        tracing::error!(
+            task = spec.name,
+            panic = message,
+            "Background task panicked, restarting in {}s",
+            RESTART_DELAY.as_secs()
+        );
+        registry.update(index, |entry| {
+            entry.state = TaskState::Panicked(message);
+            entry.restarts += 1;
+        });
+        tokio::select! {
+            () = token.cancelled() => {
+                registry.update(index, |entry| entry.state = TaskState::Stopped);
+                return;
+            }
+            () = tokio::time::sleep(RESTART_DELAY) => {}
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(ToString::to_string)
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Reports every background task in /readyz
+pub(crate) struct TaskHealthChecker {
+    registry: TaskRegistry,
+    readiness: ReadinessConfig,
+}
+
+#[async_trait::async_trait]
+impl ReadyChecker for TaskHealthChecker {
+    async fn ready_checks(&self) -> Vec<HealthCheck> {
+        let Ok(entries) = self.registry.0.lock() else {
+            return vec![HealthCheck::fail("tasks", "task registry poisoned")];
+        };
+        entries
+            .iter()
+            .map(|entry| {
+                let name = format!("task:{}", entry.name);
+                let check = match (&entry.state, entry.restarts) {
+                    (TaskState::Pending, _) => HealthCheck::skip(name.clone(), "not started"),
+                    (TaskState::Running, 0) => HealthCheck::ok(name.clone()),
+                    (TaskState::Running, restarts) => HealthCheck::warn(
+                        name.clone(),
+                        format!("restarted {restarts} time(s) after panics"),
+                    ),
+                    (TaskState::Finished, _) => HealthCheck::skip(name.clone(), "finished"),
+                    (TaskState::Stopped, _) => HealthCheck::skip(name.clone(), "stopped"),
+                    (TaskState::Panicked(message), _) => {
+                        HealthCheck::fail(name.clone(), format!("panicked: {message}"))
+                    }
+                };
+                check.with_critical(self.readiness.is_critical(&name))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn statuses(checks: &[HealthCheck]) -> Vec<(&str, &str)> {
+        checks
+            .iter()
+            .map(|check| (check.name.as_str(), check.status.as_str()))
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_panicking_task_is_restarted() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let mut tasks = BackgroundTasks::default();
+        let counter = runs.clone();
+        tasks.push(
+            "consumer".to_string(),
+            TaskOptions { restart: true },
+            move |token| {
+                let run = counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    assert!(run > 0, "first run fails");
+                    token.cancelled().await;
+                }
+            },
+        );
+        let checker = tasks.health_checker(&ReadinessConfig::default());
+        assert_eq!(
+            statuses(&checker.ready_checks().await),
+            vec![("task:consumer", "skip")]
+        );
+
+        let running = tasks.start();
+        tokio::time::sleep(RESTART_DELAY * 2).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        let checks = checker.ready_checks().await;
+        assert_eq!(statuses(&checks), vec![("task:consumer", "warn")]);
+
+        running.shutdown(Duration::from_secs(1)).await;
+        assert_eq!(
+            statuses(&checker.ready_checks().await),
+            vec![("task:consumer", "skip")]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_panicked_task_fails_readiness() {
+        let mut tasks = BackgroundTasks::default();
+        tasks.push(
+            "warmer".to_string(),
+            TaskOptions::default(),
+            |_token| async {
+                panic!("cache unreachable");
+            },
+        );
+        tasks.push(
+            "one-shot".to_string(),
+            TaskOptions::default(),
+            |_token| async {},
+        );
+        let checker = tasks.health_checker(&ReadinessConfig::default());
+
+        let running = tasks.start();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let checks = checker.ready_checks().await;
+        assert_eq!(
+            statuses(&checks),
+            vec![("task:warmer", "fail"), ("task:one-shot", "skip")]
+        );
+        assert_eq!(
+            checks[0].message.as_deref(),
+            Some("panicked: cache unreachable")
+        );
+        assert!(checks[0].critical);
+        running.shutdown(Duration::from_secs(1)).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stuck_task_is_abandoned_after_grace() {
+        let mut tasks = BackgroundTasks::default();
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let guard = dropped.clone();
+        tasks.push("stuck".to_string(), TaskOptions::default(), move |_token| {
+            struct Dropped(Arc<AtomicUsize>);
+            impl Drop for Dropped {
+                fn drop(&mut self) {
+                    self.0.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+            let dropped = Dropped(guard.clone());
+            async move {
+                let _dropped = dropped;
+                std::future::pending::<()>().await;
+            }
+        });
+
+        let running = tasks.start();
+        tokio::task::yield_now().await;
+        let started = tokio::time::Instant::now();
+        running.shutdown(Duration::from_secs(2)).await;
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+        tokio::task::yield_now().await;
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+}
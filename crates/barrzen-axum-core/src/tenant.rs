@@ -0,0 +1,332 @@
+//! Tenant of the request being handled
+//!
+//! With `FEATURE_TENANT` the built app reads the tenant id of requests to
+//! application routes (not the core endpoints) from `TENANT_HEADER` (default
+//! `x-tenant-id`), validates it and runs the request inside a scope holding
+//! it. Handlers extract it as [`TenantId`]; code without access to the
+//! request (repositories, broker messages) reads it with [`current_tenant`].
+//! The id is also recorded on the request span and in the request log.
+//!
+//! An id is at most 128 visible ASCII characters and, when configured, must
+//! match the whole of `TENANT_PATTERN` and be one of `TENANT_ALLOWLIST`.
+
+use std::{
+    collections::HashSet,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    extract::{FromRequestParts, OptionalFromRequestParts},
+    http::{HeaderName, Request, request::Parts},
+    response::{IntoResponse, Response},
+};
+use regex::Regex;
+use tower::{Layer, Service};
+
+use crate::{
+    config::TenantConfig,
+    request_context::current_request_id,
+    response::{ApiError, extract_request_id},
+};
+
+/// Longest tenant id accepted
+const MAX_TENANT_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static TENANT: TenantId;
+}
+
+/// Tenant of the current request
+///
+/// Returns `None` outside of a request with a tenant, including tasks
+/// spawned from a handler.
+#[must_use]
+pub fn current_tenant() -> Option<TenantId> {
+    TENANT.try_with(Clone::clone).ok()
+}
+
+/// Validated tenant id of a request
+///
+/// Extract it in handlers with `tenant: TenantId`, which answers 400 when the
+/// request has none, or `tenant: Option<TenantId>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TenantId(pub String);
+
+impl TenantId {
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for TenantId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<S> FromRequestParts<S> for TenantId
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(tenant) = parts.extensions.get::<Self>() {
+            return Ok(tenant.clone());
+        }
+        Err(with_request_id(tenant_required(), &parts.headers))
+    }
+}
+
+impl<S> OptionalFromRequestParts<S> for TenantId
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        Ok(parts.extensions.get::<Self>().cloned())
+    }
+}
+
+/// Header, format and requirement shared by the services of the layer
+struct TenantRules {
+    header: HeaderName,
+    pattern: Option<Regex>,
+    allowlist: HashSet<String>,
+    required: bool,
+}
+
+impl TenantRules {
+    fn is_valid(&self, tenant: &str) -> bool {
+        !tenant.is_empty()
+            && tenant.len() <= MAX_TENANT_LENGTH
+            && tenant.bytes().all(|b| b.is_ascii_graphic())
+            && self
+                .pattern
+                .as_ref()
+                .is_none_or(|pattern| pattern.is_match(tenant))
+            && (self.allowlist.is_empty() || self.allowlist.contains(tenant))
+    }
+}
+
+/// Layer resolving and scoping the tenant of each request
+///
+/// A valid tenant is stored as a [`TenantId`] request and response extension
+/// and recorded as the `tenant` field of the current span. Invalid ids are
+/// answered with 400 `invalid_tenant`, missing ones with 400
+/// `tenant_required` unless `TENANT_REQUIRED=false`.
+#[derive(Clone)]
+pub struct TenantLayer {
+    rules: Arc<TenantRules>,
+}
+
+impl TenantLayer {
+    /// Build the layer from the `TENANT_*` settings
+    ///
+    /// # Errors
+    /// Returns error if `TENANT_HEADER` is not a valid header name or
+    /// `TENANT_PATTERN` is not a valid regular expression.
+    pub fn from_config(config: &TenantConfig) -> anyhow::Result<Self> {
+        let header = HeaderName::from_bytes(config.tenant_header.trim().as_bytes())
+            .map_err(|_| anyhow::anyhow!("Invalid TENANT_HEADER: {:?}", config.tenant_header))?;
+        let pattern = config
+            .tenant_pattern
+            .as_deref()
+            .map(|pattern| Regex::new(&format!("^(?:{pattern})$")))
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("Invalid TENANT_PATTERN: {e}"))?;
+
+        Ok(Self {
+            rules: Arc::new(TenantRules {
+                header,
+                pattern,
+                allowlist: config.allowlist().into_iter().collect(),
+                required: config.tenant_required,
+            }),
+        })
+    }
+}
+
+impl<S> Layer<S> for TenantLayer {
+    type Service = TenantService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TenantService {
+            inner,
+            rules: self.rules.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct TenantService<S> {
+    inner: S,
+    rules: Arc<TenantRules>,
+}
+
+impl<S, B> Service<Request<B>> for TenantService<S>
+where
+    S: Service<Request<B>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let tenant = match req.headers().get(&self.rules.header).map(|v| v.to_str()) {
+            None if self.rules.required => {
+                let error = with_request_id(tenant_required(), req.headers());
+                return Box::pin(async move { Ok(error.into_response()) });
+            }
+            None => {
+                let mut inner = self.inner.clone();
+                return Box::pin(async move { inner.call(req).await });
+            }
+            Some(Ok(tenant)) if self.rules.is_valid(tenant) => TenantId(tenant.to_string()),
+            Some(_) => {
+                tracing::debug!(header = %self.rules.header, "Rejecting an invalid tenant id");
+                let error = ApiError::bad_request("Invalid tenant id").with_code("invalid_tenant");
+                let error = with_request_id(error, req.headers());
+                return Box::pin(async move { Ok(error.into_response()) });
+            }
+        };
+
+        tracing::Span::current().record("tenant", tenant.as_str());
+        req.extensions_mut().insert(tenant.clone());
+
+        // Inner layers read the tenant while building their future too
+        let future = TENANT.sync_scope(tenant.clone(), || self.inner.call(req));
+
+        Box::pin(async move {
+            let mut response = TENANT.scope(tenant.clone(), future).await?;
+            response.extensions_mut().insert(tenant);
+            Ok(response)
+        })
+    }
+}
+
+fn tenant_required() -> ApiError {
+    ApiError::bad_request("Tenant id is required").with_code("tenant_required")
+}
+
+fn with_request_id(error: ApiError, headers: &axum::http::HeaderMap) -> ApiError {
+    match current_request_id().or_else(|| extract_request_id(headers)) {
+        Some(request_id) => error.with_request_id(request_id),
+        None => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, http::StatusCode, routing::get};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    fn app(config: &TenantConfig) -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|tenant: Option<TenantId>| async move {
+                    let scoped = current_tenant().map(|t| t.0).unwrap_or_default();
+                    format!("{}|{scoped}", tenant.map(|t| t.0).unwrap_or_default())
+                }),
+            )
+            .route("/strict", get(|tenant: TenantId| async move { tenant.0 }))
+            .layer(TenantLayer::from_config(config).unwrap())
+    }
+
+    async fn call(app: Router, uri: &str, tenant: Option<&str>) -> (StatusCode, String) {
+        let mut request = Request::builder().uri(uri);
+        if let Some(tenant) = tenant {
+            request = request.header("x-tenant-id", tenant);
+        }
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    fn error_code(body: &str) -> Value {
+        serde_json::from_str::<Value>(body).unwrap()["error_code"].clone()
+    }
+
+    #[tokio::test]
+    async fn test_required_tenant() {
+        let config = TenantConfig::default();
+
+        let (status, body) = call(app(&config), "/", Some("acme")).await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "acme|acme"));
+
+        let (status, body) = call(app(&config), "/", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(&body), "tenant_required");
+    }
+
+    #[tokio::test]
+    async fn test_optional_tenant() {
+        let config = TenantConfig {
+            tenant_required: false,
+            ..TenantConfig::default()
+        };
+
+        let (status, body) = call(app(&config), "/", None).await;
+        assert_eq!((status, body.as_str()), (StatusCode::OK, "|"));
+
+        let (status, body) = call(app(&config), "/strict", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error_code(&body), "tenant_required");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_tenant() {
+        let config = TenantConfig {
+            tenant_pattern: Some("[a-z]+".to_string()),
+            tenant_allowlist: Some("acme, globex".to_string()),
+            ..TenantConfig::default()
+        };
+
+        let (status, _) = call(app(&config), "/strict", Some("globex")).await;
+        assert_eq!(status, StatusCode::OK);
+        for tenant in ["initech", "acme1", "ac me", ""] {
+            let (status, body) = call(app(&config), "/strict", Some(tenant)).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{tenant:?}");
+            assert_eq!(error_code(&body), "invalid_tenant", "{tenant:?}");
+        }
+
+        let long = "a".repeat(MAX_TENANT_LENGTH + 1);
+        let config = TenantConfig::default();
+        let (status, _) = call(app(&config), "/strict", Some(&long)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        let config = TenantConfig {
+            tenant_pattern: Some("[a-z".to_string()),
+            ..TenantConfig::default()
+        };
+        let error = TenantLayer::from_config(&config).err().unwrap();
+        assert!(error.to_string().contains("TENANT_PATTERN"), "{error}");
+    }
+}
@@ -0,0 +1,55 @@
+//! Shared helpers for unit tests
+
+use crate::config::{CacheBackend, Config, Environment, LogFormat};
+pub(crate) use crate::test_util::LogCapture;
+
+/// Build a config with every module disabled and quiet defaults
+pub(crate) fn test_config() -> Config {
+    Config::builder()
+        .app_name("test-app")
+        .env(Environment::Dev)
+        .host("127.0.0.1")
+        .port(0)
+        .body_limit(1024)
+        .log_level("info")
+        .feature_startup_banner(false)
+        .feature_db(false)
+        .feature_cache(false)
+        .feature_search(false)
+        .feature_broker(false)
+        .feature_mailer(false)
+        .feature_openapi(false)
+        .feature_request_log(false)
+        .feature_tracing(false)
+        .feature_otel(false)
+        .feature_otel_metrics(false)
+        .feature_tokio_console(false)
+        .feature_cors(false)
+        .feature_session(false)
+        .feature_maintenance_mode(false)
+        .feature_etag(false)
+        .feature_load_shed(false)
+        .feature_response_envelope(true)
+        .with(|config| {
+            config.app.app_debug = true;
+            config.app.app_shutdown_grace_seconds = 1;
+            config.app.app_shutdown_drain_seconds = 0;
+            config.http.http_request_timeout_seconds = 1;
+            config.logging.log_format = LogFormat::Pretty;
+            config.logging.log_include_target = false;
+            config.logging.log_include_fileline = false;
+            config.logging.request_log_headers_denylist = String::new();
+            config.cache.cache_backend = CacheBackend::None;
+            config.cache.cache_ttl_seconds = 60;
+            config.cache.cache_max_entries = 1000;
+            config.cache.cache_redis_pool_size = 1;
+            config.cache.cache_redis_connect_timeout_seconds = 1;
+            config.cors.cors_allow_methods = "GET".to_string();
+            config.cors.cors_allow_headers = "content-type".to_string();
+            config.cors.cors_allow_credentials = false;
+            config.cors.cors_max_age_seconds = 60;
+            config.banner.banner_show_secrets = false;
+            config.banner.banner_show_env_vars = false;
+        })
+        .build()
+}
@@ -0,0 +1,505 @@
+//! In-memory test harness (`test-util` feature)
+//!
+//! [`TestApp`] drives the router built by [`AppBuilder`] without binding a
+//! socket. Every request runs under a subscriber writing into a
+//! [`LogCapture`], so tests can assert on what was logged:
+//!
+//! ```ignore
+//! let app = TestApp::builder().route("/users", post(create_user)).into_test_app();
+//!
+//! let response = app.post_json("/users", &json!({ "name": "Ada" })).await;
+//! assert_eq!(response.status(), StatusCode::CREATED);
+//! assert_eq!(response.data::<User>().name, "Ada");
+//! assert!(app.logs().iter().any(|line| line.contains("path=/users")));
+//! ```
+
+use std::{
+    io,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    extract::Request,
+    http::{HeaderMap, Method, StatusCode, header},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use tower::ServiceExt;
+use tracing::{Dispatch, instrument::WithSubscriber};
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::{
+    AppBuilder, BuildInfo,
+    config::{Config, ConfigBuilder, Environment},
+    handlers::{HealthCheck, ReadyChecker},
+};
+
+/// Router under test with the logs of its requests
+///
+/// Cheap to clone; clones share the captured logs.
+#[derive(Clone)]
+pub struct TestApp {
+    router: Router,
+    logs: LogCapture,
+    dispatch: Dispatch,
+}
+
+impl TestApp {
+    /// Configuration for tests: dev, `127.0.0.1:0`, banner off, envelope on
+    ///
+    /// Everything else keeps the defaults of an empty environment; override
+    /// fields before building:
+    ///
+    /// ```ignore
+    /// use barrzen_axum_core::TestApp;
+    ///
+    /// let config = TestApp::config().feature_cors(true).build();
+    /// assert_eq!(config.app.app_port, 0);
+    /// ```
+    pub fn config() -> ConfigBuilder {
+        Config::builder()
+            .app_name("test-app")
+            .env(Environment::Dev)
+            .host("127.0.0.1")
+            .port(0)
+            .feature_startup_banner(false)
+            .feature_response_envelope(true)
+    }
+
+    /// [`AppBuilder`] over [`TestApp::config`]
+    #[must_use]
+    pub fn builder() -> AppBuilder {
+        Self::builder_with(|_| {})
+    }
+
+    /// [`AppBuilder`] over [`TestApp::config`] adjusted by `f`
+    #[must_use]
+    pub fn builder_with(f: impl FnOnce(&mut Config)) -> AppBuilder {
+        let build_info = BuildInfo::new("test-app", "0.0.0", None, "unknown", None);
+        AppBuilder::new(Self::config().with(f).build(), build_info)
+    }
+
+    /// `GET uri`
+    pub async fn get(&self, uri: &str) -> TestResponse {
+        self.request(empty(Method::GET, uri)).await
+    }
+
+    /// `DELETE uri`
+    pub async fn delete(&self, uri: &str) -> TestResponse {
+        self.request(empty(Method::DELETE, uri)).await
+    }
+
+    /// `POST uri` with `body` as JSON
+    ///
+    /// # Panics
+    /// Panics if `body` cannot be serialized.
+    pub async fn post_json<T: Serialize + ?Sized>(&self, uri: &str, body: &T) -> TestResponse {
+        self.request(json(Method::POST, uri, body)).await
+    }
+
+    /// `PUT uri` with `body` as JSON
+    ///
+    /// # Panics
+    /// Panics if `body` cannot be serialized.
+    pub async fn put_json<T: Serialize + ?Sized>(&self, uri: &str, body: &T) -> TestResponse {
+        self.request(json(Method::PUT, uri, body)).await
+    }
+
+    /// `PATCH uri` with `body` as JSON
+    ///
+    /// # Panics
+    /// Panics if `body` cannot be serialized.
+    pub async fn patch_json<T: Serialize + ?Sized>(&self, uri: &str, body: &T) -> TestResponse {
+        self.request(json(Method::PATCH, uri, body)).await
+    }
+
+    /// Send any request, e.g. one with custom headers
+    ///
+    /// # Panics
+    /// Panics if the response body cannot be read.
+    pub async fn request(&self, request: Request<Body>) -> TestResponse {
+        let response = self.router.clone().oneshot(request);
+        let response = match response.with_subscriber(self.dispatch.clone()).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        };
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX)
+            .with_subscriber(self.dispatch.clone())
+            .await
+            .unwrap_or_else(|e| panic!("failed to read the response body: {e}"));
+
+        TestResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        }
+    }
+
+    /// Lines logged while building the router and serving requests so far
+    #[must_use]
+    pub fn logs(&self) -> Vec<String> {
+        self.logs.lines()
+    }
+
+    /// The router, for driving it with other tools
+    pub fn router(&self) -> Router {
+        self.router.clone()
+    }
+}
+
+impl<S> AppBuilder<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    /// Build the router into a [`TestApp`] (`test-util` feature)
+    ///
+    /// # Panics
+    /// Panics if the configuration cannot be honored (see
+    /// [`AppBuilder::try_build`]).
+    #[must_use]
+    pub fn into_test_app(self) -> TestApp {
+        let logs = LogCapture::new();
+        let dispatch = Dispatch::new(logs.subscriber());
+        let router = tracing::dispatcher::with_default(&dispatch, || self.build());
+        TestApp {
+            router,
+            logs,
+            dispatch,
+        }
+    }
+}
+
+fn empty(method: Method, uri: &str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(Body::empty())
+        .unwrap_or_else(|e| panic!("invalid request {uri}: {e}"))
+}
+
+fn json<T: Serialize + ?Sized>(method: Method, uri: &str, body: &T) -> Request<Body> {
+    let body = serde_json::to_vec(body)
+        .unwrap_or_else(|e| panic!("failed to serialize the body for {uri}: {e}"));
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap_or_else(|e| panic!("invalid request {uri}: {e}"))
+}
+
+/// Buffered response of a [`TestApp`] request
+#[derive(Debug, Clone)]
+pub struct TestResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl TestResponse {
+    #[must_use]
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Value of header `name`, if present and valid UTF-8
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    #[must_use]
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Body as text, invalid UTF-8 replaced
+    #[must_use]
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Body parsed as JSON
+    ///
+    /// # Panics
+    /// Panics with the body if it is not a valid `T`.
+    #[must_use]
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body)
+            .unwrap_or_else(|e| panic!("unexpected response body ({e}): {}", self.text()))
+    }
+
+    /// `data` of the response envelope parsed as `T`
+    ///
+    /// # Panics
+    /// Panics with the body if it is not an envelope whose `data` is a
+    /// valid `T`.
+    #[must_use]
+    pub fn data<T: DeserializeOwned>(&self) -> T {
+        let mut envelope: serde_json::Value = self.json();
+        let data = envelope.get_mut("data").map_or_else(
+            || panic!("response has no data: {}", self.text()),
+            serde_json::Value::take,
+        );
+        serde_json::from_value(data)
+            .unwrap_or_else(|e| panic!("unexpected response data ({e}): {}", self.text()))
+    }
+}
+
+/// [`ReadyChecker`] reporting whatever checks the test sets
+///
+/// Clones share the checks, so they can change after the app is built:
+///
+/// ```ignore
+/// let ready = FakeReadyChecker::new(vec![HealthCheck::ok("db")]);
+/// let app = TestApp::builder().with_ready_checker(ready.clone()).into_test_app();
+/// ready.set(vec![HealthCheck::fail("db", "connection refused")]);
+/// ```
+#[derive(Clone, Default)]
+pub struct FakeReadyChecker {
+    checks: Arc<Mutex<Vec<HealthCheck>>>,
+}
+
+impl FakeReadyChecker {
+    #[must_use]
+    pub fn new(checks: Vec<HealthCheck>) -> Self {
+        Self {
+            checks: Arc::new(Mutex::new(checks)),
+        }
+    }
+
+    /// Replace the reported checks
+    pub fn set(&self, checks: Vec<HealthCheck>) {
+        *self.checks.lock().unwrap_or_else(PoisonError::into_inner) = checks;
+    }
+}
+
+#[async_trait::async_trait]
+impl ReadyChecker for FakeReadyChecker {
+    async fn ready_checks(&self) -> Vec<HealthCheck> {
+        self.checks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// In-memory sink for formatted tracing output
+#[derive(Clone, Default)]
+pub struct LogCapture {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl LogCapture {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Install a thread-local subscriber writing into this capture
+    #[must_use]
+    pub fn set_default(&self) -> tracing::subscriber::DefaultGuard {
+        tracing::subscriber::set_default(self.subscriber())
+    }
+
+    /// Captured output split into lines
+    #[must_use]
+    pub fn lines(&self) -> Vec<String> {
+        let buffer = self.buffer.lock().unwrap_or_else(PoisonError::into_inner);
+        String::from_utf8_lossy(&buffer)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn subscriber(&self) -> impl tracing::Subscriber + Send + Sync + use<> {
+        tracing_subscriber::fmt()
+            .with_writer(self.clone())
+            .with_ansi(false)
+            .with_max_level(tracing::Level::TRACE)
+            .finish()
+    }
+}
+
+impl io::Write for LogCapture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogCapture {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::routing::{get, post, put};
+    use serde::Deserialize;
+    use serde_json::{Value, json};
+
+    use super::*;
+    use crate::ApiResponse;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct User {
+        name: String,
+    }
+
+    fn app() -> TestApp {
+        TestApp::builder()
+            .route(
+                "/users",
+                post(|axum::Json(user): axum::Json<User>| async move {
+                    tracing::info!(name = %user.name, "creating user");
+                    ApiResponse::created(user, "User created")
+                }),
+            )
+            .route(
+                "/users/{id}",
+                put(|axum::Json(user): axum::Json<Value>| async move { axum::Json(user) })
+                    .patch(|axum::Json(user): axum::Json<Value>| async move { axum::Json(user) })
+                    .delete(|| async { StatusCode::NO_CONTENT }),
+            )
+            .into_test_app()
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config = TestApp::config().build();
+        assert_eq!(config.app.app_env, Environment::Dev);
+        assert_eq!(config.app.app_port, 0);
+        assert!(!config.features.feature_startup_banner);
+        assert!(config.features.feature_response_envelope);
+    }
+
+    #[tokio::test]
+    async fn test_get_core_routes() {
+        let app = app();
+
+        let response = app.get("/healthz").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.data::<Value>()["status"], "ok");
+        assert!(
+            response
+                .header("content-type")
+                .unwrap()
+                .starts_with("application/json")
+        );
+        assert!(response.header("x-request-id").is_some());
+
+        let version = app.get("/version").await;
+        assert_eq!(version.data::<Value>()["name"], "test-app");
+        assert_eq!(version.json::<Value>()["status"], "success");
+
+        assert_eq!(app.get("/missing").await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_json_helpers() {
+        let app = app();
+        let ada = User {
+            name: "Ada".to_string(),
+        };
+
+        let response = app.post_json("/users", &ada).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.data::<User>(), ada);
+
+        let body = json!({ "name": "Bob" });
+        assert_eq!(app.put_json("/users/1", &body).await.data::<Value>(), body);
+        assert_eq!(
+            app.patch_json("/users/1", &body).await.data::<Value>(),
+            body
+        );
+        assert_eq!(
+            app.delete("/users/1").await.status(),
+            StatusCode::NO_CONTENT
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_request() {
+        let app = app();
+        let request = Request::get("/healthz")
+            .header("x-request-id", "req-123")
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.request(request).await;
+        assert_eq!(response.header("x-request-id"), Some("req-123"));
+        assert_eq!(response.json::<Value>()["request_id"], "req-123");
+        assert!(response.text().contains("req-123"));
+        assert_eq!(response.bytes(), response.text().as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_logs_are_captured() {
+        let app = app();
+        app.post_json("/users", &json!({ "name": "Ada" })).await;
+
+        let logs = app.logs();
+        assert!(
+            logs.iter()
+                .any(|line| line.contains("creating user") && line.contains("name=Ada")),
+            "{logs:?}"
+        );
+        assert!(
+            logs.iter().any(|line| line.contains("path=/users")),
+            "{logs:?}"
+        );
+        // Each app has its own capture
+        let other = TestApp::builder().into_test_app().logs();
+        assert!(!other.iter().any(|line| line.contains("creating user")));
+    }
+
+    #[tokio::test]
+    async fn test_fake_ready_checker_and_overrides() {
+        let ready = FakeReadyChecker::new(vec![HealthCheck::ok("db")]);
+        let app = TestApp::builder_with(|config| config.readiness.readyz_strict = true)
+            .with_ready_checker(ready.clone())
+            .into_test_app();
+
+        let response = app.get("/readyz").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.data::<Value>()["checks"][0]["name"], "db");
+
+        ready.set(vec![HealthCheck::fail("db", "connection refused")]);
+        let response = app.get("/readyz").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(response.text().contains("connection refused"));
+    }
+
+    #[tokio::test]
+    async fn test_envelope_can_be_turned_off() {
+        let app = TestApp::builder_with(|config| {
+            config.features.feature_response_envelope = false;
+        })
+        .route("/ping", get(|| async { "pong" }))
+        .into_test_app();
+
+        assert_eq!(
+            app.get("/healthz").await.json::<Value>(),
+            json!({ "status": "ok" })
+        );
+        assert_eq!(app.get("/ping").await.text(), "pong");
+    }
+}
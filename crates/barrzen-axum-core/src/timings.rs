@@ -0,0 +1,279 @@
+//! Startup timing report
+//!
+//! Shows where the time goes before a service is ready: config load, tracing
+//! init, each infra component, migrations and binding, plus phases of the
+//! application itself. Phases are recorded into a [`StartupTimings`] handed to
+//! [`AppBuilder::with_startup_timings`](crate::AppBuilder::with_startup_timings),
+//! which logs them once the listeners are bound, prints them as the banner's
+//! TIMINGS section and reports them in /version.
+//!
+//! ```ignore
+//! let timings = StartupTimings::new();
+//! let config = timings.measure("config", Config::from_env)?;
+//! let _obs = timings.measure("tracing", || barrzen_axum_obs::init(&config))?;
+//! let infra = Infra::init_timed(&config, &timings).await?;
+//! timings.phase("warm_cache", warm_cache(&infra)).await?;
+//! AppBuilder::new(config, build).with_startup_timings(timings).serve().await?;
+//! ```
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+    time::Duration,
+};
+
+use serde::Serialize;
+use tokio::time::Instant;
+
+/// Shared recorder of startup phases; clones record into the same report
+///
+/// [`StartupTimings::disabled`] records nothing, so libraries can take one
+/// unconditionally at the cost of a branch per phase.
+#[derive(Clone, Default)]
+pub struct StartupTimings {
+    inner: Option<Arc<Inner>>,
+}
+
+struct Inner {
+    started: Instant,
+    /// Set once serving starts; the total keeps growing until then
+    finished: OnceLock<Duration>,
+    phases: Mutex<Vec<(String, Duration)>>,
+}
+
+/// Startup phases as reported by /version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StartupReport {
+    /// From [`StartupTimings::new`] until the listeners were bound
+    pub total_ms: u64,
+    /// In the order they finished
+    pub phases: Vec<PhaseTiming>,
+}
+
+/// A recorded phase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PhaseTiming {
+    pub name: String,
+    pub duration_ms: u64,
+}
+
+impl StartupTimings {
+    /// Recorder whose total starts now, ideally first thing in `main`
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            inner: Some(Arc::new(Inner {
+                started: Instant::now(),
+                finished: OnceLock::new(),
+                phases: Mutex::new(Vec::new()),
+            })),
+        }
+    }
+
+    /// Recorder that ignores every phase
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// Whether phases are recorded
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.inner.is_some()
+    }
+
+    /// Record a phase measured elsewhere
+    pub fn record(&self, name: impl Into<String>, duration: Duration) {
+        if let Some(inner) = &self.inner {
+            inner
+                .phases
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((name.into(), duration));
+        }
+    }
+
+    /// Await `future` and record how long it took as `name`
+    pub async fn phase<F: Future>(&self, name: &str, future: F) -> F::Output {
+        if self.inner.is_none() {
+            return future.await;
+        }
+        let started = Instant::now();
+        let output = future.await;
+        self.record(name, started.elapsed());
+        output
+    }
+
+    /// Run `f` and record how long it took as `name`
+    pub fn measure<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        if self.inner.is_none() {
+            return f();
+        }
+        let started = Instant::now();
+        let output = f();
+        self.record(name, started.elapsed());
+        output
+    }
+
+    /// Record the time until the returned guard is dropped as `name`
+    ///
+    /// For phases with early returns, where [`StartupTimings::phase`] does
+    /// not fit; a phase that fails is recorded too.
+    pub fn start(&self, name: &str) -> PhaseGuard {
+        PhaseGuard {
+            timings: self.clone(),
+            name: self.inner.as_ref().map(|_| name.to_string()),
+            started: Instant::now(),
+        }
+    }
+
+    /// Recorded phases in the order they finished
+    #[must_use]
+    pub fn phases(&self) -> Vec<(String, Duration)> {
+        self.inner.as_ref().map_or_else(Vec::new, |inner| {
+            inner
+                .phases
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        })
+    }
+
+    /// Time since [`StartupTimings::new`], frozen once serving starts
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.inner.as_ref().map_or(Duration::ZERO, |inner| {
+            inner
+                .finished
+                .get()
+                .copied()
+                .unwrap_or_else(|| inner.started.elapsed())
+        })
+    }
+
+    /// Report for /version, `None` when disabled
+    #[must_use]
+    pub fn report(&self) -> Option<StartupReport> {
+        self.inner.as_ref()?;
+        let phases = self
+            .phases()
+            .into_iter()
+            .map(|(name, duration)| PhaseTiming {
+                name,
+                duration_ms: millis(duration),
+            })
+            .collect();
+        Some(StartupReport {
+            total_ms: millis(self.total()),
+            phases,
+        })
+    }
+
+    /// Rows of the banner's TIMINGS section, e.g. `config       12ms`
+    pub(crate) fn rows(&self) -> Vec<String> {
+        let phases = self.phases();
+        let width = phases
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(std::iter::once("total".len()))
+            .max()
+            .unwrap_or_default();
+        phases
+            .iter()
+            .map(|(name, duration)| (name.as_str(), *duration))
+            .chain(std::iter::once(("total", self.total())))
+            .map(|(name, duration)| format!("{name:<width$}  {:>6}ms", millis(duration)))
+            .collect()
+    }
+
+    /// Stop the total and log every phase at info
+    pub(crate) fn finish(&self) {
+        let Some(inner) = &self.inner else {
+            return;
+        };
+        let total = *inner.finished.get_or_init(|| inner.started.elapsed());
+        for (name, duration) in self.phases() {
+            tracing::info!(
+                phase = %name,
+                duration_ms = millis(duration),
+                "Startup phase {name} took {duration:.1?}"
+            );
+        }
+        tracing::info!(total_ms = millis(total), "Started in {total:.1?}");
+    }
+}
+
+/// Phase started with [`StartupTimings::start`], recorded when dropped
+#[must_use = "the phase ends when the guard is dropped"]
+pub struct PhaseGuard {
+    timings: StartupTimings,
+    /// `None` when the timings are disabled
+    name: Option<String>,
+    started: Instant,
+}
+
+impl Drop for PhaseGuard {
+    fn drop(&mut self) {
+        if let Some(name) = self.name.take() {
+            self.timings.record(name, self.started.elapsed());
+        }
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_records_phases() {
+        let timings = StartupTimings::new();
+        timings.measure("config", || {});
+        timings
+            .phase("warm_cache", tokio::time::sleep(Duration::from_millis(40)))
+            .await;
+        {
+            let _phase = timings.start("bind");
+            tokio::time::sleep(Duration::from_millis(3)).await;
+        }
+        timings.finish();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        assert_eq!(
+            timings.rows(),
+            [
+                "config           0ms",
+                "warm_cache      40ms",
+                "bind             3ms",
+                "total           43ms",
+            ]
+        );
+        let report = serde_json::to_value(timings.report()).unwrap();
+        assert_eq!(
+            report,
+            serde_json::json!({
+                "total_ms": 43,
+                "phases": [
+                    { "name": "config", "duration_ms": 0 },
+                    { "name": "warm_cache", "duration_ms": 40 },
+                    { "name": "bind", "duration_ms": 3 },
+                ],
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disabled_records_nothing() {
+        let timings = StartupTimings::disabled();
+        assert_eq!(timings.phase("migrations", async { 7 }).await, 7);
+        timings.record("bind", Duration::from_millis(3));
+
+        assert!(timings.phases().is_empty());
+        assert_eq!(timings.report(), None);
+    }
+}
@@ -0,0 +1,301 @@
+//! W3C trace context middleware
+//!
+//! Joins the caller's trace from `traceparent`/`tracestate`, wraps each
+//! request in an OpenTelemetry server span and answers with `traceparent`
+//! and `x-trace-id`, so callers can continue or look up the trace.
+//!
+//! Propagation uses the global text map propagator, which
+//! `barrzen_axum_obs::init` sets to W3C trace context.
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Request, Response};
+use opentelemetry::{
+    global,
+    propagation::{Extractor, Injector},
+    trace::TraceContextExt,
+};
+use tower::{Layer, Service};
+use tracing::{Instrument, field::Empty};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::path_redaction::PathRedactor;
+
+/// Header carrying the trace id of the server span
+pub static TRACE_ID_HEADER: HeaderName = HeaderName::from_static("x-trace-id");
+
+/// Trace id of the current span, for correlation outside the response
+///
+/// `None` without an OpenTelemetry layer or outside a trace.
+#[must_use]
+pub fn current_trace_id() -> Option<String> {
+    let cx = tracing::Span::current().context();
+    let span = cx.span();
+    let span_context = span.span_context();
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+/// Write the trace context of `span` to `headers`, for outbound calls
+///
+/// Sets `traceparent` through the global propagator, so the callee joins the
+/// trace as a child of `span`. Writes nothing without an OpenTelemetry layer.
+pub fn inject_trace_context(span: &tracing::Span, headers: &mut HeaderMap) {
+    let cx = span.context();
+    if !cx.span().span_context().is_valid() {
+        return;
+    }
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&cx, &mut HeaderInjector(headers));
+    });
+}
+
+/// Layer creating a server span per request, parented to the remote context
+///
+/// Applied outside the per-request `TraceLayer`, so its span becomes the
+/// parent of the request span and everything logged below it. `url.path`
+/// is redacted by `REQUEST_LOG_PATH_REDACTIONS`.
+#[derive(Clone, Default)]
+pub(crate) struct TraceContextLayer {
+    redactor: PathRedactor,
+}
+
+impl TraceContextLayer {
+    pub(crate) fn new(redactor: PathRedactor) -> Self {
+        Self { redactor }
+    }
+}
+
+impl<S> Layer<S> for TraceContextLayer {
+    type Service = TraceContextService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TraceContextService {
+            inner,
+            redactor: self.redactor.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct TraceContextService<S> {
+    inner: S,
+    redactor: PathRedactor,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for TraceContextService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&HeaderExtractor(req.headers()))
+        });
+        let method = req.method().as_str();
+        let span = tracing::info_span!(
+            "HTTP request",
+            otel.name = method,
+            otel.kind = "server",
+            otel.status_code = Empty,
+            http.request.method = method,
+            url.path = %self.redactor.redact(req.uri().path()),
+            http.response.status_code = Empty,
+        );
+        // Fails only without an OpenTelemetry layer, leaving a plain span
+        let _ = span.set_parent(parent);
+
+        let future = span.in_scope(|| self.inner.call(req));
+        Box::pin(async move {
+            let mut response = future.instrument(span.clone()).await?;
+
+            let status = response.status();
+            span.record("http.response.status_code", i64::from(status.as_u16()));
+            if status.is_server_error() {
+                span.record("otel.status_code", "error");
+            }
+
+            let cx = span.context();
+            let span_context = cx.span().span_context().clone();
+            if span_context.is_valid() {
+                global::get_text_map_propagator(|propagator| {
+                    propagator.inject_context(&cx, &mut HeaderInjector(response.headers_mut()));
+                });
+                if let Ok(value) = HeaderValue::from_str(&span_context.trace_id().to_string()) {
+                    response
+                        .headers_mut()
+                        .insert(TRACE_ID_HEADER.clone(), value);
+                }
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+/// Read propagation fields from request headers
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+/// Write propagation fields to headers
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, http::StatusCode, routing::get};
+    use opentelemetry::{
+        Value,
+        trace::{SpanKind, TracerProvider as _},
+    };
+    use opentelemetry_sdk::{
+        propagation::TraceContextPropagator,
+        trace::{InMemorySpanExporter, SdkTracerProvider},
+    };
+    use tower::ServiceExt;
+    use tracing_subscriber::{Registry, layer::SubscriberExt};
+
+    const REMOTE_TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const REMOTE_SPAN_ID: &str = "00f067aa0ba902b7";
+
+    #[tokio::test]
+    async fn test_server_span_joins_remote_trace() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _subscriber = tracing::subscriber::set_default(subscriber);
+
+        let app = Router::new()
+            .route(
+                "/orders/{id}",
+                get(|| async { current_trace_id().unwrap_or_default() }),
+            )
+            .layer(TraceContextLayer::default());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/orders/7")
+                    .header(
+                        "traceparent",
+                        format!("00-{REMOTE_TRACE_ID}-{REMOTE_SPAN_ID}-01"),
+                    )
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[&TRACE_ID_HEADER], REMOTE_TRACE_ID);
+        let traceparent = response.headers()["traceparent"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(traceparent.starts_with(&format!("00-{REMOTE_TRACE_ID}-")));
+        assert!(!traceparent.contains(REMOTE_SPAN_ID), "{traceparent}");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], REMOTE_TRACE_ID.as_bytes());
+
+        provider.force_flush().unwrap();
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = spans
+            .iter()
+            .find(|span| span.span_kind == SpanKind::Server)
+            .unwrap();
+        assert_eq!(span.name, "GET");
+        assert_eq!(span.span_context.trace_id().to_string(), REMOTE_TRACE_ID);
+        assert_eq!(span.parent_span_id.to_string(), REMOTE_SPAN_ID);
+        assert!(traceparent.contains(&span.span_context.span_id().to_string()));
+
+        let attribute = |key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.clone())
+        };
+        assert_eq!(attribute("http.request.method"), Some(Value::from("GET")));
+        assert_eq!(attribute("url.path"), Some(Value::from("/orders/7")));
+        assert_eq!(
+            attribute("http.response.status_code"),
+            Some(Value::I64(200))
+        );
+    }
+
+    #[test]
+    fn test_inject_trace_context() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let mut headers = HeaderMap::new();
+        inject_trace_context(&tracing::info_span!("call"), &mut headers);
+        assert!(headers.is_empty());
+
+        let provider = SdkTracerProvider::builder().build();
+        let subscriber = Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _subscriber = tracing::subscriber::set_default(subscriber);
+        let span = tracing::info_span!("call");
+        inject_trace_context(&span, &mut headers);
+
+        let span_context = span.context().span().span_context().clone();
+        let expected = format!(
+            "00-{}-{}-01",
+            span_context.trace_id(),
+            span_context.span_id()
+        );
+        assert_eq!(headers["traceparent"], expected.as_str());
+    }
+
+    #[tokio::test]
+    async fn test_without_otel_layer_headers_are_skipped() {
+        let app = Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(TraceContextLayer::default());
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(&TRACE_ID_HEADER).is_none());
+        assert!(response.headers().get("traceparent").is_none());
+    }
+}
@@ -0,0 +1,787 @@
+//! Multipart uploads with per-part limits (`uploads` feature)
+//!
+//! [`MultipartLimits`] is the layer for routes taking uploads: it raises
+//! their body limit (through [`RouteOverrides`]) and hands the `UPLOAD_*`
+//! limits to the [`ApiMultipart`] extractor. The extractor streams
+//! `multipart/form-data` parts one at a time and enforces the limits while
+//! reading, rejecting with an [`ApiError`] that names the offending part:
+//! 413 for an oversized part or too many files, 415 for a file whose
+//! content type is not allowed.
+
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{FromRequest, Request},
+    http::{StatusCode, header},
+};
+use bytes::{Buf, BytesMut};
+use http_body_util::BodyExt;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tower::{Layer, Service};
+
+use crate::{
+    config::UploadConfig,
+    request_limits::{RouteOverrides, RouteOverridesService},
+    response::{ApiError, extract_request_id},
+};
+
+/// Room for part headers and plain form fields on top of the files
+const FORM_OVERHEAD_BYTES: u64 = 1024 * 1024;
+
+/// Largest header block of a single part
+const MAX_PART_HEADER_BYTES: usize = 8 * 1024;
+
+/// Content type assumed for file parts that do not send one
+const DEFAULT_FILE_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Upload limits for the routes of a sub-router
+///
+/// The request body limit of these routes becomes
+/// `UPLOAD_MAX_FILES * UPLOAD_MAX_FILE_SIZE_BYTES` plus 1MB for headers and
+/// form fields, replacing `HTTP_BODY_LIMIT_BYTES`.
+///
+/// ```ignore
+/// let uploads = Router::new()
+///     .route("/avatars", post(upload_avatar))
+///     .layer(MultipartLimits::from_config(&config.upload));
+/// ```
+#[derive(Clone, Debug)]
+pub struct MultipartLimits {
+    max_part_size: u64,
+    max_files: usize,
+    allowed_content_types: Arc<[String]>,
+}
+
+impl Default for MultipartLimits {
+    /// Limits from an empty environment
+    fn default() -> Self {
+        Self::from_config(&UploadConfig::default())
+    }
+}
+
+impl MultipartLimits {
+    /// Limits from `UPLOAD_MAX_FILE_SIZE_BYTES`, `UPLOAD_MAX_FILES` and
+    /// `UPLOAD_ALLOWED_CONTENT_TYPES`
+    #[must_use]
+    pub fn from_config(config: &UploadConfig) -> Self {
+        Self {
+            max_part_size: config.upload_max_file_size_bytes,
+            max_files: config.upload_max_files,
+            allowed_content_types: config.allowed_content_types().into(),
+        }
+    }
+
+    fn body_limit(&self) -> usize {
+        let files = u64::try_from(self.max_files).unwrap_or(u64::MAX);
+        let limit = self
+            .max_part_size
+            .saturating_mul(files)
+            .saturating_add(FORM_OVERHEAD_BYTES);
+        usize::try_from(limit).unwrap_or(usize::MAX)
+    }
+
+    /// Whether a file part of `content_type` is accepted; `image/*` entries
+    /// match the whole type
+    fn allows(&self, content_type: &str) -> bool {
+        let essence = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        self.allowed_content_types.is_empty()
+            || self.allowed_content_types.iter().any(|allowed| {
+                allowed.strip_suffix('*').map_or_else(
+                    || *allowed == essence,
+                    |prefix| prefix.ends_with('/') && essence.starts_with(prefix),
+                )
+            })
+    }
+}
+
+impl<S> Layer<S> for MultipartLimits {
+    type Service = MultipartLimitsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        let overrides = RouteOverrides::new().body_limit(self.body_limit());
+        MultipartLimitsService {
+            inner: overrides.layer(inner),
+            limits: self.clone(),
+        }
+    }
+}
+
+/// Service applying [`MultipartLimits`]
+#[derive(Clone)]
+pub struct MultipartLimitsService<S> {
+    inner: RouteOverridesService<S>,
+    limits: MultipartLimits,
+}
+
+impl<S> Service<Request> for MultipartLimitsService<S>
+where
+    RouteOverridesService<S>: Service<Request>,
+{
+    type Response = <RouteOverridesService<S> as Service<Request>>::Response;
+    type Error = <RouteOverridesService<S> as Service<Request>>::Error;
+    type Future = <RouteOverridesService<S> as Service<Request>>::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        req.extensions_mut().insert(self.limits.clone());
+        self.inner.call(req)
+    }
+}
+
+/// Streaming `multipart/form-data` extractor enforcing [`MultipartLimits`]
+///
+/// Routes without a [`MultipartLimits`] layer get the default limits (and
+/// keep the global body limit). Rejects non-multipart requests with 415.
+///
+/// ```ignore
+/// async fn upload(mut form: ApiMultipart) -> ApiResult<u64> {
+///     let mut total = 0;
+///     while let Some(part) = form.next_part().await? {
+///         let file = tempfile::tempfile().map_err(|_| ApiError::internal("No temp file"))?;
+///         let mut file = tokio::fs::File::from_std(file);
+///         total += part.save_to(&mut file).await?;
+///     }
+///     Ok(ApiResponse::ok(total, "uploaded"))
+/// }
+/// ```
+pub struct ApiMultipart {
+    body: Body,
+    /// `\r\n--<boundary>`
+    delimiter: Vec<u8>,
+    buffer: BytesMut,
+    state: State,
+    limits: MultipartLimits,
+    files: usize,
+    request_id: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Before the first delimiter
+    Preamble,
+    /// Right after a delimiter
+    Delimiter,
+    /// Inside a part body
+    Part,
+    Done,
+}
+
+impl<S> FromRequest<S> for ApiMultipart
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, _state: &S) -> Result<Self, Self::Rejection> {
+        let request_id = extract_request_id(req.headers());
+        let boundary = req
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_boundary);
+        let Some(boundary) = boundary else {
+            let error = ApiError::new(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Expected a multipart/form-data body with a boundary",
+            );
+            return Err(match request_id {
+                Some(id) => error.with_request_id(id),
+                None => error,
+            });
+        };
+        let limits = req
+            .extensions()
+            .get::<MultipartLimits>()
+            .cloned()
+            .unwrap_or_default();
+
+        // The leading CRLF lets the first boundary match like the others
+        let mut buffer = BytesMut::from(&b"\r\n"[..]);
+        buffer.reserve(8 * 1024);
+        Ok(Self {
+            body: req.into_body(),
+            delimiter: [b"\r\n--", boundary.as_bytes()].concat(),
+            buffer,
+            state: State::Preamble,
+            limits,
+            files: 0,
+            request_id,
+        })
+    }
+}
+
+impl ApiMultipart {
+    /// Next part, or `None` after the last one
+    ///
+    /// An unread rest of the previous part is skipped.
+    ///
+    /// # Errors
+    /// Returns 400 for a malformed body, 413 when `UPLOAD_MAX_FILES` is
+    /// exceeded and 415 for a file whose content type is not allowed.
+    pub async fn next_part(&mut self) -> Result<Option<UploadPart<'_>>, ApiError> {
+        match self.state {
+            State::Done => return Ok(None),
+            State::Preamble => self.skip_preamble().await?,
+            State::Part => while self.part_chunk().await?.is_some() {},
+            State::Delimiter => {}
+        }
+
+        self.fill(2).await?;
+        if self.buffer.starts_with(b"--") {
+            self.state = State::Done;
+            return Ok(None);
+        }
+        if !self.buffer.starts_with(b"\r\n") {
+            return Err(self.malformed("missing line break after boundary"));
+        }
+        self.buffer.advance(2);
+
+        let headers = self.read_part_headers().await?;
+        self.state = State::Part;
+        let Some(name) = headers.name else {
+            return Err(self.malformed("part without a name"));
+        };
+        if headers.file_name.is_some() {
+            self.files += 1;
+            if self.files > self.limits.max_files {
+                return Err(self.reject(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!(
+                        "Part \"{name}\" exceeds the limit of {} files (UPLOAD_MAX_FILES)",
+                        self.limits.max_files
+                    ),
+                ));
+            }
+            let content_type = headers
+                .content_type
+                .as_deref()
+                .unwrap_or(DEFAULT_FILE_CONTENT_TYPE);
+            if !self.limits.allows(content_type) {
+                return Err(self.reject(
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                    format!(
+                        "Part \"{name}\" has content type {content_type}, which is not allowed \
+                         (UPLOAD_ALLOWED_CONTENT_TYPES)"
+                    ),
+                ));
+            }
+        }
+
+        Ok(Some(UploadPart {
+            multipart: self,
+            name,
+            file_name: headers.file_name,
+            content_type: headers.content_type,
+            received: 0,
+        }))
+    }
+
+    async fn skip_preamble(&mut self) -> Result<(), ApiError> {
+        loop {
+            if let Some(pos) = find(&self.buffer, &self.delimiter) {
+                self.buffer.advance(pos + self.delimiter.len());
+                self.state = State::Delimiter;
+                return Ok(());
+            }
+            // Keep a tail that may be the start of the delimiter
+            let keep = self.delimiter.len() - 1;
+            if self.buffer.len() > keep {
+                self.buffer.advance(self.buffer.len() - keep);
+            }
+            if !self.read_more().await? {
+                return Err(self.malformed("no boundary found"));
+            }
+        }
+    }
+
+    async fn read_part_headers(&mut self) -> Result<PartHeaders, ApiError> {
+        let block = loop {
+            // A part without headers starts with the blank line
+            if self.buffer.starts_with(b"\r\n") {
+                self.buffer.advance(2);
+                break Bytes::new();
+            }
+            if let Some(pos) = find(&self.buffer, b"\r\n\r\n") {
+                let block = self.buffer.split_to(pos).freeze();
+                self.buffer.advance(4);
+                break block;
+            }
+            if self.buffer.len() > MAX_PART_HEADER_BYTES {
+                return Err(self.malformed("part headers too large"));
+            }
+            if !self.read_more().await? {
+                return Err(self.malformed("unexpected end of body in part headers"));
+            }
+        };
+        let Ok(block) = std::str::from_utf8(&block) else {
+            return Err(self.malformed("part headers are not valid UTF-8"));
+        };
+        Ok(PartHeaders::parse(block))
+    }
+
+    /// Next chunk of the current part body, `None` at its end
+    async fn part_chunk(&mut self) -> Result<Option<Bytes>, ApiError> {
+        loop {
+            if self.state != State::Part {
+                return Ok(None);
+            }
+            if let Some(pos) = find(&self.buffer, &self.delimiter) {
+                let data = self.buffer.split_to(pos).freeze();
+                self.buffer.advance(self.delimiter.len());
+                self.state = State::Delimiter;
+                return Ok((!data.is_empty()).then_some(data));
+            }
+            let safe = self.buffer.len().saturating_sub(self.delimiter.len() - 1);
+            if safe > 0 {
+                return Ok(Some(self.buffer.split_to(safe).freeze()));
+            }
+            if !self.read_more().await? {
+                return Err(self.malformed("unexpected end of body in part"));
+            }
+        }
+    }
+
+    async fn fill(&mut self, len: usize) -> Result<(), ApiError> {
+        while self.buffer.len() < len {
+            if !self.read_more().await? {
+                return Err(self.malformed("unexpected end of body"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Append the next data frame to the buffer; `false` at the end
+    async fn read_more(&mut self) -> Result<bool, ApiError> {
+        while let Some(frame) = self.body.frame().await {
+            let frame = frame.map_err(|error| self.body_error(&error))?;
+            if let Ok(data) = frame.into_data()
+                && !data.is_empty()
+            {
+                self.buffer.extend_from_slice(&data);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn body_error(&self, error: &axum::Error) -> ApiError {
+        let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+        while let Some(error) = source {
+            if error.is::<http_body_util::LengthLimitError>() {
+                return self.reject(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "Upload exceeds the request body limit".to_string(),
+                );
+            }
+            source = error.source();
+        }
+        self.reject(
+            StatusCode::BAD_REQUEST,
+            format!("Failed to read the request body: {error}"),
+        )
+    }
+
+    fn malformed(&self, reason: &str) -> ApiError {
+        self.reject(
+            StatusCode::BAD_REQUEST,
+            "Invalid multipart body".to_string(),
+        )
+        .with_details(reason)
+    }
+
+    fn reject(&self, status: StatusCode, message: String) -> ApiError {
+        let error = ApiError::new(status, message);
+        match &self.request_id {
+            Some(id) => error.with_request_id(id.clone()),
+            None => error,
+        }
+    }
+}
+
+/// One part of an [`ApiMultipart`] body, streamed on demand
+pub struct UploadPart<'a> {
+    multipart: &'a mut ApiMultipart,
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+    received: u64,
+}
+
+impl UploadPart<'_> {
+    /// Form field name
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// File name sent by the client, `None` for plain form fields
+    #[must_use]
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// Content type sent by the client
+    #[must_use]
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Next chunk of the part, `None` at its end
+    ///
+    /// # Errors
+    /// Returns 413 once the part exceeds `UPLOAD_MAX_FILE_SIZE_BYTES`, 400
+    /// for a malformed body.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, ApiError> {
+        let Some(chunk) = self.multipart.part_chunk().await? else {
+            return Ok(None);
+        };
+        self.received += chunk.len() as u64;
+        let max = self.multipart.limits.max_part_size;
+        if self.received > max {
+            return Err(self.multipart.reject(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!(
+                    "Part \"{}\" exceeds the limit of {max} bytes (UPLOAD_MAX_FILE_SIZE_BYTES)",
+                    self.name
+                ),
+            ));
+        }
+        Ok(Some(chunk))
+    }
+
+    /// Stream the part into `sink` (a file, a `Vec<u8>`, ...), returning the
+    /// number of bytes written
+    ///
+    /// # Errors
+    /// Returns the errors of [`UploadPart::chunk`], or 500 if the sink fails.
+    pub async fn save_to<W>(mut self, sink: &mut W) -> Result<u64, ApiError>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        while let Some(chunk) = self.chunk().await? {
+            sink.write_all(&chunk)
+                .await
+                .map_err(|error| self.sink_error(&error))?;
+        }
+        sink.flush()
+            .await
+            .map_err(|error| self.sink_error(&error))?;
+        Ok(self.received)
+    }
+
+    /// Read the whole part into memory
+    ///
+    /// # Errors
+    /// Returns the errors of [`UploadPart::chunk`].
+    pub async fn bytes(mut self) -> Result<Bytes, ApiError> {
+        self.read_all().await
+    }
+
+    /// Read the whole part as UTF-8 text
+    ///
+    /// # Errors
+    /// Returns the errors of [`UploadPart::chunk`], or 400 if the part is
+    /// not valid UTF-8.
+    pub async fn text(mut self) -> Result<String, ApiError> {
+        let bytes = self.read_all().await?;
+        String::from_utf8(bytes.into()).map_err(|_| {
+            self.multipart.reject(
+                StatusCode::BAD_REQUEST,
+                format!("Part \"{}\" is not valid UTF-8", self.name),
+            )
+        })
+    }
+
+    async fn read_all(&mut self) -> Result<Bytes, ApiError> {
+        let mut bytes = BytesMut::new();
+        while let Some(chunk) = self.chunk().await? {
+            bytes.extend_from_slice(&chunk);
+        }
+        Ok(bytes.freeze())
+    }
+
+    fn sink_error(&self, error: &std::io::Error) -> ApiError {
+        tracing::error!(part = %self.name, error = %error, "Failed to store upload part");
+        self.multipart.reject(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to store part \"{}\"", self.name),
+        )
+    }
+}
+
+/// The part headers the extractor uses
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PartHeaders {
+    name: Option<String>,
+    file_name: Option<String>,
+    content_type: Option<String>,
+}
+
+impl PartHeaders {
+    fn parse(block: &str) -> Self {
+        let mut headers = Self::default();
+        for line in block.split("\r\n") {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            if name.trim().eq_ignore_ascii_case("content-disposition") {
+                for (param, param_value) in header_params(value) {
+                    match param.to_ascii_lowercase().as_str() {
+                        "name" => headers.name = Some(param_value),
+                        "filename" => headers.file_name = Some(param_value),
+                        _ => {}
+                    }
+                }
+            } else if name.trim().eq_ignore_ascii_case("content-type") && !value.is_empty() {
+                headers.content_type = Some(value.to_string());
+            }
+        }
+        headers
+    }
+}
+
+/// `key=value` parameters of a header value such as
+/// `form-data; name="file"`, unquoting quoted values
+fn header_params(value: &str) -> Vec<(String, String)> {
+    let mut params = Vec::new();
+    let mut rest = value.split_once(';').map_or("", |(_, rest)| rest);
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ';']);
+        let Some((key, after)) = rest.split_once('=') else {
+            return params;
+        };
+        let key = key.trim().to_string();
+        let after = after.trim_start();
+        if let Some(quoted) = after.strip_prefix('"') {
+            let mut parsed = String::new();
+            let mut chars = quoted.char_indices();
+            let mut end = quoted.len();
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => {
+                        if let Some((_, escaped)) = chars.next() {
+                            parsed.push(escaped);
+                        }
+                    }
+                    '"' => {
+                        end = i + 1;
+                        break;
+                    }
+                    c => parsed.push(c),
+                }
+            }
+            params.push((key, parsed));
+            rest = &quoted[end..];
+        } else {
+            let (token, remaining) = after.split_once(';').unwrap_or((after, ""));
+            params.push((key, token.trim().to_string()));
+            rest = remaining;
+        }
+    }
+}
+
+/// `boundary` parameter of a `multipart/form-data` content type
+fn parse_boundary(content_type: &str) -> Option<String> {
+    let essence = content_type.split(';').next().unwrap_or_default();
+    if !essence.trim().eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    header_params(content_type)
+        .into_iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("boundary"))
+        .map(|(_, boundary)| boundary)
+        .filter(|boundary| !boundary.is_empty() && boundary.len() <= 70)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, http::Request, response::IntoResponse, routing::post};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::{AppBuilder, BuildInfo, response::ApiResponse, test_support::test_config};
+
+    const BOUNDARY: &str = "X-BOUNDARY";
+
+    /// Echo `name:file_name:len` for each part
+    async fn upload(mut form: ApiMultipart) -> Result<impl IntoResponse, ApiError> {
+        let mut parts = Vec::new();
+        while let Some(part) = form.next_part().await? {
+            let label = format!("{}:{}", part.name(), part.file_name().unwrap_or("-"));
+            let mut sink = Vec::new();
+            let len = part.save_to(&mut sink).await?;
+            parts.push(format!("{label}:{len}"));
+        }
+        Ok(ApiResponse::ok(parts, "uploaded"))
+    }
+
+    fn app(configure: impl FnOnce(&mut crate::Config)) -> Router {
+        let mut config = test_config();
+        configure(&mut config);
+        let build = BuildInfo::new("test", "1.0.0", None, "1.75.0", None);
+        let uploads = Router::new()
+            .route("/upload", post(upload))
+            .layer(MultipartLimits::from_config(&config.upload));
+        AppBuilder::new(config, build)
+            .merge(uploads)
+            .route("/plain", post(upload))
+            .build()
+    }
+
+    /// (name, file name, content type, body)
+    fn multipart(parts: &[(&str, Option<&str>, &str, &[u8])]) -> Vec<u8> {
+        let mut body = b"preamble is ignored".to_vec();
+        for (name, file_name, content_type, data) in parts {
+            body.extend_from_slice(format!("\r\n--{BOUNDARY}\r\n").as_bytes());
+            let file_name = file_name
+                .map(|f| format!("; filename=\"{f}\""))
+                .unwrap_or_default();
+            body.extend_from_slice(
+                format!("Content-Disposition: form-data; name=\"{name}\"{file_name}\r\n")
+                    .as_bytes(),
+            );
+            body.extend_from_slice(format!("Content-Type: {content_type}\r\n\r\n").as_bytes());
+            body.extend_from_slice(data);
+        }
+        body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+        body
+    }
+
+    async fn send(app: &Router, path: &str, body: Vec<u8>) -> (StatusCode, serde_json::Value) {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post(path)
+                    .header(
+                        header::CONTENT_TYPE,
+                        format!("multipart/form-data; boundary=\"{BOUNDARY}\""),
+                    )
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_valid_upload_streams_every_part() {
+        let app = app(|_| {});
+        // Larger than the 1KB HTTP_BODY_LIMIT_BYTES of the test config
+        let file = vec![b'\r'; 5000];
+        let body = multipart(&[
+            ("title", None, "text/plain", b"holiday"),
+            ("photo", Some("beach.jpg"), "image/jpeg", &file),
+            ("empty", Some("empty.txt"), "text/plain", b""),
+        ]);
+
+        let (status, json) = send(&app, "/upload", body.clone()).await;
+        assert_eq!(status, StatusCode::OK, "{json}");
+        assert_eq!(
+            json["data"],
+            serde_json::json!(["title:-:7", "photo:beach.jpg:5000", "empty:empty.txt:0"])
+        );
+
+        // Routes without the layer keep the global body limit
+        let (status, _) = send(&app, "/plain", body).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_violations_name_the_part() {
+        let app = app(|config| {
+            config.upload.upload_max_file_size_bytes = 4096;
+            config.upload.upload_max_files = 2;
+            config.upload.upload_allowed_content_types =
+                Some("image/*, application/pdf".to_string());
+        });
+
+        let body = multipart(&[("scan", Some("scan.pdf"), "application/pdf", &[0; 5000])]);
+        let (status, json) = send(&app, "/upload", body).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            json["message"],
+            "Part \"scan\" exceeds the limit of 4096 bytes (UPLOAD_MAX_FILE_SIZE_BYTES)"
+        );
+
+        let body = multipart(&[("script", Some("run.sh"), "text/x-sh", b"echo")]);
+        let (status, json) = send(&app, "/upload", body).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert!(
+            json["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("Part \"script\"")
+        );
+
+        let body = multipart(&[
+            ("a", Some("a.png"), "image/png", b"a"),
+            ("b", Some("b.png"), "image/png", b"b"),
+            ("c", Some("c.png"), "image/png", b"c"),
+        ]);
+        let (status, json) = send(&app, "/upload", body).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            json["message"],
+            "Part \"c\" exceeds the limit of 2 files (UPLOAD_MAX_FILES)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_and_non_multipart_bodies() {
+        let app = app(|_| {});
+        let (status, json) = send(&app, "/upload", b"--X-BOUNDARY\r\nno end".to_vec()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(json["message"], "Invalid multipart body");
+
+        let response = app
+            .oneshot(
+                Request::post("/upload")
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn test_header_params_unquote() {
+        let headers = PartHeaders::parse(
+            "content-disposition: form-data; name=\"doc\"; filename=\"a \\\"b\\\"; c.txt\"\r\n\
+             Content-Type: text/plain",
+        );
+        assert_eq!(headers.name.as_deref(), Some("doc"));
+        assert_eq!(headers.file_name.as_deref(), Some("a \"b\"; c.txt"));
+        assert_eq!(headers.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(
+            parse_boundary("multipart/form-data; charset=utf-8; boundary=abc").as_deref(),
+            Some("abc")
+        );
+        assert_eq!(parse_boundary("text/plain; boundary=abc"), None);
+    }
+}
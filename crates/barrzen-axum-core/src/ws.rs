@@ -0,0 +1,472 @@
+//! WebSocket connections tied to the app lifecycle
+//!
+//! [`WsSession`] wraps axum's `WebSocketUpgrade`. The handler gets a
+//! [`WsSender`] and a [`WsReceiver`] while the session pings the client every
+//! `WS_PING_INTERVAL_SECONDS`, closes the connection after
+//! `WS_IDLE_TIMEOUT_SECONDS` without messages, and closes it with a
+//! going-away frame (1001) when shutdown starts. Upgraded connections outlive
+//! the HTTP server, so without that they would hold the process open.
+//!
+//! The upgrade response is sent at once, so the request timeout and body
+//! limit only cover the handshake.
+
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use axum::{
+    body::Bytes,
+    extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade, close_code},
+    response::Response,
+};
+use tokio::{
+    sync::mpsc,
+    time::{Instant, Interval, MissedTickBehavior},
+};
+
+use crate::{
+    app_context::AppContext,
+    handlers::{HealthCheck, ReadyChecker},
+    tasks::CancellationToken,
+};
+
+/// Ping interval outside the built app
+const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
+/// Idle timeout outside the built app
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_mins(5);
+/// Messages buffered each way between the socket and the handler
+const CHANNEL_CAPACITY: usize = 32;
+
+/// Number of open WebSocket connections; clones share the count
+#[derive(Debug, Clone, Default)]
+pub struct WsConnections(Arc<AtomicUsize>);
+
+impl WsConnections {
+    /// Connections currently open
+    #[must_use]
+    pub fn active(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn open(&self) -> ConnectionGuard {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard(self.clone())
+    }
+}
+
+/// Counts a connection until dropped
+struct ConnectionGuard(WsConnections);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Reports the open connections in /readyz, never failing
+///
+/// ```rust,ignore
+/// let builder = AppBuilder::new(config, build).route("/ws", get(chat));
+/// let checker = WsHealthChecker::new(builder.ws_connections());
+/// let builder = builder.with_ready_checker(checker);
+/// ```
+#[derive(Debug, Clone)]
+pub struct WsHealthChecker {
+    connections: WsConnections,
+}
+
+impl WsHealthChecker {
+    #[must_use]
+    pub fn new(connections: WsConnections) -> Self {
+        Self { connections }
+    }
+}
+
+#[async_trait::async_trait]
+impl ReadyChecker for WsHealthChecker {
+    async fn ready_checks(&self) -> Vec<HealthCheck> {
+        let mut check = HealthCheck::ok("websocket").non_critical();
+        check.message = Some(format!("active_connections={}", self.connections.active()));
+        vec![check]
+    }
+}
+
+/// The socket was closed; the message was not sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("WebSocket connection closed")]
+pub struct WsClosed;
+
+/// Sending half handed to the session handler
+#[derive(Debug, Clone)]
+pub struct WsSender {
+    tx: mpsc::Sender<Message>,
+}
+
+impl WsSender {
+    /// Send `message` to the client
+    ///
+    /// # Errors
+    /// Returns [`WsClosed`] once the connection is closed.
+    pub async fn send(&self, message: Message) -> Result<(), WsClosed> {
+        self.tx.send(message).await.map_err(|_| WsClosed)
+    }
+
+    /// Send a text message
+    ///
+    /// # Errors
+    /// Returns [`WsClosed`] once the connection is closed.
+    pub async fn send_text(&self, text: impl Into<String>) -> Result<(), WsClosed> {
+        self.send(Message::Text(text.into().into())).await
+    }
+}
+
+/// Receiving half handed to the session handler
+///
+/// Yields text and binary messages; pings, pongs and close frames are
+/// handled by the session.
+#[derive(Debug)]
+pub struct WsReceiver {
+    rx: mpsc::Receiver<Message>,
+}
+
+impl WsReceiver {
+    /// Next message from the client, `None` once the connection is closed
+    pub async fn recv(&mut self) -> Option<Message> {
+        self.rx.recv().await
+    }
+}
+
+/// WebSocket upgrade with pings, idle timeout and shutdown close
+///
+/// ```rust,ignore
+/// async fn echo(ws: WebSocketUpgrade) -> Response {
+///     WsSession::new(ws).on_upgrade(|sender, mut receiver| async move {
+///         while let Some(message) = receiver.recv().await {
+///             if sender.send(message).await.is_err() {
+///                 break;
+///             }
+///         }
+///     })
+/// }
+/// ```
+pub struct WsSession {
+    upgrade: WebSocketUpgrade,
+    ping_interval: Option<Duration>,
+    idle_timeout: Option<Duration>,
+}
+
+impl WsSession {
+    #[must_use]
+    pub fn new(upgrade: WebSocketUpgrade) -> Self {
+        Self {
+            upgrade,
+            ping_interval: None,
+            idle_timeout: None,
+        }
+    }
+
+    /// Override `WS_PING_INTERVAL_SECONDS` for this connection (zero disables)
+    #[must_use]
+    pub fn ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = Some(interval);
+        self
+    }
+
+    /// Override `WS_IDLE_TIMEOUT_SECONDS` for this connection (zero disables)
+    #[must_use]
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Accept the upgrade and run `handler` on the connection
+    ///
+    /// The connection is closed when `handler` returns. Once the session
+    /// closes it (idle, shutdown or the client left), `recv` returns `None`
+    /// and `send` fails, so the handler should return then.
+    pub fn on_upgrade<F, Fut>(self, handler: F) -> Response
+    where
+        F: FnOnce(WsSender, WsReceiver) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        // Read while still inside the request's scope
+        let context = AppContext::current();
+        let config = context.as_ref().map(|context| &context.config.ws);
+        let settings = Settings {
+            ping_interval: self.ping_interval.unwrap_or_else(|| {
+                config.map_or(DEFAULT_PING_INTERVAL, |ws| {
+                    Duration::from_secs(ws.ws_ping_interval_seconds)
+                })
+            }),
+            idle_timeout: self.idle_timeout.unwrap_or_else(|| {
+                config.map_or(DEFAULT_IDLE_TIMEOUT, |ws| {
+                    Duration::from_secs(ws.ws_idle_timeout_seconds)
+                })
+            }),
+            shutdown: context
+                .as_ref()
+                .map_or_else(CancellationToken::new, |context| context.shutdown.clone()),
+            connections: context
+                .map(|context| context.ws_connections)
+                .unwrap_or_default(),
+        };
+
+        self.upgrade
+            .on_upgrade(move |socket| run(socket, settings, handler))
+    }
+}
+
+struct Settings {
+    ping_interval: Duration,
+    idle_timeout: Duration,
+    shutdown: CancellationToken,
+    connections: WsConnections,
+}
+
+/// Relay messages between the socket and the handler until either side ends
+async fn run<F, Fut>(mut socket: WebSocket, settings: Settings, handler: F)
+where
+    F: FnOnce(WsSender, WsReceiver) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let _guard = settings.connections.open();
+    let (outgoing_tx, mut outgoing) = mpsc::channel(CHANNEL_CAPACITY);
+    let (incoming, incoming_rx) = mpsc::channel(CHANNEL_CAPACITY);
+    let handler = tokio::spawn(handler(
+        WsSender { tx: outgoing_tx },
+        WsReceiver { rx: incoming_rx },
+    ));
+
+    let mut ping = (!settings.ping_interval.is_zero()).then(|| {
+        let start = Instant::now() + settings.ping_interval;
+        let mut ping = tokio::time::interval_at(start, settings.ping_interval);
+        ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ping
+    });
+    let idle_enabled = !settings.idle_timeout.is_zero();
+    let idle = tokio::time::sleep(settings.idle_timeout);
+    tokio::pin!(idle);
+
+    let close = loop {
+        tokio::select! {
+            () = settings.shutdown.cancelled() => {
+                break Some(close_frame(close_code::AWAY, "server shutting down"));
+            }
+            () = &mut idle, if idle_enabled => {
+                break Some(close_frame(close_code::NORMAL, "idle timeout"));
+            }
+            () = tick(&mut ping), if ping.is_some() => {
+                if socket.send(Message::Ping(Bytes::new())).await.is_err() {
+                    break None;
+                }
+            }
+            message = outgoing.recv() => {
+                let Some(message) = message else {
+                    // The handler returned
+                    break Some(close_frame(close_code::NORMAL, ""));
+                };
+                idle.as_mut().reset(Instant::now() + settings.idle_timeout);
+                if socket.send(message).await.is_err() {
+                    break None;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(message @ (Message::Text(_) | Message::Binary(_)))) => {
+                    idle.as_mut().reset(Instant::now() + settings.idle_timeout);
+                    // A handler no longer reading drops what the client sends
+                    let _ = incoming.send(message).await;
+                }
+                // Pongs to client pings are sent by axum
+                Some(Ok(Message::Ping(_) | Message::Pong(_))) => {}
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break None,
+            },
+        }
+    };
+
+    if let Some(frame) = close {
+        let _ = socket.send(Message::Close(Some(frame))).await;
+    }
+    // Nothing can reach the client anymore
+    handler.abort();
+}
+
+async fn tick(ping: &mut Option<Interval>) {
+    match ping {
+        Some(ping) => {
+            ping.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+fn close_frame(code: u16, reason: &'static str) -> CloseFrame {
+    CloseFrame {
+        code,
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::tungstenite::{self, protocol::frame::coding::CloseCode};
+
+    use super::*;
+    use crate::{AppBuilder, BuildInfo, test_support::test_config};
+
+    type Client = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    async fn echo(ws: WebSocketUpgrade) -> Response {
+        WsSession::new(ws).on_upgrade(|sender, mut receiver| async move {
+            while let Some(message) = receiver.recv().await {
+                if sender.send(message).await.is_err() {
+                    break;
+                }
+            }
+        })
+    }
+
+    /// Serve an echo endpoint until `shutdown` resolves
+    async fn serve(
+        config: crate::Config,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> (String, WsConnections, tokio::task::JoinHandle<()>) {
+        let builder = AppBuilder::new(
+            config,
+            BuildInfo::new("test", "1.0.0", None, "1.75.0", None),
+        )
+        .route("/echo", axum::routing::get(echo));
+        let connections = builder.ws_connections();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/echo", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let serve = builder
+                .with_shutdown_signal(shutdown)
+                .serve_with_listener(listener);
+            Box::pin(serve).await.unwrap();
+        });
+        (url, connections, server)
+    }
+
+    async fn next(client: &mut Client) -> tungstenite::Message {
+        tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn wait_for(connections: &WsConnections, expected: usize) {
+        for _ in 0..100 {
+            if connections.active() == expected {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(connections.active(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_echo_outlives_the_request_timeout() {
+        // HTTP_REQUEST_TIMEOUT_SECONDS is 1 in the test config
+        let (url, connections, _server) = serve(test_config(), std::future::pending()).await;
+
+        let (mut client, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        wait_for(&connections, 1).await;
+        assert_eq!(
+            WsHealthChecker::new(connections.clone())
+                .ready_checks()
+                .await[0]
+                .message
+                .as_deref(),
+            Some("active_connections=1")
+        );
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        client
+            .send(tungstenite::Message::text("hello"))
+            .await
+            .unwrap();
+        assert_eq!(next(&mut client).await, tungstenite::Message::text("hello"));
+
+        client.close(None).await.unwrap();
+        wait_for(&connections, 0).await;
+    }
+
+    #[tokio::test]
+    async fn test_ping_pong() {
+        let mut config = test_config();
+        config.ws.ws_ping_interval_seconds = 1;
+        let (url, _connections, _server) = serve(config, std::future::pending()).await;
+        let (mut client, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+
+        // The server pings on its interval
+        assert!(matches!(
+            next(&mut client).await,
+            tungstenite::Message::Ping(_)
+        ));
+
+        // and answers the client's pings
+        client
+            .send(tungstenite::Message::Ping(Bytes::from_static(
+                b"are you there",
+            )))
+            .await
+            .unwrap();
+        loop {
+            match next(&mut client).await {
+                tungstenite::Message::Pong(payload) => {
+                    assert_eq!(payload, Bytes::from_static(b"are you there"));
+                    break;
+                }
+                tungstenite::Message::Ping(_) => {}
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_idle_connection_is_closed() {
+        let mut config = test_config();
+        config.ws.ws_idle_timeout_seconds = 1;
+        let (url, connections, _server) = serve(config, std::future::pending()).await;
+        let (mut client, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+
+        let tungstenite::Message::Close(Some(frame)) = next(&mut client).await else {
+            panic!("expected a close frame");
+        };
+        assert_eq!(frame.code, CloseCode::Normal);
+        assert_eq!(frame.reason.as_str(), "idle timeout");
+        wait_for(&connections, 0).await;
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_with_going_away() {
+        let (trigger, signal) = tokio::sync::oneshot::channel::<()>();
+        let (url, connections, server) = serve(test_config(), async {
+            let _ = signal.await;
+        })
+        .await;
+        let (mut client, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        wait_for(&connections, 1).await;
+
+        trigger.send(()).unwrap();
+        let tungstenite::Message::Close(Some(frame)) = next(&mut client).await else {
+            panic!("expected a close frame");
+        };
+        assert_eq!(frame.code, CloseCode::Away);
+        wait_for(&connections, 0).await;
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,506 @@
+//! Message broker abstraction
+//!
+//! [`Infra::broker`](crate::Infra::broker) is a [`Broker`] chosen by
+//! `BROKER_BACKEND`, so application code publishes and subscribes without
+//! naming the client library:
+//!
+//! ```ignore
+//! let broker = infra.broker.clone().context("FEATURE_BROKER=false")?;
+//! broker.publish("orders.created", serde_json::to_vec(&order)?).await?;
+//!
+//! let mut messages = broker.subscribe("orders.*").await?;
+//! while let Some(message) = messages.next().await {
+//!     // ...
+//! }
+//! ```
+//!
+//! Subjects follow the NATS conventions: `.` separates tokens, `*` matches
+//! one token and a trailing `>` matches the rest. With the `test-util`
+//! feature, [`MockBroker`] delivers in memory for unit tests.
+
+use std::{pin::Pin, sync::Arc, time::Duration};
+
+use futures_util::Stream;
+
+/// Message received from a subscription or as a reply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokerMessage {
+    pub subject: String,
+    pub payload: Vec<u8>,
+    /// Subject to publish the response to, set on requests
+    pub reply: Option<String>,
+}
+
+/// Messages of one subscription, ending when the connection is closed
+pub type MessageStream = Pin<Box<dyn Stream<Item = BrokerMessage> + Send>>;
+
+/// Failed request/reply
+///
+/// [`Broker::request`] reports `Timeout` and `NoResponders` inside its
+/// `anyhow::Error`; `request_json` returns them as is.
+#[derive(Debug, thiserror::Error)]
+pub enum RequestError {
+    #[error("Request to {subject} timed out")]
+    Timeout { subject: String },
+    #[error("No responders on {subject}")]
+    NoResponders { subject: String },
+    #[error("Failed to encode request: {0}")]
+    Encode(#[source] serde_json::Error),
+    #[error(transparent)]
+    Decode(#[from] crate::broker_json::DecodeError),
+    #[error(transparent)]
+    Broker(anyhow::Error),
+}
+
+/// Publish/subscribe and request/reply over a message broker
+#[async_trait::async_trait]
+pub trait Broker: Send + Sync {
+    /// Check that the broker is reachable
+    async fn ping(&self) -> anyhow::Result<()>;
+
+    async fn publish(&self, subject: &str, payload: Vec<u8>) -> anyhow::Result<()>;
+
+    /// Receive the messages published on `subject` from now on
+    async fn subscribe(&self, subject: &str) -> anyhow::Result<MessageStream>;
+
+    /// Publish `payload` and wait up to `timeout` for one reply
+    ///
+    /// Fails right away when nobody subscribes to `subject`. Both cases are
+    /// reported as a [`RequestError`].
+    async fn request(
+        &self,
+        subject: &str,
+        payload: Vec<u8>,
+        timeout: Duration,
+    ) -> anyhow::Result<BrokerMessage>;
+}
+
+/// [`Broker`] over a NATS client (`BROKER_BACKEND=nats`)
+#[cfg(feature = "nats")]
+#[derive(Clone)]
+pub struct NatsBroker {
+    client: async_nats::Client,
+}
+
+#[cfg(feature = "nats")]
+impl NatsBroker {
+    #[must_use]
+    pub fn new(client: async_nats::Client) -> Self {
+        Self { client }
+    }
+
+    /// Connect to `url`, which may list several servers separated by commas
+    ///
+    /// # Errors
+    /// Returns error if no server can be reached.
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        use anyhow::Context;
+
+        let client = async_nats::connect(url)
+            .await
+            .context("Failed to connect to NATS")?;
+        Ok(Self::new(client))
+    }
+
+    /// The underlying client, for features outside [`Broker`] (e.g. `JetStream`)
+    #[must_use]
+    pub fn client(&self) -> &async_nats::Client {
+        &self.client
+    }
+}
+
+#[cfg(feature = "nats")]
+fn from_nats(message: async_nats::Message) -> BrokerMessage {
+    BrokerMessage {
+        subject: message.subject.to_string(),
+        payload: message.payload.to_vec(),
+        reply: message.reply.map(|reply| reply.to_string()),
+    }
+}
+
+#[cfg(feature = "nats")]
+#[async_trait::async_trait]
+impl Broker for NatsBroker {
+    async fn ping(&self) -> anyhow::Result<()> {
+        let state = self.client.connection_state();
+        anyhow::ensure!(
+            matches!(state, async_nats::connection::State::Connected),
+            "NATS connection is {state:?}"
+        );
+        // Round trip to the server
+        self.client.flush().await?;
+        Ok(())
+    }
+
+    async fn publish(&self, subject: &str, payload: Vec<u8>) -> anyhow::Result<()> {
+        self.client
+            .publish(subject.to_string(), payload.into())
+            .await?;
+        Ok(())
+    }
+
+    async fn subscribe(&self, subject: &str) -> anyhow::Result<MessageStream> {
+        use futures_util::StreamExt;
+
+        let subscriber = self.client.subscribe(subject.to_string()).await?;
+        Ok(Box::pin(subscriber.map(from_nats)))
+    }
+
+    async fn request(
+        &self,
+        subject: &str,
+        payload: Vec<u8>,
+        timeout: Duration,
+    ) -> anyhow::Result<BrokerMessage> {
+        let request = async_nats::Request::new()
+            .payload(payload.into())
+            .timeout(Some(timeout));
+        let reply = self
+            .client
+            .send_request(subject.to_string(), request)
+            .await
+            .map_err(|e| {
+                let subject = subject.to_string();
+                match e.kind() {
+                    async_nats::client::RequestErrorKind::TimedOut => {
+                        RequestError::Timeout { subject }.into()
+                    }
+                    async_nats::client::RequestErrorKind::NoResponders => {
+                        RequestError::NoResponders { subject }.into()
+                    }
+                    async_nats::client::RequestErrorKind::Other => anyhow::Error::new(e),
+                }
+            })?;
+        Ok(from_nats(reply))
+    }
+}
+
+/// Whether `subject` is covered by the subscription `pattern`
+#[cfg_attr(not(feature = "test-util"), allow(dead_code))]
+pub(crate) fn subject_matches(pattern: &str, subject: &str) -> bool {
+    let mut subject_tokens = subject.split('.');
+    for token in pattern.split('.') {
+        if token == ">" {
+            return subject_tokens.next().is_some();
+        }
+        match subject_tokens.next() {
+            Some(actual) if token == "*" || token == actual => {}
+            _ => return false,
+        }
+    }
+    subject_tokens.next().is_none()
+}
+
+/// [`AuditSink`](barrzen_axum_core::AuditSink) publishing each record's JSON
+/// on a subject (`AUDIT_BROKER_SUBJECT`)
+///
+/// Installed by [`InfraAppBuilderExt::with_infra`](crate::InfraAppBuilderExt)
+/// from [`Infra::audit_sink`](crate::Infra::audit_sink).
+pub struct BrokerAuditSink {
+    broker: Arc<dyn Broker>,
+    subject: String,
+}
+
+impl BrokerAuditSink {
+    #[must_use]
+    pub fn new(broker: Arc<dyn Broker>, subject: impl Into<String>) -> Self {
+        Self {
+            broker,
+            subject: subject.into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl barrzen_axum_core::AuditSink for BrokerAuditSink {
+    async fn write(
+        &self,
+        _record: &barrzen_axum_core::AuditRecord,
+        line: &str,
+    ) -> anyhow::Result<()> {
+        self.broker
+            .publish(&self.subject, line.as_bytes().to_vec())
+            .await
+    }
+}
+
+#[cfg(feature = "test-util")]
+pub use mock::MockBroker;
+
+#[cfg(feature = "test-util")]
+mod mock {
+    use std::sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    };
+
+    use tokio::sync::mpsc;
+
+    use super::{Broker, BrokerMessage, Duration, MessageStream, RequestError, subject_matches};
+
+    /// In-memory [`Broker`] for tests (`test-util` feature)
+    ///
+    /// Delivers to the subscriptions of the same instance with the NATS
+    /// subject rules, and keeps every published message for assertions.
+    #[derive(Default)]
+    pub struct MockBroker {
+        subscriptions: Mutex<Vec<(String, mpsc::UnboundedSender<BrokerMessage>)>>,
+        published: Mutex<Vec<BrokerMessage>>,
+        inboxes: AtomicU64,
+    }
+
+    impl MockBroker {
+        #[must_use]
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Every message published so far, including replies
+        #[must_use]
+        pub fn published(&self) -> Vec<BrokerMessage> {
+            self.published
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        }
+
+        /// Deliver `message` to the matching subscriptions, returning how
+        /// many received it
+        fn deliver(&self, message: &BrokerMessage) -> usize {
+            self.published
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(message.clone());
+            let mut subscriptions = self
+                .subscriptions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            // Dropped streams close their receiver
+            subscriptions.retain(|(_, sender)| !sender.is_closed());
+            subscriptions
+                .iter()
+                .filter(|(pattern, _)| subject_matches(pattern, &message.subject))
+                .filter(|(_, sender)| sender.send(message.clone()).is_ok())
+                .count()
+        }
+
+        fn open(&self, subject: &str) -> mpsc::UnboundedReceiver<BrokerMessage> {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            self.subscriptions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((subject.to_string(), sender));
+            receiver
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Broker for MockBroker {
+        async fn ping(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn publish(&self, subject: &str, payload: Vec<u8>) -> anyhow::Result<()> {
+            self.deliver(&BrokerMessage {
+                subject: subject.to_string(),
+                payload,
+                reply: None,
+            });
+            Ok(())
+        }
+
+        async fn subscribe(&self, subject: &str) -> anyhow::Result<MessageStream> {
+            let mut receiver = self.open(subject);
+            Ok(Box::pin(futures_util::stream::poll_fn(move |cx| {
+                receiver.poll_recv(cx)
+            })))
+        }
+
+        async fn request(
+            &self,
+            subject: &str,
+            payload: Vec<u8>,
+            timeout: Duration,
+        ) -> anyhow::Result<BrokerMessage> {
+            let inbox = format!("_INBOX.{}", self.inboxes.fetch_add(1, Ordering::Relaxed));
+            let mut replies = self.open(&inbox);
+            let receivers = self.deliver(&BrokerMessage {
+                subject: subject.to_string(),
+                payload,
+                reply: Some(inbox),
+            });
+            let subject = subject.to_string();
+            if receivers == 0 {
+                return Err(RequestError::NoResponders { subject }.into());
+            }
+            match tokio::time::timeout(timeout, replies.recv()).await {
+                Ok(Some(reply)) => Ok(reply),
+                // The inbox is only closed with the broker
+                Ok(None) | Err(_) => Err(RequestError::Timeout { subject }.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subject_matching() {
+        assert!(subject_matches("orders.created", "orders.created"));
+        assert!(subject_matches("orders.*", "orders.created"));
+        assert!(subject_matches("orders.>", "orders.eu.created"));
+        assert!(subject_matches("*.created", "orders.created"));
+        assert!(!subject_matches("orders.*", "orders.eu.created"));
+        assert!(!subject_matches("orders.>", "orders"));
+        assert!(!subject_matches("orders.created", "orders"));
+        assert!(!subject_matches("orders", "orders.created"));
+    }
+
+    /// Behaviour every [`Broker`] must share
+    #[cfg(any(feature = "test-util", feature = "nats"))]
+    mod suite {
+        use std::sync::Arc;
+
+        use futures_util::StreamExt;
+
+        use super::super::*;
+
+        const RECEIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+        async fn next(messages: &mut MessageStream) -> BrokerMessage {
+            tokio::time::timeout(RECEIVE_TIMEOUT, messages.next())
+                .await
+                .unwrap()
+                .unwrap()
+        }
+
+        /// Subjects of one run, so runs against a shared server don't mix
+        fn prefix() -> String {
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos();
+            format!("test{nanos}")
+        }
+
+        pub(super) async fn run(broker: Arc<dyn Broker>) {
+            broker.ping().await.unwrap();
+            publish_subscribe(broker.as_ref()).await;
+            wildcards(broker.as_ref()).await;
+            request_reply(Arc::clone(&broker)).await;
+            request_without_responders(broker.as_ref()).await;
+        }
+
+        async fn publish_subscribe(broker: &dyn Broker) {
+            let subject = format!("{}.orders.created", prefix());
+            let mut messages = broker.subscribe(&subject).await.unwrap();
+            broker.publish(&subject, b"42".to_vec()).await.unwrap();
+
+            let message = next(&mut messages).await;
+            assert_eq!(message.subject, subject);
+            assert_eq!(message.payload, b"42");
+            assert_eq!(message.reply, None);
+        }
+
+        async fn wildcards(broker: &dyn Broker) {
+            let prefix = prefix();
+            let mut one = broker.subscribe(&format!("{prefix}.*")).await.unwrap();
+            let mut rest = broker.subscribe(&format!("{prefix}.>")).await.unwrap();
+            broker
+                .publish(&format!("{prefix}.eu.created"), b"1".to_vec())
+                .await
+                .unwrap();
+            broker
+                .publish(&format!("{prefix}.created"), b"2".to_vec())
+                .await
+                .unwrap();
+
+            assert_eq!(next(&mut rest).await.payload, b"1");
+            assert_eq!(next(&mut rest).await.payload, b"2");
+            // The deeper subject is not one token
+            assert_eq!(next(&mut one).await.payload, b"2");
+        }
+
+        async fn request_reply(broker: Arc<dyn Broker>) {
+            let subject = format!("{}.echo", prefix());
+            let mut requests = broker.subscribe(&subject).await.unwrap();
+            let responder = Arc::clone(&broker);
+            let handle = tokio::spawn(async move {
+                let request = next(&mut requests).await;
+                let mut payload = b"echo:".to_vec();
+                payload.extend(request.payload);
+                responder
+                    .publish(&request.reply.unwrap(), payload)
+                    .await
+                    .unwrap();
+            });
+
+            let reply = broker
+                .request(&subject, b"hi".to_vec(), RECEIVE_TIMEOUT)
+                .await
+                .unwrap();
+            assert_eq!(reply.payload, b"echo:hi");
+            handle.await.unwrap();
+        }
+
+        async fn request_without_responders(broker: &dyn Broker) {
+            let subject = format!("{}.nobody", prefix());
+            let error = broker
+                .request(&subject, Vec::new(), Duration::from_millis(500))
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(
+                    error.downcast_ref::<RequestError>(),
+                    Some(RequestError::NoResponders { .. })
+                ),
+                "{error}"
+            );
+        }
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_mock_broker() {
+        let broker = std::sync::Arc::new(MockBroker::new());
+        suite::run(std::sync::Arc::clone(&broker) as _).await;
+        assert!(
+            broker
+                .published()
+                .iter()
+                .any(|message| message.payload == b"echo:hi")
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_audit_records_are_published() {
+        use barrzen_axum_core::{AuditEvent, AuditLogger, AuditOutcome};
+
+        let broker = Arc::new(MockBroker::new());
+        let audit = AuditLogger::new();
+        audit.add_sink(BrokerAuditSink::new(broker.clone(), "audit.events"));
+        let event = AuditEvent::new("ada", "login", AuditOutcome::Success);
+        let record = audit.record(event).await.unwrap();
+
+        let published = broker.published();
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].subject, "audit.events");
+        let sent: barrzen_axum_core::AuditRecord =
+            serde_json::from_slice(&published[0].payload).unwrap();
+        assert_eq!(sent, record);
+    }
+
+    /// Runs against a real server when `NATS_TEST_URL` is set
+    #[cfg(feature = "nats")]
+    #[tokio::test]
+    async fn test_nats_broker() {
+        let Ok(url) = std::env::var("NATS_TEST_URL") else {
+            return;
+        };
+        let broker = NatsBroker::connect(&url).await.unwrap();
+        suite::run(std::sync::Arc::new(broker)).await;
+    }
+}
@@ -0,0 +1,320 @@
+//! JSON messages over any [`Broker`]
+//!
+//! Values are wrapped in an [`Envelope`] carrying the publish time and, when
+//! published while handling a request, its `x-request-id`, trace id and
+//! tenant:
+//!
+//! ```ignore
+//! use barrzen_axum_infra::BrokerJsonExt;
+//!
+//! broker.publish_json("orders.created", &order).await?;
+//!
+//! let mut orders = broker.subscribe_json::<Order>("orders.created").await?;
+//! while let Some(order) = orders.next().await {
+//!     match order {
+//!         Ok(order) => handle(order).await,
+//!         // The stream goes on after a malformed message
+//!         Err(e) => tracing::warn!(error = %e, "Skipping order"),
+//!     }
+//! }
+//! ```
+
+use std::{pin::Pin, time::Duration};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use futures_util::{Stream, StreamExt};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::broker::{Broker, BrokerMessage, RequestError};
+
+/// `content_type` of the envelopes published here
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Wrapper around every JSON message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    pub content_type: String,
+    pub published_at: DateTime<Utc>,
+    /// `x-request-id` of the request that published the message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Trace id of the publishing span (`otel` feature)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    /// Tenant of the request that published the message (`FEATURE_TENANT`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    pub data: T,
+    /// Subject to answer on with `publish_json` when received as a request
+    #[serde(skip)]
+    pub reply: Option<String>,
+}
+
+impl<T> Envelope<T> {
+    /// Wrap `data`, taking the correlation ids from the current request
+    #[must_use]
+    pub fn new(data: T) -> Self {
+        #[cfg(feature = "otel")]
+        let trace_id = barrzen_axum_core::trace_context::current_trace_id();
+        #[cfg(not(feature = "otel"))]
+        let trace_id = None;
+
+        Self {
+            content_type: JSON_CONTENT_TYPE.to_string(),
+            published_at: Utc::now(),
+            request_id: barrzen_axum_core::current_request_id(),
+            trace_id,
+            tenant_id: barrzen_axum_core::current_tenant().map(|tenant| tenant.0),
+            data,
+            reply: None,
+        }
+    }
+}
+
+/// Message that is not a JSON envelope of the expected type
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to decode message on {subject}: {source}")]
+pub struct DecodeError {
+    pub subject: String,
+    /// Raw payload, e.g. for a dead letter subject
+    pub payload: Vec<u8>,
+    #[source]
+    pub source: serde_json::Error,
+}
+
+/// Typed messages of one subscription
+pub type JsonStream<T> = Pin<Box<dyn Stream<Item = Result<T, DecodeError>> + Send>>;
+
+/// Decode `message` as an envelope of `T`
+///
+/// # Errors
+/// Returns [`DecodeError`] if the payload is not such an envelope.
+pub fn decode<T: DeserializeOwned>(message: BrokerMessage) -> Result<Envelope<T>, DecodeError> {
+    match serde_json::from_slice::<Envelope<T>>(&message.payload) {
+        Ok(envelope) => Ok(Envelope {
+            reply: message.reply,
+            ..envelope
+        }),
+        Err(source) => Err(DecodeError {
+            subject: message.subject,
+            payload: message.payload,
+            source,
+        }),
+    }
+}
+
+/// `publish_json`, `subscribe_json` and `request_json` on every [`Broker`]
+#[async_trait::async_trait]
+pub trait BrokerJsonExt: Broker {
+    /// Publish `value` in an [`Envelope`]
+    ///
+    /// # Errors
+    /// Returns error if `value` cannot be serialized or publishing fails.
+    async fn publish_json<T>(&self, subject: &str, value: &T) -> anyhow::Result<()>
+    where
+        T: Serialize + Sync,
+    {
+        let payload = serde_json::to_vec(&Envelope::new(value))
+            .with_context(|| format!("Failed to encode message for {subject}"))?;
+        self.publish(subject, payload).await
+    }
+
+    /// Envelopes published on `subject`, with their correlation ids
+    ///
+    /// A message that does not decode is yielded as an error and the
+    /// subscription goes on.
+    ///
+    /// # Errors
+    /// Returns error if the subscription cannot be created.
+    async fn subscribe_envelopes<T>(&self, subject: &str) -> anyhow::Result<JsonStream<Envelope<T>>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let messages = self.subscribe(subject).await?;
+        Ok(Box::pin(messages.map(decode::<T>)))
+    }
+
+    /// Values published on `subject`, see [`BrokerJsonExt::subscribe_envelopes`]
+    ///
+    /// # Errors
+    /// Returns error if the subscription cannot be created.
+    async fn subscribe_json<T>(&self, subject: &str) -> anyhow::Result<JsonStream<T>>
+    where
+        T: DeserializeOwned + Send + 'static,
+    {
+        let envelopes = self.subscribe_envelopes::<T>(subject).await?;
+        Ok(Box::pin(
+            envelopes.map(|envelope| envelope.map(|envelope| envelope.data)),
+        ))
+    }
+
+    /// Send `value` and wait up to `timeout` for a typed reply
+    ///
+    /// The responder answers with `publish_json` on [`Envelope::reply`].
+    ///
+    /// # Errors
+    /// Returns [`RequestError::Timeout`], [`RequestError::NoResponders`] or
+    /// [`RequestError::Decode`] for a reply that is not a `Res`.
+    async fn request_json<Req, Res>(
+        &self,
+        subject: &str,
+        value: &Req,
+        timeout: Duration,
+    ) -> Result<Res, RequestError>
+    where
+        Req: Serialize + Sync,
+        Res: DeserializeOwned,
+    {
+        let payload = serde_json::to_vec(&Envelope::new(value)).map_err(RequestError::Encode)?;
+        let reply = self.request(subject, payload, timeout).await.map_err(|e| {
+            e.downcast::<RequestError>()
+                .unwrap_or_else(RequestError::Broker)
+        })?;
+        Ok(decode::<Res>(reply)?.data)
+    }
+}
+
+impl<B: Broker + ?Sized> BrokerJsonExt for B {}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use std::sync::Arc;
+
+    use barrzen_axum_core::{TenantConfig, TenantLayer};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::MockBroker;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Order {
+        id: u64,
+        total: u32,
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let broker = MockBroker::new();
+        let mut envelopes = broker
+            .subscribe_envelopes::<Order>("orders.created")
+            .await
+            .unwrap();
+        let mut orders = broker
+            .subscribe_json::<Order>("orders.created")
+            .await
+            .unwrap();
+        let order = Order { id: 7, total: 42 };
+        broker.publish_json("orders.created", &order).await.unwrap();
+
+        assert_eq!(orders.next().await.unwrap().unwrap(), order);
+        let envelope = envelopes.next().await.unwrap().unwrap();
+        assert_eq!(envelope.content_type, JSON_CONTENT_TYPE);
+        assert_eq!(envelope.request_id, None);
+        assert_eq!(envelope.tenant_id, None);
+        assert!(envelope.published_at <= Utc::now());
+
+        let raw: serde_json::Value =
+            serde_json::from_slice(&broker.published()[0].payload).unwrap();
+        assert_eq!(raw["data"], serde_json::json!({ "id": 7, "total": 42 }));
+        assert!(raw.get("request_id").is_none());
+        assert!(raw.get("tenant_id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_tenant_of_publishing_request() {
+        let broker = Arc::new(MockBroker::new());
+        let mut envelopes = broker.subscribe_envelopes::<u32>("usage").await.unwrap();
+        let publisher = Arc::clone(&broker);
+        let app = axum::Router::new()
+            .route(
+                "/",
+                axum::routing::post(move || {
+                    let publisher = Arc::clone(&publisher);
+                    async move { publisher.publish_json("usage", &1).await.unwrap() }
+                }),
+            )
+            .layer(TenantLayer::from_config(&TenantConfig::default()).unwrap());
+
+        let request = axum::http::Request::post("/")
+            .header("x-tenant-id", "acme")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        app.oneshot(request).await.unwrap();
+
+        let envelope = envelopes.next().await.unwrap().unwrap();
+        assert_eq!(envelope.tenant_id.as_deref(), Some("acme"));
+    }
+
+    #[tokio::test]
+    async fn test_decode_failure_keeps_stream() {
+        let broker = MockBroker::new();
+        let mut orders = broker.subscribe_json::<Order>("orders.>").await.unwrap();
+        broker
+            .publish("orders.created", b"not json".to_vec())
+            .await
+            .unwrap();
+        broker
+            .publish_json("orders.created", &serde_json::json!({ "id": "seven" }))
+            .await
+            .unwrap();
+        broker
+            .publish_json("orders.created", &Order { id: 8, total: 1 })
+            .await
+            .unwrap();
+
+        let error = orders.next().await.unwrap().unwrap_err();
+        assert_eq!(error.subject, "orders.created");
+        assert_eq!(error.payload, b"not json");
+        assert!(orders.next().await.unwrap().is_err());
+        assert_eq!(orders.next().await.unwrap().unwrap().id, 8);
+    }
+
+    #[tokio::test]
+    async fn test_request_reply() {
+        let broker = Arc::new(MockBroker::new());
+        let mut requests = broker
+            .subscribe_envelopes::<Order>("orders.total")
+            .await
+            .unwrap();
+        let responder = Arc::clone(&broker);
+        tokio::spawn(async move {
+            let request = requests.next().await.unwrap().unwrap();
+            responder
+                .publish_json(&request.reply.unwrap(), &(request.data.total * 2))
+                .await
+                .unwrap();
+        });
+
+        let total: u32 = broker
+            .request_json(
+                "orders.total",
+                &Order { id: 1, total: 21 },
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+        assert_eq!(total, 42);
+    }
+
+    #[tokio::test]
+    async fn test_request_errors() {
+        let broker = MockBroker::new();
+        let timeout = Duration::from_millis(50);
+        let error = broker
+            .request_json::<_, u32>("orders.total", &1, timeout)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, RequestError::NoResponders { .. }),
+            "{error}"
+        );
+
+        let _requests = broker.subscribe("orders.total").await.unwrap();
+        let error = broker
+            .request_json::<_, u32>("orders.total", &1, timeout)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, RequestError::Timeout { .. }), "{error}");
+    }
+}
@@ -0,0 +1,349 @@
+//! Cache instrumentation
+//!
+//! [`Infra::init`](crate::Infra::init) wraps the cache in an
+//! [`InstrumentedCache`] counting hits, misses, sets, deletes and errors in
+//! atomics, cheap enough to stay on. The counts are available as a
+//! [`CacheStats`] snapshot from [`Infra::cache_stats`](crate::Infra::cache_stats)
+//! and as the hit rate in the `/readyz` cache check. With the `otel` feature
+//! and `FEATURE_OTEL_METRICS=true` they are also exported as the
+//! `cache.operations` counter, next to a `cache.operation.duration`
+//! histogram, both labeled with `cache.backend`.
+
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use crate::Cache;
+
+/// Operation counters of one cache
+#[derive(Debug)]
+pub struct CacheMetrics {
+    backend: String,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    sets: AtomicU64,
+    deletes: AtomicU64,
+    errors: AtomicU64,
+}
+
+/// Point-in-time copy of [`CacheMetrics`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub sets: u64,
+    pub deletes: u64,
+    /// Failed operations of any kind
+    pub errors: u64,
+}
+
+impl CacheStats {
+    /// Share of reads that found a value, `None` before the first read
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)] // a ratio, not an exact count
+    pub fn hit_rate(&self) -> Option<f64> {
+        let reads = self.hits + self.misses;
+        (reads > 0).then(|| self.hits as f64 / reads as f64)
+    }
+}
+
+impl CacheMetrics {
+    /// Counters for a cache labeled `backend` (e.g. `redis`)
+    #[must_use]
+    pub fn new(backend: impl Into<String>) -> Self {
+        Self {
+            backend: backend.into(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            sets: AtomicU64::new(0),
+            deletes: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+        }
+    }
+
+    #[must_use]
+    pub fn backend(&self) -> &str {
+        &self.backend
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            sets: self.sets.load(Ordering::Relaxed),
+            deletes: self.deletes.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Export the counters as the `cache.operations` observable counter
+    #[cfg(feature = "otel")]
+    pub(crate) fn register(self: &Arc<Self>, meter: &opentelemetry::metrics::Meter) {
+        use opentelemetry::KeyValue;
+
+        let metrics = Arc::clone(self);
+        meter
+            .u64_observable_counter("cache.operations")
+            .with_unit("{operation}")
+            .with_description("Cache operations by outcome")
+            .with_callback(move |observer| {
+                let stats = metrics.snapshot();
+                let backend = KeyValue::new("cache.backend", metrics.backend.clone());
+                for (operation, result, value) in [
+                    ("get", "hit", stats.hits),
+                    ("get", "miss", stats.misses),
+                    ("set", "ok", stats.sets),
+                    ("delete", "ok", stats.deletes),
+                ] {
+                    observer.observe(
+                        value,
+                        &[
+                            backend.clone(),
+                            KeyValue::new("cache.operation", operation),
+                            KeyValue::new("cache.result", result),
+                        ],
+                    );
+                }
+                observer.observe(
+                    stats.errors,
+                    &[backend, KeyValue::new("cache.result", "error")],
+                );
+            })
+            .build();
+    }
+}
+
+/// [`Cache`] recording every operation in [`CacheMetrics`]
+///
+/// `ping` is not counted.
+pub struct InstrumentedCache {
+    inner: Arc<dyn Cache + Send + Sync>,
+    metrics: Arc<CacheMetrics>,
+    #[cfg(feature = "otel")]
+    duration: Option<opentelemetry::metrics::Histogram<f64>>,
+}
+
+impl InstrumentedCache {
+    #[must_use]
+    pub fn new(inner: Arc<dyn Cache + Send + Sync>, metrics: Arc<CacheMetrics>) -> Self {
+        Self {
+            inner,
+            metrics,
+            #[cfg(feature = "otel")]
+            duration: None,
+        }
+    }
+
+    /// Also record operation latency in the `cache.operation.duration`
+    /// histogram (seconds)
+    #[cfg(feature = "otel")]
+    #[must_use]
+    pub fn with_meter(mut self, meter: &opentelemetry::metrics::Meter) -> Self {
+        self.duration = Some(
+            meter
+                .f64_histogram("cache.operation.duration")
+                .with_unit("s")
+                .with_description("Duration of cache operations")
+                .build(),
+        );
+        self
+    }
+
+    fn count<'a, T>(
+        &'a self,
+        result: &anyhow::Result<T>,
+        counter: impl FnOnce(&T) -> &'a AtomicU64,
+    ) {
+        let counter = match result {
+            Ok(value) => counter(value),
+            Err(_) => &self.metrics.errors,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "otel")]
+    fn observe(&self, operation: &'static str, elapsed: Duration) {
+        if let Some(duration) = &self.duration {
+            duration.record(
+                elapsed.as_secs_f64(),
+                &[
+                    opentelemetry::KeyValue::new("cache.backend", self.metrics.backend.clone()),
+                    opentelemetry::KeyValue::new("cache.operation", operation),
+                ],
+            );
+        }
+    }
+
+    #[cfg(not(feature = "otel"))]
+    #[allow(clippy::unused_self)]
+    fn observe(&self, _operation: &'static str, _elapsed: Duration) {}
+}
+
+#[async_trait::async_trait]
+impl Cache for InstrumentedCache {
+    async fn ping(&self) -> anyhow::Result<()> {
+        self.inner.ping().await
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let start = std::time::Instant::now();
+        let result = self.inner.get(key).await;
+        self.observe("get", start.elapsed());
+        self.count(&result, |value| {
+            if value.is_some() {
+                &self.metrics.hits
+            } else {
+                &self.metrics.misses
+            }
+        });
+        result
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> anyhow::Result<Option<(Vec<u8>, Option<Duration>)>> {
+        let start = std::time::Instant::now();
+        let result = self.inner.get_with_ttl(key).await;
+        self.observe("get", start.elapsed());
+        self.count(&result, |value| {
+            if value.is_some() {
+                &self.metrics.hits
+            } else {
+                &self.metrics.misses
+            }
+        });
+        result
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        let start = std::time::Instant::now();
+        let result = self.inner.set(key, value, ttl).await;
+        self.observe("set", start.elapsed());
+        self.count(&result, |()| &self.metrics.sets);
+        result
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<bool> {
+        let start = std::time::Instant::now();
+        let result = self.inner.set_if_absent(key, value, ttl).await;
+        self.observe("set_if_absent", start.elapsed());
+        self.count(&result, |_| &self.metrics.sets);
+        result
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        let start = std::time::Instant::now();
+        let result = self.inner.delete(key).await;
+        self.observe("delete", start.elapsed());
+        self.count(&result, |()| &self.metrics.deletes);
+        result
+    }
+}
+
+#[cfg(all(test, feature = "cache-moka"))]
+mod tests {
+    use barrzen_axum_core::ReadyChecker;
+
+    use super::*;
+    use crate::{Infra, MokaCache};
+
+    fn instrumented() -> (InstrumentedCache, Arc<CacheMetrics>) {
+        let metrics = Arc::new(CacheMetrics::new("moka"));
+        let inner = Arc::new(MokaCache::new(100, Duration::from_mins(1)));
+        (InstrumentedCache::new(inner, Arc::clone(&metrics)), metrics)
+    }
+
+    #[tokio::test]
+    async fn test_counts_known_sequence() {
+        let (cache, metrics) = instrumented();
+        assert_eq!(metrics.snapshot().hit_rate(), None);
+
+        cache.set("a", b"1".to_vec(), None).await.unwrap();
+        cache.set("b", b"2".to_vec(), None).await.unwrap();
+        for key in ["a", "b", "a", "missing"] {
+            cache.get(key).await.unwrap();
+        }
+        cache.delete("a").await.unwrap();
+        assert_eq!(cache.get("a").await.unwrap(), None);
+        cache.ping().await.unwrap();
+
+        let stats = metrics.snapshot();
+        assert_eq!(
+            stats,
+            CacheStats {
+                hits: 3,
+                misses: 2,
+                sets: 2,
+                deletes: 1,
+                errors: 0,
+            }
+        );
+        assert_eq!(stats.hit_rate(), Some(0.6));
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_counted() {
+        struct Failing;
+
+        #[async_trait::async_trait]
+        impl Cache for Failing {
+            async fn ping(&self) -> anyhow::Result<()> {
+                Ok(())
+            }
+            async fn get(&self, _key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+                anyhow::bail!("timeout")
+            }
+            async fn set(&self, _: &str, _: Vec<u8>, _: Option<Duration>) -> anyhow::Result<()> {
+                anyhow::bail!("timeout")
+            }
+            async fn set_if_absent(
+                &self,
+                _: &str,
+                _: Vec<u8>,
+                _: Option<Duration>,
+            ) -> anyhow::Result<bool> {
+                anyhow::bail!("timeout")
+            }
+            async fn delete(&self, _key: &str) -> anyhow::Result<()> {
+                Ok(())
+            }
+        }
+
+        let metrics = Arc::new(CacheMetrics::new("redis"));
+        let cache = InstrumentedCache::new(Arc::new(Failing), Arc::clone(&metrics));
+        assert!(cache.get("a").await.is_err());
+        assert!(cache.set("a", Vec::new(), None).await.is_err());
+        cache.delete("a").await.unwrap();
+
+        let stats = metrics.snapshot();
+        assert_eq!((stats.errors, stats.deletes, stats.hits), (2, 1, 0));
+        assert_eq!(stats.hit_rate(), None);
+    }
+
+    #[tokio::test]
+    async fn test_readiness_reports_hit_rate() {
+        let (cache, metrics) = instrumented();
+        cache.set("a", b"1".to_vec(), None).await.unwrap();
+        cache.get("a").await.unwrap();
+        cache.get("b").await.unwrap();
+        let infra = Infra {
+            cache: Some(Arc::new(cache)),
+            cache_metrics: Some(metrics),
+            ..Infra::default()
+        };
+
+        let checks = infra.ready_checks().await;
+        let check = checks.iter().find(|check| check.name == "cache").unwrap();
+        assert_eq!(check.status, "ok");
+        assert_eq!(check.message.as_deref(), Some("hit_rate=0.50"));
+        assert_eq!(infra.cache_stats().unwrap().hits, 1);
+    }
+}
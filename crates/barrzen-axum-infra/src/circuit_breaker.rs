@@ -0,0 +1,637 @@
+//! Circuit breaker for calls to remote infrastructure
+//!
+//! After `CB_FAILURE_THRESHOLD` consecutive failures the circuit opens and
+//! calls fail fast with [`CircuitOpen`] for `CB_OPEN_DURATION_MS`, instead of
+//! each one waiting out the connect timeout. Then up to `CB_HALF_OPEN_PROBES`
+//! calls are let through: that many successes close the circuit, a failure
+//! opens it again.
+//!
+//! The Redis cache is wrapped by [`Infra::init`](crate::Infra::init)
+//! ([`BreakerCache`]); the search client gets one through
+//! [`Infra::search_client`](crate::Infra::search_client). Their state is
+//! available from [`Infra::breakers`](crate::Infra::breakers).
+
+use std::{
+    fmt,
+    future::Future,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use barrzen_axum_core::CircuitBreakerConfig;
+
+/// State of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through
+    Closed,
+    /// Calls fail fast
+    Open,
+    /// A limited number of probe calls go through
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed => write!(f, "closed"),
+            Self::Open => write!(f, "open"),
+            Self::HalfOpen => write!(f, "half-open"),
+        }
+    }
+}
+
+/// Thresholds of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakerSettings {
+    /// Consecutive failures that open the circuit
+    pub failure_threshold: u32,
+    /// How long the circuit stays open
+    pub open_duration: Duration,
+    /// Concurrent probes, and successes needed to close, when half-open
+    pub half_open_probes: u32,
+}
+
+impl BreakerSettings {
+    /// Settings from `CB_FAILURE_THRESHOLD`, `CB_OPEN_DURATION_MS` and
+    /// `CB_HALF_OPEN_PROBES`
+    #[must_use]
+    pub fn from_config(config: &CircuitBreakerConfig) -> Self {
+        Self {
+            failure_threshold: config.cb_failure_threshold.max(1),
+            open_duration: Duration::from_millis(config.cb_open_duration_ms),
+            half_open_probes: config.cb_half_open_probes.max(1),
+        }
+    }
+}
+
+impl Default for BreakerSettings {
+    fn default() -> Self {
+        Self::from_config(&CircuitBreakerConfig::default())
+    }
+}
+
+/// Point-in-time view of a [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakerSnapshot {
+    pub state: CircuitState,
+    /// Failures since the last success
+    pub consecutive_failures: u32,
+    /// Times the circuit opened since startup
+    pub trips: u64,
+}
+
+/// A call rejected because the circuit is open
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{name} circuit is open")]
+pub struct CircuitOpen {
+    /// Name of the breaker, e.g. `cache`
+    pub name: String,
+}
+
+/// Error of a call through a [`CircuitBreaker`]
+#[derive(Debug, thiserror::Error)]
+pub enum BreakerError<E> {
+    /// Rejected without calling
+    #[error(transparent)]
+    Open(CircuitOpen),
+    /// The call itself failed
+    #[error(transparent)]
+    Inner(E),
+}
+
+impl BreakerError<anyhow::Error> {
+    /// Flatten into an `anyhow::Error`; an open circuit downcasts to
+    /// [`CircuitOpen`]
+    #[must_use]
+    pub fn into_anyhow(self) -> anyhow::Error {
+        match self {
+            Self::Open(open) => open.into(),
+            Self::Inner(error) => error,
+        }
+    }
+}
+
+type Clock = Arc<dyn Fn() -> Instant + Send + Sync>;
+
+/// Circuit breaker shared by the callers of one remote dependency
+pub struct CircuitBreaker {
+    name: String,
+    settings: BreakerSettings,
+    clock: Clock,
+    inner: Mutex<Inner>,
+    trips: AtomicU64,
+}
+
+struct Inner {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    probes_in_flight: u32,
+    probe_successes: u32,
+}
+
+impl CircuitBreaker {
+    #[must_use]
+    pub fn new(name: impl Into<String>, settings: BreakerSettings) -> Self {
+        Self::with_clock(name, settings, Arc::new(Instant::now))
+    }
+
+    /// Breaker named `name` with the `CB_*` settings
+    #[must_use]
+    pub fn from_config(name: impl Into<String>, config: &barrzen_axum_core::Config) -> Self {
+        Self::new(name, BreakerSettings::from_config(&config.circuit_breaker))
+    }
+
+    fn with_clock(name: impl Into<String>, settings: BreakerSettings, clock: Clock) -> Self {
+        Self {
+            name: name.into(),
+            settings,
+            clock,
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                probes_in_flight: 0,
+                probe_successes: 0,
+            }),
+            trips: AtomicU64::new(0),
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Current state; an open circuit whose open duration has passed is
+    /// reported half-open
+    #[must_use]
+    pub fn state(&self) -> CircuitState {
+        self.snapshot().state
+    }
+
+    #[must_use]
+    pub fn snapshot(&self) -> BreakerSnapshot {
+        let mut inner = self.lock();
+        self.refresh(&mut inner);
+        BreakerSnapshot {
+            state: inner.state,
+            consecutive_failures: inner.consecutive_failures,
+            trips: self.trips.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Run `call` unless the circuit is open
+    ///
+    /// # Errors
+    /// Returns [`BreakerError::Open`] without running `call` while the
+    /// circuit is open, or [`BreakerError::Inner`] with the error of `call`.
+    pub async fn call<T, E, F, Fut>(&self, call: F) -> Result<T, BreakerError<E>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let permit = self.acquire().map_err(BreakerError::Open)?;
+        let result = call().await;
+        permit.finish(result.is_ok());
+        result.map_err(BreakerError::Inner)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Move an open circuit to half-open once its time is up
+    fn refresh(&self, inner: &mut Inner) {
+        if inner.state != CircuitState::Open {
+            return;
+        }
+        let open_until = inner
+            .opened_at
+            .map(|opened_at| opened_at + self.settings.open_duration);
+        if open_until.is_some_and(|until| (self.clock)() < until) {
+            return;
+        }
+        inner.state = CircuitState::HalfOpen;
+        inner.probes_in_flight = 0;
+        inner.probe_successes = 0;
+        tracing::warn!(breaker = %self.name, "Circuit half-open, probing");
+    }
+
+    fn acquire(&self) -> Result<Permit<'_>, CircuitOpen> {
+        let mut inner = self.lock();
+        self.refresh(&mut inner);
+        let probe = match inner.state {
+            CircuitState::Closed => false,
+            CircuitState::HalfOpen if inner.probes_in_flight < self.settings.half_open_probes => {
+                inner.probes_in_flight += 1;
+                true
+            }
+            CircuitState::Open | CircuitState::HalfOpen => {
+                return Err(CircuitOpen {
+                    name: self.name.clone(),
+                });
+            }
+        };
+        Ok(Permit {
+            breaker: self,
+            probe,
+            finished: false,
+        })
+    }
+
+    fn record(&self, probe: bool, success: bool) {
+        let mut inner = self.lock();
+        if probe {
+            inner.probes_in_flight = inner.probes_in_flight.saturating_sub(1);
+        }
+        match inner.state {
+            CircuitState::Closed if success => inner.consecutive_failures = 0,
+            CircuitState::Closed => {
+                inner.consecutive_failures += 1;
+                if inner.consecutive_failures >= self.settings.failure_threshold {
+                    self.open(&mut inner);
+                }
+            }
+            // Calls started before the circuit opened do not count as probes
+            CircuitState::HalfOpen if !probe => {}
+            CircuitState::HalfOpen if success => {
+                inner.probe_successes += 1;
+                if inner.probe_successes >= self.settings.half_open_probes {
+                    inner.state = CircuitState::Closed;
+                    inner.consecutive_failures = 0;
+                    inner.opened_at = None;
+                    tracing::warn!(breaker = %self.name, "Circuit closed");
+                }
+            }
+            CircuitState::HalfOpen => {
+                inner.consecutive_failures += 1;
+                self.open(&mut inner);
+            }
+            CircuitState::Open => {}
+        }
+    }
+
+    fn open(&self, inner: &mut Inner) {
+        inner.state = CircuitState::Open;
+        inner.opened_at = Some((self.clock)());
+        let trips = self.trips.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::warn!(
+            breaker = %self.name,
+            consecutive_failures = inner.consecutive_failures,
+            open_ms = u64::try_from(self.settings.open_duration.as_millis()).unwrap_or(u64::MAX),
+            trips,
+            "Circuit open, failing fast"
+        );
+    }
+}
+
+impl fmt::Debug for CircuitBreaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("name", &self.name)
+            .field("settings", &self.settings)
+            .field("snapshot", &self.snapshot())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Admission of one call; a probe slot is released even if the call is
+/// dropped before it finishes
+struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: bool,
+    finished: bool,
+}
+
+impl Permit<'_> {
+    fn finish(mut self, success: bool) {
+        self.finished = true;
+        self.breaker.record(self.probe, success);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if !self.finished && self.probe {
+            let mut inner = self.breaker.lock();
+            inner.probes_in_flight = inner.probes_in_flight.saturating_sub(1);
+        }
+    }
+}
+
+/// [`Cache`](crate::Cache) failing fast while its circuit is open
+#[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+pub struct BreakerCache {
+    inner: Arc<dyn crate::Cache + Send + Sync>,
+    breaker: Arc<CircuitBreaker>,
+}
+
+#[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+impl BreakerCache {
+    #[must_use]
+    pub fn new(inner: Arc<dyn crate::Cache + Send + Sync>, breaker: Arc<CircuitBreaker>) -> Self {
+        Self { inner, breaker }
+    }
+}
+
+#[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+#[async_trait::async_trait]
+impl crate::Cache for BreakerCache {
+    async fn ping(&self) -> anyhow::Result<()> {
+        self.breaker
+            .call(|| self.inner.ping())
+            .await
+            .map_err(BreakerError::into_anyhow)
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.breaker
+            .call(|| self.inner.get(key))
+            .await
+            .map_err(BreakerError::into_anyhow)
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> anyhow::Result<Option<(Vec<u8>, Option<Duration>)>> {
+        self.breaker
+            .call(|| self.inner.get_with_ttl(key))
+            .await
+            .map_err(BreakerError::into_anyhow)
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        self.breaker
+            .call(|| self.inner.set(key, value, ttl))
+            .await
+            .map_err(BreakerError::into_anyhow)
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<bool> {
+        self.breaker
+            .call(|| self.inner.set_if_absent(key, value, ttl))
+            .await
+            .map_err(BreakerError::into_anyhow)
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.breaker
+            .call(|| self.inner.delete(key))
+            .await
+            .map_err(BreakerError::into_anyhow)
+    }
+}
+
+/// Meilisearch client whose calls fail fast while its circuit is open
+#[cfg(feature = "meilisearch")]
+#[derive(Clone)]
+pub struct BreakerSearch {
+    client: meilisearch_sdk::client::Client,
+    breaker: Arc<CircuitBreaker>,
+}
+
+#[cfg(feature = "meilisearch")]
+impl BreakerSearch {
+    #[must_use]
+    pub fn new(client: meilisearch_sdk::client::Client, breaker: Arc<CircuitBreaker>) -> Self {
+        Self { client, breaker }
+    }
+
+    /// The client itself, bypassing the breaker
+    #[must_use]
+    pub fn client(&self) -> &meilisearch_sdk::client::Client {
+        &self.client
+    }
+
+    #[must_use]
+    pub fn breaker(&self) -> &Arc<CircuitBreaker> {
+        &self.breaker
+    }
+
+    /// Run `call` with the client unless the circuit is open
+    ///
+    /// ```ignore
+    /// let hits = search
+    ///     .call(|client| async move {
+    ///         client.index("orders").search().with_query(q).execute::<Order>().await
+    ///     })
+    ///     .await?;
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`BreakerError::Open`] while the circuit is open, or the
+    /// error of `call`.
+    pub async fn call<T, F, Fut>(
+        &self,
+        call: F,
+    ) -> Result<T, BreakerError<meilisearch_sdk::errors::Error>>
+    where
+        F: FnOnce(meilisearch_sdk::client::Client) -> Fut,
+        Fut: Future<Output = Result<T, meilisearch_sdk::errors::Error>>,
+    {
+        self.breaker.call(|| call(self.client.clone())).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeClock(Arc<Mutex<Instant>>);
+
+    impl FakeClock {
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    fn breaker(half_open_probes: u32) -> (CircuitBreaker, FakeClock) {
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let clock = Arc::clone(&now);
+        let settings = BreakerSettings {
+            failure_threshold: 3,
+            open_duration: Duration::from_secs(30),
+            half_open_probes,
+        };
+        let breaker =
+            CircuitBreaker::with_clock("cache", settings, Arc::new(move || *clock.lock().unwrap()));
+        (breaker, FakeClock(now))
+    }
+
+    async fn succeed(breaker: &CircuitBreaker) -> Result<(), BreakerError<&'static str>> {
+        breaker.call(|| async { Ok(()) }).await
+    }
+
+    async fn fail(breaker: &CircuitBreaker) -> Result<(), BreakerError<&'static str>> {
+        breaker.call(|| async { Err("connection refused") }).await
+    }
+
+    #[tokio::test]
+    async fn test_open_half_open_closed() {
+        let (breaker, clock) = breaker(1);
+        for _ in 0..2 {
+            assert!(matches!(fail(&breaker).await, Err(BreakerError::Inner(_))));
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.snapshot().consecutive_failures, 2);
+
+        assert!(fail(&breaker).await.is_err());
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        // Fails fast without calling
+        let mut called = false;
+        let result: Result<(), BreakerError<&str>> = breaker
+            .call(|| {
+                called = true;
+                async { Ok(()) }
+            })
+            .await;
+        let Err(BreakerError::Open(open)) = result else {
+            panic!("call went through an open circuit");
+        };
+        assert_eq!(open.to_string(), "cache circuit is open");
+        assert!(!called);
+
+        clock.advance(Duration::from_secs(29));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+
+        succeed(&breaker).await.unwrap();
+        assert_eq!(
+            breaker.snapshot(),
+            BreakerSnapshot {
+                state: CircuitState::Closed,
+                consecutive_failures: 0,
+                trips: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_probe_reopens() {
+        let (breaker, clock) = breaker(1);
+        for _ in 0..3 {
+            let _ = fail(&breaker).await;
+        }
+        clock.advance(Duration::from_secs(30));
+        assert!(matches!(fail(&breaker).await, Err(BreakerError::Inner(_))));
+
+        let snapshot = breaker.snapshot();
+        assert_eq!(snapshot.state, CircuitState::Open);
+        assert_eq!(snapshot.trips, 2);
+        assert!(matches!(
+            succeed(&breaker).await,
+            Err(BreakerError::Open(_))
+        ));
+
+        // The open duration starts over from the failed probe
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    }
+
+    #[tokio::test]
+    async fn test_half_open_probe_limit() {
+        let (breaker, clock) = breaker(2);
+        for _ in 0..3 {
+            let _ = fail(&breaker).await;
+        }
+        clock.advance(Duration::from_secs(30));
+
+        let first = breaker.acquire().unwrap();
+        let second = breaker.acquire().unwrap();
+        assert!(breaker.acquire().is_err());
+
+        // A dropped probe frees its slot
+        drop(second);
+        let second = breaker.acquire().unwrap();
+
+        first.finish(true);
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        second.finish(true);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn test_success_resets_failures() {
+        let (breaker, _clock) = breaker(1);
+        for _ in 0..2 {
+            let _ = fail(&breaker).await;
+        }
+        succeed(&breaker).await.unwrap();
+        for _ in 0..2 {
+            let _ = fail(&breaker).await;
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert_eq!(breaker.snapshot().trips, 0);
+    }
+
+    #[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+    #[tokio::test]
+    async fn test_readiness_shows_open_circuit() {
+        use barrzen_axum_core::ReadyChecker;
+
+        struct Unreachable;
+
+        #[async_trait::async_trait]
+        impl crate::Cache for Unreachable {
+            async fn ping(&self) -> anyhow::Result<()> {
+                anyhow::bail!("connection refused")
+            }
+            async fn get(&self, _key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+                anyhow::bail!("connection refused")
+            }
+            async fn set(&self, _: &str, _: Vec<u8>, _: Option<Duration>) -> anyhow::Result<()> {
+                anyhow::bail!("connection refused")
+            }
+            async fn set_if_absent(
+                &self,
+                _: &str,
+                _: Vec<u8>,
+                _: Option<Duration>,
+            ) -> anyhow::Result<bool> {
+                anyhow::bail!("connection refused")
+            }
+            async fn delete(&self, _key: &str) -> anyhow::Result<()> {
+                anyhow::bail!("connection refused")
+            }
+        }
+
+        let settings = BreakerSettings {
+            failure_threshold: 1,
+            ..BreakerSettings::default()
+        };
+        let breaker = Arc::new(CircuitBreaker::new("cache", settings));
+        let infra = crate::Infra {
+            cache: Some(Arc::new(BreakerCache::new(
+                Arc::new(Unreachable),
+                Arc::clone(&breaker),
+            ))),
+            cache_breaker: Some(breaker),
+            ..crate::Infra::default()
+        };
+
+        let cache_check = |checks: Vec<barrzen_axum_core::HealthCheck>| {
+            checks
+                .into_iter()
+                .find(|check| check.name == "cache")
+                .unwrap()
+        };
+        let check = cache_check(infra.ready_checks().await);
+        assert_eq!(check.status, "fail");
+        assert_eq!(check.message.as_deref(), Some("connection refused"));
+
+        let check = cache_check(infra.ready_checks().await);
+        assert_eq!(check.status, "warn");
+        assert_eq!(check.message.as_deref(), Some("circuit open"));
+        assert_eq!(infra.breakers()[0].snapshot().trips, 1);
+    }
+}
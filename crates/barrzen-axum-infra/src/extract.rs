@@ -0,0 +1,298 @@
+//! Extractors for the initialized infrastructure
+//!
+//! Install the [`Infra`] once with [`InfraAppBuilderExt::with_infra`] (or an
+//! `Extension(infra)` layer on a plain router) and take the components in
+//! handlers:
+//!
+//! ```ignore
+//! async fn list_orders(ReadDb(db): ReadDb, CacheHandle(cache): CacheHandle) -> impl IntoResponse {
+//!     // ...
+//! }
+//!
+//! let app = AppBuilder::new(config, build_info)
+//!     .with_infra(infra.clone())
+//!     .route("/orders", get(list_orders))
+//!     .build();
+//! ```
+//!
+//! A component that was not initialized rejects the request with a 503
+//! naming the flag that is off. With the `db` feature, [`Tx`](crate::tx::Tx)
+//! gives the handler a request-scoped transaction.
+
+// Without a component feature there is nothing to extract
+#![cfg_attr(
+    not(any(
+        feature = "db",
+        feature = "cache-moka",
+        feature = "cache-redis",
+        feature = "meilisearch",
+        feature = "broker",
+        feature = "mailer"
+    )),
+    allow(dead_code, unused_imports)
+)]
+
+use axum::{Extension, extract::FromRequestParts, http::request::Parts};
+use barrzen_axum_core::{
+    AppBuilder,
+    response::{ApiError, extract_request_id},
+};
+
+use crate::Infra;
+
+/// Database connection (`db` feature, `FEATURE_DB=true`)
+#[cfg(feature = "db")]
+#[derive(Clone)]
+pub struct Db(pub sea_orm::DatabaseConnection);
+
+/// Read replica, or the primary without `DATABASE_READ_URL` (`db` feature,
+/// `FEATURE_DB=true`)
+#[cfg(feature = "db")]
+#[derive(Clone)]
+pub struct ReadDb(pub sea_orm::DatabaseConnection);
+
+/// Shared cache (`cache-moka`/`cache-redis`, `FEATURE_CACHE=true`)
+#[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+#[derive(Clone)]
+pub struct CacheHandle(pub std::sync::Arc<dyn crate::Cache + Send + Sync>);
+
+/// Meilisearch client (`meilisearch` feature, `FEATURE_SEARCH=true`)
+#[cfg(feature = "meilisearch")]
+#[derive(Clone)]
+pub struct Search(pub meilisearch_sdk::client::Client);
+
+/// Message broker (`nats` feature, `FEATURE_BROKER=true`)
+#[cfg(feature = "broker")]
+#[derive(Clone)]
+pub struct BrokerHandle(pub std::sync::Arc<dyn crate::Broker>);
+
+/// Outgoing email (`mailer` feature, `FEATURE_MAILER=true`)
+#[cfg(feature = "mailer")]
+#[derive(Clone)]
+pub struct MailerHandle(pub std::sync::Arc<dyn crate::Mailer>);
+
+/// `AppBuilder::with_infra`
+pub trait InfraAppBuilderExt {
+    /// Make `infra` available to the extractors of every route
+    ///
+    /// With the `db` feature this also installs [`TxLayer`](crate::tx::TxLayer),
+    /// which commits or rolls back the transaction of the [`Tx`](crate::tx::Tx)
+    /// extractor. With `broker`, audit records are also published to
+    /// `AUDIT_BROKER_SUBJECT`.
+    #[must_use]
+    fn with_infra(self, infra: Infra) -> Self;
+}
+
+impl<S> InfraAppBuilderExt for AppBuilder<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn with_infra(self, infra: Infra) -> Self {
+        // Ends the transactions opened by the `Tx` extractor
+        #[cfg(feature = "db")]
+        let this = self.layer(crate::tx::TxLayer);
+        #[cfg(not(feature = "db"))]
+        let this = self;
+        #[cfg(feature = "broker")]
+        let this = match infra.audit_sink() {
+            Some(sink) => this.with_audit_sink(sink),
+            None => this,
+        };
+        this.layer(Extension(infra))
+    }
+}
+
+/// Take a component from the installed [`Infra`]
+///
+/// `unavailable` describes why it may be missing, e.g. `FEATURE_DB=false`.
+#[allow(clippy::result_large_err)] // the rejection of every extractor here
+pub(crate) fn component<T>(
+    parts: &Parts,
+    name: &str,
+    unavailable: &str,
+    pick: impl FnOnce(&Infra) -> Option<T>,
+) -> Result<T, ApiError> {
+    let error = match parts.extensions.get::<Infra>() {
+        Some(infra) => match pick(infra) {
+            Some(component) => return Ok(component),
+            None => {
+                ApiError::service_unavailable(format!("{name} is not available ({unavailable})"))
+            }
+        },
+        None => ApiError::internal(format!(
+            "{name} is not available: Infra is not installed, use AppBuilder::with_infra"
+        )),
+    };
+    Err(with_request_id(parts, error))
+}
+
+/// `error` tagged with the `x-request-id` of the request
+pub(crate) fn with_request_id(parts: &Parts, error: ApiError) -> ApiError {
+    match extract_request_id(&parts.headers) {
+        Some(request_id) => error.with_request_id(request_id),
+        None => error,
+    }
+}
+
+#[cfg(feature = "db")]
+impl<S> FromRequestParts<S> for Db
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        component(parts, "Database", "FEATURE_DB=false", |infra| {
+            infra.db.clone()
+        })
+        .map(Self)
+    }
+}
+
+#[cfg(feature = "db")]
+impl<S> FromRequestParts<S> for ReadDb
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        component(parts, "Database", "FEATURE_DB=false", |infra| {
+            infra.read_db().cloned()
+        })
+        .map(Self)
+    }
+}
+
+#[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+impl<S> FromRequestParts<S> for CacheHandle
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        component(
+            parts,
+            "Cache",
+            "FEATURE_CACHE=false or CACHE_BACKEND=none",
+            |infra| infra.cache.clone(),
+        )
+        .map(Self)
+    }
+}
+
+#[cfg(feature = "meilisearch")]
+impl<S> FromRequestParts<S> for Search
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        component(parts, "Search", "FEATURE_SEARCH=false", |infra| {
+            infra.search.clone()
+        })
+        .map(Self)
+    }
+}
+
+#[cfg(feature = "broker")]
+impl<S> FromRequestParts<S> for BrokerHandle
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        component(parts, "Broker", "FEATURE_BROKER=false", |infra| {
+            infra.broker.clone()
+        })
+        .map(Self)
+    }
+}
+
+#[cfg(feature = "mailer")]
+impl<S> FromRequestParts<S> for MailerHandle
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        component(parts, "Mailer", "FEATURE_MAILER=false", |infra| {
+            infra.mailer.clone()
+        })
+        .map(Self)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "cache-moka")]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use axum::{
+        Router,
+        body::Body,
+        http::{Request, StatusCode},
+        routing::get,
+    };
+    use barrzen_axum_core::{BuildInfo, Config};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::MokaCache;
+
+    fn app(infra: Option<Infra>) -> Router {
+        let builder = AppBuilder::new(Config::builder().build(), BuildInfo::default()).route(
+            "/greeting",
+            get(|CacheHandle(cache): CacheHandle| async move {
+                cache
+                    .set("greeting", b"hello".to_vec(), None)
+                    .await
+                    .unwrap();
+                let value = cache.get("greeting").await.unwrap().unwrap();
+                String::from_utf8(value).unwrap()
+            }),
+        );
+        match infra {
+            Some(infra) => builder.with_infra(infra).build(),
+            None => builder.build(),
+        }
+    }
+
+    async fn call(app: Router) -> (StatusCode, String) {
+        let response = app
+            .oneshot(Request::get("/greeting").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[tokio::test]
+    async fn test_cache_extractor() {
+        let infra = Infra {
+            cache: Some(Arc::new(MokaCache::new(100, Duration::from_mins(1)))),
+            ..Infra::default()
+        };
+        let (status, body) = call(app(Some(infra))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "hello");
+    }
+
+    #[tokio::test]
+    async fn test_uninitialized_component_is_unavailable() {
+        let (status, body) = call(app(Some(Infra::default()))).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(body.contains("FEATURE_CACHE=false"), "{body}");
+
+        let (status, body) = call(app(None)).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body.contains("with_infra"), "{body}");
+    }
+}
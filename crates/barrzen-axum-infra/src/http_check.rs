@@ -0,0 +1,220 @@
+//! Readiness checks against HTTP dependencies
+//!
+//! `READYZ_HTTP_CHECKS=payments=https://payments.internal/health,...` adds
+//! one /readyz check per entry, passing when the dependency answers with a
+//! 2xx status within `READYZ_HTTP_CHECK_TIMEOUT_MS`. Register it next to
+//! [`Infra`](crate::Infra):
+//!
+//! ```ignore
+//! AppBuilder::new(config.clone(), build_info!())
+//!     .with_ready_checker(infra)
+//!     .with_ready_checker(HttpReadyChecker::from_config(&config)?)
+//! ```
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use barrzen_axum_core::{Config, HealthCheck, ReadinessConfig, ReadyChecker};
+use reqwest::{Client, Url};
+
+/// Probes HTTP dependencies, one [`HealthCheck`] per endpoint
+#[derive(Clone, Debug)]
+pub struct HttpReadyChecker {
+    client: Client,
+    checks: Arc<[(String, Url)]>,
+    timeout: Duration,
+    readiness: ReadinessConfig,
+}
+
+impl HttpReadyChecker {
+    /// Checker for `READYZ_HTTP_CHECKS` and `READYZ_HTTP_CHECK_TIMEOUT_MS`
+    ///
+    /// Dependencies left out of `READYZ_CRITICAL_COMPONENTS` (when set) are
+    /// reported as non-critical.
+    ///
+    /// # Errors
+    /// Returns error if a URL cannot be parsed or the HTTP client cannot be
+    /// built.
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let mut checker = Self::new(
+            config.readiness.http_checks(),
+            Duration::from_millis(config.readiness.readyz_http_check_timeout_ms),
+        )?;
+        checker.readiness = config.readiness.clone();
+        Ok(checker)
+    }
+
+    /// Checker for `(name, url)` pairs, each given `timeout` to answer
+    ///
+    /// # Errors
+    /// Returns error if a URL cannot be parsed or the HTTP client cannot be
+    /// built.
+    pub fn new(
+        checks: impl IntoIterator<Item = (String, String)>,
+        timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        let checks = checks
+            .into_iter()
+            .map(|(name, url)| {
+                let url = Url::parse(&url)
+                    .with_context(|| format!("invalid URL for HTTP check {name:?}"))?;
+                Ok((name, url))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let client = Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("Failed to build the HTTP check client")?;
+
+        Ok(Self {
+            client,
+            checks,
+            timeout,
+            readiness: ReadinessConfig::default(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ReadyChecker for HttpReadyChecker {
+    async fn ready_checks(&self) -> Vec<HealthCheck> {
+        // Probe concurrently so /readyz takes one timeout, not one per dependency
+        let probes: Vec<_> = self
+            .checks
+            .iter()
+            .map(|(name, url)| {
+                let (client, name, url) = (self.client.clone(), name.clone(), url.clone());
+                let timeout = self.timeout;
+                tokio::spawn(async move { probe(&client, name, url, timeout).await })
+            })
+            .collect();
+
+        let mut checks = Vec::with_capacity(probes.len());
+        for (probe, (name, _)) in probes.into_iter().zip(self.checks.iter()) {
+            let check = probe
+                .await
+                .unwrap_or_else(|e| HealthCheck::fail(name.clone(), e.to_string()));
+            checks.push(check.with_critical(self.readiness.is_critical(name)));
+        }
+        checks
+    }
+}
+
+async fn probe(client: &Client, name: String, url: Url, timeout: Duration) -> HealthCheck {
+    match client.get(url).send().await {
+        Ok(response) if response.status().is_success() => HealthCheck::ok(name),
+        Ok(response) => HealthCheck::fail(name, format!("HTTP {}", response.status())),
+        Err(e) if e.is_timeout() => {
+            HealthCheck::fail(name, format!("timed out after {}ms", timeout.as_millis()))
+        }
+        Err(e) => HealthCheck::fail(name, connection_error(&e)),
+    }
+}
+
+/// Innermost cause of a request error, without the URL
+///
+/// Check URLs may carry credentials, and the root cause ("connection
+/// refused", "dns error") is the useful part.
+fn connection_error(error: &reqwest::Error) -> String {
+    let mut source: &dyn std::error::Error = error;
+    while let Some(inner) = source.source() {
+        source = inner;
+    }
+    format!("connection error: {source}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{method, path},
+    };
+
+    async fn checks_against(server: &MockServer, timeout: Duration) -> Vec<HealthCheck> {
+        let checker = HttpReadyChecker::new(
+            [("payments".to_string(), format!("{}/health", server.uri()))],
+            timeout,
+        )
+        .unwrap();
+        checker.ready_checks().await
+    }
+
+    #[tokio::test]
+    async fn test_success() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/health"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+
+        let checks = checks_against(&server, Duration::from_secs(1)).await;
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].name, "payments");
+        assert_eq!(checks[0].status, "ok");
+    }
+
+    #[tokio::test]
+    async fn test_server_error_reports_status() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/health"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let checks = checks_against(&server, Duration::from_secs(1)).await;
+        assert_eq!(checks[0].status, "fail");
+        assert_eq!(
+            checks[0].message.as_deref(),
+            Some("HTTP 500 Internal Server Error")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/health"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+            .mount(&server)
+            .await;
+
+        let checks = checks_against(&server, Duration::from_millis(100)).await;
+        assert_eq!(checks[0].status, "fail");
+        assert_eq!(checks[0].message.as_deref(), Some("timed out after 100ms"));
+    }
+
+    #[tokio::test]
+    async fn test_connection_error_and_order() {
+        // Bind then drop a listener so the port refuses connections
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let checker = HttpReadyChecker::new(
+            [
+                ("auth".to_string(), format!("http://{addr}/livez")),
+                ("payments".to_string(), server.uri()),
+            ],
+            Duration::from_secs(1),
+        )
+        .unwrap();
+        let checks = checker.ready_checks().await;
+
+        assert_eq!(checks[0].name, "auth");
+        assert_eq!(checks[0].status, "fail");
+        let message = checks[0].message.as_deref().unwrap();
+        assert!(message.starts_with("connection error: "), "{message}");
+        assert!(!message.contains("/livez"), "{message}");
+        assert_eq!(checks[1].name, "payments");
+        assert_eq!(checks[1].status, "ok");
+    }
+}
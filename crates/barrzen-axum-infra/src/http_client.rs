@@ -0,0 +1,562 @@
+//! Outbound HTTP client
+//!
+//! [`HttpClient`] wraps a `reqwest` client built from the `HTTP_CLIENT_*`
+//! settings, with a `<name>/<version>` user agent from [`BuildInfo`]. Every
+//! call runs in a client span and carries the id of the request being handled
+//! and, with the `otel` feature, `traceparent`, so the callee's logs and
+//! traces join ours:
+//!
+//! ```ignore
+//! let client = HttpClient::from_config(&config, &build_info!())?
+//!     .with_circuit_breakers(BreakerSettings::from_config(&config.circuit_breaker));
+//!
+//! let rates: Rates = client
+//!     .get("https://rates.internal/v1/eur")
+//!     .retry(RetryPolicy::default())
+//!     .send()
+//!     .await?
+//!     .error_for_status()?
+//!     .json()
+//!     .await?;
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use anyhow::Context;
+use barrzen_axum_core::{BuildInfo, Config, app_builder::REQUEST_ID_HEADER};
+use reqwest::{
+    Client, IntoUrl, Method, Request, RequestBuilder, Response, StatusCode, Url,
+    header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+};
+use serde::Serialize;
+use tracing::{Instrument, Span, field::Empty};
+
+use crate::circuit_breaker::{BreakerError, BreakerSettings, CircuitBreaker, CircuitOpen};
+
+/// Error of a call through an [`HttpClient`]
+#[derive(Debug, thiserror::Error)]
+pub enum HttpClientError {
+    /// Rejected without calling while the host's circuit is open
+    #[error(transparent)]
+    Open(#[from] CircuitOpen),
+    /// Building or sending the request failed
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+}
+
+/// Retries of idempotent requests, set with [`HttpRequest::retry`]
+///
+/// Connection errors, timeouts and 429, 502, 503 and 504 answers are retried
+/// after `base_delay`, doubled for each further retry. A `Retry-After` in
+/// seconds replaces the backoff; either is capped at `max_delay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts including the first
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub base_delay: Duration,
+    /// Longest delay between attempts
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Policy of `max_attempts` with the default delays
+    #[must_use]
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            ..Self::default()
+        }
+    }
+
+    /// Delay after failed attempt number `attempt` (from 1)
+    fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        retry_after.unwrap_or(backoff).min(self.max_delay)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+/// HTTP client propagating the request id and trace context
+///
+/// Cheap to clone; clones share the connection pool and circuit breakers.
+#[derive(Clone)]
+pub struct HttpClient {
+    client: Client,
+    request_id_header: HeaderName,
+    breakers: Option<Arc<HostBreakers>>,
+}
+
+/// One circuit breaker per `host:port`, created on first use
+struct HostBreakers {
+    settings: BreakerSettings,
+    by_host: Mutex<HashMap<String, Arc<CircuitBreaker>>>,
+}
+
+impl HttpClient {
+    /// Client with the `HTTP_CLIENT_*` timeouts and pool size, sending the
+    /// request id under `REQUEST_ID_HEADER_NAME`
+    ///
+    /// # Errors
+    /// Returns error if the `reqwest` client cannot be built.
+    pub fn from_config(config: &Config, build: &BuildInfo) -> anyhow::Result<Self> {
+        let settings = &config.http_client;
+        let client = Client::builder()
+            .timeout(Duration::from_secs(settings.http_client_timeout_seconds))
+            .connect_timeout(Duration::from_secs(
+                settings.http_client_connect_timeout_seconds,
+            ))
+            .pool_max_idle_per_host(settings.http_client_pool_max_idle)
+            .user_agent(format!("{}/{}", build.name, build.version))
+            .build()
+            .context("Failed to build the HTTP client")?;
+
+        Ok(Self {
+            request_id_header: config
+                .request_id
+                .header_name()
+                .unwrap_or_else(|| REQUEST_ID_HEADER.clone()),
+            ..Self::new(client)
+        })
+    }
+
+    /// Wrap a client built elsewhere; the request id goes in `x-request-id`
+    #[must_use]
+    pub fn new(client: Client) -> Self {
+        Self {
+            client,
+            request_id_header: REQUEST_ID_HEADER.clone(),
+            breakers: None,
+        }
+    }
+
+    /// Put calls to each host behind a circuit breaker
+    ///
+    /// Connection errors, timeouts and 5xx answers count as failures; while
+    /// the circuit of a host is open, its calls fail with
+    /// [`HttpClientError::Open`].
+    #[must_use]
+    pub fn with_circuit_breakers(mut self, settings: BreakerSettings) -> Self {
+        self.breakers = Some(Arc::new(HostBreakers {
+            settings,
+            by_host: Mutex::new(HashMap::new()),
+        }));
+        self
+    }
+
+    /// Breakers of the hosts called so far, named `http:<host>:<port>`
+    #[must_use]
+    pub fn breakers(&self) -> Vec<Arc<CircuitBreaker>> {
+        self.breakers.as_ref().map_or_else(Vec::new, |breakers| {
+            breakers
+                .by_host
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .values()
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// The wrapped client, for calls without propagation
+    #[must_use]
+    pub fn inner(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn request(&self, method: Method, url: impl IntoUrl) -> HttpRequest {
+        HttpRequest {
+            client: self.clone(),
+            builder: self.client.request(method, url),
+            retry: None,
+        }
+    }
+
+    pub fn get(&self, url: impl IntoUrl) -> HttpRequest {
+        self.request(Method::GET, url)
+    }
+
+    pub fn post(&self, url: impl IntoUrl) -> HttpRequest {
+        self.request(Method::POST, url)
+    }
+
+    pub fn put(&self, url: impl IntoUrl) -> HttpRequest {
+        self.request(Method::PUT, url)
+    }
+
+    pub fn patch(&self, url: impl IntoUrl) -> HttpRequest {
+        self.request(Method::PATCH, url)
+    }
+
+    pub fn delete(&self, url: impl IntoUrl) -> HttpRequest {
+        self.request(Method::DELETE, url)
+    }
+
+    pub fn head(&self, url: impl IntoUrl) -> HttpRequest {
+        self.request(Method::HEAD, url)
+    }
+
+    /// Add the request id and trace context unless already set
+    fn propagate(&self, span: &Span, headers: &mut HeaderMap) {
+        if !headers.contains_key(&self.request_id_header)
+            && let Some(request_id) = barrzen_axum_core::current_request_id()
+            && let Ok(value) = HeaderValue::from_str(&request_id)
+        {
+            headers.insert(self.request_id_header.clone(), value);
+        }
+        #[cfg(feature = "otel")]
+        barrzen_axum_core::trace_context::inject_trace_context(span, headers);
+        #[cfg(not(feature = "otel"))]
+        let _ = span;
+    }
+
+    /// Send `request`, retrying with `retry` when set
+    async fn execute(
+        &self,
+        mut request: Request,
+        retry: Option<RetryPolicy>,
+    ) -> Result<Response, HttpClientError> {
+        let mut attempt = 1;
+        loop {
+            // Streamed bodies cannot be sent twice, so those go out once
+            let next = retry
+                .filter(|policy| attempt < policy.max_attempts)
+                .and_then(|policy| Some((policy, request.try_clone()?)));
+            let result = self.execute_once(request).await;
+            let Some((policy, next)) = next else {
+                return result;
+            };
+
+            let delay = match &result {
+                Ok(response) if is_retryable(response.status()) => {
+                    policy.delay(attempt, retry_after(response.headers()))
+                }
+                Err(HttpClientError::Request(e)) if e.is_connect() || e.is_timeout() => {
+                    policy.delay(attempt, None)
+                }
+                _ => return result,
+            };
+            tracing::debug!(
+                attempt,
+                delay_ms = u64::try_from(delay.as_millis()).unwrap_or(u64::MAX),
+                "Retrying HTTP request"
+            );
+            tokio::time::sleep(delay).await;
+            Span::current().record("http.request.resend_count", attempt);
+            request = next;
+            attempt += 1;
+        }
+    }
+
+    async fn execute_once(&self, request: Request) -> Result<Response, HttpClientError> {
+        let Some(breaker) = self.breaker_for(request.url()) else {
+            return Ok(self.client.execute(request).await?);
+        };
+        let result = breaker
+            .call(|| async move {
+                let response = self.client.execute(request).await.map_err(Failure::Error)?;
+                if response.status().is_server_error() {
+                    return Err(Failure::Status(response));
+                }
+                Ok(response)
+            })
+            .await;
+        match result {
+            Ok(response) | Err(BreakerError::Inner(Failure::Status(response))) => Ok(response),
+            Err(BreakerError::Inner(Failure::Error(e))) => Err(e.into()),
+            Err(BreakerError::Open(open)) => Err(open.into()),
+        }
+    }
+
+    fn breaker_for(&self, url: &Url) -> Option<Arc<CircuitBreaker>> {
+        let breakers = self.breakers.as_ref()?;
+        let host = format!(
+            "{}:{}",
+            url.host_str().unwrap_or_default(),
+            url.port_or_known_default().unwrap_or_default()
+        );
+        let mut by_host = breakers
+            .by_host
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let breaker = by_host.entry(host).or_insert_with_key(|host| {
+            Arc::new(CircuitBreaker::new(
+                format!("http:{host}"),
+                breakers.settings,
+            ))
+        });
+        Some(Arc::clone(breaker))
+    }
+}
+
+/// Failed call as seen by a host's breaker
+enum Failure {
+    Error(reqwest::Error),
+    /// A 5xx answer, still handed to the caller
+    Status(Response),
+}
+
+/// Request of an [`HttpClient`], sent with [`HttpRequest::send`]
+#[must_use = "requests are only sent with `send`"]
+pub struct HttpRequest {
+    client: HttpClient,
+    builder: RequestBuilder,
+    retry: Option<RetryPolicy>,
+}
+
+impl HttpRequest {
+    pub fn header(self, name: HeaderName, value: HeaderValue) -> Self {
+        self.map(|builder| builder.header(name, value))
+    }
+
+    pub fn headers(self, headers: HeaderMap) -> Self {
+        self.map(|builder| builder.headers(headers))
+    }
+
+    pub fn bearer_auth(self, token: impl std::fmt::Display) -> Self {
+        self.map(|builder| builder.bearer_auth(token))
+    }
+
+    pub fn query<T: Serialize + ?Sized>(self, query: &T) -> Self {
+        self.map(|builder| builder.query(query))
+    }
+
+    pub fn json<T: Serialize + ?Sized>(self, json: &T) -> Self {
+        self.map(|builder| builder.json(json))
+    }
+
+    pub fn body(self, body: impl Into<reqwest::Body>) -> Self {
+        self.map(|builder| builder.body(body))
+    }
+
+    /// Timeout of this request, instead of `HTTP_CLIENT_TIMEOUT_SECONDS`
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.map(|builder| builder.timeout(timeout))
+    }
+
+    /// Apply any other `reqwest` builder method
+    pub fn map(mut self, f: impl FnOnce(RequestBuilder) -> RequestBuilder) -> Self {
+        self.builder = f(self.builder);
+        self
+    }
+
+    /// Retry with `policy`; ignored for methods that are not idempotent
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Send the request
+    ///
+    /// Answers of any status are returned as they are, after retries.
+    ///
+    /// # Errors
+    /// Returns [`HttpClientError::Request`] if the request cannot be built or
+    /// sent, or [`HttpClientError::Open`] while the host's circuit is open.
+    pub async fn send(self) -> Result<Response, HttpClientError> {
+        let Self {
+            client,
+            builder,
+            retry,
+        } = self;
+        let mut request = builder.build()?;
+        let method = request.method().clone();
+        let span = tracing::info_span!(
+            "HTTP client request",
+            otel.name = %method,
+            otel.kind = "client",
+            otel.status_code = Empty,
+            http.request.method = %method,
+            server.address = request.url().host_str().unwrap_or_default(),
+            url.path = request.url().path(),
+            http.response.status_code = Empty,
+            http.request.resend_count = Empty,
+        );
+        client.propagate(&span, request.headers_mut());
+
+        let retry = retry.filter(|_| method.is_idempotent());
+        let result = client
+            .execute(request, retry)
+            .instrument(span.clone())
+            .await;
+        let status = result.as_ref().ok().map(Response::status);
+        if let Some(status) = status {
+            span.record("http.response.status_code", i64::from(status.as_u16()));
+        }
+        if status.is_none_or(|status| status.is_server_error()) {
+            span.record("otel.status_code", "error");
+        }
+        result
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// `Retry-After` in seconds; HTTP dates fall back to the backoff
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::get};
+    use barrzen_axum_core::AppBuilder;
+    use tower::ServiceExt;
+    use wiremock::{
+        Mock, MockServer, ResponseTemplate,
+        matchers::{header, method},
+    };
+
+    const FAST: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(10),
+    };
+
+    fn build() -> BuildInfo {
+        BuildInfo::new("orders", "1.2.0", None, "1.85.0", None)
+    }
+
+    fn client() -> HttpClient {
+        HttpClient::from_config(&Config::default(), &build()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_propagates_request_id_and_user_agent() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(header("x-request-id", "req-42"))
+            .and(header("user-agent", "orders/1.2.0"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let (client, url) = (client(), format!("{}/rates", server.uri()));
+        let app = AppBuilder::new(Config::default(), build())
+            .route(
+                "/",
+                get(move || {
+                    let (client, url) = (client.clone(), url.clone());
+                    async move { client.get(url).send().await.unwrap().status().to_string() }
+                }),
+            )
+            .build();
+        let request = axum::http::Request::builder()
+            .uri("/")
+            .header("x-request-id", "req-42")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"200 OK");
+    }
+
+    #[tokio::test]
+    async fn test_retries_idempotent_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "0"))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&server)
+            .await;
+
+        let client = client();
+        let response = client.get(server.uri()).retry(FAST).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+        let response = client.post(server.uri()).retry(FAST).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(server.received_requests().await.unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(502))
+            .expect(3)
+            .mount(&server)
+            .await;
+
+        let response = client().get(server.uri()).retry(FAST).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_per_host() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(2)
+            .mount(&server)
+            .await;
+        let client = client().with_circuit_breakers(BreakerSettings {
+            failure_threshold: 2,
+            open_duration: Duration::from_mins(1),
+            half_open_probes: 1,
+        });
+
+        for _ in 0..2 {
+            let response = client.get(server.uri()).send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        let error = client.get(server.uri()).send().await.unwrap_err();
+        assert!(matches!(error, HttpClientError::Open(_)), "{error}");
+        let breakers = client.breakers();
+        assert_eq!(breakers.len(), 1);
+        assert!(breakers[0].name().starts_with("http:127.0.0.1:"));
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1, None), Duration::from_millis(100));
+        assert_eq!(policy.delay(3, None), Duration::from_millis(400));
+        assert_eq!(policy.delay(20, None), Duration::from_secs(10));
+        let retry_after = Some(Duration::from_secs(2));
+        assert_eq!(policy.delay(1, retry_after), Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,676 @@
+//! `Idempotency-Key` middleware backed by the infra cache
+//!
+//! For the configured methods and paths, a request carrying an
+//! `Idempotency-Key` header runs its handler once: the response is stored
+//! under the key, route and body hash for `IDEMPOTENCY_TTL_SECONDS` and
+//! replayed to retries with `Idempotent-Replayed: true`. A retry arriving
+//! while the first request is still running gets 409, on any replica sharing
+//! the cache. Server errors (5xx) are not stored and requests dropped before
+//! responding release their key, so both can be retried.
+
+use std::{
+    collections::HashSet,
+    fmt::{self, Write as _},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{HeaderMap, HeaderName, HeaderValue, Method, header, request},
+    response::{IntoResponse, Response},
+};
+use barrzen_axum_core::{
+    Config,
+    response::{ApiError, extract_request_id},
+};
+use sha2::{Digest, Sha256};
+use tower::{Layer, Service};
+
+use crate::{Cache, stored_response::StoredResponse};
+
+/// Request header naming the operation
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Response header set on replayed responses
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Key prefix for idempotency records in the cache
+const KEY_PREFIX: &str = "idempotency:";
+
+/// Longest accepted `Idempotency-Key`
+const MAX_KEY_LEN: usize = 255;
+
+/// Lifetime of the in-progress marker, bounding how long a crashed request
+/// blocks its key on other replicas
+const IN_PROGRESS_TTL: Duration = Duration::from_mins(5);
+
+/// Tries at claiming a key whose record disappears between the claim and
+/// the read
+const CLAIM_ATTEMPTS: usize = 3;
+
+/// Layer replaying responses for repeated `Idempotency-Key` requests
+///
+/// Add it with `AppBuilder::layer`, or `Router::layer` for single routers.
+#[derive(Clone)]
+pub struct IdempotencyLayer {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    cache: Arc<dyn Cache + Send + Sync>,
+    ttl: Duration,
+    methods: Vec<Method>,
+    paths: Vec<String>,
+    max_request_bytes: usize,
+    max_response_bytes: usize,
+    /// Keys being handled by this process
+    in_flight: Mutex<HashSet<String>>,
+}
+
+impl fmt::Debug for IdempotencyLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdempotencyLayer")
+            .field("ttl", &self.shared.ttl)
+            .field("methods", &self.shared.methods)
+            .field("paths", &self.shared.paths)
+            .finish_non_exhaustive()
+    }
+}
+
+impl IdempotencyLayer {
+    /// Build the layer from the `IDEMPOTENCY_*` settings
+    ///
+    /// Request bodies are read up to `HTTP_BODY_LIMIT_BYTES` to hash them.
+    #[must_use]
+    pub fn new(cache: Arc<dyn Cache + Send + Sync>, config: &Config) -> Self {
+        let idempotency = &config.idempotency;
+        Self {
+            shared: Arc::new(Shared {
+                cache,
+                ttl: Duration::from_secs(idempotency.idempotency_ttl_seconds),
+                methods: idempotency
+                    .methods()
+                    .iter()
+                    .filter_map(|method| Method::from_bytes(method.as_bytes()).ok())
+                    .collect(),
+                paths: idempotency.paths(),
+                max_request_bytes: config.http.http_body_limit_bytes,
+                max_response_bytes: idempotency.idempotency_max_response_bytes,
+                in_flight: Mutex::default(),
+            }),
+        }
+    }
+}
+
+#[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+impl crate::Infra {
+    /// Idempotency layer backed by the initialized cache
+    ///
+    /// Returns `None` when the cache is disabled.
+    #[must_use]
+    pub fn idempotency_layer(&self, config: &Config) -> Option<IdempotencyLayer> {
+        self.cache
+            .clone()
+            .map(|cache| IdempotencyLayer::new(cache, config))
+    }
+}
+
+impl Shared {
+    fn applies(&self, req: &Request) -> bool {
+        let path = req.uri().path();
+        self.methods.contains(req.method())
+            && (self.paths.is_empty() || self.paths.iter().any(|prefix| path.starts_with(prefix)))
+    }
+
+    /// Cache key over the idempotency key, route and body
+    fn cache_key(req: &request::Parts, key: &str, body: &[u8]) -> String {
+        let route = req
+            .uri
+            .path_and_query()
+            .map_or_else(|| req.uri.path(), |path| path.as_str());
+        let mut hasher = Sha256::new();
+        for part in [
+            key.as_bytes(),
+            req.method.as_str().as_bytes(),
+            route.as_bytes(),
+        ] {
+            hasher.update(part);
+            hasher.update([0]);
+        }
+        hasher.update(Sha256::digest(body));
+        hasher
+            .finalize()
+            .iter()
+            .fold(String::from(KEY_PREFIX), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            })
+    }
+}
+
+impl<S> Layer<S> for IdempotencyLayer {
+    type Service = IdempotencyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IdempotencyService {
+            inner,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct IdempotencyService<S> {
+    inner: S,
+    shared: Arc<Shared>,
+}
+
+impl<S> Service<Request> for IdempotencyService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let mut inner = self.inner.clone();
+        if !self.shared.applies(&req) || !req.headers().contains_key(IDEMPOTENCY_KEY_HEADER) {
+            return Box::pin(async move { inner.call(req).await });
+        }
+        Box::pin(handle(self.shared.clone(), inner, req))
+    }
+}
+
+async fn handle<S>(shared: Arc<Shared>, mut inner: S, req: Request) -> Result<Response, S::Error>
+where
+    S: Service<Request, Response = Response>,
+{
+    let request_id = extract_request_id(req.headers());
+    let reject = |error: ApiError| {
+        let error = match &request_id {
+            Some(request_id) => error.with_request_id(request_id.clone()),
+            None => error,
+        };
+        Ok(error.into_response())
+    };
+
+    let Some(key) = idempotency_key(req.headers()) else {
+        return reject(
+            ApiError::bad_request("Idempotency-Key must be 1-255 visible ASCII characters")
+                .with_code("invalid_idempotency_key"),
+        );
+    };
+    let (parts, body) = req.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, shared.max_request_bytes).await else {
+        return reject(ApiError::payload_too_large("Request body too large"));
+    };
+    let cache_key = Shared::cache_key(&parts, &key, &body);
+
+    let Some(_claim) = InFlight::claim(&shared, &cache_key) else {
+        return reject(in_progress());
+    };
+    let marker = StoredRecord::InProgress.encode();
+    let mut attempts = 0;
+    loop {
+        match shared
+            .cache
+            .set_if_absent(&cache_key, marker.clone(), Some(IN_PROGRESS_TTL))
+            .await
+        {
+            Ok(true) => break,
+            Ok(false) => {}
+            Err(e) => {
+                // Fail open: the request runs without replay protection
+                tracing::warn!(key, error = %e, "Idempotency cache unavailable");
+                return inner
+                    .call(Request::from_parts(parts, Body::from(body)))
+                    .await;
+            }
+        }
+        match shared.cache.get(&cache_key).await {
+            Ok(Some(record)) => match StoredRecord::decode(&record) {
+                Some(StoredRecord::InProgress) => return reject(in_progress()),
+                Some(StoredRecord::Response(stored)) => return Ok(replay(stored)),
+                None => {
+                    tracing::warn!(key, "Discarding unreadable idempotency record");
+                    release(&shared, &cache_key).await;
+                }
+            },
+            // Released or expired since the claim failed
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!(key, error = %e, "Idempotency cache unavailable");
+                return inner
+                    .call(Request::from_parts(parts, Body::from(body)))
+                    .await;
+            }
+        }
+        attempts += 1;
+        if attempts == CLAIM_ATTEMPTS {
+            return reject(in_progress());
+        }
+    }
+
+    let marker = Marker::new(&shared, &cache_key);
+    let response = inner
+        .call(Request::from_parts(parts, Body::from(body)))
+        .await?;
+    let response = store(&shared, &cache_key, &key, response).await;
+    marker.disarm();
+    Ok(response)
+}
+
+/// Store `response` for replays, or clear the in-progress marker when it
+/// must not be stored
+async fn store(shared: &Shared, cache_key: &str, key: &str, response: Response) -> Response {
+    let too_large = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse::<usize>().ok())
+        .is_some_and(|length| length > shared.max_response_bytes);
+    if response.status().is_server_error() || too_large {
+        if too_large {
+            tracing::warn!(key, "Response too large to store for idempotent replay");
+        }
+        release(shared, cache_key).await;
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!(key, error = %e, "Failed to read response body");
+            release(shared, cache_key).await;
+            return ApiError::internal("Failed to read response body").into_response();
+        }
+    };
+    if body.len() > shared.max_response_bytes {
+        tracing::warn!(key, "Response too large to store for idempotent replay");
+        release(shared, cache_key).await;
+        return Response::from_parts(parts, Body::from(body));
+    }
+
+    let stored = StoredResponse {
+        status: parts.status,
+        headers: parts.headers.clone(),
+        body: body.clone(),
+    };
+    let record = StoredRecord::Response(stored).encode();
+    if let Err(e) = shared.cache.set(cache_key, record, Some(shared.ttl)).await {
+        tracing::warn!(key, error = %e, "Failed to store idempotent response");
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+async fn release(shared: &Shared, cache_key: &str) {
+    if let Err(e) = shared.cache.delete(cache_key).await {
+        tracing::warn!(error = %e, "Failed to clear idempotency marker");
+    }
+}
+
+fn idempotency_key(headers: &HeaderMap) -> Option<String> {
+    let key = headers.get(IDEMPOTENCY_KEY_HEADER)?.to_str().ok()?.trim();
+    (!key.is_empty() && key.len() <= MAX_KEY_LEN).then(|| key.to_string())
+}
+
+fn in_progress() -> ApiError {
+    ApiError::conflict("A request with this Idempotency-Key is still in progress")
+        .with_code("idempotency_in_progress")
+}
+
+/// Claim on a key in this process, released on drop
+struct InFlight<'a> {
+    shared: &'a Shared,
+    key: String,
+}
+
+impl<'a> InFlight<'a> {
+    fn claim(shared: &'a Shared, key: &str) -> Option<Self> {
+        let mut in_flight = shared.in_flight.lock().ok()?;
+        in_flight.insert(key.to_string()).then(|| Self {
+            shared,
+            key: key.to_string(),
+        })
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut in_flight) = self.shared.in_flight.lock() {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+/// In-progress marker held by this request, cleared in the background if
+/// the request is dropped before its response is stored
+struct Marker {
+    shared: Option<Arc<Shared>>,
+    cache_key: String,
+}
+
+impl Marker {
+    fn new(shared: &Arc<Shared>, cache_key: &str) -> Self {
+        Self {
+            shared: Some(Arc::clone(shared)),
+            cache_key: cache_key.to_string(),
+        }
+    }
+
+    fn disarm(mut self) {
+        self.shared = None;
+    }
+}
+
+impl Drop for Marker {
+    fn drop(&mut self) {
+        let Some(shared) = self.shared.take() else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let cache_key = std::mem::take(&mut self.cache_key);
+        runtime.spawn(async move { release(&shared, &cache_key).await });
+    }
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = stored.into_response();
+    response.headers_mut().insert(
+        HeaderName::from_static(IDEMPOTENT_REPLAYED_HEADER),
+        HeaderValue::from_static("true"),
+    );
+    response
+}
+
+/// Cached value: a marker while the handler runs, then the response
+enum StoredRecord {
+    InProgress,
+    Response(StoredResponse),
+}
+
+impl StoredRecord {
+    const IN_PROGRESS: u8 = 0;
+    const RESPONSE: u8 = 1;
+
+    fn encode(&self) -> Vec<u8> {
+        let Self::Response(stored) = self else {
+            return vec![Self::IN_PROGRESS];
+        };
+        let mut out = Vec::with_capacity(stored.body.len() + 256);
+        out.push(Self::RESPONSE);
+        stored.encode_into(&mut out);
+        out
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let (&tag, rest) = bytes.split_first()?;
+        match tag {
+            Self::IN_PROGRESS => Some(Self::InProgress),
+            Self::RESPONSE => StoredResponse::decode(rest).map(Self::Response),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use axum::{Router, body::Bytes, http::StatusCode, routing::post};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    /// In-memory cache ignoring TTLs
+    #[derive(Default)]
+    struct MemoryCache(Mutex<HashMap<String, Vec<u8>>>);
+
+    #[async_trait::async_trait]
+    impl Cache for MemoryCache {
+        async fn ping(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+            Ok(self.0.lock().unwrap().get(key).cloned())
+        }
+
+        async fn set(
+            &self,
+            key: &str,
+            value: Vec<u8>,
+            _ttl: Option<Duration>,
+        ) -> anyhow::Result<()> {
+            self.0.lock().unwrap().insert(key.to_string(), value);
+            Ok(())
+        }
+
+        async fn set_if_absent(
+            &self,
+            key: &str,
+            value: Vec<u8>,
+            _ttl: Option<Duration>,
+        ) -> anyhow::Result<bool> {
+            let mut entries = self.0.lock().unwrap();
+            if entries.contains_key(key) {
+                return Ok(false);
+            }
+            entries.insert(key.to_string(), value);
+            Ok(true)
+        }
+
+        async fn delete(&self, key: &str) -> anyhow::Result<()> {
+            self.0.lock().unwrap().remove(key);
+            Ok(())
+        }
+    }
+
+    fn payments_app(config: &Config, calls: Arc<AtomicUsize>, work: Duration) -> Router {
+        replica(Arc::new(MemoryCache::default()), config, calls, work)
+    }
+
+    /// App sharing `cache` with other replicas
+    fn replica(
+        cache: Arc<MemoryCache>,
+        config: &Config,
+        calls: Arc<AtomicUsize>,
+        work: Duration,
+    ) -> Router {
+        let layer = IdempotencyLayer::new(cache, config);
+        Router::new()
+            .route(
+                "/payments",
+                post(move |body: String| {
+                    let calls = calls.clone();
+                    async move {
+                        let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                        tokio::time::sleep(work).await;
+                        (
+                            StatusCode::CREATED,
+                            [("x-call", call.to_string())],
+                            format!("paid {body} #{call}"),
+                        )
+                    }
+                }),
+            )
+            .layer(layer)
+    }
+
+    fn payment(key: Option<&str>, body: &str) -> Request {
+        let mut builder = Request::post("/payments");
+        if let Some(key) = key {
+            builder = builder.header(IDEMPOTENCY_KEY_HEADER, key);
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn send(app: &Router, req: Request) -> (StatusCode, HeaderMap, String) {
+        let response = app.clone().oneshot(req).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (
+            parts.status,
+            parts.headers,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_repeated_key_replays_response() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = payments_app(&Config::default(), calls.clone(), Duration::ZERO);
+
+        let (status, headers, first) = send(&app, payment(Some("pay-1"), "10")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(!headers.contains_key(IDEMPOTENT_REPLAYED_HEADER));
+        let (status, headers, second) = send(&app, payment(Some("pay-1"), "10")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(headers["x-call"], "1");
+        assert_eq!(headers[IDEMPOTENT_REPLAYED_HEADER], "true");
+        assert_eq!(first, second);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // A different body, key or no key runs the handler again
+        send(&app, payment(Some("pay-1"), "20")).await;
+        send(&app, payment(Some("pay-2"), "10")).await;
+        send(&app, payment(None, "10")).await;
+        send(&app, payment(None, "10")).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_request_with_same_key_conflicts() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = payments_app(
+            &Config::default(),
+            calls.clone(),
+            Duration::from_millis(200),
+        );
+
+        let first = tokio::spawn({
+            let app = app.clone();
+            async move { send(&app, payment(Some("pay-1"), "10")).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let (status, _, body) = send(&app, payment(Some("pay-1"), "10")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(body.contains("idempotency_in_progress"), "{body}");
+
+        assert_eq!(first.await.unwrap().0, StatusCode::CREATED);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_replicas_claim_key_once() {
+        let cache = Arc::new(MemoryCache::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let work = Duration::from_millis(100);
+        let first = replica(cache.clone(), &Config::default(), calls.clone(), work);
+        let second = replica(cache, &Config::default(), calls.clone(), work);
+
+        let (a, b) = tokio::join!(
+            send(&first, payment(Some("pay-1"), "10")),
+            send(&second, payment(Some("pay-1"), "10")),
+        );
+        let mut statuses = [a.0, b.0];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::CREATED, StatusCode::CONFLICT]);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_releases_key() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = payments_app(
+            &Config::default(),
+            calls.clone(),
+            Duration::from_millis(200),
+        );
+
+        let first = tokio::spawn({
+            let app = app.clone();
+            async move { send(&app, payment(Some("pay-1"), "10")).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        first.abort();
+        assert!(first.await.unwrap_err().is_cancelled());
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let (status, headers, _) = send(&app, payment(Some("pay-1"), "10")).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(!headers.contains_key(IDEMPOTENT_REPLAYED_HEADER));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_large_responses_and_other_methods_are_not_stored() {
+        let mut config = Config::default();
+        config.idempotency.idempotency_max_response_bytes = 4;
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = payments_app(&config, calls.clone(), Duration::ZERO);
+        send(&app, payment(Some("pay-1"), "10")).await;
+        let (_, headers, _) = send(&app, payment(Some("pay-1"), "10")).await;
+        assert!(!headers.contains_key(IDEMPOTENT_REPLAYED_HEADER));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        config.idempotency.idempotency_methods = "PUT".to_string();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = payments_app(&config, calls.clone(), Duration::ZERO);
+        send(&app, payment(Some("pay-1"), "10")).await;
+        send(&app, payment(Some("pay-1"), "10")).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_key_is_rejected() {
+        let app = payments_app(&Config::default(), Arc::default(), Duration::ZERO);
+        let (status, _, _) = send(&app, payment(Some(&"k".repeat(256)), "10")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        headers.append(header::SET_COOKIE, HeaderValue::from_static("a=1"));
+        headers.append(header::SET_COOKIE, HeaderValue::from_static("b=2"));
+        let encoded = StoredRecord::Response(StoredResponse {
+            status: StatusCode::ACCEPTED,
+            headers: headers.clone(),
+            body: Bytes::from_static(b"{\"ok\":true}"),
+        })
+        .encode();
+
+        let Some(StoredRecord::Response(decoded)) = StoredRecord::decode(&encoded) else {
+            panic!("expected a response");
+        };
+        assert_eq!(decoded.status, StatusCode::ACCEPTED);
+        assert_eq!(decoded.headers, headers);
+        assert_eq!(decoded.body, "{\"ok\":true}");
+        assert!(StoredRecord::decode(&encoded[..5]).is_none());
+        assert!(matches!(
+            StoredRecord::decode(&[0]),
+            Some(StoredRecord::InProgress)
+        ));
+    }
+}
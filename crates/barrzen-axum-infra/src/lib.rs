@@ -4,17 +4,125 @@
 //!
 //! Manages connections to:
 //! - Database (SeaORM)
-//! - Cache (Moka/Redis)
-//! - Search (Meilisearch)
-//! - Broker (NATS)
+//! - Cache (Moka/Redis, or Moka in front of Redis with `cache-tiered`)
+//! - Search (Meilisearch, with index management helpers)
+//! - Broker (`Broker` trait over NATS, JSON envelopes, in-memory `MockBroker` with `test-util`)
+//! - Transactional outbox relayed through the broker (`outbox`)
+//! - Email over SMTP (`mailer`, in-memory `MockMailer` with `test-util`)
+//! - HTTP dependencies for /readyz (`http-checks`)
+//! - Outbound HTTP calls with request id and trace propagation and retries (`http-client`)
+//! - `Idempotency-Key` replay over the cache (`idempotency`)
+//! - Server-side caching of `GET` responses (`response-cache`)
+//! - Handler extractors for the components, and a request-scoped transaction (`extract`)
+//! - Schema migrations at startup (`migrations`)
+//! - Circuit breakers failing fast while a remote dependency is down
 
-
-#[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
 use std::sync::Arc;
-#[cfg(feature = "db")]
 use std::time::Duration;
 
-use barrzen_axum_core::{Config, HealthCheck, ReadyChecker};
+pub mod circuit_breaker;
+
+#[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+pub mod cache_metrics;
+
+#[cfg(feature = "session")]
+pub mod session;
+
+#[cfg(feature = "meilisearch")]
+pub mod search;
+
+#[cfg(feature = "broker")]
+pub mod broker;
+
+#[cfg(feature = "broker")]
+pub mod broker_json;
+
+#[cfg(feature = "mailer")]
+pub mod mailer;
+
+#[cfg(feature = "outbox")]
+pub mod outbox;
+
+#[cfg(feature = "cache-tiered")]
+pub mod tiered;
+
+#[cfg(feature = "http-checks")]
+mod http_check;
+
+#[cfg(feature = "http-client")]
+pub mod http_client;
+
+#[cfg(feature = "idempotency")]
+pub mod idempotency;
+
+#[cfg(feature = "response-cache")]
+pub mod response_cache;
+
+#[cfg(any(feature = "idempotency", feature = "response-cache"))]
+mod stored_response;
+
+#[cfg(feature = "extract")]
+pub mod extract;
+
+#[cfg(all(feature = "extract", feature = "db"))]
+pub mod tx;
+
+#[cfg(feature = "migrations")]
+pub mod migrations;
+
+pub use circuit_breaker::{BreakerSettings, CircuitBreaker, CircuitOpen, CircuitState};
+
+#[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+pub use cache_metrics::{CacheMetrics, CacheStats, InstrumentedCache};
+
+#[cfg(feature = "cache-tiered")]
+pub use tiered::TieredCache;
+
+#[cfg(feature = "meilisearch")]
+pub use search::{IndexSettings, SearchError, SearchIndex, SyncOptions};
+
+#[cfg(feature = "broker")]
+pub use broker::{Broker, BrokerAuditSink, BrokerMessage, MessageStream, RequestError};
+
+#[cfg(feature = "broker")]
+pub use broker_json::{BrokerJsonExt, DecodeError, Envelope};
+
+#[cfg(feature = "nats")]
+pub use broker::NatsBroker;
+
+#[cfg(feature = "test-util")]
+pub use broker::MockBroker;
+
+#[cfg(feature = "mailer")]
+pub use mailer::{Email, Mailer, SmtpMailer};
+
+#[cfg(feature = "outbox")]
+pub use outbox::{Outbox, OutboxRelay, OutboxStatus, RelayReport, RelaySettings};
+
+#[cfg(all(feature = "mailer", feature = "test-util"))]
+pub use mailer::MockMailer;
+
+#[cfg(feature = "http-checks")]
+pub use http_check::HttpReadyChecker;
+
+#[cfg(feature = "http-client")]
+pub use http_client::{HttpClient, HttpClientError, HttpRequest, RetryPolicy};
+
+#[cfg(feature = "idempotency")]
+pub use idempotency::IdempotencyLayer;
+
+#[cfg(feature = "response-cache")]
+pub use response_cache::ResponseCacheLayer;
+
+#[cfg(feature = "extract")]
+pub use extract::InfraAppBuilderExt;
+
+#[cfg(all(feature = "extract", feature = "db"))]
+pub use tx::{Tx, TxLayer};
+
+use barrzen_axum_core::{
+    Config, HealthCheck, HealthRegistry, ReadinessConfig, ReadyChecker, StartupTimings,
+};
 
 /// Infrastructure container
 #[derive(Clone, Default)]
@@ -23,17 +131,44 @@ pub struct Infra {
     #[cfg(feature = "db")]
     pub db: Option<sea_orm::DatabaseConnection>,
 
+    /// Read replica (`DATABASE_READ_URL`), `None` when reads use the primary
+    #[cfg(feature = "db")]
+    pub db_read: Option<sea_orm::DatabaseConnection>,
+
     // Cache
     #[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
     pub cache: Option<Arc<dyn Cache + Send + Sync>>,
 
+    /// Breaker wrapped around Redis (`CACHE_BACKEND=redis` or `tiered`)
+    #[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+    pub cache_breaker: Option<Arc<CircuitBreaker>>,
+
+    /// Operation counters of [`Infra::cache`]
+    #[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+    pub cache_metrics: Option<Arc<CacheMetrics>>,
+
     // Search
     #[cfg(feature = "meilisearch")]
     pub search: Option<meilisearch_sdk::client::Client>,
 
-    // Broker
-    #[cfg(feature = "nats")]
-    pub broker: Option<async_nats::Client>,
+    /// Breaker of [`Infra::search_client`]
+    #[cfg(feature = "meilisearch")]
+    pub search_breaker: Option<Arc<CircuitBreaker>>,
+
+    /// Message broker selected by `BROKER_BACKEND`
+    #[cfg(feature = "broker")]
+    pub broker: Option<Arc<dyn Broker>>,
+
+    /// Subject of the audit records (`AUDIT_BROKER_SUBJECT`)
+    #[cfg(feature = "broker")]
+    pub audit_subject: Option<String>,
+
+    /// SMTP mailer (`FEATURE_MAILER`)
+    #[cfg(feature = "mailer")]
+    pub mailer: Option<Arc<dyn Mailer>>,
+
+    /// Which components are critical for /readyz (`READYZ_CRITICAL_COMPONENTS`)
+    pub readiness: ReadinessConfig,
 }
 
 impl Infra {
@@ -43,28 +178,73 @@ impl Infra {
     /// Returns error if a feature is enabled at runtime but not compiled,
     /// or if connection setup fails.
     pub async fn init(config: &Config) -> anyhow::Result<Self> {
+        Self::init_timed(config, &StartupTimings::disabled()).await
+    }
+
+    /// [`Infra::init`] recording each component as a phase of `timings`
+    ///
+    /// Phases are named `infra.db`, `infra.cache`, `infra.search`,
+    /// `infra.broker` and `infra.mailer`; disabled components are skipped.
+    ///
+    /// # Errors
+    /// Returns error if a feature is enabled at runtime but not compiled,
+    /// or if connection setup fails.
+    #[allow(clippy::unused_async, clippy::too_many_lines)] // one block per component
+    pub async fn init_timed(config: &Config, timings: &StartupTimings) -> anyhow::Result<Self> {
         #[cfg(any(
             feature = "db",
             feature = "cache-moka",
             feature = "cache-redis",
             feature = "meilisearch",
-            feature = "nats"
+            feature = "broker",
+            feature = "mailer"
         ))]
-        let mut infra = Self::default();
+        let mut infra = Self {
+            readiness: config.readiness.clone(),
+            ..Self::default()
+        };
         #[cfg(not(any(
             feature = "db",
             feature = "cache-moka",
             feature = "cache-redis",
             feature = "meilisearch",
-            feature = "nats"
+            feature = "broker",
+            feature = "mailer"
         )))]
-        let infra = Self::default();
+        let infra = Self {
+            readiness: config.readiness.clone(),
+        };
 
         // Database
         if config.features.feature_db {
+            let _phase = timings.start("infra.db");
             #[cfg(feature = "db")]
             {
-                infra.db = Some(init_db(config).await?);
+                use anyhow::Context;
+
+                let database = &config.database;
+                // DATABASE_URL, DB_URL or their *_FILE variants, resolved by the config loader
+                let url = database
+                    .url()
+                    .context("DATABASE_URL or DB_URL must be set")?;
+                infra.db = Some(
+                    init_db(
+                        url,
+                        database.db_max_connections,
+                        database.db_min_connections,
+                    )
+                    .await?,
+                );
+                if let Some(url) = database.read_url() {
+                    infra.db_read = Some(
+                        init_db(
+                            url,
+                            database.db_read_max_connections,
+                            database.db_read_min_connections,
+                        )
+                        .await?,
+                    );
+                }
             }
             #[cfg(not(feature = "db"))]
             {
@@ -74,91 +254,382 @@ impl Infra {
 
         // Cache
         if config.features.feature_cache {
+            let _phase = timings.start("infra.cache");
             // Moka
-            if matches!(config.cache.cache_backend, barrzen_axum_core::CacheBackend::Moka) {
+            if matches!(
+                config.cache.cache_backend,
+                barrzen_axum_core::CacheBackend::Moka
+            ) {
                 #[cfg(feature = "cache-moka")]
                 {
                     infra.cache = Some(init_moka_cache(config));
                 }
                 #[cfg(not(feature = "cache-moka"))]
                 {
-                    anyhow::bail!("Cache backend 'moka' selected but 'cache-moka' cargo feature is disabled");
+                    anyhow::bail!(
+                        "Cache backend 'moka' selected but 'cache-moka' cargo feature is disabled"
+                    );
                 }
             }
             // Redis
-            if matches!(config.cache.cache_backend, barrzen_axum_core::CacheBackend::Redis) {
+            if matches!(
+                config.cache.cache_backend,
+                barrzen_axum_core::CacheBackend::Redis
+            ) {
                 #[cfg(feature = "cache-redis")]
                 {
-                    infra.cache = Some(init_redis_cache(config).await?);
+                    // Fail fast while Redis is down instead of waiting out the connect timeout
+                    let breaker = Arc::new(CircuitBreaker::from_config("cache", config));
+                    let cache = init_redis_cache(config).await?;
+                    infra.cache = Some(Arc::new(circuit_breaker::BreakerCache::new(
+                        Arc::new(cache),
+                        Arc::clone(&breaker),
+                    )));
+                    infra.cache_breaker = Some(breaker);
                 }
                 #[cfg(not(feature = "cache-redis"))]
                 {
-                    anyhow::bail!("Cache backend 'redis' selected but 'cache-redis' cargo feature is disabled");
+                    anyhow::bail!(
+                        "Cache backend 'redis' selected but 'cache-redis' cargo feature is disabled"
+                    );
                 }
             }
+            // Moka in front of Redis
+            if matches!(
+                config.cache.cache_backend,
+                barrzen_axum_core::CacheBackend::Tiered
+            ) {
+                #[cfg(feature = "cache-tiered")]
+                {
+                    let breaker = Arc::new(CircuitBreaker::from_config("cache", config));
+                    let redis = init_redis_cache(config).await?;
+                    let pool = redis.pool.clone();
+                    let l2 = Arc::new(circuit_breaker::BreakerCache::new(
+                        Arc::new(redis),
+                        Arc::clone(&breaker),
+                    ));
+                    infra.cache = Some(tiered::init(config, l2, pool)?);
+                    infra.cache_breaker = Some(breaker);
+                }
+                #[cfg(not(feature = "cache-tiered"))]
+                {
+                    anyhow::bail!(
+                        "Cache backend 'tiered' selected but 'cache-tiered' cargo feature is disabled"
+                    );
+                }
+            }
+        }
+
+        // Count every cache operation, whatever the backend
+        #[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+        if let Some(cache) = infra.cache.take() {
+            let metrics = Arc::new(CacheMetrics::new(config.cache.cache_backend.to_string()));
+            #[allow(unused_mut)]
+            let mut instrumented = InstrumentedCache::new(cache, Arc::clone(&metrics));
+            #[cfg(feature = "otel")]
+            if config.features.feature_otel_metrics {
+                let meter = opentelemetry::global::meter("barrzen-axum");
+                metrics.register(&meter);
+                instrumented = instrumented.with_meter(&meter);
+            }
+            infra.cache = Some(Arc::new(instrumented));
+            infra.cache_metrics = Some(metrics);
+        }
+
+        // Sessions are stored in the cache
+        if config.features.feature_session {
+            anyhow::ensure!(
+                config.features.feature_cache
+                    && !matches!(
+                        config.cache.cache_backend,
+                        barrzen_axum_core::CacheBackend::None
+                    ),
+                "FEATURE_SESSION requires FEATURE_CACHE=true with CACHE_BACKEND other than none"
+            );
+            #[cfg(not(feature = "session"))]
+            {
+                anyhow::bail!("FEATURE_SESSION is enabled but 'session' cargo feature is disabled");
+            }
         }
 
         // Search
         if config.features.feature_search {
+            let _phase = timings.start("infra.search");
             #[cfg(feature = "meilisearch")]
             {
-                // Init meilisearch (placeholder for now)
-                // infra.search = Some(init_meilisearch(config).await?);
+                use anyhow::Context;
+
+                let url = config
+                    .search
+                    .meili_url
+                    .clone()
+                    .context("MEILI_URL must be set")?;
+                let client =
+                    meilisearch_sdk::client::Client::new(url, config.search.meili_api_key.clone())?;
+                let breaker = CircuitBreaker::from_config("search", config);
+                infra.search = Some(client);
+                infra.search_breaker = Some(Arc::new(breaker));
             }
             #[cfg(not(feature = "meilisearch"))]
             {
-                anyhow::bail!("FEATURE_SEARCH is enabled but 'meilisearch' cargo feature is disabled");
+                anyhow::bail!(
+                    "FEATURE_SEARCH is enabled but 'meilisearch' cargo feature is disabled"
+                );
             }
         }
 
         // Broker
         if config.features.feature_broker {
-            #[cfg(feature = "nats")]
+            let _phase = timings.start("infra.broker");
+            #[cfg(feature = "broker")]
+            {
+                infra
+                    .audit_subject
+                    .clone_from(&config.audit.audit_broker_subject);
+            }
+            match config.broker.broker_backend {
+                barrzen_axum_core::BrokerBackend::Nats => {
+                    #[cfg(feature = "nats")]
+                    {
+                        use anyhow::Context;
+
+                        let url = config
+                            .broker
+                            .nats_url
+                            .as_deref()
+                            .context("NATS_URL must be set for BROKER_BACKEND=nats")?;
+                        infra.broker = Some(Arc::new(NatsBroker::connect(url).await?));
+                    }
+                    #[cfg(not(feature = "nats"))]
+                    {
+                        anyhow::bail!(
+                            "Broker backend 'nats' selected but 'nats' cargo feature is disabled"
+                        );
+                    }
+                }
+            }
+        }
+
+        // Mailer
+        if config.features.feature_mailer {
+            let _phase = timings.start("infra.mailer");
+            #[cfg(feature = "mailer")]
             {
-                // Init nats (placeholder)
-                // infra.broker = Some(init_nats(config).await?);
+                infra.mailer = Some(Arc::new(SmtpMailer::from_config(&config.mail)?));
             }
-            #[cfg(not(feature = "nats"))]
+            #[cfg(not(feature = "mailer"))]
             {
-                anyhow::bail!("FEATURE_BROKER is enabled but 'nats' cargo feature is disabled");
+                anyhow::bail!("FEATURE_MAILER is enabled but 'mailer' cargo feature is disabled");
             }
         }
 
+        // HTTP dependencies are checked by HttpReadyChecker
+        #[cfg(not(feature = "http-checks"))]
+        if config.readiness.readyz_http_checks.is_some() {
+            anyhow::bail!("READYZ_HTTP_CHECKS is set but 'http-checks' cargo feature is disabled");
+        }
+
         Ok(infra)
     }
+
+    /// Cache operation counts, `None` when the cache is disabled
+    #[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+    #[must_use]
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache_metrics
+            .as_ref()
+            .map(|metrics| metrics.snapshot())
+    }
+
+    /// Circuit breakers of the initialized components, for metrics and
+    /// diagnostics
+    #[must_use]
+    pub fn breakers(&self) -> Vec<Arc<CircuitBreaker>> {
+        #[allow(unused_mut)]
+        let mut breakers = Vec::new();
+        #[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+        breakers.extend(self.cache_breaker.clone());
+        #[cfg(feature = "meilisearch")]
+        breakers.extend(self.search_breaker.clone());
+        breakers
+    }
+
+    /// Search client behind its circuit breaker
+    ///
+    /// `None` when search is disabled.
+    #[cfg(feature = "meilisearch")]
+    #[must_use]
+    pub fn search_client(&self) -> Option<circuit_breaker::BreakerSearch> {
+        let client = self.search.clone()?;
+        let breaker = self
+            .search_breaker
+            .clone()
+            .unwrap_or_else(|| Arc::new(CircuitBreaker::new("search", BreakerSettings::default())));
+        Some(circuit_breaker::BreakerSearch::new(client, breaker))
+    }
+
+    /// Connection for read-only queries
+    ///
+    /// The read replica when `DATABASE_READ_URL` is set, otherwise the
+    /// primary. `None` when the database is disabled.
+    #[cfg(feature = "db")]
+    #[must_use]
+    pub fn read_db(&self) -> Option<&sea_orm::DatabaseConnection> {
+        self.db_read.as_ref().or(self.db.as_ref())
+    }
+
+    /// Audit sink publishing to `AUDIT_BROKER_SUBJECT`
+    ///
+    /// `None` when the subject is unset or the broker is disabled.
+    #[cfg(feature = "broker")]
+    #[must_use]
+    pub fn audit_sink(&self) -> Option<BrokerAuditSink> {
+        let broker = self.broker.clone()?;
+        let subject = self.audit_subject.clone()?;
+        Some(BrokerAuditSink::new(broker, subject))
+    }
+
+    /// Registry holding a check per initialized component
+    ///
+    /// Same checks as the `ReadyChecker` impl, minus the disabled
+    /// components, so apps can register their own next to them and pass the
+    /// registry to `AppBuilder::with_ready_checker` instead of the infra.
+    #[must_use]
+    pub fn health_registry(&self) -> HealthRegistry {
+        #[allow(unused_mut)]
+        let mut registry = HealthRegistry::new();
+
+        #[cfg(feature = "db")]
+        for (name, db) in [("database", &self.db), ("database_read", &self.db_read)] {
+            if let Some(db) = db.clone() {
+                let critical = self.readiness.is_critical(name);
+                registry = registry.register(name, critical, move || {
+                    let db = db.clone();
+                    async move { db.ping().await.map_err(|e| e.to_string()) }
+                });
+            }
+        }
+
+        #[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+        if let Some(cache) = self.cache.clone() {
+            let critical = self.readiness.is_critical("cache");
+            registry = registry.register("cache", critical, move || {
+                let cache = cache.clone();
+                async move {
+                    cache.ping().await.map_err(|e| {
+                        if e.is::<CircuitOpen>() {
+                            "circuit open".to_string()
+                        } else {
+                            e.to_string()
+                        }
+                    })
+                }
+            });
+        }
+
+        #[cfg(feature = "broker")]
+        if let Some(broker) = self.broker.clone() {
+            let critical = self.readiness.is_critical("broker");
+            registry = registry.register("broker", critical, move || {
+                let broker = broker.clone();
+                async move { broker.ping().await.map_err(|e| e.to_string()) }
+            });
+        }
+
+        #[cfg(feature = "mailer")]
+        if let Some(mailer) = self.mailer.clone() {
+            let critical = self.readiness.is_critical("mailer");
+            registry = registry.register("mailer", critical, move || {
+                let mailer = mailer.clone();
+                async move { mailer.ping().await.map_err(|e| format!("{e:#}")) }
+            });
+        }
+
+        registry
+    }
 }
 
 #[async_trait::async_trait]
 impl ReadyChecker for Infra {
+    #[allow(clippy::vec_init_then_push)]
     async fn ready_checks(&self) -> Vec<HealthCheck> {
         let mut checks = Vec::new();
 
         // Database Check
         #[cfg(feature = "db")]
         if let Some(db) = &self.db {
-            match db.ping().await {
-                Ok(_) => checks.push(HealthCheck::ok("database")),
-                Err(e) => checks.push(HealthCheck::fail("database", e.to_string())),
-            }
+            let check = match db.ping().await {
+                Ok(()) => HealthCheck::ok("database"),
+                Err(e) => HealthCheck::fail("database", e.to_string()),
+            };
+            checks.push(check.with_critical(self.readiness.is_critical("database")));
         } else {
             checks.push(HealthCheck::skip("database", "disabled"));
         }
         #[cfg(not(feature = "db"))]
         checks.push(HealthCheck::skip("database", "not-compiled"));
 
+        // Read replica, only when one is configured
+        #[cfg(feature = "db")]
+        if let Some(db) = &self.db_read {
+            let check = match db.ping().await {
+                Ok(()) => HealthCheck::ok("database_read"),
+                Err(e) => HealthCheck::fail("database_read", e.to_string()),
+            };
+            checks.push(check.with_critical(self.readiness.is_critical("database_read")));
+        }
+
         // Cache Check
         #[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
         if let Some(cache) = &self.cache {
-             match cache.ping().await {
-                 Ok(_) => checks.push(HealthCheck::ok("cache")),
-                 Err(e) => checks.push(HealthCheck::fail("cache", e.to_string())),
-             }
+            let check = match cache.ping().await {
+                Ok(()) => {
+                    let mut check = HealthCheck::ok("cache");
+                    check.message = self
+                        .cache_stats()
+                        .and_then(|stats| stats.hit_rate())
+                        .map(|rate| format!("hit_rate={rate:.2}"));
+                    check
+                }
+                // Known down, and failing fast rather than timing out
+                Err(e) if e.is::<CircuitOpen>() => HealthCheck::warn("cache", "circuit open"),
+                Err(e) => HealthCheck::fail("cache", e.to_string()),
+            };
+            checks.push(check.with_critical(self.readiness.is_critical("cache")));
         } else {
-             checks.push(HealthCheck::skip("cache", "disabled"));
+            checks.push(HealthCheck::skip("cache", "disabled"));
         }
         #[cfg(not(any(feature = "cache-moka", feature = "cache-redis")))]
         checks.push(HealthCheck::skip("cache", "not-compiled"));
 
+        // Broker Check
+        #[cfg(feature = "broker")]
+        if let Some(broker) = &self.broker {
+            let check = match broker.ping().await {
+                Ok(()) => HealthCheck::ok("broker"),
+                Err(e) => HealthCheck::fail("broker", e.to_string()),
+            };
+            checks.push(check.with_critical(self.readiness.is_critical("broker")));
+        } else {
+            checks.push(HealthCheck::skip("broker", "disabled"));
+        }
+        #[cfg(not(feature = "broker"))]
+        checks.push(HealthCheck::skip("broker", "not-compiled"));
+
+        // Mailer Check (NOOP round trip, not critical unless configured)
+        #[cfg(feature = "mailer")]
+        if let Some(mailer) = &self.mailer {
+            let check = match mailer.ping().await {
+                Ok(()) => HealthCheck::ok("mailer"),
+                Err(e) => HealthCheck::fail("mailer", format!("{e:#}")),
+            };
+            checks.push(check.with_critical(self.readiness.is_critical("mailer")));
+        } else {
+            checks.push(HealthCheck::skip("mailer", "disabled"));
+        }
+        #[cfg(not(feature = "mailer"))]
+        checks.push(HealthCheck::skip("mailer", "not-compiled"));
+
         checks
     }
 }
@@ -166,59 +637,344 @@ impl ReadyChecker for Infra {
 // Internal initializers
 
 #[cfg(feature = "db")]
-async fn init_db(_config: &Config) -> anyhow::Result<sea_orm::DatabaseConnection> {
-    use anyhow::Context;
+async fn init_db(
+    url: &str,
+    max_connections: u32,
+    min_connections: u32,
+) -> anyhow::Result<sea_orm::DatabaseConnection> {
     use sea_orm::{ConnectOptions, Database};
-    
-    // We would need DATABASE_URL logic here. 
-    // Assuming config might have it or we load it from env directly since it's sensitive.
-    // Core config didn't have specific DB config struct yet.
-    // For now, let's assume DATABASE_URL env var.
-    let url = std::env::var("DATABASE_URL")
-        .or_else(|_| std::env::var("DB_URL"))
-        .context("DATABASE_URL or DB_URL must be set")?;
-    
+
     let mut opt = ConnectOptions::new(url);
-    opt.max_connections(100)
-       .min_connections(5)
-       .connect_timeout(Duration::from_secs(10))
-       .acquire_timeout(Duration::from_secs(10))
-       .idle_timeout(Duration::from_secs(10))
-       .max_lifetime(Duration::from_secs(1800))
-       .sqlx_logging(false);
+    opt.max_connections(max_connections)
+        .min_connections(min_connections)
+        .connect_timeout(Duration::from_secs(10))
+        .acquire_timeout(Duration::from_secs(10))
+        .idle_timeout(Duration::from_secs(10))
+        .max_lifetime(Duration::from_mins(30))
+        .sqlx_logging(false);
 
     let db = Database::connect(opt).await?;
     Ok(db)
 }
 
 #[cfg(feature = "cache-moka")]
-fn init_moka_cache(_config: &Config) -> Arc<dyn Cache + Send + Sync> {
-    // Placeholder Moka init
-    Arc::new(MokaCacheStub)
+fn init_moka_cache(config: &Config) -> Arc<dyn Cache + Send + Sync> {
+    Arc::new(MokaCache::new(
+        config.cache.cache_max_entries,
+        Duration::from_secs(config.cache.cache_ttl_seconds),
+    ))
 }
 
 #[cfg(feature = "cache-redis")]
-async fn init_redis_cache(_config: &Config) -> anyhow::Result<Arc<dyn Cache + Send + Sync>> {
-    // Placeholder Redis init
-    Ok(Arc::new(RedisCacheStub))
+async fn init_redis_cache(config: &Config) -> anyhow::Result<RedisCache> {
+    use anyhow::Context;
+
+    let url = config.cache.cache_redis_url.clone().with_context(|| {
+        format!(
+            "CACHE_REDIS_URL must be set for CACHE_BACKEND={}",
+            config.cache.cache_backend
+        )
+    })?;
+
+    let connect_timeout = Duration::from_secs(config.cache.cache_redis_connect_timeout_seconds);
+    let mut pool = deadpool_redis::PoolConfig::new(config.cache.cache_redis_pool_size);
+    pool.timeouts.create = Some(connect_timeout);
+    pool.timeouts.wait = Some(connect_timeout);
+
+    let mut pool_config = deadpool_redis::Config::from_url(url);
+    pool_config.pool = Some(pool);
+    let pool = pool_config.create_pool(Some(deadpool_redis::Runtime::Tokio1))?;
+
+    let cache = RedisCache {
+        pool,
+        default_ttl: Duration::from_secs(config.cache.cache_ttl_seconds),
+    };
+    cache.ping().await.context("Failed to connect to Redis")?;
+
+    Ok(cache)
 }
 
-// Cache Abstraction (Stub for now)
+/// Byte-oriented cache shared by the application and the session store
+///
+/// `ttl: None` uses `CACHE_TTL_SECONDS`.
 #[async_trait::async_trait]
 pub trait Cache {
     async fn ping(&self) -> anyhow::Result<()>;
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    /// Value of `key` with its remaining lifetime, `None` when unknown
+    ///
+    /// The default only reads the value.
+    async fn get_with_ttl(&self, key: &str) -> anyhow::Result<Option<(Vec<u8>, Option<Duration>)>> {
+        Ok(self.get(key).await?.map(|value| (value, None)))
+    }
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()>;
+    /// Atomically store `value` unless `key` already holds one
+    ///
+    /// Returns whether the value was stored.
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<bool>;
+    async fn delete(&self, key: &str) -> anyhow::Result<()>;
 }
 
-#[allow(dead_code)]
-struct MokaCacheStub;
+/// In-process cache (Moka) with per-entry TTL
+#[cfg(feature = "cache-moka")]
+pub struct MokaCache {
+    inner: moka::future::Cache<String, MokaEntry>,
+    default_ttl: Duration,
+}
+
+#[cfg(feature = "cache-moka")]
+#[derive(Clone)]
+struct MokaEntry {
+    value: Arc<[u8]>,
+    ttl: Duration,
+    stored_at: std::time::Instant,
+}
+
+#[cfg(feature = "cache-moka")]
+struct MokaExpiry;
+
+#[cfg(feature = "cache-moka")]
+impl moka::Expiry<String, MokaEntry> for MokaExpiry {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &MokaEntry,
+        _created_at: std::time::Instant,
+    ) -> Option<Duration> {
+        Some(value.ttl)
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        value: &MokaEntry,
+        _updated_at: std::time::Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(value.ttl)
+    }
+}
+
+#[cfg(feature = "cache-moka")]
+impl MokaCache {
+    /// Create a cache holding at most `max_entries` entries
+    #[must_use]
+    pub fn new(max_entries: u64, default_ttl: Duration) -> Self {
+        Self {
+            inner: moka::future::Cache::builder()
+                .max_capacity(max_entries)
+                .expire_after(MokaExpiry)
+                .build(),
+            default_ttl,
+        }
+    }
+}
+
+#[cfg(feature = "cache-moka")]
 #[async_trait::async_trait]
-impl Cache for MokaCacheStub {
-    async fn ping(&self) -> anyhow::Result<()> { Ok(()) }
+impl Cache for MokaCache {
+    async fn ping(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.inner.get(key).await.map(|entry| entry.value.to_vec()))
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> anyhow::Result<Option<(Vec<u8>, Option<Duration>)>> {
+        Ok(self.inner.get(key).await.map(|entry| {
+            let remaining = entry.ttl.saturating_sub(entry.stored_at.elapsed());
+            (entry.value.to_vec(), Some(remaining))
+        }))
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        let entry = MokaEntry {
+            value: value.into(),
+            ttl: ttl.unwrap_or(self.default_ttl),
+            stored_at: std::time::Instant::now(),
+        };
+        self.inner.insert(key.to_string(), entry).await;
+        Ok(())
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<bool> {
+        let entry = MokaEntry {
+            value: value.into(),
+            ttl: ttl.unwrap_or(self.default_ttl),
+            stored_at: std::time::Instant::now(),
+        };
+        let entry = self
+            .inner
+            .entry(key.to_string())
+            .or_insert_with(async { entry })
+            .await;
+        Ok(entry.is_fresh())
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.inner.invalidate(key).await;
+        Ok(())
+    }
+}
+
+/// Redis/Valkey cache over a connection pool
+#[cfg(feature = "cache-redis")]
+pub struct RedisCache {
+    pool: deadpool_redis::Pool,
+    default_ttl: Duration,
 }
 
-#[allow(dead_code)]
-struct RedisCacheStub;
+#[cfg(feature = "cache-redis")]
 #[async_trait::async_trait]
-impl Cache for RedisCacheStub {
-    async fn ping(&self) -> anyhow::Result<()> { Ok(()) }
+impl Cache for RedisCache {
+    async fn ping(&self) -> anyhow::Result<()> {
+        let mut conn = self.pool.get().await?;
+        deadpool_redis::redis::cmd("PING")
+            .query_async::<String>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut conn = self.pool.get().await?;
+        let value = deadpool_redis::redis::cmd("GET")
+            .arg(key)
+            .query_async::<Option<Vec<u8>>>(&mut conn)
+            .await?;
+        Ok(value)
+    }
+
+    async fn get_with_ttl(&self, key: &str) -> anyhow::Result<Option<(Vec<u8>, Option<Duration>)>> {
+        let mut conn = self.pool.get().await?;
+        let (value, pttl) = deadpool_redis::redis::pipe()
+            .atomic()
+            .cmd("GET")
+            .arg(key)
+            .cmd("PTTL")
+            .arg(key)
+            .query_async::<(Option<Vec<u8>>, i64)>(&mut conn)
+            .await?;
+        // PTTL is negative for keys without an expiry
+        let remaining = u64::try_from(pttl).ok().map(Duration::from_millis);
+        Ok(value.map(|value| (value, remaining)))
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        let ttl_ms = ttl.unwrap_or(self.default_ttl).as_millis().max(1);
+        let mut conn = self.pool.get().await?;
+        deadpool_redis::redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("PX")
+            .arg(u64::try_from(ttl_ms).unwrap_or(u64::MAX))
+            .query_async::<()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<bool> {
+        let ttl_ms = ttl.unwrap_or(self.default_ttl).as_millis().max(1);
+        let mut conn = self.pool.get().await?;
+        let stored = deadpool_redis::redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .arg("NX")
+            .arg("PX")
+            .arg(u64::try_from(ttl_ms).unwrap_or(u64::MAX))
+            .query_async::<Option<String>>(&mut conn)
+            .await?;
+        Ok(stored.is_some())
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        let mut conn = self.pool.get().await?;
+        deadpool_redis::redis::cmd("DEL")
+            .arg(key)
+            .query_async::<()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "db"))]
+mod tests {
+    use super::*;
+
+    fn config(read_url: Option<&str>) -> Config {
+        Config::builder()
+            .feature_db(true)
+            .feature_cache(false)
+            .with(|config| {
+                config.database.database_url = Some("sqlite::memory:".to_string());
+                config.database.database_read_url = read_url.map(str::to_string);
+                config.database.db_min_connections = 1;
+                config.database.db_read_min_connections = 1;
+            })
+            .build()
+    }
+
+    fn database_checks(checks: &[HealthCheck]) -> Vec<&HealthCheck> {
+        checks
+            .iter()
+            .filter(|check| check.name.starts_with("database"))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_reads_fall_back_to_primary() {
+        let infra = Infra::init(&config(None)).await.unwrap();
+        assert!(infra.db_read.is_none());
+        assert!(infra.read_db().is_some());
+
+        let checks = infra.ready_checks().await;
+        let checks = database_checks(&checks);
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].name, "database");
+    }
+
+    #[tokio::test]
+    async fn test_read_replica_is_checked_separately() {
+        let infra = Infra::init(&config(Some("sqlite::memory:"))).await.unwrap();
+        assert!(infra.db_read.is_some());
+
+        let checks = infra.ready_checks().await;
+        let checks = database_checks(&checks);
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[0].name, "database");
+        assert!(checks[0].critical);
+        assert_eq!(checks[1].name, "database_read");
+        assert_eq!(checks[1].status, "ok");
+        assert!(!checks[1].critical);
+    }
+
+    #[tokio::test]
+    async fn test_health_registry_extends_components() {
+        let infra = Infra::init(&config(Some("sqlite::memory:"))).await.unwrap();
+        let registry = infra
+            .health_registry()
+            .register("payments", true, || async { Err("refused".to_string()) });
+        assert_eq!(registry.names(), ["database", "database_read", "payments"]);
+
+        let checks = registry.ready_checks().await;
+        assert_eq!(checks[0].status, "ok");
+        assert!(checks[0].critical);
+        assert!(!checks[1].critical);
+        assert_eq!(checks[2].status, "fail");
+    }
 }
@@ -0,0 +1,368 @@
+//! Outgoing email over SMTP
+//!
+//! [`Infra::mailer`](crate::Infra::mailer) is an [`SmtpMailer`] built from the
+//! `SMTP_*` settings when `FEATURE_MAILER=true`. Application code only sees
+//! the [`Mailer`] trait:
+//!
+//! ```ignore
+//! let mailer = infra.mailer.clone().context("FEATURE_MAILER=false")?;
+//! mailer
+//!     .send(Email::new("ada@example.com", "Welcome", "Hello Ada").with_html("<p>Hello Ada</p>"))
+//!     .await?;
+//! ```
+//!
+//! With the `test-util` feature, [`MockMailer`] keeps the messages in memory
+//! for assertions.
+
+use std::time::Duration;
+
+use anyhow::Context;
+use barrzen_axum_core::{MailConfig, SmtpTls};
+use lettre::{
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::{Mailbox, MultiPart, header::ContentType},
+    transport::smtp::{PoolConfig, authentication::Credentials},
+};
+
+/// Message to send
+///
+/// `text` is always sent; with `html` the message is `multipart/alternative`
+/// and clients pick the part they can display.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Email {
+    /// Recipient addresses, `ada@example.com` or `Ada <ada@example.com>`
+    pub to: Vec<String>,
+    pub subject: String,
+    pub text: String,
+    pub html: Option<String>,
+}
+
+impl Email {
+    /// Plain text message to a single recipient
+    #[must_use]
+    pub fn new(to: impl Into<String>, subject: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            to: vec![to.into()],
+            subject: subject.into(),
+            text: text.into(),
+            html: None,
+        }
+    }
+
+    /// Add a recipient
+    #[must_use]
+    pub fn with_to(mut self, to: impl Into<String>) -> Self {
+        self.to.push(to.into());
+        self
+    }
+
+    /// Add an HTML alternative to the text
+    #[must_use]
+    pub fn with_html(mut self, html: impl Into<String>) -> Self {
+        self.html = Some(html.into());
+        self
+    }
+}
+
+/// Email delivery
+#[async_trait::async_trait]
+pub trait Mailer: Send + Sync {
+    /// Check that the server answers
+    async fn ping(&self) -> anyhow::Result<()>;
+
+    /// Send `email` from the configured sender
+    ///
+    /// Returns once the server accepted the message.
+    async fn send(&self, email: Email) -> anyhow::Result<()>;
+}
+
+/// [`Mailer`] over a pool of SMTP connections
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// Build the transport from the `SMTP_*` settings
+    ///
+    /// No connection is opened until the first send or ping.
+    ///
+    /// # Errors
+    /// Returns error if `SMTP_HOST` or `SMTP_FROM` is missing or invalid.
+    pub fn from_config(config: &MailConfig) -> anyhow::Result<Self> {
+        let host = config
+            .smtp_host
+            .as_deref()
+            .context("SMTP_HOST must be set")?;
+        let from = config
+            .smtp_from
+            .as_deref()
+            .context("SMTP_FROM must be set")?;
+        let from = from
+            .parse::<Mailbox>()
+            .with_context(|| format!("SMTP_FROM is not a valid address: {from}"))?;
+
+        let builder = match config.smtp_tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+        };
+        let mut builder = builder
+            .port(config.smtp_port)
+            .timeout(Some(Duration::from_secs(config.smtp_timeout_seconds)))
+            .pool_config(PoolConfig::new().max_size(config.smtp_pool_size));
+        if let Some(user) = &config.smtp_user {
+            let password = config.smtp_password.clone().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(user.clone(), password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Mailer for SmtpMailer {
+    async fn ping(&self) -> anyhow::Result<()> {
+        // Connects (EHLO, STARTTLS, AUTH) and sends a NOOP
+        let connected = self
+            .transport
+            .test_connection()
+            .await
+            .context("SMTP connection failed")?;
+        anyhow::ensure!(connected, "SMTP server did not answer NOOP");
+        Ok(())
+    }
+
+    async fn send(&self, email: Email) -> anyhow::Result<()> {
+        let message = build_message(&self.from, &email)?;
+        self.transport
+            .send(message)
+            .await
+            .with_context(|| format!("Failed to send email to {}", email.to.join(", ")))?;
+        Ok(())
+    }
+}
+
+/// MIME message of `email` from `from`
+fn build_message(from: &Mailbox, email: &Email) -> anyhow::Result<Message> {
+    anyhow::ensure!(!email.to.is_empty(), "Email has no recipients");
+
+    let mut builder = Message::builder()
+        .from(from.clone())
+        .subject(email.subject.clone());
+    for to in &email.to {
+        let mailbox = to
+            .parse::<Mailbox>()
+            .with_context(|| format!("Invalid recipient address: {to}"))?;
+        builder = builder.to(mailbox);
+    }
+
+    let message = match &email.html {
+        Some(html) => builder.multipart(MultiPart::alternative_plain_html(
+            email.text.clone(),
+            html.clone(),
+        )),
+        None => builder
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.text.clone()),
+    };
+    message.context("Failed to build email")
+}
+
+#[cfg(feature = "test-util")]
+pub use mock::MockMailer;
+
+#[cfg(feature = "test-util")]
+mod mock {
+    use std::sync::{Mutex, PoisonError};
+
+    use super::{Email, Mailbox, Mailer, build_message};
+
+    /// In-memory [`Mailer`] for tests (`test-util` feature)
+    ///
+    /// Rejects what the SMTP mailer would reject (no or malformed
+    /// recipients) and keeps every accepted email for assertions.
+    #[derive(Default)]
+    pub struct MockMailer {
+        sent: Mutex<Vec<Email>>,
+    }
+
+    impl MockMailer {
+        #[must_use]
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Every email sent so far
+        #[must_use]
+        pub fn sent(&self) -> Vec<Email> {
+            self.sent
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Mailer for MockMailer {
+        async fn ping(&self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn send(&self, email: Email) -> anyhow::Result<()> {
+            let from: Mailbox = "mock@localhost".parse()?;
+            build_message(&from, &email)?;
+            self.sent
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(email);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> MailConfig {
+        MailConfig {
+            smtp_host: Some("localhost".to_string()),
+            smtp_from: Some("App <app@example.com>".to_string()),
+            ..MailConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_build_message() {
+        let from: Mailbox = "App <app@example.com>".parse().unwrap();
+        let email = Email::new("ada@example.com", "Welcome", "Hello Ada")
+            .with_to("Bob <bob@example.com>")
+            .with_html("<p>Hello Ada</p>");
+
+        let raw = String::from_utf8(build_message(&from, &email).unwrap().formatted()).unwrap();
+        assert!(raw.contains("From: App <app@example.com>"), "{raw}");
+        assert!(
+            raw.contains("To: ada@example.com, Bob <bob@example.com>"),
+            "{raw}"
+        );
+        assert!(raw.contains("Subject: Welcome"), "{raw}");
+        assert!(raw.contains("multipart/alternative"), "{raw}");
+        assert!(
+            raw.contains("Hello Ada") && raw.contains("<p>Hello Ada</p>"),
+            "{raw}"
+        );
+
+        let plain = Email::new("ada@example.com", "Welcome", "Hello Ada");
+        let raw = String::from_utf8(build_message(&from, &plain).unwrap().formatted()).unwrap();
+        assert!(raw.contains("Content-Type: text/plain"), "{raw}");
+        assert!(!raw.contains("multipart"), "{raw}");
+    }
+
+    #[test]
+    fn test_build_message_rejects_bad_recipients() {
+        let from: Mailbox = "app@example.com".parse().unwrap();
+        let no_recipients = Email {
+            subject: "Welcome".to_string(),
+            ..Email::default()
+        };
+        assert!(build_message(&from, &no_recipients).is_err());
+
+        let error =
+            build_message(&from, &Email::new("not an address", "Welcome", "Hello")).unwrap_err();
+        assert!(error.to_string().contains("not an address"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_from_config() {
+        for tls in [SmtpTls::None, SmtpTls::Starttls, SmtpTls::Implicit] {
+            let config = MailConfig {
+                smtp_tls: tls,
+                smtp_user: Some("app".to_string()),
+                smtp_password: Some("secret".to_string()),
+                ..config()
+            };
+            assert!(SmtpMailer::from_config(&config).is_ok(), "{tls}");
+        }
+
+        let missing_from = MailConfig {
+            smtp_from: None,
+            ..config()
+        };
+        assert!(SmtpMailer::from_config(&missing_from).is_err());
+        let bad_from = MailConfig {
+            smtp_from: Some("app".to_string()),
+            ..config()
+        };
+        assert!(SmtpMailer::from_config(&bad_from).is_err());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_mock_mailer() {
+        let mailer: std::sync::Arc<dyn Mailer> = std::sync::Arc::new(MockMailer::new());
+        mailer.ping().await.unwrap();
+        mailer
+            .send(Email::new("ada@example.com", "Welcome", "Hello Ada"))
+            .await
+            .unwrap();
+        assert!(
+            mailer
+                .send(Email::new("not an address", "Welcome", "Hello"))
+                .await
+                .is_err()
+        );
+
+        let mock = MockMailer::new();
+        let email = Email::new("ada@example.com", "Reset", "Your code is 1234");
+        mock.send(email.clone()).await.unwrap();
+        assert_eq!(mock.sent(), vec![email]);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_readiness_not_critical() {
+        use barrzen_axum_core::ReadyChecker;
+
+        let infra = crate::Infra {
+            mailer: Some(std::sync::Arc::new(MockMailer::new())),
+            ..crate::Infra::default()
+        };
+        let checks = infra.ready_checks().await;
+        let check = checks.iter().find(|check| check.name == "mailer").unwrap();
+        assert_eq!(check.status, "ok");
+        assert!(!check.critical);
+    }
+
+    /// Runs against `MailHog` (or any open relay) when `SMTP_TEST_HOST` is set,
+    /// e.g. `SMTP_TEST_HOST=localhost SMTP_TEST_PORT=1025`
+    #[tokio::test]
+    async fn test_smtp_server() {
+        let Ok(host) = std::env::var("SMTP_TEST_HOST") else {
+            return;
+        };
+        let port = std::env::var("SMTP_TEST_PORT")
+            .ok()
+            .and_then(|port| port.parse().ok())
+            .unwrap_or(1025);
+        let mailer = SmtpMailer::from_config(&MailConfig {
+            smtp_host: Some(host),
+            smtp_port: port,
+            smtp_tls: SmtpTls::None,
+            ..config()
+        })
+        .unwrap();
+
+        mailer.ping().await.unwrap();
+        mailer
+            .send(
+                Email::new("ada@example.com", "barrzen-axum-infra test", "Hello Ada")
+                    .with_html("<p>Hello Ada</p>"),
+            )
+            .await
+            .unwrap();
+    }
+}
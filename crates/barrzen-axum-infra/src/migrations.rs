@@ -0,0 +1,309 @@
+//! Schema migrations at startup
+//!
+//! Run the application's sea-orm migrator before the listener binds:
+//!
+//! ```ignore
+//! let infra = Infra::init(&config).await?;
+//! infra.migrate_on_startup::<Migrator>(&config).await?;
+//! AppBuilder::new(config, build_info).serve().await?;
+//! ```
+//!
+//! `DB_RUN_MIGRATIONS=true` applies pending migrations, `check` fails startup
+//! while any are pending. On Postgres an advisory lock keeps replicas from
+//! migrating at the same time; the second one waits and finds nothing left.
+
+use std::time::Instant;
+
+use anyhow::Context;
+use barrzen_axum_core::{Config, RunMigrations};
+use sea_orm::{
+    ConnectionTrait, DatabaseBackend, DatabaseConnection, DatabaseTransaction, Statement,
+    TransactionTrait,
+};
+use sea_orm_migration::MigratorTrait;
+
+use crate::Infra;
+
+/// Postgres advisory lock key held while migrating (`"barrzen"` in ASCII)
+pub const MIGRATION_LOCK_KEY: i64 = 0x0062_6172_727a_656e;
+
+impl Infra {
+    /// Apply the pending migrations of `M` one at a time
+    ///
+    /// Returns the names of the applied migrations, empty when the schema was
+    /// up to date.
+    ///
+    /// # Errors
+    /// Returns error if the database is not initialized, the lock cannot be
+    /// taken, or a migration fails; the error names the failed migration.
+    pub async fn run_migrations<M: MigratorTrait>(&self) -> anyhow::Result<Vec<String>> {
+        let db = self.migration_db()?;
+        let lock = lock(db).await?;
+        // On error the lock transaction is dropped, which rolls back and releases it
+        let applied = apply::<M>(db).await?;
+        if let Some(lock) = lock {
+            lock.commit()
+                .await
+                .context("Failed to release the migration lock")?;
+        }
+        Ok(applied)
+    }
+
+    /// Names of the migrations of `M` not yet applied
+    ///
+    /// # Errors
+    /// Returns error if the database is not initialized or the migration
+    /// table cannot be read.
+    pub async fn pending_migrations<M: MigratorTrait>(&self) -> anyhow::Result<Vec<String>> {
+        let db = self.migration_db()?;
+        let pending = M::get_pending_migrations(db)
+            .await
+            .context("Failed to read the migration status")?;
+        Ok(pending
+            .iter()
+            .map(|migration| migration.name().to_string())
+            .collect())
+    }
+
+    /// Apply or check the migrations of `M` as set by `DB_RUN_MIGRATIONS`
+    ///
+    /// # Errors
+    /// Returns error if a migration fails, or with `check` if migrations are
+    /// pending.
+    pub async fn migrate_on_startup<M: MigratorTrait>(
+        &self,
+        config: &Config,
+    ) -> anyhow::Result<()> {
+        match config.database.db_run_migrations {
+            RunMigrations::Off => {}
+            RunMigrations::Apply => {
+                let applied = self.run_migrations::<M>().await?;
+                tracing::info!(applied = applied.len(), "Database schema is up to date");
+            }
+            RunMigrations::Check => {
+                let pending = self.pending_migrations::<M>().await?;
+                anyhow::ensure!(
+                    pending.is_empty(),
+                    "DB_RUN_MIGRATIONS=check: {} pending migration(s): {}",
+                    pending.len(),
+                    pending.join(", ")
+                );
+                tracing::info!("Database schema is up to date");
+            }
+        }
+        Ok(())
+    }
+
+    fn migration_db(&self) -> anyhow::Result<&DatabaseConnection> {
+        self.db
+            .as_ref()
+            .context("Migrations require the database (FEATURE_DB=true)")
+    }
+}
+
+/// Take the Postgres advisory lock in a transaction of its own
+///
+/// The transaction holds one pool connection until it ends, so the lock is
+/// released with it even if the process dies. Other backends are not locked.
+async fn lock(db: &DatabaseConnection) -> anyhow::Result<Option<DatabaseTransaction>> {
+    if db.get_database_backend() != DatabaseBackend::Postgres {
+        return Ok(None);
+    }
+    let lock = db
+        .begin()
+        .await
+        .context("Failed to open the migration lock transaction")?;
+    tracing::debug!(key = MIGRATION_LOCK_KEY, "Waiting for the migration lock");
+    lock.execute(Statement::from_sql_and_values(
+        DatabaseBackend::Postgres,
+        "SELECT pg_advisory_xact_lock($1)",
+        [MIGRATION_LOCK_KEY.into()],
+    ))
+    .await
+    .context("Failed to take the migration lock")?;
+    Ok(Some(lock))
+}
+
+async fn apply<M: MigratorTrait>(db: &DatabaseConnection) -> anyhow::Result<Vec<String>> {
+    // Read after the lock so a replica that waited sees what the other applied
+    let pending = M::get_pending_migrations(db)
+        .await
+        .context("Failed to read the migration status")?;
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+    tracing::info!(pending = pending.len(), "Applying database migrations");
+
+    let mut applied = Vec::with_capacity(pending.len());
+    for migration in pending {
+        let name = migration.name().to_string();
+        let start = Instant::now();
+        M::up(db, Some(1))
+            .await
+            .with_context(|| format!("Migration {name} failed"))?;
+        let elapsed_ms = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
+        tracing::info!(migration = %name, elapsed_ms, "Migration applied");
+        applied.push(name);
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::Database;
+    use sea_orm_migration::{MigrationName, MigrationTrait, SchemaManager, async_trait};
+
+    use super::*;
+
+    struct CreateOrders;
+
+    impl MigrationName for CreateOrders {
+        fn name(&self) -> &'static str {
+            "m20240101_000001_create_orders"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for CreateOrders {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), sea_orm::DbErr> {
+            manager
+                .get_connection()
+                .execute_unprepared("CREATE TABLE orders (id INTEGER PRIMARY KEY)")
+                .await
+                .map(|_| ())
+        }
+    }
+
+    struct AddTotal;
+
+    impl MigrationName for AddTotal {
+        fn name(&self) -> &'static str {
+            "m20240102_000001_add_total"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for AddTotal {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), sea_orm::DbErr> {
+            manager
+                .get_connection()
+                .execute_unprepared("ALTER TABLE orders ADD COLUMN total INTEGER")
+                .await
+                .map(|_| ())
+        }
+    }
+
+    struct Broken;
+
+    impl MigrationName for Broken {
+        fn name(&self) -> &'static str {
+            "m20240103_000001_broken"
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MigrationTrait for Broken {
+        async fn up(&self, manager: &SchemaManager) -> Result<(), sea_orm::DbErr> {
+            manager
+                .get_connection()
+                .execute_unprepared("ALTER TABLE missing ADD COLUMN total INTEGER")
+                .await
+                .map(|_| ())
+        }
+    }
+
+    struct Migrator;
+
+    impl MigratorTrait for Migrator {
+        fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+            vec![Box::new(CreateOrders), Box::new(AddTotal)]
+        }
+    }
+
+    struct BrokenMigrator;
+
+    impl MigratorTrait for BrokenMigrator {
+        fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+            vec![Box::new(CreateOrders), Box::new(Broken)]
+        }
+    }
+
+    async fn infra() -> Infra {
+        Infra {
+            db: Some(Database::connect("sqlite::memory:").await.unwrap()),
+            ..Infra::default()
+        }
+    }
+
+    fn config(mode: RunMigrations) -> Config {
+        Config::builder()
+            .feature_db(true)
+            .with(|config| config.database.db_run_migrations = mode)
+            .build()
+    }
+
+    #[tokio::test]
+    async fn test_apply_and_rerun() {
+        let infra = infra().await;
+        let applied = infra.run_migrations::<Migrator>().await.unwrap();
+        assert_eq!(
+            applied,
+            vec![
+                "m20240101_000001_create_orders",
+                "m20240102_000001_add_total"
+            ]
+        );
+        infra
+            .db
+            .as_ref()
+            .unwrap()
+            .execute_unprepared("INSERT INTO orders (id, total) VALUES (1, 10)")
+            .await
+            .unwrap();
+
+        assert!(infra.run_migrations::<Migrator>().await.unwrap().is_empty());
+        assert!(
+            infra
+                .pending_migrations::<Migrator>()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_mode() {
+        let infra = infra().await;
+        let error = infra
+            .migrate_on_startup::<Migrator>(&config(RunMigrations::Check))
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("2 pending migration(s)"), "{error}");
+        assert!(error.contains("m20240102_000001_add_total"), "{error}");
+
+        infra
+            .migrate_on_startup::<Migrator>(&config(RunMigrations::Apply))
+            .await
+            .unwrap();
+        infra
+            .migrate_on_startup::<Migrator>(&config(RunMigrations::Check))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failure_names_the_migration() {
+        let infra = infra().await;
+        let error = infra
+            .run_migrations::<BrokenMigrator>()
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("m20240103_000001_broken"), "{error}");
+
+        let pending = infra.pending_migrations::<BrokenMigrator>().await.unwrap();
+        assert_eq!(pending, vec!["m20240103_000001_broken"]);
+        assert!(Infra::default().run_migrations::<Migrator>().await.is_err());
+    }
+}
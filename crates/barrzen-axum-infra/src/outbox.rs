@@ -0,0 +1,685 @@
+//! Transactional outbox
+//!
+//! Events are written to the `outbox_events` table in the same transaction
+//! as the business change, so they exist exactly when the change was
+//! committed. A relay task then publishes them through the [`Broker`] and
+//! marks them published:
+//!
+//! ```ignore
+//! // Migrator of the application
+//! fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+//!     vec![Box::new(outbox::CreateOutboxEvents), Box::new(m20240101_create_orders::Migration)]
+//! }
+//!
+//! let txn = db.begin().await?;
+//! order.insert(&txn).await?;
+//! Outbox::enqueue_json(&txn, "orders.created", &order_created).await?;
+//! txn.commit().await?;
+//!
+//! let relay = infra.outbox_relay(&config).context("outbox needs FEATURE_DB and FEATURE_BROKER")?;
+//! let app = AppBuilder::new(config, build_info)
+//!     .spawn_task("outbox-relay", move |shutdown| {
+//!         let relay = relay.clone();
+//!         async move { relay.run(shutdown).await }
+//!     });
+//! ```
+//!
+//! Delivery is at least once: an event published right before a crash is
+//! published again. A failed publish is retried with exponential backoff
+//! (`OUTBOX_RETRY_BASE_MS` doubling up to `OUTBOX_RETRY_MAX_MS`); after
+//! `OUTBOX_MAX_ATTEMPTS` failures the event is dead-lettered and left for
+//! [`Outbox::requeue`]. On Postgres, rows are claimed with
+//! `FOR UPDATE SKIP LOCKED` so several replicas can run the relay.
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use barrzen_axum_core::{CancellationToken, Config, OutboxConfig};
+use chrono::{DateTime, TimeDelta, Utc};
+use sea_orm::{
+    ActiveValue::{NotSet, Set, Unchanged},
+    ColumnTrait, ConnectionTrait, DatabaseBackend, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, TransactionTrait,
+    sea_query::{Expr, LockBehavior, LockType},
+};
+use sea_orm_migration::{MigrationName, MigrationTrait, SchemaManager};
+use serde::Serialize;
+
+use crate::{Broker, Envelope, Infra};
+
+pub use entity::OutboxStatus;
+
+/// `outbox_events` table
+pub mod entity {
+    use sea_orm::entity::prelude::*;
+
+    /// One event waiting for (or done with) the relay
+    #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+    #[sea_orm(table_name = "outbox_events")]
+    pub struct Model {
+        #[sea_orm(primary_key)]
+        pub id: i64,
+        pub subject: String,
+        pub payload: Vec<u8>,
+        pub status: OutboxStatus,
+        /// Failed publishes so far
+        pub attempts: i32,
+        pub last_error: Option<String>,
+        pub created_at: ChronoDateTimeUtc,
+        /// Not relayed before this time (retry backoff)
+        pub next_attempt_at: ChronoDateTimeUtc,
+        pub published_at: Option<ChronoDateTimeUtc>,
+    }
+
+    #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+    pub enum Relation {}
+
+    impl ActiveModelBehavior for ActiveModel {}
+
+    /// Where an event is in its life
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter, DeriveActiveEnum)]
+    #[sea_orm(rs_type = "String", db_type = "Text")]
+    pub enum OutboxStatus {
+        #[sea_orm(string_value = "pending")]
+        Pending,
+        #[sea_orm(string_value = "published")]
+        Published,
+        /// Gave up after `OUTBOX_MAX_ATTEMPTS` failed publishes
+        #[sea_orm(string_value = "dead")]
+        Dead,
+    }
+}
+
+use entity::{ActiveModel, Column, Entity};
+
+/// Migration creating `outbox_events`, to include in the application's
+/// migrator (Postgres and SQLite)
+pub struct CreateOutboxEvents;
+
+impl MigrationName for CreateOutboxEvents {
+    fn name(&self) -> &'static str {
+        "m20250101_000001_create_outbox_events"
+    }
+}
+
+#[async_trait]
+impl MigrationTrait for CreateOutboxEvents {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), sea_orm::DbErr> {
+        let db = manager.get_connection();
+        let table = match db.get_database_backend() {
+            DatabaseBackend::Postgres => {
+                "CREATE TABLE IF NOT EXISTS outbox_events (
+                    id BIGSERIAL PRIMARY KEY,
+                    subject TEXT NOT NULL,
+                    payload BYTEA NOT NULL,
+                    status TEXT NOT NULL DEFAULT 'pending',
+                    attempts INTEGER NOT NULL DEFAULT 0,
+                    last_error TEXT,
+                    created_at TIMESTAMPTZ NOT NULL,
+                    next_attempt_at TIMESTAMPTZ NOT NULL,
+                    published_at TIMESTAMPTZ
+                )"
+            }
+            DatabaseBackend::Sqlite => {
+                "CREATE TABLE IF NOT EXISTS outbox_events (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    subject TEXT NOT NULL,
+                    payload BLOB NOT NULL,
+                    status TEXT NOT NULL DEFAULT 'pending',
+                    attempts INTEGER NOT NULL DEFAULT 0,
+                    last_error TEXT,
+                    created_at TEXT NOT NULL,
+                    next_attempt_at TEXT NOT NULL,
+                    published_at TEXT
+                )"
+            }
+            DatabaseBackend::MySql => {
+                return Err(sea_orm::DbErr::Migration(
+                    "outbox_events supports Postgres and SQLite".to_string(),
+                ));
+            }
+        };
+        db.execute_unprepared(table).await?;
+        db.execute_unprepared(
+            "CREATE INDEX IF NOT EXISTS outbox_events_due ON outbox_events (status, next_attempt_at)",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), sea_orm::DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DROP TABLE IF EXISTS outbox_events")
+            .await
+            .map(|_| ())
+    }
+}
+
+/// Writing to the outbox
+pub struct Outbox;
+
+impl Outbox {
+    /// Add an event for `subject`, returning its id
+    ///
+    /// Pass the transaction of the business change so both commit or roll
+    /// back together.
+    ///
+    /// # Errors
+    /// Returns error if the row cannot be inserted.
+    pub async fn enqueue<C: ConnectionTrait>(
+        db: &C,
+        subject: &str,
+        payload: Vec<u8>,
+    ) -> anyhow::Result<i64> {
+        let now = Utc::now();
+        let event = ActiveModel {
+            id: NotSet,
+            subject: Set(subject.to_string()),
+            payload: Set(payload),
+            status: Set(OutboxStatus::Pending),
+            attempts: Set(0),
+            last_error: Set(None),
+            created_at: Set(now),
+            next_attempt_at: Set(now),
+            published_at: Set(None),
+        };
+        let inserted = Entity::insert(event)
+            .exec(db)
+            .await
+            .with_context(|| format!("Failed to add outbox event for {subject}"))?;
+        Ok(inserted.last_insert_id)
+    }
+
+    /// Add `value` in an [`Envelope`], as `publish_json` would send it
+    ///
+    /// The envelope takes the request id (and trace id) of the request
+    /// enqueuing it, not of the relay.
+    ///
+    /// # Errors
+    /// Returns error if `value` cannot be serialized or the row cannot be
+    /// inserted.
+    pub async fn enqueue_json<C, T>(db: &C, subject: &str, value: &T) -> anyhow::Result<i64>
+    where
+        C: ConnectionTrait,
+        T: Serialize + Sync,
+    {
+        let payload = serde_json::to_vec(&Envelope::new(value))
+            .with_context(|| format!("Failed to encode outbox event for {subject}"))?;
+        Self::enqueue(db, subject, payload).await
+    }
+
+    /// Put a dead-lettered event back in line, with its attempts reset
+    ///
+    /// Returns `false` if no dead event has this id.
+    ///
+    /// # Errors
+    /// Returns error if the row cannot be updated.
+    pub async fn requeue<C: ConnectionTrait>(db: &C, id: i64) -> anyhow::Result<bool> {
+        let result = Entity::update_many()
+            .col_expr(Column::Status, Expr::value(OutboxStatus::Pending))
+            .col_expr(Column::Attempts, Expr::value(0))
+            .col_expr(Column::NextAttemptAt, Expr::value(Utc::now()))
+            .filter(Column::Id.eq(id))
+            .filter(Column::Status.eq(OutboxStatus::Dead))
+            .exec(db)
+            .await
+            .with_context(|| format!("Failed to requeue outbox event {id}"))?;
+        Ok(result.rows_affected > 0)
+    }
+}
+
+/// Relay timing and limits, from [`OutboxConfig`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelaySettings {
+    pub poll_interval: Duration,
+    pub batch_size: u64,
+    pub max_attempts: u32,
+    pub retry_base: Duration,
+    pub retry_max: Duration,
+}
+
+impl RelaySettings {
+    #[must_use]
+    pub fn from_config(config: &OutboxConfig) -> Self {
+        Self {
+            poll_interval: Duration::from_millis(config.outbox_poll_interval_ms),
+            batch_size: config.outbox_batch_size,
+            max_attempts: config.outbox_max_attempts,
+            retry_base: Duration::from_millis(config.outbox_retry_base_ms),
+            retry_max: Duration::from_millis(config.outbox_retry_max_ms),
+        }
+    }
+
+    /// Wait after the `attempts`-th failed publish
+    #[must_use]
+    pub fn retry_delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.retry_base.saturating_mul(factor).min(self.retry_max)
+    }
+}
+
+impl Default for RelaySettings {
+    fn default() -> Self {
+        Self::from_config(&OutboxConfig::default())
+    }
+}
+
+/// Outcome of one relay pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RelayReport {
+    /// Due events read, at most the batch size
+    pub fetched: usize,
+    pub published: usize,
+    /// Failed and scheduled for another attempt
+    pub retried: usize,
+    /// Failed for the last time
+    pub dead_lettered: usize,
+    /// Longest time between enqueue and publish in this pass
+    pub max_lag: Option<Duration>,
+}
+
+/// Publishes pending outbox events through the broker
+///
+/// Cheap to clone; clones share the connection pool and broker.
+#[derive(Clone)]
+pub struct OutboxRelay {
+    db: DatabaseConnection,
+    broker: Arc<dyn Broker>,
+    settings: RelaySettings,
+    #[cfg(feature = "otel")]
+    instruments: Option<Arc<RelayInstruments>>,
+}
+
+impl OutboxRelay {
+    #[must_use]
+    pub fn new(db: DatabaseConnection, broker: Arc<dyn Broker>, settings: RelaySettings) -> Self {
+        Self {
+            db,
+            broker,
+            settings,
+            #[cfg(feature = "otel")]
+            instruments: None,
+        }
+    }
+
+    /// Also record the `outbox.events` counter (by `outbox.result`) and the
+    /// `outbox.relay.lag` histogram (seconds from enqueue to publish)
+    #[cfg(feature = "otel")]
+    #[must_use]
+    pub fn with_meter(mut self, meter: &opentelemetry::metrics::Meter) -> Self {
+        self.instruments = Some(Arc::new(RelayInstruments::new(meter)));
+        self
+    }
+
+    /// Publish the due events of one batch
+    ///
+    /// # Errors
+    /// Returns error if the outbox cannot be read or updated. Failed
+    /// publishes are not errors; they are counted in the report.
+    pub async fn relay_batch(&self) -> anyhow::Result<RelayReport> {
+        let txn = self
+            .db
+            .begin()
+            .await
+            .context("Failed to open the outbox transaction")?;
+        // Rows stay locked until commit; other relays skip them
+        let events = Entity::find()
+            .filter(Column::Status.eq(OutboxStatus::Pending))
+            .filter(Column::NextAttemptAt.lte(Utc::now()))
+            .order_by_asc(Column::Id)
+            .limit(self.settings.batch_size)
+            .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+            .all(&txn)
+            .await
+            .context("Failed to read the outbox")?;
+
+        let mut report = RelayReport {
+            fetched: events.len(),
+            ..RelayReport::default()
+        };
+        for event in events {
+            let update = match self.broker.publish(&event.subject, event.payload).await {
+                Ok(()) => {
+                    let now = Utc::now();
+                    let lag = (now - event.created_at).to_std().unwrap_or_default();
+                    report.published += 1;
+                    report.max_lag = report.max_lag.max(Some(lag));
+                    self.record("published", Some(lag));
+                    ActiveModel {
+                        id: Unchanged(event.id),
+                        status: Set(OutboxStatus::Published),
+                        published_at: Set(Some(now)),
+                        last_error: Set(None),
+                        ..ActiveModel::default()
+                    }
+                }
+                Err(e) => self.failed(event.id, &event.subject, event.attempts, &e, &mut report),
+            };
+            Entity::update(update)
+                .exec(&txn)
+                .await
+                .with_context(|| format!("Failed to update outbox event {}", event.id))?;
+        }
+        txn.commit()
+            .await
+            .context("Failed to commit the outbox transaction")?;
+
+        if report.fetched > 0 {
+            tracing::debug!(
+                published = report.published,
+                retried = report.retried,
+                dead_lettered = report.dead_lettered,
+                lag_ms = report.max_lag.map(millis),
+                "Outbox relay pass"
+            );
+        }
+        Ok(report)
+    }
+
+    /// Age of the oldest pending event, `None` when the outbox is drained
+    ///
+    /// # Errors
+    /// Returns error if the outbox cannot be read.
+    pub async fn lag(&self) -> anyhow::Result<Option<Duration>> {
+        let oldest = Entity::find()
+            .filter(Column::Status.eq(OutboxStatus::Pending))
+            .order_by_asc(Column::Id)
+            .one(&self.db)
+            .await
+            .context("Failed to read the outbox")?;
+        Ok(oldest.map(|event| (Utc::now() - event.created_at).to_std().unwrap_or_default()))
+    }
+
+    /// Relay until `shutdown` is cancelled
+    ///
+    /// Polls every `OUTBOX_POLL_INTERVAL_MS`, and right away again after a
+    /// full batch. Database errors are logged and retried on the next poll.
+    pub async fn run(&self, shutdown: CancellationToken) {
+        tracing::info!(
+            batch_size = self.settings.batch_size,
+            poll_interval_ms = millis(self.settings.poll_interval),
+            "Outbox relay started"
+        );
+        loop {
+            let full = match self.relay_batch().await {
+                Ok(report) => report.fetched as u64 >= self.settings.batch_size,
+                Err(e) => {
+                    tracing::warn!(error = format!("{e:#}"), "Outbox relay pass failed");
+                    false
+                }
+            };
+            if full && !shutdown.is_cancelled() {
+                continue;
+            }
+            tokio::select! {
+                () = shutdown.cancelled() => break,
+                () = tokio::time::sleep(self.settings.poll_interval) => {}
+            }
+        }
+        tracing::info!("Outbox relay stopped");
+    }
+
+    /// Schedule a retry of a failed event, or dead-letter it
+    fn failed(
+        &self,
+        id: i64,
+        subject: &str,
+        attempts: i32,
+        error: &anyhow::Error,
+        report: &mut RelayReport,
+    ) -> ActiveModel {
+        let attempts = u32::try_from(attempts).unwrap_or(0).saturating_add(1);
+        let mut update = ActiveModel {
+            id: Unchanged(id),
+            attempts: Set(i32::try_from(attempts).unwrap_or(i32::MAX)),
+            last_error: Set(Some(format!("{error:#}"))),
+            ..ActiveModel::default()
+        };
+        if attempts >= self.settings.max_attempts {
+            tracing::error!(
+                event_id = id,
+                subject,
+                attempts,
+                error = format!("{error:#}"),
+                "Outbox event dead-lettered"
+            );
+            report.dead_lettered += 1;
+            self.record("dead", None);
+            update.status = Set(OutboxStatus::Dead);
+        } else {
+            let delay = self.settings.retry_delay(attempts);
+            tracing::warn!(
+                event_id = id,
+                subject,
+                attempts,
+                retry_in_ms = millis(delay),
+                error = format!("{error:#}"),
+                "Failed to publish outbox event"
+            );
+            report.retried += 1;
+            self.record("retry", None);
+            update.next_attempt_at = Set(retry_at(delay));
+        }
+        update
+    }
+
+    #[cfg(feature = "otel")]
+    fn record(&self, result: &'static str, lag: Option<Duration>) {
+        if let Some(instruments) = &self.instruments {
+            instruments.record(result, lag);
+        }
+    }
+
+    #[cfg(not(feature = "otel"))]
+    #[allow(clippy::unused_self)]
+    fn record(&self, _result: &'static str, _lag: Option<Duration>) {}
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+fn retry_at(delay: Duration) -> DateTime<Utc> {
+    let now = Utc::now();
+    TimeDelta::from_std(delay)
+        .ok()
+        .and_then(|delay| now.checked_add_signed(delay))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+#[cfg(feature = "otel")]
+struct RelayInstruments {
+    events: opentelemetry::metrics::Counter<u64>,
+    lag: opentelemetry::metrics::Histogram<f64>,
+}
+
+#[cfg(feature = "otel")]
+impl RelayInstruments {
+    fn new(meter: &opentelemetry::metrics::Meter) -> Self {
+        Self {
+            events: meter
+                .u64_counter("outbox.events")
+                .with_unit("{event}")
+                .with_description("Outbox events relayed by outcome")
+                .build(),
+            lag: meter
+                .f64_histogram("outbox.relay.lag")
+                .with_unit("s")
+                .with_description("Time from enqueue to publish of outbox events")
+                .build(),
+        }
+    }
+
+    fn record(&self, result: &'static str, lag: Option<Duration>) {
+        self.events
+            .add(1, &[opentelemetry::KeyValue::new("outbox.result", result)]);
+        if let Some(lag) = lag {
+            self.lag.record(lag.as_secs_f64(), &[]);
+        }
+    }
+}
+
+impl Infra {
+    /// Relay over the initialized database and broker, with the
+    /// `OUTBOX_*` settings
+    ///
+    /// Returns `None` when the database or the broker is disabled.
+    #[must_use]
+    pub fn outbox_relay(&self, config: &Config) -> Option<OutboxRelay> {
+        let db = self.db.clone()?;
+        let broker = self.broker.clone()?;
+        #[allow(unused_mut)]
+        let mut relay = OutboxRelay::new(db, broker, RelaySettings::from_config(&config.outbox));
+        #[cfg(feature = "otel")]
+        if config.features.feature_otel_metrics {
+            relay = relay.with_meter(&opentelemetry::global::meter("barrzen-axum"));
+        }
+        Some(relay)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "test-util")]
+mod tests {
+    use futures_util::StreamExt;
+    use sea_orm::Database;
+
+    use super::*;
+    use crate::{BrokerMessage, MessageStream, MockBroker};
+
+    async fn db() -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        CreateOutboxEvents
+            .up(&SchemaManager::new(&db))
+            .await
+            .unwrap();
+        db
+    }
+
+    async fn event(db: &DatabaseConnection, id: i64) -> entity::Model {
+        Entity::find_by_id(id).one(db).await.unwrap().unwrap()
+    }
+
+    /// Broker that is down
+    struct Unreachable;
+
+    #[async_trait]
+    impl Broker for Unreachable {
+        async fn ping(&self) -> anyhow::Result<()> {
+            anyhow::bail!("connection refused")
+        }
+        async fn publish(&self, _subject: &str, _payload: Vec<u8>) -> anyhow::Result<()> {
+            anyhow::bail!("connection refused")
+        }
+        async fn subscribe(&self, _subject: &str) -> anyhow::Result<MessageStream> {
+            anyhow::bail!("connection refused")
+        }
+        async fn request(
+            &self,
+            _subject: &str,
+            _payload: Vec<u8>,
+            _timeout: Duration,
+        ) -> anyhow::Result<BrokerMessage> {
+            anyhow::bail!("connection refused")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_enqueue_publish_ack() {
+        let db = db().await;
+        let broker = Arc::new(MockBroker::new());
+        let mut messages = broker.subscribe("orders.*").await.unwrap();
+        let relay = OutboxRelay::new(db.clone(), broker.clone(), RelaySettings::default());
+
+        let txn = db.begin().await.unwrap();
+        let id = Outbox::enqueue_json(&txn, "orders.created", &serde_json::json!({ "id": 7 }))
+            .await
+            .unwrap();
+        txn.commit().await.unwrap();
+        // Rolled back with its transaction, never published
+        let txn = db.begin().await.unwrap();
+        Outbox::enqueue(&txn, "orders.cancelled", b"{}".to_vec())
+            .await
+            .unwrap();
+        txn.rollback().await.unwrap();
+
+        assert!(relay.lag().await.unwrap().is_some());
+        let report = relay.relay_batch().await.unwrap();
+        assert_eq!((report.fetched, report.published), (1, 1));
+        assert!(report.max_lag.is_some());
+
+        let message = messages.next().await.unwrap();
+        assert_eq!(message.subject, "orders.created");
+        let envelope = crate::broker_json::decode::<serde_json::Value>(message).unwrap();
+        assert_eq!(envelope.data, serde_json::json!({ "id": 7 }));
+
+        let stored = event(&db, id).await;
+        assert_eq!(stored.status, OutboxStatus::Published);
+        assert!(stored.published_at.is_some());
+        assert_eq!(relay.relay_batch().await.unwrap(), RelayReport::default());
+        assert_eq!(relay.lag().await.unwrap(), None);
+        assert_eq!(broker.published().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_after_max_attempts() {
+        let db = db().await;
+        let settings = RelaySettings {
+            max_attempts: 2,
+            retry_base: Duration::ZERO,
+            ..RelaySettings::default()
+        };
+        let relay = OutboxRelay::new(db.clone(), Arc::new(Unreachable), settings);
+        let id = Outbox::enqueue(&db, "orders.created", b"{}".to_vec())
+            .await
+            .unwrap();
+
+        let report = relay.relay_batch().await.unwrap();
+        assert_eq!((report.retried, report.dead_lettered), (1, 0));
+        let stored = event(&db, id).await;
+        assert_eq!((stored.status, stored.attempts), (OutboxStatus::Pending, 1));
+        assert!(stored.last_error.unwrap().contains("connection refused"));
+
+        let report = relay.relay_batch().await.unwrap();
+        assert_eq!((report.retried, report.dead_lettered), (0, 1));
+        assert_eq!(event(&db, id).await.status, OutboxStatus::Dead);
+        assert_eq!(relay.relay_batch().await.unwrap().fetched, 0);
+
+        // Requeued once the broker is back
+        assert!(Outbox::requeue(&db, id).await.unwrap());
+        assert!(!Outbox::requeue(&db, id).await.unwrap());
+        let relay = OutboxRelay::new(db.clone(), Arc::new(MockBroker::new()), settings);
+        assert_eq!(relay.relay_batch().await.unwrap().published, 1);
+        assert_eq!(event(&db, id).await.status, OutboxStatus::Published);
+    }
+
+    #[tokio::test]
+    async fn test_retry_waits_for_backoff() {
+        let db = db().await;
+        let relay = OutboxRelay::new(db.clone(), Arc::new(Unreachable), RelaySettings::default());
+        let id = Outbox::enqueue(&db, "orders.created", b"{}".to_vec())
+            .await
+            .unwrap();
+
+        assert_eq!(relay.relay_batch().await.unwrap().retried, 1);
+        // Next attempt is OUTBOX_RETRY_BASE_MS away
+        assert_eq!(relay.relay_batch().await.unwrap().fetched, 0);
+        assert!(event(&db, id).await.next_attempt_at > Utc::now());
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let settings = RelaySettings {
+            retry_base: Duration::from_secs(1),
+            retry_max: Duration::from_secs(10),
+            ..RelaySettings::default()
+        };
+        let delays: Vec<u64> = (1..=6)
+            .map(|attempts| settings.retry_delay(attempts).as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 8, 10, 10]);
+        assert_eq!(settings.retry_delay(u32::MAX), Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,539 @@
+//! Server-side cache of `GET` responses backed by the infra cache
+//!
+//! [`ResponseCacheLayer`] stores the `200` responses of the routes it wraps
+//! for a TTL chosen per layer, keyed by request path, query string (pairs
+//! sorted) and the request headers listed in `RESPONSE_CACHE_VARY_HEADERS`.
+//! Responses get `x-cache: HIT` or `MISS`. A request with
+//! `Cache-Control: no-cache` skips the lookup and refreshes the entry.
+//! Responses setting cookies, other statuses and bodies over
+//! `RESPONSE_CACHE_MAX_BODY_BYTES` are never stored.
+//!
+//! ```ignore
+//! let catalog = Router::new()
+//!     .route("/catalog/{category}", get(list_products))
+//!     .layer(infra.response_cache_layer(&config, Duration::from_secs(5)).context("no cache")?);
+//! let app = AppBuilder::new(config, build_info).merge(catalog).build();
+//!
+//! // After an import
+//! response_cache::purge("/catalog").await?;
+//! ```
+//!
+//! [`purge`] works through the cache: each path segment of a route template
+//! has a generation stored in the cache, which is part of the key and
+//! replaced by a purge. Replicas sharing a Redis cache see it right away;
+//! with `CACHE_BACKEND=tiered` the others keep their in-process copy of the
+//! old generation for up to `CACHE_L1_TTL_SECONDS`.
+
+use std::{
+    fmt::{self, Write as _},
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError, Weak},
+    task::{Context, Poll},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request},
+    http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use barrzen_axum_core::{Config, response::ApiError};
+use sha2::{Digest, Sha256};
+use tower::{Layer, Service};
+
+use crate::{Cache, stored_response::StoredResponse};
+
+/// Response header telling whether the response came from the cache
+pub const X_CACHE_HEADER: &str = "x-cache";
+
+/// Key prefix for cached responses
+const KEY_PREFIX: &str = "response_cache:";
+
+/// Key prefix for the generations of route prefixes
+const GENERATION_PREFIX: &str = "response_cache:gen:";
+
+/// Generation of a route prefix that was never purged
+const INITIAL_GENERATION: &[u8] = b"0";
+
+/// Layers of the process, whose caches [`purge`] writes to
+static LAYERS: Mutex<Vec<Weak<Shared>>> = Mutex::new(Vec::new());
+
+/// Layer caching the `GET` responses of the routes it wraps
+///
+/// Add it with `Router::layer` on the routes to cache, then merge the
+/// router into the app.
+#[derive(Clone)]
+pub struct ResponseCacheLayer {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    cache: Arc<dyn Cache + Send + Sync>,
+    ttl: Duration,
+    vary_headers: Vec<HeaderName>,
+    max_body_bytes: usize,
+}
+
+impl fmt::Debug for ResponseCacheLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseCacheLayer")
+            .field("ttl", &self.shared.ttl)
+            .field("vary_headers", &self.shared.vary_headers)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ResponseCacheLayer {
+    /// Layer storing responses for `ttl`, with the `RESPONSE_CACHE_*`
+    /// settings
+    #[must_use]
+    pub fn new(cache: Arc<dyn Cache + Send + Sync>, config: &Config, ttl: Duration) -> Self {
+        let response_cache = &config.response_cache;
+        let shared = Arc::new(Shared {
+            cache,
+            ttl,
+            vary_headers: response_cache
+                .vary_headers()
+                .iter()
+                .filter_map(|name| HeaderName::from_bytes(name.as_bytes()).ok())
+                .collect(),
+            max_body_bytes: response_cache.response_cache_max_body_bytes,
+        });
+        let mut layers = LAYERS.lock().unwrap_or_else(PoisonError::into_inner);
+        layers.retain(|layer| layer.strong_count() > 0);
+        layers.push(Arc::downgrade(&shared));
+        drop(layers);
+        Self { shared }
+    }
+}
+
+#[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+impl crate::Infra {
+    /// Response cache layer backed by the initialized cache, storing
+    /// responses for `ttl`
+    ///
+    /// Returns `None` when the cache is disabled.
+    #[must_use]
+    pub fn response_cache_layer(
+        &self,
+        config: &Config,
+        ttl: Duration,
+    ) -> Option<ResponseCacheLayer> {
+        self.cache
+            .clone()
+            .map(|cache| ResponseCacheLayer::new(cache, config, ttl))
+    }
+}
+
+/// Drop the cached responses of the routes under `prefix`
+///
+/// `prefix` is matched against route templates segment by segment:
+/// `/catalog` covers `/catalog` and `/catalog/{category}` but not
+/// `/catalogue`, and `/` covers every route. Applies to the layers of this
+/// process and, through a shared cache, of the other replicas (after
+/// `CACHE_L1_TTL_SECONDS` at the latest with `CACHE_BACKEND=tiered`).
+///
+/// # Errors
+/// Returns error if a cache cannot be written; its entries are then served
+/// until their TTL ends.
+pub async fn purge(prefix: &str) -> anyhow::Result<()> {
+    let layers: Vec<_> = LAYERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    // Entries stored under an older generation are gone once it expires
+    let Some(ttl) = layers.iter().map(|layer| layer.ttl).max() else {
+        return Ok(());
+    };
+    let key = generation_key(&normalize_prefix(prefix));
+    let generation = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string();
+
+    let mut purged: Vec<&Arc<dyn Cache + Send + Sync>> = Vec::new();
+    for layer in &layers {
+        if purged.iter().any(|cache| Arc::ptr_eq(cache, &layer.cache)) {
+            continue;
+        }
+        layer
+            .cache
+            .set(&key, generation.clone().into_bytes(), Some(ttl))
+            .await?;
+        purged.push(&layer.cache);
+    }
+    Ok(())
+}
+
+impl Shared {
+    /// Hash of the path, query and vary headers, and the route template
+    /// whose generations complete the key
+    fn request_digest(&self, req: &Request) -> (Sha256, String) {
+        let route = req
+            .extensions()
+            .get::<MatchedPath>()
+            .map_or_else(|| req.uri().path(), MatchedPath::as_str)
+            .to_string();
+        let mut hasher = Sha256::new();
+        update(&mut hasher, req.uri().path().as_bytes());
+        update(&mut hasher, normalize_query(req.uri().query()).as_bytes());
+        for name in &self.vary_headers {
+            let values: Vec<_> = req
+                .headers()
+                .get_all(name)
+                .iter()
+                .map(HeaderValue::as_bytes)
+                .collect();
+            update(&mut hasher, &values.join(&b","[..]));
+        }
+        (hasher, route)
+    }
+
+    /// Cache key of the request, under the current generations of its route
+    async fn cache_key(&self, mut hasher: Sha256, route: &str) -> anyhow::Result<String> {
+        for prefix in route_prefixes(route) {
+            let generation = self.cache.get(&generation_key(&prefix)).await?;
+            update(
+                &mut hasher,
+                generation.as_deref().unwrap_or(INITIAL_GENERATION),
+            );
+        }
+        Ok(hasher
+            .finalize()
+            .iter()
+            .fold(String::from(KEY_PREFIX), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            }))
+    }
+
+    fn is_cacheable(&self, response: &Response) -> bool {
+        let headers = response.headers();
+        let too_large = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|length| length.to_str().ok()?.parse::<usize>().ok())
+            .is_some_and(|length| length > self.max_body_bytes);
+        // Streams never end, so they cannot be buffered
+        let streamed = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+        response.status() == StatusCode::OK
+            && !headers.contains_key(header::SET_COOKIE)
+            && !too_large
+            && !streamed
+    }
+}
+
+fn update(hasher: &mut Sha256, part: &[u8]) {
+    hasher.update(part);
+    hasher.update([0]);
+}
+
+impl<S> Layer<S> for ResponseCacheLayer {
+    type Service = ResponseCacheService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ResponseCacheService {
+            inner,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ResponseCacheService<S> {
+    inner: S,
+    shared: Arc<Shared>,
+}
+
+impl<S> Service<Request> for ResponseCacheService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let mut inner = self.inner.clone();
+        if req.method() != Method::GET {
+            return Box::pin(async move { inner.call(req).await });
+        }
+        Box::pin(handle(self.shared.clone(), inner, req))
+    }
+}
+
+async fn handle<S>(shared: Arc<Shared>, mut inner: S, req: Request) -> Result<Response, S::Error>
+where
+    S: Service<Request, Response = Response>,
+{
+    let bypass = no_cache(req.headers());
+    let (hasher, route) = shared.request_digest(&req);
+    let key = match shared.cache_key(hasher, &route).await {
+        Ok(key) => key,
+        Err(e) => {
+            // Fail open: the request runs uncached
+            tracing::warn!(route, error = %e, "Response cache unavailable");
+            return Ok(with_x_cache(inner.call(req).await?, "MISS"));
+        }
+    };
+    if !bypass {
+        match shared.cache.get(&key).await {
+            Ok(Some(stored)) => {
+                if let Some(stored) = StoredResponse::decode(&stored) {
+                    return Ok(with_x_cache(stored.into_response(), "HIT"));
+                }
+                tracing::warn!(route, "Discarding unreadable cached response");
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::warn!(route, error = %e, "Response cache unavailable");
+                return Ok(with_x_cache(inner.call(req).await?, "MISS"));
+            }
+        }
+    }
+
+    let response = inner.call(req).await?;
+    Ok(with_x_cache(store(&shared, &key, response).await, "MISS"))
+}
+
+/// Store `response` if it may be served again
+async fn store(shared: &Shared, key: &str, response: Response) -> Response {
+    if !shared.is_cacheable(&response) {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to read response body");
+            return ApiError::internal("Failed to read response body").into_response();
+        }
+    };
+    if body.len() <= shared.max_body_bytes {
+        let stored = StoredResponse {
+            status: parts.status,
+            headers: parts.headers.clone(),
+            body: body.clone(),
+        };
+        if let Err(e) = shared
+            .cache
+            .set(key, stored.encode(), Some(shared.ttl))
+            .await
+        {
+            tracing::warn!(error = %e, "Failed to store cached response");
+        }
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn with_x_cache(mut response: Response, value: &'static str) -> Response {
+    response.headers_mut().insert(
+        HeaderName::from_static(X_CACHE_HEADER),
+        HeaderValue::from_static(value),
+    );
+    response
+}
+
+/// Whether the request carries `Cache-Control: no-cache`
+fn no_cache(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::CACHE_CONTROL)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|directive| directive.trim().eq_ignore_ascii_case("no-cache"))
+}
+
+/// Query pairs in sorted order, so `?b=2&a=1` and `?a=1&b=2` share an entry
+fn normalize_query(query: Option<&str>) -> String {
+    let mut pairs: Vec<_> = query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .collect();
+    pairs.sort_unstable();
+    pairs.join("&")
+}
+
+/// `/catalog/` and `catalog` are both `/catalog`
+fn normalize_prefix(prefix: &str) -> String {
+    let segments: Vec<_> = prefix.split('/').filter(|s| !s.is_empty()).collect();
+    format!("/{}", segments.join("/"))
+}
+
+/// `/`, then each segment prefix of `route`: `/catalog`, `/catalog/{category}`
+fn route_prefixes(route: &str) -> Vec<String> {
+    let mut prefixes = vec!["/".to_string()];
+    let mut prefix = String::new();
+    for segment in route.split('/').filter(|s| !s.is_empty()) {
+        prefix.push('/');
+        prefix.push_str(segment);
+        prefixes.push(prefix.clone());
+    }
+    prefixes
+}
+
+fn generation_key(prefix: &str) -> String {
+    format!("{GENERATION_PREFIX}{prefix}")
+}
+
+#[cfg(test)]
+#[cfg(feature = "cache-moka")]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{Router, extract::Path, routing::get};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::MokaCache;
+
+    /// Router of `/{section}/{item}` counting handler calls
+    fn app(section: &str, calls: Arc<AtomicUsize>) -> Router {
+        let cache = Arc::new(MokaCache::new(1_000, Duration::from_mins(1)));
+        let layer = ResponseCacheLayer::new(cache, &Config::default(), Duration::from_mins(1));
+        let handler = move |Path(item): Path<String>| {
+            let calls = calls.clone();
+            async move {
+                let call = calls.fetch_add(1, Ordering::SeqCst) + 1;
+                match item.as_str() {
+                    "missing" => (StatusCode::NOT_FOUND, "missing").into_response(),
+                    "session" => ([(header::SET_COOKIE, "sid=1")], "hi").into_response(),
+                    _ => format!("{item} #{call}").into_response(),
+                }
+            }
+        };
+        Router::new()
+            .route(&format!("/{section}/{{item}}"), get(handler))
+            .layer(layer)
+    }
+
+    async fn send(app: &Router, uri: &str, headers: &[(&str, &str)]) -> (String, String) {
+        let mut builder = Request::get(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let response = app
+            .clone()
+            .oneshot(builder.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let x_cache = response.headers()[X_CACHE_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (x_cache, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_second_call_hits() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app("catalog", calls.clone());
+
+        assert_eq!(
+            send(&app, "/catalog/books?b=2&a=1", &[]).await,
+            ("MISS".into(), "books #1".into())
+        );
+        assert_eq!(
+            send(&app, "/catalog/books?a=1&b=2", &[]).await,
+            ("HIT".into(), "books #1".into())
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Other queries and vary header values get their own entry
+        assert_eq!(send(&app, "/catalog/books?a=2", &[]).await.0, "MISS");
+        let german = [("accept-language", "de")];
+        assert_eq!(
+            send(&app, "/catalog/books?a=1&b=2", &german).await.0,
+            "MISS"
+        );
+        assert_eq!(send(&app, "/catalog/books?a=1&b=2", &german).await.0, "HIT");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_path_params_get_their_own_entry() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app("shelves", calls.clone());
+
+        assert_eq!(
+            send(&app, "/shelves/books", &[]).await,
+            ("MISS".into(), "books #1".into())
+        );
+        assert_eq!(
+            send(&app, "/shelves/music", &[]).await,
+            ("MISS".into(), "music #2".into())
+        );
+        assert_eq!(
+            send(&app, "/shelves/books", &[]).await,
+            ("HIT".into(), "books #1".into())
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_no_cache_bypasses_and_refreshes() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app("offers", calls.clone());
+        send(&app, "/offers/today", &[]).await;
+
+        let no_cache = [("cache-control", "no-cache")];
+        let (x_cache, body) = send(&app, "/offers/today", &no_cache).await;
+        assert_eq!((x_cache.as_str(), body.as_str()), ("MISS", "today #2"));
+        assert_eq!(
+            send(&app, "/offers/today", &[]).await,
+            ("HIT".into(), "today #2".into())
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_errors_and_cookies_are_not_stored() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let app = app("profiles", calls.clone());
+        for uri in ["/profiles/missing", "/profiles/session"] {
+            assert_eq!(send(&app, uri, &[]).await.0, "MISS");
+            assert_eq!(send(&app, uri, &[]).await.0, "MISS");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_purge_by_route_prefix() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let products = app("products", calls.clone());
+        let brands = app("brands", calls.clone());
+        send(&products, "/products/tea", &[]).await;
+        send(&brands, "/brands/acme", &[]).await;
+
+        purge("/products/").await.unwrap();
+        assert_eq!(
+            send(&products, "/products/tea", &[]).await,
+            ("MISS".into(), "tea #3".into())
+        );
+        assert_eq!(send(&brands, "/brands/acme", &[]).await.0, "HIT");
+        assert_eq!(send(&products, "/products/tea", &[]).await.0, "HIT");
+    }
+
+    #[test]
+    fn test_route_prefixes() {
+        assert_eq!(route_prefixes("/a/{b}"), ["/", "/a", "/a/{b}"]);
+        assert_eq!(normalize_prefix("a/b/"), "/a/b");
+        assert_eq!(normalize_prefix("/"), "/");
+    }
+}
@@ -0,0 +1,447 @@
+//! Meilisearch index management
+//!
+//! Describe an index once with [`SearchIndex`], then keep it in shape at
+//! startup and sync documents through the helpers, which wait for the
+//! Meilisearch task to finish:
+//!
+//! ```ignore
+//! struct Products;
+//!
+//! impl SearchIndex for Products {
+//!     type Document = Product;
+//!
+//!     fn index_name(&self) -> &str {
+//!         "products"
+//!     }
+//!     fn primary_key(&self) -> &'static str {
+//!         "id"
+//!     }
+//!     fn settings(&self) -> IndexSettings {
+//!         IndexSettings::new()
+//!             .searchable(["name", "description"])
+//!             .filterable(["category"])
+//!     }
+//! }
+//!
+//! let options = SyncOptions::from_config(&config.search);
+//! search::ensure_index(&client, &Products, &options).await?;
+//! search::upsert_documents(&client, &Products, &products, &options).await?;
+//! ```
+
+use std::{collections::BTreeSet, fmt::Display, time::Duration};
+
+use barrzen_axum_core::SearchConfig;
+use futures_util::{Stream, StreamExt};
+use meilisearch_sdk::{
+    client::Client,
+    errors::{Error, ErrorCode, MeilisearchError},
+    settings::Settings,
+    task_info::TaskInfo,
+};
+use serde::{Serialize, de::DeserializeOwned};
+
+/// An index and the documents stored in it
+pub trait SearchIndex {
+    type Document: Serialize + DeserializeOwned + Send + Sync;
+
+    fn index_name(&self) -> &str;
+
+    /// Document field holding the id
+    fn primary_key(&self) -> &str;
+
+    fn settings(&self) -> IndexSettings;
+}
+
+/// The index settings managed by [`ensure_index`]
+///
+/// Settings left out here keep the Meilisearch defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexSettings {
+    /// Attributes searched, by decreasing relevance; empty searches all
+    pub searchable_attributes: Vec<String>,
+    pub filterable_attributes: Vec<String>,
+    pub sortable_attributes: Vec<String>,
+}
+
+impl IndexSettings {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn searchable(mut self, attributes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.searchable_attributes = attributes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    #[must_use]
+    pub fn filterable(mut self, attributes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.filterable_attributes = attributes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    #[must_use]
+    pub fn sortable(mut self, attributes: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.sortable_attributes = attributes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Names of the settings that differ from `current`
+    ///
+    /// Searchable attributes are compared in order, since the order ranks
+    /// them; filterable and sortable ones as sets.
+    #[must_use]
+    pub fn diff(&self, current: &Self) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if searchable(&self.searchable_attributes) != searchable(&current.searchable_attributes) {
+            changed.push("searchableAttributes");
+        }
+        if as_set(&self.filterable_attributes) != as_set(&current.filterable_attributes) {
+            changed.push("filterableAttributes");
+        }
+        if as_set(&self.sortable_attributes) != as_set(&current.sortable_attributes) {
+            changed.push("sortableAttributes");
+        }
+        changed
+    }
+
+    /// The `changed` settings as a Meilisearch settings update
+    fn update(&self, changed: &[&str]) -> Settings {
+        let mut settings = Settings::new();
+        if changed.contains(&"searchableAttributes") {
+            settings = settings.with_searchable_attributes(searchable(&self.searchable_attributes));
+        }
+        if changed.contains(&"filterableAttributes") {
+            settings = settings.with_filterable_attributes(&self.filterable_attributes);
+        }
+        if changed.contains(&"sortableAttributes") {
+            settings = settings.with_sortable_attributes(&self.sortable_attributes);
+        }
+        settings
+    }
+}
+
+/// Searchable attributes as Meilisearch reports them, `*` for all
+fn searchable(attributes: &[String]) -> Vec<&str> {
+    if attributes.is_empty() {
+        vec!["*"]
+    } else {
+        attributes.iter().map(String::as_str).collect()
+    }
+}
+
+fn as_set(attributes: &[String]) -> BTreeSet<&str> {
+    attributes.iter().map(String::as_str).collect()
+}
+
+/// How the helpers wait and batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncOptions {
+    /// Longest wait for one Meilisearch task
+    pub task_timeout: Duration,
+    /// Documents per task in [`reindex_all`]
+    pub batch_size: usize,
+}
+
+impl SyncOptions {
+    /// Options from `MEILI_TASK_TIMEOUT_SECONDS` and `MEILI_BATCH_SIZE`
+    #[must_use]
+    pub fn from_config(config: &SearchConfig) -> Self {
+        Self {
+            task_timeout: Duration::from_secs(config.meili_task_timeout_seconds),
+            batch_size: config.meili_batch_size.max(1),
+        }
+    }
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self::from_config(&SearchConfig::default())
+    }
+}
+
+/// Error of the index helpers
+#[derive(Debug, thiserror::Error)]
+pub enum SearchError {
+    /// Meilisearch ran the task and rejected it, e.g. invalid documents
+    #[error("Meilisearch task {task_uid} failed: {error}")]
+    Task {
+        task_uid: u32,
+        #[source]
+        error: MeilisearchError,
+    },
+    /// The task was still enqueued or processing after the timeout
+    #[error("Meilisearch task {task_uid} did not finish within {timeout:?}")]
+    Timeout { task_uid: u32, timeout: Duration },
+    /// The request did not get an answer, or Meilisearch refused it
+    #[error("Meilisearch request failed: {0}")]
+    Transport(#[from] Error),
+}
+
+/// Wait for `task` and turn a failure into [`SearchError::Task`]
+async fn wait(client: &Client, task: TaskInfo, timeout: Duration) -> Result<(), SearchError> {
+    let task_uid = task.task_uid;
+    let task = match task.wait_for_completion(client, None, Some(timeout)).await {
+        Ok(task) => task,
+        Err(Error::Timeout) => return Err(SearchError::Timeout { task_uid, timeout }),
+        Err(e) => return Err(e.into()),
+    };
+    if task.is_failure() {
+        return Err(SearchError::Task {
+            task_uid,
+            error: task.unwrap_failure(),
+        });
+    }
+    Ok(())
+}
+
+/// Create the index if missing and apply the settings that differ
+///
+/// Returns the names of the updated settings, empty when the index was
+/// already in shape. Safe to run from every replica at startup.
+///
+/// # Errors
+/// Returns [`SearchError`] if Meilisearch is unreachable or a task fails.
+pub async fn ensure_index<I: SearchIndex>(
+    client: &Client,
+    index: &I,
+    options: &SyncOptions,
+) -> Result<Vec<&'static str>, SearchError> {
+    let name = index.index_name();
+    match client.get_index(name).await {
+        Ok(_) => {}
+        Err(Error::Meilisearch(e)) if matches!(e.error_code, ErrorCode::IndexNotFound) => {
+            tracing::info!(index = name, "Creating search index");
+            let task = client.create_index(name, Some(index.primary_key())).await?;
+            match wait(client, task, options.task_timeout).await {
+                // Another replica created it first
+                Err(SearchError::Task { error, .. })
+                    if matches!(error.error_code, ErrorCode::IndexAlreadyExists) => {}
+                result => result?,
+            }
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    let handle = client.index(name);
+    let current = IndexSettings {
+        searchable_attributes: handle.get_searchable_attributes().await?,
+        filterable_attributes: handle.get_filterable_attributes().await?,
+        sortable_attributes: handle.get_sortable_attributes().await?,
+    };
+    let desired = index.settings();
+    let changed = desired.diff(&current);
+    if !changed.is_empty() {
+        tracing::info!(index = name, settings = ?changed, "Updating search index settings");
+        let task = handle.set_settings(&desired.update(&changed)).await?;
+        wait(client, task, options.task_timeout).await?;
+    }
+    Ok(changed)
+}
+
+/// Add `documents`, replacing those with the same id
+///
+/// # Errors
+/// Returns [`SearchError`] if Meilisearch is unreachable or rejects them.
+pub async fn upsert_documents<I: SearchIndex>(
+    client: &Client,
+    index: &I,
+    documents: &[I::Document],
+    options: &SyncOptions,
+) -> Result<(), SearchError> {
+    if documents.is_empty() {
+        return Ok(());
+    }
+    let task = client
+        .index(index.index_name())
+        .add_or_replace(documents, Some(index.primary_key()))
+        .await?;
+    wait(client, task, options.task_timeout).await
+}
+
+/// Delete the documents with these ids; unknown ids are ignored
+///
+/// # Errors
+/// Returns [`SearchError`] if Meilisearch is unreachable or the task fails.
+pub async fn delete_documents<I: SearchIndex>(
+    client: &Client,
+    index: &I,
+    ids: &[impl Display],
+    options: &SyncOptions,
+) -> Result<(), SearchError> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<String> = ids.iter().map(ToString::to_string).collect();
+    let task = client
+        .index(index.index_name())
+        .delete_documents(&ids)
+        .await?;
+    wait(client, task, options.task_timeout).await
+}
+
+/// Upsert every document of `documents` in batches of
+/// [`SyncOptions::batch_size`]
+///
+/// Returns the number of documents sent. Documents missing from the stream
+/// are not removed from the index.
+///
+/// # Errors
+/// Returns [`SearchError`] on the first batch that fails; earlier batches
+/// stay indexed.
+pub async fn reindex_all<I, S>(
+    client: &Client,
+    index: &I,
+    documents: S,
+    options: &SyncOptions,
+) -> Result<usize, SearchError>
+where
+    I: SearchIndex,
+    S: Stream<Item = I::Document>,
+{
+    let mut batches = std::pin::pin!(documents.chunks(options.batch_size.max(1)));
+    let mut total = 0;
+    while let Some(batch) = batches.next().await {
+        upsert_documents(client, index, &batch, options).await?;
+        total += batch.len();
+        tracing::debug!(index = index.index_name(), total, "Reindexed batch");
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_of_equal_settings_is_empty() {
+        let desired = IndexSettings::new()
+            .searchable(["name", "description"])
+            .filterable(["category", "brand"]);
+        let current = IndexSettings::new()
+            .searchable(["name", "description"])
+            .filterable(["brand", "category"]);
+        assert!(desired.diff(&current).is_empty());
+
+        // Meilisearch reports "all attributes" as `*`
+        let current = IndexSettings::new().searchable(["*"]);
+        assert!(IndexSettings::new().diff(&current).is_empty());
+    }
+
+    #[test]
+    fn test_diff_names_changed_settings() {
+        let desired = IndexSettings::new()
+            .searchable(["name", "description"])
+            .filterable(["category"])
+            .sortable(["price"]);
+        let current = IndexSettings::new()
+            .searchable(["description", "name"])
+            .filterable(["category"]);
+        assert_eq!(
+            desired.diff(&current),
+            vec!["searchableAttributes", "sortableAttributes"]
+        );
+        assert_eq!(
+            IndexSettings::new().diff(&desired),
+            vec![
+                "searchableAttributes",
+                "filterableAttributes",
+                "sortableAttributes"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sync_options_from_config() {
+        let options = SyncOptions::default();
+        assert_eq!(options.task_timeout, Duration::from_secs(30));
+        assert_eq!(options.batch_size, 1_000);
+    }
+
+    /// Runs against a real server when `MEILI_TEST_URL` is set
+    /// (`MEILI_TEST_API_KEY` for the master key)
+    #[tokio::test]
+    async fn test_sync_against_meilisearch() {
+        #[derive(Debug, PartialEq, Serialize, serde::Deserialize)]
+        struct Product {
+            id: u32,
+            name: String,
+        }
+
+        struct Products(String);
+
+        impl SearchIndex for Products {
+            type Document = Product;
+
+            fn index_name(&self) -> &str {
+                &self.0
+            }
+            fn primary_key(&self) -> &'static str {
+                "id"
+            }
+            fn settings(&self) -> IndexSettings {
+                IndexSettings::new().searchable(["name"]).filterable(["id"])
+            }
+        }
+
+        let Ok(url) = std::env::var("MEILI_TEST_URL") else {
+            return;
+        };
+        let client = Client::new(url, std::env::var("MEILI_TEST_API_KEY").ok()).unwrap();
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let index = Products(format!("test_{nanos}"));
+        let options = SyncOptions {
+            task_timeout: Duration::from_secs(30),
+            batch_size: 2,
+        };
+
+        let changed = ensure_index(&client, &index, &options).await.unwrap();
+        assert_eq!(
+            changed,
+            vec!["searchableAttributes", "filterableAttributes"]
+        );
+        assert!(
+            ensure_index(&client, &index, &options)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let products = (1..=5).map(|id| Product {
+            id,
+            name: format!("product {id}"),
+        });
+        let sent = reindex_all(
+            &client,
+            &index,
+            futures_util::stream::iter(products),
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(sent, 5);
+        delete_documents(&client, &index, &[1, 2], &options)
+            .await
+            .unwrap();
+
+        let handle = client.index(index.index_name());
+        let stats = handle.get_stats().await.unwrap();
+        assert_eq!(stats.number_of_documents, 3);
+        let product: Product = handle.get_document("3").await.unwrap();
+        assert_eq!(product.name, "product 3");
+
+        // A document without its primary key fails the task, not the request
+        let task = handle
+            .add_documents(&[serde_json::json!({ "name": "no id" })], Some("id"))
+            .await
+            .unwrap();
+        let error = wait(&client, task, options.task_timeout).await.unwrap_err();
+        assert!(matches!(error, SearchError::Task { .. }), "{error}");
+
+        client.delete_index(index.index_name()).await.unwrap();
+    }
+}
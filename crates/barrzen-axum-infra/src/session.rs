@@ -0,0 +1,201 @@
+//! Session store backed by the infra cache
+//!
+//! Pass [`Infra::session_store`](crate::Infra::session_store) to
+//! `AppBuilder::with_session_store`. With `CACHE_BACKEND=moka` sessions live
+//! in process (dev); with `CACHE_BACKEND=redis` they are shared (prod).
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use tower_sessions::{
+    SessionStore,
+    cookie::time::OffsetDateTime,
+    session::{Id, Record},
+    session_store,
+};
+
+use crate::Cache;
+
+/// Key prefix for session records in the cache
+const KEY_PREFIX: &str = "session:";
+
+/// [`SessionStore`] over an infra [`Cache`]
+#[derive(Clone)]
+pub struct CacheSessionStore {
+    cache: Arc<dyn Cache + Send + Sync>,
+}
+
+impl CacheSessionStore {
+    #[must_use]
+    pub fn new(cache: Arc<dyn Cache + Send + Sync>) -> Self {
+        Self { cache }
+    }
+
+    fn key(id: &Id) -> String {
+        format!("{KEY_PREFIX}{id}")
+    }
+}
+
+impl fmt::Debug for CacheSessionStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheSessionStore").finish_non_exhaustive()
+    }
+}
+
+#[cfg(any(feature = "cache-moka", feature = "cache-redis"))]
+impl crate::Infra {
+    /// Session store backed by the initialized cache
+    ///
+    /// Returns `None` when the cache is disabled.
+    #[must_use]
+    pub fn session_store(&self) -> Option<CacheSessionStore> {
+        self.cache.clone().map(CacheSessionStore::new)
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for CacheSessionStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        // Regenerate the id on the (unlikely) collision with a live session
+        while self.load(&record.id).await?.is_some() {
+            record.id = Id::default();
+        }
+        self.save(record).await
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        let Some(ttl) = time_left(record.expiry_date) else {
+            return self.delete(&record.id).await;
+        };
+        let value =
+            serde_json::to_vec(record).map_err(|e| session_store::Error::Encode(e.to_string()))?;
+
+        self.cache
+            .set(&Self::key(&record.id), value, Some(ttl))
+            .await
+            .map_err(backend_error)
+    }
+
+    async fn load(&self, id: &Id) -> session_store::Result<Option<Record>> {
+        let Some(value) = self
+            .cache
+            .get(&Self::key(id))
+            .await
+            .map_err(backend_error)?
+        else {
+            return Ok(None);
+        };
+        let record: Record = serde_json::from_slice(&value)
+            .map_err(|e| session_store::Error::Decode(e.to_string()))?;
+
+        Ok(time_left(record.expiry_date).map(|_| record))
+    }
+
+    async fn delete(&self, id: &Id) -> session_store::Result<()> {
+        self.cache
+            .delete(&Self::key(id))
+            .await
+            .map_err(backend_error)
+    }
+}
+
+/// Remaining lifetime of a record, `None` once expired
+fn time_left(expiry_date: OffsetDateTime) -> Option<Duration> {
+    Duration::try_from(expiry_date - OffsetDateTime::now_utc())
+        .ok()
+        .filter(|ttl| !ttl.is_zero())
+}
+
+#[allow(clippy::needless_pass_by_value)] // shaped for `map_err`
+fn backend_error(error: anyhow::Error) -> session_store::Error {
+    session_store::Error::Backend(error.to_string())
+}
+
+#[cfg(test)]
+#[cfg(feature = "cache-moka")]
+mod tests {
+    use crate::Infra;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode, header},
+        routing::get,
+    };
+    use barrzen_axum_core::{AppBuilder, BuildInfo, Config, Session};
+    use tower::ServiceExt;
+
+    fn session_config() -> Config {
+        serde_json::from_value(serde_json::json!({
+            "feature_startup_banner": false,
+            "feature_request_log": false,
+            "feature_cache": true,
+            "cache_backend": "moka",
+            "feature_session": true,
+            "session_secure": false
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_session_round_trip() {
+        let config = session_config();
+        let infra = Infra::init(&config).await.unwrap();
+        let app = AppBuilder::new(config, BuildInfo::default())
+            .with_session_store(infra.session_store().unwrap())
+            .route(
+                "/set",
+                get(|session: Session| async move {
+                    session.insert("name", "ada").await.unwrap();
+                    "stored"
+                }),
+            )
+            .route(
+                "/get",
+                get(|session: Session| async move {
+                    session
+                        .get::<String>("name")
+                        .await
+                        .unwrap()
+                        .unwrap_or_default()
+                }),
+            )
+            .build();
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/set").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookie = response.headers()[header::SET_COOKIE]
+            .to_str()
+            .unwrap()
+            .split(';')
+            .next()
+            .unwrap()
+            .to_string();
+        assert!(cookie.starts_with("session="));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/get")
+                    .header(header::COOKIE, cookie)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"ada");
+    }
+
+    #[tokio::test]
+    async fn test_session_requires_cache() {
+        let mut config = session_config();
+        config.features.feature_cache = false;
+
+        let error = Infra::init(&config).await.err().unwrap();
+        assert!(error.to_string().contains("FEATURE_SESSION"));
+    }
+}
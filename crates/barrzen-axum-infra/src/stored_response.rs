@@ -0,0 +1,86 @@
+//! Responses kept in the cache to be served again
+//!
+//! Encoded as the status, length-prefixed header pairs and the body, all
+//! big-endian.
+
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::Response,
+};
+
+pub(crate) struct StoredResponse {
+    pub(crate) status: StatusCode,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Bytes,
+}
+
+impl StoredResponse {
+    #[cfg(feature = "response-cache")]
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.body.len() + 256);
+        self.encode_into(&mut out);
+        out
+    }
+
+    pub(crate) fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.status.as_u16().to_be_bytes());
+        let headers: Vec<_> = self
+            .headers
+            .iter()
+            .filter_map(|(name, value)| {
+                let name_len = u16::try_from(name.as_str().len()).ok()?;
+                let value_len = u32::try_from(value.len()).ok()?;
+                Some((name_len, name, value_len, value))
+            })
+            .collect();
+        let count = u16::try_from(headers.len()).unwrap_or(u16::MAX);
+        out.extend_from_slice(&count.to_be_bytes());
+        for (name_len, name, value_len, value) in headers.into_iter().take(usize::from(count)) {
+            out.extend_from_slice(&name_len.to_be_bytes());
+            out.extend_from_slice(name.as_str().as_bytes());
+            out.extend_from_slice(&value_len.to_be_bytes());
+            out.extend_from_slice(value.as_bytes());
+        }
+        out.extend_from_slice(&self.body);
+    }
+
+    pub(crate) fn decode(mut rest: &[u8]) -> Option<Self> {
+        let status = StatusCode::from_u16(u16::from_be_bytes(take(&mut rest)?)).ok()?;
+        let count = u16::from_be_bytes(take(&mut rest)?);
+        let mut headers = HeaderMap::with_capacity(usize::from(count));
+        for _ in 0..count {
+            let name_len = u16::from_be_bytes(take(&mut rest)?);
+            let name =
+                HeaderName::from_bytes(take_slice(&mut rest, usize::from(name_len))?).ok()?;
+            let value_len = usize::try_from(u32::from_be_bytes(take(&mut rest)?)).ok()?;
+            let value = HeaderValue::from_bytes(take_slice(&mut rest, value_len)?).ok()?;
+            headers.append(name, value);
+        }
+        Some(Self {
+            status,
+            headers,
+            body: Bytes::copy_from_slice(rest),
+        })
+    }
+
+    pub(crate) fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        response
+    }
+}
+
+fn take<const N: usize>(rest: &mut &[u8]) -> Option<[u8; N]> {
+    take_slice(rest, N)?.try_into().ok()
+}
+
+fn take_slice<'a>(rest: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if rest.len() < len {
+        return None;
+    }
+    let (head, tail) = rest.split_at(len);
+    *rest = tail;
+    Some(head)
+}
@@ -0,0 +1,318 @@
+//! Two-level cache: Moka in front of Redis (`CACHE_BACKEND=tiered`)
+//!
+//! Reads check the in-process layer first and fill it from Redis on a hit.
+//! Writes and deletes go to both layers. An in-process entry lives at most
+//! `CACHE_L1_TTL_SECONDS`, and never longer than the Redis entry it was
+//! read from, so a value changed by another instance is seen
+//! after that long at the latest; with `CACHE_TIERED_INVALIDATION=true`
+//! deletes are also broadcast over Redis pub/sub and evicted everywhere
+//! right away.
+
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use anyhow::Context;
+use barrzen_axum_core::Config;
+use futures_util::StreamExt;
+
+use crate::{Cache, MokaCache};
+
+/// Redis pub/sub channel carrying the deleted keys
+pub const INVALIDATION_CHANNEL: &str = "barrzen:cache:invalidate";
+
+/// Delay before resubscribing after the pub/sub connection is lost
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(1);
+
+/// Broadcast of deleted keys to the other instances
+#[async_trait::async_trait]
+pub trait InvalidationBus {
+    async fn publish(&self, key: &str) -> anyhow::Result<()>;
+}
+
+/// [`InvalidationBus`] publishing on [`INVALIDATION_CHANNEL`]
+pub struct RedisInvalidation {
+    pool: deadpool_redis::Pool,
+}
+
+impl RedisInvalidation {
+    #[must_use]
+    pub fn new(pool: deadpool_redis::Pool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl InvalidationBus for RedisInvalidation {
+    async fn publish(&self, key: &str) -> anyhow::Result<()> {
+        let mut conn = self.pool.get().await?;
+        deadpool_redis::redis::cmd("PUBLISH")
+            .arg(INVALIDATION_CHANNEL)
+            .arg(key)
+            .query_async::<()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Moka (L1) in front of a shared cache (L2)
+pub struct TieredCache {
+    l1: MokaCache,
+    l2: Arc<dyn Cache + Send + Sync>,
+    l1_ttl: Duration,
+    invalidation: Option<Arc<dyn InvalidationBus + Send + Sync>>,
+}
+
+impl TieredCache {
+    /// Tiered cache keeping L1 entries at most `l1_ttl`
+    #[must_use]
+    pub fn new(l1: MokaCache, l2: Arc<dyn Cache + Send + Sync>, l1_ttl: Duration) -> Self {
+        Self {
+            l1,
+            l2,
+            l1_ttl,
+            invalidation: None,
+        }
+    }
+
+    /// Broadcast deletes on `bus`
+    #[must_use]
+    pub fn with_invalidation(mut self, bus: Arc<dyn InvalidationBus + Send + Sync>) -> Self {
+        self.invalidation = Some(bus);
+        self
+    }
+
+    /// Drop `key` from the in-process layer only, e.g. on an invalidation
+    /// message
+    pub async fn evict_local(&self, key: &str) {
+        self.l1.inner.invalidate(key).await;
+    }
+
+    fn l1_ttl(&self, ttl: Option<Duration>) -> Duration {
+        ttl.map_or(self.l1_ttl, |ttl| ttl.min(self.l1_ttl))
+    }
+}
+
+#[async_trait::async_trait]
+impl Cache for TieredCache {
+    /// Only the shared layer decides health; the local one cannot fail
+    async fn ping(&self) -> anyhow::Result<()> {
+        self.l2.ping().await
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(value) = self.l1.get(key).await? {
+            return Ok(Some(value));
+        }
+        let Some((value, remaining)) = self.l2.get_with_ttl(key).await? else {
+            return Ok(None);
+        };
+        self.l1
+            .set(key, value.clone(), Some(self.l1_ttl(remaining)))
+            .await?;
+        Ok(Some(value))
+    }
+
+    async fn set(&self, key: &str, value: Vec<u8>, ttl: Option<Duration>) -> anyhow::Result<()> {
+        self.l2.set(key, value.clone(), ttl).await?;
+        self.l1.set(key, value, Some(self.l1_ttl(ttl))).await
+    }
+
+    /// Decided by the shared layer, so only one instance wins
+    async fn set_if_absent(
+        &self,
+        key: &str,
+        value: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> anyhow::Result<bool> {
+        let stored = self.l2.set_if_absent(key, value.clone(), ttl).await?;
+        if stored {
+            self.l1.set(key, value, Some(self.l1_ttl(ttl))).await?;
+        }
+        Ok(stored)
+    }
+
+    async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.l1.delete(key).await?;
+        self.l2.delete(key).await?;
+        if let Some(bus) = &self.invalidation
+            && let Err(e) = bus.publish(key).await
+        {
+            // Other instances drop their copy after CACHE_L1_TTL_SECONDS anyway
+            tracing::warn!(error = %e, "Failed to broadcast cache invalidation");
+        }
+        Ok(())
+    }
+}
+
+/// Build the tiered cache over `l2`, subscribing to invalidations when
+/// `CACHE_TIERED_INVALIDATION=true`
+pub(crate) fn init(
+    config: &Config,
+    l2: Arc<dyn Cache + Send + Sync>,
+    pool: deadpool_redis::Pool,
+) -> anyhow::Result<Arc<TieredCache>> {
+    let cache_config = &config.cache;
+    let l1_ttl = Duration::from_secs(cache_config.cache_l1_ttl_seconds);
+    let mut cache = TieredCache::new(
+        MokaCache::new(cache_config.cache_max_entries, l1_ttl),
+        l2,
+        l1_ttl,
+    );
+    if !cache_config.cache_tiered_invalidation {
+        return Ok(Arc::new(cache));
+    }
+
+    let url = cache_config
+        .cache_redis_url
+        .clone()
+        .context("CACHE_REDIS_URL must be set for CACHE_BACKEND=tiered")?;
+    let client = deadpool_redis::redis::Client::open(url)?;
+    cache = cache.with_invalidation(Arc::new(RedisInvalidation::new(pool)));
+    let cache = Arc::new(cache);
+    tokio::spawn(listen(client, Arc::downgrade(&cache)));
+    Ok(cache)
+}
+
+/// Evict the keys published on [`INVALIDATION_CHANNEL`] until `cache` is
+/// dropped
+async fn listen(client: deadpool_redis::redis::Client, cache: Weak<TieredCache>) {
+    loop {
+        match client.get_async_pubsub().await {
+            Ok(mut pubsub) => match pubsub.subscribe(INVALIDATION_CHANNEL).await {
+                Ok(()) => {
+                    let Some(tiered) = cache.upgrade() else {
+                        return;
+                    };
+                    // Deletes may have been missed while unsubscribed
+                    tiered.l1.inner.invalidate_all();
+                    drop(tiered);
+                    tracing::debug!(
+                        channel = INVALIDATION_CHANNEL,
+                        "Listening for cache invalidations"
+                    );
+
+                    let mut messages = pubsub.on_message();
+                    while let Some(message) = messages.next().await {
+                        let Some(tiered) = cache.upgrade() else {
+                            return;
+                        };
+                        match message.get_payload::<String>() {
+                            Ok(key) => tiered.evict_local(&key).await,
+                            Err(e) => {
+                                tracing::warn!(error = %e, "Invalid cache invalidation message");
+                            }
+                        }
+                    }
+                    tracing::warn!("Cache invalidation subscription closed, resubscribing");
+                }
+                Err(e) => tracing::warn!(error = %e, "Failed to subscribe to cache invalidations"),
+            },
+            Err(e) => tracing::warn!(error = %e, "Failed to connect for cache invalidations"),
+        }
+        if cache.strong_count() == 0 {
+            return;
+        }
+        tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    /// Bus handing published keys to the test
+    struct ChannelBus(mpsc::UnboundedSender<String>);
+
+    #[async_trait::async_trait]
+    impl InvalidationBus for ChannelBus {
+        async fn publish(&self, key: &str) -> anyhow::Result<()> {
+            self.0.send(key.to_string())?;
+            Ok(())
+        }
+    }
+
+    fn shared() -> Arc<dyn Cache + Send + Sync> {
+        Arc::new(MokaCache::new(100, Duration::from_mins(5)))
+    }
+
+    fn tiered(l2: &Arc<dyn Cache + Send + Sync>) -> TieredCache {
+        TieredCache::new(
+            MokaCache::new(100, Duration::from_mins(1)),
+            Arc::clone(l2),
+            Duration::from_mins(1),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_read_through_populates_l1() {
+        let l2 = shared();
+        let cache = tiered(&l2);
+        l2.set("user:1", b"ada".to_vec(), None).await.unwrap();
+
+        assert_eq!(cache.get("user:1").await.unwrap(), Some(b"ada".to_vec()));
+        // Served from L1 once L2 no longer has it
+        l2.delete("user:1").await.unwrap();
+        assert_eq!(cache.get("user:1").await.unwrap(), Some(b"ada".to_vec()));
+        assert_eq!(cache.get("user:2").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_read_through_keeps_the_l2_expiry() {
+        let l2 = shared();
+        let cache = tiered(&l2);
+        l2.set("user:1", b"ada".to_vec(), Some(Duration::from_millis(50)))
+            .await
+            .unwrap();
+
+        assert_eq!(cache.get("user:1").await.unwrap(), Some(b"ada".to_vec()));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // The L1 copy expired with the L2 entry instead of living CACHE_L1_TTL
+        assert_eq!(cache.get("user:1").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_write_through() {
+        let l2 = shared();
+        let cache = tiered(&l2);
+        cache.set("user:1", b"ada".to_vec(), None).await.unwrap();
+
+        assert_eq!(l2.get("user:1").await.unwrap(), Some(b"ada".to_vec()));
+        assert_eq!(cache.l1.get("user:1").await.unwrap(), Some(b"ada".to_vec()));
+        assert_eq!(
+            cache.l1_ttl(Some(Duration::from_mins(10))),
+            Duration::from_mins(1)
+        );
+        assert_eq!(
+            cache.l1_ttl(Some(Duration::from_secs(5))),
+            Duration::from_secs(5)
+        );
+
+        cache.delete("user:1").await.unwrap();
+        assert_eq!(l2.get("user:1").await.unwrap(), None);
+        assert_eq!(cache.l1.get("user:1").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_invalidation_evicts_other_instances() {
+        let l2 = shared();
+        let (sender, mut published) = mpsc::unbounded_channel();
+        let writer = tiered(&l2).with_invalidation(Arc::new(ChannelBus(sender)));
+        let reader = tiered(&l2);
+
+        writer.set("user:1", b"ada".to_vec(), None).await.unwrap();
+        assert_eq!(reader.get("user:1").await.unwrap(), Some(b"ada".to_vec()));
+
+        writer.delete("user:1").await.unwrap();
+        // Still cached by the other instance until the message arrives
+        assert_eq!(reader.get("user:1").await.unwrap(), Some(b"ada".to_vec()));
+
+        let key = published.recv().await.unwrap();
+        assert_eq!(key, "user:1");
+        reader.evict_local(&key).await;
+        assert_eq!(reader.get("user:1").await.unwrap(), None);
+    }
+}
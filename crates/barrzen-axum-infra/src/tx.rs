@@ -0,0 +1,354 @@
+//! Request-scoped database transaction
+//!
+//! The [`Tx`] extractor begins a transaction on the primary connection the
+//! first time a request asks for it; every further `Tx` of the same request
+//! shares it. [`TxLayer`] (installed by
+//! [`with_infra`](crate::InfraAppBuilderExt::with_infra)) ends it once the
+//! handler returns: commit for a status below 400, rollback otherwise. A
+//! panicking handler drops the transaction, which rolls it back.
+//!
+//! ```ignore
+//! async fn create_order(tx: Tx, ApiJson(order): ApiJson<NewOrder>) -> ApiResult<Order> {
+//!     let conn = tx.conn().await?;
+//!     let order = order.into_active_model().insert(&*conn).await.map_err(db_error)?;
+//!     Outbox::enqueue_json(&*conn, "orders.created", &order).await.map_err(db_error)?;
+//!     Ok(ApiResponse::created(order, "Order created"))
+//! }
+//! ```
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use axum::{
+    body::Body,
+    extract::FromRequestParts,
+    http::{Request, request::Parts},
+    response::{IntoResponse, Response},
+};
+use barrzen_axum_core::response::{ApiError, extract_request_id};
+use sea_orm::{DatabaseTransaction, TransactionTrait};
+use tokio::sync::{MappedMutexGuard, Mutex, MutexGuard};
+use tower::{Layer, Service};
+
+use crate::extract::{component, with_request_id};
+
+/// Transaction of the current request (`extract` and `db` features)
+///
+/// Cheap to clone; clones share the transaction.
+#[derive(Clone)]
+pub struct Tx(TxSlot);
+
+/// Per-request transaction state, inserted by [`TxLayer`]
+#[derive(Clone, Default)]
+struct TxSlot(Arc<Mutex<TxState>>);
+
+#[derive(Default)]
+enum TxState {
+    #[default]
+    Idle,
+    Open(DatabaseTransaction),
+    /// Committed early or ended by the layer
+    Done,
+}
+
+impl Tx {
+    /// The open transaction, to pass as the connection of queries
+    ///
+    /// Drop the guard before calling [`Tx::commit_early`] or taking it again.
+    ///
+    /// # Errors
+    /// Returns a 500 [`ApiError`] after [`Tx::commit_early`].
+    pub async fn conn(&self) -> Result<MappedMutexGuard<'_, DatabaseTransaction>, ApiError> {
+        MutexGuard::try_map(self.0.0.lock().await, |state| match state {
+            TxState::Open(txn) => Some(txn),
+            TxState::Idle | TxState::Done => None,
+        })
+        .map_err(|_| ApiError::internal("The request transaction is already committed"))
+    }
+
+    /// Commit now instead of when the response is ready
+    ///
+    /// Later errors of the handler no longer roll back what was committed.
+    ///
+    /// # Errors
+    /// Returns a 500 [`ApiError`] if the commit fails or the transaction is
+    /// already committed.
+    pub async fn commit_early(&self) -> Result<(), ApiError> {
+        let state = std::mem::replace(&mut *self.0.0.lock().await, TxState::Done);
+        let TxState::Open(txn) = state else {
+            return Err(ApiError::internal(
+                "The request transaction is already committed",
+            ));
+        };
+        txn.commit().await.map_err(|e| {
+            tracing::error!(error = %e, "Failed to commit the request transaction");
+            ApiError::internal("Failed to commit the database transaction")
+        })
+    }
+}
+
+impl<S> FromRequestParts<S> for Tx
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let db = component(parts, "Database", "FEATURE_DB=false", |infra| {
+            infra.db.clone()
+        })?;
+        let Some(slot) = parts.extensions.get::<TxSlot>().cloned() else {
+            let error = ApiError::internal(
+                "Tx is not available: TxLayer is not installed, use AppBuilder::with_infra",
+            );
+            return Err(with_request_id(parts, error));
+        };
+
+        let mut state = slot.0.lock().await;
+        // Later extractions reuse the transaction of the first one
+        if matches!(*state, TxState::Idle) {
+            let txn = db.begin().await.map_err(|e| {
+                tracing::error!(error = %e, "Failed to begin the request transaction");
+                with_request_id(
+                    parts,
+                    ApiError::service_unavailable("Database is not available"),
+                )
+            })?;
+            *state = TxState::Open(txn);
+        }
+        drop(state);
+        Ok(Self(slot))
+    }
+}
+
+/// Layer ending the transaction of [`Tx`] with the response
+///
+/// Commits when the status is below 400 and rolls back otherwise. A failed
+/// commit replaces the response with a 500.
+#[derive(Clone, Copy, Default)]
+pub struct TxLayer;
+
+impl<S> Layer<S> for TxLayer {
+    type Service = TxService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TxService { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct TxService<S> {
+    inner: S,
+}
+
+impl<S> Service<Request<Body>> for TxService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        let slot = TxSlot::default();
+        req.extensions_mut().insert(slot.clone());
+        let request_id = extract_request_id(req.headers());
+        let future = self.inner.call(req);
+
+        Box::pin(async move {
+            let response = future.await?;
+            Ok(slot.finish(response, request_id).await)
+        })
+    }
+}
+
+impl TxSlot {
+    async fn finish(&self, response: Response, request_id: Option<String>) -> Response {
+        let state = std::mem::replace(&mut *self.0.lock().await, TxState::Done);
+        let TxState::Open(txn) = state else {
+            return response;
+        };
+
+        if response.status().as_u16() >= 400 {
+            if let Err(e) = txn.rollback().await {
+                tracing::warn!(error = %e, "Failed to roll back the request transaction");
+            }
+            return response;
+        }
+        match txn.commit().await {
+            Ok(()) => response,
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to commit the request transaction");
+                let error = ApiError::internal("Failed to commit the database transaction");
+                match request_id {
+                    Some(request_id) => error.with_request_id(request_id),
+                    None => error,
+                }
+                .into_response()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Extension, Router, http::StatusCode, routing::post};
+    use sea_orm::{ConnectionTrait, Database, DatabaseConnection, Statement};
+    use tower::ServiceExt;
+    use tower_http::catch_panic::CatchPanicLayer;
+
+    use super::*;
+    use crate::Infra;
+
+    async fn db() -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        db.execute_unprepared("CREATE TABLE orders (id INTEGER PRIMARY KEY)")
+            .await
+            .unwrap();
+        db
+    }
+
+    async fn insert(tx: &Tx, id: i64) {
+        let conn = tx.conn().await.unwrap();
+        conn.execute_unprepared(&format!("INSERT INTO orders (id) VALUES ({id})"))
+            .await
+            .unwrap();
+    }
+
+    async fn count<C: ConnectionTrait>(db: &C) -> i64 {
+        let row = db
+            .query_one(Statement::from_string(
+                db.get_database_backend(),
+                "SELECT COUNT(*) AS n FROM orders",
+            ))
+            .await
+            .unwrap()
+            .unwrap();
+        row.try_get("", "n").unwrap()
+    }
+
+    async fn panics(tx: Tx) -> &'static str {
+        insert(&tx, 3).await;
+        panic!("boom");
+    }
+
+    fn app(db: &DatabaseConnection) -> Router {
+        let infra = Infra {
+            db: Some(db.clone()),
+            ..Infra::default()
+        };
+        Router::new()
+            .route(
+                "/ok",
+                post(|tx: Tx| async move {
+                    insert(&tx, 1).await;
+                    "created"
+                }),
+            )
+            .route(
+                "/error",
+                post(|tx: Tx| async move {
+                    insert(&tx, 2).await;
+                    Err::<(), _>(ApiError::internal("boom"))
+                }),
+            )
+            .route("/panic", post(panics))
+            .route(
+                "/early",
+                post(|tx: Tx| async move {
+                    insert(&tx, 4).await;
+                    tx.commit_early().await?;
+                    assert!(tx.conn().await.is_err());
+                    Err::<(), _>(ApiError::conflict("too late to roll back"))
+                }),
+            )
+            .route(
+                "/nested",
+                post(|first: Tx, second: Tx| async move {
+                    insert(&first, 5).await;
+                    // Same transaction: sees the uncommitted row
+                    count(&*second.conn().await.unwrap()).await.to_string()
+                }),
+            )
+            .layer(TxLayer)
+            .layer(Extension(infra))
+            .layer(CatchPanicLayer::new())
+    }
+
+    async fn call(app: Router, path: &str) -> (StatusCode, String) {
+        let response = app
+            .oneshot(Request::post(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[tokio::test]
+    async fn test_commit_on_success() {
+        let db = db().await;
+        assert_eq!(call(app(&db), "/ok").await.0, StatusCode::OK);
+        assert_eq!(count(&db).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_on_error_response() {
+        let db = db().await;
+        let (status, _) = call(app(&db), "/error").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(count(&db).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_on_panic() {
+        let db = db().await;
+        let (status, _) = call(app(&db), "/panic").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(count(&db).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_commit_early_survives_error() {
+        let db = db().await;
+        let (status, _) = call(app(&db), "/early").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(count(&db).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_nested_extraction_shares_transaction() {
+        let db = db().await;
+        assert_eq!(
+            call(app(&db), "/nested").await,
+            (StatusCode::OK, "1".to_string())
+        );
+        assert_eq!(count(&db).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_missing_layer() {
+        let db = db().await;
+        let infra = Infra {
+            db: Some(db.clone()),
+            ..Infra::default()
+        };
+        let app = Router::new()
+            .route("/ok", post(|_tx: Tx| async { "created" }))
+            .layer(Extension(infra));
+        let (status, body) = call(app, "/ok").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body.contains("TxLayer"), "{body}");
+    }
+}
@@ -0,0 +1,219 @@
+//! File output for the `tracing` backend
+//!
+//! `LOG_FILE_ROTATION=hourly|daily` uses `tracing-appender`'s rolling
+//! appender, `size` uses [`SizeRollingWriter`]. Lines are written by a
+//! background worker; its guard flushes them when dropped. With
+//! `LOG_FILE_KEEP` set, only that many rotated files are kept.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use barrzen_axum_core::{LogRotation, LoggingConfig};
+use tracing_appender::{
+    non_blocking::{NonBlocking, WorkerGuard},
+    rolling::{RollingFileAppender, Rotation},
+};
+
+/// Non-blocking writer for `LOG_FILE_DIR` and the guard flushing it
+///
+/// # Errors
+/// Returns error if the directory cannot be created or is not writable.
+pub(crate) fn writer(config: &LoggingConfig) -> anyhow::Result<(NonBlocking, WorkerGuard)> {
+    let dir = Path::new(config.log_file_dir.trim());
+    ensure_writable(dir)?;
+    let prefix = config.log_file_prefix.trim();
+
+    let writer = match config.log_file_rotation {
+        LogRotation::Hourly | LogRotation::Daily => {
+            let rotation = if config.log_file_rotation == LogRotation::Hourly {
+                Rotation::HOURLY
+            } else {
+                Rotation::DAILY
+            };
+            let mut builder = RollingFileAppender::builder()
+                .rotation(rotation)
+                .filename_prefix(prefix)
+                .filename_suffix("log");
+            if let Ok(keep) = usize::try_from(config.log_file_keep)
+                && keep > 0
+            {
+                // The appender counts the current file too
+                builder = builder.max_log_files(keep.saturating_add(1));
+            }
+            let appender = builder
+                .build(dir)
+                .with_context(|| format!("Failed to open log file in {}", dir.display()))?;
+            tracing_appender::non_blocking(appender)
+        }
+        LogRotation::Size => {
+            let max_bytes = config.log_file_max_size_mb.saturating_mul(1024 * 1024);
+            let file = SizeRollingWriter::open(dir, prefix, max_bytes, config.log_file_keep)
+                .with_context(|| format!("Failed to open log file in {}", dir.display()))?;
+            tracing_appender::non_blocking(file)
+        }
+    };
+    Ok(writer)
+}
+
+/// Create `dir` if needed and check a file can be written to it
+pub(crate) fn ensure_writable(dir: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("LOG_FILE_DIR {} cannot be created", dir.display()))?;
+
+    let probe = dir.join(format!(".write-test-{}", std::process::id()));
+    File::create(&probe)
+        .with_context(|| format!("LOG_FILE_DIR {} is not writable", dir.display()))?;
+    let _ = std::fs::remove_file(probe);
+    Ok(())
+}
+
+/// Appends to `{prefix}.log`, moving it to `{prefix}.log.{n}` once it
+/// reaches `max_bytes`
+///
+/// Rotated files are numbered oldest first. Only the newest `keep` are
+/// kept (all when `keep` is 0).
+pub(crate) struct SizeRollingWriter {
+    dir: PathBuf,
+    prefix: String,
+    max_bytes: u64,
+    keep: u64,
+    file: File,
+    written: u64,
+}
+
+impl SizeRollingWriter {
+    pub(crate) fn open(dir: &Path, prefix: &str, max_bytes: u64, keep: u64) -> io::Result<Self> {
+        let path = dir.join(format!("{prefix}.log"));
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            prefix: prefix.to_string(),
+            max_bytes,
+            keep,
+            file,
+            written,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let current = self.dir.join(format!("{}.log", self.prefix));
+        let rotated = self.rotated_numbers()?;
+        let next = rotated.iter().max().map_or(1, |n| n + 1);
+        std::fs::rename(&current, self.rotated_path(next))?;
+
+        self.file = OpenOptions::new().create(true).append(true).open(current)?;
+        self.written = 0;
+
+        if self.keep > 0 {
+            for n in rotated.into_iter().filter(|n| next - n >= self.keep) {
+                std::fs::remove_file(self.rotated_path(n))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn rotated_path(&self, n: u64) -> PathBuf {
+        self.dir.join(format!("{}.log.{n}", self.prefix))
+    }
+
+    /// Numbers of the existing `{prefix}.log.{n}` files
+    fn rotated_numbers(&self) -> io::Result<Vec<u64>> {
+        let stem = format!("{}.log.", self.prefix);
+        let mut numbers = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let name = entry?.file_name();
+            if let Some(n) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(&stem))
+                .and_then(|n| n.parse().ok())
+            {
+                numbers.push(n);
+            }
+        }
+        Ok(numbers)
+    }
+}
+
+impl Write for SizeRollingWriter {
+    /// Each call is one formatted event, so lines are never split across files
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len() as u64;
+        if self.written > 0 && self.written + len > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.written += len;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_rolling_writer_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut writer = SizeRollingWriter::open(dir.path(), "app", 10, 0).unwrap();
+        writer.write_all(b"first\n").unwrap();
+        writer.write_all(b"second\n").unwrap();
+        writer.write_all(b"third\n").unwrap();
+        // A single oversized line still goes to one file
+        writer.write_all(b"a very long line\n").unwrap();
+        writer.flush().unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("app.log.1"), "first\n");
+        assert_eq!(read("app.log.2"), "second\n");
+        assert_eq!(read("app.log.3"), "third\n");
+        assert_eq!(read("app.log"), "a very long line\n");
+
+        // Reopening continues the current file and numbering
+        let mut writer = SizeRollingWriter::open(dir.path(), "app", 10, 0).unwrap();
+        writer.write_all(b"fifth\n").unwrap();
+        assert_eq!(read("app.log.4"), "a very long line\n");
+        assert_eq!(read("app.log"), "fifth\n");
+    }
+
+    #[test]
+    fn test_size_rolling_writer_keeps_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        // Leftovers from an earlier run with a larger keep
+        std::fs::write(dir.path().join("app.log.1"), "old\n").unwrap();
+        let mut writer = SizeRollingWriter::open(dir.path(), "app", 5, 2).unwrap();
+        for line in ["one\n", "two\n", "three\n", "four\n"] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+
+        let mut names: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["app.log", "app.log.3", "app.log.4"]);
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("app.log.3"), "two\n");
+        assert_eq!(read("app.log.4"), "three\n");
+        assert_eq!(read("app.log"), "four\n");
+    }
+
+    #[test]
+    fn test_unwritable_dir_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let not_a_dir = dir.path().join("file");
+        std::fs::write(&not_a_dir, "").unwrap();
+
+        let error = ensure_writable(&not_a_dir).unwrap_err().to_string();
+        assert!(error.contains("cannot be created"), "{error}");
+    }
+}
@@ -0,0 +1,153 @@
+//! Log filter from `LOG_LEVEL`, `LOG_DIRECTIVES` and `RUST_LOG`
+//!
+//! The directive list comes from [`LoggingConfig::filter_directives`]:
+//! `LOG_LEVEL` is the base level, `LOG_DIRECTIVES` overrides modules and
+//! `RUST_LOG` is layered on top. Invalid entries are skipped and reported.
+//! With `AUDIT_LOG_FORCE`, `audit=info` comes last, so none of them can turn
+//! the audit log off.
+//!
+//! [`LoggingConfig::filter_directives`]: barrzen_axum_core::LoggingConfig::filter_directives
+
+use barrzen_axum_core::{Config, audit::AUDIT_TARGET};
+use tracing_subscriber::{
+    EnvFilter,
+    filter::{Directive, LevelFilter},
+};
+
+/// Directive list for `config`, including `RUST_LOG` from the environment
+pub(crate) fn directives(config: &Config) -> String {
+    let directives = config
+        .logging
+        .filter_directives(std::env::var("RUST_LOG").ok().as_deref());
+    with_audit(&directives, config.audit.audit_log_force)
+}
+
+/// `directives` with the audit target kept at `info` when `forced`
+pub(crate) fn with_audit(directives: &str, forced: bool) -> String {
+    match (forced, directives.trim().is_empty()) {
+        (false, _) => directives.to_string(),
+        (true, true) => format!("{AUDIT_TARGET}=info"),
+        (true, false) => format!("{directives},{AUDIT_TARGET}=info"),
+    }
+}
+
+/// `EnvFilter` with `directives` applied in order, and the rejected entries
+///
+/// Starts from `info`, so an invalid `LOG_LEVEL` does not silence
+/// everything.
+pub(crate) fn env_filter(directives: &str) -> (EnvFilter, Vec<String>) {
+    let mut filter = EnvFilter::default().add_directive(LevelFilter::INFO.into());
+    let mut rejected = Vec::new();
+    for entry in entries(directives) {
+        match entry.parse::<Directive>() {
+            Ok(directive) => filter = filter.add_directive(directive),
+            Err(_) => rejected.push(entry.to_string()),
+        }
+    }
+    (filter, rejected)
+}
+
+/// Single level for backends without per-module filtering (`fast_log`)
+///
+/// A bare level replaces the base level; `target=level` entries can only
+/// raise it, so no module loses records it asked for.
+#[cfg_attr(not(feature = "fast-log"), allow(dead_code))]
+pub(crate) fn global_level(directives: &str) -> LevelFilter {
+    let mut base = LevelFilter::INFO;
+    let mut modules = LevelFilter::OFF;
+    for entry in entries(directives) {
+        match entry.rsplit_once('=') {
+            Some((_, level)) => {
+                if let Some(level) = parse_level(level) {
+                    modules = modules.max(level);
+                }
+            }
+            None => {
+                if let Some(level) = parse_level(entry) {
+                    base = level;
+                }
+            }
+        }
+    }
+    base.max(modules)
+}
+
+fn entries(directives: &str) -> impl Iterator<Item = &str> {
+    directives
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+}
+
+fn parse_level(value: &str) -> Option<LevelFilter> {
+    match value.trim().to_lowercase().as_str() {
+        "off" => Some(LevelFilter::OFF),
+        "error" => Some(LevelFilter::ERROR),
+        "warn" | "warning" => Some(LevelFilter::WARN),
+        "info" => Some(LevelFilter::INFO),
+        "debug" => Some(LevelFilter::DEBUG),
+        "trace" => Some(LevelFilter::TRACE),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_filter_applies_directives_in_order() {
+        let (filter, rejected) =
+            env_filter("warn,hyper=warn,sea_orm=debug,hyper=loud,debug,tower_http=off");
+        assert_eq!(rejected, vec!["hyper=loud"]);
+
+        let filter = filter.to_string();
+        for directive in ["debug", "hyper=warn", "sea_orm=debug", "tower_http=off"] {
+            assert!(filter.split(',').any(|d| d == directive), "{filter}");
+        }
+        assert!(
+            !filter.split(',').any(|d| d == "warn" || d == "info"),
+            "{filter}"
+        );
+    }
+
+    #[test]
+    fn test_env_filter_defaults_to_info() {
+        let (filter, rejected) = env_filter("");
+        assert!(rejected.is_empty());
+        assert_eq!(filter.to_string(), "info");
+    }
+
+    #[test]
+    fn test_forced_audit_survives_audit_off() {
+        use tracing::Level;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let audit_enabled = |directives: &str| {
+            let (filter, rejected) = env_filter(directives);
+            assert!(rejected.is_empty());
+            let subscriber = tracing_subscriber::registry().with(filter);
+            tracing::subscriber::with_default(subscriber, || {
+                assert!(!tracing::enabled!(target: "hyper", Level::INFO));
+                tracing::enabled!(target: "audit", Level::INFO)
+            })
+        };
+
+        let directives = "warn,audit=off";
+        assert!(!audit_enabled(&with_audit(directives, false)));
+        assert!(audit_enabled(&with_audit(directives, true)));
+        assert_eq!(with_audit("", true), "audit=info");
+    }
+
+    #[test]
+    fn test_global_level_merge() {
+        assert_eq!(global_level(""), LevelFilter::INFO);
+        assert_eq!(global_level("warn,hyper=error"), LevelFilter::WARN);
+        assert_eq!(global_level("warn,sea_orm=debug"), LevelFilter::DEBUG);
+        assert_eq!(global_level("info,warning"), LevelFilter::WARN);
+        assert_eq!(
+            global_level("error,sea_orm=loud,sea_orm"),
+            LevelFilter::ERROR
+        );
+    }
+}
@@ -2,131 +2,423 @@
 //!
 //! Handles tracing setup and OpenTelemetry integration.
 
-use barrzen_axum_core::{Config, LogBackend, LogFormat};
+mod file;
+mod filter;
+mod log_bridge;
+
+use barrzen_axum_core::{BuildInfo, Config, LogBackend, LogFormat, LogLevelControl};
 use tracing_subscriber::{
-    fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter,
+    EnvFilter, Registry,
+    fmt::{MakeWriter, format::FmtSpan},
+    layer::SubscriberExt,
+    reload,
 };
 
 #[cfg(feature = "otel")]
-use tracing_subscriber::Layer;
+use barrzen_axum_core::{OtelConfig, OtlpProtocol, TraceSampler};
 #[cfg(feature = "otel")]
 use std::sync::OnceLock;
+use tracing_subscriber::Layer;
 
+#[cfg(feature = "fast-log")]
+static FAST_LOG_INSTALLED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
 #[cfg(feature = "otel")]
 static OTEL_PROVIDER: OnceLock<opentelemetry_sdk::trace::SdkTracerProvider> = OnceLock::new();
+#[cfg(feature = "otel")]
+static OTEL_METER_PROVIDER: OnceLock<opentelemetry_sdk::metrics::SdkMeterProvider> =
+    OnceLock::new();
 
-/// Initialize tracing based on configuration
+/// Handle to the installed subscriber
+///
+/// Changes the log filter at runtime. Pass it to
+/// `AppBuilder::with_log_control` to serve `PUT /admin/log-level`.
+#[derive(Clone, Default)]
+pub struct ObsHandle {
+    /// `None` for backends without a reloadable filter (`fast_log`)
+    filter: Option<reload::Handle<EnvFilter, Registry>>,
+    /// Keep `audit=info` in replaced filters (`AUDIT_LOG_FORCE`)
+    force_audit: bool,
+}
+
+impl ObsHandle {
+    /// Replace the filter with `EnvFilter` directives, e.g. `debug,hyper=warn`
+    ///
+    /// With `AUDIT_LOG_FORCE`, `audit=info` is kept at the end.
+    ///
+    /// # Errors
+    /// Returns error if `filter` is invalid (the current filter stays) or the
+    /// backend cannot reload its filter.
+    pub fn set_filter(&self, filter: &str) -> anyhow::Result<()> {
+        let Some(handle) = &self.filter else {
+            anyhow::bail!("the log filter can only be changed with LOG_BACKEND=tracing");
+        };
+        let filter = EnvFilter::builder().parse(filter::with_audit(filter, self.force_audit))?;
+        handle.reload(filter)?;
+        Ok(())
+    }
+
+    /// Current filter directives, if the backend has a reloadable filter
+    #[must_use]
+    pub fn current_filter(&self) -> Option<String> {
+        self.filter
+            .as_ref()
+            .and_then(|handle| handle.with_current(ToString::to_string).ok())
+    }
+}
+
+impl LogLevelControl for ObsHandle {
+    fn current_filter(&self) -> String {
+        ObsHandle::current_filter(self).unwrap_or_default()
+    }
+
+    fn set_filter(&self, filter: &str) -> Result<(), String> {
+        ObsHandle::set_filter(self, filter).map_err(|e| e.to_string())
+    }
+}
+
+/// Wrap `filter` so it can be replaced through the returned handle
+fn reloadable(filter: EnvFilter) -> (reload::Layer<EnvFilter, Registry>, ObsHandle) {
+    let (layer, handle) = reload::Layer::new(filter);
+    let handle = ObsHandle {
+        filter: Some(handle),
+        force_audit: false,
+    };
+    (layer, handle)
+}
+
+/// Keeps observability running; flushes and tears it down on drop
+///
+/// Owns the background worker guards, the OpenTelemetry provider (shut
+/// down on drop so buffered spans are exported) and, for test-scoped
+/// subscribers, the scope itself. Hold it for the lifetime of `main`:
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// let config = barrzen_axum_core::Config::from_env()?;
+/// let obs = barrzen_axum_obs::init(&config)?;
+/// // ... serve, then drop `obs` to flush
+/// # drop(obs);
+/// # Ok(())
+/// # }
+/// ```
+#[must_use = "dropping the guard shuts observability down"]
+pub struct ObsGuard {
+    handle: ObsHandle,
+    /// Background writer guards, flushed when dropped
+    worker_guards: Vec<Box<dyn std::any::Any + Send>>,
+    #[cfg(feature = "otel")]
+    otel_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+    #[cfg(feature = "otel")]
+    meter_provider: Option<opentelemetry_sdk::metrics::SdkMeterProvider>,
+    /// Set by [`ObsGuard::try_init_for_test`]
+    scope: Option<tracing::subscriber::DefaultGuard>,
+}
+
+impl ObsGuard {
+    fn new(handle: ObsHandle) -> Self {
+        Self {
+            handle,
+            worker_guards: Vec::new(),
+            #[cfg(feature = "otel")]
+            otel_provider: None,
+            #[cfg(feature = "otel")]
+            meter_provider: None,
+            scope: None,
+        }
+    }
+
+    /// Handle for changing the log filter at runtime
+    #[must_use]
+    pub fn handle(&self) -> &ObsHandle {
+        &self.handle
+    }
+
+    /// Keep `guard` (e.g. a non-blocking writer's `WorkerGuard`) alive until
+    /// this guard is dropped
+    pub fn keep_alive(&mut self, guard: impl std::any::Any + Send) {
+        self.worker_guards.push(Box::new(guard));
+    }
+
+    /// Install the subscriber for the current thread only
+    ///
+    /// Uses `set_default` scoping instead of the global subscriber, so
+    /// parallel tests (and repeated inits) do not conflict. The scope ends
+    /// when the guard is dropped.
+    ///
+    /// # Errors
+    /// Returns error if the subscriber cannot be built or
+    /// `LOG_BACKEND=fast_log`, which only has a global logger.
+    pub fn try_init_for_test(config: &Config) -> anyhow::Result<Self> {
+        if config.logging.log_backend == LogBackend::FastLog {
+            anyhow::bail!("LOG_BACKEND=fast_log cannot be scoped to a test");
+        }
+        let (dispatch, mut guard) = build_subscriber(config, None)?;
+        // `log` has a single global logger; the scoped filter still applies
+        let _ = log_bridge::install_log_tracer();
+        guard.scope = Some(tracing::dispatcher::set_default(&dispatch));
+        Ok(guard)
+    }
+}
+
+impl Drop for ObsGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.otel_provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("OpenTelemetry shutdown failed: {e}");
+        }
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.meter_provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("OpenTelemetry metrics shutdown failed: {e}");
+        }
+    }
+}
+
+/// Initialize logging, tracing and OpenTelemetry based on configuration
+///
+/// Installs the global subscriber. Keep the returned [`ObsGuard`] alive
+/// until shutdown; dropping it flushes pending spans.
 ///
 /// # Errors
 /// Returns error if tracing subscriber setup fails.
-pub fn init_tracing(config: &Config) -> anyhow::Result<()> {
-    match config.logging.log_backend {
+pub fn init(config: &Config) -> anyhow::Result<ObsGuard> {
+    install(config, None)
+}
+
+/// [`init`], also tagging OTEL traces with `service.version` from `build`
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// let config = barrzen_axum_core::Config::from_env()?;
+/// let obs = barrzen_axum_obs::init_with_build(&config, &barrzen_axum_core::build_info!())?;
+/// # drop(obs);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+/// Returns error if tracing subscriber setup fails.
+pub fn init_with_build(config: &Config, build: &BuildInfo) -> anyhow::Result<ObsGuard> {
+    install(config, Some(build))
+}
+
+fn install(config: &Config, build: Option<&BuildInfo>) -> anyhow::Result<ObsGuard> {
+    #[cfg_attr(not(feature = "otel"), allow(unused_mut))]
+    let mut guard = match config.logging.log_backend {
         LogBackend::Tracing => {
-            let env_filter = EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| EnvFilter::new(&config.logging.log_level));
-            init_tracing_subscriber(config, env_filter)
+            let (dispatch, guard) = build_subscriber(config, build)?;
+            tracing::dispatcher::set_global_default(dispatch)?;
+            log_bridge::install_log_tracer()?;
+            #[cfg(feature = "otel")]
+            if let Some(provider) = &guard.otel_provider {
+                opentelemetry::global::set_tracer_provider(provider.clone());
+                let _ = OTEL_PROVIDER.set(provider.clone());
+            }
+            guard
         }
-        LogBackend::FastLog => init_fast_log(config),
+        LogBackend::FastLog => {
+            init_fast_log(config)?;
+            #[cfg_attr(not(feature = "fast-log"), allow(unused_mut))]
+            let mut guard = ObsGuard::new(ObsHandle::default());
+            #[cfg(feature = "fast-log")]
+            guard.keep_alive(FastLogFlush);
+            guard
+        }
+    };
+
+    // Metrics are independent of the log backend
+    #[cfg(feature = "otel")]
+    if config.features.feature_otel_metrics {
+        let provider = init_meter_provider(config, build)?;
+        opentelemetry::global::set_meter_provider(provider.clone());
+        let _ = OTEL_METER_PROVIDER.set(provider.clone());
+        guard.meter_provider = Some(provider);
     }
+
+    Ok(guard)
+}
+
+/// Initialize tracing based on configuration
+///
+/// The returned [`ObsHandle`] changes the log filter at runtime. Nothing is
+/// flushed automatically; call [`shutdown`] before exiting.
+///
+/// # Errors
+/// Returns error if tracing subscriber setup fails.
+#[deprecated(note = "use `init`, which returns an `ObsGuard` that flushes on drop")]
+pub fn init_tracing(config: &Config) -> anyhow::Result<ObsHandle> {
+    let guard = init(config)?;
+    let handle = guard.handle.clone();
+    // Keep the old behavior: everything stays installed until `shutdown`
+    std::mem::forget(guard);
+    Ok(handle)
 }
 
 /// Shutdown observability
 ///
-/// Flushes pending spans (relevant for OTEL).
+/// Flushes pending spans and metrics (relevant for OTEL) of a subscriber installed with
+/// the deprecated [`init_tracing`], and buffered `fast_log` records; [`ObsGuard`] does
+/// this on drop.
 pub fn shutdown() {
+    #[cfg(feature = "fast-log")]
+    flush_fast_log();
     #[cfg(feature = "otel")]
     {
         if let Some(provider) = OTEL_PROVIDER.get() {
             let _ = provider.shutdown();
         }
+        if let Some(provider) = OTEL_METER_PROVIDER.get() {
+            let _ = provider.shutdown();
+        }
     }
 }
 
-fn init_tracing_subscriber(config: &Config, env_filter: EnvFilter) -> anyhow::Result<()> {
-    // Console layer
-    let fmt_layer = tracing_subscriber::fmt::layer()
-        .with_target(config.logging.log_include_target)
-        .with_span_events(FmtSpan::NONE);
+/// Layer under the log filter (formatting, OpenTelemetry)
+type FilteredLayer = Box<dyn Layer<Registry> + Send + Sync>;
 
-    // Apply format
-    let registry = tracing_subscriber::registry().with(env_filter);
+/// Subscriber for the `tracing` backend and the guard owning its resources
+///
+/// Invalid filter directives are logged as a warning through the new
+/// subscriber.
+fn build_subscriber(
+    config: &Config,
+    #[cfg_attr(not(feature = "otel"), allow(unused_variables))] build: Option<&BuildInfo>,
+) -> anyhow::Result<(tracing::Dispatch, ObsGuard)> {
+    let (env_filter, rejected) = filter::env_filter(&filter::directives(config));
+    let (filter_layer, mut handle) = reloadable(env_filter);
+    handle.force_audit = config.audit.audit_log_force;
+    let mut guard = ObsGuard::new(handle);
 
-    match config.logging.log_format {
-        LogFormat::Pretty => {
-            let registry = registry.with(
-                fmt_layer
-                    .pretty()
-                    .with_file(config.logging.log_include_fileline)
-                    .with_line_number(config.logging.log_include_fileline),
-            );
+    // Output layers: stdout and/or rolling files
+    let mut layers: Vec<FilteredLayer> = Vec::new();
+    if config.logging.log_output.to_stdout() {
+        layers.push(fmt_layer(config, std::io::stdout, true));
+    }
+    if config.logging.log_output.to_file() {
+        let (writer, worker) = file::writer(&config.logging)?;
+        guard.keep_alive(worker);
+        layers.push(fmt_layer(config, writer, false));
+    }
 
-            #[cfg(feature = "otel")]
-            if config.features.feature_otel {
-                let otel_layer = init_otel_layer(config)?;
-                registry.with(otel_layer).try_init()?;
-                return Ok(());
-            }
+    #[cfg(feature = "otel")]
+    if config.features.feature_otel {
+        let (otel_layer, provider) = init_otel_layer(config, build)?;
+        guard.otel_provider = Some(provider);
+        layers.push(otel_layer.boxed());
+    }
 
-            registry.try_init()?;
-        }
-        LogFormat::Compact => {
-            let registry = registry.with(
-                fmt_layer
-                    .compact()
-                    .with_ansi(false)
-                    .with_file(config.logging.log_include_fileline)
-                    .with_line_number(config.logging.log_include_fileline),
-            );
+    // The filter applies per layer, so tokio-console still sees runtime events
+    let registry = tracing_subscriber::registry().with(layers.with_filter(filter_layer));
+    // A `None` layer would report every level enabled past the filter
+    let dispatch = match console_layer(config)? {
+        Some(console) => tracing::Dispatch::new(registry.with(console)),
+        None => tracing::Dispatch::new(registry),
+    };
+    warn_rejected(&dispatch, &rejected);
+    Ok((dispatch, guard))
+}
 
-            #[cfg(feature = "otel")]
-            if config.features.feature_otel {
-                let otel_layer = init_otel_layer(config)?;
-                registry.with(otel_layer).try_init()?;
-                return Ok(());
-            }
+/// tokio-console layer serving on `TOKIO_CONSOLE_BIND`, if enabled
+#[cfg(feature = "console")]
+fn console_layer<S>(config: &Config) -> anyhow::Result<Option<impl Layer<S>>>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use anyhow::Context;
 
-            registry.try_init()?;
-        }
-        LogFormat::Json => {
-            let registry = registry.with(
-                fmt_layer
-                    .json()
-                    .with_file(config.logging.log_include_fileline)
-                    .with_line_number(config.logging.log_include_fileline),
-            );
+    if !config.features.feature_tokio_console {
+        return Ok(None);
+    }
+    if !cfg!(tokio_unstable) {
+        anyhow::bail!(
+            "FEATURE_TOKIO_CONSOLE=true requires tokio's unstable instrumentation. Build with RUSTFLAGS=\"--cfg tokio_unstable\"."
+        );
+    }
+    let bind = &config.logging.tokio_console_bind;
+    let addr: std::net::SocketAddr = bind
+        .parse()
+        .with_context(|| format!("TOKIO_CONSOLE_BIND {bind:?} is not a socket address"))?;
+    Ok(Some(
+        console_subscriber::ConsoleLayer::builder()
+            .server_addr(addr)
+            .spawn(),
+    ))
+}
 
-            #[cfg(feature = "otel")]
-            if config.features.feature_otel {
-                let otel_layer = init_otel_layer(config)?;
-                registry.with(otel_layer).try_init()?;
-                return Ok(());
-            }
+#[cfg(not(feature = "console"))]
+fn console_layer(config: &Config) -> anyhow::Result<Option<tracing_subscriber::layer::Identity>> {
+    if config.features.feature_tokio_console {
+        anyhow::bail!(
+            "FEATURE_TOKIO_CONSOLE=true requires the \"console\" feature on barrzen-axum-obs"
+        );
+    }
+    Ok(None)
+}
 
-            registry.try_init()?;
-        }
+fn warn_rejected(dispatch: &tracing::Dispatch, rejected: &[String]) {
+    if !rejected.is_empty() {
+        tracing::dispatcher::with_default(dispatch, || {
+            tracing::warn!(rejected = ?rejected, "ignoring invalid log filter directives");
+        });
     }
+}
+
+/// Formatting layer in `LOG_FORMAT` writing to `writer`
+fn fmt_layer<W>(config: &Config, writer: W, ansi: bool) -> FilteredLayer
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_target(config.logging.log_include_target)
+        .with_span_events(FmtSpan::NONE)
+        .with_file(config.logging.log_include_fileline)
+        .with_line_number(config.logging.log_include_fileline);
 
-    Ok(())
+    match config.logging.log_format {
+        LogFormat::Pretty => layer.pretty().with_ansi(ansi).boxed(),
+        LogFormat::Compact => layer.compact().with_ansi(false).boxed(),
+        LogFormat::Json => layer.json().with_ansi(false).boxed(),
+    }
 }
 
 fn init_fast_log(config: &Config) -> anyhow::Result<()> {
     #[cfg(feature = "fast-log")]
     {
-        use fast_log::config::Config as FastLogConfig;
-
         if config.features.feature_otel {
             anyhow::bail!("LOG_BACKEND=fast_log is not compatible with FEATURE_OTEL=true");
         }
 
-        if let Err(err) = fast_log::init(FastLogConfig::new().console()) {
+        if let Err(err) = fast_log::init(fast_log_config(&config.logging)?) {
             let message = err.to_string();
             if message.contains("logging system was already initialized") {
-                anyhow::bail!("fast_log init failed because another logger is already set. Ensure init_tracing runs before any other logger initialization.");
+                anyhow::bail!(
+                    "fast_log init failed because another logger is already set. Ensure barrzen_axum_obs::init runs before any other logger initialization."
+                );
             }
             return Err(err.into());
         }
-        log::set_max_level(resolve_log_level(config));
-        return Ok(());
+        FAST_LOG_INSTALLED.store(true, std::sync::atomic::Ordering::SeqCst);
+        // fast_log has no per-module filter; the merged directives give one level
+        let directives = filter::directives(config);
+        let level = filter::global_level(&directives);
+        log::set_max_level(tracing_log::AsLog::as_log(&level));
+        // Forward `tracing` events (including the request log) to fast_log
+        let forwarder = log_bridge::LogForwarder::global().with_filter(level);
+        tracing::subscriber::set_global_default(Registry::default().with(forwarder))?;
+
+        let (_, rejected) = filter::env_filter(&directives);
+        if !rejected.is_empty() {
+            log::warn!(
+                "ignoring invalid log filter directives: {}",
+                rejected.join(",")
+            );
+        }
+        Ok(())
     }
 
     #[cfg(not(feature = "fast-log"))]
@@ -136,81 +428,665 @@ fn init_fast_log(config: &Config) -> anyhow::Result<()> {
     }
 }
 
+/// `fast_log` outputs for `LOG_OUTPUT`, rolling files like the `tracing` backend
 #[cfg(feature = "fast-log")]
-fn resolve_log_level(config: &Config) -> log::LevelFilter {
-    let mut level = parse_log_level(&config.logging.log_level).unwrap_or(log::LevelFilter::Info);
-
-    if let Ok(rust_log) = std::env::var("RUST_LOG") {
-        for directive in rust_log.split(',') {
-            let directive = directive.trim();
-            if directive.is_empty() {
-                continue;
-            }
-            let level_str = directive
-                .split_once('=')
-                .map_or(directive, |(_, level)| level);
-            if let Some(parsed) = parse_log_level(level_str) {
-                level = level.max(parsed);
+fn fast_log_config(
+    logging: &barrzen_axum_core::LoggingConfig,
+) -> anyhow::Result<fast_log::config::Config> {
+    use barrzen_axum_core::LogRotation;
+    use fast_log::{
+        consts::LogSize,
+        plugin::{
+            file_split::{DateType, KeepType, Rolling, RollingType},
+            packer::LogPacker,
+        },
+    };
+
+    let mut fast = fast_log::config::Config::new().chan_len(Some(logging.fast_log_chunk_size));
+    if logging.log_output.to_stdout() {
+        fast = fast.console();
+    }
+    if logging.log_output.to_file() {
+        let dir = std::path::Path::new(logging.log_file_dir.trim());
+        file::ensure_writable(dir)?;
+        let rolling = match logging.log_file_rotation {
+            LogRotation::Hourly => RollingType::ByDate(DateType::Hour),
+            LogRotation::Daily => RollingType::ByDate(DateType::Day),
+            LogRotation::Size => {
+                RollingType::BySize(LogSize::MB(usize::try_from(logging.log_file_max_size_mb)?))
             }
-        }
+        };
+        let keep = match logging.log_file_keep {
+            0 => KeepType::All,
+            keep => KeepType::KeepNum(i64::try_from(keep)?),
+        };
+        let path = dir.join(format!("{}.log", logging.log_file_prefix.trim()));
+        fast = fast.file_split(
+            &path.to_string_lossy(),
+            Rolling::new(rolling),
+            keep,
+            LogPacker {},
+        );
     }
+    Ok(fast)
+}
+
+/// Flushes `fast_log` when the [`ObsGuard`] is dropped
+#[cfg(feature = "fast-log")]
+struct FastLogFlush;
 
-    level
+#[cfg(feature = "fast-log")]
+impl Drop for FastLogFlush {
+    fn drop(&mut self) {
+        flush_fast_log();
+    }
 }
 
+/// Wait until `fast_log` has written every buffered record
 #[cfg(feature = "fast-log")]
-fn parse_log_level(value: &str) -> Option<log::LevelFilter> {
-    match value.trim().to_lowercase().as_str() {
-        "off" => Some(log::LevelFilter::Off),
-        "error" => Some(log::LevelFilter::Error),
-        "warn" | "warning" => Some(log::LevelFilter::Warn),
-        "info" => Some(log::LevelFilter::Info),
-        "debug" => Some(log::LevelFilter::Debug),
-        "trace" => Some(log::LevelFilter::Trace),
-        _ => None,
+fn flush_fast_log() {
+    if FAST_LOG_INSTALLED.load(std::sync::atomic::Ordering::SeqCst)
+        && let Ok(written) = fast_log::flush()
+    {
+        written.wait();
     }
 }
 
 // OpenTelemetry Setup
 
 #[cfg(feature = "otel")]
-fn init_otel_layer<S>(config: &Config) -> anyhow::Result<impl Layer<S>>
+fn init_otel_layer<S>(
+    config: &Config,
+    build: Option<&BuildInfo>,
+) -> anyhow::Result<(impl Layer<S>, opentelemetry_sdk::trace::SdkTracerProvider)>
 where
     S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
 {
-    use opentelemetry::{global, trace::TracerProvider as _};
+    use opentelemetry::global;
     use opentelemetry_otlp::WithExportConfig;
-    use opentelemetry_sdk::{propagation::TraceContextPropagator, trace as sdktrace, Resource};
-    use tracing_opentelemetry::OpenTelemetryLayer;
+    use opentelemetry_sdk::{propagation::TraceContextPropagator, trace as sdktrace};
 
     // Set global propagator
     global::set_text_map_propagator(TraceContextPropagator::new());
 
-    let app_name = &config.app.app_name;
-    let otel_endpoint = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
-        .unwrap_or_else(|_| "http://localhost:4317".to_string());
+    let otel = &config.otel;
+    let exporter = match otel.otel_exporter_otlp_protocol {
+        OtlpProtocol::Grpc => {
+            use opentelemetry_otlp::WithTonicConfig;
 
-    // OTEL 0.31: Use SpanExporter::builder().with_tonic()
-    let exporter = opentelemetry_otlp::SpanExporter::builder()
-        .with_tonic()
-        .with_endpoint(otel_endpoint)
-        .build()?;
+            opentelemetry_otlp::SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(otel.traces_endpoint())
+                .with_timeout(otel.exporter_timeout())
+                .with_metadata(grpc_metadata(otel)?)
+                .build()?
+        }
+        OtlpProtocol::HttpProtobuf => {
+            use opentelemetry_otlp::{Protocol, WithHttpConfig};
 
-    // OTEL 0.31: Use Resource::builder()
-    let resource = Resource::builder()
-        .with_service_name(app_name.clone())
-        .build();
+            opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_protocol(Protocol::HttpBinary)
+                .with_endpoint(otel.traces_endpoint())
+                .with_timeout(otel.exporter_timeout())
+                .with_headers(otel.exporter_headers().into_iter().collect())
+                .build()?
+        }
+    };
 
-    let provider = sdktrace::SdkTracerProvider::builder()
-        .with_batch_exporter(exporter)
-        .with_resource(resource)
+    let builder = sdktrace::SdkTracerProvider::builder().with_batch_exporter(exporter);
+    Ok(otel_layer(config, build, builder))
+}
+
+/// Meter provider exporting periodically to the OTLP endpoint of the traces
+#[cfg(feature = "otel")]
+fn init_meter_provider(
+    config: &Config,
+    build: Option<&BuildInfo>,
+) -> anyhow::Result<opentelemetry_sdk::metrics::SdkMeterProvider> {
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::metrics::{PeriodicReader, SdkMeterProvider};
+
+    let otel = &config.otel;
+    let exporter = match otel.otel_exporter_otlp_protocol {
+        OtlpProtocol::Grpc => {
+            use opentelemetry_otlp::WithTonicConfig;
+
+            opentelemetry_otlp::MetricExporter::builder()
+                .with_tonic()
+                .with_endpoint(otel.metrics_endpoint())
+                .with_timeout(otel.exporter_timeout())
+                .with_metadata(grpc_metadata(otel)?)
+                .build()?
+        }
+        OtlpProtocol::HttpProtobuf => {
+            use opentelemetry_otlp::{Protocol, WithHttpConfig};
+
+            opentelemetry_otlp::MetricExporter::builder()
+                .with_http()
+                .with_protocol(Protocol::HttpBinary)
+                .with_endpoint(otel.metrics_endpoint())
+                .with_timeout(otel.exporter_timeout())
+                .with_headers(otel.exporter_headers().into_iter().collect())
+                .build()?
+        }
+    };
+
+    Ok(SdkMeterProvider::builder()
+        .with_reader(PeriodicReader::builder(exporter).build())
+        .with_resource(resource(config, build))
+        .build())
+}
+
+/// `OTEL_EXPORTER_OTLP_HEADERS` as gRPC metadata
+///
+/// Errors name the header only; values are credentials.
+#[cfg(feature = "otel")]
+fn grpc_metadata(otel: &OtelConfig) -> anyhow::Result<tonic::metadata::MetadataMap> {
+    use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+
+    let mut metadata = MetadataMap::new();
+    for (name, value) in otel.exporter_headers() {
+        let key = MetadataKey::from_bytes(name.to_ascii_lowercase().as_bytes()).map_err(|_| {
+            anyhow::anyhow!("OTEL_EXPORTER_OTLP_HEADERS has an invalid header name {name:?}")
+        })?;
+        let value = MetadataValue::try_from(value.as_str()).map_err(|_| {
+            anyhow::anyhow!("OTEL_EXPORTER_OTLP_HEADERS has an invalid value for {name:?}")
+        })?;
+        metadata.insert(key, value);
+    }
+    Ok(metadata)
+}
+
+/// Finish the provider (resource, sampler) and wrap its tracer in a layer
+#[cfg(feature = "otel")]
+fn otel_layer<S>(
+    config: &Config,
+    build: Option<&BuildInfo>,
+    builder: opentelemetry_sdk::trace::TracerProviderBuilder,
+) -> (impl Layer<S>, opentelemetry_sdk::trace::SdkTracerProvider)
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    use opentelemetry::trace::TracerProvider as _;
+    use tracing_opentelemetry::OpenTelemetryLayer;
+
+    let provider = builder
+        .with_sampler(sampler(&config.otel))
+        .with_resource(resource(config, build))
         .build();
-    
-    // Set global provider
-    global::set_tracer_provider(provider.clone());
-    let _ = OTEL_PROVIDER.set(provider.clone());
 
     let tracer = provider.tracer("barrzen-axum");
 
-    Ok(OpenTelemetryLayer::new(tracer))
+    (OpenTelemetryLayer::new(tracer), provider)
+}
+
+/// Resource describing this service instance
+///
+/// `OTEL_RESOURCE_ATTRIBUTES` overrides the derived attributes, except
+/// `service.name`, which is always `APP_NAME`. Independent of `LOG_FORMAT`.
+#[cfg(feature = "otel")]
+fn resource(config: &Config, build: Option<&BuildInfo>) -> opentelemetry_sdk::Resource {
+    use opentelemetry::KeyValue;
+
+    let mut attributes = vec![
+        KeyValue::new("service.instance.id", instance_id()),
+        KeyValue::new("deployment.environment", config.app.app_env.to_string()),
+    ];
+    if let Some(build) = build {
+        attributes.push(KeyValue::new("service.version", build.version.clone()));
+    }
+    let extra = config
+        .otel
+        .resource_attributes()
+        .into_iter()
+        .map(|(key, value)| KeyValue::new(key, value));
+
+    // OTEL 0.31: Use Resource::builder(); later attributes win
+    opentelemetry_sdk::Resource::builder()
+        .with_attributes(attributes)
+        .with_attributes(extra)
+        .with_service_name(config.app.app_name.clone())
+        .build()
+}
+
+/// `HOSTNAME`, then `/etc/hostname`, else a random UUID
+#[cfg(feature = "otel")]
+fn instance_id() -> String {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/// `OTEL_TRACES_SAMPLER` / `OTEL_TRACES_SAMPLER_ARG` as an SDK sampler
+#[cfg(feature = "otel")]
+fn sampler(config: &OtelConfig) -> opentelemetry_sdk::trace::Sampler {
+    use opentelemetry_sdk::trace::Sampler;
+
+    let ratio = config.sampler_ratio();
+    match config.otel_traces_sampler {
+        TraceSampler::AlwaysOn => Sampler::AlwaysOn,
+        TraceSampler::AlwaysOff => Sampler::AlwaysOff,
+        TraceSampler::TraceIdRatio => Sampler::TraceIdRatioBased(ratio),
+        TraceSampler::ParentBasedTraceIdRatio => {
+            Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(ratio)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use barrzen_axum_core::{LogOutput, LogRotation};
+
+    /// Formatted log output shared with the test
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Capture {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+        }
+    }
+
+    #[test]
+    fn test_set_filter_enables_debug_events() {
+        let capture = Capture::default();
+        let (filter, handle) = reloadable(EnvFilter::new("info"));
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::registry().with(filter).with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone()),
+        );
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        tracing::debug!("before reload");
+        handle.set_filter("debug").unwrap();
+        tracing::debug!("after reload");
+
+        let output = capture.contents();
+        assert!(!output.contains("before reload"), "{output}");
+        assert!(output.contains("after reload"), "{output}");
+        assert_eq!(handle.current_filter().as_deref(), Some("debug"));
+
+        // Invalid directives leave the current filter in place
+        assert!(handle.set_filter("info,hyper=loud").is_err());
+        assert_eq!(handle.current_filter().as_deref(), Some("debug"));
+        assert!(LogLevelControl::set_filter(&handle, "warn").is_ok());
+        assert_eq!(LogLevelControl::current_filter(&handle), "warn");
+    }
+
+    #[test]
+    fn test_log_records_reach_tracing() {
+        let _ = log_bridge::install_log_tracer();
+        let capture = Capture::default();
+        let (filter, handle) = reloadable(EnvFilter::new("info"));
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::registry().with(filter).with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone()),
+        );
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        log::info!(target: "sea_orm", "from log");
+        tracing::info!("from tracing");
+        log::debug!("log before reload");
+        handle.set_filter("debug").unwrap();
+        log::debug!("log after reload");
+
+        let output = capture.contents();
+        assert!(output.contains("sea_orm: from log"), "{output}");
+        assert!(output.contains("from tracing"), "{output}");
+        assert!(!output.contains("log before reload"), "{output}");
+        assert!(output.contains("log after reload"), "{output}");
+    }
+
+    #[test]
+    fn test_handle_without_reload_support() {
+        let handle = ObsHandle::default();
+        assert!(handle.set_filter("debug").is_err());
+        assert_eq!(handle.current_filter(), None);
+    }
+
+    fn debug_config() -> Config {
+        Config::builder().log_level("debug").build()
+    }
+
+    /// Run `f` on another thread, failing if it takes too long
+    fn finishes_within(timeout: std::time::Duration, f: impl FnOnce() + Send + 'static) {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            f();
+            let _ = tx.send(());
+        });
+        assert!(rx.recv_timeout(timeout).is_ok(), "dropping ObsGuard hung");
+    }
+
+    #[test]
+    fn test_sequential_test_scoped_inits() {
+        for _ in 0..2 {
+            let guard = ObsGuard::try_init_for_test(&debug_config()).unwrap();
+            assert!(tracing::enabled!(tracing::Level::DEBUG));
+            guard.handle().set_filter("warn").unwrap();
+            assert!(!tracing::enabled!(tracing::Level::INFO));
+            drop(guard);
+        }
+        // The scope ended with the guard
+        assert!(!tracing::enabled!(tracing::Level::ERROR));
+    }
+
+    fn file_config(dir: &std::path::Path, format: LogFormat, rotation: LogRotation) -> Config {
+        Config::builder()
+            .with(|config| {
+                config.logging.log_output = LogOutput::File;
+                config.logging.log_file_dir = dir.display().to_string();
+                config.logging.log_file_prefix = "orders".to_string();
+                config.logging.log_file_rotation = rotation;
+                config.logging.log_format = format;
+            })
+            .build()
+    }
+
+    #[cfg(feature = "console")]
+    #[test]
+    fn test_console_layer_keeps_fmt_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = file_config(dir.path(), LogFormat::Compact, LogRotation::Daily);
+        config.features.feature_tokio_console = true;
+        config.logging.tokio_console_bind = "127.0.0.1:0".to_string();
+
+        let result = ObsGuard::try_init_for_test(&config);
+        if !cfg!(tokio_unstable) {
+            let error = result.err().unwrap().to_string();
+            assert!(error.contains("--cfg tokio_unstable"), "{error}");
+            return;
+        }
+        let guard = result.unwrap();
+        tracing::info!("still formatted");
+        drop(guard);
+
+        let contents: String = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect();
+        assert!(contents.contains("still formatted"), "{contents}");
+    }
+
+    #[test]
+    fn test_file_output_is_flushed_on_drop() {
+        for (format, rotation) in [
+            (LogFormat::Compact, LogRotation::Daily),
+            (LogFormat::Pretty, LogRotation::Hourly),
+            (LogFormat::Json, LogRotation::Size),
+        ] {
+            let dir = tempfile::tempdir().unwrap();
+            let guard =
+                ObsGuard::try_init_for_test(&file_config(dir.path(), format, rotation)).unwrap();
+            for n in 0..100 {
+                tracing::info!(n, "order placed");
+            }
+            tracing::warn!("last line");
+            drop(guard);
+
+            let contents: String = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+                .collect();
+            assert_eq!(contents.matches("order placed").count(), 100, "{format:?}");
+            assert!(contents.contains("last line"), "{format:?}: {contents}");
+            assert!(
+                !contents.contains('\u{1b}'),
+                "ANSI codes in {format:?} file"
+            );
+        }
+    }
+
+    #[test]
+    fn test_unwritable_log_dir_fails_init() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("not-a-dir");
+        std::fs::write(&file, "").unwrap();
+
+        let config = file_config(&file, LogFormat::Compact, LogRotation::Daily);
+        let Err(error) = ObsGuard::try_init_for_test(&config) else {
+            panic!("init should fail");
+        };
+        assert!(error.to_string().contains("LOG_FILE_DIR"), "{error}");
+    }
+
+    #[test]
+    fn test_fast_log_cannot_be_scoped() {
+        let mut config = debug_config();
+        config.logging.log_backend = LogBackend::FastLog;
+        assert!(ObsGuard::try_init_for_test(&config).is_err());
+    }
+
+    fn emit_spans() {
+        for id in 0..10 {
+            tracing::info_span!("request", id).in_scope(|| {});
+        }
+    }
+
+    #[test]
+    fn test_drop_after_spans_does_not_hang() {
+        finishes_within(std::time::Duration::from_secs(10), || {
+            let guard = ObsGuard::try_init_for_test(&debug_config()).unwrap();
+            emit_spans();
+            drop(guard);
+        });
+    }
+
+    #[cfg(feature = "otel")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_drop_flushes_otel_without_hanging() {
+        let config = Config::builder()
+            .log_level("debug")
+            .feature_otel(true)
+            .build();
+        let runtime = tokio::runtime::Handle::current();
+        // Nothing listens on the OTLP endpoint; shutdown must still return
+        finishes_within(std::time::Duration::from_secs(30), move || {
+            let _runtime = runtime.enter();
+            let guard = ObsGuard::try_init_for_test(&config).unwrap();
+            emit_spans();
+            drop(guard);
+        });
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_sampler_from_config() {
+        use opentelemetry_sdk::trace::Sampler;
+
+        let config = |sampler: TraceSampler, arg: &str| OtelConfig {
+            otel_traces_sampler: sampler,
+            otel_traces_sampler_arg: Some(arg.to_string()),
+            ..Config::default().otel
+        };
+
+        assert!(matches!(
+            sampler(&config(TraceSampler::AlwaysOn, "")),
+            Sampler::AlwaysOn
+        ));
+        assert!(matches!(
+            sampler(&config(TraceSampler::AlwaysOff, "")),
+            Sampler::AlwaysOff
+        ));
+        assert!(matches!(
+            sampler(&config(TraceSampler::TraceIdRatio, "0.25")),
+            Sampler::TraceIdRatioBased(ratio) if (ratio - 0.25).abs() < f64::EPSILON
+        ));
+        assert!(matches!(
+            sampler(&config(TraceSampler::ParentBasedTraceIdRatio, "0.5")),
+            Sampler::ParentBased(_)
+        ));
+        // Invalid ratios fall back to sampling everything
+        assert!(matches!(
+            sampler(&config(TraceSampler::TraceIdRatio, "1.5")),
+            Sampler::TraceIdRatioBased(ratio) if (ratio - 1.0).abs() < f64::EPSILON
+        ));
+    }
+
+    /// Spans exported to memory for `ratio`
+    #[cfg(feature = "otel")]
+    fn exported_spans(ratio: &str) -> usize {
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+        let mut config = Config::default();
+        config.otel.otel_traces_sampler_arg = Some(ratio.to_string());
+        let exporter = InMemorySpanExporter::default();
+        let builder = SdkTracerProvider::builder().with_simple_exporter(exporter.clone());
+        let (layer, provider) = otel_layer(&config, None, builder);
+
+        let subscriber = Registry::default().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..20 {
+                tracing::info_span!("request").in_scope(|| {
+                    tracing::info_span!("child").in_scope(|| {});
+                });
+            }
+        });
+        provider.force_flush().unwrap();
+        exporter.get_finished_spans().unwrap().len()
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_zero_ratio_exports_no_spans() {
+        assert_eq!(exported_spans("0.0"), 0);
+        assert_eq!(exported_spans("1.0"), 40);
+    }
+
+    #[cfg(feature = "otel")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_http_exporter_sends_headers() {
+        use wiremock::{
+            Mock, MockServer, ResponseTemplate,
+            matchers::{header, method, path},
+        };
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/traces"))
+            .and(header("authorization", "Bearer abc=="))
+            .and(header("content-type", "application/x-protobuf"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1..)
+            .mount(&server)
+            .await;
+
+        let config = Config::builder()
+            .feature_otel(true)
+            .with(|c| {
+                c.otel.otel_exporter_otlp_protocol = OtlpProtocol::HttpProtobuf;
+                c.otel.otel_exporter_otlp_endpoint = Some(server.uri());
+                c.otel.otel_exporter_otlp_headers =
+                    Some("authorization=Bearer abc==,x-tenant=acme".to_string());
+            })
+            .build();
+        // The blocking HTTP client must live outside the runtime
+        finishes_within(std::time::Duration::from_secs(30), move || {
+            let guard = ObsGuard::try_init_for_test(&config).unwrap();
+            emit_spans();
+            drop(guard);
+        });
+
+        server.verify().await;
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_resource_attributes() {
+        use opentelemetry::{Key, Value};
+
+        let config = Config::builder()
+            .app_name("orders-api")
+            .env(barrzen_axum_core::Environment::Stage)
+            .with(|c| {
+                c.otel.otel_resource_attributes =
+                    Some("team=payments,service.name=ignored,cloud.region=eu%2Dwest-1".to_string());
+            })
+            .build();
+        let build = BuildInfo::new("orders-api", "2.4.1", None, "1.85.0", None);
+
+        let attributes = resource(&config, Some(&build));
+        let get = |key: &'static str| attributes.get(&Key::from_static_str(key));
+        assert_eq!(get("service.name"), Some(Value::from("orders-api")));
+        assert_eq!(get("service.version"), Some(Value::from("2.4.1")));
+        assert_eq!(get("deployment.environment"), Some(Value::from("stage")));
+        assert_eq!(get("team"), Some(Value::from("payments")));
+        assert_eq!(get("cloud.region"), Some(Value::from("eu-west-1")));
+        assert!(get("service.instance.id").is_some_and(|id| !id.as_str().is_empty()));
+
+        // Without build info there is no version; extras may set the instance id
+        let mut config = config;
+        config.otel.otel_resource_attributes = Some("service.instance.id=pod-7".to_string());
+        let attributes = resource(&config, None);
+        assert_eq!(
+            attributes.get(&Key::from_static_str("service.version")),
+            None
+        );
+        assert_eq!(
+            attributes.get(&Key::from_static_str("service.instance.id")),
+            Some(Value::from("pod-7"))
+        );
+    }
+
+    #[cfg(feature = "otel")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_metrics_share_exporter_settings() {
+        use opentelemetry::metrics::MeterProvider as _;
+        use wiremock::{
+            Mock, MockServer, ResponseTemplate,
+            matchers::{header, method, path},
+        };
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/metrics"))
+            .and(header("authorization", "Bearer abc=="))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1..)
+            .mount(&server)
+            .await;
+
+        let config = Config::builder()
+            .feature_otel_metrics(true)
+            .with(|c| {
+                c.otel.otel_exporter_otlp_protocol = OtlpProtocol::HttpProtobuf;
+                c.otel.otel_exporter_otlp_endpoint = Some(server.uri());
+                c.otel.otel_exporter_otlp_headers = Some("authorization=Bearer abc==".to_string());
+            })
+            .build();
+        // The blocking HTTP client must live outside the runtime
+        finishes_within(std::time::Duration::from_secs(30), move || {
+            let provider = init_meter_provider(&config, None).unwrap();
+            let counter = provider.meter("test").u64_counter("jobs").build();
+            counter.add(1, &[]);
+            provider.shutdown().unwrap();
+        });
+
+        server.verify().await;
+    }
 }
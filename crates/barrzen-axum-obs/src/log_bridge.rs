@@ -0,0 +1,139 @@
+//! Bridges between the `log` and `tracing` facades
+//!
+//! Libraries such as `sea_orm` and `async_nats` log through `log`, while the
+//! kit itself uses `tracing`. Whichever backend is active, records from the
+//! other facade are forwarded instead of being dropped.
+
+use std::fmt::{self, Write as _};
+
+use tracing::{Event, Subscriber, field::Field};
+use tracing_log::{AsLog, LogTracer};
+use tracing_subscriber::layer::Context;
+
+/// Turn `log` records into tracing events (`LOG_BACKEND=tracing`)
+///
+/// All levels are forwarded and the subscriber's filter decides, so
+/// reloading the filter applies to `log` records too.
+pub(crate) fn install_log_tracer() -> anyhow::Result<()> {
+    LogTracer::init().map_err(|_| {
+        anyhow::anyhow!(
+            "forwarding `log` records to tracing failed because another logger is already set. Ensure barrzen_axum_obs::init runs before any other logger initialization."
+        )
+    })
+}
+
+/// Layer forwarding tracing events to a `log` logger (`LOG_BACKEND=fast_log`)
+///
+/// Fields other than the message are appended as `key=value` pairs.
+/// Events above the logger's level are skipped.
+pub(crate) struct LogForwarder {
+    logger: &'static dyn log::Log,
+}
+
+impl LogForwarder {
+    /// Forward to the global `log` logger
+    #[cfg_attr(not(feature = "fast-log"), allow(dead_code))]
+    pub(crate) fn global() -> Self {
+        Self {
+            logger: log::logger(),
+        }
+    }
+}
+
+impl<S: Subscriber> tracing_subscriber::Layer<S> for LogForwarder {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = metadata.level().as_log();
+        let log_metadata = log::Metadata::builder()
+            .level(level)
+            .target(metadata.target())
+            .build();
+        if level > log::max_level() || !self.logger.enabled(&log_metadata) {
+            return;
+        }
+
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+        self.logger.log(
+            &log::Record::builder()
+                .metadata(log_metadata)
+                .module_path(metadata.module_path())
+                .file(metadata.file())
+                .line(metadata.line())
+                .args(format_args!("{}{}", message.message, message.fields))
+                .build(),
+        );
+    }
+}
+
+/// Event message followed by ` key=value` pairs for the other fields
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl tracing::field::Visit for MessageVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={value}", field.name());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            let _ = write!(self.fields, " {}={value:?}", field.name());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use tracing_subscriber::{Layer, filter::LevelFilter, layer::SubscriberExt};
+
+    /// `log` logger keeping formatted records
+    struct Records(Mutex<Vec<String>>);
+
+    impl log::Log for Records {
+        fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+            metadata.level() <= log::Level::Info
+        }
+
+        fn log(&self, record: &log::Record<'_>) {
+            self.0.lock().unwrap().push(format!(
+                "{} {} {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_tracing_events_reach_log() {
+        // Normally set by the backend; the Records logger does the filtering
+        log::set_max_level(log::LevelFilter::Trace);
+        let records: &'static Records = Box::leak(Box::new(Records(Mutex::default())));
+        let subscriber = tracing_subscriber::registry()
+            .with(LogForwarder { logger: records }.with_filter(LevelFilter::DEBUG));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        tracing::info!(target: "request_log", status = 200, path = "/orders", "request completed");
+        tracing::debug!("skipped by the logger");
+        tracing::trace!("skipped by the filter");
+
+        assert_eq!(
+            *records.0.lock().unwrap(),
+            vec!["INFO request_log request completed status=200 path=/orders"]
+        );
+    }
+}
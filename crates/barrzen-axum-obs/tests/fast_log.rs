@@ -0,0 +1,30 @@
+//! `LOG_BACKEND=fast_log` writing to files
+//!
+//! `fast_log` installs the global `log` logger, so this runs in its own test
+//! binary.
+
+#![cfg(feature = "fast-log")]
+
+use barrzen_axum_core::{Config, LogBackend, LogOutput};
+
+#[test]
+fn test_fast_log_writes_both_facades_to_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.logging.log_backend = LogBackend::FastLog;
+    config.logging.log_output = LogOutput::File;
+    config.logging.log_file_dir = dir.path().display().to_string();
+    config.logging.log_file_prefix = "fast".to_string();
+
+    let guard = barrzen_axum_obs::init(&config).unwrap();
+    log::info!("from log");
+    tracing::info!(status = 200, "from tracing");
+    log::debug!("below the level");
+    barrzen_axum_obs::shutdown();
+
+    let contents = std::fs::read_to_string(dir.path().join("fast.log")).unwrap();
+    assert!(contents.contains("from log"), "{contents}");
+    assert!(contents.contains("from tracing status=200"), "{contents}");
+    assert!(!contents.contains("below the level"), "{contents}");
+    drop(guard);
+}
@@ -0,0 +1,290 @@
+//! Protection of the docs and spec routes
+//!
+//! Applied only to the routes mounted by this crate, so the API itself is
+//! unaffected.
+
+use std::{fmt, sync::Arc};
+
+use axum::{
+    extract::Request,
+    http::{HeaderMap, HeaderName, HeaderValue, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use barrzen_axum_core::{ApiError, Config, OpenApiAuth, response::extract_request_id};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use subtle::ConstantTimeEq;
+
+use crate::OpenApiError;
+
+/// `WWW-Authenticate` challenge of basic mode
+const BASIC_CHALLENGE: &str = r#"Basic realm="API docs", charset="UTF-8""#;
+
+/// Credentials required to read the docs and spec
+///
+/// `Debug` never prints the password or keys.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub enum DocsAuth {
+    /// Publicly readable
+    #[default]
+    None,
+    /// HTTP basic auth; failures get a `WWW-Authenticate` challenge
+    Basic { user: String, password: String },
+    /// One of `keys` in the `header` header; failures get a JSON 401
+    ApiKey {
+        header: HeaderName,
+        keys: Vec<String>,
+    },
+}
+
+impl DocsAuth {
+    /// Build from `OPENAPI_AUTH`
+    ///
+    /// Basic mode reads `OPENAPI_BASIC_USER`/`OPENAPI_BASIC_PASSWORD`, API key
+    /// mode reuses `AUTH_API_KEYS` and `AUTH_API_KEY_HEADER`.
+    ///
+    /// # Errors
+    /// Returns error if the selected mode has no credentials or the header
+    /// name is invalid.
+    pub fn from_config(config: &Config) -> Result<Self, OpenApiError> {
+        let openapi = &config.openapi;
+        match openapi.openapi_auth {
+            OpenApiAuth::None => Ok(Self::None),
+            OpenApiAuth::Basic => {
+                let (Some(user), Some(password)) = (
+                    openapi.openapi_basic_user.clone(),
+                    openapi.openapi_basic_password.clone(),
+                ) else {
+                    return Err(OpenApiError::Config(
+                        "OPENAPI_AUTH=basic requires OPENAPI_BASIC_USER and OPENAPI_BASIC_PASSWORD"
+                            .to_string(),
+                    ));
+                };
+                Ok(Self::Basic { user, password })
+            }
+            OpenApiAuth::ApiKey => {
+                let keys: Vec<_> = config
+                    .auth
+                    .api_keys()
+                    .into_iter()
+                    .map(|(_, key)| key)
+                    .collect();
+                if keys.is_empty() {
+                    return Err(OpenApiError::Config(
+                        "OPENAPI_AUTH=api_key requires AUTH_API_KEYS".to_string(),
+                    ));
+                }
+                let name = config.auth.auth_api_key_header.trim();
+                let header = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+                    OpenApiError::Config(format!(
+                        "AUTH_API_KEY_HEADER is not a valid header name: {name:?}"
+                    ))
+                })?;
+                Ok(Self::ApiKey { header, keys })
+            }
+        }
+    }
+
+    fn accepts(&self, headers: &HeaderMap) -> bool {
+        match self {
+            Self::None => true,
+            Self::Basic { user, password } => {
+                let Some((presented_user, presented_password)) = basic_credentials(headers) else {
+                    return false;
+                };
+                // Check both so timing does not reveal which one was wrong
+                let user_ok = user.as_bytes().ct_eq(presented_user.as_bytes());
+                let password_ok = password.as_bytes().ct_eq(presented_password.as_bytes());
+                bool::from(user_ok & password_ok)
+            }
+            Self::ApiKey { header, keys } => {
+                let Some(presented) = headers.get(header) else {
+                    return false;
+                };
+                // Compare against every key so timing does not reveal which matched
+                keys.iter().fold(false, |matched, key| {
+                    matched | bool::from(key.as_bytes().ct_eq(presented.as_bytes()))
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Debug for DocsAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::Basic { user, .. } => f
+                .debug_struct("Basic")
+                .field("user", user)
+                .field("password", &"****")
+                .finish(),
+            Self::ApiKey { header, keys } => f
+                .debug_struct("ApiKey")
+                .field("header", header)
+                .field("keys", &format!("{} key(s)", keys.len()))
+                .finish(),
+        }
+    }
+}
+
+/// `user:password` from an `Authorization: Basic` header
+fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, encoded) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+/// Middleware state guarding the docs routes
+#[derive(Clone)]
+pub(crate) struct DocsGuard {
+    auth: Arc<DocsAuth>,
+}
+
+impl DocsGuard {
+    /// Guard for `auth`, or `None` when the docs are public
+    pub(crate) fn new(auth: &DocsAuth) -> Option<Self> {
+        (*auth != DocsAuth::None).then(|| Self {
+            auth: Arc::new(auth.clone()),
+        })
+    }
+
+    /// Middleware rejecting requests without valid credentials
+    pub(crate) async fn require(self, req: Request, next: Next) -> Response {
+        if self.auth.accepts(req.headers()) {
+            return next.run(req).await;
+        }
+
+        let request_id = extract_request_id(req.headers());
+        // Never log the presented credentials
+        tracing::warn!(
+            request_id = request_id.as_deref().unwrap_or(""),
+            path = req.uri().path(),
+            "OpenAPI docs authentication failed"
+        );
+        let message = match *self.auth {
+            DocsAuth::Basic { .. } => "Invalid or missing credentials",
+            _ => "Invalid or missing API key",
+        };
+        let mut error = ApiError::unauthorized(message);
+        if let Some(request_id) = request_id {
+            error = error.with_request_id(request_id);
+        }
+        let mut response = error.into_response();
+        if matches!(*self.auth, DocsAuth::Basic { .. }) {
+            response.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(BASIC_CHALLENGE),
+            );
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mount_with_config;
+    use axum::{Router, body::Body, http::StatusCode};
+    use barrzen_axum_core::BuildInfo;
+    use tower::ServiceExt;
+    use utoipa::openapi::{Info, OpenApi, Paths};
+
+    fn app(config: &Config) -> Router {
+        let doc = OpenApi::new(Info::new("orders", "1.0.0"), Paths::new());
+        let build = BuildInfo::new("orders", "1.0.0", None, "1.85.0", None);
+        mount_with_config(Router::new(), doc, config, &build).unwrap()
+    }
+
+    fn basic_config() -> Config {
+        Config::builder()
+            .feature_openapi(true)
+            .with(|config| {
+                config.openapi.openapi_auth = OpenApiAuth::Basic;
+                config.openapi.openapi_basic_user = Some("docs".to_string());
+                config.openapi.openapi_basic_password = Some("s3cret".to_string());
+            })
+            .build()
+    }
+
+    async fn call(app: Router, uri: &str, header: Option<(&str, String)>) -> Response {
+        let mut request = axum::http::Request::get(uri);
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
+        }
+        app.oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    fn basic(user: &str, password: &str) -> (&'static str, String) {
+        let encoded = STANDARD.encode(format!("{user}:{password}"));
+        ("authorization", format!("Basic {encoded}"))
+    }
+
+    #[tokio::test]
+    async fn test_basic_auth() {
+        let app = app(&basic_config());
+        for uri in ["/docs/", "/openapi.json"] {
+            let response = call(app.clone(), uri, None).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{uri}");
+            assert_eq!(
+                response.headers()[header::WWW_AUTHENTICATE],
+                BASIC_CHALLENGE,
+                "{uri}"
+            );
+
+            let response = call(app.clone(), uri, Some(basic("docs", "wrong"))).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{uri}");
+
+            let response = call(app.clone(), uri, Some(basic("docs", "s3cret"))).await;
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_api_key_auth() {
+        let config = Config::builder()
+            .feature_openapi(true)
+            .with(|config| {
+                config.openapi.openapi_auth = OpenApiAuth::ApiKey;
+                config.auth.auth_api_keys = Some("ci:k1,k2".to_string());
+            })
+            .build();
+        let app = app(&config);
+        for uri in ["/docs/", "/openapi.json"] {
+            let response = call(app.clone(), uri, None).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{uri}");
+            assert!(response.headers().get(header::WWW_AUTHENTICATE).is_none());
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            assert_eq!(body["status"], "error");
+            assert_eq!(body["code"], 401);
+
+            let response = call(app.clone(), uri, Some(("x-api-key", "k3".to_string()))).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{uri}");
+
+            let response = call(app.clone(), uri, Some(("x-api-key", "k2".to_string()))).await;
+            assert_eq!(response.status(), StatusCode::OK, "{uri}");
+        }
+    }
+
+    #[test]
+    fn test_debug_redacts_credentials() {
+        let auth = DocsAuth::from_config(&basic_config()).unwrap();
+        let debug = format!("{auth:?}");
+        assert!(debug.contains("docs"), "{debug}");
+        assert!(!debug.contains("s3cret"), "{debug}");
+
+        let mut config = basic_config();
+        config.openapi.openapi_basic_password = None;
+        assert!(DocsAuth::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+//! Document details taken from the running service
+
+use barrzen_axum_core::{
+    BuildInfo, Config,
+    handlers::{HealthData, ReadyData, VersionData},
+};
+use utoipa::{
+    PartialSchema, ToSchema,
+    openapi::{
+        Components, Content, HttpMethod, KnownFormat, ObjectBuilder, OpenApi, Ref, RefOr,
+        ResponseBuilder, Schema, SchemaFormat, Server, Tag, Type, path::OperationBuilder,
+    },
+};
+
+/// Tag of the core endpoints
+const CORE_TAG: &str = "core";
+
+/// Fill the document from the configuration and build
+///
+/// - `info.title` is `APP_NAME` and `info.version` the build version; a
+///   description naming the package and git revision is added if missing
+/// - a server entry for `APP_BASE_PATH` (`/` without one) is added
+/// - `/healthz`, `/readyz` and `/version` (as configured by `CORE_*_PATH`)
+///   are documented with their schemas, enveloped when
+///   `FEATURE_RESPONSE_ENVELOPE=true`
+///
+/// Paths and schemas the document already has are kept.
+#[must_use]
+pub fn enrich(mut doc: OpenApi, config: &Config, build: &BuildInfo) -> OpenApi {
+    doc.info.title.clone_from(&config.app.app_name);
+    doc.info.version.clone_from(&build.version);
+    if doc.info.description.is_none() {
+        let revision = build
+            .git_sha
+            .as_deref()
+            .map(|sha| format!(" ({sha})"))
+            .unwrap_or_default();
+        doc.info.description = Some(format!("{} {}{revision}", build.name, build.version));
+    }
+
+    let server_url = config.app.base_path().unwrap_or_else(|| "/".to_string());
+    let servers = doc.servers.get_or_insert_with(Vec::new);
+    if !servers.iter().any(|server| server.url == server_url) {
+        servers.push(Server::new(server_url));
+    }
+
+    add_core_endpoints(&mut doc, config);
+    doc
+}
+
+fn add_core_endpoints(doc: &mut OpenApi, config: &Config) {
+    let envelope = config.features.feature_response_envelope;
+    let routes = &config.core_routes;
+    let version_path = routes
+        .version_path()
+        .filter(|_| config.features.feature_version_endpoint);
+    let endpoints = [
+        (
+            routes.healthz_path(),
+            "healthz",
+            "Liveness check",
+            HealthData::name(),
+        ),
+        (
+            routes.readyz_path(),
+            "readyz",
+            "Readiness of the service and its dependencies",
+            ReadyData::name(),
+        ),
+        (
+            version_path,
+            "version",
+            "Build and version info",
+            VersionData::name(),
+        ),
+    ];
+
+    let mut added = false;
+    for (path, operation_id, summary, schema) in endpoints {
+        let Some(path) = path else {
+            continue;
+        };
+        if doc.paths.get_path_item(&path).is_some() {
+            continue;
+        }
+
+        let body = if envelope {
+            enveloped(&schema)
+        } else {
+            Ref::from_schema_name(schema.as_ref()).into()
+        };
+        let mut operation = OperationBuilder::new()
+            .tag(CORE_TAG)
+            .operation_id(Some(operation_id))
+            .summary(Some(summary))
+            .response(
+                "200",
+                ResponseBuilder::new()
+                    .description(summary)
+                    .content("application/json", Content::new(Some(body.clone()))),
+            );
+        if operation_id == "readyz" {
+            operation = operation.response(
+                "503",
+                ResponseBuilder::new()
+                    .description("Shutting down, or unready with READYZ_STRICT=true")
+                    .content("application/json", Content::new(Some(body))),
+            );
+        }
+        doc.paths
+            .add_path_operation(&path, vec![HttpMethod::Get], operation.build());
+        added = true;
+    }
+    if !added {
+        return;
+    }
+
+    let mut schemas = vec![
+        (HealthData::name().into_owned(), HealthData::schema()),
+        (ReadyData::name().into_owned(), ReadyData::schema()),
+        (VersionData::name().into_owned(), VersionData::schema()),
+    ];
+    HealthData::schemas(&mut schemas);
+    ReadyData::schemas(&mut schemas);
+    VersionData::schemas(&mut schemas);
+    let components = doc.components.get_or_insert_with(Components::new);
+    for (name, schema) in schemas {
+        components.schemas.entry(name).or_insert(schema);
+    }
+
+    let tags = doc.tags.get_or_insert_with(Vec::new);
+    if !tags.iter().any(|tag| tag.name == CORE_TAG) {
+        tags.push(Tag::new(CORE_TAG));
+    }
+}
+
+/// `ApiResponse` envelope around a `data` schema
+fn enveloped(data: &str) -> RefOr<Schema> {
+    let string = || ObjectBuilder::new().schema_type(Type::String);
+    ObjectBuilder::new()
+        .property("status", string())
+        .required("status")
+        .property("code", ObjectBuilder::new().schema_type(Type::Integer))
+        .required("code")
+        .property("message", string())
+        .required("message")
+        .property(
+            "timestamp",
+            string().format(Some(SchemaFormat::KnownFormat(KnownFormat::DateTime))),
+        )
+        .required("timestamp")
+        .property("request_id", string())
+        .property(
+            "duration_ms",
+            ObjectBuilder::new().schema_type(Type::Integer),
+        )
+        .property("data", Ref::from_schema_name(data))
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utoipa::openapi::{Info, Paths};
+
+    fn build() -> BuildInfo {
+        BuildInfo::new(
+            "orders",
+            "2.4.1",
+            Some("abc1234".to_string()),
+            "1.85.0",
+            None,
+        )
+    }
+
+    #[test]
+    fn test_info_and_server_from_config() {
+        let config = Config::builder()
+            .app_name("orders-api")
+            .with(|config| config.app.app_base_path = "/api/orders/".to_string())
+            .build();
+        let doc = enrich(
+            OpenApi::new(Info::new("placeholder", "0.0.0"), Paths::new()),
+            &config,
+            &build(),
+        );
+
+        assert_eq!(doc.info.title, "orders-api");
+        assert_eq!(doc.info.version, "2.4.1");
+        assert_eq!(
+            doc.info.description.as_deref(),
+            Some("orders 2.4.1 (abc1234)")
+        );
+        let servers = doc.servers.unwrap();
+        assert_eq!(servers.len(), 1);
+        assert_eq!(servers[0].url, "/api/orders");
+    }
+
+    #[test]
+    fn test_core_endpoints_follow_config() {
+        let config = Config::builder()
+            .feature_version_endpoint(false)
+            .with(|config| config.core_routes.core_healthz_path = "/-/health".to_string())
+            .build();
+        let doc = enrich(
+            OpenApi::new(Info::new("orders", "1.0.0"), Paths::new()),
+            &config,
+            &build(),
+        );
+
+        assert!(doc.paths.get_path_item("/-/health").is_some());
+        assert!(doc.paths.get_path_item("/readyz").is_some());
+        assert!(doc.paths.get_path_item("/healthz").is_none());
+        assert!(doc.paths.get_path_item("/version").is_none());
+        let schemas = &doc.components.unwrap().schemas;
+        for name in ["HealthData", "ReadyData", "HealthCheck", "VersionData"] {
+            assert!(schemas.contains_key(name), "missing {name}");
+        }
+    }
+}
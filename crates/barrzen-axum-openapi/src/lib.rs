@@ -4,21 +4,215 @@
 
 use axum::Router;
 
+#[cfg(feature = "openapi")]
+mod auth;
+#[cfg(feature = "openapi")]
+mod enrich;
+#[cfg(feature = "openapi")]
+mod merge;
+#[cfg(feature = "openapi")]
+mod spec;
+#[cfg(feature = "openapi")]
+mod ui;
+
+#[cfg(feature = "openapi")]
+pub use auth::DocsAuth;
+#[cfg(feature = "openapi")]
+pub use enrich::enrich;
+#[cfg(feature = "openapi")]
+pub use merge::{OnConflict, merge_docs, merge_docs_with};
+#[cfg(feature = "openapi")]
+pub use spec::export_spec;
+#[cfg(feature = "openapi")]
+pub use ui::DocsUi;
+
+#[cfg(feature = "openapi")]
+use barrzen_axum_core::{BuildInfo, Config};
 #[cfg(feature = "openapi")]
 use utoipa::openapi::OpenApi;
+
+/// Errors from mounting the docs
+#[derive(Debug, thiserror::Error)]
+pub enum OpenApiError {
+    #[error("OpenAPI configuration error: {0}")]
+    Config(String),
+
+    #[error("Failed to merge OpenAPI documents: {0}")]
+    Merge(String),
+
+    #[error("Failed to export the OpenAPI spec to {path}: {source}")]
+    Export {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+/// Where and how the documentation is served
+#[cfg(feature = "openapi")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocsOptions {
+    /// UI served at `docs_path`
+    pub ui: DocsUi,
+    /// Path of the documentation UI (default `/docs`)
+    pub docs_path: String,
+    /// Path of the JSON specification (default `/openapi.json`); the YAML
+    /// one is served next to it (`/openapi.yaml`)
+    pub spec_path: String,
+    /// Credentials required for the UI and spec (default none)
+    pub auth: DocsAuth,
+}
+
 #[cfg(feature = "openapi")]
-use utoipa_swagger_ui::SwaggerUi;
+impl Default for DocsOptions {
+    fn default() -> Self {
+        Self {
+            ui: DocsUi::default(),
+            docs_path: "/docs".to_string(),
+            spec_path: "/openapi.json".to_string(),
+            auth: DocsAuth::None,
+        }
+    }
+}
 
 /// Mount OpenAPI routes onto a router
 ///
 /// Adds:
 /// - GET /docs - Swagger UI
 /// - GET /openapi.json - OpenAPI specification
+/// - GET /openapi.yaml - OpenAPI specification as YAML
 ///
 /// Use this in your application to expose documentation.
 #[cfg(feature = "openapi")]
 pub fn mount(router: Router<()>, doc: OpenApi) -> Router<()> {
-    router.merge(SwaggerUi::new("/docs").url("/openapi.json", doc))
+    mount_with(router, doc, &DocsOptions::default())
+}
+
+/// Mount OpenAPI routes with a chosen UI and paths
+///
+/// ```no_run
+/// use barrzen_axum_openapi::{DocsOptions, DocsUi, mount_with};
+/// # fn doc() -> utoipa::openapi::OpenApi { unimplemented!() }
+///
+/// let router = mount_with(
+///     axum::Router::new(),
+///     doc(),
+///     &DocsOptions { ui: DocsUi::Scalar, ..DocsOptions::default() },
+/// );
+/// ```
+#[cfg(feature = "openapi")]
+pub fn mount_with(router: Router<()>, doc: OpenApi, options: &DocsOptions) -> Router<()> {
+    mount_on(router, doc, options)
+}
+
+/// Mount OpenAPI routes onto a router of any state
+///
+/// Use this to add the docs to a `Router<CoreState>` passed to
+/// `AppBuilder::merge`: they then get the full middleware stack (request
+/// IDs, request log, CORS) and their own route in the metrics, instead of
+/// being a stateless fallback.
+///
+/// ```no_run
+/// use barrzen_axum_core::{AppBuilder, BuildInfo, Config, CoreState};
+/// use barrzen_axum_openapi::{DocsOptions, mount_on};
+/// # fn doc() -> utoipa::openapi::OpenApi { unimplemented!() }
+///
+/// let docs = mount_on(axum::Router::<CoreState>::new(), doc(), &DocsOptions::default());
+/// let app = AppBuilder::new(Config::builder().build(), BuildInfo::default())
+///     .merge(docs)
+///     .build();
+/// ```
+#[cfg(feature = "openapi")]
+pub fn mount_on<S>(router: Router<S>, doc: OpenApi, options: &DocsOptions) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    mount_routes(router, doc, options, &options.spec_path)
+}
+
+/// Mount OpenAPI routes as configured
+///
+/// `doc` is a single document or the result of [`merge_docs`]. It is first
+/// passed through [`enrich`], so the served spec carries `APP_NAME`, the
+/// build version and the core endpoints.
+///
+/// Reads `OPENAPI_DOCS_PATH` and `OPENAPI_SPEC_PATH`. The router is returned
+/// unchanged when `FEATURE_OPENAPI=false`, and when `APP_ENV=prod` unless
+/// `OPENAPI_ENABLE_IN_PROD=true`. Under `APP_BASE_PATH` the UI loads the
+/// spec from the prefixed path. Like [`mount_on`], this works on a router
+/// of any state.
+///
+/// `OPENAPI_AUTH=basic|api_key` protects the UI and spec, see [`DocsAuth`].
+///
+/// With `OPENAPI_EXPORT_PATH` set, the spec is first written to that file
+/// (see [`export_spec`]), whether or not the docs are served.
+///
+/// # Errors
+/// Returns error if a path is empty or both paths are the same, if
+/// `OPENAPI_AUTH` lacks its credentials, or if the export fails and
+/// `OPENAPI_EXPORT_REQUIRED=true`.
+#[cfg(feature = "openapi")]
+pub fn mount_with_config<S>(
+    router: Router<S>,
+    doc: OpenApi,
+    config: &Config,
+    build: &BuildInfo,
+) -> Result<Router<S>, OpenApiError>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let doc = enrich(doc, config, build);
+    spec::export_configured(&doc, &config.openapi)?;
+    if !config.openapi_enabled() {
+        if config.features.feature_openapi {
+            tracing::info!(
+                "OpenAPI docs disabled in prod, set OPENAPI_ENABLE_IN_PROD=true to serve them"
+            );
+        }
+        return Ok(router);
+    }
+
+    let openapi = &config.openapi;
+    let (Some(docs_path), Some(spec_path)) = (openapi.docs_path(), openapi.spec_path()) else {
+        return Err(OpenApiError::Config(
+            "OPENAPI_DOCS_PATH and OPENAPI_SPEC_PATH must not be empty".to_string(),
+        ));
+    };
+    if docs_path == spec_path {
+        return Err(OpenApiError::Config(format!(
+            "OPENAPI_DOCS_PATH and OPENAPI_SPEC_PATH must differ, both are {docs_path:?}"
+        )));
+    }
+
+    let spec_url = format!("{}{spec_path}", config.app.base_path().unwrap_or_default());
+    let options = DocsOptions {
+        ui: DocsUi::default(),
+        docs_path,
+        spec_path,
+        auth: DocsAuth::from_config(config)?,
+    };
+    Ok(mount_routes(router, doc, &options, &spec_url))
+}
+
+/// Spec and UI routes; the UI fetches the spec from `spec_url`
+#[cfg(feature = "openapi")]
+fn mount_routes<S>(
+    router: Router<S>,
+    doc: OpenApi,
+    options: &DocsOptions,
+    spec_url: &str,
+) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let mut docs = spec::spec_routes(doc, &options.spec_path)
+        .merge(options.ui.router(&options.docs_path, spec_url));
+    if let Some(guard) = auth::DocsGuard::new(&options.auth) {
+        docs = docs.layer(axum::middleware::from_fn(move |req, next| {
+            guard.clone().require(req, next)
+        }));
+    }
+    router.merge(docs)
 }
 
 /// No-op when openapi feature is disabled behavior depends on caller handling the feature flag
@@ -32,9 +226,270 @@ pub fn mount(router: Router<()>, doc: OpenApi) -> Router<()> {
 ///
 /// Recommending call site to use `#[cfg(feature = "openapi")]`.
 #[cfg(not(feature = "openapi"))]
-#[cfg(not(feature = "openapi"))]
-pub fn mount(_router: Router<()>, _doc: ()) -> Router<()> {
+pub fn mount(router: Router<()>, _doc: ()) -> Router<()> {
     // This signature is just a placeholder and unlikely to be used directly
     // because `doc` param would be difficult to provide.
-    _router
+    router
+}
+
+#[cfg(test)]
+#[cfg(feature = "openapi")]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode, header},
+    };
+    use barrzen_axum_core::{AppBuilder, CoreState, Environment};
+    use tower::ServiceExt;
+    use utoipa::openapi::{Info, Paths};
+
+    fn doc() -> OpenApi {
+        OpenApi::new(Info::new("orders", "1.2.3"), Paths::new())
+    }
+
+    fn build() -> BuildInfo {
+        BuildInfo::new("orders", "2.0.0", None, "1.85.0", None)
+    }
+
+    async fn get_text(app: Router, uri: &str) -> (StatusCode, String, String) {
+        let response = app
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            content_type,
+            String::from_utf8_lossy(&body).to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_each_ui_serves_its_bundle_and_the_spec() {
+        for (ui, docs_uri, bundle) in [
+            (DocsUi::SwaggerUi, "/docs/", "swagger-ui-bundle.js"),
+            (DocsUi::Redoc, "/docs", "redoc.standalone.js"),
+            (DocsUi::Scalar, "/docs", "@scalar/api-reference"),
+            (DocsUi::RapiDoc, "/docs", "rapidoc-min.js"),
+        ] {
+            let app = mount_with(
+                Router::new(),
+                doc(),
+                &DocsOptions {
+                    ui,
+                    ..DocsOptions::default()
+                },
+            );
+
+            let (status, content_type, html) = get_text(app.clone(), docs_uri).await;
+            assert_eq!(status, StatusCode::OK, "{ui:?}");
+            assert!(
+                content_type.starts_with("text/html"),
+                "{ui:?}: {content_type}"
+            );
+            assert!(html.contains(bundle), "{ui:?}: {html}");
+
+            let (status, content_type, spec) = get_text(app, "/openapi.json").await;
+            assert_eq!(status, StatusCode::OK, "{ui:?}");
+            assert_eq!(content_type, "application/json", "{ui:?}");
+            let spec: serde_json::Value = serde_json::from_str(&spec).unwrap();
+            assert_eq!(spec["info"]["version"], "1.2.3", "{ui:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_paths() {
+        let app = mount_with(
+            Router::new(),
+            doc(),
+            &DocsOptions {
+                ui: DocsUi::Redoc,
+                docs_path: "/reference".to_string(),
+                spec_path: "/spec.json".to_string(),
+                ..DocsOptions::default()
+            },
+        );
+
+        let (status, _, html) = get_text(app.clone(), "/reference").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(r#"spec-url="/spec.json""#));
+        let (status, _, _) = get_text(app.clone(), "/spec.json").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = get_text(app, "/openapi.json").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_mount_keeps_swagger_defaults() {
+        let app = mount(Router::new(), doc());
+        let (status, _, html) = get_text(app.clone(), "/docs/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("swagger-ui"));
+        let (status, _, _) = get_text(app, "/openapi.json").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_config_paths_and_base_path() {
+        let config = Config::builder()
+            .feature_openapi(true)
+            .with(|config| {
+                config.app.app_base_path = "/api".to_string();
+                config.openapi.openapi_docs_path = "/reference/".to_string();
+                config.openapi.openapi_spec_path = "spec.json".to_string();
+            })
+            .build();
+        let app = mount_with_config(Router::new(), doc(), &config, &build()).unwrap();
+
+        let (status, _, _) = get_text(app.clone(), "/reference/").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = get_text(app.clone(), "/spec.json").await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _, _) = get_text(app, "/docs/").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_mount_on_app_builder_gets_middleware() {
+        let docs = mount_on(Router::<CoreState>::new(), doc(), &DocsOptions::default());
+        let app = AppBuilder::new(Config::builder().build(), build())
+            .merge(docs)
+            .build();
+
+        let response = app
+            .oneshot(Request::get("/openapi.json").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().contains_key("x-request-id"));
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["openapi"], "3.1.0");
+    }
+
+    #[tokio::test]
+    async fn test_config_serves_enriched_spec() {
+        let config = Config::builder()
+            .feature_openapi(true)
+            .app_name("orders-api")
+            .build();
+        let app = mount_with_config(Router::new(), doc(), &config, &build()).unwrap();
+
+        let (status, _, spec) = get_text(app, "/openapi.json").await;
+        assert_eq!(status, StatusCode::OK);
+        let spec: serde_json::Value = serde_json::from_str(&spec).unwrap();
+        assert_eq!(spec["info"]["title"], "orders-api");
+        assert_eq!(spec["info"]["version"], "2.0.0");
+        assert!(spec["paths"]["/healthz"]["get"].is_object(), "{spec}");
+        assert!(spec["paths"]["/readyz"]["get"].is_object(), "{spec}");
+        assert!(
+            spec["components"]["schemas"]["HealthData"].is_object(),
+            "{spec}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_config_runtime_flag_off() {
+        let config = Config::builder().feature_openapi(false).build();
+        let app = mount_with_config(Router::new(), doc(), &config, &build()).unwrap();
+        let (status, _, _) = get_text(app, "/openapi.json").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_config_disabled_in_prod() {
+        let config = Config::builder()
+            .feature_openapi(true)
+            .env(Environment::Prod)
+            .build();
+        let app = mount_with_config(Router::new(), doc(), &config, &build()).unwrap();
+        let (status, _, _) = get_text(app, "/openapi.json").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let config = Config::builder()
+            .feature_openapi(true)
+            .env(Environment::Prod)
+            .with(|config| config.openapi.openapi_enable_in_prod = true)
+            .build();
+        let app = mount_with_config(Router::new(), doc(), &config, &build()).unwrap();
+        let (status, _, _) = get_text(app, "/openapi.json").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[test]
+    fn test_config_rejects_same_paths() {
+        let config = Config::builder()
+            .feature_openapi(true)
+            .with(|config| config.openapi.openapi_spec_path = "/docs".to_string())
+            .build();
+        let error = mount_with_config(Router::<()>::new(), doc(), &config, &build()).unwrap_err();
+        assert!(error.to_string().contains("must differ"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_yaml_spec_round_trips() {
+        let app = mount(Router::new(), doc());
+        let (status, content_type, yaml) = get_text(app, "/openapi.yaml").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(content_type, "application/yaml");
+        let parsed: OpenApi = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(
+            serde_json::to_value(parsed).unwrap(),
+            serde_json::to_value(doc()).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exported_spec_matches_served_spec() {
+        let dir = tempfile::tempdir().unwrap();
+        let json_path = dir.path().join("openapi.json");
+        let yaml_path = dir.path().join("openapi.yml");
+        let config = Config::builder()
+            .feature_openapi(true)
+            .with(|config| {
+                config.openapi.openapi_export_path = Some(json_path.display().to_string());
+            })
+            .build();
+        let app = mount_with_config(Router::new(), doc(), &config, &build()).unwrap();
+        export_spec(&enrich(doc(), &config, &build()), &yaml_path).unwrap();
+
+        let (_, _, served) = get_text(app, "/openapi.json").await;
+        let served: serde_json::Value = serde_json::from_str(&served).unwrap();
+        let exported = std::fs::read_to_string(&json_path).unwrap();
+        let exported: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        assert_eq!(exported, served);
+
+        let exported = std::fs::read_to_string(&yaml_path).unwrap();
+        let exported: serde_json::Value = serde_yaml::from_str(&exported).unwrap();
+        assert_eq!(exported, served);
+    }
+
+    #[test]
+    fn test_export_failure_is_fatal_only_when_required() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing").join("openapi.json");
+        let mut config = Config::builder()
+            .feature_openapi(true)
+            .with(|config| {
+                config.openapi.openapi_export_path = Some(missing.display().to_string());
+            })
+            .build();
+        assert!(mount_with_config(Router::<()>::new(), doc(), &config, &build()).is_ok());
+
+        config.openapi.openapi_export_required = true;
+        let error = mount_with_config(Router::<()>::new(), doc(), &config, &build()).unwrap_err();
+        assert!(matches!(error, OpenApiError::Export { .. }), "{error}");
+    }
 }
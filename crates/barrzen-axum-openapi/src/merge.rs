@@ -0,0 +1,334 @@
+//! Merging the documents of several router modules
+
+use std::collections::BTreeMap;
+
+use utoipa::openapi::{OpenApi, PathItem, path::Operation};
+
+use crate::OpenApiError;
+
+/// What to do when two documents define a schema with the same name
+/// differently
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnConflict {
+    /// Fail the merge
+    #[default]
+    Error,
+    /// Keep the first definition and suffix the later one (`Error_2`),
+    /// rewriting that document's references to it
+    Rename,
+}
+
+/// Merge documents into one, failing on conflicting definitions
+///
+/// Info and external docs come from the first document. Servers, paths,
+/// schemas, responses, security schemes, security requirements and tags are
+/// merged; identical duplicates are kept once.
+///
+/// # Errors
+/// Returns error if `docs` is empty, if the same method on a path is
+/// documented differently, or if a schema, response or security scheme name
+/// has different definitions.
+pub fn merge_docs(docs: Vec<OpenApi>) -> Result<OpenApi, OpenApiError> {
+    merge_docs_with(docs, OnConflict::Error)
+}
+
+/// Merge documents into one, resolving schema conflicts per `on_conflict`
+///
+/// Only schemas can be renamed; conflicting operations, responses and
+/// security schemes are always an error.
+///
+/// # Errors
+/// See [`merge_docs`].
+pub fn merge_docs_with(
+    docs: Vec<OpenApi>,
+    on_conflict: OnConflict,
+) -> Result<OpenApi, OpenApiError> {
+    let mut docs = docs.into_iter();
+    let Some(mut merged) = docs.next() else {
+        return Err(OpenApiError::Merge("no documents to merge".to_string()));
+    };
+
+    for doc in docs {
+        let mut doc = rename_schemas(&merged, doc, on_conflict)?;
+        check_paths(&merged, &doc)?;
+        check_components(&merged, &doc)?;
+
+        // Tags are matched by name so a differing description is not added twice
+        if let (Some(tags), Some(other)) = (merged.tags.as_ref(), doc.tags.as_mut()) {
+            other.retain(|tag| !tags.iter().any(|existing| existing.name == tag.name));
+        }
+        merged.merge(doc);
+    }
+    Ok(merged)
+}
+
+/// Fail when a path and method is documented differently
+fn check_paths(merged: &OpenApi, doc: &OpenApi) -> Result<(), OpenApiError> {
+    for (path, item) in &doc.paths.paths {
+        let Some(existing) = merged.paths.paths.get(path) else {
+            continue;
+        };
+        for (method, existing, other) in operations(existing, item) {
+            if let (Some(existing), Some(other)) = (existing, other)
+                && existing != other
+            {
+                return Err(OpenApiError::Merge(format!(
+                    "{method} {path} is documented differently in {:?} and {:?}",
+                    merged.info.title, doc.info.title
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+type OperationPair<'a> = (&'static str, Option<&'a Operation>, Option<&'a Operation>);
+
+fn operations<'a>(a: &'a PathItem, b: &'a PathItem) -> [OperationPair<'a>; 8] {
+    [
+        ("GET", a.get.as_ref(), b.get.as_ref()),
+        ("PUT", a.put.as_ref(), b.put.as_ref()),
+        ("POST", a.post.as_ref(), b.post.as_ref()),
+        ("DELETE", a.delete.as_ref(), b.delete.as_ref()),
+        ("OPTIONS", a.options.as_ref(), b.options.as_ref()),
+        ("HEAD", a.head.as_ref(), b.head.as_ref()),
+        ("PATCH", a.patch.as_ref(), b.patch.as_ref()),
+        ("TRACE", a.trace.as_ref(), b.trace.as_ref()),
+    ]
+}
+
+/// Fail when a component name has different definitions
+fn check_components(merged: &OpenApi, doc: &OpenApi) -> Result<(), OpenApiError> {
+    let (Some(existing), Some(other)) = (merged.components.as_ref(), doc.components.as_ref())
+    else {
+        return Ok(());
+    };
+    let conflict = first_conflict(&existing.schemas, &other.schemas)
+        .map(|name| ("schema", name))
+        .or_else(|| {
+            first_conflict(&existing.responses, &other.responses).map(|name| ("response", name))
+        })
+        .or_else(|| {
+            first_conflict(&existing.security_schemes, &other.security_schemes)
+                .map(|name| ("security scheme", name))
+        });
+    match conflict {
+        Some((kind, name)) => Err(OpenApiError::Merge(format!(
+            "{kind} {name:?} is defined differently in {:?} and {:?}",
+            merged.info.title, doc.info.title
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn first_conflict<'a, V: PartialEq>(
+    existing: &BTreeMap<String, V>,
+    other: &'a BTreeMap<String, V>,
+) -> Option<&'a str> {
+    other
+        .iter()
+        .find(|(name, value)| {
+            existing
+                .get(*name)
+                .is_some_and(|existing| existing != *value)
+        })
+        .map(|(name, _)| name.as_str())
+}
+
+/// With [`OnConflict::Rename`], give conflicting schemas of `doc` a free
+/// `<name>_<n>` name and point its references at it
+fn rename_schemas(
+    merged: &OpenApi,
+    doc: OpenApi,
+    on_conflict: OnConflict,
+) -> Result<OpenApi, OpenApiError> {
+    if on_conflict != OnConflict::Rename {
+        return Ok(doc);
+    }
+    let (Some(existing), Some(other)) = (merged.components.as_ref(), doc.components.as_ref())
+    else {
+        return Ok(doc);
+    };
+
+    let mut renames = BTreeMap::new();
+    for (name, schema) in &other.schemas {
+        if existing
+            .schemas
+            .get(name)
+            .is_none_or(|current| current == schema)
+        {
+            continue;
+        }
+        // Each name in either document rules out at most one suffix
+        let Some(new_name) = (2..=existing.schemas.len() + other.schemas.len() + 2)
+            .map(|n| format!("{name}_{n}"))
+            .find(|candidate| {
+                // A schema renamed the same way by an earlier document is reused
+                existing
+                    .schemas
+                    .get(candidate)
+                    .is_none_or(|current| current == schema)
+                    && !other.schemas.contains_key(candidate)
+            })
+        else {
+            return Err(OpenApiError::Merge(format!(
+                "no free name for schema {name}"
+            )));
+        };
+        renames.insert(name.clone(), new_name);
+    }
+    if renames.is_empty() {
+        return Ok(doc);
+    }
+
+    let invalid = |error: serde_json::Error| OpenApiError::Merge(error.to_string());
+    let mut value = serde_json::to_value(&doc).map_err(invalid)?;
+    if let Some(schemas) = value
+        .pointer_mut("/components/schemas")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        for (name, renamed) in &renames {
+            if let Some(schema) = schemas.remove(name) {
+                schemas.insert(renamed.clone(), schema);
+            }
+        }
+    }
+    let refs = renames
+        .iter()
+        .map(|(name, renamed)| {
+            (
+                format!("#/components/schemas/{name}"),
+                format!("#/components/schemas/{renamed}"),
+            )
+        })
+        .collect();
+    rewrite_refs(&mut value, &refs);
+    serde_json::from_value(value).map_err(invalid)
+}
+
+/// Replace `$ref` targets found in `refs`, anywhere in the document
+fn rewrite_refs(value: &mut serde_json::Value, refs: &BTreeMap<String, String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    serde_json::Value::String(target) if key == "$ref" => {
+                        if let Some(renamed) = refs.get(target.as_str()) {
+                            target.clone_from(renamed);
+                        }
+                    }
+                    _ => rewrite_refs(value, refs),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                rewrite_refs(item, refs);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utoipa::openapi::{
+        ComponentsBuilder, Content, HttpMethod, Info, ObjectBuilder, Paths, Ref, ResponseBuilder,
+        Type, path::OperationBuilder,
+    };
+
+    /// Document with `GET <path>` returning the `Error` schema
+    fn doc(title: &str, path: &str, error_field: &str) -> OpenApi {
+        let mut paths = Paths::new();
+        paths.add_path_operation(
+            path,
+            vec![HttpMethod::Get],
+            OperationBuilder::new()
+                .response(
+                    "400",
+                    ResponseBuilder::new().description("Bad request").content(
+                        "application/json",
+                        Content::new(Some(Ref::from_schema_name("Error"))),
+                    ),
+                )
+                .build(),
+        );
+        let mut doc = OpenApi::new(Info::new(title, "1.0.0"), paths);
+        doc.components = Some(
+            ComponentsBuilder::new()
+                .schema(
+                    "Error",
+                    ObjectBuilder::new()
+                        .property(error_field, ObjectBuilder::new().schema_type(Type::String)),
+                )
+                .build(),
+        );
+        doc
+    }
+
+    fn error_ref(doc: &OpenApi, path: &str) -> String {
+        let doc = serde_json::to_value(doc).unwrap();
+        let pointer = format!(
+            "/paths/{}/get/responses/400/content/application~1json/schema/$ref",
+            path.replace('/', "~1")
+        );
+        doc.pointer(&pointer).unwrap().as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_identical_schemas_are_deduplicated() {
+        let merged = merge_docs(vec![
+            doc("users", "/users", "message"),
+            doc("billing", "/invoices", "message"),
+        ])
+        .unwrap();
+
+        assert_eq!(merged.info.title, "users");
+        assert!(merged.paths.get_path_item("/users").is_some());
+        assert!(merged.paths.get_path_item("/invoices").is_some());
+        let schemas = &merged.components.unwrap().schemas;
+        assert_eq!(schemas.keys().collect::<Vec<_>>(), vec!["Error"]);
+    }
+
+    #[test]
+    fn test_conflicting_schemas() {
+        let docs = || {
+            vec![
+                doc("users", "/users", "message"),
+                doc("billing", "/invoices", "reason"),
+            ]
+        };
+
+        let Err(error) = merge_docs(docs()) else {
+            panic!("conflicting schemas were merged");
+        };
+        let error = error.to_string();
+        assert!(error.contains(r#"schema "Error""#), "{error}");
+        assert!(error.contains(r#""users" and "billing""#), "{error}");
+
+        let merged = merge_docs_with(docs(), OnConflict::Rename).unwrap();
+        assert_eq!(error_ref(&merged, "/users"), "#/components/schemas/Error");
+        assert_eq!(
+            error_ref(&merged, "/invoices"),
+            "#/components/schemas/Error_2"
+        );
+        let schemas = &merged.components.unwrap().schemas;
+        assert_eq!(schemas.keys().collect::<Vec<_>>(), vec!["Error", "Error_2"]);
+    }
+
+    #[test]
+    fn test_conflicting_operations_and_empty_input() {
+        let mut admin = doc("admin", "/users", "message");
+        if let Some(item) = admin.paths.paths.get_mut("/users") {
+            item.get.as_mut().unwrap().summary = Some("List users".to_string());
+        }
+        let Err(error) = merge_docs(vec![doc("users", "/users", "message"), admin]) else {
+            panic!("conflicting operations were merged");
+        };
+        let error = error.to_string();
+        assert!(error.contains("GET /users"), "{error}");
+
+        assert!(merge_docs(Vec::new()).is_err());
+    }
+}
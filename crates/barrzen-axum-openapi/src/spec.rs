@@ -0,0 +1,113 @@
+//! Specification routes and export
+
+use std::{io, path::Path, sync::Arc};
+
+use axum::{
+    Extension, Json, Router,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use barrzen_axum_core::SkipEnvelope;
+use utoipa::openapi::OpenApi;
+
+use crate::OpenApiError;
+
+/// YAML route next to the JSON one: `/openapi.json` -> `/openapi.yaml`
+pub(crate) fn yaml_path(spec_path: &str) -> String {
+    let stem = spec_path.strip_suffix(".json").unwrap_or(spec_path);
+    format!("{stem}.yaml")
+}
+
+/// JSON spec at `spec_path` and YAML spec at [`yaml_path`]
+///
+/// Both are marked [`SkipEnvelope`]: clients and code generators expect the
+/// bare document, also when the docs go through `AppBuilder::merge`.
+pub(crate) fn spec_routes<S>(doc: OpenApi, spec_path: &str) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let doc = Arc::new(doc);
+    let yaml_doc = Arc::clone(&doc);
+    Router::new()
+        .route(
+            spec_path,
+            get(move || async move { (Extension(SkipEnvelope), Json(&*doc)).into_response() }),
+        )
+        .route(
+            &yaml_path(spec_path),
+            get(move || async move { yaml_response(&yaml_doc) }),
+        )
+}
+
+fn yaml_response(doc: &OpenApi) -> Response {
+    match serde_yaml::to_string(doc) {
+        Ok(body) => (
+            Extension(SkipEnvelope),
+            [(header::CONTENT_TYPE, "application/yaml")],
+            body,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to serialize the OpenAPI spec as YAML: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Write the spec to `path`: YAML for `.yaml`/`.yml`, pretty JSON otherwise
+///
+/// # Errors
+/// Returns error if the spec cannot be serialized or the file written.
+pub fn export_spec(doc: &OpenApi, path: impl AsRef<Path>) -> Result<(), OpenApiError> {
+    let path = path.as_ref();
+    let yaml = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"));
+    let contents = if yaml {
+        serde_yaml::to_string(doc).map_err(io::Error::other)
+    } else {
+        doc.to_pretty_json().map_err(io::Error::other)
+    };
+    contents
+        .and_then(|contents| std::fs::write(path, contents))
+        .map_err(|source| OpenApiError::Export {
+            path: path.display().to_string(),
+            source,
+        })
+}
+
+/// Export to `OPENAPI_EXPORT_PATH`, if set
+///
+/// Failures are logged unless `OPENAPI_EXPORT_REQUIRED=true`.
+pub(crate) fn export_configured(
+    doc: &OpenApi,
+    config: &barrzen_axum_core::OpenApiConfig,
+) -> Result<(), OpenApiError> {
+    let Some(path) = config.openapi_export_path.as_deref() else {
+        return Ok(());
+    };
+    match export_spec(doc, path) {
+        Ok(()) => {
+            tracing::info!(path, "OpenAPI spec exported");
+            Ok(())
+        }
+        Err(e) if config.openapi_export_required => Err(e),
+        Err(e) => {
+            tracing::warn!("{e}");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_yaml_path() {
+        assert_eq!(yaml_path("/openapi.json"), "/openapi.yaml");
+        assert_eq!(yaml_path("/spec"), "/spec.yaml");
+    }
+}
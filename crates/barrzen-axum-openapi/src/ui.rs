@@ -0,0 +1,122 @@
+//! Documentation UIs
+//!
+//! Swagger UI is served from the assets embedded by `utoipa-swagger-ui`.
+//! Redoc, Scalar and RapiDoc are small HTML shells that load their bundle
+//! from a CDN, so they need no extra cargo feature.
+
+use axum::{Router, response::Html, routing::get};
+use utoipa_swagger_ui::SwaggerUi;
+
+/// Documentation UI served at the docs path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DocsUi {
+    /// Swagger UI from embedded assets
+    #[default]
+    SwaggerUi,
+    /// Redoc from `cdn.redoc.ly`
+    Redoc,
+    /// Scalar API reference from `cdn.jsdelivr.net`
+    Scalar,
+    /// RapiDoc from `unpkg.com`
+    RapiDoc,
+}
+
+impl DocsUi {
+    /// Router serving this UI at `docs_path`, reading the spec from `spec_url`
+    pub(crate) fn router<S>(self, docs_path: &str, spec_url: &str) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let shell = match self {
+            Self::SwaggerUi => {
+                let swagger = SwaggerUi::new(docs_path.to_string())
+                    .config(utoipa_swagger_ui::Config::from(spec_url.to_string()));
+                return Router::from(swagger);
+            }
+            Self::Redoc => redoc_html(spec_url),
+            Self::Scalar => scalar_html(spec_url),
+            Self::RapiDoc => rapidoc_html(spec_url),
+        };
+        Router::new().route(docs_path, get(move || async move { Html(shell) }))
+    }
+}
+
+const REDOC_BUNDLE: &str = "https://cdn.redoc.ly/redoc/latest/bundles/redoc.standalone.js";
+const SCALAR_BUNDLE: &str = "https://cdn.jsdelivr.net/npm/@scalar/api-reference";
+const RAPIDOC_BUNDLE: &str = "https://unpkg.com/rapidoc/dist/rapidoc-min.js";
+
+fn redoc_html(spec_url: &str) -> String {
+    page(
+        "Redoc",
+        &format!(
+            r#"<redoc spec-url="{}"></redoc>
+    <script src="{REDOC_BUNDLE}"></script>"#,
+            escape_attr(spec_url)
+        ),
+    )
+}
+
+fn scalar_html(spec_url: &str) -> String {
+    page(
+        "Scalar",
+        &format!(
+            r#"<script id="api-reference" data-url="{}"></script>
+    <script src="{SCALAR_BUNDLE}"></script>"#,
+            escape_attr(spec_url)
+        ),
+    )
+}
+
+fn rapidoc_html(spec_url: &str) -> String {
+    page(
+        "RapiDoc",
+        &format!(
+            r#"<rapi-doc spec-url="{}" render-style="read"></rapi-doc>
+    <script type="module" src="{RAPIDOC_BUNDLE}"></script>"#,
+            escape_attr(spec_url)
+        ),
+    )
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        r#"<!doctype html>
+<html>
+  <head>
+    <title>{title}</title>
+    <meta charset="utf-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+  </head>
+  <body>
+    {body}
+  </body>
+</html>
+"#
+    )
+}
+
+/// Escape a value for a double-quoted HTML attribute
+fn escape_attr(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec_url_is_escaped() {
+        let html = redoc_html(r#"/spec.json?a=1&b="x""#);
+        assert!(html.contains(r#"spec-url="/spec.json?a=1&amp;b=&quot;x&quot;""#));
+    }
+}